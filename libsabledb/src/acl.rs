@@ -0,0 +1,579 @@
+use crate::commands::{CommandMetadata, RedisCommandFlags};
+use crate::{BytesMutUtils, RedisCommand, SableError, ServerOptions, StringUtils};
+use bytes::BytesMut;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// The user every connection starts with
+pub const DEFAULT_USER: &str = "default";
+
+/// A `+@category` / `-@category` rule. `category` is `None` for `@all`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CommandRule {
+    allow: bool,
+    category: Option<RedisCommandFlags>,
+    /// The category name, as provided by the user
+    name: String,
+}
+
+impl CommandRule {
+    fn matches(&self, metadata: &CommandMetadata) -> bool {
+        match &self.category {
+            None => true,
+            Some(flag) => metadata.in_category(flag),
+        }
+    }
+}
+
+/// A user and its permissions, built from `ACL SETUSER` rules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AclUser {
+    name: String,
+    enabled: bool,
+    /// The user can authenticate with any password
+    nopass: bool,
+    /// SHA-256 digests of the user's passwords
+    passwords: Vec<Vec<u8>>,
+    /// The command rules, in the order they were set. The last rule that matches a command
+    /// decides whether the user may run it. No rule matching means the command is denied
+    command_rules: Vec<CommandRule>,
+    /// Glob-style patterns of the keys the user may access
+    key_patterns: Vec<BytesMut>,
+}
+
+impl AclUser {
+    /// A new user is disabled and has no password, no commands and no keys
+    fn new(name: &str) -> Self {
+        AclUser {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: Vec::default(),
+            command_rules: Vec::default(),
+            key_patterns: Vec::default(),
+        }
+    }
+
+    /// The `default` user: all the commands, all the keys. Without `requirepass`, any password
+    /// is accepted
+    fn default_user(requirepass: Option<Vec<u8>>) -> Self {
+        let mut user = AclUser::new(DEFAULT_USER);
+        user.enabled = true;
+        match requirepass {
+            Some(digest) => user.passwords.push(digest),
+            None => user.nopass = true,
+        }
+        user.command_rules.push(CommandRule {
+            allow: true,
+            category: None,
+            name: "all".to_string(),
+        });
+        user.key_patterns.push(BytesMut::from("*"));
+        user
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn nopass(&self) -> bool {
+        self.nopass
+    }
+
+    /// The hex encoded digests of the user's passwords
+    pub fn password_digests(&self) -> Vec<String> {
+        self.passwords
+            .iter()
+            .map(|digest| BytesMutUtils::to_hex(digest))
+            .collect()
+    }
+
+    /// The command rules, e.g. `+@all -@admin`
+    pub fn commands_description(&self) -> String {
+        if self.command_rules.is_empty() {
+            return "-@all".to_string();
+        }
+        self.command_rules
+            .iter()
+            .map(|rule| format!("{}@{}", if rule.allow { '+' } else { '-' }, rule.name))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The key patterns, e.g. `~user:* ~session:*`
+    pub fn keys_description(&self) -> String {
+        self.key_patterns
+            .iter()
+            .map(|pattern| format!("~{}", BytesMutUtils::to_string(pattern)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The user and its rules, as listed by `ACL LIST` and saved in the ACL file
+    pub fn description(&self) -> String {
+        let mut parts = vec![
+            format!("user {}", self.name),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+        if self.nopass {
+            parts.push("nopass".to_string());
+        }
+        parts.extend(
+            self.password_digests()
+                .into_iter()
+                .map(|digest| format!("#{}", digest)),
+        );
+        if !self.key_patterns.is_empty() {
+            parts.push(self.keys_description());
+        }
+        parts.push(self.commands_description());
+        parts.join(" ")
+    }
+
+    /// Does `password` authenticate this user?
+    fn check_password(&self, password: &[u8]) -> bool {
+        self.enabled
+            && (self.nopass
+                || self
+                    .passwords
+                    .contains(&ServerOptions::password_digest(password)))
+    }
+
+    /// May this user run `command`?
+    fn can_run(&self, metadata: &CommandMetadata) -> bool {
+        self.command_rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(metadata))
+            .map(|rule| rule.allow)
+            .unwrap_or(false)
+    }
+
+    /// May this user access `key`?
+    fn can_access(&self, key: &[u8]) -> bool {
+        self.key_patterns
+            .iter()
+            .any(|pattern| StringUtils::glob_match(pattern, key, false))
+    }
+
+    /// Apply a single `ACL SETUSER` rule
+    fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        let syntax_error = || format!("ERR Error in ACL SETUSER modifier '{}': Syntax error", rule);
+
+        match rule.to_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec![BytesMut::from("*")],
+            "resetkeys" => self.key_patterns.clear(),
+            "allcommands" => return self.apply_rule("+@all"),
+            "nocommands" => return self.apply_rule("-@all"),
+            "reset" => *self = AclUser::new(&self.name),
+            _ => {
+                let (prefix, value) = rule.split_at(rule.chars().next().map_or(0, char::len_utf8));
+                match prefix {
+                    ">" => {
+                        let digest = ServerOptions::password_digest(value.as_bytes());
+                        if !self.passwords.contains(&digest) {
+                            self.passwords.push(digest);
+                        }
+                        self.nopass = false;
+                    }
+                    "<" => {
+                        let digest = ServerOptions::password_digest(value.as_bytes());
+                        self.passwords.retain(|d| *d != digest);
+                    }
+                    "#" => {
+                        let digest = BytesMutUtils::from_hex(value.as_bytes())
+                            .filter(|digest| digest.len() == 32)
+                            .ok_or_else(syntax_error)?;
+                        if !self.passwords.iter().any(|d| *d == digest) {
+                            self.passwords.push(digest.to_vec());
+                        }
+                        self.nopass = false;
+                    }
+                    "~" => self.key_patterns.push(BytesMut::from(value)),
+                    "+" | "-" => {
+                        let allow = prefix == "+";
+                        let name = value
+                            .strip_prefix('@')
+                            .ok_or_else(syntax_error)?
+                            .to_lowercase();
+                        let category = match name.as_str() {
+                            "all" => None,
+                            other => Some(
+                                RedisCommandFlags::from_str(other)
+                                    .ok()
+                                    .filter(|flag| !matches!(flag, RedisCommandFlags::None))
+                                    .ok_or_else(|| {
+                                        format!(
+                                            "ERR Error in ACL SETUSER modifier '{}': Unknown command category",
+                                            rule
+                                        )
+                                    })?,
+                            ),
+                        };
+                        if category.is_none() {
+                            // `@all` overrides all the previous rules
+                            self.command_rules.clear();
+                        }
+                        self.command_rules.push(CommandRule {
+                            allow,
+                            category,
+                            name,
+                        });
+                    }
+                    _ => return Err(syntax_error()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of `Acl::check_permissions`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AclPermission {
+    Allowed,
+    /// The user may not run the command
+    CommandDenied,
+    /// The user may not access one of the command's keys
+    KeyDenied,
+}
+
+/// The server users and their permissions (`ACL`). Users are saved to `aclfile` whenever they
+/// change and loaded from it at startup
+pub struct Acl {
+    users: RwLock<BTreeMap<String, AclUser>>,
+    file: Option<PathBuf>,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        Acl::new(None, None)
+    }
+}
+
+impl Acl {
+    /// Create the ACL with the `default` user only. `requirepass` is the digest of the default
+    /// user's password
+    pub fn new(requirepass: Option<Vec<u8>>, file: Option<PathBuf>) -> Self {
+        let default_user = AclUser::default_user(requirepass);
+        Acl {
+            users: RwLock::new(BTreeMap::from([(DEFAULT_USER.to_string(), default_user)])),
+            file,
+        }
+    }
+
+    /// Load the users from the ACL file, if it exists. Users found in the file replace the ones
+    /// with the same name
+    pub fn load(&self) -> Result<(), SableError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if !file.exists() {
+            return Ok(());
+        }
+
+        let content = std::fs::read_to_string(file)?;
+        let mut loaded = Vec::<AclUser>::new();
+        for (line_num, line) in content.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let user = match (words.next(), words.next()) {
+                (None, _) => continue,
+                (Some("user"), Some(name)) => {
+                    let mut user = AclUser::new(name);
+                    for rule in words {
+                        user.apply_rule(rule).map_err(|e| {
+                            SableError::InvalidArgument(format!(
+                                "{}:{}: {}",
+                                file.display(),
+                                line_num + 1,
+                                e
+                            ))
+                        })?;
+                    }
+                    user
+                }
+                _ => {
+                    return Err(SableError::InvalidArgument(format!(
+                        "{}:{}: lines must start with `user <name>`",
+                        file.display(),
+                        line_num + 1
+                    )));
+                }
+            };
+            loaded.push(user);
+        }
+
+        let mut users = self.users.write().expect("poisoned mutex");
+        for user in loaded {
+            users.insert(user.name.clone(), user);
+        }
+        tracing::info!("Loaded {} ACL users from {}", users.len(), file.display());
+        Ok(())
+    }
+
+    /// Write all the users to the ACL file
+    fn save(&self, users: &BTreeMap<String, AclUser>) -> Result<(), SableError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut content = String::new();
+        for user in users.values() {
+            content.push_str(&user.description());
+            content.push('\n');
+        }
+        // write a temporary file first, so a crash never leaves a partial ACL file behind
+        let tmp_file = file.with_extension("tmp");
+        std::fs::write(&tmp_file, content)?;
+        std::fs::rename(&tmp_file, file)?;
+        Ok(())
+    }
+
+    /// Create or update `name` by applying `rules`, in order. Nothing is changed if one of the
+    /// rules is invalid. On error, return the error reply
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), String> {
+        let mut users = self.users.write().expect("poisoned mutex");
+        let mut user = users
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| AclUser::new(name));
+        for rule in rules {
+            user.apply_rule(rule)?;
+        }
+
+        let previous = users.insert(name.to_string(), user);
+        if let Err(e) = self.save(&users) {
+            tracing::error!("Failed to save the ACL file. {:?}", e);
+            match previous {
+                Some(previous) => users.insert(name.to_string(), previous),
+                None => users.remove(name),
+            };
+            return Err(
+                "ERR There was an error trying to save the ACLs. Please check the server logs for more information"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Return a copy of `name`
+    pub fn user(&self, name: &str) -> Option<AclUser> {
+        self.users
+            .read()
+            .expect("poisoned mutex")
+            .get(name)
+            .cloned()
+    }
+
+    /// All the users, sorted by name
+    pub fn users(&self) -> Vec<AclUser> {
+        self.users
+            .read()
+            .expect("poisoned mutex")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Can `name` authenticate with `password`?
+    pub fn authenticate(&self, name: &str, password: &[u8]) -> bool {
+        self.users
+            .read()
+            .expect("poisoned mutex")
+            .get(name)
+            .is_some_and(|user| user.check_password(password))
+    }
+
+    /// New connections are authenticated as the `default` user, unless it requires a password
+    pub fn default_user_requires_auth(&self) -> bool {
+        !self
+            .users
+            .read()
+            .expect("poisoned mutex")
+            .get(DEFAULT_USER)
+            .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    /// May `name` run `command`? The command's keys are found using its first key, last key and
+    /// step
+    pub fn check_permissions(&self, name: &str, command: &RedisCommand) -> AclPermission {
+        let users = self.users.read().expect("poisoned mutex");
+        let Some(user) = users.get(name) else {
+            return AclPermission::CommandDenied;
+        };
+        if !user.can_run(command.metadata()) {
+            return AclPermission::CommandDenied;
+        }
+
+        let all_keys = user
+            .key_patterns
+            .iter()
+            .any(|pattern| pattern.as_ref() == b"*");
+        if !all_keys {
+            for pos in command.metadata().key_positions(command.arg_count()) {
+                if let Some(key) = command.arg(pos) {
+                    if !user.can_access(key) {
+                        return AclPermission::KeyDenied;
+                    }
+                }
+            }
+        }
+        AclPermission::Allowed
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> Vec<String> {
+        rules.iter().map(|rule| rule.to_string()).collect()
+    }
+
+    #[test]
+    fn test_acl_rules() {
+        let acl = Acl::default();
+        assert!(!acl.default_user_requires_auth());
+        assert_eq!(
+            acl.user(DEFAULT_USER).unwrap().description(),
+            "user default on nopass ~* +@all"
+        );
+
+        acl.set_user(
+            "reader",
+            &rules(&["on", ">secret", "~cache:*", "+@read", "+@connection"]),
+        )
+        .unwrap();
+        let get = RedisCommand::for_test(vec!["get", "cache:1"]);
+        let get_other = RedisCommand::for_test(vec!["get", "other"]);
+        let set = RedisCommand::for_test(vec!["set", "cache:1", "value"]);
+        let mget = RedisCommand::for_test(vec!["mget", "cache:1", "other"]);
+        assert_eq!(
+            acl.check_permissions("reader", &get),
+            AclPermission::Allowed
+        );
+        assert_eq!(
+            acl.check_permissions("reader", &get_other),
+            AclPermission::KeyDenied
+        );
+        assert_eq!(
+            acl.check_permissions("reader", &set),
+            AclPermission::CommandDenied
+        );
+        assert_eq!(
+            acl.check_permissions("reader", &mget),
+            AclPermission::KeyDenied
+        );
+        assert_eq!(
+            acl.check_permissions("no_such_user", &get),
+            AclPermission::CommandDenied
+        );
+
+        assert!(acl.authenticate("reader", b"secret"));
+        assert!(!acl.authenticate("reader", b"wrong"));
+        assert!(!acl.authenticate("no_such_user", b"secret"));
+
+        // the last matching rule wins
+        acl.set_user("reader", &rules(&["+@all", "-@write", "allkeys"]))
+            .unwrap();
+        assert_eq!(
+            acl.check_permissions("reader", &get_other),
+            AclPermission::Allowed
+        );
+        assert_eq!(
+            acl.check_permissions("reader", &set),
+            AclPermission::CommandDenied
+        );
+
+        // invalid rules leave the user untouched
+        let before = acl.user("reader").unwrap();
+        assert_eq!(
+            acl.set_user("reader", &rules(&["off", "+@nosuchcategory"])),
+            Err(
+                "ERR Error in ACL SETUSER modifier '+@nosuchcategory': Unknown command category"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            acl.set_user("reader", &rules(&["+get"])),
+            Err("ERR Error in ACL SETUSER modifier '+get': Syntax error".to_string())
+        );
+        assert_eq!(acl.user("reader").unwrap(), before);
+
+        // disabled users can not authenticate
+        acl.set_user("reader", &rules(&["off"])).unwrap();
+        assert!(!acl.authenticate("reader", b"secret"));
+
+        // a new user has no permissions at all
+        acl.set_user("nobody", &[]).unwrap();
+        assert_eq!(
+            acl.user("nobody").unwrap().description(),
+            "user nobody off -@all"
+        );
+        assert_eq!(
+            acl.check_permissions("nobody", &get),
+            AclPermission::CommandDenied
+        );
+
+        let with_password = Acl::new(Some(ServerOptions::password_digest(b"pass")), None);
+        assert!(with_password.default_user_requires_auth());
+        assert!(with_password.authenticate(DEFAULT_USER, b"pass"));
+        assert!(!with_password.authenticate(DEFAULT_USER, b"wrong"));
+    }
+
+    #[test]
+    fn test_acl_file() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let file = PathBuf::from("tests/test_acl_file.acl");
+        let _ = std::fs::remove_file(&file);
+
+        let acl = Acl::new(None, Some(file.clone()));
+        acl.load()?;
+        acl.set_user(
+            "reader",
+            &rules(&["on", ">secret", "~cache:*", "+@read", "-@admin"]),
+        )
+        .unwrap();
+        acl.set_user(DEFAULT_USER, &rules(&["resetpass", ">admin"]))
+            .unwrap();
+
+        // passwords are not saved in clear
+        let content = std::fs::read_to_string(&file)?;
+        assert!(!content.contains("secret"));
+        assert_eq!(content.lines().count(), 2);
+
+        let loaded = Acl::new(None, Some(file.clone()));
+        loaded.load()?;
+        assert_eq!(loaded.users(), acl.users());
+        assert!(loaded.authenticate("reader", b"secret"));
+        assert!(loaded.authenticate(DEFAULT_USER, b"admin"));
+        assert!(loaded.default_user_requires_auth());
+
+        std::fs::write(&file, "user reader on\nnot a user line\n")?;
+        assert!(Acl::new(None, Some(file.clone())).load().is_err());
+        std::fs::remove_file(&file)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    storage::{GenericDb, StorageIterator},
+    CommonValueMetadata, LockManager, PrimaryKeyMetadata, SableError, ServerState, StorageAdapter,
+    Telemetry, U8ArrayReader,
+};
+use bytes::BytesMut;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The number of item records (hash fields, list items etc) deleted in a single batch when
+/// removing an expired composite key
+const SWEEP_BUDGET: usize = 1000;
+
+/// Removes expired keys in the background.
+///
+/// Every `active_expire_interval_ms` milliseconds, the task samples the next
+/// `active_expire_sample_size` primary keys (from all the databases) and deletes the ones that
+/// expired. The scan position is kept between rounds, so the entire key space is covered over
+/// time. A replica does not run the scan: it receives the deletions from its primary
+pub struct ActiveExpire {
+    store: StorageAdapter,
+    server_state: Arc<ServerState>,
+    /// The primary key the next scan starts from. `None` means: start from the beginning
+    cursor: Option<BytesMut>,
+}
+
+impl ActiveExpire {
+    pub fn new(store: StorageAdapter, server_state: Arc<ServerState>) -> Self {
+        ActiveExpire {
+            store,
+            server_state,
+            cursor: None,
+        }
+    }
+
+    /// Start the active expiration task on its own thread (and tokio runtime), so it never
+    /// competes with the workers
+    pub fn run(store: StorageAdapter, server_state: Arc<ServerState>) -> Result<(), SableError> {
+        let settings = server_state.options().expiration_settings.clone();
+        if settings.active_expire_interval_ms == 0 {
+            tracing::info!("Active expiration is disabled");
+            return Ok(());
+        }
+
+        let _ = std::thread::Builder::new()
+            .name("ActiveExpire".to_string())
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .thread_name("ActiveExpire")
+                    .build()
+                    .unwrap_or_else(|e| {
+                        panic!("failed to create tokio runtime. {:?}", e);
+                    });
+
+                rt.block_on(async move {
+                    let mut active_expire = ActiveExpire::new(store, server_state);
+                    active_expire
+                        .main_loop(settings.active_expire_interval_ms as u64)
+                        .await;
+                });
+            })?;
+        Ok(())
+    }
+
+    async fn main_loop(&mut self, interval_ms: u64) {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_millis(interval_ms)).await;
+            if self.store.is_replica_mode() {
+                continue;
+            }
+
+            // the sample size can be changed with `CONFIG SET`
+            let sample_size = self
+                .server_state
+                .options()
+                .expiration_settings
+                .active_expire_sample_size;
+
+            match self.scan(sample_size).await {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::debug!("actively expired {} key(s)", count);
+                    Telemetry::inc_active_expired_keys(count as u128);
+                    self.server_state
+                        .shared_telemetry()
+                        .lock()
+                        .expect("mutex")
+                        .merge_worker_telemetry(Telemetry::clone());
+                    Telemetry::clear();
+                }
+                Err(e) => {
+                    tracing::warn!("active expiration error. {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// Sample the next `sample_size` keys and delete the expired ones. Return the number of
+    /// keys deleted
+    pub async fn scan(&mut self, sample_size: usize) -> Result<usize, SableError> {
+        let candidates = self.collect_expired(sample_size)?;
+        let mut deleted = 0usize;
+        for (db_id, user_key) in candidates {
+            if self.delete_if_expired(db_id, &user_key).await? {
+                self.server_state
+                    .notify_keyspace_event(db_id, "expired", &user_key);
+                deleted = deleted.saturating_add(1);
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Visit up to `sample_size` primary keys, starting from the cursor, and return the ones
+    /// that expired. The iterator is created directly (instead of using
+    /// `StorageAdapter::iterate`) so the scan can resume from where the previous one stopped
+    fn collect_expired(&mut self, sample_size: usize) -> Result<Vec<(u16, BytesMut)>, SableError> {
+        let seek_key = match self.cursor.take() {
+            Some(cursor) => cursor,
+            None => BytesMut::from(&[PrimaryKeyMetadata::KEY_PRIMARY][..]),
+        };
+
+        let mut expired = Vec::<(u16, BytesMut)>::new();
+        match self.store.create_iterator(Rc::new(seek_key))? {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                let mut visited = 0usize;
+                while let (Some(key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value()) {
+                    if key.first() != Some(&PrimaryKeyMetadata::KEY_PRIMARY) {
+                        // end of the primary keys: the next scan wraps around
+                        return Ok(expired);
+                    }
+
+                    if visited == sample_size {
+                        self.cursor = Some(BytesMut::from(key));
+                        return Ok(expired);
+                    }
+                    visited = visited.saturating_add(1);
+
+                    let mut reader = U8ArrayReader::with_buffer(value);
+                    let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+                    if common_md.expiration().is_expired()? {
+                        let (key_md, user_key) =
+                            PrimaryKeyMetadata::from_raw(&BytesMut::from(key))?;
+                        expired.push((key_md.db_id(), user_key));
+                    }
+                    rocksdb_iter.next();
+                }
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Delete `user_key` if it is still expired. The check is repeated under the key's lock,
+    /// since the key might have been updated after it was sampled
+    async fn delete_if_expired(&self, db_id: u16, user_key: &BytesMut) -> Result<bool, SableError> {
+        let generic_db = GenericDb::with_storage(&self.store, db_id);
+        let items_prefix = {
+            let _unused = LockManager::lock_user_key_exclusive(user_key, db_id);
+            let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, db_id);
+            let Some(value) = self.store.get(&internal_key)? else {
+                return Ok(false);
+            };
+
+            let mut reader = U8ArrayReader::with_buffer(&value);
+            let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+            if !common_md.expiration().is_expired()? {
+                return Ok(false);
+            }
+
+            let items_prefix = generic_db.items_prefix(user_key)?;
+            generic_db.delete(user_key)?;
+            items_prefix
+        };
+
+        // the primary record is gone, the items it owned are unreachable and can be
+        // removed without holding the lock
+        if let Some(items_prefix) = items_prefix {
+            while generic_db.delete_items(&items_prefix, SWEEP_BUDGET)? == SWEEP_BUDGET {
+                tokio::task::yield_now().await;
+            }
+        }
+        Ok(true)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, RedisCommand};
+
+    #[test]
+    fn test_active_expire() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let client = Client::new(server_state.clone(), store.clone(), None);
+
+            let commands = vec![
+                vec!["set", "persistent", "value"],
+                vec!["set", "k1", "value", "px", "1"],
+                vec!["set", "k2", "value", "px", "1"],
+                vec!["set", "k3", "value", "px", "1"],
+                vec!["hset", "hash", "f1", "v1", "f2", "v2"],
+                vec!["pexpire", "hash", "1"],
+            ];
+            for args in commands {
+                let mut sink = crate::tests::ResponseSink::with_name("test_active_expire").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+            }
+
+            let generic_db = GenericDb::with_storage(&store, 0);
+            let hash_items_prefix = generic_db
+                .items_prefix(&BytesMut::from("hash"))
+                .unwrap()
+                .unwrap();
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+
+            // scan in small samples: the cursor must move forward between rounds
+            let mut active_expire = ActiveExpire::new(store.clone(), server_state.clone());
+            let mut deleted = 0usize;
+            for _ in 0..3 {
+                deleted += active_expire.scan(2).await.unwrap();
+            }
+            assert_eq!(deleted, 4);
+            assert_eq!(active_expire.scan(10).await.unwrap(), 0);
+
+            for user_key in ["k1", "k2", "k3", "hash"] {
+                assert!(!generic_db.contains(&BytesMut::from(user_key)).unwrap());
+            }
+            assert!(generic_db.contains(&BytesMut::from("persistent")).unwrap());
+            assert_eq!(generic_db.delete_items(&hash_items_prefix, 10).unwrap(), 0);
+        });
+        Ok(())
+    }
+}
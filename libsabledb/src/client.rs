@@ -1,13 +1,18 @@
 use crate::{
+    acl::DEFAULT_USER,
     commands::{ClientNextAction, ErrorStrings, HandleCommandResult},
-    ClientCommands, GenericCommands, HashCommands, ListCommands, ParserError, RedisCommand,
-    RedisCommandName, RequestParser, RespBuilderV2, SableError, ServerCommands, ServerState,
-    StorageAdapter, StringCommands, Telemetry,
+    metadata::StreamId,
+    AclPermission, BitmapCommands, ClientCommands, GenericCommands, HashCommands, ListCommands,
+    LockManager, MemoryCommands, MemoryTracker, ObjectCommands, ParserError, PubSubCommands,
+    RedisCommand, RedisCommandName, RequestParser, RespBuilderV2, RespProtocolVersion, SableError,
+    ScanCommands, ScriptCommands, ServerCommands, ServerState, SetCommands, SortCommands,
+    StorageAdapter, StreamCommands, StringCommands, Telemetry, TimeUtils, TransactionCommands,
+    ZSetCommands,
 };
 
 use bytes::BytesMut;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::{
@@ -16,16 +21,35 @@ use std::sync::{
 };
 
 const PONG: &[u8] = b"+PONG\r\n";
+/// The number of published messages that can be pending for a subscriber. A subscriber that
+/// falls further behind is disconnected
+pub(crate) const PUBSUB_CHANNEL_CAPACITY: usize = 1024;
+/// How often a write command held by a failover checks whether the failover completed
+const FAILOVER_POLL_INTERVAL_MS: u64 = 10;
+/// How often a command held by `CLIENT PAUSE` checks whether the pause was lifted early
+const CLIENT_PAUSE_POLL_INTERVAL_MS: u64 = 10;
+/// The size of the buffer that coalesces the replies of pipelined commands into a single write
+const REPLY_BUFFER_SIZE: usize = 16 << 10;
 
 #[allow(unused_imports)]
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
     sync::mpsc::Receiver as TokioReceiver,
+    sync::mpsc::Sender as TokioSender,
+    sync::Notify,
     time::{sleep, Duration},
 };
 
 use tracing::log::{log_enabled, Level};
 
+/// What the reader task of a connection hands over to its writer task
+enum ClientRequest {
+    /// A parsed command to execute
+    Command(Rc<RedisCommand>),
+    /// The input could not be parsed. The error reply is sent and the connection is closed
+    ProtocolError(BytesMut),
+}
+
 lazy_static::lazy_static! {
     static ref CLIENT_ID_GENERATOR: Mutex<u128> = Mutex::new(0);
 }
@@ -51,12 +75,67 @@ pub struct ClientState {
     db_id: AtomicU16,
     attributes: RwLock<HashMap<String, String>>,
     is_active: AtomicBool,
+    /// The RESP protocol version negotiated with `HELLO`
+    resp_version: Cell<RespProtocolVersion>,
+    /// Set once the client provided its user's password (`AUTH` or `HELLO AUTH`)
+    authenticated: Cell<bool>,
+    /// The ACL user whose permissions apply to this client
+    user: RefCell<String>,
+    /// Set while the client is inside a `MULTI` block
+    transaction: RefCell<Option<TransactionState>>,
+    /// The keys watched by this client (`WATCH`) and their version at the time they were watched
+    watched_keys: RefCell<Vec<WatchedKeyVersion>>,
+    /// The pub-sub channels this client is subscribed to
+    subscriptions: RefCell<BTreeSet<BytesMut>>,
+    /// The pub-sub patterns this client is subscribed to
+    pattern_subscriptions: RefCell<BTreeSet<BytesMut>>,
+    /// Published messages are pushed to the client over this channel
+    pubsub_tx: TokioSender<BytesMut>,
+    /// The receiving end of `pubsub_tx`, owned by the client's writer task
+    pubsub_rx: RefCell<Option<TokioReceiver<BytesMut>>>,
+    /// Notified by `PUBLISH` when `pubsub_tx` is full: the connection is closed
+    pubsub_overflow: Arc<Notify>,
+    /// The blocked `XREAD` command and the stream IDs it waits after (`$` is resolved when the
+    /// command first runs), so the retry that follows a wakeup does not skip any entry
+    blocked_xread: RefCell<Option<(Rc<RedisCommand>, Vec<StreamId>)>>,
+}
+
+/// What `CLIENT LIST` reports about a connected client. Kept in `ServerState` so clients
+/// served by other workers are listed too
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    /// The peer address (`ip:port`). Empty for clients that are not attached to a socket
+    pub addr: String,
+    /// The name set by `CLIENT SETNAME` (or `HELLO SETNAME`)
+    pub name: String,
+    /// When the client connected, in milliseconds since the Unix epoch
+    pub connected_at_ms: u64,
+    /// The last command executed by the client
+    pub last_command: String,
+    /// The client's database ID
+    pub db: u16,
+}
+
+/// A key watched by the client: `(database ID, user key, version)`
+type WatchedKeyVersion = (u16, BytesMut, u64);
+
+/// The commands queued between `MULTI` and `EXEC`
+#[derive(Default)]
+pub struct TransactionState {
+    pub commands: Vec<Rc<RedisCommand>>,
+    /// A command failed to queue (unknown command, wrong arity...). `EXEC` must discard the
+    /// transaction
+    pub aborted: bool,
 }
 
 #[derive(PartialEq, PartialOrd)]
 enum CanHandleCommandResult {
     Ok,
     WriteInReadOnlyReplica,
+    // The server requires a password and the client did not authenticate yet
+    NoAuth,
+    // The client's user is not allowed to run the command. Holds the error message
+    NoPerm(String),
     // Client was killed
     ClientKilled,
 }
@@ -89,6 +168,8 @@ impl ClientState {
     /// Set the active database ID for this client
     pub fn set_database_id(&self, id: u16) {
         self.db_id.store(id, std::sync::atomic::Ordering::Relaxed);
+        self.server_state
+            .update_client(self.client_id, |info| info.db = id);
     }
 
     /// Return the client's database ID
@@ -120,6 +201,234 @@ impl ClientState {
             .cloned()
     }
 
+    /// The client name (`CLIENT SETNAME`). Empty when not set
+    pub fn name(&self) -> String {
+        self.attribute(&"name".to_string()).unwrap_or_default()
+    }
+
+    /// Set the client name (`CLIENT SETNAME`)
+    pub fn set_name(&self, name: &str) {
+        self.set_attribute("name", name);
+        self.server_state
+            .update_client(self.client_id, |info| info.name = name.to_string());
+    }
+
+    /// Return the RESP protocol version used by this client
+    pub fn resp_version(&self) -> RespProtocolVersion {
+        self.resp_version.get()
+    }
+
+    /// Switch the client to a different RESP protocol version (`HELLO`)
+    pub fn set_resp_version(&self, version: RespProtocolVersion) {
+        self.resp_version.set(version);
+    }
+
+    /// Return a response builder that speaks the client's protocol version
+    pub fn resp_builder(&self) -> RespBuilderV2 {
+        RespBuilderV2::with_protocol(self.resp_version())
+    }
+
+    /// Can this client run commands? Clients must authenticate only when the `default` user
+    /// requires a password (e.g. `requirepass` is set)
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated.get() || !self.server_state.acl().default_user_requires_auth()
+    }
+
+    /// Mark the client as authenticated (`AUTH`)
+    pub fn set_authenticated(&self, authenticated: bool) {
+        self.authenticated.set(authenticated);
+    }
+
+    /// The ACL user of this client
+    pub fn user(&self) -> String {
+        self.user.borrow().clone()
+    }
+
+    /// Switch the client to the ACL user `name` (`AUTH`)
+    pub fn set_user(&self, name: &str) {
+        *self.user.borrow_mut() = name.to_string();
+    }
+
+    /// Is this client inside a `MULTI` block?
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.borrow().is_some()
+    }
+
+    /// Start a transaction (`MULTI`)
+    pub fn begin_transaction(&self) {
+        *self.transaction.borrow_mut() = Some(TransactionState::default());
+    }
+
+    /// Queue a command for the next `EXEC`
+    pub fn queue_command(&self, command: Rc<RedisCommand>) {
+        if let Some(txn) = self.transaction.borrow_mut().as_mut() {
+            txn.commands.push(command);
+        }
+    }
+
+    /// Mark the current transaction so `EXEC` discards it
+    pub fn abort_transaction(&self) {
+        if let Some(txn) = self.transaction.borrow_mut().as_mut() {
+            txn.aborted = true;
+        }
+    }
+
+    /// End the transaction (`EXEC` or `DISCARD`) and return its state
+    pub fn take_transaction(&self) -> Option<TransactionState> {
+        self.transaction.borrow_mut().take()
+    }
+
+    /// Watch `user_key` in the client's active database. Watching the same key more than once
+    /// is a no-op
+    pub fn watch_key(&self, user_key: &BytesMut) {
+        let db_id = self.database_id();
+        let mut watched_keys = self.watched_keys.borrow_mut();
+        if watched_keys
+            .iter()
+            .any(|(id, key, _)| *id == db_id && key == user_key)
+        {
+            return;
+        }
+        let version = self.server_state.watched_keys().watch(db_id, user_key);
+        watched_keys.push((db_id, user_key.clone(), version));
+    }
+
+    /// Return `true` if any of the watched keys was modified since it was watched
+    pub fn watched_keys_modified(&self) -> bool {
+        let table = self.server_state.watched_keys();
+        self.watched_keys
+            .borrow()
+            .iter()
+            .any(|(db_id, user_key, version)| table.version(*db_id, user_key) != Some(*version))
+    }
+
+    /// Forget about all the watched keys (`UNWATCH`, `EXEC`, `DISCARD`)
+    pub fn unwatch_all(&self) {
+        let table = self.server_state.watched_keys();
+        for (db_id, user_key, _) in self.watched_keys.borrow_mut().drain(..) {
+            table.unwatch(db_id, &user_key);
+        }
+    }
+
+    /// Subscribe to `channel`. Return the number of channels and patterns this client is
+    /// subscribed to
+    pub fn subscribe(&self, channel: &BytesMut) -> usize {
+        if self.subscriptions.borrow_mut().insert(channel.clone()) {
+            self.server_state.subscribe(
+                channel,
+                self.client_id,
+                self.pubsub_tx.clone(),
+                self.pubsub_overflow.clone(),
+            );
+        }
+        self.subscriptions_count()
+    }
+
+    /// Unsubscribe from `channel`. Return the number of channels and patterns this client is
+    /// still subscribed to
+    pub fn unsubscribe(&self, channel: &BytesMut) -> usize {
+        if self.subscriptions.borrow_mut().remove(channel) {
+            self.server_state.unsubscribe(channel, self.client_id);
+        }
+        self.subscriptions_count()
+    }
+
+    /// Subscribe to all the channels matching the glob-style `pattern`. Return the number of
+    /// channels and patterns this client is subscribed to
+    pub fn psubscribe(&self, pattern: &BytesMut) -> usize {
+        if self
+            .pattern_subscriptions
+            .borrow_mut()
+            .insert(pattern.clone())
+        {
+            self.server_state.psubscribe(
+                pattern,
+                self.client_id,
+                self.pubsub_tx.clone(),
+                self.pubsub_overflow.clone(),
+            );
+        }
+        self.subscriptions_count()
+    }
+
+    /// Unsubscribe from `pattern`. Return the number of channels and patterns this client is
+    /// still subscribed to
+    pub fn punsubscribe(&self, pattern: &BytesMut) -> usize {
+        if self.pattern_subscriptions.borrow_mut().remove(pattern) {
+            self.server_state.punsubscribe(pattern, self.client_id);
+        }
+        self.subscriptions_count()
+    }
+
+    /// Return the channels this client is subscribed to
+    pub fn subscriptions(&self) -> Vec<BytesMut> {
+        self.subscriptions.borrow().iter().cloned().collect()
+    }
+
+    /// Return the patterns this client is subscribed to
+    pub fn pattern_subscriptions(&self) -> Vec<BytesMut> {
+        self.pattern_subscriptions
+            .borrow()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Return the number of channels and patterns this client is subscribed to
+    pub fn subscriptions_count(&self) -> usize {
+        self.subscriptions
+            .borrow()
+            .len()
+            .saturating_add(self.pattern_subscriptions.borrow().len())
+    }
+
+    /// Is this client in the "subscribed" state?
+    pub fn is_subscribed(&self) -> bool {
+        self.subscriptions_count() > 0
+    }
+
+    /// Unsubscribe from all the channels and patterns
+    pub fn unsubscribe_all(&self) {
+        for channel in self.subscriptions() {
+            self.unsubscribe(&channel);
+        }
+        for pattern in self.pattern_subscriptions() {
+            self.punsubscribe(&pattern);
+        }
+    }
+
+    /// Return the client to the state of a new connection (`RESET`): the transaction is
+    /// discarded, the watched keys and the subscriptions are dropped, the database is 0, the name
+    /// is cleared and the protocol is back to RESP2. The client is switched back to the `default`
+    /// user and has to authenticate again if that user requires a password
+    pub fn reset(&self) {
+        let _ = self.take_transaction();
+        self.unwatch_all();
+        self.unsubscribe_all();
+        self.set_database_id(0);
+        self.set_name("");
+        self.set_resp_version(RespProtocolVersion::default());
+        self.set_authenticated(false);
+        self.set_user(DEFAULT_USER);
+    }
+
+    /// Take the receiving end of the pub-sub channel. Only the first call returns a receiver
+    pub fn take_pubsub_receiver(&self) -> Option<TokioReceiver<BytesMut>> {
+        self.pubsub_rx.borrow_mut().take()
+    }
+
+    /// Remember the stream IDs the blocked `XREAD` `command` waits after
+    pub fn set_blocked_xread(&self, command: Rc<RedisCommand>, last_ids: Vec<StreamId>) {
+        *self.blocked_xread.borrow_mut() = Some((command, last_ids));
+    }
+
+    /// Take the stream IDs remembered by `set_blocked_xread`. `None` if they were remembered for
+    /// another command (e.g. the client timed out and this is a new `XREAD`)
+    pub fn take_blocked_xread(&self, command: &Rc<RedisCommand>) -> Option<Vec<StreamId>> {
+        let (blocked_command, last_ids) = self.blocked_xread.borrow_mut().take()?;
+        Rc::ptr_eq(&blocked_command, command).then_some(last_ids)
+    }
+
     pub fn error(&self, msg: &str) {
         tracing::error!("CLNT {}: {}", self.client_id, msg);
     }
@@ -171,6 +480,7 @@ impl Client {
         tls_acceptor: Option<Rc<tokio_rustls::TlsAcceptor>>,
     ) -> Self {
         Telemetry::inc_connections_opened();
+        let (pubsub_tx, pubsub_rx) = tokio::sync::mpsc::channel(PUBSUB_CHANNEL_CAPACITY);
         let state = Rc::new(ClientState {
             server_state,
             store,
@@ -179,8 +489,27 @@ impl Client {
             db_id: AtomicU16::new(0),
             attributes: RwLock::new(HashMap::<String, String>::new()),
             is_active: AtomicBool::new(true),
+            resp_version: Cell::new(RespProtocolVersion::default()),
+            authenticated: Cell::new(false),
+            user: RefCell::new(DEFAULT_USER.to_string()),
+            transaction: RefCell::new(None),
+            watched_keys: RefCell::new(Vec::new()),
+            subscriptions: RefCell::new(BTreeSet::new()),
+            pattern_subscriptions: RefCell::new(BTreeSet::new()),
+            pubsub_tx,
+            pubsub_rx: RefCell::new(Some(pubsub_rx)),
+            pubsub_overflow: Arc::new(Notify::new()),
+            blocked_xread: RefCell::new(None),
         });
 
+        state.server_state.register_client(
+            state.client_id,
+            ClientInfo {
+                connected_at_ms: TimeUtils::epoch_ms().unwrap_or_default(),
+                ..Default::default()
+            },
+        );
+
         let state_clone = state.clone();
 
         // register this client
@@ -199,6 +528,11 @@ impl Client {
 
     /// The client's main loop
     async fn main_loop(&mut self, stream: std::net::TcpStream) -> Result<(), SableError> {
+        if let Ok(addr) = stream.peer_addr() {
+            self.state
+                .server_state
+                .update_client(self.state.client_id, |info| info.addr = addr.to_string());
+        }
         let tokio_stream = tokio::net::TcpStream::from_std(stream)?;
         let (channel_tx, channel_rx) = tokio::sync::mpsc::channel(100);
 
@@ -234,9 +568,15 @@ impl Client {
             });
             (r, w)
         };
+        let r_abort = r.abort_handle();
+        let w_abort = w.abort_handle();
+        let pubsub_overflow = self.state.pubsub_overflow.clone();
 
-        // If any of the tasks (reader - writer) ends,
-        // abort the connection
+        // If any of the tasks (reader - writer) ends, abort the connection. When the writer ends
+        // (e.g. `QUIT`), the reader is still waiting for input: stop it, so the connection is
+        // closed now. When the reader ends, the writer still completes the pending commands.
+        // A subscriber that does not keep up with the published messages is dropped at once:
+        // its writer is most likely stuck on a full socket
         tokio::select! {
             _ = r => {
                 Err(SableError::StdIoError(std::io::Error::new(
@@ -245,23 +585,41 @@ impl Client {
                 )))
             },
             _ = w => {
+                r_abort.abort();
                 Err(SableError::StdIoError(std::io::Error::new(
                     std::io::ErrorKind::ConnectionAborted,
                     "writer task ended prematurely. closing connection",
                 )))
             }
+            _ = pubsub_overflow.notified() => {
+                r_abort.abort();
+                w_abort.abort();
+                Err(SableError::StdIoError(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionAborted,
+                    "pub-sub messages overflow. closing connection",
+                )))
+            }
         }
     }
 
     /// Read data from the network, parse it and send it "writer" task for processing
     async fn reader_loop(
         mut rx: impl AsyncReadExt + std::marker::Unpin,
-        channel_tx: tokio::sync::mpsc::Sender<Rc<RedisCommand>>,
+        channel_tx: tokio::sync::mpsc::Sender<ClientRequest>,
         client_state: Rc<ClientState>,
     ) -> Result<(), SableError> {
         let mut buffer = BytesMut::new();
+        let server_state = client_state.server_inner_state();
+        let mut limits_generation = server_state.options_generation();
+        let mut limits = Self::protocol_limits(&server_state);
         loop {
-            let mut request_parser = RequestParser::default();
+            // the limits are read again only after `CONFIG SET` changed the options
+            let generation = server_state.options_generation();
+            if generation != limits_generation {
+                limits_generation = generation;
+                limits = Self::protocol_limits(&server_state);
+            }
+            let mut request_parser = RequestParser::with_limits(limits.0, limits.1);
             match request_parser.parse(&buffer) {
                 Err(SableError::Parser(ParserError::NeedMoreData)) => {
                     if log_enabled!(Level::Trace) {
@@ -294,6 +652,21 @@ impl Client {
                     continue;
                 }
 
+                Err(SableError::Parser(ParserError::ProtocolError(msg))) => {
+                    client_state.warn(&format!("Protocol error: {}. Closing connection", msg));
+                    let mut response_buffer = BytesMut::with_capacity(64);
+                    RespBuilderV2::default().error_string(
+                        &mut response_buffer,
+                        &format!("ERR Protocol error: {}", msg),
+                    );
+                    channel_tx
+                        .send(ClientRequest::ProtocolError(response_buffer))
+                        .await
+                        .map_err(|_| SableError::ConnectionClosed)?;
+                    // the writer closes the connection once the reply is sent
+                    channel_tx.closed().await;
+                    return Ok(());
+                }
                 Err(e) => {
                     client_state.warn(&format!("Error while parsing input message. {:?}", e));
                     client_state.warn("Closing connection");
@@ -310,26 +683,74 @@ impl Client {
                         // make sure we have enough room for 1K of message
                         buffer.reserve(1024 - buffer.len());
                     }
-                    channel_tx.send(result.command).await?;
+                    channel_tx
+                        .send(ClientRequest::Command(result.command))
+                        .await
+                        .map_err(|_| SableError::ConnectionClosed)?;
                 }
             }
         }
     }
 
-    /// Accepts the parsed requests, execute the command and send back the response
+    /// The limits enforced by the request parser: `(proto_max_bulk_len,
+    /// proto_max_multibulk_len)`
+    fn protocol_limits(server_state: &ServerState) -> (usize, usize) {
+        let options = server_state.options();
+        (
+            options.client_limits.proto_max_bulk_len,
+            options.client_limits.proto_max_multibulk_len,
+        )
+    }
+
+    /// Accepts the parsed requests, execute the command and send back the response.
+    ///
+    /// The replies are buffered and written to the socket once there are no more parsed commands
+    /// waiting, so the replies of a pipeline are sent with a single write. The buffer is also
+    /// flushed before the client is suspended (blocking commands, `CLIENT PAUSE`...)
     async fn writer_loop(
-        mut tx: impl AsyncWriteExt + std::marker::Unpin,
-        mut channel_rx: TokioReceiver<Rc<RedisCommand>>,
+        tx: impl AsyncWriteExt + std::marker::Unpin,
+        mut channel_rx: TokioReceiver<ClientRequest>,
         client_state: Rc<ClientState>,
     ) -> Result<(), SableError> {
-        while let Some(command) = channel_rx.recv().await {
+        let mut tx = BufWriter::with_capacity(REPLY_BUFFER_SIZE, tx);
+        let mut pubsub_rx = client_state.take_pubsub_receiver();
+        let group_commit = client_state.database().open_params().group_commit_enabled();
+        loop {
+            if channel_rx.is_empty() {
+                tx.flush().await?;
+            }
+
+            // wait for the next command. While waiting, stream any published message to a
+            // subscribed client
+            let command = tokio::select! {
+                request = channel_rx.recv() => {
+                    match request {
+                        Some(ClientRequest::Command(command)) => command,
+                        Some(ClientRequest::ProtocolError(response)) => {
+                            Self::send_response(&mut tx, &response, client_state.client_id).await?;
+                            tx.flush().await?;
+                            return Err(SableError::ConnectionClosed);
+                        }
+                        None => break,
+                    }
+                }
+                Some(message) = Self::next_pubsub_message(&mut pubsub_rx) => {
+                    Self::send_response(&mut tx, &message, client_state.client_id).await?;
+                    continue;
+                }
+            };
+
             // update telemetry and process the command
             Telemetry::inc_total_commands_processed();
 
             // Use a loop here to handle timeouts & retries
             loop {
-                let response =
-                    Self::handle_command(client_state.clone(), command.clone(), &mut tx).await;
+                let response = if group_commit && Self::acknowledges_writes(&command) {
+                    Self::handle_committed_command(client_state.clone(), command.clone(), &mut tx)
+                        .await
+                } else {
+                    Self::handle_command(client_state.clone(), command.clone(), &mut tx).await
+                };
                 match response {
                     Ok(next_action) => match next_action {
                         ClientNextAction::NoAction => {
@@ -342,6 +763,7 @@ impl Client {
                         }
                         ClientNextAction::Wait((rx, duration)) => {
                             // suspend the client for the specified duration or until a wakeup bit arrives
+                            tx.flush().await?;
                             match Self::wait_for(rx, duration).await {
                                 WaitResult::Timeout => {
                                     if log_enabled!(Level::Debug) {
@@ -367,6 +789,8 @@ impl Client {
                         }
                         ClientNextAction::TerminateConnection(response) => {
                             Self::send_response(&mut tx, &response, client_state.client_id).await?;
+                            // flush the reply and let the peer know that we are done
+                            let _ = tx.shutdown().await;
                             return Err(SableError::ConnectionClosed);
                         }
                     },
@@ -375,6 +799,8 @@ impl Client {
                             "failed to process command: {:?} error: {:?}",
                             command, e
                         ));
+                        // the replies of the commands that completed are still delivered
+                        let _ = tx.flush().await;
                         return Err(e);
                     }
                 }
@@ -383,27 +809,78 @@ impl Client {
         Ok(())
     }
 
-    /// Suspend the client until a message arrives or a time-out occurs
+    /// Does the reply of `command` acknowledge writes? (`EXEC` runs the queued writes)
+    fn acknowledges_writes(command: &RedisCommand) -> bool {
+        Self::is_write_command(command)
+            || matches!(command.metadata().name(), RedisCommandName::Exec)
+    }
+
+    /// Run a command that writes while the group commit is enabled: its reply is held until its
+    /// writes are durable. The reply is captured, the commit is awaited (the worker serves the
+    /// other clients meanwhile) and only then is the reply added to the replies of the client
+    async fn handle_committed_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<ClientNextAction, SableError> {
+        // the replies of the previous commands are sent now, in case this command is held
+        // (`CLIENT PAUSE`, failover)
+        tx.flush().await?;
+        let mut reply = Vec::<u8>::new();
+        let next_action = Self::handle_command(client_state.clone(), command, &mut reply).await;
+        client_state.database().commit().await?;
+        tx.write_all(&reply).await?;
+        next_action
+    }
+
+    /// Wait for the next published message. Never completes if there is no receiver
+    async fn next_pubsub_message(
+        pubsub_rx: &mut Option<TokioReceiver<BytesMut>>,
+    ) -> Option<BytesMut> {
+        match pubsub_rx {
+            Some(rx) => rx.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Suspend the client until a message arrives or a time-out occurs. A zero `duration`
+    /// blocks forever
     pub async fn wait_for(mut cont: TokioReceiver<u8>, duration: Duration) -> WaitResult {
-        tokio::select! {
-            _ = cont.recv() => {
-                WaitResult::TryAgain
-            }
-            _ = sleep(duration) => {
-                WaitResult::Timeout
+        let result = if duration.is_zero() {
+            cont.recv().await;
+            WaitResult::TryAgain
+        } else {
+            match tokio::time::timeout(duration, cont.recv()).await {
+                Ok(_) => WaitResult::TryAgain,
+                Err(_) => WaitResult::Timeout,
             }
+        };
+
+        // Stop accepting wakeups: from now on, the server skips this client in all the queues
+        // it is blocked on and wakes the next one. A wakeup that raced with the timeout is not
+        // lost, the client tries again
+        cont.close();
+        match result {
+            WaitResult::Timeout if cont.try_recv().is_ok() => WaitResult::TryAgain,
+            result => result,
         }
     }
 
     /// Handle time-out for command
-    fn handle_timeout(
+    pub(crate) fn handle_timeout(
         _client_state: Rc<ClientState>,
-        _command: Rc<RedisCommand>,
+        command: Rc<RedisCommand>,
     ) -> Result<BytesMut, SableError> {
         let builder = RespBuilderV2::default();
         let mut response_buffer = BytesMut::new();
 
-        builder.null_string(&mut response_buffer);
+        match command.metadata().name() {
+            RedisCommandName::Blpop
+            | RedisCommandName::Brpop
+            | RedisCommandName::Blmpop
+            | RedisCommandName::Xread => builder.null_array(&mut response_buffer),
+            _ => builder.null_string(&mut response_buffer),
+        }
         Ok(response_buffer)
     }
 
@@ -416,29 +893,229 @@ impl Client {
     ) -> CanHandleCommandResult {
         if !client_state.active() {
             CanHandleCommandResult::ClientKilled
-        } else if client_state.server_state.is_replica() && command.metadata().is_write_command() {
+        } else if matches!(
+            command.metadata().name(),
+            RedisCommandName::Auth
+                | RedisCommandName::Hello
+                | RedisCommandName::Quit
+                | RedisCommandName::Reset
+        ) {
+            // authenticating, resetting and disconnecting are always allowed
+            CanHandleCommandResult::Ok
+        } else if !client_state.is_authenticated() {
+            if matches!(command.metadata().name(), RedisCommandName::Ping) {
+                CanHandleCommandResult::Ok
+            } else {
+                CanHandleCommandResult::NoAuth
+            }
+        } else if let Some(denied) = Self::check_permissions(&client_state, &command) {
+            denied
+        } else if client_state.server_state.is_replica() && Self::is_write_command(&command) {
             CanHandleCommandResult::WriteInReadOnlyReplica
         } else {
             CanHandleCommandResult::Ok
         }
     }
 
-    /// Accepts the parsed requests, execute the command and send back the response
+    /// Does `command` write? Usually this is known from the command table, but some commands
+    /// only write depending on their arguments (`SORT ... STORE`)
+    fn is_write_command(command: &RedisCommand) -> bool {
+        command.metadata().is_write_command() || SortCommands::is_write(command)
+    }
+
+    /// Check the command against the permissions of the client's ACL user
+    fn check_permissions(
+        client_state: &ClientState,
+        command: &RedisCommand,
+    ) -> Option<CanHandleCommandResult> {
+        let user = client_state.user();
+        match client_state
+            .server_state
+            .acl()
+            .check_permissions(&user, command)
+        {
+            AclPermission::Allowed => None,
+            AclPermission::CommandDenied => Some(CanHandleCommandResult::NoPerm(format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                user,
+                command.main_command()
+            ))),
+            AclPermission::KeyDenied => Some(CanHandleCommandResult::NoPerm(
+                "NOPERM No permissions to access a key".to_string(),
+            )),
+        }
+    }
+
+    /// Accepts the parsed requests, execute the command and send back the response. Commands
+    /// that take longer than `slowlog_log_slower_than` are added to the slow log
     pub async fn handle_command(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
         tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<ClientNextAction, SableError> {
+        client_state
+            .server_state
+            .update_client(client_state.client_id, |info| {
+                if info.last_command != *command.main_command() {
+                    info.last_command.clone_from(command.main_command());
+                }
+            });
+
+        // commands queued by `MULTI` are timed when `EXEC` runs them
+        let queued = Self::is_queued(&client_state, command.metadata().name());
+        let started_at = std::time::Instant::now();
+        let result = Self::dispatch_command(client_state.clone(), command.clone(), tx).await;
+        if !queued {
+            Self::update_slowlog(&client_state, &command, started_at.elapsed())?;
+        }
+        result
+    }
+
+    /// Run `command` and return its reply instead of sending it to the client (`EXEC` and
+    /// scripts). Blocking commands never block here, they reply as if they timed out
+    pub async fn handle_command_captured(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+    ) -> BytesMut {
+        let builder = RespBuilderV2::default();
+        let mut reply = Vec::<u8>::new();
+        let next_action = Box::pin(Self::handle_command(client_state, command, &mut reply)).await;
+        let mut reply = BytesMut::from(reply.as_slice());
+        match next_action {
+            Ok(ClientNextAction::NoAction) => {}
+            Ok(ClientNextAction::SendResponse(buffer))
+            | Ok(ClientNextAction::TerminateConnection(buffer)) => reply.extend_from_slice(&buffer),
+            Ok(ClientNextAction::Wait(_)) => builder.add_null_string(&mut reply),
+            Err(e) => {
+                let mut buffer = BytesMut::with_capacity(64);
+                builder.error_string(&mut buffer, &format!("ERR {}", e));
+                reply.extend_from_slice(&buffer);
+            }
+        }
+        reply
+    }
+
+    /// Add `command` to the slow log if it ran for longer than `slowlog_log_slower_than`
+    fn update_slowlog(
+        client_state: &Rc<ClientState>,
+        command: &Rc<RedisCommand>,
+        duration: std::time::Duration,
+    ) -> Result<(), SableError> {
+        let server_state = client_state.server_inner_state();
+        let (log_slower_than, max_len) = {
+            let options = server_state.options();
+            (
+                options.slowlog_settings.slowlog_log_slower_than,
+                options.slowlog_settings.slowlog_max_len,
+            )
+        };
+
+        let duration_us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        if log_slower_than < 0 || duration_us < log_slower_than as u64 {
+            return Ok(());
+        }
+
+        server_state.slowlog().add(
+            command.args_vec(),
+            duration_us,
+            TimeUtils::epoch_ms()? / 1000,
+            client_state.id(),
+            client_state.name(),
+            max_len,
+        );
+        Ok(())
+    }
+
+    /// Write commands that only delete keys are allowed once `maxmemory` is reached
+    fn releases_memory(kind: &RedisCommandName) -> bool {
+        matches!(
+            kind,
+            RedisCommandName::Del
+                | RedisCommandName::Unlink
+                | RedisCommandName::Flushdb
+                | RedisCommandName::Flushall
+        )
+    }
+
+    /// Is `kind` going to be queued instead of executed (the client is inside a `MULTI` block)?
+    fn is_queued(client_state: &ClientState, kind: &RedisCommandName) -> bool {
+        client_state.in_transaction()
+            && !matches!(
+                kind,
+                RedisCommandName::Multi
+                    | RedisCommandName::Exec
+                    | RedisCommandName::Discard
+                    | RedisCommandName::Watch
+                    | RedisCommandName::Quit
+                    | RedisCommandName::Reset
+            )
+    }
+
+    async fn dispatch_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
     ) -> Result<ClientNextAction, SableError> {
         let builder = RespBuilderV2::default();
 
+        // Commands called by a script are never held: the script was held before it started and
+        // it holds its keys locked while it runs
+        let in_script = LockManager::in_locked_scope();
+
+        // Writes are held while a failover is in progress. Once it completes, they either run (the
+        // failover was aborted) or are rejected (this server is now a replica)
+        if Self::is_write_command(&command)
+            && !in_script
+            && !Self::is_queued(&client_state, command.metadata().name())
+        {
+            while client_state.server_state.failover_in_progress() && client_state.active() {
+                tx.flush().await?;
+                sleep(Duration::from_millis(FAILOVER_POLL_INTERVAL_MS)).await;
+            }
+        }
+
+        // Commands are held while the clients are paused (`CLIENT PAUSE`). `CLIENT` itself is
+        // never held, so the pause can be lifted with `CLIENT UNPAUSE`
+        if !matches!(command.metadata().name(), RedisCommandName::Client)
+            && !in_script
+            && !Self::is_queued(&client_state, command.metadata().name())
+        {
+            let is_write = Self::is_write_command(&command);
+            while let Some(remaining_ms) = client_state.server_state.pause_remaining_ms(is_write) {
+                if !client_state.active() {
+                    break;
+                }
+                tx.flush().await?;
+                sleep(Duration::from_millis(
+                    remaining_ms.min(CLIENT_PAUSE_POLL_INTERVAL_MS),
+                ))
+                .await;
+            }
+        }
+
         // Can we handle this command?
         match Self::can_handle(client_state.clone(), command.clone()) {
             CanHandleCommandResult::WriteInReadOnlyReplica => {
+                client_state.abort_transaction();
                 let mut buffer = BytesMut::with_capacity(256);
                 builder.error_string(&mut buffer, ErrorStrings::WRITE_CMD_AGAINST_REPLICA);
                 Self::send_response(tx, &buffer, client_state.client_id).await?;
                 return Ok(ClientNextAction::NoAction);
             }
+            CanHandleCommandResult::NoAuth => {
+                client_state.abort_transaction();
+                let mut buffer = BytesMut::with_capacity(64);
+                builder.error_string(&mut buffer, ErrorStrings::NOAUTH);
+                Self::send_response(tx, &buffer, client_state.client_id).await?;
+                return Ok(ClientNextAction::NoAction);
+            }
+            CanHandleCommandResult::NoPerm(message) => {
+                client_state.abort_transaction();
+                let mut buffer = BytesMut::with_capacity(128);
+                builder.error_string(&mut buffer, &message);
+                Self::send_response(tx, &buffer, client_state.client_id).await?;
+                return Ok(ClientNextAction::NoAction);
+            }
             CanHandleCommandResult::ClientKilled => {
                 let mut buffer = BytesMut::with_capacity(256);
                 builder.error_string(&mut buffer, "ERR: server closed the connection");
@@ -448,12 +1125,93 @@ impl Client {
         }
 
         let kind = command.metadata().name();
+        if client_state.is_subscribed() {
+            // only a few commands are allowed while the client is subscribed
+            match kind {
+                RedisCommandName::Subscribe
+                | RedisCommandName::Unsubscribe
+                | RedisCommandName::Psubscribe
+                | RedisCommandName::Punsubscribe
+                | RedisCommandName::Quit
+                | RedisCommandName::Reset => {}
+                RedisCommandName::Ping => {
+                    let mut buffer = BytesMut::with_capacity(32);
+                    builder.add_array_len(&mut buffer, 2);
+                    builder.add_bulk_string_u8_arr(&mut buffer, b"pong");
+                    builder.add_bulk_string_u8_arr(&mut buffer, b"");
+                    Self::send_response(tx, &buffer, client_state.client_id).await?;
+                    return Ok(ClientNextAction::NoAction);
+                }
+                _ => {
+                    let mut buffer = BytesMut::with_capacity(128);
+                    builder.error_string(
+                        &mut buffer,
+                        &format!(
+                            "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                            command.main_command()
+                        ),
+                    );
+                    Self::send_response(tx, &buffer, client_state.client_id).await?;
+                    return Ok(ClientNextAction::NoAction);
+                }
+            }
+        }
+
+        if Self::is_queued(&client_state, kind) {
+            return Self::queue_command(client_state, command, tx).await;
+        }
+
+        // `maxmemory`: record the access to the command's keys and make room before a write
+        // runs. Commands called by a script were checked when the script started. `OBJECT`
+        // inspects a key without accessing it
+        if !in_script {
+            let tracker = client_state.server_state.memory_tracker();
+            if tracker.is_tracking() && !matches!(kind, RedisCommandName::Object) {
+                let user_keys: Vec<&BytesMut> = command
+                    .metadata()
+                    .key_positions(command.arg_count())
+                    .into_iter()
+                    .filter_map(|pos| command.arg(pos))
+                    .collect();
+                tracker.touch(client_state.database_id(), &user_keys);
+            }
+
+            if Self::is_write_command(&command) && !Self::releases_memory(kind) {
+                let incoming = command.args_vec().iter().skip(1).map(|arg| arg.len()).sum();
+                if !MemoryTracker::make_room(
+                    &client_state.server_state,
+                    client_state.database(),
+                    incoming,
+                )
+                .await?
+                {
+                    let mut buffer = BytesMut::with_capacity(64);
+                    builder.error_string(&mut buffer, ErrorStrings::OOM);
+                    Self::send_response(tx, &buffer, client_state.client_id).await?;
+                    return Ok(ClientNextAction::NoAction);
+                }
+            }
+        }
+
         let client_action = match kind {
             RedisCommandName::Ping => {
                 tx.write_all(PONG).await?;
                 Telemetry::inc_net_bytes_written(PONG.len() as u128);
                 ClientNextAction::NoAction
             }
+            RedisCommandName::Quit => {
+                // a transaction that was not executed is discarded
+                let _ = client_state.take_transaction();
+                let mut buffer = BytesMut::with_capacity(8);
+                builder.ok(&mut buffer);
+                ClientNextAction::TerminateConnection(buffer)
+            }
+            RedisCommandName::Reset => {
+                client_state.reset();
+                let mut buffer = BytesMut::with_capacity(16);
+                builder.simple_string(&mut buffer, "RESET");
+                ClientNextAction::SendResponse(buffer)
+            }
             RedisCommandName::Set
             | RedisCommandName::Append
             | RedisCommandName::Get
@@ -491,10 +1249,60 @@ impl Client {
                 }
                 ClientNextAction::NoAction
             }
+            RedisCommandName::Setbit | RedisCommandName::Getbit | RedisCommandName::Bitcount => {
+                match BitmapCommands::handle_command(client_state.clone(), command.clone(), tx)
+                    .await?
+                {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?
+                    }
+                    HandleCommandResult::ResponseSent => {}
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                }
+                ClientNextAction::NoAction
+            }
+            RedisCommandName::Scan => {
+                match ScanCommands::handle_command(client_state.clone(), command.clone(), tx)
+                    .await?
+                {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?
+                    }
+                    HandleCommandResult::ResponseSent => {}
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                }
+                ClientNextAction::NoAction
+            }
             RedisCommandName::Ttl
+            | RedisCommandName::Pttl
             | RedisCommandName::Del
+            | RedisCommandName::Unlink
             | RedisCommandName::Exists
-            | RedisCommandName::Expire => {
+            | RedisCommandName::Expire
+            | RedisCommandName::Pexpire
+            | RedisCommandName::Expireat
+            | RedisCommandName::Pexpireat
+            | RedisCommandName::Type
+            | RedisCommandName::Persist
+            | RedisCommandName::Randomkey
+            | RedisCommandName::Dbsize
+            | RedisCommandName::Flushdb
+            | RedisCommandName::Flushall
+            | RedisCommandName::Touch
+            | RedisCommandName::Rename
+            | RedisCommandName::Renamenx
+            | RedisCommandName::Dump
+            | RedisCommandName::Restore
+            | RedisCommandName::Migrate
+            | RedisCommandName::Move => {
                 match GenericCommands::handle_command(client_state.clone(), command.clone(), tx)
                     .await?
                 {
@@ -510,23 +1318,52 @@ impl Client {
                 }
                 ClientNextAction::NoAction
             }
-            RedisCommandName::Config => {
-                let mut buffer = BytesMut::with_capacity(32);
-                builder.ok(&mut buffer);
-                Self::send_response(tx, &buffer, client_state.client_id).await?;
+            RedisCommandName::Object => {
+                match ObjectCommands::handle_command(client_state.clone(), command.clone(), tx)
+                    .await?
+                {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?
+                    }
+                    HandleCommandResult::ResponseSent => {}
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                }
                 ClientNextAction::NoAction
             }
-            RedisCommandName::Info => {
-                let mut buffer = BytesMut::with_capacity(1024);
-                // build the stats
-                let stats = client_state
-                    .server_state
-                    .shared_telemetry()
-                    .lock()
-                    .expect("mutex")
-                    .to_string();
-                builder.bulk_string(&mut buffer, &BytesMut::from(stats.as_bytes()));
-                Self::send_response(tx, &buffer, client_state.client_id).await?;
+            RedisCommandName::Sort | RedisCommandName::SortRo => {
+                match SortCommands::handle_command(client_state.clone(), command.clone(), tx)
+                    .await?
+                {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?
+                    }
+                    HandleCommandResult::ResponseSent => {}
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                }
+                ClientNextAction::NoAction
+            }
+            RedisCommandName::Memory => {
+                match MemoryCommands::handle_command(client_state.clone(), command.clone(), tx)
+                    .await?
+                {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?
+                    }
+                    HandleCommandResult::ResponseSent => {}
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                }
                 ClientNextAction::NoAction
             }
             // List commands
@@ -564,7 +1401,10 @@ impl Client {
                 }
             }
             // Client commands
-            RedisCommandName::Client | RedisCommandName::Select => {
+            RedisCommandName::Client
+            | RedisCommandName::Select
+            | RedisCommandName::Hello
+            | RedisCommandName::Auth => {
                 match ClientCommands::handle_command(client_state.clone(), command, tx).await? {
                     HandleCommandResult::ResponseBufferUpdated(buffer) => {
                         Self::send_response(tx, &buffer, client_state.client_id).await?;
@@ -574,7 +1414,20 @@ impl Client {
                 }
                 ClientNextAction::NoAction
             }
-            RedisCommandName::ReplicaOf | RedisCommandName::SlaveOf | RedisCommandName::Command => {
+            RedisCommandName::ReplicaOf
+            | RedisCommandName::SlaveOf
+            | RedisCommandName::Command
+            | RedisCommandName::Debug
+            | RedisCommandName::Config
+            | RedisCommandName::Wait
+            | RedisCommandName::Slowlog
+            | RedisCommandName::Failover
+            | RedisCommandName::Acl
+            | RedisCommandName::Save
+            | RedisCommandName::Bgsave
+            | RedisCommandName::Lastsave
+            | RedisCommandName::SableCdc
+            | RedisCommandName::Info => {
                 match ServerCommands::handle_command(client_state.clone(), command, tx).await? {
                     HandleCommandResult::ResponseBufferUpdated(buffer) => {
                         Self::send_response(tx, &buffer, client_state.client_id).await?;
@@ -597,7 +1450,12 @@ impl Client {
             | RedisCommandName::Hvals
             | RedisCommandName::Hmget
             | RedisCommandName::Hmset
-            | RedisCommandName::Hrandfield => {
+            | RedisCommandName::Hrandfield
+            | RedisCommandName::Hscan
+            | RedisCommandName::Hsetnx
+            | RedisCommandName::Hstrlen
+            | RedisCommandName::Hexpire
+            | RedisCommandName::Httl => {
                 match HashCommands::handle_command(client_state.clone(), command, tx).await? {
                     HandleCommandResult::Blocked(_) => {
                         return Err(SableError::OtherError(
@@ -611,6 +1469,129 @@ impl Client {
                     }
                 }
             }
+            // Set commands
+            RedisCommandName::Sadd
+            | RedisCommandName::Srem
+            | RedisCommandName::Smembers
+            | RedisCommandName::Sismember
+            | RedisCommandName::Scard
+            | RedisCommandName::Spop
+            | RedisCommandName::Srandmember
+            | RedisCommandName::Smove
+            | RedisCommandName::Sinter
+            | RedisCommandName::Sinterstore
+            | RedisCommandName::Sunion
+            | RedisCommandName::Sunionstore
+            | RedisCommandName::Sdiff
+            | RedisCommandName::Sdiffstore => {
+                match SetCommands::handle_command(client_state.clone(), command, tx).await? {
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                }
+            }
+            // Sorted set commands
+            RedisCommandName::Zadd
+            | RedisCommandName::Zscore
+            | RedisCommandName::Zrange
+            | RedisCommandName::Zrem => {
+                match ZSetCommands::handle_command(client_state.clone(), command, tx).await? {
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                }
+            }
+            // Stream commands
+            RedisCommandName::Xadd
+            | RedisCommandName::Xlen
+            | RedisCommandName::Xrange
+            | RedisCommandName::Xread
+            | RedisCommandName::Xtrim
+            | RedisCommandName::Xdel
+            | RedisCommandName::Xinfo => {
+                match StreamCommands::handle_command(client_state.clone(), command, tx).await? {
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                    HandleCommandResult::Blocked((rx, duration)) => {
+                        ClientNextAction::Wait((rx, duration))
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                }
+            }
+            // Transaction commands
+            RedisCommandName::Multi
+            | RedisCommandName::Exec
+            | RedisCommandName::Discard
+            | RedisCommandName::Watch
+            | RedisCommandName::Unwatch => {
+                match TransactionCommands::handle_command(client_state.clone(), command, tx).await?
+                {
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                }
+            }
+            // Scripting commands
+            RedisCommandName::Eval
+            | RedisCommandName::Evalsha
+            | RedisCommandName::Script
+            | RedisCommandName::Function
+            | RedisCommandName::Fcall => {
+                match ScriptCommands::handle_command(client_state.clone(), command, tx).await? {
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                }
+            }
+            // Pub-sub commands
+            RedisCommandName::Subscribe
+            | RedisCommandName::Unsubscribe
+            | RedisCommandName::Psubscribe
+            | RedisCommandName::Punsubscribe
+            | RedisCommandName::Publish => {
+                match PubSubCommands::handle_command(client_state.clone(), command, tx).await? {
+                    HandleCommandResult::Blocked(_) => {
+                        return Err(SableError::OtherError(
+                            "Inernal error: client is in invalid state".to_string(),
+                        ));
+                    }
+                    HandleCommandResult::ResponseSent => ClientNextAction::NoAction,
+                    HandleCommandResult::ResponseBufferUpdated(buffer) => {
+                        Self::send_response(tx, &buffer, client_state.client_id).await?;
+                        ClientNextAction::NoAction
+                    }
+                }
+            }
             // Misc
             RedisCommandName::NotSupported(msg) => {
                 tracing::info!(msg);
@@ -623,6 +1604,35 @@ impl Client {
         Ok(client_action)
     }
 
+    /// Queue a command received inside a `MULTI` block. Commands that can not be queued fail
+    /// the entire transaction
+    async fn queue_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<ClientNextAction, SableError> {
+        let builder = RespBuilderV2::default();
+        let mut buffer = BytesMut::with_capacity(64);
+        if let RedisCommandName::NotSupported(msg) = command.metadata().name() {
+            client_state.abort_transaction();
+            builder.error_string(&mut buffer, msg.as_str());
+        } else if !command.metadata().arity_matches(command.arg_count()) {
+            client_state.abort_transaction();
+            builder.error_string(
+                &mut buffer,
+                &format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    command.main_command()
+                ),
+            );
+        } else {
+            client_state.queue_command(command);
+            builder.simple_string(&mut buffer, "QUEUED");
+        }
+        Self::send_response(tx, &buffer, client_state.client_id).await?;
+        Ok(ClientNextAction::NoAction)
+    }
+
     /// Write buffer to `tx`, upon success, update the telemetry
     async fn send_response(
         tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
@@ -644,9 +1654,384 @@ impl Client {
 impl Drop for Client {
     fn drop(&mut self) {
         Telemetry::inc_connections_closed();
+        self.state.unwatch_all();
+        self.state.unsubscribe_all();
+        self.state
+            .server_state
+            .unregister_client(self.state.client_id);
         // remove this client from this worker's list
         WORKER_CLIENTS.with(|clients| {
             let _ = clients.borrow_mut().remove(&self.state.client_id);
         });
     }
 }
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A writer that records what is written to the "socket" and how many times it was written to
+    #[derive(Default)]
+    struct RecordingWriter {
+        data: Vec<u8>,
+        writes: usize,
+    }
+
+    impl tokio::io::AsyncWrite for RecordingWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_inline_commands() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::task::spawn_local(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut client = Client::new(Arc::<ServerState>::default(), store, None);
+                let _ = client.run(stream.into_std().unwrap()).await;
+            });
+
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            async fn call(stream: &mut tokio::net::TcpStream, request: &str) -> String {
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let mut response = BytesMut::with_capacity(256);
+                stream.read_buf(&mut response).await.unwrap();
+                String::from_utf8_lossy(&response).to_string()
+            }
+
+            // a telnet session: no RESP framing, empty lines are ignored
+            assert_eq!(call(&mut stream, "PING\r\n").await, "+PONG\r\n");
+            assert_eq!(call(&mut stream, "\r\nping\n").await, "+PONG\r\n");
+            assert_eq!(
+                call(&mut stream, "SET key \"hello world\"\r\n").await,
+                "+OK\r\n"
+            );
+            assert_eq!(
+                call(&mut stream, "GET key\r\n").await,
+                "$11\r\nhello world\r\n"
+            );
+
+            // unbalanced quotes are a protocol error, the connection is then closed
+            assert_eq!(
+                call(&mut stream, "SET key \"hello\r\n").await,
+                "-ERR Protocol error: unbalanced quotes in request\r\n"
+            );
+            let mut response = BytesMut::new();
+            assert_eq!(stream.read_buf(&mut response).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn test_malformed_requests() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server_state = Arc::<ServerState>::default();
+            server_state.update_options(|options| {
+                options.client_limits.proto_max_bulk_len = 16;
+            });
+            tokio::task::spawn_local(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut client = Client::new(server_state.clone(), store.clone(), None);
+                    tokio::task::spawn_local(async move {
+                        let _ = client.run(stream.into_std().unwrap()).await;
+                    });
+                }
+            });
+
+            for (request, expected_error) in [
+                ("*-5\r\n", "invalid multibulk length"),
+                ("*1\r\n$abc\r\n", "invalid bulk length"),
+                ("*1\r\n$17\r\n", "invalid bulk length"),
+                ("*1\r\n:1\r\n", "expected '$', got ':'"),
+            ] {
+                let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let mut response = BytesMut::with_capacity(256);
+                stream.read_buf(&mut response).await.unwrap();
+                assert_eq!(
+                    String::from_utf8_lossy(&response),
+                    format!("-ERR Protocol error: {}\r\n", expected_error)
+                );
+                // the connection is closed
+                response.clear();
+                assert_eq!(stream.read_buf(&mut response).await.unwrap(), 0);
+            }
+
+            // a valid request at the limit is served
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$16\r\n0123456789abcdef\r\n")
+                .await
+                .unwrap();
+            let mut response = BytesMut::with_capacity(256);
+            stream.read_buf(&mut response).await.unwrap();
+            assert_eq!(response, "+OK\r\n");
+        });
+    }
+
+    #[test]
+    fn test_protocol_limits_follow_config_set() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server_state = Arc::<ServerState>::default();
+            tokio::task::spawn_local(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut client = Client::new(server_state.clone(), store.clone(), None);
+                    tokio::task::spawn_local(async move {
+                        let _ = client.run(stream.into_std().unwrap()).await;
+                    });
+                }
+            });
+
+            // the new limit applies to the connection that changed it
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            for (request, expected_value) in [
+                (
+                    "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$17\r\n0123456789abcdefg\r\n",
+                    "+OK\r\n",
+                ),
+                (
+                    "*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$18\r\nproto-max-bulk-len\r\n$2\r\n16\r\n",
+                    "+OK\r\n",
+                ),
+                (
+                    "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$17\r\n0123456789abcdefg\r\n",
+                    "-ERR Protocol error: invalid bulk length\r\n",
+                ),
+            ] {
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let mut response = BytesMut::with_capacity(256);
+                stream.read_buf(&mut response).await.unwrap();
+                assert_eq!(String::from_utf8_lossy(&response), expected_value);
+            }
+        });
+    }
+
+    #[test]
+    fn test_quit() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store();
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server_state = Arc::<ServerState>::default();
+            let state = server_state.clone();
+            let server_store = store.clone();
+            tokio::task::spawn_local(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut client = Client::new(state, server_store, None);
+                let _ = client.run(stream.into_std().unwrap()).await;
+            });
+
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let mut response = BytesMut::with_capacity(256);
+            stream
+                .write_all(b"*1\r\n$5\r\nMULTI\r\n*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n")
+                .await
+                .unwrap();
+            while response.len() < "+OK\r\n+QUEUED\r\n".len() {
+                stream.read_buf(&mut response).await.unwrap();
+            }
+            assert_eq!(response, "+OK\r\n+QUEUED\r\n");
+            assert_eq!(server_state.clients().len(), 1);
+
+            // QUIT replies and closes the connection, the queued transaction is discarded
+            response.clear();
+            stream.write_all(b"*1\r\n$4\r\nQUIT\r\n").await.unwrap();
+            while stream.read_buf(&mut response).await.unwrap() > 0 {}
+            assert_eq!(response, "+OK\r\n");
+
+            // the client is no longer tracked once its connection is closed
+            for _ in 0..100 {
+                if server_state.clients().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(server_state.clients().is_empty());
+            assert!(crate::storage::StringsDb::with_storage(&store, 0)
+                .get(&BytesMut::from("key"))
+                .unwrap()
+                .is_none());
+        });
+    }
+
+    #[test]
+    fn test_reset() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+            let client_state = client.inner();
+            for (args, expected_value) in [
+                (vec!["select", "3"], "+OK\r\n"),
+                (vec!["client", "setname", "myclient"], "+OK\r\n"),
+                (vec!["watch", "key"], "+OK\r\n"),
+                (
+                    vec!["subscribe", "ch"],
+                    "*3\r\n$9\r\nsubscribe\r\n$2\r\nch\r\n:1\r\n",
+                ),
+                (vec!["reset"], "+RESET\r\n"),
+                // no longer subscribed, so PING gets the regular reply
+                (vec!["ping"], "+PONG\r\n"),
+            ] {
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let reply = Client::handle_command_captured(client_state.clone(), cmd).await;
+                assert_eq!(reply, expected_value);
+            }
+
+            assert_eq!(client_state.database_id(), 0);
+            assert!(!client_state.is_subscribed());
+            assert!(client_state.name().is_empty());
+            assert!(client_state.watched_keys.borrow().is_empty());
+            assert!(!client_state.in_transaction());
+        });
+    }
+
+    #[test]
+    fn test_pipelined_replies_are_coalesced() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // 1000 pipelined commands, all parsed before the writer runs
+            let (channel_tx, channel_rx) = tokio::sync::mpsc::channel(1000);
+            for _ in 0..1000 {
+                let command = RedisCommand::for_test(vec!["set", "key", "value"]);
+                let _ = channel_tx
+                    .send(ClientRequest::Command(Rc::new(command)))
+                    .await;
+            }
+            drop(channel_tx);
+
+            let mut writer = RecordingWriter::default();
+            Client::writer_loop(&mut writer, channel_rx, client.inner())
+                .await
+                .unwrap();
+            assert_eq!(writer.writes, 1);
+            assert_eq!(writer.data, "+OK\r\n".repeat(1000).as_bytes());
+        });
+    }
+
+    /// Send `writes` pipelined `SET` commands from each of `clients` clients, all of them served
+    /// by the current worker, and wait for all the replies
+    async fn concurrent_writes(store: &StorageAdapter, clients: usize, writes: usize) {
+        let server_state = Arc::<ServerState>::default();
+        let mut tasks = Vec::new();
+        for client_id in 0..clients {
+            let client = Client::new(server_state.clone(), store.clone(), None);
+            let (channel_tx, channel_rx) = tokio::sync::mpsc::channel(writes);
+            for i in 0..writes {
+                let args = vec![
+                    BytesMut::from("set"),
+                    BytesMut::from(format!("key_{}", client_id).as_str()),
+                    BytesMut::from(format!("{}", i).as_str()),
+                ];
+                let command = RedisCommand::new(args).unwrap();
+                let _ = channel_tx
+                    .send(ClientRequest::Command(Rc::new(command)))
+                    .await;
+            }
+            drop(channel_tx);
+
+            tasks.push(tokio::task::spawn_local(async move {
+                let mut writer = RecordingWriter::default();
+                Client::writer_loop(&mut writer, channel_rx, client.inner())
+                    .await
+                    .unwrap();
+                writer.data
+            }));
+        }
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), "+OK\r\n".repeat(writes).as_bytes());
+        }
+
+        // every client reads its last write
+        for client_id in 0..clients {
+            let key = BytesMut::from(format!("key_{}", client_id).as_str());
+            assert_eq!(
+                crate::storage::StringsDb::with_storage(store, 0)
+                    .get(&key)
+                    .unwrap()
+                    .map(|(value, _)| value),
+                Some(BytesMut::from(format!("{}", writes - 1).as_str()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_group_commit_shares_syncs() {
+        const CLIENTS: usize = 8;
+        const WRITES: usize = 5;
+        // long enough for all the clients of the worker to join every group
+        const INTERVAL_MICROS: usize = 100_000;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store_with(|open_params| {
+                open_params
+                    .set_wal_disabled(false)
+                    .set_appendfsync(crate::storage::AppendFsync::Always)
+                    .set_group_commit_interval_micros(INTERVAL_MICROS)
+            });
+            concurrent_writes(&store, CLIENTS, WRITES).await;
+
+            // the worker is not blocked while a group is collected: the clients of the worker
+            // share the syncs, instead of waiting for a sync each, one after the other
+            let syncs = store
+                .stats()
+                .unwrap()
+                .into_iter()
+                .find(|(name, _)| name == "group_commit_syncs")
+                .map(|(_, syncs)| syncs)
+                .unwrap();
+            assert!(syncs >= WRITES as u64, "{}", syncs);
+            assert!(syncs < (CLIENTS * WRITES) as u64, "{}", syncs);
+        });
+    }
+}
@@ -0,0 +1,331 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count, check_value_type,
+    client::ClientState,
+    command_arg_at, command_arg_at_as_str,
+    commands::{ErrorStrings, HandleCommandResult},
+    metadata::{Encoding, ValueTypeIs},
+    storage::{PutFlags, StringsDb},
+    to_number, to_number_ex, BytesMutUtils, LockManager, RedisCommand, RedisCommandName,
+    RespBuilderV2, SableError, StringValueMetadata,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+/// Bit offsets must fit in a 512MB string, same as Redis
+const MAX_BIT_OFFSET: u64 = (512 * 1024 * 1024 * 8) - 1;
+
+pub struct BitmapCommands {}
+
+impl BitmapCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Setbit => {
+                Self::setbit(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Getbit => {
+                Self::getbit(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Bitcount => {
+                Self::bitcount(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non bitmap command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Sets or clears the bit at offset in the string value stored at key. The string is grown
+    /// (zero padded) to make sure it can hold a bit at offset. Returns the original bit value
+    /// stored at offset
+    async fn setbit(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let key = command_arg_at!(command, 1);
+        let Some(offset) = Self::parse_bit_offset(command_arg_at!(command, 2), response_buffer)
+        else {
+            return Ok(());
+        };
+        let bit = match command_arg_at!(command, 3).as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(response_buffer, ErrorStrings::BIT_NOT_AN_INT);
+                return Ok(());
+            }
+        };
+
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let strings_db =
+            StringsDb::with_storage(client_state.database(), client_state.database_id());
+
+        // keep the metadata (and the TTL) of an existing value
+        let (mut value, md) = match strings_db.get(key)? {
+            Some((value, md)) => {
+                check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+                (value, md)
+            }
+            None => (BytesMut::new(), StringValueMetadata::new()),
+        };
+
+        let byte_index = (offset / 8) as usize;
+        let mask = Self::bit_mask(offset);
+        if value.len() <= byte_index {
+            value.resize(byte_index.saturating_add(1), 0);
+        }
+
+        let old_bit = value[byte_index] & mask != 0;
+        if bit {
+            value[byte_index] |= mask;
+        } else {
+            value[byte_index] &= !mask;
+        }
+        strings_db.put(key, &value, &md, PutFlags::Override)?;
+
+        let builder = RespBuilderV2::default();
+        builder.number_usize(response_buffer, old_bit as usize);
+        Ok(())
+    }
+
+    /// Returns the bit value at offset in the string value stored at key. When offset is beyond
+    /// the string length, or the key does not exist, the bit is assumed to be `0`
+    async fn getbit(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let key = command_arg_at!(command, 1);
+        let Some(offset) = Self::parse_bit_offset(command_arg_at!(command, 2), response_buffer)
+        else {
+            return Ok(());
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let strings_db =
+            StringsDb::with_storage(client_state.database(), client_state.database_id());
+
+        let mut bit = false;
+        if let Some((value, md)) = strings_db.get(key)? {
+            check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+            if let Some(byte) = value.get((offset / 8) as usize) {
+                bit = byte & Self::bit_mask(offset) != 0;
+            }
+        }
+
+        let builder = RespBuilderV2::default();
+        builder.number_usize(response_buffer, bit as usize);
+        Ok(())
+    }
+
+    /// `BITCOUNT key [start end [BYTE | BIT]]`
+    /// Count the number of set bits in a string. By default all the bytes of the string are
+    /// examined. `start` and `end` (inclusive, negative values count from the end) limit the
+    /// range to the given bytes, or to the given bits with `BIT`
+    async fn bitcount(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // (start, end, range in bits?)
+        let range = match command.arg_count() {
+            2 => None,
+            4 | 5 => {
+                let start = to_number!(command_arg_at!(command, 2), i64, response_buffer, Ok(()));
+                let end = to_number!(command_arg_at!(command, 3), i64, response_buffer, Ok(()));
+                let bit_range = if command.arg_count() == 5 {
+                    match command_arg_at_as_str!(command, 4).as_str() {
+                        "byte" => false,
+                        "bit" => true,
+                        _ => {
+                            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    false
+                };
+                Some((start, end, bit_range))
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let strings_db =
+            StringsDb::with_storage(client_state.database(), client_state.database_id());
+
+        let Some((value, md)) = strings_db.get(key)? else {
+            builder.number_usize(response_buffer, 0);
+            return Ok(());
+        };
+        check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+
+        let count = match range {
+            None => Self::count_bytes(&value),
+            Some((start, end, false)) => match Self::normalise_range(start, end, value.len()) {
+                Some((start, end)) => Self::count_bytes(&value[start..=end]),
+                None => 0,
+            },
+            Some((start, end, true)) => {
+                let total_bits = value.len().saturating_mul(8);
+                match Self::normalise_range(start, end, total_bits) {
+                    Some((start, end)) => (start..=end)
+                        .filter(|offset| value[offset / 8] & Self::bit_mask(*offset as u64) != 0)
+                        .count(),
+                    None => 0,
+                }
+            }
+        };
+        builder.number_usize(response_buffer, count);
+        Ok(())
+    }
+
+    // =========-------------------------------------------
+    // Internal helpers
+    // =========-------------------------------------------
+
+    /// Parse a bit offset. On failure, an error is written to `response_buffer` and `None` is
+    /// returned
+    fn parse_bit_offset(offset: &BytesMut, response_buffer: &mut BytesMut) -> Option<u64> {
+        match BytesMutUtils::parse::<u64>(offset) {
+            Some(offset) if offset <= MAX_BIT_OFFSET => Some(offset),
+            _ => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(response_buffer, ErrorStrings::BIT_OFFSET_OUT_OF_RANGE);
+                None
+            }
+        }
+    }
+
+    /// The mask of the bit at `offset` inside its byte. Bit `0` is the most significant bit of
+    /// the first byte
+    fn bit_mask(offset: u64) -> u8 {
+        1u8 << (7 - (offset % 8))
+    }
+
+    fn count_bytes(bytes: &[u8]) -> usize {
+        bytes.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// Convert the inclusive range `[start, end]` (negative values count from the end) into
+    /// valid indices for a sequence of `len` elements. Returns `None` for an empty range
+    fn normalise_range(start: i64, end: i64, len: usize) -> Option<(usize, usize)> {
+        let len = len as i64;
+        let start = if start < 0 { len + start } else { start }.max(0);
+        let end = if end < 0 { len + end } else { end }.min(len - 1);
+        if len == 0 || start > end {
+            None
+        } else {
+            Some((start as usize, end as usize))
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["setbit", "bitmap", "7"], "-ERR wrong number of arguments for 'setbit' command\r\n"),
+        (vec!["setbit", "bitmap", "-1", "1"], "-ERR bit offset is not an integer or out of range\r\n"),
+        (vec!["setbit", "bitmap", "4294967296", "1"], "-ERR bit offset is not an integer or out of range\r\n"),
+        (vec!["setbit", "bitmap", "7", "2"], "-ERR bit is not an integer or out of range\r\n"),
+        (vec!["exists", "bitmap"], ":0\r\n"),
+        // growth from an empty key
+        (vec!["setbit", "bitmap", "7", "1"], ":0\r\n"),
+        (vec!["get", "bitmap"], "$1\r\n\x01\r\n"),
+        (vec!["setbit", "bitmap", "7", "1"], ":1\r\n"),
+        (vec!["setbit", "bitmap", "23", "1"], ":0\r\n"),
+        (vec!["strlen", "bitmap"], ":3\r\n"),
+        (vec!["get", "bitmap"], "$3\r\n\x01\x00\x01\r\n"),
+        (vec!["setbit", "bitmap", "7", "0"], ":1\r\n"),
+        (vec!["get", "bitmap"], "$3\r\n\x00\x00\x01\r\n"),
+        (vec!["getbit", "bitmap", "23"], ":1\r\n"),
+        (vec!["getbit", "bitmap", "7"], ":0\r\n"),
+        (vec!["getbit", "bitmap", "1000"], ":0\r\n"),
+        (vec!["getbit", "no_such_key", "0"], ":0\r\n"),
+        (vec!["getbit", "bitmap", "abc"], "-ERR bit offset is not an integer or out of range\r\n"),
+        // a TTL is kept when bits are updated
+        (vec!["set", "volatile", "a", "ex", "100"], "+OK\r\n"),
+        (vec!["setbit", "volatile", "7", "0"], ":1\r\n"),
+        (vec!["get", "volatile"], "$1\r\n`\r\n"),
+        (vec!["ttl", "volatile"], ":100\r\n"),
+        (vec!["rpush", "mylist", "a"], ":1\r\n"),
+        (vec!["setbit", "mylist", "1", "1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["getbit", "mylist", "1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ]; "test_setbit_getbit")]
+    #[test_case(vec![
+        (vec!["bitcount"], "-ERR wrong number of arguments for 'bitcount' command\r\n"),
+        (vec!["bitcount", "no_such_key"], ":0\r\n"),
+        (vec!["set", "mykey", "foobar"], "+OK\r\n"),
+        (vec!["bitcount", "mykey"], ":26\r\n"),
+        (vec!["bitcount", "mykey", "0", "0"], ":4\r\n"),
+        (vec!["bitcount", "mykey", "1", "1"], ":6\r\n"),
+        (vec!["bitcount", "mykey", "1", "1", "BYTE"], ":6\r\n"),
+        (vec!["bitcount", "mykey", "-2", "-1"], ":7\r\n"),
+        (vec!["bitcount", "mykey", "5", "30"], ":4\r\n"),
+        (vec!["bitcount", "mykey", "3", "1"], ":0\r\n"),
+        (vec!["bitcount", "mykey", "5", "30", "BIT"], ":17\r\n"),
+        (vec!["bitcount", "mykey", "-8", "-1", "bit"], ":4\r\n"),
+        (vec!["bitcount", "mykey", "1"], "-ERR syntax error\r\n"),
+        (vec!["bitcount", "mykey", "1", "2", "BITS"], "-ERR syntax error\r\n"),
+        (vec!["bitcount", "mykey", "a", "2"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["hset", "myhash", "f", "v"], ":1\r\n"),
+        (vec!["bitcount", "myhash"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ]; "test_bitcount")]
+    fn test_bitmap_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name("test_bitmap_commands").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+}
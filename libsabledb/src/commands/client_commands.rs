@@ -1,5 +1,6 @@
 #[allow(unused_imports)]
 use crate::{
+    acl::DEFAULT_USER,
     check_args_count, check_value_type,
     client::ClientState,
     command_arg_at,
@@ -10,7 +11,8 @@ use crate::{
     storage::StringsDb,
     worker::BroadcastMessageType,
     BytesMutUtils, Expiration, LockManager, PrimaryKeyMetadata, RedisCommand, RedisCommandName,
-    RespBuilderV2, SableError, StorageAdapter, StringUtils, Telemetry, TimeUtils,
+    RespBuilderV2, RespProtocolVersion, SableError, StorageAdapter, StringUtils, Telemetry,
+    TimeUtils,
 };
 
 use bytes::BytesMut;
@@ -33,6 +35,12 @@ impl ClientCommands {
             RedisCommandName::Select => {
                 Self::select(client_state, command, &mut response_buffer).await?;
             }
+            RedisCommandName::Hello => {
+                Self::hello(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Auth => {
+                Self::auth(client_state, command, &mut response_buffer).await?;
+            }
             _ => {
                 return Err(SableError::InvalidArgument(format!(
                     "Non client command {}",
@@ -75,6 +83,78 @@ impl ClientCommands {
             "id" => {
                 builder.number::<u128>(response_buffer, client_state.id(), false);
             }
+            "setname" => {
+                check_args_count!(command, 3, response_buffer);
+                let name = command_arg_at!(command, 2);
+                // names are listed in space separated `CLIENT LIST` lines
+                if name.iter().any(|c| !(b'!'..=b'~').contains(c)) {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR Client names cannot contain spaces, newlines or special characters.",
+                    );
+                    return Ok(());
+                }
+                client_state.set_name(&BytesMutUtils::to_string(name));
+                builder.ok(response_buffer);
+            }
+            "getname" => {
+                let name = client_state.name();
+                if name.is_empty() {
+                    builder.null_string(response_buffer);
+                } else {
+                    builder.bulk_string(response_buffer, &BytesMut::from(name.as_str()));
+                }
+            }
+            "list" => {
+                let now_ms = TimeUtils::epoch_ms()?;
+                let mut list = String::new();
+                for (client_id, info) in client_state.server_inner_state().clients() {
+                    list.push_str(&format!(
+                        "id={} addr={} name={} age={} db={} cmd={}\n",
+                        client_id,
+                        info.addr,
+                        info.name,
+                        now_ms.saturating_sub(info.connected_at_ms) / 1000,
+                        info.db,
+                        info.last_command,
+                    ));
+                }
+                builder.bulk_string(response_buffer, &BytesMut::from(list.as_str()));
+            }
+            "pause" => {
+                // CLIENT PAUSE timeout [WRITE | ALL]
+                check_args_count!(command, 3, response_buffer);
+                let Some(timeout_ms) = BytesMutUtils::parse::<u64>(command_arg_at!(command, 2))
+                else {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR timeout is not an integer or out of range",
+                    );
+                    return Ok(());
+                };
+                let all = match command.arg_as_lowercase_string(3).as_deref() {
+                    None | Some("all") if command.arg_count() <= 4 => true,
+                    Some("write") if command.arg_count() == 4 => false,
+                    _ => {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    }
+                };
+                let until_ms = TimeUtils::epoch_ms()?.saturating_add(timeout_ms);
+                client_state
+                    .server_inner_state()
+                    .pause_clients(until_ms, all);
+                builder.ok(response_buffer);
+            }
+            "unpause" => {
+                client_state.server_inner_state().unpause_clients();
+                builder.ok(response_buffer);
+            }
+            "kill" if command.arg_count() == 3 => {
+                // CLIENT KILL addr:port
+                let addr = command_arg_at_as_str!(command, 2);
+                Self::kill_by_addr(&client_state, &addr, response_buffer).await?;
+            }
             "kill" => {
                 check_args_count!(command, 4, response_buffer);
                 let filter = command_arg_at_as_str!(command, 2);
@@ -95,6 +175,11 @@ impl ClientCommands {
                             .await?;
                         builder.ok(response_buffer);
                     }
+                    "addr" => {
+                        // CLIENT KILL ADDR addr:port
+                        let addr = command_arg_at_as_str!(command, 3);
+                        Self::kill_by_addr(&client_state, &addr, response_buffer).await?;
+                    }
                     other => {
                         let msg = format!("command `client kill {}` is not supported", other);
                         builder.error_string(response_buffer, msg.as_str());
@@ -109,6 +194,27 @@ impl ClientCommands {
         Ok(())
     }
 
+    /// Kill the client connected from `addr` (`ip:port`)
+    async fn kill_by_addr(
+        client_state: &ClientState,
+        addr: &str,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let server_state = client_state.server_inner_state();
+        let Some((client_id, _)) = server_state
+            .clients()
+            .into_iter()
+            .find(|(_, info)| info.addr == addr)
+        else {
+            builder.error_string(response_buffer, "ERR No such client");
+            return Ok(());
+        };
+        server_state.terminate_client(client_id).await?;
+        builder.ok(response_buffer);
+        Ok(())
+    }
+
     /// Select the Redis logical database having the specified zero-based numeric index.
     /// New connections always use the database 0.
     async fn select(
@@ -117,20 +223,192 @@ impl ClientCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 2, response_buffer);
-        let db_index = command_arg_at_as_str!(command, 1);
         let builder = RespBuilderV2::default();
-        let Ok(db_index) = db_index.parse::<u16>() else {
-            // parsing failed
+        let Some(db_index) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 1)) else {
             builder.error_string(
                 response_buffer,
-                "ERR value is not an integer or out of range",
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             );
             return Ok(());
         };
+        let databases = client_state
+            .server_inner_state()
+            .options()
+            .general_settings
+            .databases;
+        let Some(db_index) = usize::try_from(db_index)
+            .ok()
+            .filter(|db_index| *db_index < databases)
+            .and_then(|db_index| u16::try_from(db_index).ok())
+        else {
+            builder.error_string(response_buffer, "ERR DB index is out of range");
+            return Ok(());
+        };
         client_state.set_database_id(db_index);
         builder.ok(response_buffer);
         Ok(())
     }
+
+    /// `HELLO [protover [AUTH username password] [SETNAME clientname]]`
+    /// Switch the connection to the requested protocol version and reply with a map describing
+    /// the server. Without `protover`, the current protocol is kept
+    async fn hello(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let mut protocol = client_state.resp_version();
+        if command.arg_count() > 1 {
+            let Some(version) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 1)) else {
+                builder.error_string(
+                    response_buffer,
+                    "ERR Protocol version is not an integer or out of range",
+                );
+                return Ok(());
+            };
+            protocol = match version {
+                2 => RespProtocolVersion::Resp2,
+                3 => RespProtocolVersion::Resp3,
+                _ => {
+                    builder.error_string(response_buffer, "NOPROTO unsupported protocol version");
+                    return Ok(());
+                }
+            };
+        }
+
+        // validate all the options before changing anything
+        let mut client_name = None;
+        let mut auth_user = None;
+        let mut pos = 2usize;
+        while pos < command.arg_count() {
+            let option = command_arg_at_as_str!(command, pos);
+            match option.as_str() {
+                "auth" if pos + 2 < command.arg_count() => {
+                    let username = BytesMutUtils::to_string(command_arg_at!(command, pos + 1));
+                    let password = command_arg_at!(command, pos + 2);
+                    if !Self::check_credentials(&client_state, &username, password) {
+                        builder.error_string(response_buffer, ErrorStrings::WRONGPASS);
+                        return Ok(());
+                    }
+                    auth_user = Some(username);
+                    pos += 3;
+                }
+                "setname" if pos + 1 < command.arg_count() => {
+                    let name = command_arg_at!(command, pos + 1);
+                    client_name = Some(String::from_utf8_lossy(name).to_string());
+                    pos += 2;
+                }
+                _ => {
+                    builder.error_string(
+                        response_buffer,
+                        &format!("ERR Syntax error in HELLO option '{}'", option),
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(username) = auth_user {
+            client_state.set_user(&username);
+            client_state.set_authenticated(true);
+        } else if !client_state.is_authenticated() {
+            builder.error_string(
+                response_buffer,
+                "NOAUTH HELLO must be called with the client already authenticated, otherwise the \
+                HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and \
+                select the RESP protocol version at the same time",
+            );
+            return Ok(());
+        }
+
+        if let Some(client_name) = client_name {
+            client_state.set_name(&client_name);
+        }
+        client_state.set_resp_version(protocol);
+
+        // the reply uses the new protocol
+        let builder = client_state.resp_builder();
+        let role = if client_state.server_inner_state().is_replica() {
+            "replica"
+        } else {
+            "master"
+        };
+        response_buffer.clear();
+        builder.add_map_len(response_buffer, 7);
+        builder.add_bulk_string_u8_arr(response_buffer, b"server");
+        builder.add_bulk_string_u8_arr(response_buffer, b"sabledb");
+        builder.add_bulk_string_u8_arr(response_buffer, b"version");
+        builder.add_bulk_string_u8_arr(response_buffer, env!("CARGO_PKG_VERSION").as_bytes());
+        builder.add_bulk_string_u8_arr(response_buffer, b"proto");
+        builder.add_number::<u64>(response_buffer, protocol.as_number(), false);
+        builder.add_bulk_string_u8_arr(response_buffer, b"id");
+        builder.add_number::<u128>(response_buffer, client_state.id(), false);
+        builder.add_bulk_string_u8_arr(response_buffer, b"mode");
+        builder.add_bulk_string_u8_arr(response_buffer, b"standalone");
+        builder.add_bulk_string_u8_arr(response_buffer, b"role");
+        builder.add_bulk_string_u8_arr(response_buffer, role.as_bytes());
+        builder.add_bulk_string_u8_arr(response_buffer, b"modules");
+        builder.add_empty_array(response_buffer);
+        Ok(())
+    }
+
+    /// `AUTH [username] password`
+    /// Authenticate the connection as `username` (`default` when omitted). The commands that
+    /// follow run with the user's permissions
+    async fn auth(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let (username, password) = match command.arg_count() {
+            2 => (None, command_arg_at!(command, 1)),
+            3 => (
+                Some(command_arg_at!(command, 1)),
+                command_arg_at!(command, 2),
+            ),
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        if username.is_none()
+            && !client_state
+                .server_inner_state()
+                .acl()
+                .default_user_requires_auth()
+        {
+            builder.error_string(
+                response_buffer,
+                "ERR AUTH <password> called without any password configured for the default user. \
+                Are you sure your configuration is correct?",
+            );
+            return Ok(());
+        }
+
+        let username = username
+            .map(BytesMutUtils::to_string)
+            .unwrap_or(DEFAULT_USER.to_string());
+        if !Self::check_credentials(&client_state, &username, password) {
+            builder.error_string(response_buffer, ErrorStrings::WRONGPASS);
+            return Ok(());
+        }
+        client_state.set_user(&username);
+        client_state.set_authenticated(true);
+        builder.ok(response_buffer);
+        Ok(())
+    }
+
+    /// Can `username` authenticate with `password`?
+    fn check_credentials(client_state: &ClientState, username: &str, password: &[u8]) -> bool {
+        client_state
+            .server_inner_state()
+            .acl()
+            .authenticate(username, password)
+    }
 }
 
 //  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
@@ -150,8 +428,11 @@ mod tests {
 
     #[test_case(vec![
         (vec!["select", "abc"], "-ERR value is not an integer or out of range\r\n"),
-        (vec!["select", "-1"], "-ERR value is not an integer or out of range\r\n"),
-        (vec!["select", "67000"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["select", "1.5"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["select", "-1"], "-ERR DB index is out of range\r\n"),
+        (vec!["select", "16"], "-ERR DB index is out of range\r\n"),
+        (vec!["select", "67000"], "-ERR DB index is out of range\r\n"),
+        (vec!["select", "15"], "+OK\r\n"),
         (vec!["select", "1"], "+OK\r\n"),
         (vec!["set", "key", "value_1"], "+OK\r\n"),
         (vec!["select", "2"], "+OK\r\n"),
@@ -250,4 +531,280 @@ mod tests {
         });
         Ok(())
     }
+
+    #[test]
+    fn test_client_name_and_list() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let client1 = Client::new(server_state.clone(), store.clone(), None);
+            let client2 = Client::new(server_state.clone(), store.clone(), None);
+            server_state.update_client(client2.inner().id(), |info| {
+                info.addr = "127.0.0.1:7000".to_string()
+            });
+
+            let run_command = |client: &Client, args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink =
+                        crate::tests::ResponseSink::with_name("test_client_name_and_list").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            assert_eq!(run_command(&client1, vec!["client", "getname"]).await, "$-1\r\n");
+            assert_eq!(
+                run_command(&client1, vec!["client", "setname", "my name"]).await,
+                "-ERR Client names cannot contain spaces, newlines or special characters.\r\n"
+            );
+            assert_eq!(
+                run_command(&client1, vec!["client", "setname", "worker-1"]).await,
+                "+OK\r\n"
+            );
+            assert_eq!(
+                run_command(&client1, vec!["client", "getname"]).await,
+                "$8\r\nworker-1\r\n"
+            );
+            assert_eq!(run_command(&client1, vec!["select", "2"]).await, "+OK\r\n");
+
+            let expected_list = format!(
+                "id={} addr= name=worker-1 age=0 db=2 cmd=client\nid={} addr=127.0.0.1:7000 name= age=0 db=0 cmd=\n",
+                client1.inner().id(),
+                client2.inner().id()
+            );
+            assert_eq!(
+                run_command(&client1, vec!["client", "list"]).await,
+                format!("${}\r\n{}\r\n", expected_list.len(), expected_list)
+            );
+
+            // kill by address
+            assert_eq!(
+                run_command(&client1, vec!["client", "kill", "127.0.0.1:7001"]).await,
+                "-ERR No such client\r\n"
+            );
+            assert_eq!(
+                run_command(&client1, vec!["client", "kill", "addr", "127.0.0.1:7000"]).await,
+                "+OK\r\n"
+            );
+            assert!(!client2.inner().active());
+
+            // disconnected clients are no longer listed
+            let client2_id = client2.inner().id();
+            drop(client2);
+            assert!(!run_command(&client1, vec!["client", "list"])
+                .await
+                .contains(&format!("id={} ", client2_id)));
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_pause() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let writer = Client::new(server_state.clone(), store.clone(), None);
+            let reader = Client::new(server_state.clone(), store.clone(), None);
+
+            // run a command, return its reply and how long it took
+            let run_command = |client: &Client, args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_client_pause").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    let started_at = std::time::Instant::now();
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    (sink.read_all().await, started_at.elapsed())
+                }
+            };
+
+            for (args, expected_value) in [
+                (
+                    vec!["client", "pause", "abc"],
+                    "-ERR timeout is not an integer or out of range\r\n",
+                ),
+                (
+                    vec!["client", "pause", "-1"],
+                    "-ERR timeout is not an integer or out of range\r\n",
+                ),
+                (
+                    vec!["client", "pause", "10", "read"],
+                    "-ERR syntax error\r\n",
+                ),
+                (
+                    vec!["client", "pause", "10", "write", "all"],
+                    "-ERR syntax error\r\n",
+                ),
+            ] {
+                assert_eq!(run_command(&writer, args).await.0, expected_value);
+            }
+
+            // the write is held until the pause expires, the read is not
+            assert_eq!(
+                run_command(&writer, vec!["client", "pause", "300", "write"])
+                    .await
+                    .0,
+                "+OK\r\n"
+            );
+            let (write, read) = tokio::join!(
+                run_command(&writer, vec!["set", "key", "value"]),
+                run_command(&reader, vec!["get", "key"]),
+            );
+            assert_eq!(read.0, "$-1\r\n");
+            assert!(read.1 < std::time::Duration::from_millis(200));
+            assert_eq!(write.0, "+OK\r\n");
+            assert!(write.1 >= std::time::Duration::from_millis(200));
+
+            // `ALL` holds the reads too, `CLIENT UNPAUSE` lifts the pause early
+            assert_eq!(
+                run_command(&writer, vec!["client", "pause", "10000"])
+                    .await
+                    .0,
+                "+OK\r\n"
+            );
+            let (read, _) = tokio::join!(run_command(&reader, vec!["get", "key"]), async {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                assert_eq!(
+                    run_command(&writer, vec!["client", "unpause"]).await.0,
+                    "+OK\r\n"
+                );
+            });
+            assert_eq!(read.0, "$5\r\nvalue\r\n");
+            assert!(read.1 >= std::time::Duration::from_millis(100));
+            assert!(read.1 < std::time::Duration::from_millis(5000));
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let server_info = |proto: &str, map_header: &str| {
+                format!(
+                    "{}$6\r\nserver\r\n$7\r\nsabledb\r\n$7\r\nversion\r\n${}\r\n{}\r\n$5\r\nproto\r\n:{}\r\n$2\r\nid\r\n:{}\r\n$4\r\nmode\r\n$10\r\nstandalone\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n",
+                    map_header,
+                    env!("CARGO_PKG_VERSION").len(),
+                    env!("CARGO_PKG_VERSION"),
+                    proto,
+                    client.inner().id()
+                )
+            };
+
+            for (args, expected_value) in [
+                (vec!["hello", "abc"], "-ERR Protocol version is not an integer or out of range\r\n".to_string()),
+                (vec!["hello", "4"], "-NOPROTO unsupported protocol version\r\n".to_string()),
+                (vec!["hello", "3", "setname"], "-ERR Syntax error in HELLO option 'setname'\r\n".to_string()),
+                (vec!["hello", "3", "auth", "someone", "pass"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n".to_string()),
+                // failed negotiations leave the protocol untouched
+                (vec!["hello"], server_info("2", "*14\r\n")),
+                (vec!["hset", "myhash", "f1", "v1", "f2", "v2"], ":2\r\n".to_string()),
+                (vec!["hgetall", "myhash"], "*4\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n".to_string()),
+                (vec!["hello", "3", "auth", "default", "pass", "setname", "myclient"], server_info("3", "%7\r\n")),
+                (vec!["hgetall", "myhash"], "%2\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n".to_string()),
+                (vec!["hgetall", "no_such_hash"], "%0\r\n".to_string()),
+                (vec!["hkeys", "myhash"], "*2\r\n$2\r\nf1\r\n$2\r\nf2\r\n".to_string()),
+                (vec!["hello", "2"], server_info("2", "*14\r\n")),
+                (vec!["hgetall", "no_such_hash"], "*0\r\n".to_string()),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_hello").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+            assert_eq!(
+                client.inner().attribute(&"name".to_string()),
+                Some("myclient".to_string())
+            );
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_auth() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let first = Client::new(server_state.clone(), store.clone(), None);
+            let second = Client::new(server_state.clone(), store.clone(), None);
+
+            // without a password, any client can run commands
+            for (client, args, expected_value) in [
+                (&first, vec!["set", "key", "value"], "+OK\r\n"),
+                (&first, vec!["auth", "secret"], "-ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?\r\n"),
+                (&first, vec!["auth", "someone", "secret"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n"),
+                (&first, vec!["auth", "default", "secret"], "+OK\r\n"),
+                (&first, vec!["auth", "default", "secret", "extra"], "-ERR syntax error\r\n"),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_auth").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+
+            server_state
+                .acl()
+                .set_user("default", &["resetpass".to_string(), ">secret".to_string()])
+                .unwrap();
+            let third = Client::new(server_state.clone(), store.clone(), None);
+            first.inner().set_authenticated(false);
+
+            for (client, args, expected_value) in [
+                (&first, vec!["get", "key"], "-NOAUTH Authentication required.\r\n"),
+                (&first, vec!["set", "key", "value"], "-NOAUTH Authentication required.\r\n"),
+                (&first, vec!["ping"], "+PONG\r\n"),
+                (&first, vec!["hello", "2"], "-NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time\r\n"),
+                (&first, vec!["auth", "wrong"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n"),
+                (&first, vec!["auth", "someone", "secret"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n"),
+                (&first, vec!["get", "key"], "-NOAUTH Authentication required.\r\n"),
+                (&first, vec!["auth", "secret"], "+OK\r\n"),
+                (&first, vec!["get", "key"], "$5\r\nvalue\r\n"),
+                // authentication is per client
+                (&second, vec!["get", "key"], "-NOAUTH Authentication required.\r\n"),
+                (&second, vec!["auth", "default", "secret"], "+OK\r\n"),
+                (&second, vec!["get", "key"], "$5\r\nvalue\r\n"),
+                (&third, vec!["hello", "2", "auth", "default", "wrong"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n"),
+                (&third, vec!["get", "key"], "-NOAUTH Authentication required.\r\n"),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_auth").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+
+            // `HELLO AUTH` authenticates the client too
+            let mut sink = crate::tests::ResponseSink::with_name("test_auth").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec![
+                "hello", "2", "auth", "default", "secret",
+            ]));
+            Client::handle_command(third.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert!(sink.read_all().await.starts_with("*14\r\n"));
+            assert!(third.inner().is_authenticated());
+        });
+        Ok(())
+    }
 }
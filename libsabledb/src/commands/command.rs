@@ -2,14 +2,28 @@ use crate::commands::{CommandMetadata, CommandsManager};
 use crate::{BytesMutUtils, SableError};
 use bytes::BytesMut;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
-lazy_static::lazy_static! {
-    static ref COMMANDS_MGR: CommandsManager = CommandsManager::default();
-}
+static COMMANDS_MGR: OnceLock<CommandsManager> = OnceLock::new();
 
 /// Generate a RESPv2 output from the command table. Used by the `command` command
 pub fn commands_manager() -> &'static CommandsManager {
-    &COMMANDS_MGR
+    COMMANDS_MGR.get_or_init(CommandsManager::default)
+}
+
+/// Initialise the global commands table, applying the `rename_commands` on top of the
+/// default one, in order. This function should be called once during startup, before
+/// any command is processed
+pub fn init_commands_manager(rename_commands: &[(String, String)]) -> Result<(), SableError> {
+    let mut manager = CommandsManager::default();
+    manager.rename_commands(rename_commands)?;
+
+    if COMMANDS_MGR.set(manager).is_err() {
+        return Err(SableError::OtherError(
+            "commands table is already initialised".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 #[derive(Default, Debug, Clone)]
@@ -39,7 +53,7 @@ impl RedisCommand {
         };
 
         let command_name = String::from_utf8_lossy(command_name).to_lowercase();
-        let metadata = COMMANDS_MGR.metadata(command_name.as_str());
+        let metadata = commands_manager().metadata(command_name.as_str());
 
         Ok(RedisCommand {
             args,
@@ -1,8 +1,9 @@
+use crate::SableError;
 use bytes::BytesMut;
 use std::collections::HashMap;
 use strum_macros::EnumString;
 
-#[derive(Default, Debug, Clone, EnumString)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, EnumString)]
 pub enum RedisCommandFlags {
     #[default]
     None = 0,
@@ -21,6 +22,16 @@ pub enum RedisCommandFlags {
     /// Command might block the client
     #[strum(serialize = "blocking")]
     Blocking = 1 << 4,
+    /// @pubsub command
+    #[strum(serialize = "pubsub")]
+    PubSub = 1 << 5,
+    /// @scripting command. These commands can not be called from a script
+    #[strum(serialize = "scripting")]
+    Scripting = 1 << 6,
+    /// Can not be called from a script: the command may suspend the client (sleep, network
+    /// I/O...) while the script holds the locks of its keys. Not an ACL category
+    #[strum(disabled)]
+    NoScript = 1 << 7,
 }
 
 #[derive(Clone, Debug, Default, EnumString)]
@@ -50,6 +61,10 @@ pub enum RedisCommandName {
     SetRange,
     Strlen,
     Substr,
+    // Bitmap commands
+    Setbit,
+    Getbit,
+    Bitcount,
     // List commands
     Lpush,
     Lpushx,
@@ -76,16 +91,52 @@ pub enum RedisCommandName {
     // Client commands
     Client,
     Select,
+    Hello,
+    Auth,
+    Quit,
+    Reset,
     // Server commands
     ReplicaOf,
     SlaveOf,
     Info,
     Command,
+    Debug,
+    Wait,
+    Slowlog,
+    Failover,
+    Acl,
+    Save,
+    Bgsave,
+    Lastsave,
+    SableCdc,
     // Generic commands
     Ttl,
+    Pttl,
     Del,
+    Unlink,
     Exists,
     Expire,
+    Pexpire,
+    Expireat,
+    Pexpireat,
+    Scan,
+    Type,
+    Persist,
+    Object,
+    Memory,
+    Sort,
+    SortRo,
+    Randomkey,
+    Dbsize,
+    Flushdb,
+    Flushall,
+    Touch,
+    Rename,
+    Renamenx,
+    Dump,
+    Restore,
+    Migrate,
+    Move,
     // Hash commands
     Hset,
     Hget,
@@ -100,11 +151,62 @@ pub enum RedisCommandName {
     Hmget,
     Hmset,
     Hrandfield,
+    Hscan,
+    Hsetnx,
+    Hstrlen,
+    Hexpire,
+    Httl,
+    // Set commands
+    Sadd,
+    Srem,
+    Smembers,
+    Sismember,
+    Scard,
+    Spop,
+    Srandmember,
+    Smove,
+    Sinter,
+    Sinterstore,
+    Sunion,
+    Sunionstore,
+    Sdiff,
+    Sdiffstore,
+    // Sorted set commands
+    Zadd,
+    Zscore,
+    Zrange,
+    Zrem,
+    // Stream commands
+    Xadd,
+    Xlen,
+    Xrange,
+    Xread,
+    Xtrim,
+    Xdel,
+    Xinfo,
+    // Transaction commands
+    Multi,
+    Exec,
+    Discard,
+    Watch,
+    Unwatch,
+    // Scripting commands
+    Eval,
+    Evalsha,
+    Script,
+    Function,
+    Fcall,
+    // Pub-sub commands
+    Subscribe,
+    Unsubscribe,
+    Psubscribe,
+    Punsubscribe,
+    Publish,
     NotSupported(String),
 }
 
 pub struct CommandsManager {
-    cmds: HashMap<&'static str, CommandMetadata>,
+    cmds: HashMap<String, CommandMetadata>,
 }
 
 impl CommandsManager {
@@ -144,10 +246,64 @@ impl CommandsManager {
         buffer
     }
 
+    /// Return the `COMMAND INFO` output for `names`. Unknown commands are reported as null
+    pub fn command_info_output(&self, names: &[&BytesMut]) -> BytesMut {
+        let builder = crate::RespBuilderV2::default();
+        let mut buffer = BytesMut::with_capacity(1024);
+
+        builder.add_array_len(&mut buffer, names.len());
+        for name in names {
+            let name = String::from_utf8_lossy(name).to_lowercase();
+            match self.cmds.get(&name) {
+                Some(cmd_md) => builder.add_resp_string(&mut buffer, &cmd_md.to_resp_v2()),
+                None => builder.add_null_string(&mut buffer),
+            }
+        }
+        buffer
+    }
+
     /// Return the commands table
-    pub fn all_commands(&self) -> &HashMap<&'static str, CommandMetadata> {
+    pub fn all_commands(&self) -> &HashMap<String, CommandMetadata> {
         &self.cmds
     }
+
+    /// Rename command `name` to `new_name`. An empty `new_name` disables the command
+    /// completely. After the rename, the command is no longer reachable by its
+    /// original name
+    pub fn rename_command(&mut self, name: &str, new_name: &str) -> Result<(), SableError> {
+        let name = name.to_lowercase();
+        let new_name = new_name.to_lowercase();
+        let Some(cmd_md) = self.cmds.remove(&name) else {
+            return Err(SableError::InvalidArgument(format!(
+                "can not rename unknown command `{}`",
+                name
+            )));
+        };
+
+        if new_name.is_empty() {
+            tracing::info!("Command `{}` is disabled", name);
+            return Ok(());
+        }
+
+        if self.cmds.contains_key(&new_name) {
+            // restore the original entry before failing
+            self.cmds.insert(name.clone(), cmd_md);
+            return Err(SableError::AlreadyExists);
+        }
+
+        tracing::info!("Command `{}` renamed to `{}`", name, new_name);
+        self.cmds.insert(new_name, cmd_md);
+        Ok(())
+    }
+
+    /// Apply the `(name, new_name)` renames in order: a rename may refer to the new name
+    /// given by a previous one
+    pub fn rename_commands(&mut self, renames: &[(String, String)]) -> Result<(), SableError> {
+        for (name, new_name) in renames {
+            self.rename_command(name, new_name)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -207,6 +363,16 @@ impl CommandMetadata {
         self
     }
 
+    /// Return true if `arg_count` (which includes the command name) satisfies this command's arity
+    pub fn arity_matches(&self, arg_count: usize) -> bool {
+        let arity = self.arity.unsigned_abs() as usize;
+        if self.arity < 0 {
+            arg_count >= arity
+        } else {
+            arg_count == arity
+        }
+    }
+
     /// This command might block the client
     pub fn blocking(mut self) -> Self {
         self.set_flag(RedisCommandFlags::Blocking);
@@ -237,10 +403,62 @@ impl CommandMetadata {
         self
     }
 
+    /// This command falls under the @pubsub category
+    pub fn pubsub(mut self) -> Self {
+        self.set_flag(RedisCommandFlags::PubSub);
+        self
+    }
+
+    /// This command falls under the @scripting category
+    pub fn scripting(mut self) -> Self {
+        self.set_flag(RedisCommandFlags::Scripting);
+        self
+    }
+
+    /// This command can not be called from a script
+    pub fn noscript(mut self) -> Self {
+        self.set_flag(RedisCommandFlags::NoScript);
+        self
+    }
+
+    /// Can this command be called from a script (`redis.call`)?
+    pub fn allowed_from_script(&self) -> bool {
+        !self.has_flag(RedisCommandFlags::Scripting) && !self.has_flag(RedisCommandFlags::NoScript)
+    }
+
     pub fn name(&self) -> &RedisCommandName {
         &self.cmd_name
     }
 
+    /// Return the positions of the keys in a command with `arg_count` arguments (including the
+    /// command name), based on the command's first key, last key and step. A negative last key
+    /// is counted from the end of the command (`-1` is the last argument)
+    pub fn key_positions(&self, arg_count: usize) -> Vec<usize> {
+        if self.first_key <= 0 || self.step == 0 {
+            return Vec::default();
+        }
+
+        let last_key = if self.last_key < 0 {
+            arg_count as i64 + self.last_key as i64
+        } else {
+            self.last_key as i64
+        };
+
+        let last_key = last_key.min(arg_count as i64 - 1);
+        if last_key < self.first_key as i64 {
+            return Vec::default();
+        }
+
+        (self.first_key as usize..=last_key as usize)
+            .step_by(self.step as usize)
+            .collect()
+    }
+
+    /// Does this command belong to the `flag` category (e.g. `@read`)?
+    pub fn in_category(&self, flag: &RedisCommandFlags) -> bool {
+        self.has_flag(flag.clone())
+    }
+
     /// Is this command a "Write" command?
     pub fn is_write_command(&self) -> bool {
         self.cmd_flags & RedisCommandFlags::Write as u64 == RedisCommandFlags::Write as u64
@@ -266,8 +484,18 @@ impl CommandMetadata {
         if self.has_flag(RedisCommandFlags::Connection) {
             flags.push("connection");
         }
+        if self.has_flag(RedisCommandFlags::PubSub) {
+            flags.push("pubsub");
+        }
+        if !self.allowed_from_script() {
+            flags.push("noscript");
+        }
 
-        let cmdname = BytesMut::from(format!("{:?}", self.cmd_name).to_lowercase().as_str());
+        let cmdname = match self.cmd_name {
+            // the variant name can not hold the underscore
+            RedisCommandName::SortRo => BytesMut::from("sort_ro"),
+            _ => BytesMut::from(format!("{:?}", self.cmd_name).to_lowercase().as_str()),
+        };
 
         // convert this object into RESP
         builder.add_array_len(&mut buffer, 10);
@@ -299,7 +527,7 @@ impl Default for CommandsManager {
         CommandsManager {
             cmds: HashMap::from([
                 (
-                    "config",
+                    "config".to_string(),
                     CommandMetadata::new(RedisCommandName::Config)
                         .read_only()
                         .with_arity(-2)
@@ -308,7 +536,7 @@ impl Default for CommandsManager {
                         .with_step(0),
                 ),
                 (
-                    "info",
+                    "info".to_string(),
                     CommandMetadata::new(RedisCommandName::Info)
                         .read_only()
                         .with_arity(-1)
@@ -318,93 +546,93 @@ impl Default for CommandsManager {
                 ),
                 // string commands
                 (
-                    "append",
+                    "append".to_string(),
                     CommandMetadata::new(RedisCommandName::Append)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "decr",
+                    "decr".to_string(),
                     CommandMetadata::new(RedisCommandName::Decr)
                         .write()
                         .with_arity(2),
                 ),
                 (
-                    "decrby",
+                    "decrby".to_string(),
                     CommandMetadata::new(RedisCommandName::DecrBy)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "incr",
+                    "incr".to_string(),
                     CommandMetadata::new(RedisCommandName::Incr)
                         .write()
                         .with_arity(2),
                 ),
                 (
-                    "incrby",
+                    "incrby".to_string(),
                     CommandMetadata::new(RedisCommandName::IncrBy)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "incrbyfloat",
+                    "incrbyfloat".to_string(),
                     CommandMetadata::new(RedisCommandName::IncrByFloat)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "set",
+                    "set".to_string(),
                     CommandMetadata::new(RedisCommandName::Set)
                         .write()
-                        .with_arity(3),
+                        .with_arity(-3),
                 ),
                 (
-                    "get",
+                    "get".to_string(),
                     CommandMetadata::new(RedisCommandName::Get)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "getdel",
+                    "getdel".to_string(),
                     CommandMetadata::new(RedisCommandName::GetDel)
                         .write()
                         .with_arity(2),
                 ),
                 (
-                    "getset",
+                    "getset".to_string(),
                     CommandMetadata::new(RedisCommandName::GetSet)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "getex",
+                    "getex".to_string(),
                     CommandMetadata::new(RedisCommandName::GetEx)
                         .write()
                         .with_arity(-2),
                 ),
                 (
-                    "getrange",
+                    "getrange".to_string(),
                     CommandMetadata::new(RedisCommandName::GetRange)
                         .read_only()
                         .with_arity(4),
                 ),
                 (
-                    "lcs",
+                    "lcs".to_string(),
                     CommandMetadata::new(RedisCommandName::Lcs)
                         .read_only()
                         .with_arity(-3)
                         .with_last_key(2),
                 ),
                 (
-                    "mget",
+                    "mget".to_string(),
                     CommandMetadata::new(RedisCommandName::Mget)
                         .read_only()
                         .with_arity(-2)
                         .with_last_key(-1),
                 ),
                 (
-                    "mset",
+                    "mset".to_string(),
                     CommandMetadata::new(RedisCommandName::Mset)
                         .write()
                         .with_arity(-3)
@@ -412,7 +640,7 @@ impl Default for CommandsManager {
                         .with_step(2),
                 ),
                 (
-                    "msetnx",
+                    "msetnx".to_string(),
                     CommandMetadata::new(RedisCommandName::Msetnx)
                         .write()
                         .with_arity(-3)
@@ -420,142 +648,161 @@ impl Default for CommandsManager {
                         .with_step(2),
                 ),
                 (
-                    "psetex",
+                    "psetex".to_string(),
                     CommandMetadata::new(RedisCommandName::Psetex)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "setex",
+                    "setex".to_string(),
                     CommandMetadata::new(RedisCommandName::Setex)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "setnx",
+                    "setnx".to_string(),
                     CommandMetadata::new(RedisCommandName::Setnx)
                         .write()
                         .with_arity(3),
                 ),
                 (
-                    "setrange",
+                    "setrange".to_string(),
                     CommandMetadata::new(RedisCommandName::SetRange)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "strlen",
+                    "strlen".to_string(),
                     CommandMetadata::new(RedisCommandName::Strlen)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "substr",
+                    "substr".to_string(),
                     CommandMetadata::new(RedisCommandName::Substr)
                         .read_only()
                         .with_arity(4),
                 ),
+                // bitmap commands
+                (
+                    "setbit".to_string(),
+                    CommandMetadata::new(RedisCommandName::Setbit)
+                        .write()
+                        .with_arity(4),
+                ),
+                (
+                    "getbit".to_string(),
+                    CommandMetadata::new(RedisCommandName::Getbit)
+                        .read_only()
+                        .with_arity(3),
+                ),
+                (
+                    "bitcount".to_string(),
+                    CommandMetadata::new(RedisCommandName::Bitcount)
+                        .read_only()
+                        .with_arity(-2),
+                ),
                 // list commands
                 (
-                    "lpush",
+                    "lpush".to_string(),
                     CommandMetadata::new(RedisCommandName::Lpush)
                         .write()
                         .with_arity(-3),
                 ),
                 (
-                    "lpushx",
+                    "lpushx".to_string(),
                     CommandMetadata::new(RedisCommandName::Lpushx)
                         .write()
                         .with_arity(-3),
                 ),
                 (
-                    "rpush",
+                    "rpush".to_string(),
                     CommandMetadata::new(RedisCommandName::Rpush)
                         .write()
                         .with_arity(-3),
                 ),
                 (
-                    "rpushx",
+                    "rpushx".to_string(),
                     CommandMetadata::new(RedisCommandName::Rpushx)
                         .write()
                         .with_arity(-3),
                 ),
                 (
-                    "lpop",
+                    "lpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Lpop)
                         .write()
                         .with_arity(-2),
                 ),
                 (
-                    "rpop",
+                    "rpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Rpop)
                         .write()
                         .with_arity(-2),
                 ),
                 (
-                    "llen",
+                    "llen".to_string(),
                     CommandMetadata::new(RedisCommandName::Llen)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "lindex",
+                    "lindex".to_string(),
                     CommandMetadata::new(RedisCommandName::Lindex)
                         .read_only()
-                        .with_arity(4),
+                        .with_arity(3),
                 ),
                 (
-                    "linsert",
+                    "linsert".to_string(),
                     CommandMetadata::new(RedisCommandName::Linsert)
                         .write()
                         .with_arity(5),
                 ),
                 (
-                    "lset",
+                    "lset".to_string(),
                     CommandMetadata::new(RedisCommandName::Lset)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "lpos",
+                    "lpos".to_string(),
                     CommandMetadata::new(RedisCommandName::Lpos)
                         .read_only()
                         .with_arity(-3),
                 ),
                 (
-                    "ltrim",
+                    "ltrim".to_string(),
                     CommandMetadata::new(RedisCommandName::Ltrim)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "lrange",
+                    "lrange".to_string(),
                     CommandMetadata::new(RedisCommandName::Lrange)
                         .read_only()
                         .with_arity(4),
                 ),
                 (
-                    "lrem",
+                    "lrem".to_string(),
                     CommandMetadata::new(RedisCommandName::Lrem)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "lmove",
+                    "lmove".to_string(),
                     CommandMetadata::new(RedisCommandName::Lmove)
                         .write()
                         .with_arity(5)
                         .with_last_key(2),
                 ),
                 (
-                    "rpoplpush",
+                    "rpoplpush".to_string(),
                     CommandMetadata::new(RedisCommandName::Rpoplpush)
                         .write()
                         .with_arity(3)
                         .with_last_key(2),
                 ),
                 (
-                    "lmpop",
+                    "lmpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Lmpop)
                         .write()
                         .with_arity(-4)
@@ -564,7 +811,7 @@ impl Default for CommandsManager {
                         .with_step(0),
                 ),
                 (
-                    "brpoplpush",
+                    "brpoplpush".to_string(),
                     CommandMetadata::new(RedisCommandName::Brpoplpush)
                         .write()
                         .blocking()
@@ -572,7 +819,7 @@ impl Default for CommandsManager {
                         .with_last_key(2),
                 ),
                 (
-                    "blpop",
+                    "blpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Blpop)
                         .write()
                         .blocking()
@@ -580,7 +827,7 @@ impl Default for CommandsManager {
                         .with_last_key(-2),
                 ),
                 (
-                    "blmove",
+                    "blmove".to_string(),
                     CommandMetadata::new(RedisCommandName::Blmove)
                         .write()
                         .blocking()
@@ -588,7 +835,7 @@ impl Default for CommandsManager {
                         .with_last_key(2),
                 ),
                 (
-                    "blmpop",
+                    "blmpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Blmpop)
                         .write()
                         .blocking()
@@ -598,7 +845,7 @@ impl Default for CommandsManager {
                         .with_step(0),
                 ),
                 (
-                    "brpop",
+                    "brpop".to_string(),
                     CommandMetadata::new(RedisCommandName::Brpop)
                         .write()
                         .blocking()
@@ -607,11 +854,32 @@ impl Default for CommandsManager {
                 ),
                 // Client commands
                 (
-                    "client",
-                    CommandMetadata::new(RedisCommandName::Client).connection(),
+                    "client".to_string(),
+                    CommandMetadata::new(RedisCommandName::Client)
+                        .connection()
+                        .noscript()
+                        .with_arity(-2),
+                ),
+                (
+                    "hello".to_string(),
+                    CommandMetadata::new(RedisCommandName::Hello)
+                        .connection()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
                 ),
                 (
-                    "select",
+                    "auth".to_string(),
+                    CommandMetadata::new(RedisCommandName::Auth)
+                        .connection()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "select".to_string(),
                     CommandMetadata::new(RedisCommandName::Select)
                         .connection()
                         .with_arity(2)
@@ -619,27 +887,49 @@ impl Default for CommandsManager {
                         .with_last_key(0)
                         .with_step(0),
                 ),
+                (
+                    "quit".to_string(),
+                    CommandMetadata::new(RedisCommandName::Quit)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "reset".to_string(),
+                    CommandMetadata::new(RedisCommandName::Reset)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
                 // Server commands
                 (
-                    "replicaof",
+                    "replicaof".to_string(),
                     CommandMetadata::new(RedisCommandName::ReplicaOf)
                         .admin()
+                        .noscript()
                         .with_arity(3)
                         .with_first_key(0)
                         .with_last_key(0)
                         .with_step(0),
                 ),
                 (
-                    "slaveof",
+                    "slaveof".to_string(),
                     CommandMetadata::new(RedisCommandName::SlaveOf)
                         .admin()
+                        .noscript()
                         .with_arity(3)
                         .with_first_key(0)
                         .with_last_key(0)
                         .with_step(0),
                 ),
                 (
-                    "ping",
+                    "ping".to_string(),
                     CommandMetadata::new(RedisCommandName::Ping)
                         .read_only()
                         .with_arity(-1)
@@ -648,120 +938,767 @@ impl Default for CommandsManager {
                         .with_step(0),
                 ),
                 (
-                    "command",
+                    "command".to_string(),
                     CommandMetadata::new(RedisCommandName::Command)
                         .with_arity(-1)
                         .with_first_key(0)
                         .with_last_key(0)
                         .with_step(0),
                 ),
+                (
+                    "debug".to_string(),
+                    CommandMetadata::new(RedisCommandName::Debug)
+                        .admin()
+                        .noscript()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "wait".to_string(),
+                    CommandMetadata::new(RedisCommandName::Wait)
+                        .with_arity(3)
+                        .noscript()
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "slowlog".to_string(),
+                    CommandMetadata::new(RedisCommandName::Slowlog)
+                        .admin()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "failover".to_string(),
+                    CommandMetadata::new(RedisCommandName::Failover)
+                        .admin()
+                        .noscript()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "acl".to_string(),
+                    CommandMetadata::new(RedisCommandName::Acl)
+                        .admin()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "save".to_string(),
+                    CommandMetadata::new(RedisCommandName::Save)
+                        .admin()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "bgsave".to_string(),
+                    CommandMetadata::new(RedisCommandName::Bgsave)
+                        .admin()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "lastsave".to_string(),
+                    CommandMetadata::new(RedisCommandName::Lastsave)
+                        .admin()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "sable.cdc".to_string(),
+                    CommandMetadata::new(RedisCommandName::SableCdc)
+                        .admin()
+                        .noscript()
+                        .with_arity(2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
                 // generic commands
                 (
-                    "ttl",
+                    "ttl".to_string(),
                     CommandMetadata::new(RedisCommandName::Ttl)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "del",
+                    "pttl".to_string(),
+                    CommandMetadata::new(RedisCommandName::Pttl)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "persist".to_string(),
+                    CommandMetadata::new(RedisCommandName::Persist)
+                        .write()
+                        .with_arity(2),
+                ),
+                (
+                    "type".to_string(),
+                    CommandMetadata::new(RedisCommandName::Type)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "randomkey".to_string(),
+                    CommandMetadata::new(RedisCommandName::Randomkey)
+                        .read_only()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "dbsize".to_string(),
+                    CommandMetadata::new(RedisCommandName::Dbsize)
+                        .read_only()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "flushdb".to_string(),
+                    CommandMetadata::new(RedisCommandName::Flushdb)
+                        .write()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "flushall".to_string(),
+                    CommandMetadata::new(RedisCommandName::Flushall)
+                        .write()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "touch".to_string(),
+                    CommandMetadata::new(RedisCommandName::Touch)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "rename".to_string(),
+                    CommandMetadata::new(RedisCommandName::Rename)
+                        .write()
+                        .with_arity(3)
+                        .with_last_key(2),
+                ),
+                (
+                    "renamenx".to_string(),
+                    CommandMetadata::new(RedisCommandName::Renamenx)
+                        .write()
+                        .with_arity(3)
+                        .with_last_key(2),
+                ),
+                (
+                    "dump".to_string(),
+                    CommandMetadata::new(RedisCommandName::Dump)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "restore".to_string(),
+                    CommandMetadata::new(RedisCommandName::Restore)
+                        .write()
+                        .with_arity(-4),
+                ),
+                (
+                    "migrate".to_string(),
+                    CommandMetadata::new(RedisCommandName::Migrate)
+                        .write()
+                        .noscript()
+                        .with_arity(-6)
+                        .with_first_key(3)
+                        .with_last_key(3),
+                ),
+                (
+                    "move".to_string(),
+                    CommandMetadata::new(RedisCommandName::Move)
+                        .write()
+                        .with_arity(3),
+                ),
+                (
+                    "object".to_string(),
+                    CommandMetadata::new(RedisCommandName::Object)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_first_key(2)
+                        .with_last_key(2),
+                ),
+                (
+                    "sort".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sort)
+                        .read_only()
+                        .with_arity(-2),
+                ),
+                (
+                    "sort_ro".to_string(),
+                    CommandMetadata::new(RedisCommandName::SortRo)
+                        .read_only()
+                        .with_arity(-2),
+                ),
+                (
+                    "memory".to_string(),
+                    CommandMetadata::new(RedisCommandName::Memory)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_first_key(2)
+                        .with_last_key(2),
+                ),
+                (
+                    "scan".to_string(),
+                    CommandMetadata::new(RedisCommandName::Scan)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "del".to_string(),
                     CommandMetadata::new(RedisCommandName::Del)
                         .write()
                         .with_arity(-2)
                         .with_last_key(-1),
                 ),
                 (
-                    "exists",
+                    "unlink".to_string(),
+                    CommandMetadata::new(RedisCommandName::Unlink)
+                        .write()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "exists".to_string(),
                     CommandMetadata::new(RedisCommandName::Exists)
                         .read_only()
                         .with_arity(-2)
                         .with_last_key(-1),
                 ),
                 (
-                    "expire",
+                    "expire".to_string(),
                     CommandMetadata::new(RedisCommandName::Expire)
                         .write()
                         .with_arity(-3),
                 ),
+                (
+                    "pexpire".to_string(),
+                    CommandMetadata::new(RedisCommandName::Pexpire)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "expireat".to_string(),
+                    CommandMetadata::new(RedisCommandName::Expireat)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "pexpireat".to_string(),
+                    CommandMetadata::new(RedisCommandName::Pexpireat)
+                        .write()
+                        .with_arity(-3),
+                ),
                 // Hash commands
                 (
-                    "hset",
+                    "hset".to_string(),
                     CommandMetadata::new(RedisCommandName::Hset)
                         .write()
                         .with_arity(-4),
                 ),
                 (
-                    "hmset",
+                    "hmset".to_string(),
                     CommandMetadata::new(RedisCommandName::Hmset)
                         .write()
                         .with_arity(-4),
                 ),
                 (
-                    "hget",
+                    "hget".to_string(),
                     CommandMetadata::new(RedisCommandName::Hget)
                         .read_only()
                         .with_arity(3),
                 ),
                 (
-                    "hdel",
+                    "hdel".to_string(),
                     CommandMetadata::new(RedisCommandName::Hdel)
                         .write()
                         .with_arity(-3),
                 ),
                 (
-                    "hlen",
+                    "hlen".to_string(),
                     CommandMetadata::new(RedisCommandName::Hlen)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "hexists",
+                    "hexists".to_string(),
                     CommandMetadata::new(RedisCommandName::Hexists)
                         .read_only()
                         .with_arity(3),
                 ),
                 (
-                    "hgetall",
+                    "hgetall".to_string(),
                     CommandMetadata::new(RedisCommandName::Hgetall)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "hincrbyfloat",
+                    "hincrbyfloat".to_string(),
                     CommandMetadata::new(RedisCommandName::Hincrbyfloat)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "hincrby",
+                    "hincrby".to_string(),
                     CommandMetadata::new(RedisCommandName::Hincrby)
                         .write()
                         .with_arity(4),
                 ),
                 (
-                    "hkeys",
+                    "hkeys".to_string(),
                     CommandMetadata::new(RedisCommandName::Hkeys)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "hvals",
+                    "hvals".to_string(),
                     CommandMetadata::new(RedisCommandName::Hvals)
                         .read_only()
                         .with_arity(2),
                 ),
                 (
-                    "hmget",
+                    "hmget".to_string(),
                     CommandMetadata::new(RedisCommandName::Hmget)
                         .read_only()
                         .with_arity(-3),
                 ),
                 (
-                    "hrandfield",
+                    "hrandfield".to_string(),
                     CommandMetadata::new(RedisCommandName::Hrandfield)
                         .read_only()
                         .with_arity(-2),
                 ),
+                (
+                    "hscan".to_string(),
+                    CommandMetadata::new(RedisCommandName::Hscan)
+                        .read_only()
+                        .with_arity(-3),
+                ),
+                (
+                    "hsetnx".to_string(),
+                    CommandMetadata::new(RedisCommandName::Hsetnx)
+                        .write()
+                        .with_arity(4),
+                ),
+                (
+                    "hstrlen".to_string(),
+                    CommandMetadata::new(RedisCommandName::Hstrlen)
+                        .read_only()
+                        .with_arity(3),
+                ),
+                (
+                    "hexpire".to_string(),
+                    CommandMetadata::new(RedisCommandName::Hexpire)
+                        .write()
+                        .with_arity(-6),
+                ),
+                (
+                    "httl".to_string(),
+                    CommandMetadata::new(RedisCommandName::Httl)
+                        .read_only()
+                        .with_arity(-5),
+                ),
+                (
+                    "sadd".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sadd)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "srem".to_string(),
+                    CommandMetadata::new(RedisCommandName::Srem)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "smembers".to_string(),
+                    CommandMetadata::new(RedisCommandName::Smembers)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "sismember".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sismember)
+                        .read_only()
+                        .with_arity(3),
+                ),
+                (
+                    "scard".to_string(),
+                    CommandMetadata::new(RedisCommandName::Scard)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "spop".to_string(),
+                    CommandMetadata::new(RedisCommandName::Spop)
+                        .write()
+                        .with_arity(-2),
+                ),
+                (
+                    "srandmember".to_string(),
+                    CommandMetadata::new(RedisCommandName::Srandmember)
+                        .read_only()
+                        .with_arity(-2),
+                ),
+                (
+                    "smove".to_string(),
+                    CommandMetadata::new(RedisCommandName::Smove)
+                        .write()
+                        .with_arity(4)
+                        .with_last_key(2),
+                ),
+                (
+                    "sinter".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sinter)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "sinterstore".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sinterstore)
+                        .write()
+                        .with_arity(-3)
+                        .with_last_key(-1),
+                ),
+                (
+                    "sunion".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sunion)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "sunionstore".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sunionstore)
+                        .write()
+                        .with_arity(-3)
+                        .with_last_key(-1),
+                ),
+                (
+                    "sdiff".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sdiff)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "sdiffstore".to_string(),
+                    CommandMetadata::new(RedisCommandName::Sdiffstore)
+                        .write()
+                        .with_arity(-3)
+                        .with_last_key(-1),
+                ),
+                (
+                    "zadd".to_string(),
+                    CommandMetadata::new(RedisCommandName::Zadd)
+                        .write()
+                        .with_arity(-4),
+                ),
+                (
+                    "zscore".to_string(),
+                    CommandMetadata::new(RedisCommandName::Zscore)
+                        .read_only()
+                        .with_arity(3),
+                ),
+                (
+                    "zrange".to_string(),
+                    CommandMetadata::new(RedisCommandName::Zrange)
+                        .read_only()
+                        .with_arity(-4),
+                ),
+                (
+                    "zrem".to_string(),
+                    CommandMetadata::new(RedisCommandName::Zrem)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "xadd".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xadd)
+                        .write()
+                        .with_arity(-5),
+                ),
+                (
+                    "xlen".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xlen)
+                        .read_only()
+                        .with_arity(2),
+                ),
+                (
+                    "xrange".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xrange)
+                        .read_only()
+                        .with_arity(-4),
+                ),
+                (
+                    "xtrim".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xtrim)
+                        .write()
+                        .with_arity(-4),
+                ),
+                (
+                    "xdel".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xdel)
+                        .write()
+                        .with_arity(-3),
+                ),
+                (
+                    "xinfo".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xinfo)
+                        .read_only()
+                        .with_arity(-2)
+                        .with_first_key(2)
+                        .with_last_key(2),
+                ),
+                (
+                    "xread".to_string(),
+                    CommandMetadata::new(RedisCommandName::Xread)
+                        .read_only()
+                        .blocking()
+                        .with_arity(-4)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "multi".to_string(),
+                    CommandMetadata::new(RedisCommandName::Multi)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "exec".to_string(),
+                    CommandMetadata::new(RedisCommandName::Exec)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "discard".to_string(),
+                    CommandMetadata::new(RedisCommandName::Discard)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "watch".to_string(),
+                    CommandMetadata::new(RedisCommandName::Watch)
+                        .connection()
+                        .noscript()
+                        .with_arity(-2)
+                        .with_last_key(-1),
+                ),
+                (
+                    "unwatch".to_string(),
+                    CommandMetadata::new(RedisCommandName::Unwatch)
+                        .connection()
+                        .noscript()
+                        .with_arity(1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "eval".to_string(),
+                    CommandMetadata::new(RedisCommandName::Eval)
+                        .write()
+                        .scripting()
+                        .with_arity(-3)
+                        .with_first_key(0),
+                ),
+                (
+                    "evalsha".to_string(),
+                    CommandMetadata::new(RedisCommandName::Evalsha)
+                        .write()
+                        .scripting()
+                        .with_arity(-3)
+                        .with_first_key(0),
+                ),
+                (
+                    "script".to_string(),
+                    CommandMetadata::new(RedisCommandName::Script)
+                        .scripting()
+                        .with_arity(-2)
+                        .with_first_key(0),
+                ),
+                (
+                    "function".to_string(),
+                    CommandMetadata::new(RedisCommandName::Function)
+                        .write()
+                        .scripting()
+                        .with_arity(-2)
+                        .with_first_key(0),
+                ),
+                (
+                    "fcall".to_string(),
+                    CommandMetadata::new(RedisCommandName::Fcall)
+                        .write()
+                        .scripting()
+                        .with_arity(-3)
+                        .with_first_key(0),
+                ),
+                (
+                    "subscribe".to_string(),
+                    CommandMetadata::new(RedisCommandName::Subscribe)
+                        .pubsub()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "unsubscribe".to_string(),
+                    CommandMetadata::new(RedisCommandName::Unsubscribe)
+                        .pubsub()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "psubscribe".to_string(),
+                    CommandMetadata::new(RedisCommandName::Psubscribe)
+                        .pubsub()
+                        .with_arity(-2)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "punsubscribe".to_string(),
+                    CommandMetadata::new(RedisCommandName::Punsubscribe)
+                        .pubsub()
+                        .with_arity(-1)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
+                (
+                    "publish".to_string(),
+                    CommandMetadata::new(RedisCommandName::Publish)
+                        .pubsub()
+                        .with_arity(3)
+                        .with_first_key(0)
+                        .with_last_key(0)
+                        .with_step(0),
+                ),
             ]),
         }
     }
 }
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_command() -> Result<(), SableError> {
+        let mut manager = CommandsManager::default();
+        manager.rename_command("REPLICAOF", "my_secret_replicaof")?;
+
+        // the command responds under its new name
+        let md = manager.metadata("my_secret_replicaof");
+        assert!(matches!(md.name(), RedisCommandName::ReplicaOf));
+
+        // and is no longer known by its old name
+        let md = manager.metadata("replicaof");
+        assert!(matches!(md.name(), RedisCommandName::NotSupported(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_command_chain() -> Result<(), SableError> {
+        let mut manager = CommandsManager::default();
+        manager.rename_commands(&[
+            ("replicaof".to_string(), "b".to_string()),
+            ("b".to_string(), "c".to_string()),
+        ])?;
+
+        assert!(matches!(
+            manager.metadata("c").name(),
+            RedisCommandName::ReplicaOf
+        ));
+        for name in ["replicaof", "b"] {
+            assert!(matches!(
+                manager.metadata(name).name(),
+                RedisCommandName::NotSupported(_)
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_disable_command() -> Result<(), SableError> {
+        let mut manager = CommandsManager::default();
+        manager.rename_command("slaveof", "")?;
+
+        let md = manager.metadata("slaveof");
+        assert!(matches!(md.name(), RedisCommandName::NotSupported(_)));
+        assert!(!manager.all_commands().contains_key("slaveof"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_command_errors() {
+        let mut manager = CommandsManager::default();
+        assert!(manager.rename_command("no_such_command", "abc").is_err());
+
+        // renaming into an existing command is not allowed, and the original command is kept
+        assert!(manager.rename_command("get", "set").is_err());
+        assert!(matches!(
+            manager.metadata("get").name(),
+            RedisCommandName::Get
+        ));
+    }
+}
@@ -4,7 +4,10 @@ pub struct ErrorStrings {}
 impl ErrorStrings {
     pub const VALUE_NOT_AN_INT_OR_OUT_OF_RANGE: &'static str =
         "ERR value is not an integer or out of range";
+    pub const VALUE_MUST_BE_POSITIVE: &'static str = "ERR value is out of range, must be positive";
+    pub const VALUE_OUT_OF_RANGE: &'static str = "ERR value is out of range";
     pub const VALUE_NOT_VALID_FLOAT: &'static str = "ERR value is not a valid float";
+    pub const INCR_NAN_OR_INFINITY: &'static str = "ERR increment would produce NaN or Infinity";
     pub const LCS_FAILED_TO_READ_EXTRA_ARG: &'static str =
         "failed to read extra argument for command 'lcs'";
     pub const LCS_LEN_AND_IDX: &'static str =
@@ -21,5 +24,70 @@ impl ErrorStrings {
     pub const MAXLNE_CANT_BE_NEGATIVE: &'static str = "ERR MAXLEN can't be negative";
     pub const WRITE_CMD_AGAINST_REPLICA: &'static str =
         "READONLY You can't write against a read only replica.";
+    pub const NOAUTH: &'static str = "NOAUTH Authentication required.";
+    pub const WRONGPASS: &'static str =
+        "WRONGPASS invalid username-password pair or user is disabled.";
     pub const INVALID_PRIMARY_PORT: &'static str = "ERR Invalid master port";
+    pub const ZADD_XX_AND_NX: &'static str =
+        "ERR XX and NX options at the same time are not compatible";
+    pub const ZADD_GT_LT_AND_NX: &'static str =
+        "ERR GT, LT, and/or NX options at the same time are not compatible";
+    pub const MULTI_NESTED: &'static str = "ERR MULTI calls can not be nested";
+    pub const EXEC_WITHOUT_MULTI: &'static str = "ERR EXEC without MULTI";
+    pub const DISCARD_WITHOUT_MULTI: &'static str = "ERR DISCARD without MULTI";
+    pub const EXEC_ABORT: &'static str =
+        "EXECABORT Transaction discarded because of previous errors.";
+    pub const WATCH_INSIDE_MULTI: &'static str = "ERR WATCH inside MULTI is not allowed";
+    pub const INVALID_CURSOR: &'static str = "ERR invalid cursor";
+    pub const NO_SUCH_KEY: &'static str = "ERR no such key";
+    pub const BIT_OFFSET_OUT_OF_RANGE: &'static str =
+        "ERR bit offset is not an integer or out of range";
+    pub const BIT_NOT_AN_INT: &'static str = "ERR bit is not an integer or out of range";
+    pub const OFFSET_OUT_OF_RANGE: &'static str = "ERR offset is out of range";
+    pub const STRING_EXCEEDS_MAX_SIZE: &'static str =
+        "ERR string exceeds maximum allowed size (proto-max-bulk-len)";
+    pub const GETEX_INVALID_EXPIRE_TIME: &'static str =
+        "ERR invalid expire time in 'getex' command";
+    pub const HEXPIRE_INVALID_EXPIRE_TIME: &'static str =
+        "ERR invalid expire time in 'hexpire' command";
+    pub const FIELDS_ARG_MISSING: &'static str =
+        "ERR Mandatory argument FIELDS is missing or not at the right position";
+    pub const NUMFIELDS_NOT_POSITIVE: &'static str =
+        "ERR Parameter `numFields` should be greater than 0";
+    pub const NUMFIELDS_MISMATCH: &'static str =
+        "ERR The `numfields` parameter must match the number of arguments";
+    pub const SAME_SOURCE_AND_DESTINATION: &'static str =
+        "ERR source and destination objects are the same";
+    pub const BUSYKEY: &'static str = "BUSYKEY Target key name already exists.";
+    pub const DUMP_PAYLOAD_INVALID: &'static str = "ERR DUMP payload version or checksum are wrong";
+    pub const MIGRATE_KEYS_NEEDS_EMPTY_KEY: &'static str =
+        "ERR When using MIGRATE KEYS option, the key argument must be set to the empty string";
+    pub const MIGRATE_IOERR: &'static str = "IOERR error or timeout talking to target instance";
+    pub const NOSCRIPT: &'static str = "NOSCRIPT No matching script. Please use EVAL.";
+    pub const NEGATIVE_NUMKEYS: &'static str = "ERR Number of keys can't be negative";
+    pub const NUMKEYS_EXCEEDS_ARGS: &'static str =
+        "ERR Number of keys can't be greater than number of args";
+    pub const COMMAND_NOT_ALLOWED_FROM_SCRIPT: &'static str =
+        "ERR This Redis command is not allowed from script";
+    pub const FUNCTION_NOT_FOUND: &'static str = "ERR Function not found";
+    pub const LIBRARY_NOT_FOUND: &'static str = "ERR Library not found";
+    pub const SORT_NOT_A_DOUBLE: &'static str =
+        "ERR One or more scores can't be converted into double";
+    pub const SORT_PATTERNS_NOT_SUPPORTED: &'static str =
+        "ERR SORT BY and GET patterns are not supported";
+    pub const OOM: &'static str = "OOM command not allowed when used memory > 'maxmemory'.";
+    pub const INVALID_STREAM_ID: &'static str =
+        "ERR Invalid stream ID specified as stream command argument";
+    pub const XADD_ID_TOO_SMALL: &'static str =
+        "ERR The ID specified in XADD is equal or smaller than the target stream top item";
+    pub const XADD_ID_IS_ZERO: &'static str =
+        "ERR The ID specified in XADD must be greater than 0-0";
+    pub const XTRIM_MAXLEN_NEGATIVE: &'static str = "ERR The MAXLEN argument must be >= 0.";
+    pub const XTRIM_LIMIT_NEGATIVE: &'static str = "ERR The LIMIT argument must be >= 0.";
+    pub const XTRIM_LIMIT_WITHOUT_APPROX: &'static str =
+        "ERR syntax error, LIMIT cannot be used without the special ~ option";
+    pub const XTRIM_MAXLEN_AND_MINID: &'static str =
+        "ERR syntax error, MAXLEN and MINID options at the same time are not compatible";
+    pub const XREAD_UNBALANCED_STREAMS: &'static str = "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.";
+    pub const LFU_NOT_SELECTED: &'static str = "ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.";
 }
@@ -8,16 +8,22 @@ use crate::{
     metadata::CommonValueMetadata,
     metadata::Encoding,
     parse_string_to_number,
-    storage::GenericDb,
+    replication::{PeerConnection, PeerReply},
+    storage::{DumpDb, GenericDb, RestoreResult},
     types::List,
     BytesMutUtils, Expiration, LockManager, PrimaryKeyMetadata, RedisCommand, RedisCommandName,
     RespBuilderV2, SableError, StorageAdapter, StringUtils, Telemetry, TimeUtils,
 };
 
 use bytes::BytesMut;
+use std::future::Future;
 use std::rc::Rc;
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 
+/// Maximum number of item records removed by `DEL` before yielding back to the scheduler
+const DEL_SWEEP_BUDGET: usize = 1000;
+
 pub struct GenericCommands {}
 
 impl GenericCommands {
@@ -32,14 +38,68 @@ impl GenericCommands {
                 Self::ttl(client_state, command, &mut response_buffer).await?;
             }
             RedisCommandName::Del => {
-                Self::del(client_state, command, &mut response_buffer).await?;
+                Self::del(client_state, command, false, &mut response_buffer).await?;
+            }
+            RedisCommandName::Unlink => {
+                Self::del(client_state, command, true, &mut response_buffer).await?;
             }
             RedisCommandName::Exists => {
                 Self::exists(client_state, command, &mut response_buffer).await?;
             }
+            RedisCommandName::Pttl => {
+                Self::pttl(client_state, command, &mut response_buffer).await?;
+            }
             RedisCommandName::Expire => {
                 Self::expire(client_state, command, &mut response_buffer).await?;
             }
+            RedisCommandName::Pexpire => {
+                Self::pexpire(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Expireat => {
+                Self::expireat(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Pexpireat => {
+                Self::pexpireat(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Type => {
+                Self::key_type(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Persist => {
+                Self::persist(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Randomkey => {
+                Self::random_key(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Dbsize => {
+                Self::dbsize(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Flushdb => {
+                Self::flush(client_state, command, false, &mut response_buffer).await?;
+            }
+            RedisCommandName::Flushall => {
+                Self::flush(client_state, command, true, &mut response_buffer).await?;
+            }
+            RedisCommandName::Move => {
+                Self::move_key(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Touch => {
+                Self::touch(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Rename => {
+                Self::rename(client_state, command, false, &mut response_buffer).await?;
+            }
+            RedisCommandName::Renamenx => {
+                Self::rename(client_state, command, true, &mut response_buffer).await?;
+            }
+            RedisCommandName::Dump => {
+                Self::dump(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Restore => {
+                Self::restore(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Migrate => {
+                Self::migrate(client_state, command, &mut response_buffer).await?;
+            }
             _ => {
                 return Err(SableError::InvalidArgument(format!(
                     "Non generic command {}",
@@ -53,9 +113,13 @@ impl GenericCommands {
     /// O(N) where N is the number of keys that will be removed. When a key to remove holds a value other than a string,
     /// the individual complexity for this key is O(M) where M is the number of elements in the list, set, sorted
     /// set or hash. Removing a single key that holds a string value is O(1).
+    ///
+    /// With `lazy` set (`UNLINK`), only the primary records are removed here (so the keys are gone
+    /// immediately) and reclaiming the items is left to the background `Reclaimer`
     async fn del(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
+        lazy: bool,
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 2, response_buffer);
@@ -65,38 +129,44 @@ impl GenericCommands {
 
         let mut deleted_items = 0usize;
         let db_id = client_state.database_id();
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
         for user_key in iter {
-            // obtain the lock per key
-            let _unused = LockManager::lock_user_key_exclusive(user_key, db_id);
-            let key_type =
-                Self::query_key_type(client_state.clone(), command.clone(), user_key).await?;
-            match key_type {
-                Some(Encoding::VALUE_STRING) => {
-                    let generic_db = GenericDb::with_storage(client_state.database(), db_id);
-                    generic_db.delete(user_key)?;
-                    deleted_items = deleted_items.saturating_add(1);
-                }
-                Some(Encoding::VALUE_LIST) => {
-                    let list = List::with_storage(client_state.database(), db_id);
-                    list.remove(
-                        user_key,
-                        None, // remove all items
-                        i32::MAX,
-                        response_buffer,
-                    )?;
-                    deleted_items = deleted_items.saturating_add(1);
-                }
-                Some(unknown_type) => {
-                    tracing::warn!(
-                        "Deleting unknown type found in database for key `{:?}`. type=`{}`",
-                        user_key,
-                        unknown_type
-                    );
-                    let generic_db = GenericDb::with_storage(client_state.database(), db_id);
-                    generic_db.delete(user_key)?;
-                    deleted_items = deleted_items.saturating_add(1);
+            let items_prefix = {
+                // obtain the lock per key
+                let _unused = LockManager::lock_user_key_exclusive(user_key, db_id);
+                let key_type =
+                    Self::query_key_type(client_state.clone(), command.clone(), user_key).await?;
+                match key_type {
+                    None => continue,
+                    Some(Encoding::VALUE_STRING)
+                    | Some(Encoding::VALUE_LIST)
+                    | Some(Encoding::VALUE_HASH)
+                    | Some(Encoding::VALUE_SET)
+                    | Some(Encoding::VALUE_ZSET)
+                    | Some(Encoding::VALUE_STREAM) => {}
+                    Some(unknown_type) => {
+                        tracing::warn!(
+                            "Deleting unknown type found in database for key `{:?}`. type=`{}`",
+                            user_key,
+                            unknown_type
+                        );
+                    }
                 }
-                _ => {}
+
+                // Removing the primary record makes the key (and all the items it owns)
+                // unreachable, so the items can be swept after the lock is released
+                let items_prefix = generic_db.items_prefix(user_key)?;
+                generic_db.delete(user_key)?;
+                deleted_items = deleted_items.saturating_add(1);
+                items_prefix
+            };
+
+            match items_prefix {
+                Some(items_prefix) if lazy => client_state
+                    .server_inner_state()
+                    .enqueue_reclaim(items_prefix),
+                Some(items_prefix) => Self::sweep_items(&generic_db, &items_prefix).await?,
+                None => {}
             }
         }
 
@@ -105,6 +175,22 @@ impl GenericCommands {
         Ok(())
     }
 
+    /// Delete the items found under `items_prefix`, `DEL_SWEEP_BUDGET` records at a time.
+    /// Between two batches, we yield back to the scheduler so a huge composite key
+    /// does not block the other clients served by this worker
+    async fn sweep_items(
+        generic_db: &GenericDb<'_>,
+        items_prefix: &BytesMut,
+    ) -> Result<(), SableError> {
+        while generic_db.delete_items(items_prefix, DEL_SWEEP_BUDGET)? == DEL_SWEEP_BUDGET {
+            // a running script holds its keys locked, it must not let the other clients in
+            if !LockManager::in_locked_scope() {
+                tokio::task::yield_now().await;
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the remaining time to live of a key that has a timeout.
     /// This introspection capability allows a Redis client to check how
     /// many seconds a given key will continue to be part of the dataset.
@@ -114,27 +200,58 @@ impl GenericCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 2, response_buffer);
+        let Some(expiration) = Self::key_expiration(client_state, command, response_buffer)? else {
+            return Ok(());
+        };
+        let builder = RespBuilderV2::default();
+        builder.number_u64(response_buffer, expiration.ttl_in_seconds()?);
+        Ok(())
+    }
+
+    /// Like `TTL` this command returns the remaining time to live of a key that has an expire set,
+    /// with the sole difference that `TTL` returns the amount of remaining time in seconds while
+    /// `PTTL` returns it in milliseconds
+    async fn pttl(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let Some(expiration) = Self::key_expiration(client_state, command, response_buffer)? else {
+            return Ok(());
+        };
+        let builder = RespBuilderV2::default();
+        builder.number_u64(response_buffer, expiration.ttl_in_millis()?);
+        Ok(())
+    }
+
+    /// Return the expiration of the key found at position `1` of `command`. If the key does not
+    /// exist (`-2`) or has no timeout (`-1`), the reply is written into `response_buffer` and
+    /// `None` is returned
+    fn key_expiration(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<Option<Expiration>, SableError> {
         let builder = RespBuilderV2::default();
         let key = command_arg_at!(command, 1);
 
         let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
         let generic_db =
             GenericDb::with_storage(client_state.database(), client_state.database_id());
-        if let Some((_, value_metadata)) = generic_db.get(key)? {
-            if !value_metadata.expiration().has_ttl() {
+        match generic_db.get_expiration(key)? {
+            Some(expiration) if expiration.has_ttl() => Ok(Some(expiration)),
+            Some(_) => {
                 // No timeout
                 builder.number_i64(response_buffer, -1);
-            } else {
-                builder.number_u64(
-                    response_buffer,
-                    value_metadata.expiration().ttl_in_seconds()?,
-                );
+                Ok(None)
+            }
+            None => {
+                // The command returns -2 if the key does not exist.
+                builder.number_i64(response_buffer, -2);
+                Ok(None)
             }
-        } else {
-            // The command returns -2 if the key does not exist.
-            builder.number_i64(response_buffer, -2);
         }
-        Ok(())
     }
 
     /// Returns if key exists.
@@ -165,124 +282,762 @@ impl GenericCommands {
         Ok(())
     }
 
-    /// Load entry from the database, don't care about the value type
-    async fn query_key_type(
+    /// Remove the existing timeout on key, turning the key from volatile (a key with an expire set)
+    /// to persistent (a key that will never expire as no timeout is associated)
+    async fn persist(
         client_state: Rc<ClientState>,
-        _command: Rc<RedisCommand>,
-        user_key: &BytesMut,
-    ) -> Result<Option<u8>, SableError> {
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let db_id = client_state.database_id();
+        let _unused = LockManager::lock_user_key_exclusive(key, db_id);
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+
+        // 0 if key does not exist or does not have an associated timeout
+        let Some(mut expiration) = generic_db.get_expiration(key)? else {
+            builder.number_usize(response_buffer, 0);
+            return Ok(());
+        };
+
+        if !expiration.has_ttl() {
+            builder.number_usize(response_buffer, 0);
+            return Ok(());
+        }
+
+        expiration.set_no_expiration()?;
+        generic_db.put_expiration(key, &expiration)?;
+        builder.number_usize(response_buffer, 1);
+        Ok(())
+    }
+
+    /// Return a random key from the currently selected database, or nil when the database is
+    /// empty. See `GenericDb::random_key` for the distribution of the returned keys
+    async fn random_key(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 1, response_buffer);
         let generic_db =
             GenericDb::with_storage(client_state.database(), client_state.database_id());
-        let Some((_, md)) = generic_db.get(user_key)? else {
-            return Ok(None);
+
+        let builder = RespBuilderV2::default();
+        match generic_db.random_key()? {
+            Some(user_key) => builder.bulk_string(response_buffer, &user_key),
+            None => builder.null_string(response_buffer),
+        }
+        Ok(())
+    }
+
+    /// Return the number of keys in the currently selected database. Keys that expired but were
+    /// not removed yet are counted
+    async fn dbsize(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        if command.arg_count() != 1 {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'dbsize' command",
+            );
+            return Ok(());
+        }
+
+        let count = client_state
+            .server_inner_state()
+            .key_counter()
+            .dbsize(client_state.database(), client_state.database_id())?;
+        builder.number_u64(response_buffer, count);
+        Ok(())
+    }
+
+    /// `FLUSHDB [ASYNC|SYNC]` and `FLUSHALL [ASYNC|SYNC]`: delete all the keys of the currently
+    /// selected database, or of all the databases.
+    ///
+    /// The keys are removed using RocksDB range deletions, under the lock of all the keys: once
+    /// `OK` is returned the keys are gone, and the keys written afterwards are kept. Each range
+    /// deletion is replicated as a single change, so the replicas flush the same keys. With
+    /// `ASYNC`, the items owned by the keys of a flushed database (hash fields, list items etc)
+    /// are left to the `Reclaimer` instead of being deleted before the reply
+    async fn flush(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        all_databases: bool,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let lazy = match command.arg_count() {
+            1 => false,
+            2 => match command_arg_at_as_str!(command, 1).as_str() {
+                "async" => true,
+                "sync" => false,
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
+                }
+            },
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
         };
-        Ok(Some(md.value_type()))
+
+        let db_id = if all_databases {
+            None
+        } else {
+            Some(client_state.database_id())
+        };
+
+        let store = client_state.database();
+        let _unused = LockManager::lock_all_keys_exclusive();
+        // the SCAN cursors created before the flush are no longer valid
+        client_state.server_inner_state().bump_db_generation(db_id);
+        match db_id {
+            Some(db_id) => {
+                let items_prefixes = GenericDb::with_storage(store, db_id).flush()?;
+                for items_prefix in items_prefixes {
+                    if lazy {
+                        client_state
+                            .server_inner_state()
+                            .enqueue_reclaim(items_prefix);
+                    } else {
+                        store.delete_range(&items_prefix)?;
+                    }
+                }
+            }
+            // a range deletion per record type, whatever the mode
+            None => Self::flush_all_databases(store)?,
+        }
+        builder.ok(response_buffer);
+        Ok(())
     }
 
-    async fn expire(
+    /// Delete all the records of all the databases. The caller must hold the lock of all the keys
+    fn flush_all_databases(store: &StorageAdapter) -> Result<(), SableError> {
+        // every record starts with its type
+        for key_type in [
+            PrimaryKeyMetadata::KEY_PRIMARY,
+            Encoding::KEY_LIST_ITEM,
+            Encoding::KEY_HASH_ITEM,
+            Encoding::KEY_SET_ITEM,
+            Encoding::KEY_ZSET_ITEM,
+            Encoding::KEY_STREAM_ITEM,
+        ] {
+            store.delete_range(&BytesMut::from(&[key_type][..]))?;
+        }
+        Ok(())
+    }
+
+    /// Alters the last access time of a key(s). A key is ignored if it does not exist.
+    /// Returns the number of keys that were touched
+    async fn touch(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
-        // at least 3 arguments
+        check_args_count!(command, 2, response_buffer);
+
+        let user_keys: Vec<&BytesMut> = command.args_vec().iter().skip(1).collect();
+        let db_id = client_state.database_id();
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+
+        // the locks are held until the batch is written
+        let _unused = LockManager::lock_user_keys_shared(&user_keys, db_id);
+        let touched = generic_db.touch(&user_keys)?;
+
+        let builder = RespBuilderV2::default();
+        builder.number_usize(response_buffer, touched);
+        Ok(())
+    }
+
+    /// Renames `key` to `newkey`, keeping its value and its TTL. It returns an error when `key` does
+    /// not exist. If `newkey` already exists it is overwritten (`RENAME`) or left untouched
+    /// (`RENAMENX`, which replies `0` in this case)
+    async fn rename(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        nx: bool,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
         check_args_count!(command, 3, response_buffer);
+        let key = command_arg_at!(command, 1);
+        let new_key = command_arg_at!(command, 2);
+
         let builder = RespBuilderV2::default();
+        let db_id = client_state.database_id();
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+        let items_prefix = {
+            // the locks are sorted internally, so two renames in opposite directions can't deadlock
+            let _unused = LockManager::lock_user_keys_exclusive(&[key, new_key], db_id);
+            if generic_db.get_live(key)?.is_none() {
+                builder.error_string(response_buffer, ErrorStrings::NO_SUCH_KEY);
+                return Ok(());
+            }
 
-        // EXPIRE key seconds [NX | XX | GT | LT]
+            let new_key_exists = key != new_key && generic_db.get_live(new_key)?.is_some();
+            if nx && (new_key_exists || key == new_key) {
+                builder.number_usize(response_buffer, 0);
+                return Ok(());
+            }
+
+            if key == new_key {
+                builder.ok(response_buffer);
+                return Ok(());
+            }
+
+            // the items of the value we are about to replace become unreachable
+            let items_prefix = if new_key_exists {
+                generic_db.items_prefix(new_key)?
+            } else {
+                None
+            };
+            generic_db.rename(key, new_key)?;
+            items_prefix
+        };
+
+        if let Some(items_prefix) = items_prefix {
+            Self::sweep_items(&generic_db, &items_prefix).await?;
+        }
+
+        if nx {
+            builder.number_usize(response_buffer, 1);
+        } else {
+            builder.ok(response_buffer);
+        }
+        Ok(())
+    }
+
+    /// `DUMP key`: serialise the value stored at `key` into an opaque payload that can be loaded
+    /// back with `RESTORE` (on this or another instance). Nil if the key does not exist
+    async fn dump(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let key = command_arg_at!(command, 1);
+
+        let db_id = client_state.database_id();
+        let _unused = LockManager::lock_user_key_shared(key, db_id);
+        let dump_db = DumpDb::with_storage(client_state.database(), db_id);
+
+        let builder = RespBuilderV2::default();
+        match dump_db.dump(key)? {
+            Some(payload) => builder.bulk_string(response_buffer, &payload),
+            None => builder.null_string(response_buffer),
+        }
+        Ok(())
+    }
+
+    /// `RESTORE key ttl serialized-value [REPLACE]`: create `key` from a payload produced by
+    /// `DUMP`. `ttl` is in milliseconds, `0` means no expiration. Without `REPLACE`, an existing
+    /// `key` is an error
+    async fn restore(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
         let key = command_arg_at!(command, 1);
-        let seconds = command_arg_at!(command, 2);
+        let payload = command_arg_at!(command, 3);
 
-        // Convert into seconds
-        let Some(seconds) = BytesMutUtils::parse::<u64>(seconds) else {
+        let builder = RespBuilderV2::default();
+        let Some(ttl_ms) = command.arg_as_number::<i64>(2) else {
             builder.error_string(
                 response_buffer,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             );
             return Ok(());
         };
+        if ttl_ms < 0 {
+            builder.error_string(response_buffer, "ERR Invalid TTL value, must be >= 0");
+            return Ok(());
+        }
+
+        let mut replace = false;
+        for pos in 4..command.arg_count() {
+            match command_arg_at_as_str!(command, pos).as_str() {
+                "replace" => replace = true,
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
+                }
+            }
+        }
+
+        let mut expiration = Expiration::default();
+        if ttl_ms > 0 {
+            expiration.set_ttl_millis(ttl_ms as u64)?;
+        }
 
         let db_id = client_state.database_id();
-        let _unused = LockManager::lock_user_key_exclusive(key, db_id);
         let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+        let items_prefix = {
+            let _unused = LockManager::lock_user_key_exclusive(key, db_id);
+            let key_exists = generic_db.get_live(key)?.is_some();
+            if key_exists && !replace {
+                builder.error_string(response_buffer, ErrorStrings::BUSYKEY);
+                return Ok(());
+            }
 
-        // Make sure the key exists in the database
-        let Some(mut expiration) = generic_db.get_expiration(key)? else {
-            builder.number_usize(response_buffer, 0);
-            return Ok(());
+            // the items of the value we are about to replace become unreachable
+            let items_prefix = if key_exists {
+                generic_db.items_prefix(key)?
+            } else {
+                None
+            };
+
+            let dump_db = DumpDb::with_storage(client_state.database(), db_id);
+            match dump_db.restore(key, payload, &expiration)? {
+                RestoreResult::Restored => builder.ok(response_buffer),
+                RestoreResult::InvalidPayload => {
+                    builder.error_string(response_buffer, ErrorStrings::DUMP_PAYLOAD_INVALID);
+                    return Ok(());
+                }
+            }
+            items_prefix
         };
 
-        // If no other param was provided, set the ttl in seconds and leave
-        let Some(arg) = command.arg(3) else {
-            expiration.set_ttl_seconds(seconds)?;
-            generic_db.put_expiration(key, &expiration)?;
-            builder.number_usize(response_buffer, 1);
+        if let Some(items_prefix) = items_prefix {
+            Self::sweep_items(&generic_db, &items_prefix).await?;
+        }
+        Ok(())
+    }
+
+    /// `MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [KEYS key [key ...]]`:
+    /// move keys to another server. Each key is `DUMP`ed here and `RESTORE`d on the target, the
+    /// local copy is deleted once the target accepted it (unless `COPY` is given). `timeout` is
+    /// the maximum idle time (in milliseconds) of any exchange with the target. On a network error
+    /// or a timeout, the keys that were not migrated are left intact
+    async fn migrate(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 6, response_buffer);
+        let host = BytesMutUtils::to_string(command_arg_at!(command, 1));
+        let key = command_arg_at!(command, 3);
+
+        let builder = RespBuilderV2::default();
+        let (Some(port), Some(target_db_id), Some(timeout_ms)) = (
+            command.arg_as_number::<u16>(2),
+            command.arg_as_number::<u16>(4),
+            command.arg_as_number::<i64>(5),
+        ) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
             return Ok(());
         };
+        // like Redis, a non positive timeout falls back to 1 second
+        let timeout = Duration::from_millis(if timeout_ms <= 0 {
+            1000
+        } else {
+            timeout_ms as u64
+        });
 
-        let arg = BytesMutUtils::to_string(arg).to_lowercase();
-        match arg.as_str() {
-            "nx" => {
-                // NX -- Set expiry only when the key has no expiry
-                if !expiration.has_ttl() {
-                    expiration.set_ttl_seconds(seconds)?;
-                    generic_db.put_expiration(key, &expiration)?;
-                    builder.number_usize(response_buffer, 1);
-                } else {
-                    builder.number_usize(response_buffer, 0);
+        let mut copy = false;
+        let mut replace = false;
+        let mut keys = vec![key.clone()];
+        let mut pos = 6usize;
+        while pos < command.arg_count() {
+            match command_arg_at_as_str!(command, pos).as_str() {
+                "copy" => copy = true,
+                "replace" => replace = true,
+                "keys" => {
+                    if !key.is_empty() {
+                        builder.error_string(
+                            response_buffer,
+                            ErrorStrings::MIGRATE_KEYS_NEEDS_EMPTY_KEY,
+                        );
+                        return Ok(());
+                    }
+                    keys = command.args_vec()[pos + 1..].to_vec();
+                    break;
                 }
-            }
-            "xx" => {
-                // XX -- Set expiry only when the key has an existing expiry
-                if expiration.has_ttl() {
-                    expiration.set_ttl_seconds(seconds)?;
-                    generic_db.put_expiration(key, &expiration)?;
-                    builder.number_usize(response_buffer, 1);
-                } else {
-                    builder.number_usize(response_buffer, 0);
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
                 }
             }
-            "gt" => {
-                // GT -- Set expiry only when the new expiry is greater than current one
-                if seconds > expiration.ttl_in_seconds()? {
-                    expiration.set_ttl_seconds(seconds)?;
-                    generic_db.put_expiration(key, &expiration)?;
-                    builder.number_usize(response_buffer, 1);
-                } else {
-                    builder.number_usize(response_buffer, 0);
+            pos += 1;
+        }
+
+        // serialize the keys that exist, together with their remaining time to live
+        let db_id = client_state.database_id();
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+        let dump_db = DumpDb::with_storage(client_state.database(), db_id);
+        let mut payloads = Vec::<(BytesMut, BytesMut, u64)>::with_capacity(keys.len());
+        for key in keys {
+            let _unused = LockManager::lock_user_key_shared(&key, db_id);
+            let Some(payload) = dump_db.dump(&key)? else {
+                continue;
+            };
+            let ttl_ms = match generic_db.get_expiration(&key)? {
+                Some(expiration) if expiration.has_ttl() => expiration.ttl_in_millis()?.max(1),
+                _ => 0,
+            };
+            payloads.push((key, payload, ttl_ms));
+        }
+
+        if payloads.is_empty() {
+            builder.simple_string(response_buffer, "NOKEY");
+            return Ok(());
+        }
+
+        // the locks are not held while talking to the target
+        let mut migrated = 0usize;
+        let mut error = None;
+        match Self::migrate_io(timeout, PeerConnection::connect(&host, port)).await {
+            None => error = Some(ErrorStrings::MIGRATE_IOERR.to_string()),
+            Some(mut connection) => {
+                let target_db_id = target_db_id.to_string();
+                let mut exchanges = vec![vec![b"SELECT".as_slice(), target_db_id.as_bytes()]];
+                let ttls: Vec<String> =
+                    payloads.iter().map(|(_, _, ttl)| ttl.to_string()).collect();
+                for ((key, payload, _), ttl) in payloads.iter().zip(&ttls) {
+                    let mut restore = vec![b"RESTORE".as_slice(), key, ttl.as_bytes(), payload];
+                    if replace {
+                        restore.push(b"REPLACE");
+                    }
+                    exchanges.push(restore);
                 }
-            }
-            "lt" => {
-                // LT -- Set expiry only when the new expiry is less than current one
-                if seconds < expiration.ttl_in_seconds()? {
-                    expiration.set_ttl_seconds(seconds)?;
-                    generic_db.put_expiration(key, &expiration)?;
-                    builder.number_usize(response_buffer, 1);
-                } else {
-                    builder.number_usize(response_buffer, 0);
+
+                for (index, args) in exchanges.iter().enumerate() {
+                    match Self::migrate_io(timeout, connection.call(args)).await {
+                        None => {
+                            error = Some(ErrorStrings::MIGRATE_IOERR.to_string());
+                            break;
+                        }
+                        Some(PeerReply::Error(msg)) => {
+                            error = Some(format!(
+                                "ERR Target instance replied with error: {}",
+                                BytesMutUtils::to_string(&msg)
+                            ));
+                            break;
+                        }
+                        Some(_) => {
+                            // the first exchange is `SELECT`
+                            migrated = index;
+                        }
+                    }
                 }
             }
-            option => {
-                builder.error_string(
-                    response_buffer,
-                    format!("ERR Unsupported option {}", option).as_str(),
-                );
+        }
+
+        if !copy {
+            for (key, payload, _) in &payloads[..migrated] {
+                let items_prefix = {
+                    let _unused = LockManager::lock_user_key_exclusive(key, db_id);
+                    // the key was changed while we were talking to the target, keep the new value
+                    if dump_db.dump(key)?.as_ref() != Some(payload) {
+                        continue;
+                    }
+                    let items_prefix = generic_db.items_prefix(key)?;
+                    generic_db.delete(key)?;
+                    items_prefix
+                };
+                if let Some(items_prefix) = items_prefix {
+                    Self::sweep_items(&generic_db, &items_prefix).await?;
+                }
             }
         }
+
+        match error {
+            Some(error) => builder.error_string(response_buffer, &error),
+            None => builder.ok(response_buffer),
+        }
         Ok(())
     }
-}
 
-//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
-// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
-// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
-// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
-// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
-//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
-//
+    /// Run a single exchange with the `MIGRATE` target. Return `None` if it failed or did not
+    /// complete within `timeout`
+    async fn migrate_io<T>(
+        timeout: Duration,
+        io: impl Future<Output = Result<T, SableError>>,
+    ) -> Option<T> {
+        match tokio::time::timeout(timeout, io).await {
+            Ok(Ok(result)) => Some(result),
+            Ok(Err(e)) => {
+                tracing::debug!("MIGRATE: {:?}", e);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// `MOVE key db`: move `key` from the currently selected database to database `db`. Return
+    /// `1` if the key was moved, `0` if it does not exist or if `db` already has it
+    async fn move_key(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let key = command_arg_at!(command, 1);
+        let target_db_id = command_arg_at!(command, 2);
+
+        let builder = RespBuilderV2::default();
+        let Some(target_db_id) = BytesMutUtils::parse::<u16>(target_db_id) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+
+        let db_id = client_state.database_id();
+        if db_id == target_db_id {
+            builder.error_string(response_buffer, ErrorStrings::SAME_SOURCE_AND_DESTINATION);
+            return Ok(());
+        }
+
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+        let moved = {
+            let _unused = LockManager::lock_user_key_in_dbs_exclusive(key, &[db_id, target_db_id]);
+            generic_db.move_key(key, target_db_id)?
+        };
+        builder.number_usize(response_buffer, if moved { 1 } else { 0 });
+        Ok(())
+    }
+
+    /// Returns the string representation of the type of the value stored at key.
+    /// The different types that can be returned are: `string`, `list`, `hash`, `set`, `zset`
+    /// and `stream`
+    async fn key_type(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let key = command_arg_at!(command, 1);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let type_name =
+            match Self::query_key_type(client_state.clone(), command.clone(), key).await? {
+                Some(Encoding::VALUE_STRING) => "string",
+                Some(Encoding::VALUE_LIST) => "list",
+                Some(Encoding::VALUE_HASH) => "hash",
+                Some(Encoding::VALUE_SET) => "set",
+                Some(Encoding::VALUE_ZSET) => "zset",
+                Some(Encoding::VALUE_STREAM) => "stream",
+                Some(unknown_type) => {
+                    tracing::warn!(
+                        "Unknown type found in database for key `{:?}`. type=`{}`",
+                        key,
+                        unknown_type
+                    );
+                    "none"
+                }
+                None => "none",
+            };
+
+        let builder = RespBuilderV2::default();
+        builder.simple_string(response_buffer, type_name);
+        Ok(())
+    }
+
+    /// Load entry from the database, don't care about the value type
+    async fn query_key_type(
+        client_state: Rc<ClientState>,
+        _command: Rc<RedisCommand>,
+        user_key: &BytesMut,
+    ) -> Result<Option<u8>, SableError> {
+        let generic_db =
+            GenericDb::with_storage(client_state.database(), client_state.database_id());
+        let Some((_, md)) = generic_db.get(user_key)? else {
+            return Ok(None);
+        };
+        Ok(Some(md.value_type()))
+    }
+
+    async fn expire(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        // at least 3 arguments
+        check_args_count!(command, 3, response_buffer);
+
+        // EXPIRE key seconds [NX | XX | GT | LT]
+        let Some(seconds) = BytesMutUtils::parse::<u64>(command_arg_at!(command, 2)) else {
+            let builder = RespBuilderV2::default();
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+        Self::expire_internal(
+            client_state,
+            command,
+            seconds.saturating_mul(1000),
+            response_buffer,
+        )
+        .await
+    }
+
+    /// This command works exactly like `EXPIRE` but the time to live of the key is specified
+    /// in milliseconds instead of seconds
+    async fn pexpire(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        // at least 3 arguments
+        check_args_count!(command, 3, response_buffer);
+
+        // PEXPIRE key milliseconds [NX | XX | GT | LT]
+        let Some(millis) = BytesMutUtils::parse::<u64>(command_arg_at!(command, 2)) else {
+            let builder = RespBuilderV2::default();
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+        Self::expire_internal(client_state, command, millis, response_buffer).await
+    }
+
+    /// `EXPIREAT` has the same effect and semantic as `EXPIRE`, but instead of specifying the number
+    /// of seconds representing the TTL, it takes an absolute Unix timestamp (seconds since
+    /// January 1, 1970). A timestamp in the past will delete the key immediately
+    async fn expireat(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        // at least 3 arguments
+        check_args_count!(command, 3, response_buffer);
+
+        // EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+        let Some(timestamp_secs) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 2)) else {
+            let builder = RespBuilderV2::default();
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+        let ttl_ms = Self::ttl_until(timestamp_secs.saturating_mul(1000))?;
+        Self::expire_internal(client_state, command, ttl_ms, response_buffer).await
+    }
+
+    /// `PEXPIREAT` has the same effect and semantic as `EXPIREAT`, but the Unix time at which the
+    /// key will expire is specified in milliseconds instead of seconds
+    async fn pexpireat(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        // at least 3 arguments
+        check_args_count!(command, 3, response_buffer);
+
+        // PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+        let Some(timestamp_ms) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 2)) else {
+            let builder = RespBuilderV2::default();
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+        let ttl_ms = Self::ttl_until(timestamp_ms)?;
+        Self::expire_internal(client_state, command, ttl_ms, response_buffer).await
+    }
+
+    /// Return the number of milliseconds left until `timestamp_ms` (millis since UNIX_EPOCH).
+    /// A timestamp in the past yields `0`
+    fn ttl_until(timestamp_ms: i64) -> Result<u64, SableError> {
+        let timestamp_ms = u64::try_from(timestamp_ms).unwrap_or(0);
+        Ok(timestamp_ms.saturating_sub(TimeUtils::epoch_ms()?))
+    }
+
+    /// Set the time to live (in milliseconds) of the key found at position `1` of `command`.
+    /// The optional `NX | XX | GT | LT` condition is expected at position `3`.
+    /// A TTL of `0` deletes the key
+    async fn expire_internal(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        ttl_ms: u64,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let db_id = client_state.database_id();
+        let lock = LockManager::lock_user_key_exclusive(key, db_id);
+        let generic_db = GenericDb::with_storage(client_state.database(), db_id);
+
+        // Make sure the key exists in the database
+        let Some(mut expiration) = generic_db.get_expiration(key)? else {
+            builder.number_usize(response_buffer, 0);
+            return Ok(());
+        };
+
+        let can_update = match command.arg(3) {
+            // If no other param was provided, set the ttl
+            None => true,
+            Some(arg) => {
+                let arg = BytesMutUtils::to_string(arg).to_lowercase();
+                match arg.as_str() {
+                    // NX -- Set expiry only when the key has no expiry
+                    "nx" => !expiration.has_ttl(),
+                    // XX -- Set expiry only when the key has an existing expiry
+                    "xx" => expiration.has_ttl(),
+                    // GT -- Set expiry only when the new expiry is greater than current one
+                    "gt" => ttl_ms > expiration.ttl_in_millis()?,
+                    // LT -- Set expiry only when the new expiry is less than current one
+                    "lt" => ttl_ms < expiration.ttl_in_millis()?,
+                    option => {
+                        builder.error_string(
+                            response_buffer,
+                            format!("ERR Unsupported option {}", option).as_str(),
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        if can_update && ttl_ms == 0 {
+            // The key expires now: delete it (same as `DEL`)
+            let items_prefix = generic_db.items_prefix(key)?;
+            generic_db.delete(key)?;
+            drop(lock);
+            if let Some(items_prefix) = items_prefix {
+                Self::sweep_items(&generic_db, &items_prefix).await?;
+            }
+            builder.number_usize(response_buffer, 1);
+        } else if can_update {
+            expiration.set_ttl_millis(ttl_ms)?;
+            generic_db.put_expiration(key, &expiration)?;
+            builder.number_usize(response_buffer, 1);
+        } else {
+            builder.number_usize(response_buffer, 0);
+        }
+        Ok(())
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{commands::ClientNextAction, Client, ServerState};
+    use crate::{commands::ClientNextAction, storage::PutFlags, Client, ServerState};
     use std::rc::Rc;
     use std::sync::Arc;
     use test_case::test_case;
@@ -298,6 +1053,18 @@ mod test {
         (vec!["llen", "mylist_1"], ":0\r\n"),
         (vec!["llen", "mylist_2"], ":0\r\n"),
         (vec!["del", "mylist_2"], ":0\r\n"),
+        (vec!["hset", "myhash", "f1", "v1", "f2", "v2"], ":2\r\n"),
+        (vec!["del", "myhash"], ":1\r\n"),
+        (vec!["hlen", "myhash"], ":0\r\n"),
+        (vec!["hget", "myhash", "f1"], "$-1\r\n"),
+        (vec!["sadd", "myset", "a", "b"], ":2\r\n"),
+        (vec!["del", "myset"], ":1\r\n"),
+        (vec!["scard", "myset"], ":0\r\n"),
+        (vec!["sismember", "myset", "a"], ":0\r\n"),
+        (vec!["zadd", "myzset", "1", "a", "2", "b"], ":2\r\n"),
+        (vec!["del", "myzset"], ":1\r\n"),
+        (vec!["zscore", "myzset", "a"], "$-1\r\n"),
+        (vec!["zrange", "myzset", "0", "-1"], "*0\r\n"),
     ], "test_del"; "test_del")]
     #[test_case(vec![
         (vec!["set", "mykey1", "myvalue"], "+OK\r\n"),
@@ -325,6 +1092,102 @@ mod test {
         (vec!["expire", "mykey5", "120", "XX"], ":0\r\n"),
         (vec!["expire", "mykey5", "120", "NX"], ":1\r\n"),
     ], "test_expire"; "test_expire")]
+    #[test_case(vec![
+        (vec!["type"], "-ERR wrong number of arguments for 'type' command\r\n"),
+        (vec!["type", "no_such_key"], "+none\r\n"),
+        (vec!["set", "mystr", "myvalue"], "+OK\r\n"),
+        (vec!["type", "mystr"], "+string\r\n"),
+        (vec!["lpush", "mylist", "a"], ":1\r\n"),
+        (vec!["type", "mylist"], "+list\r\n"),
+        (vec!["hset", "myhash", "field", "value"], ":1\r\n"),
+        (vec!["type", "myhash"], "+hash\r\n"),
+        (vec!["sadd", "myset", "a"], ":1\r\n"),
+        (vec!["type", "myset"], "+set\r\n"),
+        (vec!["zadd", "myzset", "1", "a"], ":1\r\n"),
+        (vec!["type", "myzset"], "+zset\r\n"),
+    ], "test_type"; "test_type")]
+    #[test_case(vec![
+        (vec!["persist"], "-ERR wrong number of arguments for 'persist' command\r\n"),
+        (vec!["persist", "no_such_key"], ":0\r\n"),
+        (vec!["set", "mykey", "myvalue"], "+OK\r\n"),
+        (vec!["persist", "mykey"], ":0\r\n"),
+        (vec!["set", "mykey", "myvalue", "EX", "100"], "+OK\r\n"),
+        (vec!["persist", "mykey"], ":1\r\n"),
+        (vec!["ttl", "mykey"], ":-1\r\n"),
+        (vec!["get", "mykey"], "$7\r\nmyvalue\r\n"),
+        (vec!["persist", "mykey"], ":0\r\n"),
+        (vec!["lpush", "mylist", "a"], ":1\r\n"),
+        (vec!["expire", "mylist", "100"], ":1\r\n"),
+        (vec!["persist", "mylist"], ":1\r\n"),
+        (vec!["ttl", "mylist"], ":-1\r\n"),
+    ], "test_persist"; "test_persist")]
+    #[test_case(vec![
+        (vec!["pttl"], "-ERR wrong number of arguments for 'pttl' command\r\n"),
+        (vec!["pttl", "no_such_key"], ":-2\r\n"),
+        (vec!["set", "mykey", "myvalue"], "+OK\r\n"),
+        (vec!["pttl", "mykey"], ":-1\r\n"),
+        (vec!["pexpire", "no_such_key", "1000"], ":0\r\n"),
+        (vec!["pexpire", "mykey", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["pexpire", "mykey", "100000", "NX"], ":1\r\n"),
+        (vec!["pexpire", "mykey", "200000", "NX"], ":0\r\n"),
+        (vec!["pexpire", "mykey", "90000", "GT"], ":0\r\n"),
+        (vec!["pexpire", "mykey", "120000", "GT"], ":1\r\n"),
+        (vec!["pexpire", "mykey", "150000", "LT"], ":0\r\n"),
+        (vec!["pexpire", "mykey", "500", "LT"], ":1\r\n"),
+        (vec!["ttl", "mykey"], ":1\r\n"),
+        (vec!["pexpire", "mykey", "1000", "XX"], ":1\r\n"),
+        (vec!["pexpire", "mykey", "1000", "YY"], "-ERR Unsupported option yy\r\n"),
+        (vec!["set", "mykey2", "myvalue", "EX", "100"], "+OK\r\n"),
+        (vec!["pexpire", "mykey2", "99000", "GT"], ":0\r\n"),
+        (vec!["expire", "mykey2", "99", "LT"], ":1\r\n"),
+    ], "test_pexpire"; "test_pexpire")]
+    #[test_case(vec![
+        (vec!["expireat", "mykey"], "-ERR wrong number of arguments for 'expireat' command\r\n"),
+        (vec!["expireat", "no_such_key", "4102444800"], ":0\r\n"),
+        (vec!["set", "mykey", "myvalue"], "+OK\r\n"),
+        (vec!["expireat", "mykey", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["expireat", "mykey", "4102444800", "XX"], ":0\r\n"),
+        (vec!["expireat", "mykey", "4102444800", "NX"], ":1\r\n"),
+        (vec!["expireat", "mykey", "4102444900", "NX"], ":0\r\n"),
+        (vec!["expireat", "mykey", "4000000000", "GT"], ":0\r\n"),
+        (vec!["expireat", "mykey", "4102444900", "GT"], ":1\r\n"),
+        (vec!["pexpireat", "mykey", "4102444900000", "LT"], ":0\r\n"),
+        (vec!["pexpireat", "mykey", "4102444800000", "LT"], ":1\r\n"),
+        (vec!["get", "mykey"], "$7\r\nmyvalue\r\n"),
+        (vec!["expireat", "mykey", "1"], ":1\r\n"),
+        (vec!["get", "mykey"], "$-1\r\n"),
+        (vec!["exists", "mykey"], ":0\r\n"),
+        (vec!["lpush", "mylist", "a", "b"], ":2\r\n"),
+        (vec!["pexpireat", "mylist", "1000"], ":1\r\n"),
+        (vec!["llen", "mylist"], ":0\r\n"),
+        (vec!["hset", "myhash", "f1", "v1"], ":1\r\n"),
+        (vec!["expireat", "myhash", "1", "XX"], ":0\r\n"),
+        (vec!["hlen", "myhash"], ":1\r\n"),
+        (vec!["expireat", "myhash", "-1", "NX"], ":1\r\n"),
+        (vec!["hlen", "myhash"], ":0\r\n"),
+    ], "test_expireat"; "test_expireat")]
+    #[test_case(vec![
+        (vec!["rename", "src"], "-ERR wrong number of arguments for 'rename' command\r\n"),
+        (vec!["rename", "no_such_key", "dst"], "-ERR no such key\r\n"),
+        (vec!["renamenx", "no_such_key", "dst"], "-ERR no such key\r\n"),
+        (vec!["set", "mystr", "myvalue", "EX", "100"], "+OK\r\n"),
+        (vec!["rename", "mystr", "mystr"], "+OK\r\n"),
+        (vec!["renamenx", "mystr", "mystr"], ":0\r\n"),
+        (vec!["rename", "mystr", "mystr2"], "+OK\r\n"),
+        (vec!["exists", "mystr"], ":0\r\n"),
+        (vec!["get", "mystr2"], "$7\r\nmyvalue\r\n"),
+        (vec!["ttl", "mystr2"], ":100\r\n"),
+        (vec!["hset", "myhash", "f1", "v1", "f2", "v2"], ":2\r\n"),
+        (vec!["rpush", "mylist", "a", "b"], ":2\r\n"),
+        (vec!["renamenx", "myhash", "mylist"], ":0\r\n"),
+        (vec!["rename", "myhash", "mylist"], "+OK\r\n"),
+        (vec!["type", "mylist"], "+hash\r\n"),
+        (vec!["hget", "mylist", "f2"], "$2\r\nv2\r\n"),
+        (vec!["hlen", "mylist"], ":2\r\n"),
+        (vec!["renamenx", "mylist", "myhash"], ":1\r\n"),
+        (vec!["hget", "myhash", "f1"], "$2\r\nv1\r\n"),
+        (vec!["type", "mylist"], "+none\r\n"),
+    ], "test_rename"; "test_rename")]
     fn test_generic_commands(
         args_vec: Vec<(Vec<&'static str>, &'static str)>,
         test_name: &str,
@@ -350,4 +1213,784 @@ mod test {
         });
         Ok(())
     }
+
+    #[test]
+    fn test_pttl() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // (command, the upper bound of the `PTTL` reply)
+            for (args, pttl_upper_bound) in [
+                (vec!["set", "mykey", "myvalue", "PX", "1500"], None),
+                (vec!["pttl", "mykey"], Some(1500u64)),
+                (vec!["pexpire", "mykey", "2500"], None),
+                (vec!["pttl", "mykey"], Some(2500u64)),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_pttl").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                let response = sink.read_all().await;
+                if let Some(upper_bound) = pttl_upper_bound {
+                    // `:<millis>\r\n`
+                    let millis = response
+                        .trim_start_matches(':')
+                        .trim_end()
+                        .parse::<u64>()
+                        .unwrap();
+                    assert!(millis <= upper_bound);
+                    assert!(millis > upper_bound - 500);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_del_set_removes_members() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+            let client_state = client.inner();
+            let generic_db = GenericDb::with_storage(client_state.database(), 0);
+
+            let mut sink = crate::tests::ResponseSink::with_name("test_del_set").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["sadd", "myset", "a", "b", "c"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(sink.read_all().await, ":3\r\n");
+
+            let items_prefix = generic_db
+                .items_prefix(&BytesMut::from("myset"))
+                .unwrap()
+                .unwrap();
+
+            let mut sink = crate::tests::ResponseSink::with_name("test_del_set").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["del", "myset"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(sink.read_all().await, ":1\r\n");
+
+            // no member records are left behind
+            assert_eq!(generic_db.delete_items(&items_prefix, 100).unwrap(), 0);
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_expiration() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state =
+                Arc::new(ServerState::new().set_reclaim_queue(store.reclaim_queue()));
+            let client = Client::new(server_state.clone(), store.clone(), None);
+            let client_state = client.inner();
+            let generic_db = GenericDb::with_storage(client_state.database(), 0);
+
+            let setup = vec![
+                vec!["set", "str", "value", "ex", "1"],
+                vec!["hset", "hash", "f1", "v1", "f2", "v2"],
+                vec!["expire", "hash", "1"],
+                vec!["rpush", "list", "a", "b", "c"],
+                vec!["expire", "list", "1"],
+            ];
+            for args in setup {
+                let mut sink = crate::tests::ResponseSink::with_name("test_lazy_expiration").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+            }
+
+            // advance the clock by moving the keys' last update time 2 seconds back
+            let mut items_prefixes = Vec::<BytesMut>::new();
+            for user_key in ["str", "hash", "list"] {
+                let user_key = BytesMut::from(user_key);
+                if let Some(items_prefix) = generic_db.items_prefix(&user_key).unwrap() {
+                    items_prefixes.push(items_prefix);
+                }
+                let mut expiration = generic_db.get_expiration(&user_key).unwrap().unwrap();
+                assert_eq!(expiration.ttl_ms, 1000);
+                expiration.last_updated -= 2000;
+                generic_db.put_expiration(&user_key, &expiration).unwrap();
+            }
+
+            let reads = vec![
+                (vec!["get", "str"], "$-1\r\n"),
+                (vec!["hget", "hash", "f1"], "$-1\r\n"),
+                // same reply as a list that does not exist
+                (vec!["lrange", "list", "0", "-1"], "+OK\r\n"),
+            ];
+            for (args, expected_value) in reads {
+                let mut sink = crate::tests::ResponseSink::with_name("test_lazy_expiration").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+
+            // the reads removed the keys and left the items they owned to the reclaimer
+            for user_key in ["str", "hash", "list"] {
+                assert!(!generic_db.contains(&BytesMut::from(user_key)).unwrap());
+            }
+            assert_eq!(items_prefixes.len(), 2);
+            assert_eq!(server_state.pending_reclaims(), 2);
+            let reclaimer = crate::Reclaimer::new(store, server_state.clone());
+            assert_eq!(reclaimer.reclaim().await.unwrap(), 5);
+            assert_eq!(server_state.pending_reclaims(), 0);
+            for items_prefix in items_prefixes {
+                assert_eq!(generic_db.delete_items(&items_prefix, 100).unwrap(), 0);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_key() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let setup = vec![
+                (vec!["randomkey"], "$-1\r\n"),
+                (vec!["set", "k1", "v"], "+OK\r\n"),
+                (vec!["hset", "k2", "f", "v"], ":1\r\n"),
+                (vec!["rpush", "k3", "a"], ":1\r\n"),
+                (vec!["set", "expired", "v", "px", "1"], "+OK\r\n"),
+                // keys of another database must not be returned
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["set", "other_db_key", "v"], "+OK\r\n"),
+                (vec!["select", "0"], "+OK\r\n"),
+            ];
+            for (args, expected_value) in setup {
+                let mut sink = crate::tests::ResponseSink::with_name("test_random_key").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+
+            let expected_keys = ["$2\r\nk1\r\n", "$2\r\nk2\r\n", "$2\r\nk3\r\n"];
+            for _ in 0..50 {
+                let mut sink = crate::tests::ResponseSink::with_name("test_random_key").await;
+                let cmd = Rc::new(RedisCommand::for_test(vec!["randomkey"]));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                let reply = sink.read_all().await;
+                assert!(expected_keys.contains(&reply.as_str()), "{}", reply);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_dbsize() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.key_counter());
+            let client = Client::new(server_state, store, None);
+
+            for (args, expected_value) in [
+                (vec!["dbsize"], ":0\r\n"),
+                (vec!["set", "k1", "v"], "+OK\r\n"),
+                (vec!["set", "k1", "v2"], "+OK\r\n"),
+                (vec!["dbsize"], ":1\r\n"),
+                // a multi-field hash is a single key
+                (vec!["hset", "k2", "f1", "v1", "f2", "v2"], ":2\r\n"),
+                (vec!["hset", "k2", "f3", "v3"], ":1\r\n"),
+                (vec!["rpush", "k3", "a", "b", "c"], ":3\r\n"),
+                (vec!["sadd", "k4", "a", "b"], ":2\r\n"),
+                (vec!["zadd", "k5", "1", "a", "2", "b"], ":2\r\n"),
+                (vec!["dbsize"], ":5\r\n"),
+                // popping the last item removes the key
+                (
+                    vec!["rpop", "k3", "3"],
+                    "*3\r\n$1\r\nc\r\n$1\r\nb\r\n$1\r\na\r\n",
+                ),
+                (vec!["dbsize"], ":4\r\n"),
+                (vec!["del", "k1", "k2", "no_such_key"], ":2\r\n"),
+                (vec!["dbsize"], ":2\r\n"),
+                (vec!["rename", "k4", "k6"], "+OK\r\n"),
+                (vec!["dbsize"], ":2\r\n"),
+                // each database has its own count
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["dbsize"], ":0\r\n"),
+                (vec!["set", "k1", "v"], "+OK\r\n"),
+                (vec!["dbsize"], ":1\r\n"),
+                (vec!["select", "0"], "+OK\r\n"),
+                (vec!["dbsize"], ":2\r\n"),
+                (
+                    vec!["dbsize", "extra"],
+                    "-ERR wrong number of arguments for 'dbsize' command\r\n",
+                ),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_dbsize").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_flushdb_and_flushall() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.key_counter());
+            let client = Client::new(server_state.clone(), store.clone(), None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink =
+                        crate::tests::ResponseSink::with_name("test_flushdb_and_flushall").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    let ClientNextAction::NoAction =
+                        Client::handle_command(client_state, cmd, &mut sink.fp)
+                            .await
+                            .unwrap()
+                    else {
+                        panic!("unexpected client action");
+                    };
+                    sink.read_all().await
+                }
+            };
+
+            let populate = |db: &'static str| {
+                let run_command = &run_command;
+                async move {
+                    assert_eq!(run_command(vec!["select", db]).await, "+OK\r\n");
+                    assert_eq!(run_command(vec!["set", "k1", "v"]).await, "+OK\r\n");
+                    assert_eq!(
+                        run_command(vec!["hset", "k2", "f1", "v1", "f2", "v2"]).await,
+                        ":2\r\n"
+                    );
+                    assert_eq!(run_command(vec!["dbsize"]).await, ":2\r\n");
+                }
+            };
+            populate("1").await;
+            populate("0").await;
+
+            for (args, expected_value) in [
+                (vec!["flushdb", "lazy"], "-ERR syntax error\r\n"),
+                (vec!["flushdb", "sync", "async"], "-ERR syntax error\r\n"),
+                (vec!["flushall", "lazy"], "-ERR syntax error\r\n"),
+                // FLUSHDB leaves the other databases untouched
+                (vec!["flushdb"], "+OK\r\n"),
+                (vec!["dbsize"], ":0\r\n"),
+                (vec!["get", "k1"], "$-1\r\n"),
+                (vec!["hget", "k2", "f1"], "$-1\r\n"),
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["dbsize"], ":2\r\n"),
+                (vec!["get", "k1"], "$1\r\nv\r\n"),
+                (vec!["hget", "k2", "f1"], "$2\r\nv1\r\n"),
+                // keys written after the flush are counted
+                (vec!["select", "0"], "+OK\r\n"),
+                (vec!["set", "k3", "v"], "+OK\r\n"),
+                (vec!["dbsize"], ":1\r\n"),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+
+            // the hash fields of the flushed database were removed as well, the fields of the
+            // hash in database 1 are left
+            let hash_items = BytesMut::from(&[Encoding::KEY_HASH_ITEM][..]);
+            let generic_db = GenericDb::with_storage(&store, 0);
+            assert_eq!(generic_db.sample_items(&hash_items, usize::MAX)?.0, 2);
+
+            // FLUSHDB ASYNC: the keys are gone once it replies, the keys written afterwards are
+            // kept and the hash fields are left to the reclaimer
+            for (args, expected_value) in [
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["flushdb", "async"], "+OK\r\n"),
+                (vec!["get", "k1"], "$-1\r\n"),
+                (vec!["hget", "k2", "f1"], "$-1\r\n"),
+                (vec!["set", "k4", "v"], "+OK\r\n"),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+            assert_eq!(server_state.pending_reclaims(), 1);
+            let reclaimer = crate::Reclaimer::new(store.clone(), server_state.clone());
+            assert_eq!(reclaimer.reclaim().await?, 2);
+            assert_eq!(generic_db.sample_items(&hash_items, usize::MAX)?.0, 0);
+            assert_eq!(run_command(vec!["get", "k4"]).await, "$1\r\nv\r\n");
+            assert_eq!(run_command(vec!["dbsize"]).await, ":1\r\n");
+
+            // FLUSHALL ASYNC removes the keys of all the databases before it replies
+            assert_eq!(run_command(vec!["flushall", "async"]).await, "+OK\r\n");
+            for (args, expected_value) in [
+                (vec!["dbsize"], ":0\r\n"),
+                (vec!["set", "k5", "v"], "+OK\r\n"),
+                (vec!["select", "0"], "+OK\r\n"),
+                (vec!["dbsize"], ":0\r\n"),
+                (vec!["get", "k3"], "$-1\r\n"),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+            // nothing is left running in the background: the key written after the flush stays
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            assert_eq!(run_command(vec!["select", "1"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["get", "k5"]).await, "$1\r\nv\r\n");
+            Ok::<(), SableError>(())
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_move() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in [
+                (vec!["set", "k1", "v1"], "+OK\r\n"),
+                (vec!["expire", "k1", "100"], ":1\r\n"),
+                (vec!["hset", "k2", "f1", "v1", "f2", "v2"], ":2\r\n"),
+                (vec!["set", "k3", "source"], "+OK\r\n"),
+                (vec!["move", "k1", "1"], ":1\r\n"),
+                (vec!["move", "k2", "1"], ":1\r\n"),
+                (vec!["move", "no_such_key", "1"], ":0\r\n"),
+                (vec!["exists", "k1", "k2"], ":0\r\n"),
+                (
+                    vec!["move", "k3", "0"],
+                    "-ERR source and destination objects are the same\r\n",
+                ),
+                (
+                    vec!["move", "k3", "-1"],
+                    "-ERR value is not an integer or out of range\r\n",
+                ),
+                (
+                    vec!["move", "k3"],
+                    "-ERR wrong number of arguments for 'move' command\r\n",
+                ),
+                // the moved keys are found in the target database, with their TTL and items
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["get", "k1"], "$2\r\nv1\r\n"),
+                (vec!["hget", "k2", "f2"], "$2\r\nv2\r\n"),
+                (vec!["hlen", "k2"], ":2\r\n"),
+                (vec!["persist", "k1"], ":1\r\n"),
+                // a key that exists in the target database is not moved
+                (vec!["set", "k3", "target"], "+OK\r\n"),
+                (vec!["select", "0"], "+OK\r\n"),
+                (vec!["move", "k3", "1"], ":0\r\n"),
+                (vec!["get", "k3"], "$6\r\nsource\r\n"),
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["get", "k3"], "$6\r\ntarget\r\n"),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_move").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_restore() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            async fn execute(client: &Client, args: Vec<BytesMut>) -> String {
+                let mut sink = crate::tests::ResponseSink::with_name("test_dump_restore").await;
+                let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                sink.read_all().await
+            }
+
+            // the payload is binary: read it from the response buffer, not from the sink
+            async fn dump(client: &Client, key: &'static str) -> BytesMut {
+                let mut sink = crate::tests::ResponseSink::with_name("test_dump_restore").await;
+                let cmd = Rc::new(RedisCommand::for_test(vec!["dump", key]));
+                let HandleCommandResult::ResponseBufferUpdated(mut response) =
+                    GenericCommands::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("expected a response buffer");
+                };
+                // `$<len>\r\n<payload>\r\n`
+                let header_len = response.iter().position(|c| *c == b'\n').unwrap() + 1;
+                let _ = response.split_to(header_len);
+                response.truncate(response.len() - 2);
+                response
+            }
+
+            let args = |args: &[&str]| -> Vec<BytesMut> {
+                args.iter().map(|arg| BytesMut::from(*arg)).collect()
+            };
+
+            for setup in [
+                vec!["hset", "hash", "f1", "v1", "f2", "v2", "f3", ""],
+                vec!["rpush", "list", "a", "b", "a"],
+                vec!["sadd", "set", "a", "b"],
+                vec!["zadd", "zset", "1.5", "a", "-2", "b"],
+                vec!["set", "string", "value"],
+            ] {
+                execute(&client, args(&setup)).await;
+            }
+
+            // a round trip yields an identical value
+            for (key, query) in [
+                ("hash", vec!["hgetall"]),
+                ("list", vec!["lrange", "", "0", "-1"]),
+                ("set", vec!["smembers"]),
+                ("zset", vec!["zrange", "", "0", "-1", "withscores"]),
+                ("string", vec!["get"]),
+            ] {
+                let payload = dump(&client, key).await;
+                let new_key = format!("{}_restored", key);
+                let mut restore = args(&["restore", &new_key, "0"]);
+                restore.push(payload);
+                assert_eq!(execute(&client, restore).await, "+OK\r\n");
+
+                let query_for = |key: &str| {
+                    let mut query = args(&query);
+                    if query.len() > 1 {
+                        query[1] = BytesMut::from(key);
+                    } else {
+                        query.push(BytesMut::from(key));
+                    }
+                    query
+                };
+                let original = execute(&client, query_for(key)).await;
+                assert_eq!(execute(&client, query_for(&new_key)).await, original);
+                assert_eq!(execute(&client, args(&["ttl", &new_key])).await, ":-1\r\n");
+            }
+
+            let payload = dump(&client, "hash").await;
+            let restore = |key: &str, ttl: &str, options: &[&str]| {
+                let mut restore = args(&["restore", key, ttl]);
+                restore.push(payload.clone());
+                restore.extend(options.iter().map(|option| BytesMut::from(*option)));
+                restore
+            };
+
+            // the key exists: REPLACE is required, whatever the type of the existing value
+            assert_eq!(
+                execute(&client, restore("string", "0", &[])).await,
+                "-BUSYKEY Target key name already exists.\r\n"
+            );
+            assert_eq!(
+                execute(&client, restore("string", "0", &["REPLACE"])).await,
+                "+OK\r\n"
+            );
+            assert_eq!(
+                execute(&client, args(&["type", "string"])).await,
+                "+hash\r\n"
+            );
+            assert_eq!(execute(&client, args(&["hlen", "string"])).await, ":3\r\n");
+
+            // with a TTL
+            assert_eq!(
+                execute(&client, restore("with_ttl", "100000", &[])).await,
+                "+OK\r\n"
+            );
+            assert_eq!(
+                execute(&client, args(&["ttl", "with_ttl"])).await,
+                ":100\r\n"
+            );
+
+            // errors
+            assert_eq!(
+                execute(&client, restore("k", "-1", &[])).await,
+                "-ERR Invalid TTL value, must be >= 0\r\n"
+            );
+            assert_eq!(
+                execute(&client, restore("k", "0", &["NOSUCHOPTION"])).await,
+                "-ERR syntax error\r\n"
+            );
+            assert_eq!(
+                execute(&client, args(&["restore", "k", "0", "not a payload"])).await,
+                "-ERR DUMP payload version or checksum are wrong\r\n"
+            );
+            assert_eq!(
+                execute(&client, args(&["restore", "hash", "0", "bad", "replace"])).await,
+                "-ERR DUMP payload version or checksum are wrong\r\n"
+            );
+            // a failed RESTORE REPLACE leaves the existing value untouched
+            assert_eq!(execute(&client, args(&["hlen", "hash"])).await, ":3\r\n");
+            assert_eq!(execute(&client, args(&["exists", "k"])).await, ":0\r\n");
+            assert_eq!(
+                execute(&client, args(&["dump", "no_such_key"])).await,
+                "$-1\r\n"
+            );
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_source_guard, source_store) = crate::tests::open_store();
+            let (_target_guard, target_store) = crate::tests::open_store();
+            let source = Client::new(Arc::<ServerState>::default(), source_store, None);
+            let target = Client::new(Arc::<ServerState>::default(), target_store.clone(), None);
+
+            // the target server: every accepted connection is served by its own client
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port().to_string();
+            tokio::task::spawn_local(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut client =
+                        Client::new(Arc::<ServerState>::default(), target_store.clone(), None);
+                    let stream = stream.into_std().unwrap();
+                    tokio::task::spawn_local(async move {
+                        let _ = client.run(stream).await;
+                    });
+                }
+            });
+
+            async fn execute(client: &Client, args: &[&str]) -> String {
+                let mut sink = crate::tests::ResponseSink::with_name("test_migrate").await;
+                let args = args.iter().map(|arg| BytesMut::from(*arg)).collect();
+                let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                sink.read_all().await
+            }
+            let migrate = |key: &'static str, options: &'static [&'static str]| {
+                let port = port.clone();
+                let source = &source;
+                async move {
+                    let mut args = vec!["migrate", "127.0.0.1", port.as_str(), key, "0", "5000"];
+                    args.extend_from_slice(options);
+                    execute(source, &args).await
+                }
+            };
+
+            // the key is moved together with its time to live
+            assert_eq!(execute(&source, &["set", "key", "value"]).await, "+OK\r\n");
+            assert_eq!(
+                execute(&source, &["pexpire", "key", "100000"]).await,
+                ":1\r\n"
+            );
+            assert_eq!(migrate("key", &[]).await, "+OK\r\n");
+            assert_eq!(execute(&source, &["get", "key"]).await, "$-1\r\n");
+            assert_eq!(execute(&target, &["get", "key"]).await, "$5\r\nvalue\r\n");
+            assert_eq!(execute(&target, &["ttl", "key"]).await, ":100\r\n");
+            assert_eq!(migrate("key", &[]).await, "+NOKEY\r\n");
+
+            // the target refuses to overwrite an existing key unless asked to
+            assert_eq!(execute(&source, &["set", "key", "new"]).await, "+OK\r\n");
+            assert_eq!(
+                migrate("key", &[]).await,
+                "-ERR Target instance replied with error: BUSYKEY Target key name already exists.\r\n"
+            );
+            assert_eq!(execute(&source, &["get", "key"]).await, "$3\r\nnew\r\n");
+            assert_eq!(migrate("key", &["COPY", "REPLACE"]).await, "+OK\r\n");
+            assert_eq!(execute(&source, &["get", "key"]).await, "$3\r\nnew\r\n");
+            assert_eq!(execute(&target, &["get", "key"]).await, "$3\r\nnew\r\n");
+
+            // multiple keys
+            assert_eq!(
+                execute(&source, &["mset", "k1", "v1", "k2", "v2"]).await,
+                "+OK\r\n"
+            );
+            assert_eq!(
+                migrate("k1", &["KEYS", "k1", "k2"]).await,
+                "-ERR When using MIGRATE KEYS option, the key argument must be set to the empty string\r\n"
+            );
+            assert_eq!(
+                migrate("", &["KEYS", "k1", "no_such_key", "k2"]).await,
+                "+OK\r\n"
+            );
+            assert_eq!(execute(&source, &["exists", "k1", "k2"]).await, ":0\r\n");
+            assert_eq!(
+                execute(&target, &["mget", "k1", "k2"]).await,
+                "*2\r\n$2\r\nv1\r\n$2\r\nv2\r\n"
+            );
+
+            // a target that can not be reached leaves the source intact
+            let closed_port = {
+                let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                listener.local_addr().unwrap().port().to_string()
+            };
+            assert_eq!(
+                execute(
+                    &source,
+                    &["migrate", "127.0.0.1", &closed_port, "key", "0", "100"]
+                )
+                .await,
+                "-IOERR error or timeout talking to target instance\r\n"
+            );
+            assert_eq!(execute(&source, &["get", "key"]).await, "$3\r\nnew\r\n");
+
+            assert_eq!(
+                migrate("key", &["NOSUCHOPTION"]).await,
+                "-ERR syntax error\r\n"
+            );
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_touch() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+            let client_state = client.inner();
+            let generic_db = GenericDb::with_storage(client_state.database(), 0);
+
+            let setup = vec![
+                vec!["set", "str", "value", "ex", "100"],
+                vec!["hset", "hash", "f1", "v1"],
+            ];
+            for args in setup {
+                let mut sink = crate::tests::ResponseSink::with_name("test_touch").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+            }
+
+            // move the access time of the keys 10 seconds back
+            for user_key in ["str", "hash"] {
+                let user_key = BytesMut::from(user_key);
+                let (value, mut common_md) = generic_db.get(&user_key).unwrap().unwrap();
+                common_md.set_accessed_at(common_md.accessed_at() - 10_000);
+                generic_db
+                    .put(&user_key, &value, &common_md, PutFlags::Override)
+                    .unwrap();
+            }
+
+            let commands = vec![
+                (
+                    vec!["touch"],
+                    "-ERR wrong number of arguments for 'touch' command\r\n",
+                ),
+                (vec!["object", "idletime", "str"], ":10\r\n"),
+                (vec!["object", "idletime", "hash"], ":10\r\n"),
+                (vec!["touch", "str", "no_such_key", "hash"], ":2\r\n"),
+                (vec!["object", "idletime", "str"], ":0\r\n"),
+                (vec!["object", "idletime", "hash"], ":0\r\n"),
+                // the value and the ttl are left untouched
+                (vec!["get", "str"], "$5\r\nvalue\r\n"),
+                (vec!["ttl", "str"], ":100\r\n"),
+                (vec!["hget", "hash", "f1"], "$2\r\nv1\r\n"),
+            ];
+            for (args, expected_value) in commands {
+                let mut sink = crate::tests::ResponseSink::with_name("test_touch").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_del_huge_hash_does_not_block_worker() -> Result<(), SableError> {
+        const HASH_SIZE: usize = 100_000;
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&rt, async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // hset myhash field_0 value_0 ... field_N value_N
+            let mut args = vec![BytesMut::from("hset"), BytesMut::from("myhash")];
+            for i in 0..HASH_SIZE {
+                args.push(BytesMut::from(format!("field_{}", i).as_bytes()));
+                args.push(BytesMut::from(format!("value_{}", i).as_bytes()));
+            }
+            let mut sink = crate::tests::ResponseSink::with_name("test_del_huge_hash").await;
+            Client::handle_command(
+                client.inner(),
+                Rc::new(RedisCommand::new(args).unwrap()),
+                &mut sink.fp,
+            )
+            .await
+            .unwrap();
+            assert_eq!(sink.read_all().await, format!(":{}\r\n", HASH_SIZE));
+
+            let client_state = client.inner();
+            let generic_db = GenericDb::with_storage(client_state.database(), 0);
+            let items_prefix = generic_db
+                .items_prefix(&BytesMut::from("myhash"))
+                .unwrap()
+                .unwrap();
+
+            let del_done = Rc::new(std::cell::Cell::new(false));
+            let mut del_sink = crate::tests::ResponseSink::with_name("test_del_huge_hash").await;
+            let mut ping_sink = crate::tests::ResponseSink::with_name("test_del_huge_hash").await;
+
+            let del_task = tokio::task::spawn_local({
+                let client_state = client.inner();
+                let del_done = del_done.clone();
+                async move {
+                    let cmd = Rc::new(RedisCommand::for_test(vec!["del", "myhash"]));
+                    Client::handle_command(client_state, cmd, &mut del_sink.fp)
+                        .await
+                        .unwrap();
+                    del_done.set(true);
+                    del_sink.read_all().await
+                }
+            });
+
+            let ping_task = tokio::task::spawn_local({
+                let client_state = client.inner();
+                let del_done = del_done.clone();
+                async move {
+                    let cmd = Rc::new(RedisCommand::for_test(vec!["ping"]));
+                    Client::handle_command(client_state, cmd, &mut ping_sink.fp)
+                        .await
+                        .unwrap();
+                    (del_done.get(), ping_sink.read_all().await)
+                }
+            });
+
+            // PING must be served while the DEL is still sweeping the hash fields
+            let (del_done_before_ping, ping_response) = ping_task.await.unwrap();
+            assert!(!del_done_before_ping);
+            assert_eq!(ping_response, "+PONG\r\n");
+
+            assert_eq!(del_task.await.unwrap(), ":1\r\n");
+            assert_eq!(generic_db.delete_items(&items_prefix, 1).unwrap(), 0);
+        });
+        Ok(())
+    }
 }
@@ -3,19 +3,22 @@ use crate::{
     check_args_count, check_value_type,
     client::ClientState,
     command_arg_at,
-    commands::{ErrorStrings, HandleCommandResult, StringCommands},
+    commands::{ErrorStrings, HandleCommandResult, ScanCommands, StringCommands},
     metadata::Encoding,
-    metadata::{CommonValueMetadata, HashFieldKey, HashValueMetadata},
+    metadata::{CommonValueMetadata, HashFieldKey},
     parse_string_to_number,
     storage::{
         GenericDb, GetHashMetadataResult, HashDb, HashDeleteResult, HashExistsResult,
-        HashGetMultiResult, HashGetResult, HashLenResult, HashPutResult,
+        HashExpireCondition, HashExpireResult, HashGetMultiResult, HashGetResult, HashLenResult,
+        HashPutResult, HashTtlResult,
     },
     types::List,
     BytesMutUtils, Expiration, LockManager, PrimaryKeyMetadata, RedisCommand, RedisCommandName,
-    RespBuilderV2, SableError, StorageAdapter, StringUtils, Telemetry, TimeUtils,
+    RespBuilderV2, SableError, StorageAdapter, StringUtils, Telemetry, TimeUtils, U8ArrayBuilder,
 };
 
+use super::scan_commands::{SCAN_CURSOR_START, SCAN_MAX_PREALLOCATED};
+use super::set_commands::MAX_RANDOM_COUNT;
 use crate::storage::StorageIterator;
 use bytes::BytesMut;
 use rand::prelude::*;
@@ -95,6 +98,21 @@ impl HashCommands {
                 Self::hrandfield(client_state, command, tx).await?;
                 return Ok(HandleCommandResult::ResponseSent);
             }
+            RedisCommandName::Hscan => {
+                Self::hscan(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Hsetnx => {
+                Self::hsetnx(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Hstrlen => {
+                Self::hstrlen(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Hexpire => {
+                Self::hexpire(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Httl => {
+                Self::httl(client_state, command, &mut response_buffer).await?;
+            }
             _ => {
                 return Err(SableError::InvalidArgument(format!(
                     "Non hash command {}",
@@ -199,6 +217,79 @@ impl HashCommands {
         Ok(())
     }
 
+    /// Sets field in the hash stored at key to value, only if field does not yet exist.
+    /// If field already exists, this operation has no effect
+    async fn hsetnx(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+
+        let key = command_arg_at!(command, 1);
+        let field = command_arg_at!(command, 2);
+        let value = command_arg_at!(command, 3);
+
+        // Read + write: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
+
+        match hash_db.get(key, field)? {
+            HashGetResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+            HashGetResult::Some(_) => {
+                builder.number_usize(response_buffer, 0);
+                return Ok(());
+            }
+            HashGetResult::NotFound | HashGetResult::FieldNotFound => {}
+        }
+
+        let items_put = match hash_db.put_multi(key, &[(field, value)])? {
+            HashPutResult::Some(count) => count,
+            HashPutResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        builder.number_usize(response_buffer, items_put);
+        Ok(())
+    }
+
+    /// Returns the string length of the value associated with field in the hash stored at key.
+    /// If the key or the field do not exist, 0 is returned
+    async fn hstrlen(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+
+        let key = command_arg_at!(command, 1);
+        let field = command_arg_at!(command, 2);
+
+        // reading an expired field deletes it: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
+
+        match hash_db.get(key, field)? {
+            HashGetResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+            }
+            HashGetResult::Some(value) => {
+                builder.number_usize(response_buffer, value.len());
+            }
+            HashGetResult::NotFound | HashGetResult::FieldNotFound => {
+                builder.number_usize(response_buffer, 0);
+            }
+        };
+        Ok(())
+    }
+
     /// Removes the specified fields from the hash stored at key. Specified fields that do not exist within this hash
     /// are ignored. If key does not exist, it is treated as an empty hash and this command returns 0
     async fn hdel(
@@ -280,8 +371,8 @@ impl HashCommands {
         let key = command_arg_at!(command, 1);
         let field = command_arg_at!(command, 2);
 
-        // Lock and delete
-        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        // reading an expired field deletes it: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
         let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
 
         match hash_db.field_exists(key, field)? {
@@ -303,21 +394,22 @@ impl HashCommands {
     ) -> Result<(), SableError> {
         check_args_count_tx!(command, 2, tx);
 
-        let builder = RespBuilderV2::default();
+        // HGETALL replies with a map, which is a flat array in RESP2
+        let builder = client_state.resp_builder();
         let mut response_buffer = BytesMut::with_capacity(128);
         let key = command_arg_at!(command, 1);
 
         // multiple db access -> use exclusive lock
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
         let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
-        let hash_md = match hash_db.hash_metadata(key)? {
+        let hash_md = match hash_db.purge_expired_fields(key)? {
             GetHashMetadataResult::WrongType => {
                 builder.error_string(&mut response_buffer, ErrorStrings::WRONGTYPE);
                 tx.write_all(&response_buffer).await?;
                 return Ok(());
             }
             GetHashMetadataResult::NotFound => {
-                builder.empty_array(&mut response_buffer);
+                Self::empty_hgetall_reply(&builder, &mut response_buffer, &output_type);
                 tx.write_all(&response_buffer).await?;
                 return Ok(());
             }
@@ -326,7 +418,7 @@ impl HashCommands {
 
         // empty hash? empty array
         if hash_md.is_empty() {
-            builder.empty_array(&mut response_buffer);
+            Self::empty_hgetall_reply(&builder, &mut response_buffer, &output_type);
             tx.write_all(&response_buffer).await?;
             return Ok(());
         }
@@ -339,18 +431,12 @@ impl HashCommands {
 
         // Write the length
         let mut response_buffer = BytesMut::with_capacity(128 << 10);
-        builder.add_array_len(
-            &mut response_buffer,
-            hash_md
-                .len()
-                .saturating_mul(if output_type == HGetAllOutput::Both {
-                    2
-                } else {
-                    1
-                })
-                .try_into()
-                .unwrap_or(usize::MAX),
-        );
+        let len: usize = hash_md.len().try_into().unwrap_or(usize::MAX);
+        if output_type == HGetAllOutput::Both {
+            builder.add_map_len(&mut response_buffer, len);
+        } else {
+            builder.add_array_len(&mut response_buffer, len);
+        }
 
         let prefix = Rc::new(hash_md.prefix());
         let mut fields_added = 0usize;
@@ -372,6 +458,7 @@ impl HashCommands {
 
                     // extract the key from the row data
                     let hash_field_key = HashFieldKey::from_bytes(key)?;
+                    let value = hash_md.decode_field(value)?.value();
                     match output_type {
                         HGetAllOutput::Keys => {
                             builder
@@ -407,6 +494,19 @@ impl HashCommands {
         Ok(())
     }
 
+    /// The reply of `hgetall` (and friends) for a missing or an empty hash
+    fn empty_hgetall_reply(
+        builder: &RespBuilderV2,
+        response_buffer: &mut BytesMut,
+        output_type: &HGetAllOutput,
+    ) {
+        if *output_type == HGetAllOutput::Both {
+            builder.empty_map(response_buffer);
+        } else {
+            builder.empty_array(response_buffer);
+        }
+    }
+
     /// Increments the number stored at field in the hash stored at key by increment.
     /// If key does not exist, a new key holding a hash is created. If field does not exist the value is set to 0
     /// before the operation is performed.
@@ -473,11 +573,8 @@ impl HashCommands {
         let field = command_arg_at!(command, 2);
         let increment = command_arg_at!(command, 3);
 
-        let Some(increment) = BytesMutUtils::parse::<f64>(increment) else {
-            builder.error_string(
-                response_buffer,
-                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
-            );
+        let Some(increment) = BytesMutUtils::parse_float(increment) else {
+            builder.error_string(response_buffer, ErrorStrings::VALUE_NOT_VALID_FLOAT);
             return Ok(());
         };
 
@@ -492,8 +589,8 @@ impl HashCommands {
             }
             HashGetResult::NotFound | HashGetResult::FieldNotFound => 0f64,
             HashGetResult::Some(value) => {
-                let Some(value) = BytesMutUtils::parse::<f64>(&value) else {
-                    builder.error_string(response_buffer, "ERR hash value is not an integer");
+                let Some(value) = BytesMutUtils::parse_float(&value) else {
+                    builder.error_string(response_buffer, "ERR hash value is not a float");
                     return Ok(());
                 };
                 value
@@ -501,11 +598,15 @@ impl HashCommands {
         };
 
         let new_value = prev_value + increment;
-        builder.number::<f64>(response_buffer, new_value, true);
+        if !new_value.is_finite() {
+            builder.error_string(response_buffer, ErrorStrings::INCR_NAN_OR_INFINITY);
+            return Ok(());
+        }
 
-        // store the new value
-        let new_value = BytesMutUtils::from::<f64>(&new_value);
+        // store the new value, formatted as in the reply
+        let new_value = BytesMut::from(StringUtils::format_redis_float(new_value).as_str());
         let _ = hash_db.put_multi(key, &[(field, &new_value)])?;
+        builder.bulk_string(response_buffer, &new_value);
         Ok(())
     }
 
@@ -566,6 +667,262 @@ impl HashCommands {
         Ok(())
     }
 
+    /// `HSCAN key cursor [MATCH pattern] [COUNT count]`
+    ///
+    /// Like `SCAN`, the cursor is opaque to the client: it is the hex encoding of the last field
+    /// visited by the previous call. The next call seeks to the first field that follows it, so
+    /// fields that were added or deleted between calls (including the cursor field itself) do not
+    /// break the iteration. The reply contains the fields and their values, flattened
+    async fn hscan(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+        let cursor = command_arg_at!(command, 2);
+
+        let Some((pattern, count)) =
+            ScanCommands::parse_scan_options(&command, 3, response_buffer)?
+        else {
+            return Ok(());
+        };
+
+        let cursor_field = if cursor == SCAN_CURSOR_START {
+            None
+        } else {
+            let Some(cursor_field) = BytesMutUtils::from_hex(cursor) else {
+                builder.error_string(response_buffer, ErrorStrings::INVALID_CURSOR);
+                return Ok(());
+            };
+            Some(cursor_field)
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
+        let hash_md = match hash_db.hash_metadata(key)? {
+            GetHashMetadataResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+            GetHashMetadataResult::NotFound => {
+                builder.add_array_len(response_buffer, 2);
+                builder.add_bulk_string_u8_arr(response_buffer, SCAN_CURSOR_START.as_bytes());
+                builder.add_empty_array(response_buffer);
+                return Ok(());
+            }
+            GetHashMetadataResult::Some(hash_md) => hash_md,
+        };
+
+        let prefix = hash_md.prefix();
+        let seek_key = match &cursor_field {
+            Some(cursor_field) => {
+                let mut seek_key = BytesMut::new();
+                let mut key_builder = U8ArrayBuilder::with_buffer(&mut seek_key);
+                HashFieldKey::with_user_key(hash_md.id(), cursor_field).to_bytes(&mut key_builder);
+                seek_key
+            }
+            None => prefix.clone(),
+        };
+
+        let now_ms = TimeUtils::epoch_ms()?;
+        let mut fields =
+            Vec::<(BytesMut, BytesMut)>::with_capacity(count.min(SCAN_MAX_PREALLOCATED));
+        let mut last_field: Option<BytesMut> = None;
+        let mut examined = 0usize;
+        let mut has_more = false;
+        match client_state.database().create_iterator(Rc::new(seek_key))? {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                while rocksdb_iter.valid() {
+                    let (Some(item_key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value())
+                    else {
+                        break;
+                    };
+
+                    if !item_key.starts_with(&prefix) {
+                        break;
+                    }
+
+                    let field_key = HashFieldKey::from_bytes(item_key)?;
+                    let field = field_key.key();
+                    // the cursor field itself was already returned by the previous call
+                    if cursor_field.as_deref() == Some(field) {
+                        rocksdb_iter.next();
+                        continue;
+                    }
+
+                    if examined == count {
+                        has_more = true;
+                        break;
+                    }
+                    examined = examined.saturating_add(1);
+
+                    // expired fields are skipped (they are deleted by the next write)
+                    let field_value = hash_md.decode_field(value)?;
+                    let matched = match &pattern {
+                        Some(pattern) => StringUtils::glob_match(pattern, field, false),
+                        None => true,
+                    };
+                    if matched && !field_value.is_expired(now_ms) {
+                        fields.push((BytesMut::from(field), BytesMut::from(field_value.value())));
+                    }
+                    last_field = Some(BytesMut::from(field));
+                    rocksdb_iter.next();
+                }
+            }
+        }
+
+        let next_cursor = match last_field {
+            Some(last_field) if has_more => BytesMutUtils::to_hex(&last_field),
+            _ => SCAN_CURSOR_START.to_string(),
+        };
+
+        builder.add_array_len(response_buffer, 2);
+        builder.add_bulk_string_u8_arr(response_buffer, next_cursor.as_bytes());
+        builder.add_array_len(response_buffer, fields.len().saturating_mul(2));
+        for (field, value) in &fields {
+            builder.add_bulk_string(response_buffer, field);
+            builder.add_bulk_string(response_buffer, value);
+        }
+        Ok(())
+    }
+
+    /// `HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]`
+    /// Set an expiration (TTL) on one or more fields of the hash stored at key. Once a field
+    /// expires, it is treated as if it does not exist. The reply contains a status per field:
+    /// `-2` no such field, `0` the condition was not met, `1` the expiration was set and `2` the
+    /// field was deleted (`seconds` is 0)
+    async fn hexpire(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 6, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let Some(seconds) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 2)) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+
+        if seconds < 0 {
+            builder.error_string(response_buffer, ErrorStrings::HEXPIRE_INVALID_EXPIRE_TIME);
+            return Ok(());
+        }
+
+        let (condition, fields_pos) = match command.arg_as_lowercase_string(3).as_deref() {
+            Some("nx") => (HashExpireCondition::Nx, 4),
+            Some("xx") => (HashExpireCondition::Xx, 4),
+            Some("gt") => (HashExpireCondition::Gt, 4),
+            Some("lt") => (HashExpireCondition::Lt, 4),
+            _ => (HashExpireCondition::Always, 3),
+        };
+
+        let Some(fields) = Self::parse_fields_arg(&command, fields_pos, response_buffer) else {
+            return Ok(());
+        };
+
+        let expire_at_ms =
+            TimeUtils::epoch_ms()?.saturating_add((seconds as u64).saturating_mul(1000));
+
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
+
+        match hash_db.expire_fields(key, &fields, expire_at_ms, condition)? {
+            HashExpireResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+            }
+            HashExpireResult::Some(statuses) => {
+                builder.add_array_len(response_buffer, statuses.len());
+                for status in statuses {
+                    builder.add_number::<i64>(response_buffer, status, false);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `HTTL key FIELDS numfields field [field ...]`
+    /// Returns the remaining TTL (in seconds) of the hash fields: `-2` if the field does not
+    /// exist and `-1` if the field exists but has no expiration
+    async fn httl(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 5, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let Some(fields) = Self::parse_fields_arg(&command, 2, response_buffer) else {
+            return Ok(());
+        };
+
+        // reading an expired field deletes it: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
+
+        match hash_db.fields_ttl(key, &fields)? {
+            HashTtlResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+            }
+            HashTtlResult::Some(ttls) => {
+                builder.add_array_len(response_buffer, ttls.len());
+                for ttl_ms in ttls {
+                    // round up, so a field that is about to expire is reported with a TTL of 1
+                    let ttl = if ttl_ms < 0 {
+                        ttl_ms
+                    } else {
+                        (ttl_ms + 999) / 1000
+                    };
+                    builder.add_number::<i64>(response_buffer, ttl, false);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the `FIELDS numfields field [field ...]` part of the command, starting at `pos`.
+    /// On error, `response_buffer` is updated and `None` is returned
+    fn parse_fields_arg<'a>(
+        command: &'a RedisCommand,
+        pos: usize,
+        response_buffer: &mut BytesMut,
+    ) -> Option<Vec<&'a BytesMut>> {
+        let builder = RespBuilderV2::default();
+        if command.arg_as_lowercase_string(pos).as_deref() != Some("fields") {
+            builder.error_string(response_buffer, ErrorStrings::FIELDS_ARG_MISSING);
+            return None;
+        }
+
+        let numfields = match command.arg(pos + 1).map(BytesMutUtils::parse::<i64>) {
+            Some(Some(numfields)) if numfields > 0 => numfields as usize,
+            Some(Some(_)) => {
+                builder.error_string(response_buffer, ErrorStrings::NUMFIELDS_NOT_POSITIVE);
+                return None;
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                );
+                return None;
+            }
+        };
+
+        let fields: Vec<&BytesMut> = command.args_vec().iter().skip(pos + 2).collect();
+        if fields.len() != numfields {
+            builder.error_string(response_buffer, ErrorStrings::NUMFIELDS_MISMATCH);
+            return None;
+        }
+        Some(fields)
+    }
+
     #[allow(unused_variables)]
     /// `HRANDFIELD key [count [WITHVALUES]]`
     /// When called with just the key argument, return a random field from the hash value stored at key.
@@ -575,6 +932,7 @@ impl HashCommands {
     /// multiple times. In this case, the number of returned fields is the absolute value of the specified count.
     /// The optional WITHVALUES modifier changes the reply so it includes the respective values of the randomly selected
     /// hash fields.
+    /// If the key does not exist, the reply is nil without a count and an empty array with one.
     async fn hrandfield(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
@@ -590,8 +948,8 @@ impl HashCommands {
         // Parse the arguments
         let builder = RespBuilderV2::default();
         let mut response_buffer = BytesMut::with_capacity(4096);
-        let (count, with_values, allow_dups) = match (iter.next(), iter.next()) {
-            (Some(count), None) => {
+        let (count, with_values, allow_dups) = match (iter.next(), iter.next(), iter.next()) {
+            (Some(count), with_values, None) => {
                 let Some(count) = BytesMutUtils::parse::<i64>(count) else {
                     builder.error_string(
                         &mut response_buffer,
@@ -600,36 +958,51 @@ impl HashCommands {
                     tx.write_all(&response_buffer).await?;
                     return Ok(());
                 };
-                (count.abs(), false, count < 0)
-            }
-            (Some(count), Some(with_values)) => {
-                let Some(count) = BytesMutUtils::parse::<i64>(count) else {
-                    builder.error_string(
-                        &mut response_buffer,
-                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
-                    );
-                    tx.write_all(&response_buffer).await?;
-                    return Ok(());
+                let with_values = match with_values {
+                    None => false,
+                    Some(with_values)
+                        if BytesMutUtils::to_string(with_values).to_lowercase() == "withvalues" =>
+                    {
+                        true
+                    }
+                    Some(_) => {
+                        builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        tx.write_all(&response_buffer).await?;
+                        return Ok(());
+                    }
                 };
-                if BytesMutUtils::to_string(with_values).to_lowercase() != "withvalues" {
-                    builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                if count < 0 && count.unsigned_abs() > MAX_RANDOM_COUNT {
+                    builder.error_string(&mut response_buffer, ErrorStrings::VALUE_OUT_OF_RANGE);
                     tx.write_all(&response_buffer).await?;
                     return Ok(());
                 }
-                (count.abs(), true, count < 0)
+                (Some(count.unsigned_abs()), with_values, count < 0)
+            }
+            (None, _, _) => (None, false, false),
+            (Some(_), _, Some(_)) => {
+                builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                tx.write_all(&response_buffer).await?;
+                return Ok(());
             }
-            (_, _) => (1i64, false, false),
         };
 
+        // Without a count, the reply is a single field (bulk string). Otherwise it is an array
+        let reply_with_array = count.is_some();
+        let count = count.unwrap_or(1);
+
         // multiple db calls, requires exclusive lock
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
         let hash_db = HashDb::with_storage(client_state.database(), client_state.database_id());
 
         // determine the array length
-        let hash_md = match hash_db.hash_metadata(key)? {
+        let hash_md = match hash_db.purge_expired_fields(key)? {
             GetHashMetadataResult::Some(hash_md) => hash_md,
             GetHashMetadataResult::NotFound => {
-                builder.null_string(&mut response_buffer);
+                if reply_with_array {
+                    builder.empty_array(&mut response_buffer);
+                } else {
+                    builder.null_string(&mut response_buffer);
+                }
                 tx.write_all(&response_buffer).await?;
                 return Ok(());
             }
@@ -644,8 +1017,9 @@ impl HashCommands {
         let count = if allow_dups {
             count
         } else {
-            std::cmp::min(count, hash_md.len() as i64)
+            std::cmp::min(count, hash_md.len())
         };
+        let count: usize = count.try_into().unwrap_or(usize::MAX);
 
         // fast bail out
         if count.eq(&0) {
@@ -657,10 +1031,10 @@ impl HashCommands {
         let possible_indexes = (0..hash_md.len() as usize).collect::<Vec<usize>>();
 
         // select the indices we want to pick
-        let mut indices = choose_multiple_values(count as usize, &possible_indexes, allow_dups)?;
+        let mut indices = choose_multiple_values(count, &possible_indexes, allow_dups)?;
 
-        // When returning multiple items, we return an array
-        if indices.len() > 1 || with_values {
+        // When a count is provided, we return an array
+        if reply_with_array {
             builder.add_array_len(
                 &mut response_buffer,
                 if with_values {
@@ -707,7 +1081,10 @@ impl HashCommands {
                             builder
                                 .add_bulk_string_u8_arr(&mut response_buffer, hash_field_key.key());
                             if with_values {
-                                builder.add_bulk_string_u8_arr(&mut response_buffer, value);
+                                builder.add_bulk_string_u8_arr(
+                                    &mut response_buffer,
+                                    hash_md.decode_field(value)?.value(),
+                                );
                             }
                             // pop the first element
                             indices.pop_front();
@@ -754,26 +1131,8 @@ fn choose_multiple_values(
         let mut unique_values = options.clone();
         unique_values.sort();
         unique_values.dedup();
-        loop {
-            if unique_values.is_empty() {
-                break;
-            }
-
-            if chosen.len() == count {
-                break;
-            }
-
-            let pos = rng.gen_range(0..unique_values.len());
-            let Some(val) = unique_values.get(pos) else {
-                return Err(SableError::OtherError(format!(
-                    "Internal error: failed to read from vector (len: {}, pos: {})",
-                    unique_values.len(),
-                    pos
-                )));
-            };
-            chosen.push(*val);
-            unique_values.remove(pos);
-        }
+        // every subset of size `count` is equally likely
+        chosen.extend(unique_values.choose_multiple(&mut rng, count));
     }
 
     chosen.sort();
@@ -861,9 +1220,19 @@ mod test {
         (vec!["hincrbyfloat"], "-ERR wrong number of arguments for 'hincrbyfloat' command\r\n"),
         (vec!["hincrbyfloat", "myhash"], "-ERR wrong number of arguments for 'hincrbyfloat' command\r\n"),
         (vec!["hincrbyfloat", "myhash", "field"], "-ERR wrong number of arguments for 'hincrbyfloat' command\r\n"),
-        (vec!["hincrbyfloat", "myhash", "field", "1"], ",1\r\n"),
-        (vec!["hincrbyfloat", "myhash", "field", "1"], ",2\r\n"),
-        (vec!["hincrbyfloat", "myhash", "field", "1.0"], ",3\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field", "1"], "$1\r\n1\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field", "1"], "$1\r\n2\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field", "1.0"], "$1\r\n3\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field", "7.5"], "$4\r\n10.5\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field", "0.1"], "$4\r\n10.6\r\n"),
+        (vec!["hget", "myhash", "field"], "$4\r\n10.6\r\n"),
+        (vec!["hset", "myhash", "field2", "3.0e3"], ":1\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field2", "200"], "$4\r\n3200\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field2", "abc"], "-ERR value is not a valid float\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field2", "nan"], "-ERR value is not a valid float\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field2", "-inf"], "-ERR increment would produce NaN or Infinity\r\n"),
+        (vec!["hset", "myhash", "field3", "abc"], ":1\r\n"),
+        (vec!["hincrbyfloat", "myhash", "field3", "1"], "-ERR hash value is not a float\r\n"),
         (vec!["set", "string", "field"], "+OK\r\n"),
         (vec!["hincrbyfloat", "string", "field", "1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
     ], "test_hincrbyfloat"; "test_hincrbyfloat")]
@@ -910,7 +1279,92 @@ mod test {
         (vec!["hset", "myhash_1_item", "f1", "v1"], ":1\r\n"),
         (vec!["hrandfield", "myhash_1_item", "1", "withvalues"], "*2\r\n$2\r\nf1\r\n$2\r\nv1\r\n"),
         (vec!["hrandfield", "myhash_1_item"], "$2\r\nf1\r\n"),
+        // with a count, the reply is always an array
+        (vec!["hrandfield", "myhash_1_item", "1"], "*1\r\n$2\r\nf1\r\n"),
+        (vec!["hrandfield", "myhash_1_item", "0"], "*0\r\n"),
+        // a negative count allows the same field to be returned multiple times
+        (vec!["hrandfield", "myhash_1_item", "-3"], "*3\r\n$2\r\nf1\r\n$2\r\nf1\r\n$2\r\nf1\r\n"),
+        (vec!["hrandfield", "myhash_1_item", "-2", "withvalues"], "*4\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf1\r\n$2\r\nv1\r\n"),
+        // a negative count is not bounded by the hash size, a huge one is rejected
+        (vec!["hrandfield", "myhash_1_item", "-9223372036854775808"], "-ERR value is out of range\r\n"),
+        (vec!["hrandfield", "myhash_1_item", "-9223372036854775807", "withvalues"], "-ERR value is out of range\r\n"),
+        (vec!["hrandfield", "myhash_1_item", "9223372036854775807"], "*1\r\n$2\r\nf1\r\n"),
+        (vec!["hrandfield", "nosuchhash", "3"], "*0\r\n"),
+        (vec!["hrandfield", "myhash", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["hrandfield", "myhash", "3", "novalues"], "-ERR syntax error\r\n"),
+        (vec!["hrandfield", "myhash", "3", "withvalues", "extra"], "-ERR syntax error\r\n"),
     ], "test_hrandfield"; "test_hrandfield")]
+    #[test_case(vec![
+        (vec!["hscan", "myhash"], "-ERR wrong number of arguments for 'hscan' command\r\n"),
+        (vec!["hscan", "no_such_hash", "0"], "*2\r\n$1\r\n0\r\n*0\r\n"),
+        (vec!["hset", "myhash", "f1", "v1", "f2", "v2", "g1", "v3"], ":3\r\n"),
+        (vec!["hscan", "myhash", "0"], "*2\r\n$1\r\n0\r\n*6\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n$2\r\ng1\r\n$2\r\nv3\r\n"),
+        (vec!["hscan", "myhash", "0", "match", "f*"], "*2\r\n$1\r\n0\r\n*4\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n"),
+        (vec!["hscan", "myhash", "0", "count", "1"], "*2\r\n$4\r\n6631\r\n*2\r\n$2\r\nf1\r\n$2\r\nv1\r\n"),
+        (vec!["hscan", "myhash", "6631", "count", "2"], "*2\r\n$1\r\n0\r\n*4\r\n$2\r\nf2\r\n$2\r\nv2\r\n$2\r\ng1\r\n$2\r\nv3\r\n"),
+        (vec!["hscan", "myhash", "xyz"], "-ERR invalid cursor\r\n"),
+        (vec!["hscan", "myhash", "0", "count", "0"], "-ERR syntax error\r\n"),
+        (vec!["hscan", "myhash", "0", "match", "g*", "count", "18446744073709551615"], "*2\r\n$1\r\n0\r\n*2\r\n$2\r\ng1\r\n$2\r\nv3\r\n"),
+        (vec!["set", "mystr", "value"], "+OK\r\n"),
+        (vec!["hscan", "mystr", "0"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_hscan"; "test_hscan")]
+    #[test_case(vec![
+        (vec!["hsetnx", "myhash", "f1"], "-ERR wrong number of arguments for 'hsetnx' command\r\n"),
+        (vec!["hsetnx", "myhash", "f1", "v1"], ":1\r\n"),
+        (vec!["hsetnx", "myhash", "f1", "v2"], ":0\r\n"),
+        (vec!["hget", "myhash", "f1"], "$2\r\nv1\r\n"),
+        (vec!["hset", "myhash", "f2", "value_2"], ":1\r\n"),
+        (vec!["hsetnx", "myhash", "f2", "v2"], ":0\r\n"),
+        (vec!["hget", "myhash", "f2"], "$7\r\nvalue_2\r\n"),
+        (vec!["hsetnx", "myhash", "f3", "v3"], ":1\r\n"),
+        (vec!["hlen", "myhash"], ":3\r\n"),
+        (vec!["set", "mystr", "value"], "+OK\r\n"),
+        (vec!["hsetnx", "mystr", "f1", "v1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["get", "mystr"], "$5\r\nvalue\r\n"),
+    ], "test_hsetnx"; "test_hsetnx")]
+    #[test_case(vec![
+        (vec!["hstrlen", "myhash"], "-ERR wrong number of arguments for 'hstrlen' command\r\n"),
+        (vec!["hstrlen", "myhash", "f1"], ":0\r\n"),
+        (vec!["hset", "myhash", "f1", "hello world", "f2", ""], ":2\r\n"),
+        (vec!["hstrlen", "myhash", "f1"], ":11\r\n"),
+        (vec!["hstrlen", "myhash", "f2"], ":0\r\n"),
+        (vec!["hstrlen", "myhash", "no_such_field"], ":0\r\n"),
+        (vec!["set", "mystr", "value"], "+OK\r\n"),
+        (vec!["hstrlen", "mystr", "f1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_hstrlen"; "test_hstrlen")]
+    #[test_case(vec![
+        (vec!["hexpire", "myhash", "100", "fields", "1"], "-ERR wrong number of arguments for 'hexpire' command\r\n"),
+        (vec!["hexpire", "nosuchhash", "100", "fields", "2", "f1", "f2"], "*2\r\n:-2\r\n:-2\r\n"),
+        (vec!["hset", "myhash", "f1", "v1", "f2", "v2", "f3", "v3"], ":3\r\n"),
+        (vec!["hexpire", "myhash", "100", "fields", "2", "f1", "nosuchfield"], "*2\r\n:1\r\n:-2\r\n"),
+        (vec!["hexpire", "myhash", "100", "nx", "fields", "2", "f1", "f2"], "*2\r\n:0\r\n:1\r\n"),
+        (vec!["hexpire", "myhash", "200", "xx", "fields", "2", "f1", "f3"], "*2\r\n:1\r\n:0\r\n"),
+        (vec!["hexpire", "myhash", "50", "gt", "fields", "2", "f1", "f3"], "*2\r\n:0\r\n:0\r\n"),
+        (vec!["hexpire", "myhash", "50", "lt", "fields", "2", "f1", "f3"], "*2\r\n:1\r\n:1\r\n"),
+        (vec!["hexpire", "myhash", "0", "fields", "1", "f2"], "*1\r\n:2\r\n"),
+        (vec!["hlen", "myhash"], ":2\r\n"),
+        (vec!["hget", "myhash", "f2"], "$-1\r\n"),
+        (vec!["hexpire", "myhash", "-1", "fields", "1", "f1"], "-ERR invalid expire time in 'hexpire' command\r\n"),
+        (vec!["hexpire", "myhash", "abc", "fields", "1", "f1"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["hexpire", "myhash", "100", "nx", "f1", "1", "f1"], "-ERR Mandatory argument FIELDS is missing or not at the right position\r\n"),
+        (vec!["hexpire", "myhash", "100", "fields", "0", "f1"], "-ERR Parameter `numFields` should be greater than 0\r\n"),
+        (vec!["hexpire", "myhash", "100", "fields", "2", "f1"], "-ERR The `numfields` parameter must match the number of arguments\r\n"),
+        (vec!["set", "mystr", "value"], "+OK\r\n"),
+        (vec!["hexpire", "mystr", "100", "fields", "1", "f1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_hexpire"; "test_hexpire")]
+    #[test_case(vec![
+        (vec!["httl", "myhash", "fields", "1"], "-ERR wrong number of arguments for 'httl' command\r\n"),
+        (vec!["httl", "nosuchhash", "fields", "1", "f1"], "*1\r\n:-2\r\n"),
+        (vec!["hset", "myhash", "f1", "v1", "f2", "v2"], ":2\r\n"),
+        (vec!["hexpire", "myhash", "100", "fields", "1", "f1"], "*1\r\n:1\r\n"),
+        (vec!["httl", "myhash", "fields", "3", "f1", "f2", "nosuchfield"], "*3\r\n:100\r\n:-1\r\n:-2\r\n"),
+        // overriding the field clears its TTL
+        (vec!["hset", "myhash", "f1", "v1"], ":0\r\n"),
+        (vec!["httl", "myhash", "fields", "1", "f1"], "*1\r\n:-1\r\n"),
+        (vec!["httl", "myhash", "f1", "1", "f1"], "-ERR Mandatory argument FIELDS is missing or not at the right position\r\n"),
+        (vec!["set", "mystr", "value"], "+OK\r\n"),
+        (vec!["httl", "mystr", "fields", "1", "f1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_httl"; "test_httl")]
     fn test_hash_commands(
         args: Vec<(Vec<&'static str>, &'static str)>,
         test_name: &str,
@@ -937,6 +1391,241 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_hscan_pages() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // run a command and return its raw response
+            let execute = |args: Vec<String>| {
+                let client = &client;
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_hscan_pages").await;
+                    let args = args.iter().map(|s| BytesMut::from(s.as_str())).collect();
+                    let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                    let ClientNextAction::NoAction =
+                        Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                            .await
+                            .unwrap()
+                    else {
+                        panic!("unexpected client action");
+                    };
+                    sink.read_all_with_size(64 << 10).await
+                }
+            };
+
+            let mut hset = vec!["hset".to_string(), "bighash".to_string()];
+            for i in 0..1000 {
+                hset.push(format!("field_{:04}", i));
+                hset.push(format!("value_{}", i));
+            }
+            assert_eq!(execute(hset).await, ":1000\r\n");
+
+            let mut cursor = SCAN_CURSOR_START.to_string();
+            let mut seen = std::collections::HashSet::<String>::new();
+            let mut deleted = std::collections::HashSet::<String>::new();
+            let mut pages = 0usize;
+            loop {
+                let response = execute(vec![
+                    "hscan".to_string(),
+                    "bighash".to_string(),
+                    cursor.clone(),
+                    "count".to_string(),
+                    "50".to_string(),
+                ])
+                .await;
+                let tokens: Vec<&str> = response.split("\r\n").collect();
+                assert_eq!(tokens[0], "*2");
+                cursor = tokens[2].to_string();
+                let fields: Vec<&str> = tokens[5..]
+                    .iter()
+                    .step_by(2)
+                    .filter(|s| !s.is_empty())
+                    .copied()
+                    .collect();
+                assert!(fields.len() <= 100);
+                for pair in fields.chunks(2) {
+                    let field = pair[0].to_string();
+                    let index: usize = field["field_".len()..].parse().unwrap();
+                    assert_eq!(pair[1], format!("value_{}", index));
+                    assert!(!deleted.contains(&field));
+                    assert!(seen.insert(field));
+                }
+                pages = pages.saturating_add(1);
+                if cursor == SCAN_CURSOR_START {
+                    break;
+                }
+
+                // delete the cursor field and a field that was not visited yet
+                let cursor_field = BytesMutUtils::from_hex(cursor.as_bytes()).unwrap();
+                let cursor_field = String::from_utf8(cursor_field.to_vec()).unwrap();
+                let unvisited_field = format!("field_{:04}", 999 - pages);
+                for field in [cursor_field, unvisited_field] {
+                    execute(vec![
+                        "hdel".to_string(),
+                        "bighash".to_string(),
+                        field.clone(),
+                    ])
+                    .await;
+                    deleted.insert(field);
+                }
+            }
+
+            // every field that was not deleted before being visited is returned exactly once
+            for i in 0..1000 {
+                let field = format!("field_{:04}", i);
+                assert!(
+                    seen.contains(&field) || deleted.contains(&field),
+                    "{}",
+                    field
+                );
+            }
+            assert_eq!(seen.len() + pages - 1, 1000);
+        });
+    }
+
+    #[test]
+    fn test_hrandfield_sampling() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // run a command and return the tokens of its raw response
+            let execute = |args: Vec<String>| {
+                let client = &client;
+                async move {
+                    let mut sink =
+                        crate::tests::ResponseSink::with_name("test_hrandfield_sampling").await;
+                    let args = args.iter().map(|s| BytesMut::from(s.as_str())).collect();
+                    let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                    let ClientNextAction::NoAction =
+                        Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                            .await
+                            .unwrap()
+                    else {
+                        panic!("unexpected client action");
+                    };
+                    let response = sink.read_all_with_size(64 << 10).await;
+                    let tokens: Vec<String> =
+                        response.split("\r\n").map(|s| s.to_string()).collect();
+                    tokens
+                }
+            };
+            let hrandfield = |args: &[&str]| {
+                let mut command = vec!["hrandfield".to_string(), "myhash".to_string()];
+                command.extend(args.iter().map(|s| s.to_string()));
+                command
+            };
+
+            let mut hset = vec!["hset".to_string(), "myhash".to_string()];
+            for i in 0..10 {
+                hset.push(format!("field_{}", i));
+                hset.push(format!("value_{}", i));
+            }
+            assert_eq!(execute(hset).await[0], ":10");
+
+            // positive count: distinct fields
+            for _ in 0..20 {
+                let tokens = execute(hrandfield(&["5"])).await;
+                assert_eq!(tokens[0], "*5");
+                let fields: std::collections::HashSet<&String> =
+                    tokens[1..].iter().skip(1).step_by(2).collect();
+                assert_eq!(fields.len(), 5);
+            }
+
+            // negative count: |count| fields, with duplicates
+            let tokens = execute(hrandfield(&["-50"])).await;
+            assert_eq!(tokens[0], "*50");
+            let fields: Vec<&String> = tokens[1..].iter().skip(1).step_by(2).collect();
+            assert_eq!(fields.len(), 50);
+            assert!(fields.iter().all(|field| field.starts_with("field_")));
+            let distinct: std::collections::HashSet<&&String> = fields.iter().collect();
+            assert!(distinct.len() <= 10);
+
+            // WITHVALUES: every field is followed by its own value
+            for args in [["10", "withvalues"], ["-30", "WITHVALUES"]] {
+                let tokens = execute(hrandfield(&args)).await;
+                let count: usize = args[0].trim_start_matches('-').parse().unwrap();
+                assert_eq!(tokens[0], format!("*{}", count * 2));
+                let items: Vec<&String> = tokens[1..].iter().skip(1).step_by(2).collect();
+                assert_eq!(items.len(), count * 2);
+                for pair in items.chunks(2) {
+                    let index = &pair[0]["field_".len()..];
+                    assert_eq!(pair[1], &format!("value_{}", index));
+                }
+            }
+
+            // no count: a single field
+            let tokens = execute(hrandfield(&[])).await;
+            assert!(tokens[0].starts_with('$'));
+            assert!(tokens[1].starts_with("field_"));
+        });
+    }
+
+    #[test]
+    fn test_expired_field_disappears_from_hgetall() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let execute = |args: Vec<&'static str>| {
+                let client = &client;
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name(
+                        "test_expired_field_disappears_from_hgetall",
+                    )
+                    .await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    let ClientNextAction::NoAction =
+                        Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                            .await
+                            .unwrap()
+                    else {
+                        panic!("unexpected client action");
+                    };
+                    sink.read_all().await
+                }
+            };
+
+            assert_eq!(
+                execute(vec!["hset", "myhash", "f1", "v1", "f2", "v2"]).await,
+                ":2\r\n"
+            );
+            assert_eq!(
+                execute(vec!["hexpire", "myhash", "1", "fields", "1", "f1"]).await,
+                "*1\r\n:1\r\n"
+            );
+            assert_eq!(
+                execute(vec!["hgetall", "myhash"]).await,
+                "*4\r\n$2\r\nf1\r\n$2\r\nv1\r\n$2\r\nf2\r\n$2\r\nv2\r\n"
+            );
+
+            tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+            assert_eq!(
+                execute(vec!["hgetall", "myhash"]).await,
+                "*2\r\n$2\r\nf2\r\n$2\r\nv2\r\n"
+            );
+            assert_eq!(execute(vec!["hlen", "myhash"]).await, ":1\r\n");
+            assert_eq!(
+                execute(vec!["httl", "myhash", "fields", "1", "f1"]).await,
+                "*1\r\n:-2\r\n"
+            );
+
+            // once the last field expires, the hash is gone
+            assert_eq!(
+                execute(vec!["hexpire", "myhash", "1", "fields", "1", "f2"]).await,
+                "*1\r\n:1\r\n"
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+            assert_eq!(execute(vec!["hgetall", "myhash"]).await, "*0\r\n");
+            assert_eq!(execute(vec!["exists", "myhash"]).await, ":0\r\n");
+        });
+    }
+
     #[test]
     fn test_rng_selection() {
         let options = vec![1, 2, 2, 2, 3, 4, 5, 6, 7, 7, 7];
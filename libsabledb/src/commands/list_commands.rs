@@ -181,18 +181,15 @@ impl ListCommands {
 
         let src_list_name = command_arg_at!(command, 1);
         let target_list_name = command_arg_at!(command, 2);
-        let timeout = command_arg_at!(command, 3);
-        let Some(timeout_secs) = BytesMutUtils::parse::<f64>(timeout) else {
-            let builder = RespBuilderV2::default();
-            builder.error_string(
-                &mut response_buffer,
-                "ERR timeout is not a float or out of range",
-            );
-            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        let timeout = match Self::parse_timeout(command_arg_at!(command, 3)) {
+            Ok(timeout) => timeout,
+            Err(msg) => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(&mut response_buffer, msg);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            }
         };
 
-        let timeout_ms = (timeout_secs * 1000.0) as u64; // convert to milliseconds and round it
-
         // same as calling "lmove src target right left"
         Self::lmove_internal(
             client_state,
@@ -201,7 +198,7 @@ impl ListCommands {
             "right",
             "left",
             response_buffer,
-            Some(Duration::from_millis(timeout_ms)),
+            Some(timeout),
         )
         .await
     }
@@ -258,18 +255,15 @@ impl ListCommands {
         let target_list_name = command_arg_at!(command, 2);
         let src_left_or_right = command_arg_at_as_str!(command, 3);
         let target_left_or_right = command_arg_at_as_str!(command, 4);
-        let timeout = command_arg_at!(command, 5);
-        let Some(timeout_secs) = BytesMutUtils::parse::<f64>(timeout) else {
-            let builder = RespBuilderV2::default();
-            builder.error_string(
-                &mut response_buffer,
-                "ERR timeout is not a float or out of range",
-            );
-            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        let timeout = match Self::parse_timeout(command_arg_at!(command, 5)) {
+            Ok(timeout) => timeout,
+            Err(msg) => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(&mut response_buffer, msg);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            }
         };
 
-        let timeout_ms = (timeout_secs * 1000.0) as u64; // convert to milliseconds and round it
-
         Self::lmove_internal(
             client_state,
             src_list_name,
@@ -277,7 +271,7 @@ impl ListCommands {
             &src_left_or_right,
             &target_left_or_right,
             response_buffer,
-            Some(Duration::from_millis(timeout_ms)),
+            Some(timeout),
         )
         .await
     }
@@ -293,7 +287,7 @@ impl ListCommands {
     ) -> Result<HandleCommandResult, SableError> {
         let (src_flags, target_flags) = match (src_left_or_right, target_left_or_right) {
             ("left", "left") => (ListFlags::FromLeft, ListFlags::FromLeft),
-            ("right", "right") => (ListFlags::FromRight, ListFlags::FromLeft),
+            ("right", "right") => (ListFlags::FromRight, ListFlags::FromRight),
             ("left", "right") => (ListFlags::FromLeft, ListFlags::FromRight),
             ("right", "left") => (ListFlags::FromRight, ListFlags::FromLeft),
             (_, _) => {
@@ -303,7 +297,8 @@ impl ListCommands {
             }
         };
 
-        // lock both keys
+        // lock both keys (the locks are sorted internally, so two moves in opposite directions
+        // can't deadlock, and the same key is locked once)
         let keys = vec![src_list_name, target_list_name];
         let _unused = LockManager::lock_user_keys_exclusive(&keys, client_state.database_id());
 
@@ -328,11 +323,10 @@ impl ListCommands {
             }
             MoveResult::None => {
                 if let Some(blocking_duration) = blocking_duration {
-                    let interersting_keys: Vec<BytesMut> =
-                        keys.iter().map(|e| (*e).clone()).collect();
+                    // only a push to the source can satisfy the move
                     let rx = client_state
                         .server_inner_state()
-                        .block_client(&interersting_keys)
+                        .block_client(&[src_list_name.clone()])
                         .await;
                     Ok(HandleCommandResult::Blocked((rx, blocking_duration)))
                 } else {
@@ -359,122 +353,72 @@ impl ListCommands {
         );
 
         let builder = RespBuilderV2::default();
-        let mut iter = command.args_vec().iter();
-        iter.next(); // skip the command
-        let (numkeys, timeout_ms) = if allow_blocking {
-            let Some(timeout_secs) = command.arg_as_number::<f64>(1) else {
-                builder.error_string(&mut response_buffer, "ERR numkeys should be greater than 0");
-                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-            };
-
-            let Some(numkeys) = command.arg_as_number::<usize>(2) else {
-                builder.error_string(&mut response_buffer, "ERR numkeys should be greater than 0");
-                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-            };
-            iter.next(); // timeout
-            iter.next(); // numkeys
-            (numkeys, (timeout_secs * 1000.0) as usize)
+        // BLMPOP has the timeout before `numkeys`
+        let numkeys_pos = if allow_blocking { 2 } else { 1 };
+        let timeout = if allow_blocking {
+            match Self::parse_timeout(command_arg_at!(command, 1)) {
+                Ok(timeout) => timeout,
+                Err(msg) => {
+                    builder.error_string(&mut response_buffer, msg);
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                }
+            }
         } else {
-            let Some(numkeys) = command.arg_as_number::<usize>(1) else {
-                builder.error_string(&mut response_buffer, "ERR numkeys should be greater than 0");
-                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-            };
-            iter.next(); // numkeys
-            (numkeys, 0)
+            Duration::ZERO
         };
 
-        if numkeys == 0 {
+        let Some(numkeys) = command
+            .arg_as_number::<i64>(numkeys_pos)
+            .filter(|numkeys| *numkeys > 0)
+            .map(|numkeys| numkeys as usize)
+        else {
             builder.error_string(&mut response_buffer, "ERR numkeys should be greater than 0");
             return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-        }
-
-        let mut keys = Vec::<&BytesMut>::with_capacity(numkeys);
-        enum State {
-            CollectingKeys,
-            RightOrLeft,
-            Count,
-        }
-
-        let mut state = State::CollectingKeys;
-        let mut list_flags: Option<ListFlags> = None;
-        let mut count = 1;
-        while let Some(arg) = iter.next() {
-            match state {
-                State::CollectingKeys => {
-                    keys.push(arg);
-                    if keys.len() == numkeys {
-                        state = State::RightOrLeft;
-                    }
-                }
-                State::RightOrLeft => {
-                    let argstr = BytesMutUtils::to_string(arg).to_lowercase();
-                    match argstr.as_str() {
-                        "left" => {
-                            list_flags = Some(ListFlags::FromLeft);
-                        }
-                        "right" => {
-                            list_flags = Some(ListFlags::FromRight);
-                        }
-                        _ => {
-                            builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
-                            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-                        }
-                    }
-                    state = State::Count;
-                }
-                State::Count => {
-                    let argstr = BytesMutUtils::to_string(arg).to_lowercase();
-                    match argstr.as_str() {
-                        "count" => {
-                            let Some(elements_to_pop) = iter.next() else {
-                                builder
-                                    .error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
-                                return Ok(HandleCommandResult::ResponseBufferUpdated(
-                                    response_buffer,
-                                ));
-                            };
-
-                            let Some(elements_to_pop) =
-                                BytesMutUtils::parse::<usize>(elements_to_pop)
-                            else {
-                                builder
-                                    .error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
-                                return Ok(HandleCommandResult::ResponseBufferUpdated(
-                                    response_buffer,
-                                ));
-                            };
-
-                            if elements_to_pop == 0 {
-                                builder.error_string(
-                                    &mut response_buffer,
-                                    "ERR count should be greater than 0",
-                                );
-                                return Ok(HandleCommandResult::ResponseBufferUpdated(
-                                    response_buffer,
-                                ));
-                            }
-                            count = elements_to_pop;
-                            break; // Parsing is completed
-                        }
-                        _ => {
-                            builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
-                            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-                        }
-                    }
-                }
-            }
-        }
+        };
 
-        if keys.len() != numkeys {
+        // the keys are followed by the direction
+        let direction_pos = numkeys_pos.saturating_add(numkeys).saturating_add(1);
+        let Some(direction) = command.arg_as_lowercase_string(direction_pos) else {
             builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
             return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-        }
+        };
+        let list_flags = match direction.as_str() {
+            "left" => ListFlags::FromLeft,
+            "right" => ListFlags::FromRight,
+            _ => {
+                builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            }
+        };
 
-        let Some(list_flags) = list_flags else {
-            builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
-            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        // optional `COUNT count`, nothing else may follow
+        let count = match command.arg_count() - direction_pos - 1 {
+            0 => 1,
+            2 if command
+                .arg_as_lowercase_string(direction_pos + 1)
+                .as_deref()
+                == Some("count") =>
+            {
+                let Some(count) = command
+                    .arg_as_number::<i64>(direction_pos + 2)
+                    .filter(|count| *count > 0)
+                else {
+                    builder
+                        .error_string(&mut response_buffer, "ERR count should be greater than 0");
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                };
+                count as usize
+            }
+            _ => {
+                builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            }
         };
 
+        let keys: Vec<&BytesMut> = command.args_vec()[numkeys_pos + 1..direction_pos]
+            .iter()
+            .collect();
+
         let _unused = LockManager::lock_user_keys_exclusive(&keys, client_state.database_id());
         let list = List::with_storage(client_state.database(), client_state.database_id());
         match list.multi_pop(&keys, count, list_flags)? {
@@ -496,10 +440,7 @@ impl ListCommands {
                 if allow_blocking {
                     let keys: Vec<BytesMut> = keys.iter().map(|x| (*x).clone()).collect();
                     let rx = client_state.server_inner_state().block_client(&keys).await;
-                    Ok(HandleCommandResult::Blocked((
-                        rx,
-                        Duration::from_millis(timeout_ms as u64),
-                    )))
+                    Ok(HandleCommandResult::Blocked((rx, timeout)))
                 } else {
                     builder.null_string(&mut response_buffer);
                     Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
@@ -525,16 +466,36 @@ impl ListCommands {
             HandleCommandResult::ResponseBufferUpdated(response_buffer)
         );
 
-        let key = command_arg_at!(command, 1);
-        let count = if command.arg_count() == 3 {
-            to_number!(
-                command_arg_at!(command, 2),
-                usize,
+        let builder = RespBuilderV2::default();
+        if command.arg_count() > 3 {
+            builder.error_string(
                 &mut response_buffer,
-                Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
-            )
-        } else {
-            1usize
+                &format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    command.main_command()
+                ),
+            );
+            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        }
+
+        let key = command_arg_at!(command, 1);
+        let count = match command.arg(2) {
+            None => None,
+            Some(count) => match BytesMutUtils::parse::<i64>(count) {
+                None => {
+                    builder.error_string(
+                        &mut response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                }
+                Some(count) if count < 0 => {
+                    builder
+                        .error_string(&mut response_buffer, ErrorStrings::VALUE_MUST_BE_POSITIVE);
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                }
+                Some(count) => Some(count as usize),
+            },
         };
 
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
@@ -595,6 +556,15 @@ impl ListCommands {
             return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
         }
 
+        let timeout = match Self::parse_timeout(timeout) {
+            Ok(timeout) => timeout,
+            Err(msg) => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(&mut response_buffer, msg);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            }
+        };
+
         // Try to pop an element from one of the lists
         // if all the lists are empty, block the client
         let _unused = LockManager::lock_user_keys_exclusive(&lists, client_state.database_id());
@@ -609,19 +579,7 @@ impl ListCommands {
             let rx = client_state.server_inner_state().block_client(&keys).await;
 
             // Notify the caller
-            let Some(timeout_secs) = BytesMutUtils::parse::<f64>(timeout) else {
-                let builder = RespBuilderV2::default();
-                builder.error_string(
-                    &mut response_buffer,
-                    "ERR timeout is not a float or out of range",
-                );
-                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
-            };
-            let timeout_ms = (timeout_secs * 1000.0) as u64; // convert to milliseconds and round it
-            Ok(HandleCommandResult::Blocked((
-                rx,
-                std::time::Duration::from_millis(timeout_ms),
-            )))
+            Ok(HandleCommandResult::Blocked((rx, timeout)))
         }
     }
 
@@ -643,16 +601,21 @@ impl ListCommands {
         let key = command_arg_at!(command, 1);
         let start = to_number!(
             command_arg_at!(command, 2),
-            i32,
+            i64,
             &mut response_buffer,
             Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
         );
         let end = to_number!(
             command_arg_at!(command, 3),
-            i32,
+            i64,
             &mut response_buffer,
             Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
         );
+
+        // out of range bounds are clamped anyway, so this does not change the result
+        let start = start.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        let end = end.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
 
         let list = List::with_storage(client_state.database(), client_state.database_id());
@@ -818,8 +781,13 @@ impl ListCommands {
 
         // parse the command line arguments
         let builder = RespBuilderV2::default();
-        while let (Some(arg), Some(value)) = (iter.next(), iter.next()) {
-            let keyword_lowercase = BytesMutUtils::to_string(arg);
+        while let Some(arg) = iter.next() {
+            // every option comes with a value
+            let Some(value) = iter.next() else {
+                builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+            };
+            let keyword_lowercase = BytesMutUtils::to_string(arg).to_lowercase();
             match keyword_lowercase.as_str() {
                 "rank" => {
                     let value = to_number!(
@@ -944,6 +912,19 @@ impl ListCommands {
         list.remove(key, Some(element), count, &mut response_buffer)?;
         Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
     }
+
+    /// Parse the timeout of a blocking command: a float number of seconds, e.g. `0.1`. A
+    /// timeout of `0` blocks forever. On error, the error message is returned
+    fn parse_timeout(value: &BytesMut) -> Result<Duration, &'static str> {
+        let Some(timeout_secs) = BytesMutUtils::parse_float(value).filter(|v| v.is_finite()) else {
+            return Err("ERR timeout is not a float or out of range");
+        };
+        if timeout_secs < 0.0 {
+            return Err("ERR timeout is negative");
+        }
+        Duration::try_from_secs_f64(timeout_secs)
+            .map_err(|_| "ERR timeout is not a float or out of range")
+    }
 }
 
 //  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
@@ -992,6 +973,16 @@ mod tests {
         (vec!["lpop", "lpop_mykey"], "$-1\r\n"),
         (vec!["rpush", "lpop_mykey", "foo", "bar", "baz", "apple"], ":4\r\n"),
         (vec!["lpop", "lpop_mykey"], "$3\r\nfoo\r\n"),
+        (vec!["lpop", "lpop_mykey", "1"], "*1\r\n$3\r\nbar\r\n"),
+        (vec!["lpop", "lpop_mykey", "0"], "*0\r\n"),
+        (vec!["lpop", "lpop_mykey", "-1"], "-ERR value is out of range, must be positive\r\n"),
+        (vec!["lpop", "lpop_mykey", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["lpop", "lpop_mykey", "1", "2"], "-ERR wrong number of arguments for 'lpop' command\r\n"),
+        // a count larger than the list pops all the items and deletes the list
+        (vec!["lpop", "lpop_mykey", "10"], "*2\r\n$3\r\nbaz\r\n$5\r\napple\r\n"),
+        (vec!["exists", "lpop_mykey"], ":0\r\n"),
+        (vec!["lpop", "lpop_mykey", "10"], "*-1\r\n"),
+        (vec!["lpop", "lpop_mykey", "0"], "*-1\r\n"),
         ], "lpop"; "lpop")]
     #[test_case(vec![
         (vec!["rpush", "rpop_mykey", "foo", "bar", "baz", "apple"], ":4\r\n"),
@@ -1000,6 +991,9 @@ mod tests {
         (vec!["rpop", "rpop_mykey"], "$-1\r\n"),
         (vec!["lpush", "rpop_mykey", "foo", "bar", "baz", "apple"], ":4\r\n"),
         (vec!["rpop", "rpop_mykey"], "$3\r\nfoo\r\n"),
+        (vec!["rpop", "rpop_mykey", "5"], "*3\r\n$3\r\nbar\r\n$3\r\nbaz\r\n$5\r\napple\r\n"),
+        (vec!["rpop", "rpop_mykey"], "$-1\r\n"),
+        (vec!["rpop", "rpop_mykey", "5"], "*-1\r\n"),
         ], "rpop"; "rpop")]
     #[test_case(vec![
         (vec!["lpush", "len_key", "foo", "bar", "baz", "apple"], ":4\r\n"),
@@ -1044,6 +1038,21 @@ mod tests {
         (vec!["lpos", "lpos_list", "x", "rank", "-2", "count", "2"], "*2\r\n:6\r\n:4\r\n"),
         (vec!["lpos", "lpos_list", "x", "rank", "2", "count", "2"], "*2\r\n:2\r\n:4\r\n"),
         (vec!["lpos", "lpos_list", "x", "rank", "not_a_number"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["lpos", "lpos_list", "x", "RANK", "-1", "COUNT", "0"], "*5\r\n:8\r\n:6\r\n:4\r\n:2\r\n:0\r\n"),
+        (vec!["lpos", "lpos_list", "x", "rank", "-3", "count", "0"], "*3\r\n:4\r\n:2\r\n:0\r\n"),
+        (vec!["lpos", "lpos_list", "x", "rank", "6"], "$-1\r\n"),
+        (vec!["lpos", "lpos_list", "x", "rank", "6", "count", "0"], "*0\r\n"),
+        (vec!["lpos", "lpos_list", "hellow", "count", "2"], "*0\r\n"),
+        (vec!["lpos", "lpos_no_such_list", "x", "count", "2"], "*0\r\n"),
+        // MAXLEN limits the number of items compared
+        (vec!["lpos", "lpos_list", "y", "maxlen", "1"], "$-1\r\n"),
+        (vec!["lpos", "lpos_list", "y", "maxlen", "2"], ":1\r\n"),
+        (vec!["lpos", "lpos_list", "x", "count", "0", "maxlen", "5"], "*3\r\n:0\r\n:2\r\n:4\r\n"),
+        (vec!["lpos", "lpos_list", "x", "rank", "-1", "count", "0", "maxlen", "4"], "*2\r\n:8\r\n:6\r\n"),
+        (vec!["lpos", "lpos_list", "x", "count", "0", "maxlen", "0"], "*5\r\n:0\r\n:2\r\n:4\r\n:6\r\n:8\r\n"),
+        (vec!["lpos", "lpos_list", "x", "maxlen", "-1"], "-ERR MAXLEN can't be negative\r\n"),
+        (vec!["lpos", "lpos_list", "x", "count"], "-ERR syntax error\r\n"),
+        (vec!["lpos", "lpos_list", "x", "foo", "1"], "-ERR syntax error\r\n"),
         ], "lpos"; "lpos")]
     #[test_case(vec![
         (vec!["rpush", "ltrim_list", "one", "two", "three"], ":3\r\n"),
@@ -1052,7 +1061,43 @@ mod tests {
         (vec!["llen", "ltrim_list"], ":2\r\n"),
         (vec!["lpos", "ltrim_list", "one"], "$-1\r\n"),
         (vec!["ltrim", "ltrim_list_not_existing", "1", "2"], "+OK\r\n"),
+        (vec!["exists", "ltrim_list_not_existing"], ":0\r\n"),
+        (vec!["ltrim", "ltrim_list", "a", "2"], "-ERR value is not an integer or out of range\r\n"),
         ], "ltrim"; "ltrim")]
+    #[test_case(vec![
+        (vec!["rpush", "ltrim_list", "a", "b", "c", "d", "e"], ":5\r\n"),
+        // keep a middle slice
+        (vec!["ltrim", "ltrim_list", "1", "3"], "+OK\r\n"),
+        (vec!["lrange", "ltrim_list", "0", "-1"], "*3\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        (vec!["lindex", "ltrim_list", "0"], "$1\r\nb\r\n"),
+        (vec!["lindex", "ltrim_list", "-1"], "$1\r\nd\r\n"),
+        // out of range bounds are clamped
+        (vec!["ltrim", "ltrim_list", "-100", "100"], "+OK\r\n"),
+        (vec!["ltrim", "ltrim_list", "0", "99999999999"], "+OK\r\n"),
+        (vec!["llen", "ltrim_list"], ":3\r\n"),
+        // start > stop deletes the key
+        (vec!["ltrim", "ltrim_list", "2", "1"], "+OK\r\n"),
+        (vec!["exists", "ltrim_list"], ":0\r\n"),
+        // start beyond the end of the list deletes the key
+        (vec!["rpush", "ltrim_list", "a", "b", "c"], ":3\r\n"),
+        (vec!["ltrim", "ltrim_list", "5", "10"], "+OK\r\n"),
+        (vec!["exists", "ltrim_list"], ":0\r\n"),
+        ], "ltrim_ranges"; "ltrim_ranges")]
+    #[test_case(vec![
+        (vec!["rpush", "ltrim_list", "a", "b", "c", "d", "e"], ":5\r\n"),
+        // negative bounds count from the tail
+        (vec!["ltrim", "ltrim_list", "-4", "-2"], "+OK\r\n"),
+        (vec!["lrange", "ltrim_list", "0", "-1"], "*3\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        (vec!["ltrim", "ltrim_list", "-1", "-1"], "+OK\r\n"),
+        (vec!["lrange", "ltrim_list", "0", "-1"], "*1\r\n$1\r\nd\r\n"),
+        // the list is still usable from both ends
+        (vec!["lpush", "ltrim_list", "x"], ":2\r\n"),
+        (vec!["rpush", "ltrim_list", "y"], ":3\r\n"),
+        (vec!["lrange", "ltrim_list", "0", "-1"], "*3\r\n$1\r\nx\r\n$1\r\nd\r\n$1\r\ny\r\n"),
+        // a negative stop before the start deletes the key
+        (vec!["ltrim", "ltrim_list", "0", "-10"], "+OK\r\n"),
+        (vec!["exists", "ltrim_list"], ":0\r\n"),
+        ], "ltrim_negative"; "ltrim_negative")]
     #[test_case(vec![
         (vec!["rpush", "lrange_list", "one", "two", "three"], ":3\r\n"),
         (vec!["lrange", "lrange_list", "1", "-1"], "*2\r\n$3\r\ntwo\r\n$5\r\nthree\r\n"),
@@ -1099,7 +1144,7 @@ mod tests {
         (vec!["lmove", "lmove_list1", "lmove_list2", "right", "right"], "$1\r\na\r\n"),
         (vec!["llen", "lmove_list1"], ":0\r\n"),
         (vec!["llen", "lmove_list2"], ":6\r\n"),
-        (vec!["lrange", "lmove_list2", "0", "-1"], "*6\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\n1\r\n$1\r\n2\r\n$1\r\n3\r\n"),
+        (vec!["lrange", "lmove_list2", "0", "-1"], "*6\r\n$1\r\n1\r\n$1\r\n2\r\n$1\r\n3\r\n$1\r\nc\r\n$1\r\nb\r\n$1\r\na\r\n"),
         ], "lmove4"; "lmove4")]
     #[test_case(vec![
         (vec!["set", "lmove_list1", "a"], "+OK\r\n"),
@@ -1107,6 +1152,29 @@ mod tests {
         (vec!["lmove", "lmove_list1", "lmove_list2", "right", "right"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
         (vec!["lmove", "lmove_list1", "lmove_list2", "ssdsd", "right"], "-ERR syntax error\r\n"),
         ], "lmove5"; "lmove5")]
+    #[test_case(vec![
+        (vec!["rpush", "rotate_list", "a", "b", "c"], ":3\r\n"),
+        // same source and destination: the list is rotated
+        (vec!["lmove", "rotate_list", "rotate_list", "left", "right"], "$1\r\na\r\n"),
+        (vec!["lrange", "rotate_list", "0", "-1"], "*3\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\na\r\n"),
+        (vec!["lmove", "rotate_list", "rotate_list", "RIGHT", "LEFT"], "$1\r\na\r\n"),
+        (vec!["lrange", "rotate_list", "0", "-1"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["rpoplpush", "rotate_list", "rotate_list"], "$1\r\nc\r\n"),
+        (vec!["lrange", "rotate_list", "0", "-1"], "*3\r\n$1\r\nc\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        // popping and pushing on the same side leaves the list as is
+        (vec!["lmove", "rotate_list", "rotate_list", "left", "left"], "$1\r\nc\r\n"),
+        (vec!["lrange", "rotate_list", "0", "-1"], "*3\r\n$1\r\nc\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        (vec!["llen", "rotate_list"], ":3\r\n"),
+        // a single item list
+        (vec!["rpush", "single_list", "x"], ":1\r\n"),
+        (vec!["lmove", "single_list", "single_list", "left", "right"], "$1\r\nx\r\n"),
+        (vec!["lrange", "single_list", "0", "-1"], "*1\r\n$1\r\nx\r\n"),
+        // a missing source returns nil and does not touch the destination
+        (vec!["lmove", "no_such_list", "rotate_list", "left", "right"], "$-1\r\n"),
+        (vec!["lmove", "no_such_list", "new_list", "left", "right"], "$-1\r\n"),
+        (vec!["exists", "new_list"], ":0\r\n"),
+        (vec!["llen", "rotate_list"], ":3\r\n"),
+        ], "lmove_same_key"; "lmove_same_key")]
     #[test_case(vec![
         (vec!["rpush", "list1", "a", "b", "c"], ":3\r\n"),
         (vec!["rpush", "list2", "1", "2", "3"], ":3\r\n"),
@@ -1130,14 +1198,19 @@ mod tests {
         (vec!["blpop", "blpop_list2", "blpop_list1", "1"], "*2\r\n$11\r\nblpop_list1\r\n$1\r\na\r\n"),
         (vec!["blpop", "blpop_list2", "blpop_list1", "1"], "*2\r\n$11\r\nblpop_list1\r\n$1\r\nb\r\n"),
         (vec!["blpop", "blpop_list2", "blpop_list1", "1"], "*2\r\n$11\r\nblpop_list1\r\n$1\r\nc\r\n"),
-        (vec!["blpop", "blpop_list2", "blpop_list1", "1"], "$-1\r\n"), // timeout
+        (vec!["blpop", "blpop_list2", "blpop_list1", "1"], "*-1\r\n"), // timeout
+        (vec!["blpop", "blpop_list2", "blpop_list1", "0.2"], "*-1\r\n"), // fractional timeout
+        (vec!["blpop", "blpop_list1", "-1"], "-ERR timeout is negative\r\n"),
+        (vec!["blpop", "blpop_list1", "abc"], "-ERR timeout is not a float or out of range\r\n"),
+        (vec!["blpop", "blpop_list1", "inf"], "-ERR timeout is not a float or out of range\r\n"),
         ], "blpop"; "blpop")]
     #[test_case(vec![
         (vec!["rpush", "brpop_list1", "a", "b", "c"], ":3\r\n"),
         (vec!["brpop", "brpop_list2", "brpop_list1", "1"], "*2\r\n$11\r\nbrpop_list1\r\n$1\r\nc\r\n"),
         (vec!["brpop", "brpop_list2", "brpop_list1", "1"], "*2\r\n$11\r\nbrpop_list1\r\n$1\r\nb\r\n"),
         (vec!["brpop", "brpop_list2", "brpop_list1", "1"], "*2\r\n$11\r\nbrpop_list1\r\n$1\r\na\r\n"),
-        (vec!["brpop", "brpop_list2", "brpop_list1", "1"], "$-1\r\n"), // timeout
+        (vec!["brpop", "brpop_list2", "brpop_list1", "1"], "*-1\r\n"), // timeout
+        (vec!["brpop", "brpop_list2", "brpop_list1", "0.2"], "*-1\r\n"), // fractional timeout
         ], "brpop"; "brpop")]
     #[test_case(vec![
         (vec!["rpush", "list1", "a", "b", "c"], ":3\r\n"),
@@ -1147,7 +1220,16 @@ mod tests {
         (vec!["lmpop", "2", "list1", "list2", "right"], "*2\r\n$5\r\nlist1\r\n*1\r\n$1\r\nc\r\n"),
         (vec!["lmpop", "2", "list1", "list2", "left"], "*2\r\n$5\r\nlist1\r\n*1\r\n$1\r\na\r\n"),
         (vec!["lmpop", "2", "list1", "list2", "left", "COUNT"], "-ERR syntax error\r\n"),
-        (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "bla"], "-ERR syntax error\r\n"),
+        (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "bla"], "-ERR count should be greater than 0\r\n"),
+        (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "0"], "-ERR count should be greater than 0\r\n"),
+        (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "1", "COUNT", "1"], "-ERR syntax error\r\n"),
+        (vec!["lmpop", "2", "list1", "list2", "left", "extra"], "-ERR syntax error\r\n"),
+        (vec!["lmpop", "-1", "list1", "list2", "left"], "-ERR numkeys should be greater than 0\r\n"),
+        (vec!["lmpop", "0", "list1", "left"], "-ERR numkeys should be greater than 0\r\n"),
+        // numkeys does not match the number of keys
+        (vec!["lmpop", "3", "list1", "list2", "left"], "-ERR syntax error\r\n"),
+        (vec!["lmpop", "1", "list1", "list2", "left"], "-ERR syntax error\r\n"),
+        (vec!["lmpop", "100", "list1", "list2", "left"], "-ERR syntax error\r\n"),
         (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "1"], "*2\r\n$5\r\nlist1\r\n*1\r\n$1\r\nb\r\n"),
         (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "1"], "*2\r\n$5\r\nlist2\r\n*1\r\n$1\r\nd\r\n"),
         (vec!["lmpop", "2", "list1", "list2", "left", "COUNT", "1"], "$-1\r\n"),
@@ -1165,6 +1247,22 @@ mod tests {
         (vec!["linsert", "list1", "before", "a", "_"], ":6\r\n"),
         // [_, a, b, b.1, c, d]
         (vec!["lrange", "list1", "0", "-1"], "*6\r\n$1\r\n_\r\n$1\r\na\r\n$1\r\nb\r\n$3\r\nb.1\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        // the new head and tail are reachable from both ends
+        (vec!["lindex", "list1", "0"], "$1\r\n_\r\n"),
+        (vec!["lpop", "list1"], "$1\r\n_\r\n"),
+        (vec!["rpop", "list1"], "$1\r\nd\r\n"),
+        (vec!["linsert", "list1", "AFTER", "c", "e"], ":5\r\n"),
+        (vec!["rpop", "list1"], "$1\r\ne\r\n"),
+        // pivot not found
+        (vec!["linsert", "list1", "before", "no_such_pivot", "x"], ":-1\r\n"),
+        (vec!["llen", "list1"], ":4\r\n"),
+        // the first occurrence of the pivot is used
+        (vec!["rpush", "list2", "x", "y", "x"], ":3\r\n"),
+        (vec!["linsert", "list2", "before", "x", "z"], ":4\r\n"),
+        (vec!["linsert", "list2", "after", "x", "w"], ":5\r\n"),
+        (vec!["lrange", "list2", "0", "-1"], "*5\r\n$1\r\nz\r\n$1\r\nx\r\n$1\r\nw\r\n$1\r\ny\r\n$1\r\nx\r\n"),
+        (vec!["set", "string", "value"], "+OK\r\n"),
+        (vec!["linsert", "string", "before", "x", "z"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
         ], "linsert"; "linsert")]
     #[test_case(vec![
         (vec!["blmove", "blmove_src", "blmove_target", "left", "left", "0.1"], "$-1\r\n"),
@@ -1178,14 +1276,18 @@ mod tests {
     #[test_case(vec![
         (vec!["rpush", "blmpop_list1", "a", "b", "c"], ":3\r\n"),
         (vec!["rpush", "blmpop_list2", "d"], ":1\r\n"),
-        (vec!["blmpop", "not_anumber", "blmpop_list1", "blmpop_list1", "left"], "-ERR numkeys should be greater than 0\r\n"),
+        (vec!["blmpop", "1", "not_anumber", "blmpop_list1", "blmpop_list1", "left"], "-ERR numkeys should be greater than 0\r\n"),
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "sdsd"], "-ERR syntax error\r\n"),
+        (vec!["blmpop", "not_anumber", "2", "blmpop_list1", "blmpop_list2", "left"], "-ERR timeout is not a float or out of range\r\n"),
+        (vec!["blmpop", "-1", "2", "blmpop_list1", "blmpop_list2", "left"], "-ERR timeout is negative\r\n"),
+        (vec!["blmpop", "1", "3", "blmpop_list1", "blmpop_list2", "left"], "-ERR syntax error\r\n"),
+        (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "left", "COUNT", "-2"], "-ERR count should be greater than 0\r\n"),
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "right", "COUNT", "1"], "*2\r\n$12\r\nblmpop_list1\r\n*1\r\n$1\r\nc\r\n"),
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "left", "COUNT", "1"], "*2\r\n$12\r\nblmpop_list1\r\n*1\r\n$1\r\na\r\n"),
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "right", "COUNT", "1"], "*2\r\n$12\r\nblmpop_list1\r\n*1\r\n$1\r\nb\r\n"),
         // pop from the second list now ("d")
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "right", "COUNT", "1"], "*2\r\n$12\r\nblmpop_list2\r\n*1\r\n$1\r\nd\r\n"),
-        (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "right", "COUNT", "1"], "$-1\r\n"),
+        (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "right", "COUNT", "1"], "*-1\r\n"), // timeout
         (vec!["blmpop", "1", "2", "blmpop_list1", "blmpop_list2", "left", "COUNT"], "-ERR syntax error\r\n"),
         ], "blmpop"; "blmpop")]
     fn test_list_commands(args_vec: Vec<(Vec<&'static str>, &'static str)>, test_name: &str) {
@@ -1226,10 +1328,7 @@ mod tests {
                                 execute_command(client.inner(), cmd.clone()).await
                             }
                             crate::client::WaitResult::Timeout => {
-                                let builder = RespBuilderV2::default();
-                                let mut response = BytesMut::new();
-                                builder.null_string(&mut response);
-                                response
+                                Client::handle_timeout(client.inner(), cmd.clone()).unwrap()
                             }
                         };
                         assert_eq!(BytesMutUtils::to_string(&response).as_str(), expected_value);
@@ -1320,4 +1419,175 @@ mod tests {
             }
         });
     }
+
+    #[test]
+    fn test_blocking_move() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+
+            let server = Arc::<ServerState>::default();
+            let reader = Client::new(server.clone(), store.clone(), None);
+            let writer = Client::new(server, store, None);
+
+            // the source list is empty: the client is blocked
+            let move_cmd = Rc::new(RedisCommand::for_test(vec![
+                "blmove",
+                "test_blocking_move_src",
+                "test_blocking_move_target",
+                "left",
+                "right",
+                "5",
+            ]));
+            let (rx, duration) = deferred_command(reader.inner(), move_cmd.clone()).await;
+
+            // second connection: push data to the source list
+            let push_cmd = Rc::new(RedisCommand::for_test(vec![
+                "rpush",
+                "test_blocking_move_src",
+                "value",
+            ]));
+            let response = execute_command(writer.inner(), push_cmd).await;
+            assert_eq!(":1\r\n", BytesMutUtils::to_string(&response).as_str());
+
+            // the blocked client is woken up and completes the move
+            assert_eq!(
+                Client::wait_for(rx, duration).await,
+                crate::client::WaitResult::TryAgain
+            );
+            let response = execute_command(reader.inner(), move_cmd).await;
+            assert_eq!(
+                "$5\r\nvalue\r\n",
+                BytesMutUtils::to_string(&response).as_str()
+            );
+
+            let lrange_cmd = Rc::new(RedisCommand::for_test(vec![
+                "lrange",
+                "test_blocking_move_target",
+                "0",
+                "-1",
+            ]));
+            let response = execute_command(writer.inner(), lrange_cmd).await;
+            assert_eq!(
+                "*1\r\n$5\r\nvalue\r\n",
+                BytesMutUtils::to_string(&response).as_str()
+            );
+
+            let exists_cmd = Rc::new(RedisCommand::for_test(vec![
+                "exists",
+                "test_blocking_move_src",
+            ]));
+            let response = execute_command(writer.inner(), exists_cmd).await;
+            assert_eq!(":0\r\n", BytesMutUtils::to_string(&response).as_str());
+        });
+    }
+
+    #[test]
+    fn test_blocking_pop_wakes_one_client_per_element() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+
+            let server = Arc::<ServerState>::default();
+            let reader1 = Client::new(server.clone(), store.clone(), None);
+            let reader2 = Client::new(server.clone(), store.clone(), None);
+            let writer = Client::new(server, store, None);
+
+            // both clients are blocked on the list, the second one on another key as well
+            let read_cmd1 = Rc::new(RedisCommand::for_test(vec![
+                "blpop",
+                "test_fairness_list1",
+                "1",
+            ]));
+            let read_cmd2 = Rc::new(RedisCommand::for_test(vec![
+                "blpop",
+                "test_fairness_list2",
+                "test_fairness_list1",
+                "1",
+            ]));
+            let (rx1, duration1) = deferred_command(reader1.inner(), read_cmd1.clone()).await;
+            let (rx2, duration2) = deferred_command(reader2.inner(), read_cmd2.clone()).await;
+
+            // a single element: only the first blocked client is served
+            let push_cmd = Rc::new(RedisCommand::for_test(vec![
+                "lpush",
+                "test_fairness_list1",
+                "value",
+            ]));
+            let response = execute_command(writer.inner(), push_cmd).await;
+            assert_eq!(":1\r\n", BytesMutUtils::to_string(&response).as_str());
+
+            let (result1, result2) = tokio::join!(
+                Client::wait_for(rx1, duration1),
+                Client::wait_for(rx2, duration2)
+            );
+            assert_eq!(result1, crate::client::WaitResult::TryAgain);
+            assert_eq!(result2, crate::client::WaitResult::Timeout);
+
+            let response = execute_command(reader1.inner(), read_cmd1).await;
+            assert_eq!(
+                "*2\r\n$19\r\ntest_fairness_list1\r\n$5\r\nvalue\r\n",
+                BytesMutUtils::to_string(&response).as_str()
+            );
+
+            // the second client timed out: a push to its other key must not be lost on it.
+            // Block it again, this time it gets the element
+            let (rx2, duration2) = deferred_command(reader2.inner(), read_cmd2.clone()).await;
+            let push_cmd = Rc::new(RedisCommand::for_test(vec![
+                "lpush",
+                "test_fairness_list2",
+                "value2",
+            ]));
+            let response = execute_command(writer.inner(), push_cmd).await;
+            assert_eq!(":1\r\n", BytesMutUtils::to_string(&response).as_str());
+            assert_eq!(
+                Client::wait_for(rx2, duration2).await,
+                crate::client::WaitResult::TryAgain
+            );
+            let response = execute_command(reader2.inner(), read_cmd2).await;
+            assert_eq!(
+                "*2\r\n$19\r\ntest_fairness_list2\r\n$6\r\nvalue2\r\n",
+                BytesMutUtils::to_string(&response).as_str()
+            );
+        });
+    }
+
+    #[test]
+    fn test_blocking_pop_fractional_timeout() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+
+            let server = Arc::<ServerState>::default();
+            let reader = Client::new(server.clone(), store.clone(), None);
+            let writer = Client::new(server, store, None);
+
+            let read_cmd = Rc::new(RedisCommand::for_test(vec![
+                "blpop",
+                "test_fractional_timeout",
+                "0.5",
+            ]));
+            let (rx, duration) = deferred_command(reader.inner(), read_cmd.clone()).await;
+            assert_eq!(duration, Duration::from_millis(500));
+
+            // a push within the window serves the blocked client
+            let push_cmd = Rc::new(RedisCommand::for_test(vec![
+                "lpush",
+                "test_fractional_timeout",
+                "value",
+            ]));
+            let response = execute_command(writer.inner(), push_cmd).await;
+            assert_eq!(":1\r\n", BytesMutUtils::to_string(&response).as_str());
+
+            assert_eq!(
+                Client::wait_for(rx, duration).await,
+                crate::client::WaitResult::TryAgain
+            );
+            let response = execute_command(reader.inner(), read_cmd).await;
+            assert_eq!(
+                "*2\r\n$23\r\ntest_fractional_timeout\r\n$5\r\nvalue\r\n",
+                BytesMutUtils::to_string(&response).as_str()
+            );
+        });
+    }
 }
@@ -0,0 +1,286 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    metadata::{
+        CommonValueMetadata, Encoding, HashValueMetadata, ListValueMetadata, SetValueMetadata,
+        StreamValueMetadata, ZSetValueMetadata,
+    },
+    storage::GenericDb,
+    BytesMutUtils, LockManager, PrimaryKeyMetadata, RedisCommand, RedisCommandName, RespBuilderV2,
+    SableError, U8ArrayReader,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+/// The bytes RocksDB adds to every record on top of its key and value: the internal key
+/// footer (sequence number and record type, 8 bytes) and the varint encoded key and value
+/// lengths. Every record a key owns (its primary record and its items) is charged this overhead
+const RECORD_OVERHEAD_BYTES: usize = 12;
+
+/// The number of items sampled by `MEMORY USAGE` when `SAMPLES` is not provided (same as Redis)
+const DEFAULT_SAMPLES: usize = 5;
+
+pub struct MemoryCommands {}
+
+impl MemoryCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Memory => {
+                Self::memory(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non memory command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// `MEMORY USAGE key [SAMPLES count]`
+    async fn memory(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = client_state.resp_builder();
+        if sub_command != "usage" {
+            builder.error_string(
+                response_buffer,
+                format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+            );
+            return Ok(());
+        }
+
+        check_args_count!(command, 3, response_buffer);
+        let key = command_arg_at!(command, 2);
+
+        // `SAMPLES 0` visits all the items
+        let samples = match command.arg_count() {
+            3 => DEFAULT_SAMPLES,
+            5 => {
+                let option = command_arg_at_as_str!(command, 3);
+                let Some(samples) = BytesMutUtils::parse::<usize>(command_arg_at!(command, 4))
+                else {
+                    builder.error_string(
+                        response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(());
+                };
+                if option != "samples" {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
+                }
+                if samples == 0 {
+                    usize::MAX
+                } else {
+                    samples
+                }
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let generic_db =
+            GenericDb::with_storage(client_state.database(), client_state.database_id());
+        let Some(value) = generic_db.get_live(key)? else {
+            builder.null_string(response_buffer);
+            return Ok(());
+        };
+
+        let primary_key = PrimaryKeyMetadata::new_primary_key(key, client_state.database_id());
+        let mut usage = primary_key.len() + value.len() + RECORD_OVERHEAD_BYTES;
+        if let Some((items_prefix, records_count)) = Self::items_records(&value)? {
+            usage = usage.saturating_add(Self::items_usage(
+                &generic_db,
+                &items_prefix,
+                records_count,
+                samples,
+            )?);
+        }
+        builder.number_usize(response_buffer, usage);
+        Ok(())
+    }
+
+    /// Estimate the bytes used by the `records_count` item records under `items_prefix`, by
+    /// averaging the size of the first `samples` records
+    fn items_usage(
+        generic_db: &GenericDb,
+        items_prefix: &BytesMut,
+        records_count: usize,
+        samples: usize,
+    ) -> Result<usize, SableError> {
+        let (sampled, sampled_size) =
+            generic_db.sample_items(items_prefix, samples.min(records_count))?;
+        if sampled == 0 {
+            return Ok(0);
+        }
+
+        let sampled_usage =
+            sampled_size.saturating_add(sampled.saturating_mul(RECORD_OVERHEAD_BYTES));
+        Ok(sampled_usage / sampled * records_count.max(sampled))
+    }
+
+    /// For collections, return the prefix of the item records owned by `value` (the raw value
+    /// of the key, including its metadata) and the number of records stored under it
+    fn items_records(value: &BytesMut) -> Result<Option<(BytesMut, usize)>, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let (prefix, records_count) = match common_md.value_type() {
+            Encoding::VALUE_LIST => {
+                let md = ListValueMetadata::from_bytes(&mut reader)?;
+                (md.prefix(), md.len())
+            }
+            Encoding::VALUE_HASH => {
+                let md = HashValueMetadata::from_bytes(&mut reader)?;
+                (md.prefix(), md.len())
+            }
+            Encoding::VALUE_SET => {
+                let md = SetValueMetadata::from_bytes(&mut reader)?;
+                (md.prefix(), md.len())
+            }
+            Encoding::VALUE_ZSET => {
+                // every member is stored twice: by member and by score
+                let md = ZSetValueMetadata::from_bytes(&mut reader)?;
+                (md.prefix(), md.len().saturating_mul(2))
+            }
+            Encoding::VALUE_STREAM => {
+                let md = StreamValueMetadata::from_bytes(&mut reader)?;
+                (md.prefix(), md.len())
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some((
+            prefix,
+            usize::try_from(records_count).unwrap_or(usize::MAX),
+        )))
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState, StringValueMetadata};
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["memory", "usage", "no_such_key"], "$-1\r\n"),
+        (vec!["set", "mykey", "value"], "+OK\r\n"),
+        (vec!["memory", "usage", "mykey", "count", "1"], "-ERR syntax error\r\n"),
+        (vec!["memory", "usage", "mykey", "samples"], "-ERR syntax error\r\n"),
+        (vec!["memory", "usage", "mykey", "samples", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["memory", "doctor"], "-ERR unknown subcommand 'doctor'\r\n"),
+        (vec!["memory", "usage"], "-ERR wrong number of arguments for 'memory' command\r\n"),
+        (vec!["memory"], "-ERR wrong number of arguments for 'memory' command\r\n"),
+    ]; "test_memory_usage_errors")]
+    fn test_memory_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name("test_memory_commands").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_usage() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let run_command = |args: Vec<String>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_memory_usage").await;
+                    let args: Vec<BytesMut> =
+                        args.iter().map(|s| BytesMut::from(s.as_str())).collect();
+                    let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+            let usage = |args: &[&str]| {
+                let args = args.iter().map(|s| s.to_string()).collect();
+                let response = run_command(args);
+                async move {
+                    let response = response.await;
+                    response
+                        .trim_start_matches(':')
+                        .trim_end()
+                        .parse::<usize>()
+                        .unwrap_or_else(|_| panic!("unexpected response {}", response))
+                }
+            };
+
+            // a small string: the key and value bytes, plus the metadata and the record overhead
+            assert_eq!(
+                run_command(vec!["set".into(), "mykey".into(), "value".into()]).await,
+                "+OK\r\n"
+            );
+            let string_usage = usage(&["memory", "usage", "mykey"]).await;
+            let min_usage = "mykey".len() + "value".len() + StringValueMetadata::SIZE;
+            assert!(string_usage >= min_usage);
+            assert!(string_usage <= min_usage + 64);
+
+            // collections are charged for their items
+            let mut hset = vec!["hset".to_string(), "myhash".to_string()];
+            for i in 0..20 {
+                hset.push(format!("field_{:02}", i));
+                hset.push(format!("value_{:02}", i));
+            }
+            assert_eq!(run_command(hset).await, ":20\r\n");
+
+            let sampled = usage(&["memory", "usage", "myhash"]).await;
+            let exact = usage(&["memory", "usage", "myhash", "SAMPLES", "0"]).await;
+            assert!(exact > string_usage + 20 * ("field_00".len() + "value_00".len()));
+            // all the fields have the same size, so sampling finds the exact usage
+            assert_eq!(sampled, exact);
+        });
+        Ok(())
+    }
+}
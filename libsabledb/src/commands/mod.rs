@@ -176,6 +176,7 @@ pub enum ClientNextAction {
 }
 
 mod base_commands;
+mod bitmap_commands;
 mod client_commands;
 mod command;
 mod commander;
@@ -183,19 +184,40 @@ mod error_strings;
 mod generic_commands;
 mod hash_commands;
 mod list_commands;
+mod memory_commands;
+mod object_commands;
+mod pubsub_commands;
+mod scan_commands;
+mod script_commands;
 mod server_commands;
+mod set_commands;
+mod sort_commands;
+mod stream_commands;
 mod string_commands;
+mod transaction_commands;
+mod zset_commands;
 
 pub use crate::commands::error_strings::ErrorStrings;
 pub use base_commands::BaseCommands;
+pub use bitmap_commands::BitmapCommands;
 pub use client_commands::ClientCommands;
-pub use command::commands_manager;
 pub use command::RedisCommand;
-pub use commander::{CommandMetadata, CommandsManager, RedisCommandName};
+pub use command::{commands_manager, init_commands_manager};
+pub use commander::{CommandMetadata, CommandsManager, RedisCommandFlags, RedisCommandName};
 pub use generic_commands::GenericCommands;
 pub use hash_commands::HashCommands;
 pub use list_commands::ListCommands;
+pub use memory_commands::MemoryCommands;
+pub use object_commands::ObjectCommands;
+pub use pubsub_commands::PubSubCommands;
+pub use scan_commands::ScanCommands;
+pub use script_commands::ScriptCommands;
 pub use server_commands::ServerCommands;
+pub use set_commands::SetCommands;
+pub use sort_commands::SortCommands;
+pub use stream_commands::StreamCommands;
 pub use string_commands::StringCommands;
+pub use transaction_commands::TransactionCommands;
+pub use zset_commands::ZSetCommands;
 
 use tokio::{sync::mpsc::Receiver, time::Duration};
@@ -0,0 +1,364 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    metadata::{
+        CommonValueMetadata, Encoding, HashValueMetadata, ListValueMetadata, SetValueMetadata,
+        ZSetValueMetadata,
+    },
+    server_options::EvictionPolicy,
+    storage::{GenericDb, ZSetDb, ZSetRangeResult},
+    BytesMutUtils, LockManager, MemoryTracker, RedisCommand, RedisCommandName, RespBuilderV2,
+    SableError, TimeUtils, U8ArrayReader,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+/// Lists, hashes and sets with up to this number of items are reported with their "small"
+/// encoding (e.g. `listpack`), the same default threshold Redis uses (`hash-max-listpack-entries`
+/// etc). The sorted sets thresholds are configurable (`zset-max-listpack-entries`)
+const MAX_LISTPACK_ENTRIES: u64 = 128;
+
+pub struct ObjectCommands {}
+
+impl ObjectCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Object => {
+                Self::object(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non object command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// `OBJECT <ENCODING | REFCOUNT | IDLETIME | FREQ> key`
+    async fn object(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "encoding" | "refcount" | "idletime" | "freq" => {}
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+                return Ok(());
+            }
+        }
+
+        check_args_count!(command, 3, response_buffer);
+        let key = command_arg_at!(command, 2);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let generic_db =
+            GenericDb::with_storage(client_state.database(), client_state.database_id());
+        let Some(value) = generic_db.get_live(key)? else {
+            builder.error_string(response_buffer, ErrorStrings::NO_SUCH_KEY);
+            return Ok(());
+        };
+
+        match sub_command.as_str() {
+            "encoding" => {
+                let encoding = Self::encoding(&client_state, key, &value)?;
+                builder.bulk_string(response_buffer, &BytesMut::from(encoding));
+            }
+            "refcount" => {
+                // values are never shared between keys
+                builder.number_u64(response_buffer, 1);
+            }
+            "freq" => {
+                let server_state = client_state.server_inner_state();
+                let policy = server_state.options().memory_settings.maxmemory_policy;
+                if policy != EvictionPolicy::AllKeysLfu {
+                    builder.error_string(response_buffer, ErrorStrings::LFU_NOT_SELECTED);
+                    return Ok(());
+                }
+
+                // the live counter is kept by the memory tracker. Until tracking starts, the
+                // counter kept in the metadata is reported
+                let tracker = server_state.memory_tracker();
+                let frequency = match tracker.frequency(client_state.database_id(), key) {
+                    Some(frequency) => frequency,
+                    None => {
+                        let mut reader = U8ArrayReader::with_buffer(&value);
+                        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+                        MemoryTracker::stored_frequency(&common_md)
+                    }
+                };
+                builder.number_u64(response_buffer, frequency as u64);
+            }
+            _ => {
+                // the access time is updated when the key is created and by `TOUCH`
+                let mut reader = U8ArrayReader::with_buffer(&value);
+                let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+                let idle_ms = TimeUtils::epoch_ms()?.saturating_sub(common_md.accessed_at());
+                builder.number_u64(response_buffer, idle_ms / 1000);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the Redis name of the encoding that matches `value` (the raw value of a key,
+    /// including its metadata) stored at `key`. SableDB keeps all the types in RocksDB, so this is
+    /// the encoding Redis would have picked for a value of the same size.
+    ///
+    /// The caller must hold a lock on `key`
+    pub(crate) fn encoding(
+        client_state: &ClientState,
+        key: &BytesMut,
+        value: &BytesMut,
+    ) -> Result<&'static str, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        let md_len = reader.consumed();
+
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let encoding = match common_md.value_type() {
+            Encoding::VALUE_STRING => {
+                let mut user_value = value.clone();
+                let _ = user_value.split_to(md_len);
+                if BytesMutUtils::parse_integer(&user_value).is_some() {
+                    "int"
+                } else {
+                    "raw"
+                }
+            }
+            Encoding::VALUE_LIST => {
+                if ListValueMetadata::from_bytes(&mut reader)?.len() <= MAX_LISTPACK_ENTRIES {
+                    "listpack"
+                } else {
+                    "quicklist"
+                }
+            }
+            Encoding::VALUE_HASH => {
+                if HashValueMetadata::from_bytes(&mut reader)?.len() <= MAX_LISTPACK_ENTRIES {
+                    "listpack"
+                } else {
+                    "hashtable"
+                }
+            }
+            Encoding::VALUE_SET => {
+                if SetValueMetadata::from_bytes(&mut reader)?.len() <= MAX_LISTPACK_ENTRIES {
+                    "listpack"
+                } else {
+                    "hashtable"
+                }
+            }
+            Encoding::VALUE_ZSET => {
+                let settings = client_state
+                    .server_inner_state()
+                    .options()
+                    .encoding_settings
+                    .clone();
+                let len = ZSetValueMetadata::from_bytes(&mut reader)?.len();
+                if len <= settings.zset_max_listpack_entries as u64
+                    && !Self::zset_has_long_member(
+                        client_state,
+                        key,
+                        settings.zset_max_listpack_value,
+                    )?
+                {
+                    "listpack"
+                } else {
+                    "skiplist"
+                }
+            }
+            Encoding::VALUE_STREAM => "stream",
+            unknown_type => {
+                return Err(SableError::OtherError(format!(
+                    "unknown value type {}",
+                    unknown_type
+                )));
+            }
+        };
+        Ok(encoding)
+    }
+
+    /// Does the sorted set stored at `key` have a member longer than `max_len` bytes? Only
+    /// called for sorted sets that are within `zset-max-listpack-entries`, so the scan is short
+    fn zset_has_long_member(
+        client_state: &ClientState,
+        key: &BytesMut,
+        max_len: usize,
+    ) -> Result<bool, SableError> {
+        let zset_db = ZSetDb::with_storage(client_state.database(), client_state.database_id());
+        let ZSetRangeResult::Some(members) = zset_db.range_by_rank(key, 0, -1)? else {
+            return Ok(false);
+        };
+        Ok(members.iter().any(|(member, _)| member.len() > max_len))
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["set", "int_str", "1234"], "+OK\r\n"),
+        (vec!["set", "raw_str", "hello"], "+OK\r\n"),
+        (vec!["object", "encoding", "int_str"], "$3\r\nint\r\n"),
+        (vec!["object", "encoding", "raw_str"], "$3\r\nraw\r\n"),
+        (vec!["rpush", "small_list", "a", "b"], ":2\r\n"),
+        (vec!["object", "encoding", "small_list"], "$8\r\nlistpack\r\n"),
+        (vec!["hset", "small_hash", "f1", "v1"], ":1\r\n"),
+        (vec!["object", "encoding", "small_hash"], "$8\r\nlistpack\r\n"),
+        (vec!["sadd", "small_set", "m1"], ":1\r\n"),
+        (vec!["object", "encoding", "small_set"], "$8\r\nlistpack\r\n"),
+        (vec!["zadd", "small_zset", "1", "m1"], ":1\r\n"),
+        (vec!["object", "encoding", "small_zset"], "$8\r\nlistpack\r\n"),
+        (vec!["object", "refcount", "raw_str"], ":1\r\n"),
+        (vec!["object", "idletime", "raw_str"], ":0\r\n"),
+        (vec!["object", "encoding", "no_such_key"], "-ERR no such key\r\n"),
+        (vec!["object", "refcount", "no_such_key"], "-ERR no such key\r\n"),
+        (vec!["object", "freq", "raw_str"], "-ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.\r\n"),
+        (vec!["object", "freq", "no_such_key"], "-ERR no such key\r\n"),
+        (vec!["object", "foo", "raw_str"], "-ERR unknown subcommand 'foo'\r\n"),
+        (vec!["object", "encoding"], "-ERR wrong number of arguments for 'object' command\r\n"),
+        (vec!["object"], "-ERR wrong number of arguments for 'object' command\r\n"),
+    ]; "test_object_commands")]
+    fn test_object_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name("test_object_commands").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_collections_encoding() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let mut commands = vec![
+                vec!["rpush".to_string(), "big_list".to_string()],
+                vec!["hset".to_string(), "big_hash".to_string()],
+                vec!["zadd".to_string(), "big_zset".to_string()],
+            ];
+            for i in 0..=MAX_LISTPACK_ENTRIES {
+                commands[0].push(format!("item_{}", i));
+                commands[1].push(format!("field_{}", i));
+                commands[1].push(format!("value_{}", i));
+                commands[2].push(format!("{}", i));
+                commands[2].push(format!("member_{}", i));
+            }
+
+            for args in commands {
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_large_collections_encoding").await;
+                let args: Vec<BytesMut> = args.iter().map(|s| BytesMut::from(s.as_str())).collect();
+                let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+            }
+
+            for (key, expected_value) in [
+                ("big_list", "$9\r\nquicklist\r\n"),
+                ("big_hash", "$9\r\nhashtable\r\n"),
+                ("big_zset", "$8\r\nskiplist\r\n"),
+            ] {
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_large_collections_encoding").await;
+                let cmd = Rc::new(RedisCommand::for_test(vec!["object", "encoding", key]));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_encoding_conversion() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            // 65 bytes, one more than the default `zset-max-listpack-value`
+            let long_member = "mmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmmm";
+            for (args, expected_value) in [
+                (vec!["config", "set", "zset-max-listpack-entries", "2"], "+OK\r\n"),
+                (vec!["zadd", "myzset", "1", "m1", "2", "m2"], ":2\r\n"),
+                (vec!["object", "encoding", "myzset"], "$8\r\nlistpack\r\n"),
+                // crossing the entries threshold converts the sorted set
+                (vec!["zadd", "myzset", "3", "m3"], ":1\r\n"),
+                (vec!["object", "encoding", "myzset"], "$8\r\nskiplist\r\n"),
+                (vec!["config", "set", "zset-max-listpack-entries", "128"], "+OK\r\n"),
+                (vec!["object", "encoding", "myzset"], "$8\r\nlistpack\r\n"),
+                // so does a member longer than `zset-max-listpack-value`
+                (vec!["zadd", "myzset", "4", long_member], ":1\r\n"),
+                (vec!["object", "encoding", "myzset"], "$8\r\nskiplist\r\n"),
+                (vec!["config", "set", "zset-max-listpack-value", "65"], "+OK\r\n"),
+                (vec!["object", "encoding", "myzset"], "$8\r\nlistpack\r\n"),
+                (vec!["config", "set", "zset-max-listpack-value", "-1"], "-ERR CONFIG SET failed (possibly related to argument 'zset-max-listpack-value') - argument must be a non-negative integer\r\n"),
+            ] {
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_zset_encoding_conversion").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+}
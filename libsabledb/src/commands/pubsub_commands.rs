@@ -0,0 +1,345 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct PubSubCommands {}
+
+impl PubSubCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Subscribe => {
+                Self::subscribe(client_state, command, &mut response_buffer);
+            }
+            RedisCommandName::Unsubscribe => {
+                Self::unsubscribe(client_state, command, &mut response_buffer);
+            }
+            RedisCommandName::Psubscribe => {
+                Self::psubscribe(client_state, command, &mut response_buffer);
+            }
+            RedisCommandName::Punsubscribe => {
+                Self::punsubscribe(client_state, command, &mut response_buffer);
+            }
+            RedisCommandName::Publish => {
+                Self::publish(client_state, command, &mut response_buffer)?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non pub-sub command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Subscribes the client to the specified channels. The reply contains one `subscribe`
+    /// frame per channel, with the number of channels the client is subscribed to
+    fn subscribe(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) {
+        for channel in command.args_vec().iter().skip(1) {
+            let count = client_state.subscribe(channel);
+            Self::add_subscription_frame(response_buffer, "subscribe", Some(channel), count);
+        }
+    }
+
+    /// Unsubscribes the client from the given channels, or from all of them if none is given
+    fn unsubscribe(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) {
+        let channels: Vec<BytesMut> = if command.arg_count() > 1 {
+            command.args_vec().iter().skip(1).cloned().collect()
+        } else {
+            client_state.subscriptions()
+        };
+
+        if channels.is_empty() {
+            // not subscribed to any channel
+            let count = client_state.subscriptions_count();
+            Self::add_subscription_frame(response_buffer, "unsubscribe", None, count);
+            return;
+        }
+
+        for channel in channels {
+            let count = client_state.unsubscribe(&channel);
+            Self::add_subscription_frame(response_buffer, "unsubscribe", Some(&channel), count);
+        }
+    }
+
+    /// Subscribes the client to the given glob-style patterns (e.g. `news.*`). The reply
+    /// contains one `psubscribe` frame per pattern
+    fn psubscribe(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) {
+        for pattern in command.args_vec().iter().skip(1) {
+            let count = client_state.psubscribe(pattern);
+            Self::add_subscription_frame(response_buffer, "psubscribe", Some(pattern), count);
+        }
+    }
+
+    /// Unsubscribes the client from the given patterns, or from all of them if none is given
+    fn punsubscribe(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) {
+        let patterns: Vec<BytesMut> = if command.arg_count() > 1 {
+            command.args_vec().iter().skip(1).cloned().collect()
+        } else {
+            client_state.pattern_subscriptions()
+        };
+
+        if patterns.is_empty() {
+            // not subscribed to any pattern
+            let count = client_state.subscriptions_count();
+            Self::add_subscription_frame(response_buffer, "punsubscribe", None, count);
+            return;
+        }
+
+        for pattern in patterns {
+            let count = client_state.punsubscribe(&pattern);
+            Self::add_subscription_frame(response_buffer, "punsubscribe", Some(&pattern), count);
+        }
+    }
+
+    /// Posts a message to the given channel. Return the number of clients that received the
+    /// message
+    fn publish(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let channel = command_arg_at!(command, 1);
+        let message = command_arg_at!(command, 2);
+
+        let received = client_state.server_inner_state().publish(channel, message);
+        let builder = RespBuilderV2::default();
+        builder.number_usize(response_buffer, received);
+        Ok(())
+    }
+
+    /// Append a `[kind, channel, count]` frame to the response buffer
+    fn add_subscription_frame(
+        response_buffer: &mut BytesMut,
+        kind: &str,
+        channel: Option<&BytesMut>,
+        count: usize,
+    ) {
+        let builder = RespBuilderV2::default();
+        builder.add_array_len(response_buffer, 3);
+        builder.add_bulk_string_u8_arr(response_buffer, kind.as_bytes());
+        match channel {
+            Some(channel) => builder.add_bulk_string(response_buffer, channel),
+            None => builder.add_null_string(response_buffer),
+        }
+        builder.add_number::<usize>(response_buffer, count, false);
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (0, vec!["publish", "ch1", "hello"], ":0\r\n"),
+        (0, vec!["subscribe", "ch1", "ch2"], "*3\r\n$9\r\nsubscribe\r\n$3\r\nch1\r\n:1\r\n*3\r\n$9\r\nsubscribe\r\n$3\r\nch2\r\n:2\r\n"),
+        (1, vec!["subscribe", "ch1"], "*3\r\n$9\r\nsubscribe\r\n$3\r\nch1\r\n:1\r\n"),
+        (2, vec!["publish", "ch1", "hello"], ":2\r\n"),
+        (2, vec!["publish", "ch2", "world"], ":1\r\n"),
+        (0, vec!["get", "k"], "-ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context\r\n"),
+        (0, vec!["ping"], "*2\r\n$4\r\npong\r\n$0\r\n\r\n"),
+        (0, vec!["unsubscribe", "ch1"], "*3\r\n$11\r\nunsubscribe\r\n$3\r\nch1\r\n:1\r\n"),
+        (2, vec!["publish", "ch1", "hello"], ":1\r\n"),
+        (0, vec!["unsubscribe"], "*3\r\n$11\r\nunsubscribe\r\n$3\r\nch2\r\n:0\r\n"),
+        (0, vec!["unsubscribe"], "*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n"),
+        (0, vec!["get", "k"], "$-1\r\n"),
+    ]; "test_pubsub")]
+    fn test_pubsub_commands(
+        args: Vec<(usize, Vec<&'static str>, &'static str)>,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let clients = [
+                Client::new(server_state.clone(), store.clone(), None),
+                Client::new(server_state.clone(), store.clone(), None),
+                Client::new(server_state.clone(), store.clone(), None),
+            ];
+
+            for (client_index, args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name("test_pubsub").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(clients[client_index].inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+
+            // check the messages delivered to the subscribers
+            let mut rx = clients[0].inner().take_pubsub_receiver().unwrap();
+            assert_eq!(
+                rx.try_recv().unwrap(),
+                BytesMut::from("*3\r\n$7\r\nmessage\r\n$3\r\nch1\r\n$5\r\nhello\r\n")
+            );
+            assert_eq!(
+                rx.try_recv().unwrap(),
+                BytesMut::from("*3\r\n$7\r\nmessage\r\n$3\r\nch2\r\n$5\r\nworld\r\n")
+            );
+            assert!(rx.try_recv().is_err());
+
+            let mut rx = clients[1].inner().take_pubsub_receiver().unwrap();
+            assert!(rx.try_recv().is_ok());
+            assert!(rx.try_recv().is_ok());
+            assert!(rx.try_recv().is_err());
+        });
+        Ok(())
+    }
+
+    #[test_case(vec![
+        (0, vec!["subscribe", "news.tech"], "*3\r\n$9\r\nsubscribe\r\n$9\r\nnews.tech\r\n:1\r\n"),
+        (1, vec!["psubscribe", "news.*", "sport.*"], "*3\r\n$10\r\npsubscribe\r\n$6\r\nnews.*\r\n:1\r\n*3\r\n$10\r\npsubscribe\r\n$7\r\nsport.*\r\n:2\r\n"),
+        // reaches both the exact and the pattern subscriber
+        (2, vec!["publish", "news.tech", "hello"], ":2\r\n"),
+        (2, vec!["publish", "news", "hello"], ":0\r\n"),
+        (1, vec!["punsubscribe", "sport.*"], "*3\r\n$12\r\npunsubscribe\r\n$7\r\nsport.*\r\n:1\r\n"),
+        (1, vec!["unsubscribe"], "*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:1\r\n"),
+        (1, vec!["punsubscribe"], "*3\r\n$12\r\npunsubscribe\r\n$6\r\nnews.*\r\n:0\r\n"),
+        (2, vec!["publish", "news.tech", "world"], ":1\r\n"),
+    ]; "test_psubscribe")]
+    fn test_psubscribe_commands(
+        args: Vec<(usize, Vec<&'static str>, &'static str)>,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let clients = [
+                Client::new(server_state.clone(), store.clone(), None),
+                Client::new(server_state.clone(), store.clone(), None),
+                Client::new(server_state.clone(), store.clone(), None),
+            ];
+
+            for (client_index, args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name("test_psubscribe").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(clients[client_index].inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+
+            // each subscriber received the first message exactly once
+            let mut rx = clients[0].inner().take_pubsub_receiver().unwrap();
+            assert_eq!(
+                rx.try_recv().unwrap(),
+                BytesMut::from("*3\r\n$7\r\nmessage\r\n$9\r\nnews.tech\r\n$5\r\nhello\r\n")
+            );
+            assert_eq!(
+                rx.try_recv().unwrap(),
+                BytesMut::from("*3\r\n$7\r\nmessage\r\n$9\r\nnews.tech\r\n$5\r\nworld\r\n")
+            );
+            assert!(rx.try_recv().is_err());
+
+            let mut rx = clients[1].inner().take_pubsub_receiver().unwrap();
+            assert_eq!(
+                rx.try_recv().unwrap(),
+                BytesMut::from(
+                    "*4\r\n$8\r\npmessage\r\n$6\r\nnews.*\r\n$9\r\nnews.tech\r\n$5\r\nhello\r\n"
+                )
+            );
+            assert!(rx.try_recv().is_err());
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_disconnected_subscriber_is_removed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let publisher = Client::new(server_state.clone(), store.clone(), None);
+            {
+                let subscriber = Client::new(server_state.clone(), store.clone(), None);
+                subscriber.inner().subscribe(&BytesMut::from("ch1"));
+                assert_eq!(
+                    server_state.publish(&BytesMut::from("ch1"), &BytesMut::from("msg")),
+                    1
+                );
+            }
+            let mut sink =
+                crate::tests::ResponseSink::with_name("test_disconnected_subscriber").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["publish", "ch1", "msg"]));
+            Client::handle_command(publisher.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(sink.read_all().await.as_str(), ":0\r\n");
+        });
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_disconnected() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let subscriber = Client::new(server_state.clone(), store.clone(), None);
+            subscriber.inner().subscribe(&BytesMut::from("ch1"));
+            subscriber.inner().psubscribe(&BytesMut::from("ch*"));
+            // the subscriber never reads its messages
+            let mut rx = subscriber.inner().take_pubsub_receiver().unwrap();
+
+            let channel = BytesMut::from("ch1");
+            let message = BytesMut::from("msg");
+            for _ in 0..crate::client::PUBSUB_CHANNEL_CAPACITY / 2 {
+                assert_eq!(server_state.publish(&channel, &message), 2);
+            }
+
+            // the queue is full: the message is dropped and the subscriber is removed from
+            // the channel and the pattern instead of blocking the publisher
+            assert_eq!(server_state.publish(&channel, &message), 0);
+            while rx.try_recv().is_ok() {}
+            assert_eq!(server_state.publish(&channel, &message), 0);
+            assert!(rx.try_recv().is_err());
+        });
+    }
+}
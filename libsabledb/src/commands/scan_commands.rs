@@ -0,0 +1,446 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at, command_arg_at_as_str,
+    commands::{ErrorStrings, HandleCommandResult},
+    BytesMutUtils, PrimaryKeyMetadata, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+    StringUtils, U8ArrayBuilder, U8ArrayReader,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct ScanCommands {}
+
+/// The cursor that starts (or restarts) a scan. It is also the cursor returned once the scan is complete
+pub(crate) const SCAN_CURSOR_START: &str = "0";
+
+/// Number of keys examined by a single `SCAN` call, unless `COUNT` says otherwise
+const SCAN_DEFAULT_COUNT: usize = 10;
+
+/// The number of results pre-allocated by a `SCAN` call. `COUNT` comes from the client, so it
+/// only bounds the number of keys examined: the results grow as they are found
+pub(crate) const SCAN_MAX_PREALLOCATED: usize = 1024;
+
+impl ScanCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Scan => {
+                Self::scan(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non scan command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// `SCAN cursor [MATCH pattern] [COUNT count]`
+    ///
+    /// The cursor is opaque to the client: it is the hex encoding of the database generation
+    /// followed by the last internal key visited by the previous call. Since the internal key
+    /// carries the database ID, a cursor can be resumed from any connection, as long as it is used
+    /// with the same database. Keys that were added or deleted between calls are handled
+    /// naturally: we seek to the first key that follows the cursor, whether the cursor key still
+    /// exists or not. A cursor created before the database was flushed is rejected
+    async fn scan(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let cursor = command_arg_at!(command, 1);
+
+        let Some((pattern, count)) = Self::parse_scan_options(&command, 2, response_buffer)? else {
+            return Ok(());
+        };
+
+        // All the keys of the current database share this prefix
+        let db_id = client_state.database_id();
+        let db_prefix = PrimaryKeyMetadata::primary_key_prefix(db_id);
+        let generation = client_state.server_inner_state().db_generation(db_id);
+        let cursor_key = if cursor == SCAN_CURSOR_START {
+            None
+        } else {
+            match Self::parse_cursor(cursor) {
+                Some((cursor_generation, last_key))
+                    if cursor_generation == generation && last_key.starts_with(&db_prefix) =>
+                {
+                    let (_, user_key) = PrimaryKeyMetadata::from_raw(&last_key)?;
+                    Some(user_key)
+                }
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::INVALID_CURSOR);
+                    return Ok(());
+                }
+            }
+        };
+
+        let mut keys = Vec::<BytesMut>::with_capacity(count.min(SCAN_MAX_PREALLOCATED));
+        let mut last_key: Option<BytesMut> = None;
+        let mut examined = 0usize;
+        let mut iter = client_state
+            .database()
+            .user_keys_iterator(db_id, cursor_key.as_ref())?
+            .peekable();
+
+        // the cursor key itself was already returned by the previous call
+        if let (Some(cursor_key), Some(Ok((user_key, _)))) = (&cursor_key, iter.peek()) {
+            if cursor_key == user_key {
+                let _ = iter.next();
+            }
+        }
+
+        while examined < count {
+            let Some(item) = iter.next() else {
+                break;
+            };
+            let (user_key, md) = item?;
+            examined = examined.saturating_add(1);
+
+            // skip expired keys, they will be removed by the next access to them
+            if !md.expiration().is_expired()? {
+                let matched = match &pattern {
+                    Some(pattern) => StringUtils::glob_match(pattern, &user_key, false),
+                    None => true,
+                };
+                if matched {
+                    keys.push(user_key.clone());
+                }
+            }
+            last_key = Some(user_key);
+        }
+
+        // resume from the last examined key, unless there is nothing left to visit
+        let next_cursor = match last_key {
+            Some(last_key) if iter.peek().is_some() => Self::make_cursor(
+                generation,
+                &PrimaryKeyMetadata::new_primary_key(&last_key, db_id),
+            ),
+            _ => SCAN_CURSOR_START.to_string(),
+        };
+
+        builder.add_array_len(response_buffer, 2);
+        builder.add_bulk_string_u8_arr(response_buffer, next_cursor.as_bytes());
+        builder.add_array_len(response_buffer, keys.len());
+        for key in &keys {
+            builder.add_bulk_string(response_buffer, key);
+        }
+        Ok(())
+    }
+
+    /// Build a `SCAN` cursor: the hex encoding of `[generation | last key]`
+    fn make_cursor(generation: u64, last_key: &BytesMut) -> String {
+        let mut cursor = BytesMut::with_capacity(std::mem::size_of::<u64>() + last_key.len());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut cursor);
+        builder.write_u64(generation);
+        builder.write_bytes(last_key);
+        BytesMutUtils::to_hex(&cursor)
+    }
+
+    /// Split a `SCAN` cursor into the database generation and the last key it was created with
+    fn parse_cursor(cursor: &BytesMut) -> Option<(u64, BytesMut)> {
+        let mut cursor = BytesMutUtils::from_hex(cursor)?;
+        let mut reader = U8ArrayReader::with_buffer(&cursor);
+        let generation = reader.read_u64()?;
+        let last_key = cursor.split_off(reader.consumed());
+        Some((generation, last_key))
+    }
+
+    /// Parse the `[MATCH pattern] [COUNT count]` options shared by the `SCAN` family of commands,
+    /// starting at argument `first_option_pos`. On a parsing error, the error is written to
+    /// `response_buffer` and `None` is returned
+    pub(crate) fn parse_scan_options(
+        command: &RedisCommand,
+        first_option_pos: usize,
+        response_buffer: &mut BytesMut,
+    ) -> Result<Option<(Option<BytesMut>, usize)>, SableError> {
+        let builder = RespBuilderV2::default();
+        let mut pattern: Option<BytesMut> = None;
+        let mut count = SCAN_DEFAULT_COUNT;
+        let mut pos = first_option_pos;
+        while pos < command.arg_count() {
+            let option = command_arg_at_as_str!(command, pos);
+            let Some(value) = command.arg(pos.saturating_add(1)) else {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(None);
+            };
+            match option.as_str() {
+                "match" => pattern = Some(value.clone()),
+                "count" => match BytesMutUtils::parse::<usize>(value) {
+                    Some(value) if value > 0 => count = value,
+                    Some(_) => {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(None);
+                    }
+                    None => {
+                        builder.error_string(
+                            response_buffer,
+                            ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                        );
+                        return Ok(None);
+                    }
+                },
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(None);
+                }
+            }
+            pos = pos.saturating_add(2);
+        }
+        Ok(Some((pattern, count)))
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    /// Run `args` and return the raw response
+    async fn execute(client: &Client, args: Vec<&str>) -> String {
+        let mut sink = crate::tests::ResponseSink::with_name("test_scan").await;
+        let args = args.iter().map(|s| BytesMut::from(s.as_bytes())).collect();
+        let cmd = Rc::new(RedisCommand::new(args).unwrap());
+        match Client::handle_command(client.inner(), cmd, &mut sink.fp)
+            .await
+            .unwrap()
+        {
+            ClientNextAction::NoAction => sink.read_all_with_size(64 << 10).await,
+            _ => panic!("unexpected client action"),
+        }
+    }
+
+    /// Split a `SCAN` response into its cursor and keys
+    fn parse_scan_response(response: &str) -> (String, Vec<String>) {
+        let tokens: Vec<&str> = response.split("\r\n").collect();
+        assert_eq!(tokens[0], "*2");
+        let cursor = tokens[2].to_string();
+        let keys = tokens[5..]
+            .iter()
+            .step_by(2)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect();
+        (cursor, keys)
+    }
+
+    /// Scan the entire database and return all the keys reported
+    async fn scan_all(client: &Client, extra_args: Vec<&str>) -> Vec<String> {
+        let mut cursor = SCAN_CURSOR_START.to_string();
+        let mut all_keys = Vec::<String>::new();
+        loop {
+            let mut args = vec!["scan", cursor.as_str()];
+            args.extend_from_slice(&extra_args);
+            let (next_cursor, keys) = parse_scan_response(&execute(client, args).await);
+            all_keys.extend(keys);
+            if next_cursor == SCAN_CURSOR_START {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        all_keys
+    }
+
+    #[test_case(vec![
+        (vec!["scan"], "-ERR wrong number of arguments for 'scan' command\r\n"),
+        (vec!["scan", "0"], "*2\r\n$1\r\n0\r\n*0\r\n"),
+        (vec!["scan", "0", "count"], "-ERR syntax error\r\n"),
+        (vec!["scan", "0", "count", "0"], "-ERR syntax error\r\n"),
+        (vec!["scan", "0", "count", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["scan", "0", "nosuchoption", "1"], "-ERR syntax error\r\n"),
+        (vec!["scan", "xyz"], "-ERR invalid cursor\r\n"),
+        (vec!["scan", "ffff"], "-ERR invalid cursor\r\n"),
+        (vec!["set", "key", "value"], "+OK\r\n"),
+        (vec!["scan", "0"], "*2\r\n$1\r\n0\r\n*1\r\n$3\r\nkey\r\n"),
+        (vec!["scan", "0", "match", "k*"], "*2\r\n$1\r\n0\r\n*1\r\n$3\r\nkey\r\n"),
+        (vec!["scan", "0", "match", "x*"], "*2\r\n$1\r\n0\r\n*0\r\n"),
+        (vec!["scan", "0", "count", "18446744073709551615"], "*2\r\n$1\r\n0\r\n*1\r\n$3\r\nkey\r\n"),
+        (vec!["scan", "0", "count", "1000000000000"], "*2\r\n$1\r\n0\r\n*1\r\n$3\r\nkey\r\n"),
+    ], "test_scan"; "test_scan")]
+    fn test_scan_commands(
+        args_vec: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args_vec {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_iteration() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let mut expected = HashSet::<String>::new();
+            for i in 0..50 {
+                let key = format!("user:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+                expected.insert(key);
+                let key = format!("item:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+            }
+
+            // keys in another database must not be visible
+            execute(&client, vec!["select", "1"]).await;
+            execute(&client, vec!["set", "user:other_db", "value"]).await;
+            execute(&client, vec!["select", "0"]).await;
+
+            // a full iteration visits every matching key exactly once
+            let keys = scan_all(&client, vec!["match", "user:*", "count", "7"]).await;
+            assert_eq!(keys.len(), expected.len());
+            assert_eq!(keys.into_iter().collect::<HashSet<String>>(), expected);
+
+            // `0` always restarts the iteration
+            let (first_cursor, _) =
+                parse_scan_response(&execute(&client, vec!["scan", "0", "count", "5"]).await);
+            assert_ne!(first_cursor, SCAN_CURSOR_START);
+            let (cursor, _) =
+                parse_scan_response(&execute(&client, vec!["scan", "0", "count", "5"]).await);
+            assert_eq!(cursor, first_cursor);
+
+            // a cursor belongs to the database it was created in
+            execute(&client, vec!["select", "1"]).await;
+            assert_eq!(
+                execute(&client, vec!["scan", first_cursor.as_str()]).await,
+                "-ERR invalid cursor\r\n"
+            );
+            execute(&client, vec!["select", "0"]).await;
+        });
+    }
+
+    #[test]
+    fn test_scan_after_flush() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for i in 0..20 {
+                let key = format!("key:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+            }
+            let (cursor, _) =
+                parse_scan_response(&execute(&client, vec!["scan", "0", "count", "5"]).await);
+            assert_ne!(cursor, SCAN_CURSOR_START);
+
+            // flushing another database does not invalidate the cursor
+            execute(&client, vec!["select", "1"]).await;
+            assert_eq!(execute(&client, vec!["flushdb"]).await, "+OK\r\n");
+            execute(&client, vec!["select", "0"]).await;
+            let (cursor, _) = parse_scan_response(
+                &execute(&client, vec!["scan", cursor.as_str(), "count", "5"]).await,
+            );
+            assert_ne!(cursor, SCAN_CURSOR_START);
+
+            // the database is flushed and refilled: the cursor from before the flush is stale
+            assert_eq!(execute(&client, vec!["flushdb"]).await, "+OK\r\n");
+            for i in 0..20 {
+                let key = format!("other:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+            }
+            assert_eq!(
+                execute(&client, vec!["scan", cursor.as_str()]).await,
+                "-ERR invalid cursor\r\n"
+            );
+
+            // FLUSHALL invalidates the cursors of all the databases
+            let (cursor, _) =
+                parse_scan_response(&execute(&client, vec!["scan", "0", "count", "5"]).await);
+            assert_eq!(execute(&client, vec!["flushall"]).await, "+OK\r\n");
+            assert_eq!(
+                execute(&client, vec!["scan", cursor.as_str()]).await,
+                "-ERR invalid cursor\r\n"
+            );
+            assert_eq!(scan_all(&client, vec![]).await.len(), 0);
+        });
+    }
+
+    #[test]
+    fn test_scan_with_changes_between_calls() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for i in 0..20 {
+                let key = format!("key:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+            }
+
+            let (cursor, keys) =
+                parse_scan_response(&execute(&client, vec!["scan", "0", "count", "5"]).await);
+            assert_eq!(keys.len(), 5);
+
+            // delete all the keys returned so far (including the cursor key) and add new ones
+            for key in &keys {
+                execute(&client, vec!["del", key.as_str()]).await;
+            }
+            for i in 0..5 {
+                let key = format!("new_key:{}", i);
+                execute(&client, vec!["set", key.as_str(), "value"]).await;
+            }
+
+            // resuming must not fail and must not return the deleted keys
+            let mut cursor = cursor;
+            let mut remaining = HashSet::<String>::new();
+            loop {
+                let (next_cursor, keys) = parse_scan_response(
+                    &execute(&client, vec!["scan", cursor.as_str(), "count", "5"]).await,
+                );
+                remaining.extend(keys);
+                if next_cursor == SCAN_CURSOR_START {
+                    break;
+                }
+                cursor = next_cursor;
+            }
+
+            for key in &keys {
+                assert!(!remaining.contains(key));
+            }
+            // all the keys that were not deleted are still visited
+            for i in 0..20 {
+                let key = format!("key:{}", i);
+                assert!(keys.contains(&key) || remaining.contains(&key));
+            }
+        });
+    }
+}
@@ -0,0 +1,577 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at, command_arg_at_as_str,
+    commands::{ErrorStrings, HandleCommandResult},
+    scripting::{ScriptCache, ScriptRunner},
+    storage::FunctionDb,
+    BytesMutUtils, LockManager, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+    StringUtils,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct ScriptCommands {}
+
+impl ScriptCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Eval | RedisCommandName::Evalsha => {
+                Self::eval(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Script => {
+                Self::script(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Function => {
+                Self::function(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Fcall => {
+                Self::fcall(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non scripting command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// `EVAL script numkeys [key [key ...]] [arg [arg ...]]` and
+    /// `EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]`
+    /// Run a Lua script. The keys are available to the script as `KEYS`, the other arguments as
+    /// `ARGV`. The keys are locked for the entire run of the script, so it runs atomically with
+    /// respect to the other clients accessing them
+    async fn eval(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let script = command_arg_at!(command, 1);
+
+        let builder = RespBuilderV2::default();
+        let Some(numkeys) = Self::parse_numkeys(&command, response_buffer) else {
+            return Ok(());
+        };
+
+        let server_state = client_state.server_inner_state();
+        let script_cache = server_state.script_cache();
+        let body = match command.metadata().name() {
+            RedisCommandName::Evalsha => {
+                let Some(body) = script_cache.get(&BytesMutUtils::to_string(script)) else {
+                    builder.error_string(response_buffer, ErrorStrings::NOSCRIPT);
+                    return Ok(());
+                };
+                body
+            }
+            _ => {
+                script_cache.add(script);
+                script.clone()
+            }
+        };
+
+        let keys = &command.args_vec()[3..3 + numkeys];
+        let args = &command.args_vec()[3 + numkeys..];
+        let user_keys: Vec<&BytesMut> = keys.iter().collect();
+        let reply = LockManager::with_user_keys_locked(
+            &user_keys,
+            client_state.database_id(),
+            ScriptRunner::run(client_state.clone(), &body, keys, args),
+        )
+        .await;
+        response_buffer.clear();
+        response_buffer.extend_from_slice(&reply);
+        Ok(())
+    }
+
+    /// `SCRIPT <LOAD script | EXISTS sha1 [sha1 ...] | FLUSH [ASYNC | SYNC]>`
+    /// Manage the scripts cache
+    async fn script(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let server_state = client_state.server_inner_state();
+        let script_cache = server_state.script_cache();
+
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "load" => {
+                check_args_count!(command, 3, response_buffer);
+                let script = command_arg_at!(command, 2);
+                match ScriptRunner::compile(script) {
+                    Ok(()) => {
+                        let sha1 = script_cache.add(script);
+                        builder.bulk_string(response_buffer, &BytesMut::from(sha1.as_str()));
+                    }
+                    Err(msg) => builder.error_string(response_buffer, &msg),
+                }
+            }
+            "exists" => {
+                check_args_count!(command, 3, response_buffer);
+                let shas = &command.args_vec()[2..];
+                builder.add_array_len(response_buffer, shas.len());
+                for sha1 in shas {
+                    let exists = script_cache.contains(&BytesMutUtils::to_string(sha1));
+                    builder.add_number::<usize>(response_buffer, if exists { 1 } else { 0 }, false);
+                }
+            }
+            "flush" => {
+                for pos in 2..command.arg_count() {
+                    match command_arg_at_as_str!(command, pos).as_str() {
+                        "async" | "sync" => {}
+                        _ => {
+                            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                            return Ok(());
+                        }
+                    }
+                }
+                script_cache.flush();
+                builder.ok(response_buffer);
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `FCALL function numkeys [key [key ...]] [arg [arg ...]]`
+    /// Call a function loaded with `FUNCTION LOAD`. The function is called with the keys and the
+    /// other arguments as its two arguments. Like `EVAL`, the keys are locked for the entire call
+    async fn fcall(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let function = BytesMutUtils::to_string(command_arg_at!(command, 1));
+
+        let builder = RespBuilderV2::default();
+        let Some(numkeys) = Self::parse_numkeys(&command, response_buffer) else {
+            return Ok(());
+        };
+
+        let server_state = client_state.server_inner_state();
+        let Some(library) = server_state.function_registry().find_function(&function) else {
+            builder.error_string(response_buffer, ErrorStrings::FUNCTION_NOT_FOUND);
+            return Ok(());
+        };
+
+        let keys = &command.args_vec()[3..3 + numkeys];
+        let args = &command.args_vec()[3 + numkeys..];
+        let user_keys: Vec<&BytesMut> = keys.iter().collect();
+        let reply = LockManager::with_user_keys_locked(
+            &user_keys,
+            client_state.database_id(),
+            ScriptRunner::fcall(client_state.clone(), &library.code, &function, keys, args),
+        )
+        .await;
+        response_buffer.clear();
+        response_buffer.extend_from_slice(&reply);
+        Ok(())
+    }
+
+    /// `FUNCTION <LOAD [REPLACE] code | LIST [LIBRARYNAME pattern] [WITHCODE] |
+    /// DELETE library | FLUSH [ASYNC | SYNC]>`
+    /// Manage the function libraries. The libraries are persisted, so they survive a restart
+    async fn function(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let server_state = client_state.server_inner_state();
+        let registry = server_state.function_registry();
+        let function_db = FunctionDb::with_storage(client_state.database());
+
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "load" => {
+                check_args_count!(command, 3, response_buffer);
+                let replace = match command.arg_count() {
+                    3 => false,
+                    4 if command_arg_at_as_str!(command, 2) == "replace" => true,
+                    _ => {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    }
+                };
+                let code = command_arg_at!(command, command.arg_count() - 1);
+                let library = match ScriptRunner::load_library(code) {
+                    Ok(library) => library,
+                    Err(msg) => {
+                        builder.error_string(response_buffer, &msg);
+                        return Ok(());
+                    }
+                };
+
+                let name = library.name.clone();
+                if let Err(msg) = registry.add(library, replace) {
+                    builder.error_string(response_buffer, &msg);
+                    return Ok(());
+                }
+                function_db.put(&name, code)?;
+                builder.bulk_string(response_buffer, &BytesMut::from(name.as_str()));
+            }
+            "list" => {
+                let mut pattern = None;
+                let mut with_code = false;
+                let mut pos = 2;
+                while pos < command.arg_count() {
+                    match command_arg_at_as_str!(command, pos).as_str() {
+                        "withcode" => with_code = true,
+                        "libraryname" if pos + 1 < command.arg_count() => {
+                            pos += 1;
+                            pattern = Some(command_arg_at!(command, pos));
+                        }
+                        _ => {
+                            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                            return Ok(());
+                        }
+                    }
+                    pos += 1;
+                }
+
+                let libraries: Vec<_> = registry
+                    .libraries()
+                    .into_iter()
+                    .filter(|library| match pattern {
+                        Some(pattern) => {
+                            StringUtils::glob_match(pattern, library.name.as_bytes(), false)
+                        }
+                        None => true,
+                    })
+                    .collect();
+
+                let builder = client_state.resp_builder();
+                builder.add_array_len(response_buffer, libraries.len());
+                for library in libraries {
+                    builder.add_map_len(response_buffer, if with_code { 4 } else { 3 });
+                    builder.add_bulk_string_u8_arr(response_buffer, b"library_name");
+                    builder.add_bulk_string_u8_arr(response_buffer, library.name.as_bytes());
+                    builder.add_bulk_string_u8_arr(response_buffer, b"engine");
+                    builder.add_bulk_string_u8_arr(response_buffer, b"LUA");
+                    builder.add_bulk_string_u8_arr(response_buffer, b"functions");
+                    builder.add_array_len(response_buffer, library.functions.len());
+                    for function in &library.functions {
+                        builder.add_map_len(response_buffer, 3);
+                        builder.add_bulk_string_u8_arr(response_buffer, b"name");
+                        builder.add_bulk_string_u8_arr(response_buffer, function.name.as_bytes());
+                        builder.add_bulk_string_u8_arr(response_buffer, b"description");
+                        match &function.description {
+                            Some(description) => builder
+                                .add_bulk_string_u8_arr(response_buffer, description.as_bytes()),
+                            None => builder.add_null_string(response_buffer),
+                        }
+                        builder.add_bulk_string_u8_arr(response_buffer, b"flags");
+                        builder.add_array_len(response_buffer, function.flags.len());
+                        for flag in &function.flags {
+                            builder.add_bulk_string_u8_arr(response_buffer, flag.as_bytes());
+                        }
+                    }
+                    if with_code {
+                        builder.add_bulk_string_u8_arr(response_buffer, b"library_code");
+                        builder.add_bulk_string(response_buffer, &library.code);
+                    }
+                }
+            }
+            "delete" => {
+                if command.arg_count() != 3 {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'function|delete' command",
+                    );
+                    return Ok(());
+                }
+                let name = BytesMutUtils::to_string(command_arg_at!(command, 2));
+                if !registry.remove(&name) {
+                    builder.error_string(response_buffer, ErrorStrings::LIBRARY_NOT_FOUND);
+                    return Ok(());
+                }
+                function_db.delete(&name)?;
+                builder.ok(response_buffer);
+            }
+            "flush" => {
+                for pos in 2..command.arg_count() {
+                    match command_arg_at_as_str!(command, pos).as_str() {
+                        "async" | "sync" => {}
+                        _ => {
+                            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                            return Ok(());
+                        }
+                    }
+                }
+                registry.flush();
+                function_db.flush()?;
+                builder.ok(response_buffer);
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the `numkeys` argument of `EVAL`, `EVALSHA` and `FCALL`. On error, the error reply
+    /// is written to `response_buffer` and `None` is returned
+    fn parse_numkeys(command: &RedisCommand, response_buffer: &mut BytesMut) -> Option<usize> {
+        let builder = RespBuilderV2::default();
+        let Some(numkeys) = command.arg_as_number::<i64>(2) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return None;
+        };
+        if numkeys < 0 {
+            builder.error_string(response_buffer, ErrorStrings::NEGATIVE_NUMKEYS);
+            return None;
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > command.arg_count() - 3 {
+            builder.error_string(response_buffer, ErrorStrings::NUMKEYS_EXCEEDS_ARGS);
+            return None;
+        }
+        Some(numkeys)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["eval", "redis.call('set', KEYS[1], ARGV[1]) return redis.call('get', KEYS[1])", "1", "key", "value"], "$5\r\nvalue\r\n"),
+        (vec!["get", "key"], "$5\r\nvalue\r\n"),
+        (vec!["eval", "return {KEYS[1], KEYS[2], ARGV[1]}", "2", "k1", "k2", "a1"], "*3\r\n$2\r\nk1\r\n$2\r\nk2\r\n$2\r\na1\r\n"),
+        (vec!["eval", "return redis.call('incr', KEYS[1])", "1", "counter"], ":1\r\n"),
+        (vec!["eval", "return redis.call('get', KEYS[1])", "1", "nosuchkey"], "$-1\r\n"),
+        (vec!["eval", "return redis.call('get', KEYS[1]) == false", "1", "nosuchkey"], ":1\r\n"),
+        (vec!["rpush", "mylist", "a", "b"], ":2\r\n"),
+        (vec!["eval", "local items = redis.call('lrange', KEYS[1], 0, -1) return #items", "1", "mylist"], ":2\r\n"),
+        (vec!["eval", "return redis.call('set', 'k', 'v')['ok']", "0"], "$2\r\nOK\r\n"),
+    ], "test_eval_calls"; "test_eval_calls")]
+    #[test_case(vec![
+        (vec!["eval", "return {1, 'two', {3}, nil, 4}", "0"], "*3\r\n:1\r\n$3\r\ntwo\r\n*1\r\n:3\r\n"),
+        (vec!["eval", "return 3.9", "0"], ":3\r\n"),
+        (vec!["eval", "return true", "0"], ":1\r\n"),
+        (vec!["eval", "return false", "0"], "$-1\r\n"),
+        (vec!["eval", "return nil", "0"], "$-1\r\n"),
+        (vec!["eval", "return redis.status_reply('DONE')", "0"], "+DONE\r\n"),
+        (vec!["eval", "return redis.error_reply('MYERR failed')", "0"], "-MYERR failed\r\n"),
+        // the script runs in a sandbox
+        (vec!["eval", "return os", "0"], "$-1\r\n"),
+    ], "test_eval_conversions"; "test_eval_conversions")]
+    #[test_case(vec![
+        (vec!["set", "key", "value"], "+OK\r\n"),
+        (vec!["eval", "return redis.call('incr', KEYS[1])", "1", "key"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["eval", "return redis.pcall('incr', KEYS[1])['err']", "1", "key"], "$43\r\nERR value is not an integer or out of range\r\n"),
+        (vec!["eval", "return redis.call('eval', 'return 1', 0)", "0"], "-ERR This Redis command is not allowed from script\r\n"),
+        (vec!["eval", "return redis.call('multi')", "0"], "-ERR This Redis command is not allowed from script\r\n"),
+        // commands that may suspend the client while the script holds its keys locks
+        (vec!["eval", "redis.call('debug', 'sleep', '1')", "1", "k"], "-ERR This Redis command is not allowed from script\r\n"),
+        (vec!["eval", "return redis.call('wait', '1', '0')", "0"], "-ERR This Redis command is not allowed from script\r\n"),
+        (vec!["eval", "return redis.call('migrate', '127.0.0.1', '6379', 'k', '0', '100')", "1", "k"], "-ERR This Redis command is not allowed from script\r\n"),
+        (vec!["eval", "return redis.call('subscribe', 'ch')", "0"], "-ERR This Redis command is not allowed from script\r\n"),
+        (vec!["eval", "return redis.call('publish', 'ch', 'msg')", "0"], ":0\r\n"),
+        (vec!["eval", "return redis.call({})", "0"], "-ERR Lua redis lib command arguments must be strings or integers\r\n"),
+        (vec!["eval", "return redis.call()", "0"], "-ERR Please specify at least one argument for this redis lib call\r\n"),
+        (vec!["eval", "return 1", "-1"], "-ERR Number of keys can't be negative\r\n"),
+        (vec!["eval", "return 1", "2", "k1"], "-ERR Number of keys can't be greater than number of args\r\n"),
+        (vec!["eval", "return 1", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["eval", "return 1"], "-ERR wrong number of arguments for 'eval' command\r\n"),
+    ], "test_eval_errors"; "test_eval_errors")]
+    #[test_case(vec![
+        (vec!["script", "load", "return 1"], "$40\r\ne0e1f9fabfc9d4800c877a703b823ac0578ff8db\r\n"),
+        (vec!["evalsha", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db", "0"], ":1\r\n"),
+        (vec!["evalsha", "E0E1F9FABFC9D4800C877A703B823AC0578FF8DB", "0"], ":1\r\n"),
+        (vec!["script", "exists", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db", "ffffffffffffffffffffffffffffffffffffffff"], "*2\r\n:1\r\n:0\r\n"),
+        // EVAL caches its script too
+        (vec!["eval", "return 2", "0"], ":2\r\n"),
+        (vec!["script", "exists", "7f923f79fe76194c868d7e1d0820de36700eb649"], "*1\r\n:1\r\n"),
+        (vec!["script", "flush"], "+OK\r\n"),
+        (vec!["evalsha", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db", "0"], "-NOSCRIPT No matching script. Please use EVAL.\r\n"),
+        (vec!["script", "exists", "e0e1f9fabfc9d4800c877a703b823ac0578ff8db"], "*1\r\n:0\r\n"),
+        (vec!["script", "flush", "async"], "+OK\r\n"),
+        (vec!["script", "flush", "later"], "-ERR syntax error\r\n"),
+        (vec!["script", "kill"], "-ERR unknown subcommand 'kill'\r\n"),
+    ], "test_script_cache"; "test_script_cache")]
+    #[test_case(vec![
+        (vec!["function", "load", "#!lua name=mylib\nredis.register_function('myset', function(keys, args) return redis.call('set', keys[1], args[1]) end)"], "$5\r\nmylib\r\n"),
+        (vec!["fcall", "myset", "1", "key", "value"], "+OK\r\n"),
+        (vec!["get", "key"], "$5\r\nvalue\r\n"),
+        (vec!["fcall", "MYSET", "1", "key", "value"], "-ERR Function not found\r\n"),
+        (vec!["fcall", "myset", "2", "key"], "-ERR Number of keys can't be greater than number of args\r\n"),
+        (vec!["function", "load", "#!lua name=mylib\nredis.register_function('f', function() return 1 end)"], "-ERR Library 'mylib' already exists\r\n"),
+        (vec!["function", "load", "replace", "#!lua name=mylib\nredis.register_function{function_name='f', callback=function() return 1 end, flags={'no-writes'}, description='one'}"], "$5\r\nmylib\r\n"),
+        (vec!["fcall", "myset", "1", "key", "value"], "-ERR Function not found\r\n"),
+        (vec!["fcall", "f", "0"], ":1\r\n"),
+        (vec!["function", "load", "#!lua name=otherlib\nredis.register_function('f', function() return 2 end)"], "-ERR Function f already exists\r\n"),
+        (vec!["function", "list"], "*1\r\n*6\r\n$12\r\nlibrary_name\r\n$5\r\nmylib\r\n$6\r\nengine\r\n$3\r\nLUA\r\n$9\r\nfunctions\r\n*1\r\n*6\r\n$4\r\nname\r\n$1\r\nf\r\n$11\r\ndescription\r\n$3\r\none\r\n$5\r\nflags\r\n*1\r\n$9\r\nno-writes\r\n"),
+        (vec!["function", "list", "libraryname", "other*"], "*0\r\n"),
+        (vec!["function", "delete", "mylib"], "+OK\r\n"),
+        (vec!["function", "delete", "mylib"], "-ERR Library not found\r\n"),
+        (vec!["fcall", "f", "0"], "-ERR Function not found\r\n"),
+        (vec!["function", "load", "#!lua name=lib1\nredis.register_function('f1', function() return 1 end)"], "$4\r\nlib1\r\n"),
+        (vec!["function", "flush"], "+OK\r\n"),
+        (vec!["function", "list"], "*0\r\n"),
+    ], "test_functions"; "test_functions")]
+    #[test_case(vec![
+        (vec!["function", "load", "return 1"], "-ERR Missing library metadata\r\n"),
+        (vec!["function", "load", "#!python name=lib\n"], "-ERR Engine 'python' not found\r\n"),
+        (vec!["function", "load", "#!lua\n"], "-ERR Library name was not given\r\n"),
+        (vec!["function", "load", "#!lua name=lib foo=bar\n"], "-ERR Invalid metadata value given: foo=bar\r\n"),
+        (vec!["function", "load", "#!lua name=my-lib\n"], "-ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long\r\n"),
+        (vec!["function", "load", "#!lua name=lib\nlocal x = 1"], "-ERR No functions registered\r\n"),
+        (vec!["function", "load", "#!lua name=lib\nredis.register_function('f', function() return 1 end)\nredis.register_function('f', function() return 2 end)"], "-ERR Function already exists in the library\r\n"),
+        (vec!["function", "load", "#!lua name=lib\nredis.register_function{function_name='f', callback=function() return 1 end, flags={'no-such-flag'}}"], "-ERR Unknown flag given: no-such-flag\r\n"),
+        (vec!["function", "load", "#!lua name=lib\nredis.register_function('f')"], "-ERR wrong arguments given to redis.register_function\r\n"),
+        (vec!["function", "load", "#!lua name=lib\nredis.call('set', 'k', 'v')"], "-ERR Error running script: [string \"user_script\"]:2: attempt to call a nil value (field 'call')\r\n"),
+        (vec!["function", "load", "extra", "#!lua name=lib\n"], "-ERR syntax error\r\n"),
+        (vec!["function", "delete"], "-ERR wrong number of arguments for 'function|delete' command\r\n"),
+        (vec!["function", "stats"], "-ERR unknown subcommand 'stats'\r\n"),
+    ], "test_function_errors"; "test_function_errors")]
+    fn test_script_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_lua_errors() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_prefix) in [
+                (
+                    vec!["eval", "return +", "0"],
+                    "-ERR Error compiling script: ",
+                ),
+                (
+                    vec!["script", "load", "return +"],
+                    "-ERR Error compiling script: ",
+                ),
+                (
+                    vec!["eval", "error('boom')", "0"],
+                    "-ERR Error running script: ",
+                ),
+                (
+                    vec!["eval", "return nosuchfunction()", "0"],
+                    "-ERR Error running script: ",
+                ),
+            ] {
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_script_lua_errors").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                let reply = sink.read_all().await;
+                assert!(reply.starts_with(expected_prefix), "{}", reply);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions_survive_restart() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let open_params = store.open_params().clone();
+            let commands = [
+                (
+                    vec![
+                        "function",
+                        "load",
+                        "#!lua name=mylib\nredis.register_function('echo', function(keys, args) return args[1] end)",
+                    ],
+                    "$5\r\nmylib\r\n",
+                ),
+                (vec!["fcall", "echo", "0", "hello"], "$5\r\nhello\r\n"),
+            ];
+            {
+                let client = Client::new(Arc::<ServerState>::default(), store, None);
+                for (args, expected_value) in commands {
+                    let mut sink =
+                        crate::tests::ResponseSink::with_name("test_functions_survive_restart")
+                            .await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+                // the client and the storage are dropped here
+            }
+
+            // "restart": reopen the storage and load the libraries into a new server state
+            let store = crate::storage_rocksdb!(open_params);
+            let server_state = Arc::<ServerState>::default();
+            server_state.function_registry().load(&store).unwrap();
+            let client = Client::new(server_state, store, None);
+
+            let mut sink =
+                crate::tests::ResponseSink::with_name("test_functions_survive_restart").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["fcall", "echo", "0", "again"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(sink.read_all().await.as_str(), "$5\r\nagain\r\n");
+        });
+        Ok(())
+    }
+}
@@ -4,18 +4,38 @@ use crate::{
     client::ClientState,
     command_arg_at,
     commands::ErrorStrings,
-    commands::{HandleCommandResult, StringCommands},
+    commands::{HandleCommandResult, ObjectCommands, StringCommands},
     metadata::CommonValueMetadata,
     parse_string_to_number,
-    storage::StringsDb,
+    replication::{FailoverTarget, ReplicationClient, StorageUpdatesIterItem},
+    storage::{BackupInfo, GenericDb, StringsDb},
     BytesMutUtils, Expiration, LockManager, PrimaryKeyMetadata, RedisCommand, RedisCommandName,
-    RespBuilderV2, SableError, StorageAdapter, StringUtils, Telemetry, TimeUtils,
+    RespBuilderV2, SableError, ServerOptions, ServerState, SetConfigResult, StorageAdapter,
+    StringUtils, Telemetry, TimeUtils, U8ArrayReader,
 };
 
 use bytes::BytesMut;
 use std::rc::Rc;
+use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 
+/// How often `WAIT` checks the replicas acknowledgements
+const WAIT_POLL_INTERVAL_MS: u64 = 10;
+
+/// `SABLE.CDC` writes the changes to the client whenever this many bytes are pending
+const CDC_FLUSH_THRESHOLD: usize = 64 << 10;
+
+/// Marks a failover as completed when it goes out of scope, so writes resume on every path
+struct FailoverGuard {
+    server_state: Arc<ServerState>,
+}
+
+impl Drop for FailoverGuard {
+    fn drop(&mut self) {
+        self.server_state.end_failover();
+    }
+}
+
 pub struct ServerCommands {}
 
 impl ServerCommands {
@@ -33,6 +53,40 @@ impl ServerCommands {
                 Self::command(client_state, command, tx).await?;
                 return Ok(HandleCommandResult::ResponseSent);
             }
+            RedisCommandName::Debug => {
+                Self::debug(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Config => {
+                Self::config(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Wait => {
+                Self::wait(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Info => {
+                Self::info(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Slowlog => {
+                Self::slowlog(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Failover => {
+                Self::failover(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Acl => {
+                Self::acl(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Save => {
+                Self::save(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Bgsave => {
+                Self::bgsave(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Lastsave => {
+                Self::lastsave(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::SableCdc => {
+                Self::sable_cdc(client_state, command, tx).await?;
+                return Ok(HandleCommandResult::ResponseSent);
+            }
             _ => {
                 return Err(SableError::InvalidArgument(format!(
                     "Non server command {}",
@@ -43,43 +97,1080 @@ impl ServerCommands {
         Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
     }
 
+    /// `SAVE`: flush the database and back it up into `backup_dir`. Unlike `BGSAVE`, the reply is
+    /// sent once the backup completes, and the worker is blocked until then
+    async fn save(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        if command.arg_count() != 1 {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'save' command",
+            );
+            return Ok(());
+        }
+
+        let server_state = client_state.server_inner_state();
+        if !server_state.start_save() {
+            builder.error_string(response_buffer, "ERR Background save already in progress");
+            return Ok(());
+        }
+        let result = Self::create_backup(&server_state, client_state.database());
+        server_state.end_save();
+
+        match result {
+            Ok(_) => builder.ok(response_buffer),
+            Err(_) => builder.error_string(response_buffer, "ERR Failed to create a backup"),
+        }
+        Ok(())
+    }
+
+    /// `BGSAVE`: back up the database into `backup_dir`, without blocking the clients. Only the
+    /// `backup_keep` most recent backups are kept
+    async fn bgsave(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        if command.arg_count() != 1 {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'bgsave' command",
+            );
+            return Ok(());
+        }
+
+        let server_state = client_state.server_inner_state();
+        if !server_state.start_save() {
+            builder.error_string(response_buffer, "ERR Background save already in progress");
+            return Ok(());
+        }
+
+        let store = client_state.database().clone();
+        tokio::task::spawn_blocking(move || {
+            // errors are logged by `create_backup`
+            let _ = Self::create_backup(&server_state, &store);
+            server_state.end_save();
+        });
+        builder.simple_string(response_buffer, "Background saving started");
+        Ok(())
+    }
+
+    /// `LASTSAVE`: the time of the last successful backup
+    async fn lastsave(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        if command.arg_count() != 1 {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'lastsave' command",
+            );
+            return Ok(());
+        }
+        builder.number_u64(
+            response_buffer,
+            client_state.server_inner_state().last_save(),
+        );
+        Ok(())
+    }
+
+    /// `SABLE.CDC <from_seq>`: stream the changes made after the write batch `from_seq`, up to
+    /// the latest change at the time the command started. Every change is sent as its own frame:
+    /// `[seq, "put", key, value]`, `[seq, "del", key]` (`key` is the internal storage key) or
+    /// `[seq, "delrange", prefix]` (all the keys starting with `prefix`, e.g. `FLUSHDB`). The
+    /// last frame is the sequence number to pass as `from_seq` on the next call. The delivery is
+    /// at-least-once, see `StorageAdapter::changes_since`
+    async fn sable_cdc(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let mut buffer = BytesMut::with_capacity(CDC_FLUSH_THRESHOLD);
+        let from_seq = command_arg_at!(command, 1);
+        let Some(from_seq) = BytesMutUtils::parse::<u64>(from_seq) else {
+            builder.error_string(&mut buffer, ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE);
+            tx.write_all(&buffer).await?;
+            return Ok(());
+        };
+
+        let store = client_state.database();
+        if store.open_params().rocksdb.disable_wal {
+            builder.error_string(&mut buffer, "ERR CDC requires the WAL to be enabled");
+            tx.write_all(&buffer).await?;
+            return Ok(());
+        }
+
+        // stop once caught up with the writes that preceded this command
+        let latest_seq = store.latest_sequence_number()?;
+        let mut last_seq = from_seq;
+        for change in store.changes_since(from_seq)? {
+            let change = change?;
+            if change.seq > latest_seq {
+                break;
+            }
+            match change.change {
+                StorageUpdatesIterItem::Put(put) => {
+                    builder.add_array_len(&mut buffer, 4);
+                    builder.add_number::<u64>(&mut buffer, change.seq, false);
+                    builder.add_bulk_string_u8_arr(&mut buffer, b"put");
+                    builder.add_bulk_string(&mut buffer, &put.key);
+                    builder.add_bulk_string(&mut buffer, &put.value);
+                }
+                StorageUpdatesIterItem::Del(del) => {
+                    builder.add_array_len(&mut buffer, 3);
+                    builder.add_number::<u64>(&mut buffer, change.seq, false);
+                    builder.add_bulk_string_u8_arr(&mut buffer, b"del");
+                    builder.add_bulk_string(&mut buffer, &del.key);
+                }
+                StorageUpdatesIterItem::DelRange(del_range) => {
+                    builder.add_array_len(&mut buffer, 3);
+                    builder.add_number::<u64>(&mut buffer, change.seq, false);
+                    builder.add_bulk_string_u8_arr(&mut buffer, b"delrange");
+                    builder.add_bulk_string(&mut buffer, &del_range.prefix);
+                }
+            }
+            last_seq = change.seq;
+            if buffer.len() >= CDC_FLUSH_THRESHOLD {
+                tx.write_all(&buffer).await?;
+                buffer.clear();
+            }
+        }
+        builder.add_number::<u64>(&mut buffer, last_seq, false);
+        tx.write_all(&buffer).await?;
+        Ok(())
+    }
+
+    /// Flush the database and back it up into `backup_dir`, keeping the `backup_keep` most
+    /// recent backups. On success, `LASTSAVE` is updated
+    fn create_backup(
+        server_state: &ServerState,
+        store: &StorageAdapter,
+    ) -> Result<BackupInfo, SableError> {
+        let (backup_dir, backup_keep) = {
+            let options = server_state.options();
+            (options.backup_dir(), options.backup_settings.backup_keep)
+        };
+
+        let result = store
+            .flush()
+            .and_then(|_| store.create_backup_retaining(&backup_dir, backup_keep));
+        match &result {
+            Ok(backup) => {
+                tracing::info!(
+                    "Backup {} created successfully under {}",
+                    backup.id,
+                    backup_dir.display()
+                );
+                server_state.set_last_save(backup.timestamp);
+            }
+            Err(e) => tracing::warn!(
+                "Failed to create a backup under {}. {:?}",
+                backup_dir.display(),
+                e
+            ),
+        }
+        result
+    }
+
     /// Generate output for the `command` command
     async fn command(
         _client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
-        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+        tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let mut buffer = BytesMut::with_capacity(4096);
+        if !command.expect_args_count(1) {
+            let errmsg = format!(
+                "ERR wrong number of arguments for '{}' command",
+                command.main_command()
+            );
+            builder.add_bulk_string_u8_arr(&mut buffer, errmsg.as_bytes());
+            tx.write_all(buffer.as_ref()).await?;
+            return Ok(());
+        }
+
+        let manager = crate::commands::commands_manager();
+        if let Some(sub_command) = command.arg(1) {
+            let sub_command = BytesMutUtils::to_string(sub_command).to_lowercase();
+            match sub_command.as_str() {
+                "docs" => {
+                    tx.write_all(&manager.cmmand_docs_output()).await?;
+                }
+                "count" => {
+                    builder.number_usize(&mut buffer, manager.all_commands().len());
+                    tx.write_all(&buffer).await?;
+                }
+                "info" => {
+                    // COMMAND INFO [command-name [command-name ...]]
+                    if command.arg_count() == 2 {
+                        tx.write_all(&manager.cmmand_output()).await?;
+                    } else {
+                        let names: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+                        tx.write_all(&manager.command_info_output(&names)).await?;
+                    }
+                }
+                "getkeys" => {
+                    Self::command_getkeys(command, &mut buffer)?;
+                    tx.write_all(&buffer).await?;
+                }
+                _ => {
+                    // send an supported response
+                    builder.error_string(
+                        &mut buffer,
+                        format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                    );
+                    tx.write_all(&buffer).await?;
+                }
+            }
+        } else {
+            tx.write_all(&manager.cmmand_output()).await?;
+        }
+        Ok(())
+    }
+
+    /// `COMMAND GETKEYS command [arg [arg ...]]`: extract the keys from a full command, using
+    /// the key positions of the command's metadata
+    fn command_getkeys(
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        if command.arg_count() < 3 {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'command|getkeys' command",
+            );
+            return Ok(());
+        }
+
+        let args: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+        let cmd_name = BytesMutUtils::to_string(args[0]).to_lowercase();
+        let manager = crate::commands::commands_manager();
+        let Some(cmd_md) = manager.all_commands().get(&cmd_name) else {
+            builder.error_string(response_buffer, "ERR Invalid command specified");
+            return Ok(());
+        };
+
+        if !cmd_md.arity_matches(args.len()) {
+            builder.error_string(
+                response_buffer,
+                "ERR Invalid number of arguments specified for command",
+            );
+            return Ok(());
+        }
+
+        let positions = cmd_md.key_positions(args.len());
+        if positions.is_empty() {
+            builder.error_string(response_buffer, "ERR The command has no key arguments");
+            return Ok(());
+        }
+
+        builder.add_array_len(response_buffer, positions.len());
+        for pos in positions {
+            builder.add_bulk_string(response_buffer, args[pos]);
+        }
+        Ok(())
+    }
+
+    /// Handle the `config` command. `CONFIG GET`, `CONFIG SET` and `CONFIG REWRITE` are
+    /// implemented, other sub commands are rejected
+    async fn config(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "get" => {
+                // CONFIG GET parameter [parameter ...]
+                if !command.expect_args_count(3) {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'config|get' command",
+                    );
+                    return Ok(());
+                }
+
+                let patterns: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+                let parameters = client_state
+                    .server_inner_state()
+                    .options()
+                    .config_parameters();
+
+                // a parameter matched by several patterns is reported once
+                let matched: Vec<&(&str, String)> = parameters
+                    .iter()
+                    .filter(|(name, _)| {
+                        patterns
+                            .iter()
+                            .any(|pattern| StringUtils::glob_match(pattern, name.as_bytes(), true))
+                    })
+                    .collect();
+
+                builder.add_array_len(response_buffer, matched.len() * 2);
+                for (name, value) in matched {
+                    builder.add_bulk_string_u8_arr(response_buffer, name.as_bytes());
+                    builder.add_bulk_string_u8_arr(response_buffer, value.as_bytes());
+                }
+            }
+            "set" => {
+                // CONFIG SET parameter value [parameter value ...]
+                if !command.expect_args_count(4) || !command.arg_count().is_multiple_of(2) {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'config|set' command",
+                    );
+                    return Ok(());
+                }
+
+                let mut parameters = Vec::<(String, String)>::new();
+                let mut pos = 2usize;
+                while pos < command.arg_count() {
+                    let name = command_arg_at_as_str!(command, pos);
+                    let value = BytesMutUtils::to_string(command_arg_at!(command, pos + 1));
+                    parameters.push((name, value));
+                    pos += 2;
+                }
+
+                // all or nothing: the options are only updated if all the parameters are valid
+                let result = client_state.server_inner_state().update_options(|options| {
+                    let mut updated_options = options.clone();
+                    for (name, value) in &parameters {
+                        match updated_options.set_config_parameter(name, value) {
+                            SetConfigResult::Ok => {}
+                            err => return Err((name.clone(), err)),
+                        }
+                    }
+                    *options = updated_options;
+                    Ok(())
+                });
+
+                match result {
+                    Ok(()) | Err((_, SetConfigResult::Ok)) => builder.ok(response_buffer),
+                    Err((name, SetConfigResult::NotFound)) => builder.error_string(
+                        response_buffer,
+                        &format!(
+                            "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                            name
+                        ),
+                    ),
+                    Err((name, SetConfigResult::Immutable)) => builder.error_string(
+                        response_buffer,
+                        &format!(
+                            "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                            name
+                        ),
+                    ),
+                    Err((name, SetConfigResult::InvalidValue(accepted))) => builder.error_string(
+                        response_buffer,
+                        &format!(
+                            "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                            name, accepted
+                        ),
+                    ),
+                }
+            }
+            "rewrite" => {
+                let server_state = client_state.server_inner_state();
+                let options = server_state.options();
+                if options.config_file.is_none() {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR The server is running without a config file",
+                    );
+                    return Ok(());
+                }
+                match options.rewrite_config() {
+                    Ok(()) => builder.ok(response_buffer),
+                    Err(e) => builder.error_string(
+                        response_buffer,
+                        &format!("ERR Rewriting config file: {}", e),
+                    ),
+                }
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the `debug` command. Supported sub commands:
+    /// - `DEBUG REPL-OFFSET`: reply with the sequence number of the last write applied to the
+    ///   storage (`write_offset`). When running as a replica, the reply also contains the last
+    ///   primary sequence number that was applied to this instance (`applied_offset`)
+    /// - `DEBUG SLEEP <seconds>`: delay the reply (fractions of a second are accepted)
+    /// - `DEBUG OBJECT key`: low level information about the key
+    ///
+    /// `DEBUG` is an admin command, destructive sub commands are not supported
+    async fn debug(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "repl-offset" => {
+                let write_offset = client_state.database().latest_sequence_number()?;
+                if client_state.server_inner_state().is_replica() {
+                    let applied_offset = ReplicationClient::applied_sequence_number(
+                        &client_state.database().open_params().db_path,
+                    )
+                    .unwrap_or_default();
+                    builder.add_array_len(response_buffer, 4);
+                    builder.add_bulk_string_u8_arr(response_buffer, b"write_offset");
+                    builder.add_number::<u64>(response_buffer, write_offset, false);
+                    builder.add_bulk_string_u8_arr(response_buffer, b"applied_offset");
+                    builder.add_number::<u64>(response_buffer, applied_offset, false);
+                } else {
+                    builder.add_array_len(response_buffer, 2);
+                    builder.add_bulk_string_u8_arr(response_buffer, b"write_offset");
+                    builder.add_number::<u64>(response_buffer, write_offset, false);
+                }
+            }
+            "sleep" => {
+                if !command.expect_args_count(3) || command.arg_count() > 3 {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'debug|sleep' command",
+                    );
+                    return Ok(());
+                }
+                let Some(seconds) = BytesMutUtils::parse::<f64>(command_arg_at!(command, 2))
+                    .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                else {
+                    builder.error_string(response_buffer, ErrorStrings::VALUE_NOT_VALID_FLOAT);
+                    return Ok(());
+                };
+                // only this client is delayed, the worker keeps serving its other clients
+                tokio::time::sleep(tokio::time::Duration::from_secs_f64(seconds)).await;
+                builder.ok(response_buffer);
+            }
+            "object" => {
+                if !command.expect_args_count(3) || command.arg_count() > 3 {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'debug|object' command",
+                    );
+                    return Ok(());
+                }
+                Self::debug_object(client_state, command_arg_at!(command, 2), response_buffer)?;
+            }
+            "compact" => {
+                if command.arg_count() > 3 {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'debug|compact' command",
+                    );
+                    return Ok(());
+                }
+                Self::debug_compact(client_state, command, response_buffer)?;
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `DEBUG COMPACT [db]`: compact the storage in the background, so the space used by deleted
+    /// records is reclaimed. With `db`, only the range of the database primary records is
+    /// compacted (the items of the collections are keyed by their owner, not by database). The
+    /// progress is reported by `INFO persistence`
+    fn debug_compact(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let server_state = client_state.server_inner_state();
+        let prefix = match command.arg(2) {
+            None => None,
+            Some(db_id) => {
+                let databases = server_state.options().general_settings.databases;
+                match BytesMutUtils::parse::<u16>(db_id) {
+                    Some(db_id) if (db_id as usize) < databases => {
+                        Some(PrimaryKeyMetadata::primary_key_prefix(db_id))
+                    }
+                    _ => {
+                        builder.error_string(response_buffer, "ERR DB index is out of range");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        if !server_state.start_compaction() {
+            builder.error_string(response_buffer, "ERR Compaction already in progress");
+            return Ok(());
+        }
+
+        let store = client_state.database().clone();
+        tokio::task::spawn_blocking(move || {
+            let started_at = std::time::Instant::now();
+            match store.compact(prefix.as_ref()) {
+                Ok(()) => tracing::info!(
+                    "Compaction completed in {} ms",
+                    started_at.elapsed().as_millis()
+                ),
+                Err(e) => tracing::warn!("Compaction failed. {:?}", e),
+            }
+            server_state.end_compaction(TimeUtils::epoch_ms().unwrap_or_default() / 1000);
+        });
+        builder.simple_string(response_buffer, "Background compaction started");
+        Ok(())
+    }
+
+    /// `DEBUG OBJECT key`: the key encoding, its serialized length (the bytes of its primary
+    /// record and all its items) and, for collections, the number of item records it owns
+    fn debug_object(
+        client_state: Rc<ClientState>,
+        key: &BytesMut,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let generic_db =
+            GenericDb::with_storage(client_state.database(), client_state.database_id());
+        let Some(value) = generic_db.get_live(key)? else {
+            builder.error_string(response_buffer, ErrorStrings::NO_SUCH_KEY);
+            return Ok(());
+        };
+
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        let idle_seconds = TimeUtils::epoch_ms()?.saturating_sub(common_md.accessed_at()) / 1000;
+        let mut output = format!(
+            "Value at:0 refcount:1 encoding:{}",
+            ObjectCommands::encoding(&client_state, key, &value)?
+        );
+
+        let mut serialized_length = value.len();
+        let mut subkeys = None;
+        if let Some(items_prefix) = generic_db.items_prefix(key)? {
+            let (count, items_size) = generic_db.sample_items(&items_prefix, usize::MAX)?;
+            serialized_length = serialized_length.saturating_add(items_size);
+            subkeys = Some(count);
+        }
+
+        output.push_str(&format!(
+            " serializedlength:{} lru_seconds_idle:{}",
+            serialized_length, idle_seconds
+        ));
+        if let Some(subkeys) = subkeys {
+            output.push_str(&format!(" subkeys:{}", subkeys));
+        }
+        builder.simple_string(response_buffer, &output);
+        Ok(())
+    }
+
+    /// `SLOWLOG <GET [count] | LEN | RESET>`
+    async fn slowlog(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = RespBuilderV2::default();
+        let server_state = client_state.server_inner_state();
+        match (sub_command.as_str(), command.arg_count()) {
+            ("get", 2 | 3) => {
+                // `-1` returns all the entries
+                let count = match command.arg(2) {
+                    None => 10,
+                    Some(count) => match BytesMutUtils::parse::<i64>(count) {
+                        Some(-1) => usize::MAX,
+                        Some(count) if count >= 0 => count as usize,
+                        _ => {
+                            builder.error_string(
+                                response_buffer,
+                                "ERR count should be greater than or equal to -1",
+                            );
+                            return Ok(());
+                        }
+                    },
+                };
+
+                let entries = server_state.slowlog().entries(count);
+                builder.add_array_len(response_buffer, entries.len());
+                for entry in entries {
+                    builder.add_array_len(response_buffer, 6);
+                    builder.add_number::<u64>(response_buffer, entry.id, false);
+                    builder.add_number::<u64>(response_buffer, entry.timestamp, false);
+                    builder.add_number::<u64>(response_buffer, entry.duration_us, false);
+                    builder.add_array_len(response_buffer, entry.arguments.len());
+                    for arg in &entry.arguments {
+                        builder.add_bulk_string_u8_arr(response_buffer, arg);
+                    }
+                    // SableDb does not keep the peer address, the client ID is reported instead
+                    builder.add_bulk_string_u8_arr(
+                        response_buffer,
+                        entry.client_id.to_string().as_bytes(),
+                    );
+                    builder.add_bulk_string_u8_arr(response_buffer, entry.client_name.as_bytes());
+                }
+            }
+            ("len", 2) => {
+                builder.number_usize(response_buffer, server_state.slowlog().len());
+            }
+            ("reset", 2) => {
+                server_state.slowlog().reset();
+                builder.ok(response_buffer);
+            }
+            ("get" | "len" | "reset", _) => {
+                builder.error_string(
+                    response_buffer,
+                    &format!(
+                        "ERR wrong number of arguments for 'slowlog|{}' command",
+                        sub_command
+                    ),
+                );
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    &format!("ERR unknown subcommand '{}'", sub_command),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `ACL SETUSER username [rule [rule ...]]`, `ACL GETUSER username`, `ACL LIST` and
+    /// `ACL WHOAMI`
+    async fn acl(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = client_state.resp_builder();
+        let server_state = client_state.server_inner_state();
+        match (sub_command.as_str(), command.arg_count()) {
+            ("setuser", 3..) => {
+                let username = BytesMutUtils::to_string(command_arg_at!(command, 2));
+                let rules: Vec<String> = command.args_vec()[3..]
+                    .iter()
+                    .map(BytesMutUtils::to_string)
+                    .collect();
+                match server_state.acl().set_user(&username, &rules) {
+                    Ok(()) => builder.ok(response_buffer),
+                    Err(msg) => builder.error_string(response_buffer, &msg),
+                }
+            }
+            ("getuser", 3) => {
+                let username = BytesMutUtils::to_string(command_arg_at!(command, 2));
+                let Some(user) = server_state.acl().user(&username) else {
+                    builder.null_string(response_buffer);
+                    return Ok(());
+                };
+                let mut flags = vec![if user.enabled() { "on" } else { "off" }];
+                if user.nopass() {
+                    flags.push("nopass");
+                }
+                builder.add_map_len(response_buffer, 4);
+                builder.add_bulk_string_u8_arr(response_buffer, b"flags");
+                builder.add_strings(response_buffer, &flags);
+                builder.add_bulk_string_u8_arr(response_buffer, b"passwords");
+                let passwords = user.password_digests();
+                builder.add_array_len(response_buffer, passwords.len());
+                for password in &passwords {
+                    builder.add_bulk_string_u8_arr(response_buffer, password.as_bytes());
+                }
+                builder.add_bulk_string_u8_arr(response_buffer, b"commands");
+                builder.add_bulk_string_u8_arr(
+                    response_buffer,
+                    user.commands_description().as_bytes(),
+                );
+                builder.add_bulk_string_u8_arr(response_buffer, b"keys");
+                builder.add_bulk_string_u8_arr(response_buffer, user.keys_description().as_bytes());
+            }
+            ("list", 2) => {
+                let users = server_state.acl().users();
+                builder.add_array_len(response_buffer, users.len());
+                for user in &users {
+                    builder.add_bulk_string_u8_arr(response_buffer, user.description().as_bytes());
+                }
+            }
+            ("whoami", 2) => {
+                builder.add_bulk_string_u8_arr(response_buffer, client_state.user().as_bytes());
+            }
+            ("setuser" | "getuser" | "list" | "whoami", _) => {
+                builder.error_string(
+                    response_buffer,
+                    &format!(
+                        "ERR wrong number of arguments for 'acl|{}' command",
+                        sub_command
+                    ),
+                );
+            }
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    &format!("ERR unknown subcommand '{}'", sub_command),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `INFO [section [section ...]]`. Without a section (or with `all`, `default` or
+    /// `everything`) all the sections are returned. A section is only built when it is
+    /// requested
+    async fn info(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let requested: Vec<String> = command
+            .args_vec()
+            .iter()
+            .skip(1)
+            .map(|section| BytesMutUtils::to_string(section).to_lowercase())
+            .collect();
+        let all_sections = requested.is_empty()
+            || requested
+                .iter()
+                .any(|section| matches!(section.as_str(), "all" | "default" | "everything"));
+        let is_requested =
+            |name: &str| all_sections || requested.iter().any(|section| section == name);
+
+        let mut sections = Vec::<String>::new();
+        let telemetry_sections = client_state
+            .server_inner_state()
+            .shared_telemetry()
+            .lock()
+            .expect("mutex")
+            .info_sections();
+        for (name, content) in telemetry_sections {
+            if is_requested(name) {
+                sections.push(content);
+            }
+        }
+
+        if is_requested("persistence") {
+            sections.push(Self::info_persistence(&client_state)?);
+        }
+
+        if is_requested("replication") {
+            sections.push(Self::info_replication(&client_state)?);
+        }
+
+        if is_requested("keyspace") {
+            sections.push(Self::info_keyspace(&client_state)?);
+        }
+
+        let mut info = sections.join("\n\n");
+        info.push('\n');
+        builder.bulk_string(response_buffer, &BytesMut::from(info.as_bytes()));
+        Ok(())
+    }
+
+    /// Build the `persistence` section of `INFO`: the backups, the compactions and the storage
+    /// engine statistics (e.g. a large number of level 0 files or pending compaction bytes
+    /// means compaction falls behind)
+    fn info_persistence(client_state: &ClientState) -> Result<String, SableError> {
+        let server_state = client_state.server_inner_state();
+        let mut lines = vec![
+            "# Persistence".to_string(),
+            format!(
+                "rdb_bgsave_in_progress: {}",
+                server_state.is_save_in_progress() as u8
+            ),
+            format!("rdb_last_save_time: {}", server_state.last_save()),
+            format!(
+                "compaction_in_progress: {}",
+                server_state.is_compaction_in_progress() as u8
+            ),
+            format!("last_compaction_time: {}", server_state.last_compaction()),
+        ];
+        for (name, value) in client_state.database().stats()? {
+            lines.push(format!("rocksdb_{}: {}", name, value));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Build the `replication` section of `INFO`
+    fn info_replication(client_state: &ClientState) -> Result<String, SableError> {
+        let server_state = client_state.server_inner_state();
+        let mut lines = vec!["# Replication".to_string()];
+        if server_state.is_primary() {
+            let replicas = server_state.replica_acks().replicas();
+            lines.push("role: master".to_string());
+            lines.push(format!("connected_slaves: {}", replicas.len()));
+            for (index, (address, acked_seq)) in replicas.iter().enumerate() {
+                let (ip, port) = address.rsplit_once(':').unwrap_or((address.as_str(), ""));
+                let (state, offset) = match acked_seq {
+                    Some(acked_seq) => ("online", *acked_seq),
+                    None => ("wait_bgsave", 0),
+                };
+                lines.push(format!(
+                    "slave{}: ip={},port={},state={},offset={}",
+                    index, ip, port, state, offset
+                ));
+            }
+            lines.push(format!(
+                "master_repl_offset: {}",
+                client_state.database().latest_sequence_number()?
+            ));
+        } else {
+            let repl_config = server_state.options().load_replication_config();
+            let link_status = if ReplicationClient::primary_link_up() {
+                "up"
+            } else {
+                "down"
+            };
+            let last_io = match ReplicationClient::primary_last_io_seconds_ago() {
+                Some(seconds) => seconds.to_string(),
+                None => "-1".to_string(),
+            };
+            lines.push("role: slave".to_string());
+            lines.push(format!("master_host: {}", repl_config.ip));
+            lines.push(format!("master_port: {}", repl_config.port));
+            lines.push(format!("master_link_status: {}", link_status));
+            lines.push(format!("master_last_io_seconds_ago: {}", last_io));
+            lines.push(format!(
+                "master_repl_offset: {}",
+                ReplicationClient::applied_sequence_number(
+                    &client_state.database().open_params().db_path
+                )
+                .unwrap_or_default()
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Build the `keyspace` section of `INFO`: a `db<N>:keys=<count>,expires=<count>` line for
+    /// every database that has keys
+    fn info_keyspace(client_state: &ClientState) -> Result<String, SableError> {
+        let server_state = client_state.server_inner_state();
+        let key_counter = server_state.key_counter();
+        let databases = server_state.options().general_settings.databases;
+        let mut lines = vec!["# Keyspace".to_string()];
+        for db_id in 0..databases as u16 {
+            let counts = key_counter.key_counts(client_state.database(), db_id)?;
+            if counts.keys == 0 {
+                continue;
+            }
+            lines.push(format!(
+                "db{}:keys={},expires={}",
+                db_id, counts.keys, counts.expires
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// `WAIT numreplicas timeout`: block until `numreplicas` replicas applied all the changes
+    /// written so far, or until `timeout` milliseconds elapsed (`0` means: wait forever).
+    /// Reply with the number of replicas that acknowledged the changes
+    async fn wait(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let Some(num_replicas) = BytesMutUtils::parse::<u64>(command_arg_at!(command, 1)) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+        let Some(timeout_ms) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 2)) else {
+            builder.error_string(
+                response_buffer,
+                "ERR timeout is not an integer or out of range",
+            );
+            return Ok(());
+        };
+        if timeout_ms < 0 {
+            builder.error_string(response_buffer, "ERR timeout is negative");
+            return Ok(());
+        }
+
+        let seq_num = client_state.database().latest_sequence_number()?;
+        let deadline = (timeout_ms > 0).then(|| {
+            std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms as u64)
+        });
+
+        let server_state = client_state.server_inner_state();
+        let acked = loop {
+            let acked = server_state.replica_acks().acked_replicas(seq_num);
+            if acked as u64 >= num_replicas
+                || server_state.is_replica()
+                || server_state.replica_acks().replicas_count() == 0
+                || deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline)
+                || !client_state.active()
+            {
+                break acked;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(WAIT_POLL_INTERVAL_MS)).await;
+        };
+        builder.number_usize(response_buffer, acked);
+        Ok(())
+    }
+
+    /// `FAILOVER TO host port [TIMEOUT milliseconds]`: hand the primary role over to the replica
+    /// accepting clients on `host:port`. Writes are held until the replica applied the last
+    /// change written to this server, the replica is then promoted and this server becomes its
+    /// replica. If the replica does not catch up within `TIMEOUT` (default: no timeout) or can
+    /// not be promoted, the failover is aborted and this server remains the primary
+    async fn failover(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         let builder = RespBuilderV2::default();
-        let mut buffer = BytesMut::with_capacity(4096);
-        if !command.expect_args_count(1) {
-            let errmsg = format!(
-                "ERR wrong number of arguments for '{}' command",
-                command.main_command()
+        let mut target: Option<(String, u16)> = None;
+        let mut timeout_ms: Option<u64> = None;
+        let mut pos = 1usize;
+        while pos < command.arg_count() {
+            let option = command_arg_at_as_str!(command, pos);
+            match (option.as_str(), command.arg(pos + 1), command.arg(pos + 2)) {
+                ("to", Some(host), Some(port)) if target.is_none() => {
+                    let Some(port) = BytesMutUtils::parse::<u16>(port) else {
+                        builder.error_string(response_buffer, ErrorStrings::INVALID_PRIMARY_PORT);
+                        return Ok(());
+                    };
+                    target = Some((BytesMutUtils::to_string(host), port));
+                    pos += 3;
+                }
+                ("timeout", Some(timeout), _) if timeout_ms.is_none() => {
+                    match BytesMutUtils::parse::<u64>(timeout) {
+                        Some(timeout) if timeout > 0 => timeout_ms = Some(timeout),
+                        _ => {
+                            builder.error_string(
+                                response_buffer,
+                                "ERR FAILOVER timeout must be greater than 0",
+                            );
+                            return Ok(());
+                        }
+                    }
+                    pos += 2;
+                }
+                _ => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
+                }
+            }
+        }
+
+        let server_state = client_state.server_inner_state();
+        if server_state.is_replica() {
+            builder.error_string(
+                response_buffer,
+                "ERR FAILOVER is not valid when server is a replica.",
+            );
+            return Ok(());
+        }
+        // replicas do not announce the port on which they accept clients, the target must be
+        // provided
+        let Some((host, port)) = target else {
+            builder.error_string(
+                response_buffer,
+                "ERR FAILOVER requires a target replica (FAILOVER TO host port)",
             );
-            builder.add_bulk_string_u8_arr(&mut buffer, errmsg.as_bytes());
-            tx.write_all(buffer.as_ref()).await?;
+            return Ok(());
+        };
+        if !server_state.start_failover() {
+            builder.error_string(response_buffer, "ERR FAILOVER already in progress.");
             return Ok(());
         }
+        let _failover = FailoverGuard { server_state };
 
-        let manager = crate::commands::commands_manager();
-        if let Some(sub_command) = command.arg(1) {
-            let sub_command = BytesMutUtils::to_string(sub_command).to_lowercase();
-            if sub_command == "docs" {
-                tx.write_all(&manager.cmmand_docs_output()).await?;
-            } else {
-                // send an supported response
+        match Self::run_failover(&client_state, &host, port, timeout_ms).await {
+            Ok(()) => builder.ok(response_buffer),
+            Err(reason) => {
+                tracing::warn!("Failover to {}:{} aborted. {}", host, port, reason);
                 builder.error_string(
-                    &mut buffer,
-                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                    response_buffer,
+                    format!("ERR FAILOVER aborted. {}", reason).as_str(),
                 );
-                tx.write_all(&buffer).await?;
             }
-        } else {
-            tx.write_all(&manager.cmmand_output()).await?;
         }
         Ok(())
     }
 
+    /// Wait for the replica `host:port` to catch up, promote it and follow it. Writes are held
+    /// by the caller. On error, return the reason the failover was aborted
+    async fn run_failover(
+        client_state: &ClientState,
+        host: &str,
+        port: u16,
+        timeout_ms: Option<u64>,
+    ) -> Result<(), String> {
+        let mut target = FailoverTarget::connect(host, port)
+            .await
+            .map_err(|e| format!("Failed to connect to {}:{}. {:?}", host, port, e))?;
+
+        // new writes are held, wait for the writes that are already running
+        {
+            let _unused = LockManager::lock_all_keys_exclusive();
+        }
+        let seq_num = client_state
+            .database()
+            .latest_sequence_number()
+            .map_err(|e| format!("{:?}", e))?;
+
+        let deadline = timeout_ms.map(|timeout_ms| {
+            std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms)
+        });
+        loop {
+            let applied = target
+                .applied_offset()
+                .await
+                .map_err(|e| format!("Failed to query {}:{}. {:?}", host, port, e))?;
+            match applied {
+                None => return Err(format!("{}:{} is not a replica", host, port)),
+                Some(applied) if applied >= seq_num => break,
+                Some(_) => {}
+            }
+            if deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+                return Err(format!(
+                    "{}:{} did not catch up with offset {} in time",
+                    host, port, seq_num
+                ));
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(WAIT_POLL_INTERVAL_MS)).await;
+        }
+
+        target
+            .promote()
+            .await
+            .map_err(|e| format!("Failed to promote {}:{}. {:?}", host, port, e))?;
+        client_state
+            .server_inner_state()
+            .connect_to_primary(
+                host.to_string(),
+                ServerOptions::default_replication_port(port),
+            )
+            .await
+            .map_err(|e| format!("Failed to follow the new primary. {:?}", e))
+    }
+
     async fn replica_of(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
@@ -138,10 +1229,11 @@ impl ServerCommands {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Client, ServerState};
+    use crate::{Client, ServerOptions, ServerState};
 
     use std::rc::Rc;
     use std::sync::Arc;
+    use test_case::test_case;
 
     #[test]
     fn test_command() {
@@ -202,4 +1294,873 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_debug_sleep_and_object() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_debug").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            let started_at = std::time::Instant::now();
+            assert_eq!(run_command(vec!["debug", "sleep", "0.2"]).await, "+OK\r\n");
+            assert!(started_at.elapsed() >= std::time::Duration::from_millis(200));
+
+            assert_eq!(run_command(vec!["set", "mykey", "value"]).await, "+OK\r\n");
+            assert_eq!(
+                run_command(vec!["debug", "object", "mykey"]).await,
+                format!(
+                    "+Value at:0 refcount:1 encoding:raw serializedlength:{} lru_seconds_idle:0\r\n",
+                    crate::StringValueMetadata::SIZE + "value".len()
+                )
+            );
+
+            assert_eq!(run_command(vec!["rpush", "mylist", "a", "b", "c"]).await, ":3\r\n");
+            let output = run_command(vec!["debug", "object", "mylist"]).await;
+            assert!(output.starts_with("+Value at:0 refcount:1 encoding:listpack serializedlength:"));
+            assert!(output.ends_with(" lru_seconds_idle:0 subkeys:3\r\n"));
+
+            for (args, expected_value) in [
+                (vec!["debug", "object", "no_such_key"], "-ERR no such key\r\n"),
+                (
+                    vec!["debug", "object"],
+                    "-ERR wrong number of arguments for 'debug|object' command\r\n",
+                ),
+                (
+                    vec!["debug", "sleep", "1", "2"],
+                    "-ERR wrong number of arguments for 'debug|sleep' command\r\n",
+                ),
+                (
+                    vec!["debug", "sleep", "abc"],
+                    "-ERR value is not a valid float\r\n",
+                ),
+                (
+                    vec!["debug", "sleep", "-1"],
+                    "-ERR value is not a valid float\r\n",
+                ),
+                (
+                    vec!["debug", "reload"],
+                    "-ERR unknown subcommand 'reload'\r\n",
+                ),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+        });
+    }
+
+    #[test]
+    fn test_debug_repl_offset() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store.clone(), None);
+
+            // primary: the write offset must move forward after a write
+            let cmd = Rc::new(RedisCommand::for_test(["debug", "repl-offset"].to_vec()));
+            let mut sink = crate::tests::ResponseSink::with_name("test_debug_repl_offset").await;
+            Client::handle_command(client.inner(), cmd.clone(), &mut sink.fp)
+                .await
+                .unwrap();
+            let before = store.latest_sequence_number().unwrap();
+            assert_eq!(
+                sink.read_all().await,
+                format!("*2\r\n$12\r\nwrite_offset\r\n:{}\r\n", before)
+            );
+
+            let set_cmd = Rc::new(RedisCommand::for_test(["set", "key", "value"].to_vec()));
+            let mut sink = crate::tests::ResponseSink::with_name("test_debug_repl_offset").await;
+            Client::handle_command(client.inner(), set_cmd, &mut sink.fp)
+                .await
+                .unwrap();
+
+            let mut sink = crate::tests::ResponseSink::with_name("test_debug_repl_offset").await;
+            Client::handle_command(client.inner(), cmd.clone(), &mut sink.fp)
+                .await
+                .unwrap();
+            let after = store.latest_sequence_number().unwrap();
+            assert!(after > before);
+            assert_eq!(
+                sink.read_all().await,
+                format!("*2\r\n$12\r\nwrite_offset\r\n:{}\r\n", after)
+            );
+
+            // replica: the applied offset is reported as well
+            client.inner().server_inner_state().set_replica();
+            let mut sink = crate::tests::ResponseSink::with_name("test_debug_repl_offset").await;
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(
+                sink.read_all().await,
+                format!(
+                    "*4\r\n$12\r\nwrite_offset\r\n:{}\r\n$14\r\napplied_offset\r\n:0\r\n",
+                    after
+                )
+            );
+
+            // unknown sub command
+            let cmd = Rc::new(RedisCommand::for_test(["debug", "nosuchcmd"].to_vec()));
+            let mut sink = crate::tests::ResponseSink::with_name("test_debug_repl_offset").await;
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(
+                sink.read_all().await,
+                "-ERR unknown subcommand 'nosuchcmd'\r\n"
+            );
+        });
+    }
+
+    #[test_case(vec![
+        (vec!["config"], "-ERR wrong number of arguments for 'config' command\r\n"),
+        (vec!["config", "get"], "-ERR wrong number of arguments for 'config|get' command\r\n"),
+        (vec!["config", "get", "maxmemory", "save"], "*4\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n$4\r\nsave\r\n$0\r\n\r\n"),
+        (vec!["config", "get", "max*", "maxmemory", "MAXMEMORY"], "*6\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n$16\r\nmaxmemory-policy\r\n$10\r\nnoeviction\r\n$17\r\nmaxmemory-samples\r\n$1\r\n5\r\n"),
+        (vec!["config", "get", "nosuchparam"], "*0\r\n"),
+        (vec!["config", "get", "port", "nosuchparam"], "*2\r\n$4\r\nport\r\n$4\r\n6379\r\n"),
+        (vec!["config", "set", "save", ""], "+OK\r\n"),
+        (vec!["config", "resetstat"], "-ERR unknown subcommand 'resetstat'\r\n"),
+    ], "test_config"; "test_config")]
+    #[test_case(vec![
+        (vec!["config", "set", "client-response-buffer-size"], "-ERR wrong number of arguments for 'config|set' command\r\n"),
+        (vec!["config", "set", "client-response-buffer-size", "4096", "active-expire-sample-size"], "-ERR wrong number of arguments for 'config|set' command\r\n"),
+        (vec!["config", "set", "client-response-buffer-size", "4096"], "+OK\r\n"),
+        (vec!["config", "get", "client-response-buffer-size"], "*2\r\n$27\r\nclient-response-buffer-size\r\n$4\r\n4096\r\n"),
+        (vec!["config", "set", "CLIENT-RESPONSE-BUFFER-SIZE", "8192", "active-expire-sample-size", "50"], "+OK\r\n"),
+        (vec!["config", "get", "client-response-buffer-size", "active-expire-sample-size"], "*4\r\n$27\r\nclient-response-buffer-size\r\n$4\r\n8192\r\n$25\r\nactive-expire-sample-size\r\n$2\r\n50\r\n"),
+        // all or nothing
+        (vec!["config", "set", "active-expire-sample-size", "10", "client-response-buffer-size", "abc"], "-ERR CONFIG SET failed (possibly related to argument 'client-response-buffer-size') - argument must be a positive integer\r\n"),
+        (vec!["config", "set", "active-expire-sample-size", "0"], "-ERR CONFIG SET failed (possibly related to argument 'active-expire-sample-size') - argument must be a positive integer\r\n"),
+        (vec!["config", "get", "active-expire-sample-size"], "*2\r\n$25\r\nactive-expire-sample-size\r\n$2\r\n50\r\n"),
+        (vec!["config", "set", "rocksdb-write-buffer-size", "1024"], "-ERR CONFIG SET failed (possibly related to argument 'rocksdb-write-buffer-size') - can't set immutable config\r\n"),
+        (vec!["config", "set", "port", "7000"], "-ERR CONFIG SET failed (possibly related to argument 'port') - can't set immutable config\r\n"),
+        (vec!["config", "set", "maxmemory", "1mb", "maxmemory-policy", "ALLKEYS-LRU"], "+OK\r\n"),
+        (vec!["config", "get", "maxmemory", "maxmemory-policy"], "*4\r\n$9\r\nmaxmemory\r\n$7\r\n1048576\r\n$16\r\nmaxmemory-policy\r\n$11\r\nallkeys-lru\r\n"),
+        (vec!["config", "set", "maxmemory-policy", "random"], "-ERR CONFIG SET failed (possibly related to argument 'maxmemory-policy') - argument(s) must be one of the following: noeviction, allkeys-lru, allkeys-lfu, volatile-lru, volatile-ttl\r\n"),
+        (vec!["config", "set", "maxmemory", "lots"], "-ERR CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value\r\n"),
+        (vec!["config", "set", "maxmemory", "100mb"], "+OK\r\n"),
+        (vec!["config", "get", "maxmemory"], "*2\r\n$9\r\nmaxmemory\r\n$9\r\n104857600\r\n"),
+        (vec!["config", "set", "maxmemory", "1GB"], "+OK\r\n"),
+        (vec!["config", "get", "maxmemory"], "*2\r\n$9\r\nmaxmemory\r\n$10\r\n1073741824\r\n"),
+        (vec!["config", "set", "maxmemory", "0"], "+OK\r\n"),
+        (vec!["config", "set", "slowlog-log-slower-than", "-1"], "+OK\r\n"),
+        (vec!["config", "set", "slowlog-log-slower-than", "fast"], "-ERR CONFIG SET failed (possibly related to argument 'slowlog-log-slower-than') - argument must be an integer\r\n"),
+        (vec!["config", "get", "maxmemory", "slowlog-log-slower-than"], "*4\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n$23\r\nslowlog-log-slower-than\r\n$2\r\n-1\r\n"),
+        (vec!["config", "set", "nosuchparam", "1"], "-ERR Unknown option or number of arguments for CONFIG SET - 'nosuchparam'\r\n"),
+    ], "test_config_set"; "test_config_set")]
+    fn test_config_command(
+        args_vec: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args_vec {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rewrite() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let test_path = store
+                .open_params()
+                .db_path
+                .display()
+                .to_string()
+                .trim_end_matches(".db")
+                .to_string();
+            let config_file = format!("{}.ini", test_path);
+            // the replication configuration is kept next to the test files
+            let config_dir = std::path::PathBuf::from(format!("{}.conf", test_path));
+            std::fs::create_dir_all(&config_dir).unwrap();
+            std::fs::write(
+                &config_file,
+                "[general]\n# the port\nport = 6380\n\n[client_limits]\n# flush threshold\nclient_response_buffer_size = 1MB\n",
+            )
+            .unwrap();
+
+            // running without a config file
+            let client = Client::new(Arc::<ServerState>::default(), store.clone(), None);
+            let mut sink = crate::tests::ResponseSink::with_name("test_config_rewrite").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["config", "rewrite"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(
+                sink.read_all().await,
+                "-ERR The server is running without a config file\r\n"
+            );
+
+            let mut options = ServerOptions::from_config(config_file.clone()).unwrap();
+            assert_eq!(options.client_limits.client_response_buffer_size, 1 << 20);
+            options.general_settings.config_dir = Some(config_dir.clone());
+            let server_state = Arc::new(ServerState::new().set_server_options(options));
+            let client = Client::new(server_state, store, None);
+            for (args, expected_value) in [
+                (
+                    vec!["config", "set", "client-response-buffer-size", "4096"],
+                    "+OK\r\n",
+                ),
+                (
+                    vec!["config", "set", "active-expire-sample-size", "10"],
+                    "+OK\r\n",
+                ),
+                (vec!["config", "rewrite"], "+OK\r\n"),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_config_rewrite").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+
+            // the changes survive a reload, the comments and the other keys are kept
+            let options = ServerOptions::from_config(config_file.clone()).unwrap();
+            assert_eq!(options.client_limits.client_response_buffer_size, 4096);
+            assert_eq!(options.expiration_settings.active_expire_sample_size, 10);
+            assert_eq!(options.general_settings.port, 6380);
+            assert_eq!(
+                std::fs::read_to_string(&config_file).unwrap(),
+                "[general]\n# the port\nport = 6380\n\n[client_limits]\n# flush threshold\nclient_response_buffer_size = 4096\nproto_max_bulk_len = 536870912\nproto_max_multibulk_len = 1048576\n\n[expiration]\nactive_expire_sample_size = 10\n\n[slowlog]\nslowlog_log_slower_than = 10000\nslowlog_max_len = 128\n\n[memory]\nmaxmemory = 0\nmaxmemory_policy = noeviction\nmaxmemory_samples = 5\n\n[encoding]\nzset_max_listpack_entries = 128\nzset_max_listpack_value = 64\n"
+            );
+            assert!(config_dir.join("replication.json").exists());
+            let _ = std::fs::remove_file(&config_file);
+            let _ = std::fs::remove_dir_all(&config_dir);
+        });
+        Ok(())
+    }
+
+    #[test_case(vec![
+        (vec!["command", "getkeys"], "-ERR wrong number of arguments for 'command|getkeys' command\r\n"),
+        (vec!["command", "getkeys", "get", "mykey"], "*1\r\n$5\r\nmykey\r\n"),
+        (vec!["command", "getkeys", "GET", "mykey"], "*1\r\n$5\r\nmykey\r\n"),
+        (vec!["command", "getkeys", "mset", "k1", "v1", "k2", "v2", "k3", "v3"], "*3\r\n$2\r\nk1\r\n$2\r\nk2\r\n$2\r\nk3\r\n"),
+        (vec!["command", "getkeys", "del", "k1", "k2"], "*2\r\n$2\r\nk1\r\n$2\r\nk2\r\n"),
+        (vec!["command", "getkeys", "rename", "src", "dst"], "*2\r\n$3\r\nsrc\r\n$3\r\ndst\r\n"),
+        (vec!["command", "getkeys", "get"], "-ERR Invalid number of arguments specified for command\r\n"),
+        (vec!["command", "getkeys", "nosuchcmd", "k1"], "-ERR Invalid command specified\r\n"),
+        (vec!["command", "getkeys", "ping"], "-ERR The command has no key arguments\r\n"),
+        (vec!["command", "info", "get", "nosuchcmd"], "*2\r\n*10\r\n$3\r\nget\r\n:2\r\n*1\r\n$8\r\nreadonly\r\n:1\r\n:1\r\n:1\r\n*0\r\n*0\r\n*0\r\n*0\r\n$-1\r\n"),
+    ], "test_command_subcommands"; "test_command_subcommands")]
+    fn test_command_subcommands(
+        args_vec: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new()?;
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args_vec {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_count() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let mut sink = crate::tests::ResponseSink::with_name("test_command_count").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["command", "count"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            let manager = crate::commands::commands_manager();
+            assert_eq!(
+                sink.read_all().await,
+                format!(":{}\r\n", manager.all_commands().len())
+            );
+        });
+    }
+
+    #[test]
+    fn test_wait() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store.clone(), None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_wait").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            assert_eq!(
+                run_command(vec!["wait", "abc", "0"]).await,
+                "-ERR value is not an integer or out of range\r\n"
+            );
+            assert_eq!(
+                run_command(vec!["wait", "1", "-1"]).await,
+                "-ERR timeout is negative\r\n"
+            );
+
+            // no replicas: return immediately, even when waiting forever
+            assert_eq!(run_command(vec!["set", "key", "value"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["wait", "1", "0"]).await, ":0\r\n");
+
+            // one replica is up to date, the other is behind
+            let seq_num = store.latest_sequence_number().unwrap();
+            let server_state = client.inner().server_inner_state();
+            server_state
+                .replica_acks()
+                .record_ack("test_wait_replica_1", seq_num);
+            server_state
+                .replica_acks()
+                .record_ack("test_wait_replica_2", 0);
+            assert_eq!(run_command(vec!["wait", "1", "0"]).await, ":1\r\n");
+
+            let start = std::time::Instant::now();
+            assert_eq!(run_command(vec!["wait", "2", "50"]).await, ":1\r\n");
+            assert!(start.elapsed() >= std::time::Duration::from_millis(50));
+
+            // a new write is not acknowledged until the replica asks for the next changes
+            assert_eq!(run_command(vec!["set", "key", "value2"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["wait", "1", "20"]).await, ":0\r\n");
+        });
+    }
+
+    #[test]
+    fn test_slowlog() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_slowlog").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            // commands are fast, nothing is logged with the default threshold
+            assert_eq!(run_command(vec!["set", "key", "value"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["slowlog", "len"]).await, ":0\r\n");
+
+            // log every command (including the `CONFIG SET` itself)
+            assert_eq!(
+                run_command(vec!["config", "set", "slowlog-log-slower-than", "0"]).await,
+                "+OK\r\n"
+            );
+            assert_eq!(run_command(vec!["set", "key", "value"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["slowlog", "len"]).await, ":2\r\n");
+
+            let entries = run_command(vec!["slowlog", "get", "1"]).await;
+            assert!(entries.starts_with("*1\r\n*6\r\n:2\r\n:"));
+            assert!(entries.contains("*2\r\n$7\r\nslowlog\r\n$3\r\nlen\r\n"));
+            assert!(entries.ends_with(&format!(
+                "${}\r\n{}\r\n$0\r\n\r\n",
+                client.inner().id().to_string().len(),
+                client.inner().id()
+            )));
+            let entries = run_command(vec!["slowlog", "get"]).await;
+            assert!(entries.starts_with("*4\r\n*6\r\n:3\r\n"));
+            assert!(entries.contains("*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n"));
+
+            // the oldest entries are dropped
+            assert_eq!(
+                run_command(vec!["config", "set", "slowlog-max-len", "2"]).await,
+                "+OK\r\n"
+            );
+            assert!(run_command(vec!["slowlog", "get", "-1"])
+                .await
+                .starts_with("*2\r\n*6\r\n:5\r\n"));
+
+            assert_eq!(run_command(vec!["slowlog", "reset"]).await, "+OK\r\n");
+            // the `RESET` itself
+            assert_eq!(run_command(vec!["slowlog", "len"]).await, ":1\r\n");
+
+            assert_eq!(
+                run_command(vec!["config", "set", "slowlog-log-slower-than", "-1"]).await,
+                "+OK\r\n"
+            );
+            assert_eq!(run_command(vec!["slowlog", "reset"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["set", "key", "value"]).await, "+OK\r\n");
+            assert_eq!(run_command(vec!["slowlog", "len"]).await, ":0\r\n");
+
+            for (args, expected_value) in [
+                (
+                    vec!["slowlog", "get", "-2"],
+                    "-ERR count should be greater than or equal to -1\r\n",
+                ),
+                (
+                    vec!["slowlog", "get", "abc"],
+                    "-ERR count should be greater than or equal to -1\r\n",
+                ),
+                (
+                    vec!["slowlog", "len", "1"],
+                    "-ERR wrong number of arguments for 'slowlog|len' command\r\n",
+                ),
+                (
+                    vec!["slowlog", "nosuchcmd"],
+                    "-ERR unknown subcommand 'nosuchcmd'\r\n",
+                ),
+                (
+                    vec!["slowlog"],
+                    "-ERR wrong number of arguments for 'slowlog' command\r\n",
+                ),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+        });
+    }
+
+    #[test]
+    fn test_info_sections() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let client = Client::new(server_state.clone(), store.clone(), None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_info").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            let info = run_command(vec!["info"]).await;
+            assert!(info.contains("# Commands\n"));
+            assert!(info.contains("# Replication\nrole: master\nconnected_slaves: "));
+
+            assert_eq!(run_command(vec!["set", "key", "value"]).await, "+OK\r\n");
+            let info = run_command(vec!["info", "REPLICATION"]).await;
+            assert!(!info.contains("# Commands"));
+            assert!(info.contains(&format!(
+                "master_repl_offset: {}\n",
+                store.latest_sequence_number().unwrap()
+            )));
+
+            let info = run_command(vec!["info", "commands", "network"]).await;
+            assert!(info.contains("# Commands\n"));
+            assert!(info.contains("# Network\n"));
+            assert!(!info.contains("# Replication"));
+
+            // the replica is not connected to its primary
+            server_state.set_replica();
+            let info = run_command(vec!["info", "replication"]).await;
+            assert!(info.contains("role: slave\nmaster_host: "));
+            assert!(info.contains("master_link_status: down\n"));
+            assert!(info.contains("master_last_io_seconds_ago: -1\n"));
+            assert!(info.contains("master_repl_offset: "));
+        });
+    }
+
+    #[test]
+    fn test_info_keyspace() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.key_counter());
+            let client = Client::new(server_state, store, None);
+
+            let run_command = |args: Vec<&'static str>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink =
+                        crate::tests::ResponseSink::with_name("test_info_keyspace").await;
+                    let cmd = Rc::new(RedisCommand::for_test(args));
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+
+            // empty databases are not listed
+            assert_eq!(
+                run_command(vec!["info", "keyspace"]).await,
+                "$11\r\n# Keyspace\n\r\n"
+            );
+
+            for (args, expected_value) in [
+                (vec!["set", "k1", "v"], "+OK\r\n"),
+                (vec!["set", "k2", "v", "ex", "100"], "+OK\r\n"),
+                (vec!["set", "k3", "v"], "+OK\r\n"),
+                (vec!["expire", "k3", "100"], ":1\r\n"),
+                (vec!["hset", "k4", "f1", "v1", "f2", "v2"], ":2\r\n"),
+                (vec!["expire", "k4", "100"], ":1\r\n"),
+                // updating the TTL of a volatile key does not count it twice
+                (vec!["expire", "k4", "200"], ":1\r\n"),
+                (vec!["persist", "k3"], ":1\r\n"),
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["set", "k1", "v", "px", "100000"], "+OK\r\n"),
+                (vec!["set", "k2", "v", "ex", "100"], "+OK\r\n"),
+                // SET without KEEPTTL drops the TTL
+                (vec!["set", "k2", "v"], "+OK\r\n"),
+                (vec!["set", "k3", "v", "ex", "100"], "+OK\r\n"),
+                (vec!["del", "k3"], ":1\r\n"),
+            ] {
+                assert_eq!(run_command(args).await, expected_value);
+            }
+
+            let info = run_command(vec!["info", "keyspace"]).await;
+            assert!(info.contains("# Keyspace\ndb0:keys=4,expires=2\ndb1:keys=2,expires=1\n"));
+            assert!(!info.contains("db2:"));
+
+            let info = run_command(vec!["info"]).await;
+            assert!(info.contains("db0:keys=4,expires=2\n"));
+            let info = run_command(vec!["info", "replication"]).await;
+            assert!(!info.contains("# Keyspace"));
+        });
+    }
+
+    /// A fake replica for `FAILOVER`: `applied_offset` is called for every `DEBUG REPL-OFFSET`
+    /// (`None`: reply like a primary) and `promoted` is set by `REPLICAOF NO ONE`
+    async fn failover_target(
+        applied_offset: impl Fn() -> Option<u64> + Send + 'static,
+        promoted: Arc<std::sync::atomic::AtomicBool>,
+    ) -> u16 {
+        use tokio::io::AsyncReadExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0u8; 1024];
+            while let Ok(count) = stream.read(&mut buffer).await {
+                if count == 0 {
+                    break;
+                }
+                let request = String::from_utf8_lossy(&buffer[..count]).to_lowercase();
+                let reply = if request.contains("repl-offset") {
+                    match applied_offset() {
+                        Some(offset) => format!(
+                            "*4\r\n$12\r\nwrite_offset\r\n:0\r\n$14\r\napplied_offset\r\n:{}\r\n",
+                            offset
+                        ),
+                        None => "*2\r\n$12\r\nwrite_offset\r\n:0\r\n".to_string(),
+                    }
+                } else if request.contains("replicaof") {
+                    promoted.store(true, std::sync::atomic::Ordering::Relaxed);
+                    "+OK\r\n".to_string()
+                } else {
+                    "-ERR unknown command\r\n".to_string()
+                };
+                if stream.write_all(reply.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_failover() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store.clone(), None);
+
+            let run_command = |args: Vec<String>| {
+                let client_state = client.inner();
+                async move {
+                    let mut sink = crate::tests::ResponseSink::with_name("test_failover").await;
+                    let args: Vec<BytesMut> =
+                        args.iter().map(|s| BytesMut::from(s.as_str())).collect();
+                    let cmd = Rc::new(RedisCommand::new(args).unwrap());
+                    Client::handle_command(client_state, cmd, &mut sink.fp)
+                        .await
+                        .unwrap();
+                    sink.read_all().await
+                }
+            };
+            let failover = |args: &str| run_command(args.split(' ').map(String::from).collect());
+
+            assert_eq!(
+                failover("failover").await,
+                "-ERR FAILOVER requires a target replica (FAILOVER TO host port)\r\n"
+            );
+            assert_eq!(
+                failover("failover to 127.0.0.1 abc").await,
+                "-ERR Invalid master port\r\n"
+            );
+            assert_eq!(
+                failover("failover to 127.0.0.1 6379 timeout 0").await,
+                "-ERR FAILOVER timeout must be greater than 0\r\n"
+            );
+            assert_eq!(
+                failover("failover to 127.0.0.1 6379 force").await,
+                "-ERR syntax error\r\n"
+            );
+            assert_eq!(failover("set key value").await, "+OK\r\n");
+            let seq_num = store.latest_sequence_number().unwrap();
+
+            // the target is not a replica
+            let promoted = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let port = failover_target(|| None, promoted.clone()).await;
+            let reply = failover(&format!("failover to 127.0.0.1 {}", port)).await;
+            assert!(reply.starts_with("-ERR FAILOVER aborted. "));
+            assert!(reply.contains("is not a replica"));
+
+            // the target does not catch up in time: the failover is aborted, this server remains
+            // the primary and accepts writes
+            let port = failover_target(move || Some(seq_num - 1), promoted.clone()).await;
+            let reply = failover(&format!("failover to 127.0.0.1 {} timeout 50", port)).await;
+            assert!(reply.starts_with("-ERR FAILOVER aborted. "));
+            assert!(reply.contains("did not catch up"));
+            assert!(!promoted.load(std::sync::atomic::Ordering::Relaxed));
+            assert!(client.inner().server_inner_state().is_primary());
+            assert!(!client.inner().server_inner_state().failover_in_progress());
+            assert_eq!(failover("set key value2").await, "+OK\r\n");
+
+            // the target catches up after a while, writes are held in the meantime
+            let seq_num = store.latest_sequence_number().unwrap();
+            let started_at = std::time::Instant::now();
+            let port = failover_target(
+                move || {
+                    (started_at.elapsed() >= std::time::Duration::from_millis(100))
+                        .then_some(seq_num)
+                        .or(Some(0))
+                },
+                promoted.clone(),
+            )
+            .await;
+            let failover_done = async {
+                let reply = failover(&format!("failover to 127.0.0.1 {}", port)).await;
+                (reply, std::time::Instant::now())
+            };
+            let write_done = async {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                let reply = failover("set key value3").await;
+                (reply, std::time::Instant::now())
+            };
+            let ((failover_reply, failover_at), (write_reply, write_at)) =
+                tokio::join!(failover_done, write_done);
+            assert_eq!(failover_reply, "+OK\r\n");
+            assert!(promoted.load(std::sync::atomic::Ordering::Relaxed));
+            assert!(write_at >= failover_at);
+            // (without a replicator, this server can not follow the new primary, and the write
+            // is accepted)
+            assert_eq!(write_reply, "+OK\r\n");
+        });
+    }
+
+    #[test]
+    fn test_acl() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let admin = Client::new(server_state.clone(), store.clone(), None);
+            let reader = Client::new(server_state.clone(), store.clone(), None);
+            let digest = BytesMutUtils::to_hex(&ServerOptions::password_digest(b"secret"));
+            let reader_description = format!("user reader on #{} ~* +@read", digest);
+
+            for (client, args, expected_value) in [
+                (&admin, vec!["acl", "whoami"], "$7\r\ndefault\r\n".to_string()),
+                (&admin, vec!["acl", "setuser", "reader", "on", ">secret", "~*", "+@read"], "+OK\r\n".to_string()),
+                (&admin, vec!["acl", "setuser", "reader", "+@nosuchcategory"], "-ERR Error in ACL SETUSER modifier '+@nosuchcategory': Unknown command category\r\n".to_string()),
+                (&admin, vec!["set", "key", "value"], "+OK\r\n".to_string()),
+                (&reader, vec!["auth", "reader", "wrong"], "-WRONGPASS invalid username-password pair or user is disabled.\r\n".to_string()),
+                (&reader, vec!["auth", "reader", "secret"], "+OK\r\n".to_string()),
+                // a read-only user can read but not write
+                (&reader, vec!["get", "key"], "$5\r\nvalue\r\n".to_string()),
+                (&reader, vec!["set", "key", "other"], "-NOPERM User reader has no permissions to run the 'set' command\r\n".to_string()),
+                (&reader, vec!["acl", "whoami"], "-NOPERM User reader has no permissions to run the 'acl' command\r\n".to_string()),
+                (&admin, vec!["get", "key"], "$5\r\nvalue\r\n".to_string()),
+                (
+                    &admin,
+                    vec!["acl", "getuser", "reader"],
+                    format!("*8\r\n$5\r\nflags\r\n*1\r\n$2\r\non\r\n$9\r\npasswords\r\n*1\r\n$64\r\n{}\r\n$8\r\ncommands\r\n$6\r\n+@read\r\n$4\r\nkeys\r\n$2\r\n~*\r\n", digest),
+                ),
+                (&admin, vec!["acl", "getuser", "nosuchuser"], "$-1\r\n".to_string()),
+                (
+                    &admin,
+                    vec!["acl", "list"],
+                    format!("*2\r\n$31\r\nuser default on nopass ~* +@all\r\n${}\r\n{}\r\n", reader_description.len(), reader_description),
+                ),
+                // restrict the keys
+                (&admin, vec!["acl", "setuser", "reader", "resetkeys", "~cache:*"], "+OK\r\n".to_string()),
+                (&reader, vec!["get", "key"], "-NOPERM No permissions to access a key\r\n".to_string()),
+                (&reader, vec!["get", "cache:1"], "$-1\r\n".to_string()),
+                (&admin, vec!["acl", "list", "extra"], "-ERR wrong number of arguments for 'acl|list' command\r\n".to_string()),
+                (&admin, vec!["acl", "nosuchcmd"], "-ERR unknown subcommand 'nosuchcmd'\r\n".to_string()),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_acl").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+        });
+    }
+
+    #[test]
+    fn test_bgsave() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let mut backup_dir = store.open_params().db_path.clone().into_os_string();
+            backup_dir.push(".backups");
+            let backup_dir = std::path::PathBuf::from(backup_dir);
+            let _backups_guard =
+                crate::tests::DirDeleter::with_path(backup_dir.to_string_lossy().to_string());
+
+            let server_state = Arc::<ServerState>::default();
+            server_state.update_options(|options| {
+                options.backup_settings.backup_dir = Some(backup_dir.clone())
+            });
+            let client = Client::new(server_state.clone(), store.clone(), None);
+            async fn call(client: &Client, args: Vec<&'static str>) -> BytesMut {
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command_captured(client.inner(), cmd).await
+            }
+
+            // pretend the last backup is old
+            server_state.set_last_save(1000);
+            assert_eq!(call(&client, vec!["lastsave"]).await, ":1000\r\n");
+
+            for (args, expected_value) in [
+                (vec!["set", "key", "value"], "+OK\r\n"),
+                (vec!["bgsave"], "+Background saving started\r\n"),
+                (
+                    vec!["bgsave", "schedule"],
+                    "-ERR wrong number of arguments for 'bgsave' command\r\n",
+                ),
+            ] {
+                assert_eq!(call(&client, args).await, expected_value);
+            }
+
+            while server_state.is_save_in_progress() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+            }
+            let backups = store.list_backups(&backup_dir).unwrap();
+            assert_eq!(backups.len(), 1);
+            assert_eq!(backups[0].id, 1);
+            assert!(backups[0].timestamp > 1000);
+            assert_eq!(
+                call(&client, vec!["lastsave"]).await,
+                format!(":{}\r\n", backups[0].timestamp).as_str()
+            );
+
+            // only one backup runs at a time
+            assert!(server_state.start_save());
+            for args in [vec!["bgsave"], vec!["save"]] {
+                assert_eq!(
+                    call(&client, args).await,
+                    "-ERR Background save already in progress\r\n"
+                );
+            }
+            server_state.end_save();
+
+            server_state.set_last_save(1000);
+            assert_eq!(call(&client, vec!["save"]).await, "+OK\r\n");
+            let backups = store.list_backups(&backup_dir).unwrap();
+            assert_eq!(backups.len(), 2);
+            assert_eq!(
+                call(&client, vec!["lastsave"]).await,
+                format!(":{}\r\n", backups[1].timestamp).as_str()
+            );
+        });
+    }
+
+    #[test]
+    fn test_debug_compact() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let client = Client::new(server_state.clone(), store.clone(), None);
+            async fn call(client: &Client, args: Vec<&'static str>) -> BytesMut {
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command_captured(client.inner(), cmd).await
+            }
+
+            let info = call(&client, vec!["info", "persistence"]).await;
+            let info = BytesMutUtils::to_string(&info);
+            assert!(info.contains("# Persistence\n"));
+            assert!(info.contains("compaction_in_progress: 0\n"));
+            assert!(info.contains("last_compaction_time: 0\n"));
+            assert!(info.contains("rocksdb_estimate_num_keys: "));
+            assert!(info.contains("rocksdb_num_files_at_level0: "));
+
+            for (args, expected_value) in [
+                (
+                    vec!["debug", "compact", "16"],
+                    "-ERR DB index is out of range\r\n",
+                ),
+                (
+                    vec!["debug", "compact", "0", "1"],
+                    "-ERR wrong number of arguments for 'debug|compact' command\r\n",
+                ),
+                (
+                    vec!["debug", "compact", "1"],
+                    "+Background compaction started\r\n",
+                ),
+            ] {
+                assert_eq!(call(&client, args).await, expected_value);
+            }
+            while server_state.is_compaction_in_progress() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+            }
+            assert!(server_state.last_compaction() > 0);
+
+            // only one compaction runs at a time
+            assert!(server_state.start_compaction());
+            assert_eq!(
+                call(&client, vec!["debug", "compact"]).await,
+                "-ERR Compaction already in progress\r\n"
+            );
+            server_state.end_compaction(0);
+            assert_eq!(
+                call(&client, vec!["debug", "compact"]).await,
+                "+Background compaction started\r\n"
+            );
+        });
+    }
 }
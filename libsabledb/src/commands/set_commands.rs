@@ -0,0 +1,650 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count, check_value_type,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    storage::{
+        GenericDb, SetDb, SetDeleteResult, SetExistsResult, SetLenResult, SetMembersResult,
+        SetMoveResult, SetPutResult,
+    },
+    BytesMutUtils, LockManager, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+/// The largest number of members a negative count can ask from `SRANDMEMBER` (and fields from
+/// `HRANDFIELD`). A negative count may repeat members, so it is not bounded by the size of the
+/// value: a larger count is rejected instead of building a reply that does not fit in memory
+pub(crate) const MAX_RANDOM_COUNT: u64 = 16 * 1024 * 1024;
+
+pub struct SetCommands {}
+
+/// The operation applied by `SINTER`, `SUNION`, `SDIFF` and their `STORE` variants
+enum SetOperation {
+    Intersection,
+    Union,
+    Difference,
+}
+
+impl SetCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Sadd => {
+                Self::sadd(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Srem => {
+                Self::srem(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Smembers => {
+                Self::smembers(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Sismember => {
+                Self::sismember(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Scard => {
+                Self::scard(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Spop => {
+                Self::spop(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Srandmember => {
+                Self::srandmember(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Smove => {
+                Self::smove(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Sinter => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Intersection,
+                    false,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            RedisCommandName::Sinterstore => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Intersection,
+                    true,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            RedisCommandName::Sunion => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Union,
+                    false,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            RedisCommandName::Sunionstore => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Union,
+                    true,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            RedisCommandName::Sdiff => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Difference,
+                    false,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            RedisCommandName::Sdiffstore => {
+                Self::combine(
+                    client_state,
+                    command,
+                    SetOperation::Difference,
+                    true,
+                    &mut response_buffer,
+                )
+                .await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non set command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Add the specified members to the set stored at key
+    async fn sadd(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // sadd key <member> [<member>..]
+        let members: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.put_multi(key, &members)? {
+            SetPutResult::Some(count) => builder.number_usize(response_buffer, count),
+            SetPutResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the specified members from the set stored at key
+    async fn srem(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // srem key <member> [<member>..]
+        let members: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.delete(key, &members)? {
+            SetDeleteResult::Some(count) => builder.number_usize(response_buffer, count),
+            SetDeleteResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns all the members of the set value stored at key
+    async fn smembers(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.members(key)? {
+            SetMembersResult::Some(members) => {
+                builder.add_array_len(response_buffer, members.len());
+                for member in &members {
+                    builder.add_bulk_string(response_buffer, member);
+                }
+            }
+            SetMembersResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns if member is a member of the set stored at key
+    async fn sismember(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+        let member = command_arg_at!(command, 2);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.member_exists(key, member)? {
+            SetExistsResult::NotExists => builder.number_usize(response_buffer, 0),
+            SetExistsResult::Exists => builder.number_usize(response_buffer, 1),
+            SetExistsResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the set cardinality (number of elements) of the set stored at key
+    async fn scard(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.len(key)? {
+            SetLenResult::Some(count) => builder.number_usize(response_buffer, count),
+            SetLenResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns one or more random members from the set value store at key.
+    /// `SPOP key [count]`
+    async fn spop(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // without a count, the reply is a single bulk string
+        let count = match command.arg_count() {
+            2 => None,
+            3 => {
+                let count = command_arg_at!(command, 2);
+                let Some(count) = BytesMutUtils::parse::<i64>(count) else {
+                    builder.error_string(
+                        response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(());
+                };
+                if count < 0 {
+                    builder.error_string(response_buffer, ErrorStrings::VALUE_MUST_BE_POSITIVE);
+                    return Ok(());
+                }
+                Some(count as usize)
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        let members = match set_db.pop(key, count.unwrap_or(1))? {
+            SetMembersResult::Some(members) => members,
+            SetMembersResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        if count.is_some() {
+            builder.add_array_len(response_buffer, members.len());
+            for member in &members {
+                builder.add_bulk_string(response_buffer, member);
+            }
+        } else if let Some(member) = members.first() {
+            builder.bulk_string(response_buffer, member);
+        } else {
+            builder.null_string(response_buffer);
+        }
+        Ok(())
+    }
+
+    /// Returns one or more random members from the set value store at key, without removing
+    /// them. `SRANDMEMBER key [count]`
+    ///
+    /// A positive count returns distinct members (up to the set size), a negative count returns
+    /// exactly `|count|` members and may return the same member multiple times
+    async fn srandmember(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // without a count, the reply is a single bulk string
+        let count = match command.arg_count() {
+            2 => None,
+            3 => {
+                let count = command_arg_at!(command, 2);
+                let Some(count) = BytesMutUtils::parse::<i64>(count) else {
+                    builder.error_string(
+                        response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(());
+                };
+                Some(count)
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        let (how_many, allow_dups) = match count {
+            None => (1usize, false),
+            Some(count) if count < 0 && count.unsigned_abs() > MAX_RANDOM_COUNT => {
+                builder.error_string(response_buffer, ErrorStrings::VALUE_OUT_OF_RANGE);
+                return Ok(());
+            }
+            Some(count) => (
+                count.unsigned_abs().try_into().unwrap_or(usize::MAX),
+                count < 0,
+            ),
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        let members = match set_db.random_members(key, how_many, allow_dups)? {
+            SetMembersResult::Some(members) => members,
+            SetMembersResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        if count.is_some() {
+            builder.add_array_len(response_buffer, members.len());
+            for member in &members {
+                builder.add_bulk_string(response_buffer, member);
+            }
+        } else if let Some(member) = members.first() {
+            builder.bulk_string(response_buffer, member);
+        } else {
+            builder.null_string(response_buffer);
+        }
+        Ok(())
+    }
+
+    /// Move member from the set at source to the set at destination.
+    /// `SMOVE source destination member`
+    async fn smove(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+        let src = command_arg_at!(command, 1);
+        let dst = command_arg_at!(command, 2);
+        let member = command_arg_at!(command, 3);
+
+        // the locks are sorted internally, so two moves in opposite directions can't deadlock
+        let _unused =
+            LockManager::lock_user_keys_exclusive(&[src, dst], client_state.database_id());
+        let set_db = SetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match set_db.move_member(src, dst, member)? {
+            SetMoveResult::Moved => builder.number_usize(response_buffer, 1),
+            SetMoveResult::NotMoved => builder.number_usize(response_buffer, 0),
+            SetMoveResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// `SINTER`, `SUNION` and `SDIFF` reply with the members resulting from `operation`.
+    /// With `store` (e.g. `SINTERSTORE destination key [key ...]`) the members are written into
+    /// the set at destination instead, and the reply is the size of that set
+    async fn combine(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        operation: SetOperation,
+        store: bool,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, if store { 3 } else { 2 }, response_buffer);
+        let builder = RespBuilderV2::default();
+        let keys: Vec<&BytesMut> = command.args_vec().iter().skip(1).collect();
+        let (destination, sources) = if store {
+            (keys.first().copied(), &keys[1..])
+        } else {
+            (None, &keys[..])
+        };
+
+        let db_id = client_state.database_id();
+        let items_prefix = {
+            let _unused = if store {
+                LockManager::lock_user_keys_exclusive(&keys, db_id)
+            } else {
+                LockManager::lock_user_keys_shared(&keys, db_id)
+            };
+            let set_db = SetDb::with_storage(client_state.database(), db_id);
+
+            let result = match operation {
+                SetOperation::Intersection => set_db.intersection(sources)?,
+                SetOperation::Union => set_db.union(sources)?,
+                SetOperation::Difference => set_db.difference(sources)?,
+            };
+
+            let members = match result {
+                SetMembersResult::Some(members) => members,
+                SetMembersResult::WrongType => {
+                    builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                    return Ok(());
+                }
+            };
+
+            let Some(destination) = destination else {
+                builder.add_array_len(response_buffer, members.len());
+                for member in &members {
+                    builder.add_bulk_string(response_buffer, member);
+                }
+                return Ok(());
+            };
+
+            // the items of the value we are about to replace become unreachable
+            let items_prefix = GenericDb::with_storage(client_state.database(), db_id)
+                .items_prefix(destination)?;
+            let len = set_db.store(destination, &members)?;
+            builder.number_usize(response_buffer, len);
+            items_prefix
+        };
+
+        if let Some(items_prefix) = items_prefix {
+            client_state
+                .server_inner_state()
+                .enqueue_reclaim(items_prefix);
+        }
+        Ok(())
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["sadd", "myset"], "-ERR wrong number of arguments for 'sadd' command\r\n"),
+        (vec!["sadd", "myset", "a", "b", "a"], ":2\r\n"),
+        (vec!["sadd", "myset", "b", "c"], ":1\r\n"),
+        (vec!["scard", "myset"], ":3\r\n"),
+        (vec!["scard", "nosuchset"], ":0\r\n"),
+        (vec!["smembers", "myset"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["smembers", "nosuchset"], "*0\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sadd", "str_key", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["smembers", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["type", "myset"], "+set\r\n"),
+    ], "test_sadd"; "test_sadd")]
+    #[test_case(vec![
+        (vec!["sadd", "myset", "a", "b", "c"], ":3\r\n"),
+        (vec!["sismember", "myset", "a"], ":1\r\n"),
+        (vec!["sismember", "myset", "d"], ":0\r\n"),
+        (vec!["sismember", "nosuchset", "a"], ":0\r\n"),
+        (vec!["srem", "myset", "a", "d", "a"], ":1\r\n"),
+        (vec!["sismember", "myset", "a"], ":0\r\n"),
+        (vec!["srem", "nosuchset", "a"], ":0\r\n"),
+        (vec!["srem", "myset", "b", "c"], ":2\r\n"),
+        // removing the last member removes the key
+        (vec!["exists", "myset"], ":0\r\n"),
+    ], "test_srem"; "test_srem")]
+    #[test_case(vec![
+        (vec!["spop", "nosuchset"], "$-1\r\n"),
+        (vec!["spop", "nosuchset", "2"], "*0\r\n"),
+        (vec!["sadd", "myset", "a", "b", "c"], ":3\r\n"),
+        (vec!["spop", "myset", "-1"], "-ERR value is out of range, must be positive\r\n"),
+        (vec!["spop", "myset", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["spop", "myset", "1", "2"], "-ERR syntax error\r\n"),
+        (vec!["spop", "myset", "0"], "*0\r\n"),
+        // a count that exceeds the set size pops everything and deletes the key
+        (vec!["spop", "myset", "10"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["scard", "myset"], ":0\r\n"),
+        (vec!["exists", "myset"], ":0\r\n"),
+        (vec!["sadd", "myset", "a"], ":1\r\n"),
+        (vec!["spop", "myset"], "$1\r\na\r\n"),
+        (vec!["exists", "myset"], ":0\r\n"),
+    ], "test_spop"; "test_spop")]
+    #[test_case(vec![
+        (vec!["srandmember", "nosuchset"], "$-1\r\n"),
+        (vec!["srandmember", "nosuchset", "2"], "*0\r\n"),
+        (vec!["srandmember", "nosuchset", "-2"], "*0\r\n"),
+        (vec!["sadd", "myset", "a", "b", "c"], ":3\r\n"),
+        (vec!["srandmember", "myset", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["srandmember", "myset", "1", "2"], "-ERR syntax error\r\n"),
+        (vec!["srandmember", "myset", "0"], "*0\r\n"),
+        // a positive count returns distinct members, up to the set size
+        (vec!["srandmember", "myset", "10"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["srandmember", "myset", "9223372036854775807"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        // a negative count is not bounded by the set size, a huge one is rejected
+        (vec!["srandmember", "myset", "-9223372036854775808"], "-ERR value is out of range\r\n"),
+        (vec!["sadd", "single", "a"], ":1\r\n"),
+        (vec!["srandmember", "single"], "$1\r\na\r\n"),
+        (vec!["srandmember", "single", "1"], "*1\r\n$1\r\na\r\n"),
+        // a negative count returns duplicates and does not modify the set
+        (vec!["srandmember", "single", "-3"], "*3\r\n$1\r\na\r\n$1\r\na\r\n$1\r\na\r\n"),
+        (vec!["scard", "single"], ":1\r\n"),
+        (vec!["scard", "myset"], ":3\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["srandmember", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_srandmember"; "test_srandmember")]
+    #[test_case(vec![
+        (vec!["sadd", "src", "a", "b"], ":2\r\n"),
+        (vec!["sadd", "dst", "b", "c"], ":2\r\n"),
+        (vec!["smove", "src", "dst"], "-ERR wrong number of arguments for 'smove' command\r\n"),
+        // moving a member that does not exist is a no-op
+        (vec!["smove", "src", "dst", "x"], ":0\r\n"),
+        (vec!["smove", "nosuchset", "dst", "a"], ":0\r\n"),
+        (vec!["smembers", "src"], "*2\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        (vec!["smembers", "dst"], "*2\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["smove", "src", "dst", "a"], ":1\r\n"),
+        (vec!["smembers", "dst"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        // the member already exists in the destination
+        (vec!["smove", "src", "dst", "b"], ":1\r\n"),
+        (vec!["scard", "dst"], ":3\r\n"),
+        (vec!["exists", "src"], ":0\r\n"),
+        (vec!["smove", "dst", "newset", "c"], ":1\r\n"),
+        (vec!["smembers", "newset"], "*1\r\n$1\r\nc\r\n"),
+        (vec!["smove", "dst", "dst", "a"], ":1\r\n"),
+        (vec!["scard", "dst"], ":2\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["smove", "dst", "str_key", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["smove", "str_key", "dst", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_smove"; "test_smove")]
+    #[test_case(vec![
+        (vec!["sadd", "set1", "a", "b", "c", "d"], ":4\r\n"),
+        (vec!["sadd", "set2", "c", "d", "e"], ":3\r\n"),
+        (vec!["sadd", "set3", "a", "c", "d"], ":3\r\n"),
+        (vec!["sinter", "set1", "set2", "set3"], "*2\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        (vec!["sinter", "set1", "nosuchset"], "*0\r\n"),
+        (vec!["sinterstore", "dest", "set1", "set2", "set3"], ":2\r\n"),
+        (vec!["scard", "dest"], ":2\r\n"),
+        (vec!["smembers", "dest"], "*2\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        // the destination can be one of the sources
+        (vec!["sinterstore", "set1", "set1", "set3"], ":3\r\n"),
+        (vec!["smembers", "set1"], "*3\r\n$1\r\na\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        // an empty result deletes the destination
+        (vec!["sinterstore", "dest", "set1", "nosuchset"], ":0\r\n"),
+        (vec!["exists", "dest"], ":0\r\n"),
+        // the destination is overwritten, whatever its type
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sinterstore", "str_key", "set1", "set2"], ":2\r\n"),
+        (vec!["type", "str_key"], "+set\r\n"),
+        (vec!["sinter", "set1", "str_key"], "*2\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sinter", "nosuchset", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["sinterstore", "dest"], "-ERR wrong number of arguments for 'sinterstore' command\r\n"),
+    ], "test_sinter"; "test_sinter")]
+    #[test_case(vec![
+        (vec!["sadd", "set1", "a", "b", "c"], ":3\r\n"),
+        (vec!["sadd", "set2", "c", "d"], ":2\r\n"),
+        (vec!["sunion", "set1", "set2", "nosuchset"], "*4\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\nd\r\n"),
+        (vec!["sunionstore", "dest", "set1", "set2"], ":4\r\n"),
+        (vec!["scard", "dest"], ":4\r\n"),
+        (vec!["sdiff", "set1", "set2", "nosuchset"], "*2\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        (vec!["sdiff", "nosuchset", "set1"], "*0\r\n"),
+        (vec!["sdiffstore", "dest", "set1", "set2"], ":2\r\n"),
+        (vec!["smembers", "dest"], "*2\r\n$1\r\na\r\n$1\r\nb\r\n"),
+        (vec!["sdiffstore", "dest", "set1", "set1"], ":0\r\n"),
+        (vec!["exists", "dest"], ":0\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sunion", "set1", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["sdiff", "set1", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_sunion_sdiff"; "test_sunion_sdiff")]
+    fn test_set_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    storage::{GenericDb, SetDb, SetMembersResult},
+    types::{List, ListValuesResult},
+    BytesMutUtils, LockManager, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct SortCommands {}
+
+/// The parsed options of `SORT` and `SORT_RO`
+#[derive(Default)]
+struct SortOptions {
+    alpha: bool,
+    descending: bool,
+    /// `LIMIT offset count`
+    limit: Option<(i64, i64)>,
+    /// `BY` or `GET` were given
+    has_patterns: bool,
+    /// `STORE destination` (`SORT` only)
+    store: Option<BytesMut>,
+}
+
+impl SortCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Sort | RedisCommandName::SortRo => {
+                Self::sort(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non sort command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// `SORT` is registered as read-only, but it writes when `STORE` is given. Return `true` if
+    /// `command` is a `SORT` that stores its result
+    pub fn is_write(command: &RedisCommand) -> bool {
+        matches!(command.metadata().name(), RedisCommandName::Sort)
+            && matches!(Self::parse_options(command), Ok(options) if options.store.is_some())
+    }
+
+    /// `SORT key [BY pattern] [LIMIT offset count] [GET pattern [GET pattern ...]] [ASC | DESC]
+    /// [ALPHA] [STORE destination]`
+    /// Sort the elements of the list (or set) stored at `key`. Elements are compared as double
+    /// precision numbers, unless `ALPHA` is given. `BY` and `GET` are parsed, but not supported.
+    /// `SORT_RO` is the same, without `STORE`
+    async fn sort(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let key = command_arg_at!(command, 1);
+        let builder = RespBuilderV2::default();
+
+        let options = match Self::parse_options(&command) {
+            Ok(options) => options,
+            Err(msg) => {
+                builder.error_string(response_buffer, msg);
+                return Ok(());
+            }
+        };
+
+        if options.has_patterns {
+            builder.error_string(response_buffer, ErrorStrings::SORT_PATTERNS_NOT_SUPPORTED);
+            return Ok(());
+        }
+
+        let db_id = client_state.database_id();
+        let _unused = if let Some(destination) = &options.store {
+            LockManager::lock_user_keys_exclusive(&[key, destination], db_id)
+        } else {
+            LockManager::lock_user_keys_shared(&[key], db_id)
+        };
+
+        let list = List::with_storage(client_state.database(), db_id);
+        let mut values = match list.values(key)? {
+            ListValuesResult::Some(values) => values,
+            ListValuesResult::None => Vec::default(),
+            ListValuesResult::WrongType => {
+                let set_db = SetDb::with_storage(client_state.database(), db_id);
+                match set_db.members(key)? {
+                    SetMembersResult::Some(members) => members,
+                    SetMembersResult::WrongType => {
+                        builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        if options.alpha {
+            values.sort();
+        } else {
+            let mut scored_values = Vec::<(f64, BytesMut)>::with_capacity(values.len());
+            for value in values {
+                let Some(score) = BytesMutUtils::parse_float(&value) else {
+                    builder.error_string(response_buffer, ErrorStrings::SORT_NOT_A_DOUBLE);
+                    return Ok(());
+                };
+                scored_values.push((score, value));
+            }
+            // like Redis, elements with the same score are compared as strings
+            scored_values.sort_by(|(score1, value1), (score2, value2)| {
+                score1
+                    .partial_cmp(score2)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| value1.cmp(value2))
+            });
+            values = scored_values.into_iter().map(|(_, value)| value).collect();
+        }
+
+        if options.descending {
+            values.reverse();
+        }
+
+        // a negative offset starts from the first element, a negative count takes all the
+        // elements starting from the offset
+        let (offset, count) = match options.limit {
+            Some((offset, count)) => (
+                usize::try_from(offset).unwrap_or(0),
+                usize::try_from(count).unwrap_or(usize::MAX),
+            ),
+            None => (0, usize::MAX),
+        };
+        let values: Vec<BytesMut> = values.into_iter().skip(offset).take(count).collect();
+
+        let Some(destination) = &options.store else {
+            builder.add_array_len(response_buffer, values.len());
+            for value in &values {
+                builder.add_bulk_string(response_buffer, value);
+            }
+            return Ok(());
+        };
+
+        // the items of the value we are about to replace become unreachable
+        let items_prefix =
+            GenericDb::with_storage(client_state.database(), db_id).items_prefix(destination)?;
+        let len = list.store(destination, &values)?;
+        if let Some(items_prefix) = items_prefix {
+            client_state
+                .server_inner_state()
+                .enqueue_reclaim(items_prefix);
+        }
+        client_state
+            .server_inner_state()
+            .wakeup_clients(destination, len)
+            .await;
+        builder.number_usize(response_buffer, len);
+        Ok(())
+    }
+
+    /// Parse the options of `SORT` / `SORT_RO`, starting after the key. On error, the error
+    /// message is returned
+    fn parse_options(command: &RedisCommand) -> Result<SortOptions, &'static str> {
+        let allow_store = matches!(command.metadata().name(), RedisCommandName::Sort);
+        let mut options = SortOptions::default();
+        let mut pos = 2usize;
+        while pos < command.arg_count() {
+            let Some(option) = command.arg_as_lowercase_string(pos) else {
+                return Err(ErrorStrings::SYNTAX_ERROR);
+            };
+            match option.as_str() {
+                "alpha" => options.alpha = true,
+                "asc" => options.descending = false,
+                "desc" => options.descending = true,
+                "limit" if pos + 2 < command.arg_count() => {
+                    let (Some(offset), Some(count)) = (
+                        command.arg_as_number::<i64>(pos + 1),
+                        command.arg_as_number::<i64>(pos + 2),
+                    ) else {
+                        return Err(ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE);
+                    };
+                    options.limit = Some((offset, count));
+                    pos += 2;
+                }
+                "by" | "get" if pos + 1 < command.arg_count() => {
+                    options.has_patterns = true;
+                    pos += 1;
+                }
+                "store" if allow_store && pos + 1 < command.arg_count() => {
+                    options.store = command.arg(pos + 1).cloned();
+                    pos += 1;
+                }
+                _ => return Err(ErrorStrings::SYNTAX_ERROR),
+            }
+            pos += 1;
+        }
+        Ok(options)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["rpush", "mylist", "3", "10", "1", "2.5", "-4"], ":5\r\n"),
+        (vec!["sort", "mylist"], "*5\r\n$2\r\n-4\r\n$1\r\n1\r\n$3\r\n2.5\r\n$1\r\n3\r\n$2\r\n10\r\n"),
+        (vec!["sort", "mylist", "ASC"], "*5\r\n$2\r\n-4\r\n$1\r\n1\r\n$3\r\n2.5\r\n$1\r\n3\r\n$2\r\n10\r\n"),
+        (vec!["sort", "mylist", "desc"], "*5\r\n$2\r\n10\r\n$1\r\n3\r\n$3\r\n2.5\r\n$1\r\n1\r\n$2\r\n-4\r\n"),
+        (vec!["sort", "mylist", "limit", "1", "2"], "*2\r\n$1\r\n1\r\n$3\r\n2.5\r\n"),
+        (vec!["sort", "mylist", "desc", "limit", "-1", "2"], "*2\r\n$2\r\n10\r\n$1\r\n3\r\n"),
+        (vec!["sort", "mylist", "limit", "3", "-1"], "*2\r\n$1\r\n3\r\n$2\r\n10\r\n"),
+        (vec!["sort", "mylist", "limit", "10", "2"], "*0\r\n"),
+        // the list is not modified
+        (vec!["lrange", "mylist", "0", "-1"], "*5\r\n$1\r\n3\r\n$2\r\n10\r\n$1\r\n1\r\n$3\r\n2.5\r\n$2\r\n-4\r\n"),
+        (vec!["sort", "nosuchlist"], "*0\r\n"),
+    ], "test_sort_numeric"; "test_sort_numeric")]
+    #[test_case(vec![
+        (vec!["rpush", "mylist", "banana", "apple", "10", "cherry", "9"], ":5\r\n"),
+        (vec!["sort", "mylist"], "-ERR One or more scores can't be converted into double\r\n"),
+        (vec!["sort", "mylist", "alpha"], "*5\r\n$2\r\n10\r\n$1\r\n9\r\n$5\r\napple\r\n$6\r\nbanana\r\n$6\r\ncherry\r\n"),
+        (vec!["sort", "mylist", "ALPHA", "DESC", "LIMIT", "0", "2"], "*2\r\n$6\r\ncherry\r\n$6\r\nbanana\r\n"),
+        (vec!["sort", "mylist", "limit", "2", "2", "alpha"], "*2\r\n$5\r\napple\r\n$6\r\nbanana\r\n"),
+        (vec!["sadd", "myset", "b", "c", "a"], ":3\r\n"),
+        (vec!["sort", "myset", "alpha"], "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+    ], "test_sort_alpha"; "test_sort_alpha")]
+    #[test_case(vec![
+        (vec!["sort"], "-ERR wrong number of arguments for 'sort' command\r\n"),
+        (vec!["rpush", "mylist", "1", "2"], ":2\r\n"),
+        (vec!["sort", "mylist", "limit", "1"], "-ERR syntax error\r\n"),
+        (vec!["sort", "mylist", "limit", "a", "1"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["sort", "mylist", "nosuchoption"], "-ERR syntax error\r\n"),
+        (vec!["sort", "mylist", "by", "weight_*"], "-ERR SORT BY and GET patterns are not supported\r\n"),
+        (vec!["sort", "mylist", "get", "#"], "-ERR SORT BY and GET patterns are not supported\r\n"),
+        (vec!["sort", "mylist", "get"], "-ERR syntax error\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sort", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_sort_errors"; "test_sort_errors")]
+    #[test_case(vec![
+        (vec!["rpush", "mylist", "3", "1", "2"], ":3\r\n"),
+        (vec!["sort", "mylist", "desc", "store", "dest"], ":3\r\n"),
+        (vec!["lrange", "dest", "0", "-1"], "*3\r\n$1\r\n3\r\n$1\r\n2\r\n$1\r\n1\r\n"),
+        // STORE replaces the destination, whatever its type
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["sort", "mylist", "limit", "0", "2", "store", "str_key"], ":2\r\n"),
+        (vec!["lrange", "str_key", "0", "-1"], "*2\r\n$1\r\n1\r\n$1\r\n2\r\n"),
+        // an empty result deletes the destination
+        (vec!["sort", "nosuchlist", "store", "dest"], ":0\r\n"),
+        (vec!["exists", "dest"], ":0\r\n"),
+        (vec!["sort", "mylist", "store"], "-ERR syntax error\r\n"),
+        (vec!["sort_ro", "mylist", "store", "dest"], "-ERR syntax error\r\n"),
+        (vec!["sort_ro", "mylist", "desc"], "*3\r\n$1\r\n3\r\n$1\r\n2\r\n$1\r\n1\r\n"),
+    ], "test_sort_store"; "test_sort_store")]
+    fn test_sort_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_on_replica() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            let mut sink = crate::tests::ResponseSink::with_name("test_sort_on_replica").await;
+            let cmd = Rc::new(RedisCommand::for_test(vec!["rpush", "mylist", "2", "1"]));
+            Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                .await
+                .unwrap();
+            assert_eq!(sink.read_all().await.as_str(), ":2\r\n");
+
+            client.inner().server_inner_state().set_replica();
+            for (args, expected_value) in [
+                (vec!["sort_ro", "mylist"], "*2\r\n$1\r\n1\r\n$1\r\n2\r\n"),
+                (vec!["sort", "mylist"], "*2\r\n$1\r\n1\r\n$1\r\n2\r\n"),
+                (
+                    vec!["sort", "mylist", "store", "dest"],
+                    "-READONLY You can't write against a read only replica.\r\n",
+                ),
+            ] {
+                let mut sink = crate::tests::ResponseSink::with_name("test_sort_on_replica").await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+        });
+    }
+}
@@ -0,0 +1,962 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count, check_value_type,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    metadata::StreamId,
+    storage::{
+        GetStreamMetadataResult, StreamAddResult, StreamDb, StreamDeleteResult, StreamEntry,
+        StreamInfoResult, StreamLenResult, StreamRangeResult, StreamTrimOptions, StreamTrimResult,
+        StreamTrimThreshold,
+    },
+    BytesMutUtils, LockManager, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+use tokio::time::Duration;
+
+pub struct StreamCommands {}
+
+impl StreamCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Xadd => {
+                Self::xadd(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xlen => {
+                Self::xlen(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xrange => {
+                Self::xrange(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xtrim => {
+                Self::xtrim(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xdel => {
+                Self::xdel(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xinfo => {
+                Self::xinfo(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Xread => {
+                return Self::xread(client_state, command, response_buffer).await;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non stream command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Appends the specified stream entry to the stream at the specified key, and optionally
+    /// trims the stream.
+    /// `XADD key [<MAXLEN | MINID> [= | ~] threshold [LIMIT count]] <* | id> field value
+    /// [field value ...]`
+    async fn xadd(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 5, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let mut pos = 2usize;
+        let trim = match command.arg_as_lowercase_string(pos).as_deref() {
+            Some("maxlen" | "minid") => {
+                let Some((trim, next_pos)) =
+                    Self::parse_trim_options(&command, pos, response_buffer)
+                else {
+                    return Ok(());
+                };
+                pos = next_pos;
+                Some(trim)
+            }
+            _ => None,
+        };
+        if let Some("maxlen" | "minid") = command.arg_as_lowercase_string(pos).as_deref() {
+            builder.error_string(response_buffer, ErrorStrings::XTRIM_MAXLEN_AND_MINID);
+            return Ok(());
+        }
+
+        let Some(id) = command.arg(pos) else {
+            builder.error_string(
+                response_buffer,
+                "ERR wrong number of arguments for 'xadd' command",
+            );
+            return Ok(());
+        };
+        let id = if id.as_ref() == b"*" {
+            None
+        } else {
+            let Some(id) = StreamId::parse(id, 0) else {
+                builder.error_string(response_buffer, ErrorStrings::INVALID_STREAM_ID);
+                return Ok(());
+            };
+            Some(id)
+        };
+
+        // parse the field/value pairs
+        let mut iter = command.args_vec().iter().skip(pos + 1);
+        let mut fields = Vec::<(&BytesMut, &BytesMut)>::with_capacity(command.arg_count() / 2);
+        loop {
+            match (iter.next(), iter.next()) {
+                (Some(field), Some(value)) => fields.push((field, value)),
+                (None, None) if !fields.is_empty() => break,
+                (_, _) => {
+                    builder.error_string(
+                        response_buffer,
+                        "ERR wrong number of arguments for 'xadd' command",
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        match stream_db.add(key, id, &fields, trim.as_ref())? {
+            StreamAddResult::Some(id) => {
+                builder.bulk_string(response_buffer, &id.to_bytes());
+                // reading a stream does not consume its entries: wake all the blocked readers
+                client_state
+                    .server_inner_state()
+                    .wakeup_clients(key, usize::MAX)
+                    .await;
+            }
+            StreamAddResult::IdTooSmall => {
+                builder.error_string(response_buffer, ErrorStrings::XADD_ID_TOO_SMALL)
+            }
+            StreamAddResult::IdIsZero => {
+                builder.error_string(response_buffer, ErrorStrings::XADD_ID_IS_ZERO)
+            }
+            StreamAddResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of entries inside a stream
+    async fn xlen(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        match stream_db.len(key)? {
+            StreamLenResult::Some(len) => builder.number_usize(response_buffer, len),
+            StreamLenResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Trims the stream by evicting its oldest entries. Returns the number of entries removed.
+    /// `XTRIM key <MAXLEN | MINID> [= | ~] threshold [LIMIT count]`
+    async fn xtrim(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let Some("maxlen" | "minid") = command.arg_as_lowercase_string(2).as_deref() else {
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return Ok(());
+        };
+        let Some((trim, pos)) = Self::parse_trim_options(&command, 2, response_buffer) else {
+            return Ok(());
+        };
+        if pos != command.arg_count() {
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return Ok(());
+        }
+
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        match stream_db.trim(key, &trim)? {
+            StreamTrimResult::Some(removed) => builder.number_usize(response_buffer, removed),
+            StreamTrimResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the specified entries from a stream. Returns the number of entries deleted, IDs
+    /// that do not exist are skipped.
+    /// `XDEL key id [id ...]`
+    async fn xdel(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let mut ids = Vec::<StreamId>::with_capacity(command.arg_count() - 2);
+        for id in command.args_vec().iter().skip(2) {
+            let Some(id) = StreamId::parse(id, 0) else {
+                builder.error_string(response_buffer, ErrorStrings::INVALID_STREAM_ID);
+                return Ok(());
+            };
+            ids.push(id);
+        }
+
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        match stream_db.delete(key, &ids)? {
+            StreamDeleteResult::Some(deleted) => builder.number_usize(response_buffer, deleted),
+            StreamDeleteResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns information about a stream and its consumer groups.
+    /// `XINFO <STREAM | GROUPS> key`
+    async fn xinfo(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 2, response_buffer);
+        let sub_command = command_arg_at_as_str!(command, 1);
+        let builder = RespBuilderV2::default();
+        match sub_command.as_str() {
+            "stream" | "groups" => {}
+            _ => {
+                builder.error_string(
+                    response_buffer,
+                    format!("ERR unknown subcommand '{}'", sub_command).as_str(),
+                );
+                return Ok(());
+            }
+        }
+
+        check_args_count!(command, 3, response_buffer);
+        if command.arg_count() != 3 {
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return Ok(());
+        }
+        let key = command_arg_at!(command, 2);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        let info = match stream_db.info(key)? {
+            StreamInfoResult::Some(info) => info,
+            StreamInfoResult::NotFound => {
+                builder.error_string(response_buffer, ErrorStrings::NO_SUCH_KEY);
+                return Ok(());
+            }
+            StreamInfoResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        if sub_command == "groups" {
+            // consumer groups are not supported: a stream never has any
+            builder.empty_array(response_buffer);
+            return Ok(());
+        }
+
+        builder.add_array_len(response_buffer, 10);
+        builder.add_bulk_string_u8_arr(response_buffer, b"length");
+        builder.add_number(response_buffer, info.len, false);
+        builder.add_bulk_string_u8_arr(response_buffer, b"last-generated-id");
+        builder.add_bulk_string(response_buffer, &info.last_generated_id.to_bytes());
+        builder.add_bulk_string_u8_arr(response_buffer, b"groups");
+        builder.add_number(response_buffer, 0, false);
+        for (name, entry) in [
+            ("first-entry", &info.first_entry),
+            ("last-entry", &info.last_entry),
+        ] {
+            builder.add_bulk_string_u8_arr(response_buffer, name.as_bytes());
+            match entry {
+                Some(entry) => Self::add_entry(&builder, response_buffer, entry),
+                None => builder.add_null_string(response_buffer),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the stream entries matching a given range of IDs (both inclusive).
+    /// `XRANGE key start end [COUNT count]`
+    async fn xrange(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+        let start = command_arg_at!(command, 2);
+        let end = command_arg_at!(command, 3);
+
+        // an incomplete start ID starts at the first sequence, an incomplete end ID ends at
+        // the last one
+        let (Some(start), Some(end)) = (
+            Self::parse_range_id(start, 0),
+            Self::parse_range_id(end, u64::MAX),
+        ) else {
+            builder.error_string(response_buffer, ErrorStrings::INVALID_STREAM_ID);
+            return Ok(());
+        };
+
+        let count = match command.arg_count() {
+            4 => None,
+            6 if BytesMutUtils::to_string(command_arg_at!(command, 4)).to_lowercase()
+                == "count" =>
+            {
+                // a negative count returns an empty result, same as Redis
+                let Some(count) = BytesMutUtils::parse::<i64>(command_arg_at!(command, 5)) else {
+                    builder.error_string(
+                        response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(());
+                };
+                Some(usize::try_from(count).unwrap_or(0))
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        let entries = match stream_db.range(key, start, end, count)? {
+            StreamRangeResult::Some(entries) => entries,
+            StreamRangeResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        builder.add_array_len(response_buffer, entries.len());
+        for entry in &entries {
+            Self::add_entry(&builder, response_buffer, entry);
+        }
+        Ok(())
+    }
+
+    /// Read data from one or multiple streams, only returning entries with an ID greater than
+    /// the last ID reported by the caller. With `BLOCK`, the client is blocked until an entry is
+    /// added to one of the streams or the timeout (`0` means forever) elapses.
+    /// `XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]`
+    async fn xread(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        mut response_buffer: BytesMut,
+    ) -> Result<HandleCommandResult, SableError> {
+        expect_args_count!(
+            command,
+            4,
+            &mut response_buffer,
+            HandleCommandResult::ResponseBufferUpdated(response_buffer)
+        );
+
+        let builder = RespBuilderV2::default();
+        let mut count: Option<usize> = None;
+        let mut timeout: Option<Duration> = None;
+        let mut pos = 1usize;
+        loop {
+            let option = command.arg_as_lowercase_string(pos).unwrap_or_default();
+            match (option.as_str(), command.arg(pos + 1)) {
+                ("count", Some(value)) => {
+                    let Some(value) = BytesMutUtils::parse::<i64>(value) else {
+                        builder.error_string(
+                            &mut response_buffer,
+                            ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                        );
+                        return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                    };
+                    // same as Redis, a count that is not positive means no limit
+                    count = usize::try_from(value).ok().filter(|count| *count > 0);
+                }
+                ("block", Some(value)) => {
+                    let Some(value) = BytesMutUtils::parse::<i64>(value) else {
+                        builder.error_string(
+                            &mut response_buffer,
+                            "ERR timeout is not an integer or out of range",
+                        );
+                        return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                    };
+                    let Ok(value) = u64::try_from(value) else {
+                        builder.error_string(&mut response_buffer, "ERR timeout is negative");
+                        return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                    };
+                    timeout = Some(Duration::from_millis(value));
+                }
+                ("streams", _) => {
+                    pos += 1;
+                    break;
+                }
+                _ => {
+                    builder.error_string(&mut response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                }
+            }
+            pos += 2;
+        }
+
+        // the keys are followed by one ID per key
+        let streams = &command.args_vec()[pos.min(command.arg_count())..];
+        if streams.is_empty() || streams.len() % 2 != 0 {
+            builder.error_string(&mut response_buffer, ErrorStrings::XREAD_UNBALANCED_STREAMS);
+            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        let keys: Vec<&BytesMut> = keys.iter().collect();
+
+        let _unused = LockManager::lock_user_keys_shared(&keys, client_state.database_id());
+        let stream_db = StreamDb::with_storage(client_state.database(), client_state.database_id());
+
+        // `$` is resolved once: after a wakeup, the client reads after the IDs it blocked on
+        let last_ids = match client_state.take_blocked_xread(&command) {
+            Some(last_ids) => last_ids,
+            None => {
+                let mut last_ids = Vec::<StreamId>::with_capacity(ids.len());
+                for (key, id) in keys.iter().zip(ids) {
+                    let last_id = if id.as_ref() == b"$" {
+                        match stream_db.stream_metadata(key)? {
+                            GetStreamMetadataResult::WrongType => {
+                                builder.error_string(&mut response_buffer, ErrorStrings::WRONGTYPE);
+                                return Ok(HandleCommandResult::ResponseBufferUpdated(
+                                    response_buffer,
+                                ));
+                            }
+                            GetStreamMetadataResult::NotFound => StreamId::MIN,
+                            GetStreamMetadataResult::Some(stream) => stream.last_id(),
+                        }
+                    } else {
+                        let Some(last_id) = StreamId::parse(id, 0) else {
+                            builder.error_string(
+                                &mut response_buffer,
+                                ErrorStrings::INVALID_STREAM_ID,
+                            );
+                            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                        };
+                        last_id
+                    };
+                    last_ids.push(last_id);
+                }
+                last_ids
+            }
+        };
+
+        let mut streams_entries = Vec::<(&BytesMut, Vec<StreamEntry>)>::new();
+        for (key, last_id) in keys.iter().zip(&last_ids) {
+            let Some(start) = last_id.next() else {
+                continue;
+            };
+            match stream_db.range(key, start, StreamId::MAX, count)? {
+                StreamRangeResult::WrongType => {
+                    builder.error_string(&mut response_buffer, ErrorStrings::WRONGTYPE);
+                    return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+                }
+                StreamRangeResult::Some(entries) if entries.is_empty() => {}
+                StreamRangeResult::Some(entries) => streams_entries.push((key, entries)),
+            }
+        }
+
+        if !streams_entries.is_empty() {
+            builder.add_array_len(&mut response_buffer, streams_entries.len());
+            for (key, entries) in &streams_entries {
+                builder.add_array_len(&mut response_buffer, 2);
+                builder.add_bulk_string(&mut response_buffer, key);
+                builder.add_array_len(&mut response_buffer, entries.len());
+                for entry in entries {
+                    Self::add_entry(&builder, &mut response_buffer, entry);
+                }
+            }
+            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        }
+
+        let Some(timeout) = timeout else {
+            builder.null_array(&mut response_buffer);
+            return Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer));
+        };
+
+        // No new entries: block the client. When woken up, the command runs again and blocks
+        // again if there is still nothing to read
+        client_state.set_blocked_xread(command.clone(), last_ids);
+        let keys: Vec<BytesMut> = keys.iter().map(|key| (*key).clone()).collect();
+        let rx = client_state.server_inner_state().block_client(&keys).await;
+        Ok(HandleCommandResult::Blocked((rx, timeout)))
+    }
+
+    /// Parse the trimming options that start at `pos` (the `MAXLEN` or `MINID` keyword):
+    /// `<MAXLEN | MINID> [= | ~] threshold [LIMIT count]`. Return the options and the position
+    /// of the argument that follows them. On error, the error is written to `response_buffer`
+    /// and `None` is returned
+    fn parse_trim_options(
+        command: &RedisCommand,
+        pos: usize,
+        response_buffer: &mut BytesMut,
+    ) -> Option<(StreamTrimOptions, usize)> {
+        let builder = RespBuilderV2::default();
+        let is_maxlen = command.arg_as_lowercase_string(pos).as_deref() == Some("maxlen");
+        let mut pos = pos + 1;
+        let approximate = match command.arg(pos).map(|arg| arg.as_ref()) {
+            Some(b"~") => {
+                pos += 1;
+                true
+            }
+            Some(b"=") => {
+                pos += 1;
+                false
+            }
+            _ => false,
+        };
+
+        let Some(threshold) = command.arg(pos) else {
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return None;
+        };
+        pos += 1;
+        let threshold = if is_maxlen {
+            let Some(max_len) = BytesMutUtils::parse::<i64>(threshold) else {
+                builder.error_string(
+                    response_buffer,
+                    ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                );
+                return None;
+            };
+            let Ok(max_len) = u64::try_from(max_len) else {
+                builder.error_string(response_buffer, ErrorStrings::XTRIM_MAXLEN_NEGATIVE);
+                return None;
+            };
+            StreamTrimThreshold::MaxLen(max_len)
+        } else {
+            let Some(min_id) = StreamId::parse(threshold, 0) else {
+                builder.error_string(response_buffer, ErrorStrings::INVALID_STREAM_ID);
+                return None;
+            };
+            StreamTrimThreshold::MinId(min_id)
+        };
+
+        // an approximate trim is always bounded, unless `LIMIT 0` is used
+        let mut limit = approximate.then_some(StreamDb::DEFAULT_TRIM_LIMIT);
+        if command.arg_as_lowercase_string(pos).as_deref() == Some("limit") {
+            if !approximate {
+                builder.error_string(response_buffer, ErrorStrings::XTRIM_LIMIT_WITHOUT_APPROX);
+                return None;
+            }
+            let Some(value) = command
+                .arg(pos + 1)
+                .and_then(|value| BytesMutUtils::parse::<i64>(value))
+            else {
+                builder.error_string(
+                    response_buffer,
+                    ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                );
+                return None;
+            };
+            let Ok(value) = usize::try_from(value) else {
+                builder.error_string(response_buffer, ErrorStrings::XTRIM_LIMIT_NEGATIVE);
+                return None;
+            };
+            limit = (value > 0).then_some(value);
+            pos += 2;
+        }
+
+        Some((
+            StreamTrimOptions {
+                threshold,
+                approximate,
+                limit,
+            },
+            pos,
+        ))
+    }
+
+    /// Parse a range boundary: `-`, `+`, `<ms>` or `<ms>-<seq>`
+    fn parse_range_id(id: &BytesMut, default_seq: u64) -> Option<StreamId> {
+        match id.as_ref() {
+            b"-" => Some(StreamId::MIN),
+            b"+" => Some(StreamId::MAX),
+            _ => StreamId::parse(id, default_seq),
+        }
+    }
+
+    /// Add a stream entry to the response: `[id, [field, value, ...]]`
+    fn add_entry(builder: &RespBuilderV2, response_buffer: &mut BytesMut, entry: &StreamEntry) {
+        let (id, fields) = entry;
+        builder.add_array_len(response_buffer, 2);
+        builder.add_bulk_string(response_buffer, &id.to_bytes());
+        builder.add_array_len(response_buffer, fields.len() * 2);
+        for (field, value) in fields {
+            builder.add_bulk_string(response_buffer, field);
+            builder.add_bulk_string(response_buffer, value);
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+    use tokio::sync::mpsc::Receiver;
+
+    #[test_case(vec![
+        (vec!["xadd", "mystream", "1-1", "a"], "-ERR wrong number of arguments for 'xadd' command\r\n"),
+        (vec!["xadd", "mystream", "1-1", "a", "1", "b"], "-ERR wrong number of arguments for 'xadd' command\r\n"),
+        (vec!["xadd", "mystream", "abc", "a", "1"], "-ERR Invalid stream ID specified as stream command argument\r\n"),
+        (vec!["xadd", "mystream", "0-0", "a", "1"], "-ERR The ID specified in XADD must be greater than 0-0\r\n"),
+        (vec!["xadd", "mystream", "5-1", "a", "1", "b", "2"], "$3\r\n5-1\r\n"),
+        (vec!["xadd", "mystream", "5-1", "a", "1"], "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"),
+        (vec!["xadd", "mystream", "4", "a", "1"], "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"),
+        (vec!["xadd", "mystream", "6", "a", "1"], "$3\r\n6-0\r\n"),
+        (vec!["xlen", "mystream"], ":2\r\n"),
+        (vec!["xlen", "nosuchstream"], ":0\r\n"),
+        (vec!["type", "mystream"], "+stream\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["xadd", "str_key", "*", "a", "1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["xlen", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_xadd"; "test_xadd")]
+    #[test_case(vec![
+        (vec!["xadd", "mystream", "1-1", "f", "a"], "$3\r\n1-1\r\n"),
+        (vec!["xadd", "mystream", "1-2", "f", "b"], "$3\r\n1-2\r\n"),
+        (vec!["xadd", "mystream", "2-0", "f", "c"], "$3\r\n2-0\r\n"),
+        (vec!["xadd", "mystream", "3-5", "f", "d"], "$3\r\n3-5\r\n"),
+        (vec!["xrange", "mystream", "-", "+"], "*4\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\na\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nf\r\n$1\r\nc\r\n*2\r\n$3\r\n3-5\r\n*2\r\n$1\r\nf\r\n$1\r\nd\r\n"),
+        // both bounds are inclusive
+        (vec!["xrange", "mystream", "1-2", "2-0"], "*2\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nf\r\n$1\r\nc\r\n"),
+        // an incomplete end ID includes all the sequences of that millisecond
+        (vec!["xrange", "mystream", "1", "1"], "*2\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\na\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n"),
+        (vec!["xrange", "mystream", "3", "+"], "*1\r\n*2\r\n$3\r\n3-5\r\n*2\r\n$1\r\nf\r\n$1\r\nd\r\n"),
+        (vec!["xrange", "mystream", "-", "+", "COUNT", "1"], "*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\na\r\n"),
+        (vec!["xrange", "mystream", "-", "+", "count", "0"], "*0\r\n"),
+        (vec!["xrange", "mystream", "3-0", "1-0"], "*0\r\n"),
+        (vec!["xrange", "mystream", "4", "+"], "*0\r\n"),
+        (vec!["xrange", "nosuchstream", "-", "+"], "*0\r\n"),
+        (vec!["xrange", "mystream", "x", "+"], "-ERR Invalid stream ID specified as stream command argument\r\n"),
+        (vec!["xrange", "mystream", "-", "+", "count"], "-ERR syntax error\r\n"),
+        (vec!["xrange", "mystream", "-", "+", "count", "a"], "-ERR value is not an integer or out of range\r\n"),
+    ], "test_xrange"; "test_xrange")]
+    #[test_case(vec![
+        (vec!["xadd", "s1", "1-1", "f", "a"], "$3\r\n1-1\r\n"),
+        (vec!["xadd", "s1", "1-2", "f", "b"], "$3\r\n1-2\r\n"),
+        (vec!["xadd", "s2", "2-0", "f", "c"], "$3\r\n2-0\r\n"),
+        (vec!["xread", "streams", "s1", "s2", "1-1", "0"], "*2\r\n*2\r\n$2\r\ns1\r\n*1\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n*2\r\n$2\r\ns2\r\n*1\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nf\r\n$1\r\nc\r\n"),
+        (vec!["xread", "COUNT", "1", "STREAMS", "s1", "0-0"], "*1\r\n*2\r\n$2\r\ns1\r\n*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\na\r\n"),
+        // streams with no new entries are omitted, nothing new at all is a null reply
+        (vec!["xread", "streams", "s1", "s2", "$", "2-0"], "*-1\r\n"),
+        (vec!["xread", "streams", "s1", "nosuchstream", "1-2", "0"], "*-1\r\n"),
+        (vec!["xread", "streams", "s1"], "-ERR wrong number of arguments for 'xread' command\r\n"),
+        (vec!["xread", "streams", "s1", "s2", "0"], "-ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.\r\n"),
+        (vec!["xread", "count", "1", "s1", "0"], "-ERR syntax error\r\n"),
+        (vec!["xread", "count", "a", "streams", "s1", "0"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["xread", "block", "-1", "streams", "s1", "0"], "-ERR timeout is negative\r\n"),
+        (vec!["xread", "streams", "s1", "x"], "-ERR Invalid stream ID specified as stream command argument\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["xread", "streams", "str_key", "0"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_xread"; "test_xread")]
+    #[test_case(vec![
+        (vec!["xadd", "s", "1-0", "f", "v"], "$3\r\n1-0\r\n"),
+        (vec!["xadd", "s", "2-0", "f", "v"], "$3\r\n2-0\r\n"),
+        (vec!["xadd", "s", "3-0", "f", "v"], "$3\r\n3-0\r\n"),
+        (vec!["xadd", "s", "4-0", "f", "v"], "$3\r\n4-0\r\n"),
+        (vec!["xadd", "s", "5-0", "f", "v"], "$3\r\n5-0\r\n"),
+        // exact trimming keeps exactly the newest entries
+        (vec!["xtrim", "s", "maxlen", "=", "3"], ":2\r\n"),
+        (vec!["xlen", "s"], ":3\r\n"),
+        (vec!["xrange", "s", "-", "2"], "*0\r\n"),
+        (vec!["xrange", "s", "-", "3"], "*1\r\n*2\r\n$3\r\n3-0\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n"),
+        (vec!["xtrim", "s", "MAXLEN", "3"], ":0\r\n"),
+        (vec!["xtrim", "s", "minid", "4"], ":1\r\n"),
+        (vec!["xlen", "s"], ":2\r\n"),
+        // an approximate trim only removes whole chunks
+        (vec!["xtrim", "s", "maxlen", "~", "0"], ":0\r\n"),
+        (vec!["xlen", "s"], ":2\r\n"),
+        // XADD trims the stream while adding the entry
+        (vec!["xadd", "s", "maxlen", "1", "6-0", "f", "v"], "$3\r\n6-0\r\n"),
+        (vec!["xlen", "s"], ":1\r\n"),
+        (vec!["xrange", "s", "-", "5"], "*0\r\n"),
+        (vec!["xadd", "s", "MINID", "=", "10", "7-0", "f", "v"], "$3\r\n7-0\r\n"),
+        (vec!["xlen", "s"], ":0\r\n"),
+        (vec!["xadd", "s", "7-0", "f", "v"], "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"),
+        (vec!["xtrim", "nosuchstream", "maxlen", "0"], ":0\r\n"),
+        (vec!["xtrim", "s", "maxlen", "-1"], "-ERR The MAXLEN argument must be >= 0.\r\n"),
+        (vec!["xtrim", "s", "maxlen", "x"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["xtrim", "s", "maxlen", "=", "1", "limit", "10"], "-ERR syntax error, LIMIT cannot be used without the special ~ option\r\n"),
+        (vec!["xtrim", "s", "maxlen", "~", "1", "limit", "-1"], "-ERR The LIMIT argument must be >= 0.\r\n"),
+        (vec!["xtrim", "s", "minid", "x"], "-ERR Invalid stream ID specified as stream command argument\r\n"),
+        (vec!["xtrim", "s", "size", "1"], "-ERR syntax error\r\n"),
+        (vec!["xtrim", "s", "maxlen", "1", "extra"], "-ERR syntax error\r\n"),
+        (vec!["xtrim", "s", "maxlen", "~"], "-ERR syntax error\r\n"),
+        (vec!["xadd", "s", "maxlen", "1", "minid", "1", "*", "f", "v"], "-ERR syntax error, MAXLEN and MINID options at the same time are not compatible\r\n"),
+        (vec!["xadd", "s", "maxlen", "1", "*", "f"], "-ERR wrong number of arguments for 'xadd' command\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["xtrim", "str_key", "maxlen", "0"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_xtrim"; "test_xtrim")]
+    #[test_case(vec![
+        (vec!["xadd", "s", "1-0", "f", "a"], "$3\r\n1-0\r\n"),
+        (vec!["xadd", "s", "2-0", "f", "b"], "$3\r\n2-0\r\n"),
+        (vec!["xadd", "s", "3-0", "f", "c"], "$3\r\n3-0\r\n"),
+        (vec!["xadd", "s", "4-0", "f", "d"], "$3\r\n4-0\r\n"),
+        // IDs that do not exist are skipped
+        (vec!["xdel", "s", "2-0", "2-0", "9-0"], ":1\r\n"),
+        (vec!["xlen", "s"], ":3\r\n"),
+        (vec!["xdel", "s", "1", "4-0"], ":2\r\n"),
+        (vec!["xlen", "s"], ":1\r\n"),
+        (vec!["xrange", "s", "-", "+"], "*1\r\n*2\r\n$3\r\n3-0\r\n*2\r\n$1\r\nf\r\n$1\r\nc\r\n"),
+        // the last generated ID is kept: new IDs must still be greater than it
+        (vec!["xadd", "s", "4-0", "f", "e"], "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"),
+        (vec!["xdel", "nosuchstream", "1-0"], ":0\r\n"),
+        (vec!["xdel", "s", "x"], "-ERR Invalid stream ID specified as stream command argument\r\n"),
+        (vec!["xdel", "s"], "-ERR wrong number of arguments for 'xdel' command\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["xdel", "str_key", "1-0"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_xdel"; "test_xdel")]
+    #[test_case(vec![
+        (vec!["xadd", "s", "1-0", "f", "a"], "$3\r\n1-0\r\n"),
+        (vec!["xadd", "s", "2-0", "f", "b"], "$3\r\n2-0\r\n"),
+        (vec!["xadd", "s", "5-3", "f", "c"], "$3\r\n5-3\r\n"),
+        (vec!["xinfo", "stream", "s"], "*10\r\n$6\r\nlength\r\n:3\r\n$17\r\nlast-generated-id\r\n$3\r\n5-3\r\n$6\r\ngroups\r\n:0\r\n$11\r\nfirst-entry\r\n*2\r\n$3\r\n1-0\r\n*2\r\n$1\r\nf\r\n$1\r\na\r\n$10\r\nlast-entry\r\n*2\r\n$3\r\n5-3\r\n*2\r\n$1\r\nf\r\n$1\r\nc\r\n"),
+        // deleting the newest entry does not change the last generated ID
+        (vec!["xdel", "s", "5-3", "1-0"], ":2\r\n"),
+        (vec!["xinfo", "STREAM", "s"], "*10\r\n$6\r\nlength\r\n:1\r\n$17\r\nlast-generated-id\r\n$3\r\n5-3\r\n$6\r\ngroups\r\n:0\r\n$11\r\nfirst-entry\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n$10\r\nlast-entry\r\n*2\r\n$3\r\n2-0\r\n*2\r\n$1\r\nf\r\n$1\r\nb\r\n"),
+        (vec!["xdel", "s", "2-0"], ":1\r\n"),
+        (vec!["xinfo", "stream", "s"], "*10\r\n$6\r\nlength\r\n:0\r\n$17\r\nlast-generated-id\r\n$3\r\n5-3\r\n$6\r\ngroups\r\n:0\r\n$11\r\nfirst-entry\r\n$-1\r\n$10\r\nlast-entry\r\n$-1\r\n"),
+        (vec!["xinfo", "groups", "s"], "*0\r\n"),
+        (vec!["xinfo", "stream", "nosuchstream"], "-ERR no such key\r\n"),
+        (vec!["xinfo", "groups", "nosuchstream"], "-ERR no such key\r\n"),
+        (vec!["xinfo", "consumers", "s"], "-ERR unknown subcommand 'consumers'\r\n"),
+        (vec!["xinfo", "stream", "s", "full"], "-ERR syntax error\r\n"),
+        (vec!["xinfo", "stream"], "-ERR wrong number of arguments for 'xinfo' command\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["xinfo", "stream", "str_key"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "test_xinfo"; "test_xinfo")]
+    fn test_stream_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn deferred_command(
+        client_state: Rc<ClientState>,
+        cmd: Rc<RedisCommand>,
+    ) -> (Receiver<u8>, Duration) {
+        let mut sink = crate::tests::ResponseSink::with_name("deferred_command").await;
+        let next_action = Client::handle_command(client_state, cmd, &mut sink.fp)
+            .await
+            .unwrap();
+        match next_action {
+            ClientNextAction::Wait((rx, duration)) => (rx, duration),
+            _ => panic!("expected to be blocked"),
+        }
+    }
+
+    /// Execute a command
+    async fn execute_command(client_state: Rc<ClientState>, cmd: Rc<RedisCommand>) -> String {
+        let mut sink = crate::tests::ResponseSink::with_name("execute_command").await;
+        let next_action = Client::handle_command(client_state, cmd.clone(), &mut sink.fp)
+            .await
+            .unwrap();
+        match next_action {
+            ClientNextAction::SendResponse(buffer) => BytesMutUtils::to_string(&buffer),
+            ClientNextAction::NoAction => sink.read_all().await,
+            _ => panic!("Command {:?} is not expected to be blocked", cmd),
+        }
+    }
+
+    /// Add the entries `<id>-0` for all the IDs in `ids` to `key`
+    async fn add_entries(client_state: Rc<ClientState>, key: &str, ids: std::ops::Range<u64>) {
+        for id in ids {
+            let args = vec![
+                BytesMut::from("xadd"),
+                BytesMut::from(key),
+                BytesMut::from(format!("{}-0", id).as_str()),
+                BytesMut::from("f"),
+                BytesMut::from("v"),
+            ];
+            let cmd = Rc::new(RedisCommand::new(args).unwrap());
+            execute_command(client_state.clone(), cmd).await;
+        }
+    }
+
+    #[test]
+    fn test_xtrim_approximate() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+            let execute = |args: Vec<&'static str>| {
+                execute_command(client.inner(), Rc::new(RedisCommand::for_test(args)))
+            };
+
+            add_entries(client.inner(), "s", 1..351).await;
+
+            // LIMIT bounds the work: only the whole chunks that fit in the limit are removed
+            assert_eq!(
+                execute(vec!["xtrim", "s", "maxlen", "~", "10", "limit", "250"]).await,
+                ":200\r\n"
+            );
+            assert_eq!(execute(vec!["xlen", "s"]).await, ":150\r\n");
+
+            // the stream may keep more entries than the threshold
+            assert_eq!(
+                execute(vec!["xtrim", "s", "maxlen", "~", "10"]).await,
+                ":100\r\n"
+            );
+            assert_eq!(
+                execute(vec!["xtrim", "s", "maxlen", "~", "10"]).await,
+                ":0\r\n"
+            );
+            assert_eq!(execute(vec!["xlen", "s"]).await, ":50\r\n");
+            assert_eq!(execute(vec!["xtrim", "s", "maxlen", "10"]).await, ":40\r\n");
+            assert_eq!(execute(vec!["xlen", "s"]).await, ":10\r\n");
+            assert_eq!(execute(vec!["xrange", "s", "-", "340"]).await, "*0\r\n");
+
+            // MINID stops in the middle of a chunk: that chunk is kept
+            add_entries(client.inner(), "s", 351..601).await;
+            assert_eq!(
+                execute(vec!["xtrim", "s", "minid", "~", "500"]).await,
+                ":100\r\n"
+            );
+            assert_eq!(
+                execute(vec!["xtrim", "s", "minid", "~", "500", "limit", "0"]).await,
+                ":0\r\n"
+            );
+            assert_eq!(execute(vec!["xtrim", "s", "minid", "500"]).await, ":59\r\n");
+            assert_eq!(execute(vec!["xlen", "s"]).await, ":101\r\n");
+
+            // XADD with an approximate trim
+            assert_eq!(
+                execute(vec![
+                    "xadd", "s", "maxlen", "~", "1", "limit", "100", "601-0", "f", "v"
+                ])
+                .await,
+                "$5\r\n601-0\r\n"
+            );
+            assert_eq!(execute(vec!["xlen", "s"]).await, ":2\r\n");
+        });
+    }
+
+    #[test]
+    fn test_blocking_xread() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+
+            let server = Arc::<ServerState>::default();
+            let reader = Client::new(server.clone(), store.clone(), None);
+            let writer = Client::new(server, store, None);
+
+            // `$` on a missing stream: wait for the first entry
+            let read_cmd = Rc::new(RedisCommand::for_test(vec![
+                "xread", "block", "5000", "streams", "mystream", "$",
+            ]));
+            let (rx, duration) = deferred_command(reader.inner(), read_cmd.clone()).await;
+            assert_eq!(duration, Duration::from_millis(5000));
+
+            let add_cmd = Rc::new(RedisCommand::for_test(vec![
+                "xadd", "mystream", "5-0", "f", "v",
+            ]));
+            assert_eq!(
+                execute_command(writer.inner(), add_cmd).await,
+                "$3\r\n5-0\r\n"
+            );
+
+            // the woken reader runs the command again and gets the entry added after it blocked
+            assert_eq!(
+                Client::wait_for(rx, duration).await,
+                crate::client::WaitResult::TryAgain
+            );
+            assert_eq!(
+                execute_command(reader.inner(), read_cmd).await,
+                "*1\r\n*2\r\n$8\r\nmystream\r\n*1\r\n*2\r\n$3\r\n5-0\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+            );
+
+            // a wakeup for an entry the reader is not interested in: the reader blocks again
+            let read_cmd = Rc::new(RedisCommand::for_test(vec![
+                "xread", "block", "0", "streams", "mystream", "10-0",
+            ]));
+            let (rx, duration) = deferred_command(reader.inner(), read_cmd.clone()).await;
+            let add_cmd = Rc::new(RedisCommand::for_test(vec![
+                "xadd", "mystream", "7-0", "f", "v",
+            ]));
+            execute_command(writer.inner(), add_cmd).await;
+            assert_eq!(
+                Client::wait_for(rx, duration).await,
+                crate::client::WaitResult::TryAgain
+            );
+            let (rx, duration) = deferred_command(reader.inner(), read_cmd.clone()).await;
+
+            let add_cmd = Rc::new(RedisCommand::for_test(vec![
+                "xadd", "mystream", "11-0", "f", "v",
+            ]));
+            execute_command(writer.inner(), add_cmd).await;
+            assert_eq!(
+                Client::wait_for(rx, duration).await,
+                crate::client::WaitResult::TryAgain
+            );
+            assert_eq!(
+                execute_command(reader.inner(), read_cmd).await,
+                "*1\r\n*2\r\n$8\r\nmystream\r\n*1\r\n*2\r\n$4\r\n11-0\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+            );
+        });
+    }
+}
@@ -23,11 +23,37 @@ use tokio::io::AsyncWriteExt;
 pub enum SetInternalReturnValue {
     KeyExistsErr,
     KeyDoesNotExistErr,
-    Success(Option<BytesMut>),
+    Success,
+    /// `SET .. GET` was requested: the value stored at key before the command, if any
+    OldValue(Option<BytesMut>),
     SyntaxError,
     WrongType,
 }
 
+/// Strings can not grow beyond 512MB, same as Redis
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// What to do with a string value after it was read by `read_string_then`
+enum StringPostReadAction<'a> {
+    /// Read only (`GET`)
+    None,
+    /// Delete the key (`GETDEL`)
+    Delete,
+    /// Replace the value and discard any TTL (`GETSET`)
+    Replace(&'a BytesMut),
+    /// Update the key's expiration (`GETEX`)
+    Expire(GetExExpiry),
+}
+
+/// The `GETEX` expiration options
+enum GetExExpiry {
+    Seconds(u64),
+    Millis(u64),
+    UnixTimeSeconds(u64),
+    UnixTimeMillis(u64),
+    Persist,
+}
+
 pub struct StringCommands {}
 
 impl StringCommands {
@@ -139,18 +165,43 @@ impl StringCommands {
             let arg_lowercase = BytesMutUtils::to_string(arg).to_lowercase();
             match arg_lowercase.as_str() {
                 "ex" | "px" | "exat" | "pxat" => {
-                    if let Some(val) = iter.next() {
-                        expiry = (Some(arg_lowercase), Some(BytesMutUtils::to_string(val)));
-                    } else {
-                        expiry = (Some(arg_lowercase), None);
+                    // only one expiration option is allowed and it can not be combined with KEEPTTL
+                    let Some(val) = iter.next() else {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    };
+                    if expiry.0.is_some() || flags.intersects(SetFlags::KeepTtl) {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    }
+                    expiry = (Some(arg_lowercase), Some(BytesMutUtils::to_string(val)));
+                }
+                "keepttl" => {
+                    if expiry.0.is_some() {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    }
+                    flags |= SetFlags::KeepTtl
+                }
+                "idle" => {
+                    // SableDB does not track the keys idle time, the option is validated and ignored
+                    let Some(seconds) = iter.next() else {
+                        builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                        return Ok(());
+                    };
+                    if BytesMutUtils::parse::<u64>(seconds).is_none() {
+                        builder.error_string(
+                            response_buffer,
+                            ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                        );
+                        return Ok(());
                     }
                 }
-                "keepttl" => flags |= SetFlags::KeepTtl,
                 "xx" => flags |= SetFlags::SetIfExists,
                 "nx" => flags |= SetFlags::SetIfNotExists,
                 "get" => flags |= SetFlags::ReturnOldValue,
                 _ => {
-                    builder.error_string(response_buffer, "ERR syntax error");
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
                     return Ok(());
                 }
             }
@@ -205,54 +256,32 @@ impl StringCommands {
         command: Rc<RedisCommand>,
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
-        let builder = RespBuilderV2::default();
         check_args_count!(command, 2, response_buffer);
         let key = command_arg_at!(command, 1);
-
-        // fetch the value
-        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
-        let strings_db =
-            StringsDb::with_storage(client_state.database(), client_state.database_id());
-
-        match strings_db.get(key)? {
-            Some((value, metadata)) => {
-                check_value_type!(metadata, Encoding::VALUE_STRING, response_buffer);
-                Telemetry::inc_db_hit();
-                builder.bulk_string(response_buffer, &value);
-            }
-            None => {
-                Telemetry::inc_db_miss();
-                builder.null_string(response_buffer);
-            }
-        }
-        Ok(())
+        Self::read_string_then(
+            client_state,
+            key,
+            StringPostReadAction::None,
+            response_buffer,
+        )
     }
 
     /// Get the value of key and delete the key. This command is similar to GET,
-    /// except for the fact that it also deletes the key on success (if and only if the key's value type is a string)
+    /// except for the fact that it also deletes the key on success (if and only if the key's value type is a string).
+    /// The read and the delete are done while holding the key's exclusive lock (see `read_string_then`)
     async fn getdel(
         client_state: Rc<ClientState>,
         command: Rc<RedisCommand>,
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 2, response_buffer);
-        let builder = RespBuilderV2::default();
         let key = command_arg_at!(command, 1);
-
-        // start atomic operation here
-        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
-        let strings_db =
-            StringsDb::with_storage(client_state.database(), client_state.database_id());
-        if let Some((old_value, metadata)) = strings_db.get(key)? {
-            check_value_type!(metadata, Encoding::VALUE_STRING, response_buffer);
-            builder.bulk_string(response_buffer, &old_value);
-
-            // delete the old value
-            strings_db.delete(key)?;
-        } else {
-            builder.null_string(response_buffer);
-        }
-        Ok(())
+        Self::read_string_then(
+            client_state,
+            key,
+            StringPostReadAction::Delete,
+            response_buffer,
+        )
     }
 
     /// Atomically sets key to value and returns the old value stored at key.
@@ -264,28 +293,14 @@ impl StringCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 3, response_buffer);
-        let builder = RespBuilderV2::default();
         let key = command_arg_at!(command, 1);
         let new_value = command_arg_at!(command, 2);
-
-        // start atomic operation here
-        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
-        let strings_db =
-            StringsDb::with_storage(client_state.database(), client_state.database_id());
-        if let Some((old_value, metadata)) = strings_db.get(key)? {
-            check_value_type!(metadata, Encoding::VALUE_STRING, response_buffer);
-            builder.bulk_string(response_buffer, &old_value);
-        } else {
-            builder.null_string(response_buffer);
-        }
-
-        strings_db.put(
+        Self::read_string_then(
+            client_state,
             key,
-            new_value,
-            &StringValueMetadata::new(),
-            PutFlags::Override,
-        )?;
-        Ok(())
+            StringPostReadAction::Replace(new_value),
+            response_buffer,
+        )
     }
 
     /// Get the value of key and optionally set its expiration. GETEX is similar to GET,
@@ -295,67 +310,60 @@ impl StringCommands {
         command: Rc<RedisCommand>,
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
-        let builder = RespBuilderV2::default();
         check_args_count!(command, 2, response_buffer);
-        // fetch the value
         let key = command_arg_at!(command, 1);
 
-        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
-        let strings_db =
-            StringsDb::with_storage(client_state.database(), client_state.database_id());
-        match strings_db.get(key)? {
-            Some((value, mut metadata)) => {
-                // ensure the key is of type string
-                check_value_type!(metadata, Encoding::VALUE_STRING, response_buffer);
-
-                Telemetry::inc_db_hit();
-                builder.bulk_string(response_buffer, &value);
-
-                let third_arg = command.arg_as_lowercase_string(2);
-                let fourth_arg = command.arg_as_lowercase_string(3);
-
-                match (third_arg.as_deref(), fourth_arg.as_deref()) {
-                    (Some("ex"), Some(seconds)) => {
-                        // seconds -- Set the specified expire time, in seconds
-                        let num = parse_string_to_number!(seconds, response_buffer);
-                        metadata.expiration_mut().set_ttl_seconds(num)?;
-                        strings_db.put(key, &value, &metadata, PutFlags::Override)?;
-                    }
-                    (Some("px"), Some(milliseconds)) => {
-                        // milliseconds -- Set the specified expire time, in milliseconds.
-                        let num = parse_string_to_number!(milliseconds, response_buffer);
-                        metadata.expiration_mut().set_ttl_millis(num)?;
-                        strings_db.put(key, &value, &metadata, PutFlags::Override)?;
-                    }
-                    (Some("exat"), Some(unix_time_seconds)) => {
-                        // timestamp-seconds -- Set the specified Unix time at which the key will expire, in seconds.
-                        let num = parse_string_to_number!(unix_time_seconds, response_buffer);
-                        metadata
-                            .expiration_mut()
-                            .set_expire_timestamp_seconds(num)?;
-                        strings_db.put(key, &value, &metadata, PutFlags::Override)?;
-                    }
-                    (Some("pxat"), Some(unix_time_milliseconds)) => {
-                        // timestamp-milliseconds -- Set the specified Unix time at which the
-                        // key will expire, in milliseconds
-                        let num = parse_string_to_number!(unix_time_milliseconds, response_buffer);
-                        metadata.expiration_mut().set_expire_timestamp_millis(num)?;
-                        strings_db.put(key, &value, &metadata, PutFlags::Override)?;
-                    }
-                    (Some("persist"), None) => {
-                        // Remove the time to live associated with the key.
-                        metadata.expiration_mut().set_no_expiration()?;
-                        strings_db.put(key, &value, &metadata, PutFlags::Override)?;
-                    }
-                    (_, _) => {}
+        // parse the options before touching the database, so a bad option
+        // does not leave a half written response behind
+        let third_arg = command.arg_as_lowercase_string(2);
+        let fourth_arg = command.arg_as_lowercase_string(3);
+        let action = match (third_arg.as_deref(), fourth_arg.as_deref()) {
+            (None, None) => StringPostReadAction::None,
+            (Some(option @ ("ex" | "px" | "exat" | "pxat")), Some(value)) => {
+                let Ok(num) = StringUtils::parse_str_to_number::<i64>(value) else {
+                    let builder = RespBuilderV2::default();
+                    builder.error_string(
+                        response_buffer,
+                        ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+                    );
+                    return Ok(());
+                };
+                if num <= 0 {
+                    let builder = RespBuilderV2::default();
+                    builder.error_string(response_buffer, ErrorStrings::GETEX_INVALID_EXPIRE_TIME);
+                    return Ok(());
                 }
+                let num = num as u64;
+                let expiry = match option {
+                    // seconds -- Set the specified expire time, in seconds
+                    "ex" => GetExExpiry::Seconds(num),
+                    // milliseconds -- Set the specified expire time, in milliseconds.
+                    "px" => GetExExpiry::Millis(num),
+                    // timestamp-seconds -- Set the specified Unix time at which the key will expire, in seconds.
+                    "exat" => GetExExpiry::UnixTimeSeconds(num),
+                    // timestamp-milliseconds -- Set the specified Unix time at which the
+                    // key will expire, in milliseconds
+                    _ => GetExExpiry::UnixTimeMillis(num),
+                };
+                StringPostReadAction::Expire(expiry)
             }
-            None => {
-                Telemetry::inc_db_miss();
-                builder.null_string(response_buffer);
+            (Some("persist"), None) => {
+                // Remove the time to live associated with the key.
+                StringPostReadAction::Expire(GetExExpiry::Persist)
             }
+            (_, _) => {
+                let builder = RespBuilderV2::default();
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        if command.arg_count() > 4 {
+            let builder = RespBuilderV2::default();
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return Ok(());
         }
-        Ok(())
+        Self::read_string_then(client_state, key, action, response_buffer)
     }
 
     /// Returns the substring of the string value stored at key, determined by the
@@ -368,38 +376,34 @@ impl StringCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 4, response_buffer);
-        // Check that the key exists
         let key = command_arg_at!(command, 1);
+        let start = command_arg_at!(command, 2);
+        let end = command_arg_at!(command, 3);
+        let start_index = to_number!(start, i64, response_buffer, Ok(()));
+        let end_index = to_number!(end, i64, response_buffer, Ok(()));
         let builder = RespBuilderV2::default();
 
         let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
         let strings_db =
             StringsDb::with_storage(client_state.database(), client_state.database_id());
-        let result = strings_db.get(key)?;
-        match result {
-            Some((value, _)) => {
-                let start = command_arg_at!(command, 2);
-                let end = command_arg_at!(command, 3);
-                let start_index = to_number!(start, i64, response_buffer, Ok(()));
-                let end_index = to_number!(end, i64, response_buffer, Ok(()));
-
-                // translate negative index into valid index in value
-                let index_pair = BaseCommands::fix_range_indexes(&value, start_index, end_index);
-
-                match index_pair {
-                    Some((start, end)) => {
-                        builder.bulk_string(response_buffer, &BytesMut::from(&value[start..end]));
-                    }
-                    None => {
-                        builder.empty_string(response_buffer);
-                    }
-                }
+
+        // a missing key is an empty string
+        let Some((value, md)) = strings_db.get(key)? else {
+            builder.empty_string(response_buffer);
+            return Ok(());
+        };
+        check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+
+        // negative indexes count from the end of the string, the range is clamped to the
+        // string bounds
+        match BaseCommands::fix_range_indexes(&value, start_index, end_index) {
+            Some((start, end)) => {
+                builder.bulk_string(response_buffer, &BytesMut::from(&value[start..end]));
             }
             None => {
                 builder.empty_string(response_buffer);
             }
         }
-
         Ok(())
     }
 
@@ -425,7 +429,6 @@ impl StringCommands {
                 Some(&old_value),
                 -1,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         } else {
@@ -433,7 +436,6 @@ impl StringCommands {
                 None,
                 -1,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         };
@@ -471,7 +473,6 @@ impl StringCommands {
                 Some(&old_value),
                 1,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         } else {
@@ -479,7 +480,6 @@ impl StringCommands {
                 None,
                 1,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         };
@@ -519,7 +519,6 @@ impl StringCommands {
                 Some(&old_value),
                 -decrement,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         } else {
@@ -527,7 +526,6 @@ impl StringCommands {
                 None,
                 -decrement,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         };
@@ -567,7 +565,6 @@ impl StringCommands {
                 Some(&old_value),
                 decrement,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         } else {
@@ -575,7 +572,6 @@ impl StringCommands {
                 None,
                 decrement,
                 response_buffer,
-                false,
                 ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
             )
         };
@@ -601,47 +597,40 @@ impl StringCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
         let key = command_arg_at!(command, 1);
-        let interval = command_arg_at!(command, 2);
-        let decrement = to_number_ex!(
-            interval,
-            f64,
-            response_buffer,
-            Ok(()),
-            ErrorStrings::VALUE_NOT_VALID_FLOAT
-        );
+        let increment = command_arg_at!(command, 2);
+        let Some(increment) = BytesMutUtils::parse_float(increment) else {
+            builder.error_string(response_buffer, ErrorStrings::VALUE_NOT_VALID_FLOAT);
+            return Ok(());
+        };
 
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
         let strings_db =
             StringsDb::with_storage(client_state.database(), client_state.database_id());
 
-        let result = if let Some((old_value, old_md)) = strings_db.get(key)? {
-            check_value_type!(old_md, Encoding::VALUE_STRING, response_buffer);
-            Self::incr_by_internal::<f64>(
-                Some(&old_value),
-                decrement,
-                response_buffer,
-                true,
-                ErrorStrings::VALUE_NOT_VALID_FLOAT,
-            )
-        } else {
-            Self::incr_by_internal::<f64>(
-                None,
-                decrement,
-                response_buffer,
-                true,
-                ErrorStrings::VALUE_NOT_VALID_FLOAT,
-            )
+        let (current, md) = match strings_db.get(key)? {
+            Some((old_value, md)) => {
+                check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+                let Some(current) = BytesMutUtils::parse_float(&old_value) else {
+                    builder.error_string(response_buffer, ErrorStrings::VALUE_NOT_VALID_FLOAT);
+                    return Ok(());
+                };
+                (current, md)
+            }
+            None => (0f64, StringValueMetadata::new()),
         };
 
-        if let Some(result) = result {
-            strings_db.put(
-                key,
-                &result,
-                &StringValueMetadata::new(),
-                PutFlags::Override,
-            )?;
+        let new_value = current + increment;
+        if !new_value.is_finite() {
+            builder.error_string(response_buffer, ErrorStrings::INCR_NAN_OR_INFINITY);
+            return Ok(());
         }
+
+        // the value is stored as formatted in the reply, and keeps its TTL
+        let new_value = BytesMut::from(StringUtils::format_redis_float(new_value).as_str());
+        strings_db.put(key, &new_value, &md, PutFlags::Override)?;
+        builder.bulk_string(response_buffer, &new_value);
         Ok(())
     }
 
@@ -951,29 +940,50 @@ impl StringCommands {
         response_buffer: &mut BytesMut,
     ) -> Result<(), SableError> {
         check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
         let key = command_arg_at!(command, 1);
         let offset = command_arg_at!(command, 2);
         let value = command_arg_at!(command, 3);
 
+        let offset = to_number!(offset, i64, response_buffer, Ok(()));
+        let Ok(offset) = usize::try_from(offset) else {
+            builder.error_string(response_buffer, ErrorStrings::OFFSET_OUT_OF_RANGE);
+            return Ok(());
+        };
+
         let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
         let strings_db =
             StringsDb::with_storage(client_state.database(), client_state.database_id());
 
-        let new_value = if let Some((old_value, md)) = strings_db.get(key)? {
-            check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
-            Self::setrange_internal(key, Some(&old_value), Some(value), offset, response_buffer)
-        } else {
-            Self::setrange_internal(key, None, Some(value), offset, response_buffer)
+        let (mut new_value, md) = match strings_db.get(key)? {
+            Some((old_value, md)) => {
+                check_value_type!(md, Encoding::VALUE_STRING, response_buffer);
+                (old_value, md)
+            }
+            None => (BytesMut::new(), StringValueMetadata::new()),
         };
 
-        if let Some(new_value) = new_value {
-            strings_db.put(
-                key,
-                &new_value,
-                &StringValueMetadata::new(),
-                PutFlags::Override,
-            )?;
+        // an empty value does not modify (or create) the key
+        if value.is_empty() {
+            builder.number_usize(response_buffer, new_value.len());
+            return Ok(());
         }
+
+        let end = offset.saturating_add(value.len());
+        if end > MAX_STRING_LEN {
+            builder.error_string(response_buffer, ErrorStrings::STRING_EXCEEDS_MAX_SIZE);
+            return Ok(());
+        }
+
+        // the gap between the current end of the string and `offset` is zero padded
+        if end > new_value.len() {
+            new_value.resize(end, 0u8);
+        }
+        new_value[offset..end].copy_from_slice(value);
+
+        // keep the metadata (and the TTL) of the existing value
+        strings_db.put(key, &new_value, &md, PutFlags::Override)?;
+        builder.number_usize(response_buffer, new_value.len());
         Ok(())
     }
 
@@ -1041,9 +1051,12 @@ impl StringCommands {
         }
 
         // choose the correct lock
-        let _unused = if flags
-            .intersects(SetFlags::SetIfNotExists | SetFlags::SetIfExists | SetFlags::ReturnOldValue)
-        {
+        let _unused = if flags.intersects(
+            SetFlags::SetIfNotExists
+                | SetFlags::SetIfExists
+                | SetFlags::ReturnOldValue
+                | SetFlags::KeepTtl,
+        ) {
             // requires exclusive lock
             LockManager::lock_user_key_exclusive(user_key, client_state.database_id())
         } else {
@@ -1058,18 +1071,6 @@ impl StringCommands {
                 | SetFlags::SetIfNotExists,
         ) {
             if let Some((old_value, old_metadata)) = strings_db.get(user_key)? {
-                // key exists
-                if flags.intersects(SetFlags::SetIfNotExists) {
-                    // key exists, but `SetIfNotExists` is set
-                    return Ok(SetInternalReturnValue::KeyExistsErr);
-                }
-                // keep the old ttl?
-                if flags.intersects(SetFlags::KeepTtl) {
-                    metadata
-                        .expiration_mut()
-                        .set_ttl_millis(old_metadata.expiration().ttl_in_millis()?)?;
-                }
-
                 // return the old value?
                 if flags.intersects(SetFlags::ReturnOldValue) {
                     if !old_metadata.is_type(Encoding::VALUE_STRING) {
@@ -1077,6 +1078,22 @@ impl StringCommands {
                     }
                     return_value = Some(old_value);
                 }
+
+                // key exists
+                if flags.intersects(SetFlags::SetIfNotExists) {
+                    // key exists, but `SetIfNotExists` is set. With `GET`, reply with the old
+                    // value without overwriting it
+                    return Ok(if flags.intersects(SetFlags::ReturnOldValue) {
+                        SetInternalReturnValue::OldValue(return_value)
+                    } else {
+                        SetInternalReturnValue::KeyExistsErr
+                    });
+                }
+
+                // keep the old ttl?
+                if flags.intersects(SetFlags::KeepTtl) {
+                    *metadata.expiration_mut() = old_metadata.expiration().clone();
+                }
             } else if flags.intersects(SetFlags::SetIfExists) {
                 // key does not exists, but `SetIfExists` is set
                 return Ok(SetInternalReturnValue::KeyDoesNotExistErr);
@@ -1094,7 +1111,7 @@ impl StringCommands {
                     "exat" => metadata
                         .expiration_mut()
                         .set_expire_timestamp_seconds(num)?,
-                    "pexat" => metadata.expiration_mut().set_expire_timestamp_millis(num)?,
+                    "pxat" => metadata.expiration_mut().set_expire_timestamp_millis(num)?,
                     _ => return Ok(SetInternalReturnValue::SyntaxError),
                 }
             }
@@ -1114,7 +1131,12 @@ impl StringCommands {
             // shared lock is enough here
             strings_db.put(user_key, value, &metadata, PutFlags::Override)?;
         }
-        Ok(SetInternalReturnValue::Success(return_value))
+
+        if flags.intersects(SetFlags::ReturnOldValue) {
+            Ok(SetInternalReturnValue::OldValue(return_value))
+        } else {
+            Ok(SetInternalReturnValue::Success)
+        }
     }
 
     /// Build the response buffer based on the result from `set_internal` output
@@ -1133,64 +1155,89 @@ impl StringCommands {
             SetInternalReturnValue::WrongType => {
                 builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
             }
-            SetInternalReturnValue::Success(Some(old_value)) => {
+            SetInternalReturnValue::OldValue(Some(old_value)) => {
                 builder.bulk_string(response_buffer, &old_value);
             }
-            SetInternalReturnValue::Success(None) => {
+            SetInternalReturnValue::OldValue(None) => {
+                builder.null_string(response_buffer);
+            }
+            SetInternalReturnValue::Success => {
                 builder.ok(response_buffer);
             }
         }
     }
 
-    fn setrange_internal(
-        _key: &BytesMut,
-        old_value: Option<&BytesMut>,
-        new_value: Option<&BytesMut>,
-        offset: &BytesMut,
+    /// The shared read path of GET, GETDEL, GETEX and GETSET.
+    ///
+    /// Read the string stored at `key` (under the proper lock), reply with its value
+    /// (or nil if it does not exist) and apply `post_action` on it. If `key` holds a
+    /// non string value, reply with `WRONGTYPE` and leave the key untouched
+    fn read_string_then(
+        client_state: Rc<ClientState>,
+        key: &BytesMut,
+        post_action: StringPostReadAction,
         response_buffer: &mut BytesMut,
-    ) -> Option<BytesMut> {
+    ) -> Result<(), SableError> {
         let builder = RespBuilderV2::default();
+        let db_id = client_state.database_id();
 
-        let Some(value) = new_value else {
-            builder.error_string(response_buffer, "ERR wrong number of arguments for command");
-            return None;
+        // a plain read can share the lock, anything else modifies the key
+        let _unused = match post_action {
+            StringPostReadAction::None => LockManager::lock_user_key_shared(key, db_id),
+            _ => LockManager::lock_user_key_exclusive(key, db_id),
         };
 
-        let offset = to_number!(offset, usize, response_buffer, None);
-
-        let mut new_value_mut = BytesMut::new();
-        let new_value = if let Some(old_value) = old_value {
-            // Copy the old value to our result
-            new_value_mut.extend_from_slice(old_value);
-            if offset + value.len() >= new_value_mut.len() {
-                // extend old_value
-                new_value_mut.resize(offset + value.len(), 0u8);
+        let strings_db = StringsDb::with_storage(client_state.database(), db_id);
+        let Some((value, mut metadata)) = strings_db.get(key)? else {
+            Telemetry::inc_db_miss();
+            builder.null_string(response_buffer);
+            if let StringPostReadAction::Replace(new_value) = post_action {
+                strings_db.put(
+                    key,
+                    new_value,
+                    &StringValueMetadata::new(),
+                    PutFlags::Override,
+                )?;
             }
-
-            new_value_mut[offset..].clone_from_slice(value);
-
-            // use the old Metadata + modified value
-            &new_value_mut
-        } else {
-            // new Metadata + new value
-            new_value_mut.resize(value.len() + offset, 0u8);
-            new_value_mut[offset..].clone_from_slice(value);
-            &new_value_mut
+            return Ok(());
         };
 
-        builder.number_u64(
-            response_buffer,
-            new_value.len().try_into().unwrap_or(u64::MAX),
-        );
+        check_value_type!(metadata, Encoding::VALUE_STRING, response_buffer);
+        Telemetry::inc_db_hit();
+        builder.bulk_string(response_buffer, &value);
 
-        Some(new_value.clone())
+        match post_action {
+            StringPostReadAction::None => {}
+            StringPostReadAction::Delete => strings_db.delete(key)?,
+            StringPostReadAction::Replace(new_value) => strings_db.put(
+                key,
+                new_value,
+                &StringValueMetadata::new(),
+                PutFlags::Override,
+            )?,
+            StringPostReadAction::Expire(expiry) => {
+                let expiration = metadata.expiration_mut();
+                match expiry {
+                    GetExExpiry::Seconds(num) => expiration.set_ttl_seconds(num)?,
+                    GetExExpiry::Millis(num) => expiration.set_ttl_millis(num)?,
+                    GetExExpiry::UnixTimeSeconds(num) => {
+                        expiration.set_expire_timestamp_seconds(num)?
+                    }
+                    GetExExpiry::UnixTimeMillis(num) => {
+                        expiration.set_expire_timestamp_millis(num)?
+                    }
+                    GetExExpiry::Persist => expiration.set_no_expiration()?,
+                }
+                strings_db.put(key, &value, &metadata, PutFlags::Override)?;
+            }
+        }
+        Ok(())
     }
 
     fn incr_by_internal<N: Num + Display + FromStr + NumAssignOps>(
         old_value: Option<&BytesMut>,
         incr_by: N,
         response_buffer: &mut BytesMut,
-        is_float: bool,
         parse_error: &'static str,
     ) -> Option<BytesMut> {
         let builder = RespBuilderV2::default();
@@ -1198,6 +1245,11 @@ impl StringCommands {
         // in case we won't find it, set it to 0
         let mut number: N = N::zero();
         if let Some(old_value) = old_value {
+            // an integer must be in the same canonical form that OBJECT ENCODING reports as `int`
+            if BytesMutUtils::parse_integer(old_value).is_none() {
+                builder.error_string(response_buffer, parse_error);
+                return None;
+            }
             let old_number = to_number_ex!(old_value, N, response_buffer, None, parse_error);
             number = old_number;
         }
@@ -1207,7 +1259,7 @@ impl StringCommands {
 
         // build the response buffer
         let number_as_bytes = BytesMutUtils::from(&number);
-        builder.number::<N>(response_buffer, number, is_float);
+        builder.number::<N>(response_buffer, number, false);
         Some(number_as_bytes)
     }
 }
@@ -1236,6 +1288,25 @@ mod test {
         (vec!["get", "set_key"], "$11\r\nvaluevalue2\r\n"),
         (vec!["append", "set_key_no_such_key", "value"], ":5\r\n"),
         (vec!["get", "set_key_no_such_key"], "$5\r\nvalue\r\n"),
+        // the TTL is kept
+        (vec!["set", "append_ttl_key", "a", "ex", "100"], "+OK\r\n"),
+        (vec!["append", "append_ttl_key", "bc"], ":3\r\n"),
+        (vec!["ttl", "append_ttl_key"], ":100\r\n"),
+        // the encoding follows the value
+        (vec!["append", "append_int_key", "12"], ":2\r\n"),
+        (vec!["object", "encoding", "append_int_key"], "$3\r\nint\r\n"),
+        (vec!["append", "append_int_key", "34"], ":4\r\n"),
+        (vec!["object", "encoding", "append_int_key"], "$3\r\nint\r\n"),
+        (vec!["incr", "append_int_key"], ":1235\r\n"),
+        (vec!["append", "append_int_key", "abc"], ":7\r\n"),
+        (vec!["object", "encoding", "append_int_key"], "$3\r\nraw\r\n"),
+        (vec!["incr", "append_int_key"], "-ERR value is not an integer or out of range\r\n"),
+        // leading zeros are not an integer
+        (vec!["append", "append_zero_key", "0"], ":1\r\n"),
+        (vec!["object", "encoding", "append_zero_key"], "$3\r\nint\r\n"),
+        (vec!["append", "append_zero_key", "7"], ":2\r\n"),
+        (vec!["object", "encoding", "append_zero_key"], "$3\r\nraw\r\n"),
+        (vec!["incr", "append_zero_key"], "-ERR value is not an integer or out of range\r\n"),
         ], "set_get_append"; "set get append")]
     #[test_case(vec![
         (vec!["getdel", "test_getdel_no_such_key"], "$-1\r\n"),
@@ -1243,6 +1314,21 @@ mod test {
         (vec!["getdel", "test_getdel_key"], "$5\r\nvalue\r\n"),
         (vec!["get", "test_getdel_key"], "$-1\r\n"),
         ], "getdel"; "getdel")]
+    #[test_case(vec![
+        (vec!["get", "shared_read_no_such_key"], "$-1\r\n"),
+        (vec!["getdel", "shared_read_no_such_key"], "$-1\r\n"),
+        (vec!["getex", "shared_read_no_such_key"], "$-1\r\n"),
+        (vec!["getex", "shared_read_no_such_key", "persist"], "$-1\r\n"),
+        (vec!["getset", "shared_read_no_such_key", "value"], "$-1\r\n"),
+        (vec!["lpush", "shared_read_list", "a"], ":1\r\n"),
+        (vec!["get", "shared_read_list"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["getdel", "shared_read_list"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["getex", "shared_read_list"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["getex", "shared_read_list", "ex", "10"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["getset", "shared_read_list", "value"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        // the list was not modified by any of the above
+        (vec!["llen", "shared_read_list"], ":1\r\n"),
+        ], "shared_read_path"; "shared read path")]
     #[test_case(vec![
         (vec!["decrby", "test_decr_by_counter", "10"], ":-10\r\n"),
         (vec!["decrby", "test_decr_by_counter", "5"], ":-15\r\n"),
@@ -1264,6 +1350,26 @@ mod test {
         (vec!["set", "getex_key", "value"], "+OK\r\n"),
         (vec!["getex", "getex_key", "ex", "3"], "$5\r\nvalue\r\n"),
         (vec!["ttl", "getex_key"], ":3\r\n"),
+        (vec!["getex", "getex_key", "persist"], "$5\r\nvalue\r\n"),
+        (vec!["ttl", "getex_key"], ":-1\r\n"),
+        (vec!["getex", "getex_key", "ex"], "-ERR syntax error\r\n"),
+        (vec!["getex", "getex_key", "ex", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["getex", "getex_key", "persist", "10"], "-ERR syntax error\r\n"),
+        (vec!["getex", "getex_key", "keepttl"], "-ERR syntax error\r\n"),
+        (vec!["getex", "getex_key", "ex", "0"], "-ERR invalid expire time in 'getex' command\r\n"),
+        (vec!["getex", "getex_key", "px", "-10"], "-ERR invalid expire time in 'getex' command\r\n"),
+        // without options, the TTL is left untouched
+        (vec!["getex", "getex_key", "px", "5000"], "$5\r\nvalue\r\n"),
+        (vec!["getex", "getex_key"], "$5\r\nvalue\r\n"),
+        (vec!["ttl", "getex_key"], ":5\r\n"),
+        (vec!["getex", "getex_key", "persist"], "$5\r\nvalue\r\n"),
+        (vec!["ttl", "getex_key"], ":-1\r\n"),
+        // a timestamp in the past expires the key, after its value is returned
+        (vec!["getex", "getex_key", "exat", "1"], "$5\r\nvalue\r\n"),
+        (vec!["get", "getex_key"], "$-1\r\n"),
+        (vec!["set", "getex_key", "value"], "+OK\r\n"),
+        (vec!["getex", "getex_key", "pxat", "1"], "$5\r\nvalue\r\n"),
+        (vec!["get", "getex_key"], "$-1\r\n"),
     ], "getex"; "getex")]
     #[test_case(vec![
         (vec!["set", "getrange_key", "value"], "+OK\r\n"),
@@ -1273,6 +1379,18 @@ mod test {
         (vec!["getrange", "getrange_key", "100000", "10000"], "$0\r\n\r\n"),
         (vec!["getrange", "getrange_key", "-2", "-1"], "$2\r\nue\r\n"),
         (vec!["getrange", "getrange_key", "-1000", "-1000"], "$1\r\nv\r\n"),
+        (vec!["getrange", "getrange_key", "-3", "-1"], "$3\r\nlue\r\n"),
+        (vec!["getrange", "getrange_key", "-100", "2"], "$3\r\nval\r\n"),
+        (vec!["getrange", "getrange_key", "1", "-100"], "$0\r\n\r\n"),
+        // inverted ranges
+        (vec!["getrange", "getrange_key", "3", "1"], "$0\r\n\r\n"),
+        (vec!["getrange", "getrange_key", "-1", "-3"], "$0\r\n\r\n"),
+        (vec!["getrange", "getrange_key", "5", "10"], "$0\r\n\r\n"),
+        (vec!["getrange", "getrange_no_such_key", "0", "-1"], "$0\r\n\r\n"),
+        (vec!["getrange", "getrange_no_such_key", "a", "-1"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["lpush", "getrange_list", "a"], ":1\r\n"),
+        (vec!["getrange", "getrange_list", "0", "-1"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["substr", "getrange_key", "-3", "-2"], "$2\r\nlu\r\n"),
     ], "getrange"; "getrange")]
     #[test_case(vec![
         (vec!["getset", "getset_no_such_key", "value"], "$-1\r\n"),
@@ -1282,8 +1400,24 @@ mod test {
         (vec!["getset", "getset_2nd_key"], "-ERR wrong number of arguments for 'getset' command\r\n"),
     ], "getset"; "getset")]
     #[test_case(vec![
-        (vec!["incrbyfloat", "incrbyfloat_no_such_key", "0.1"], ",0.1\r\n"),
-        (vec!["incrbyfloat", "incrbyfloat_no_such_key", "1.2"], ",1.3\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_no_such_key", "0.1"], "$3\r\n0.1\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_no_such_key", "1.2"], "$3\r\n1.3\r\n"),
+        (vec!["set", "incrbyfloat_key", "10.5"], "+OK\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "0.1"], "$4\r\n10.6\r\n"),
+        (vec!["set", "incrbyfloat_key", "3.0e3"], "+OK\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "200"], "$4\r\n3200\r\n"),
+        (vec!["get", "incrbyfloat_key"], "$4\r\n3200\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "-3200.5"], "$4\r\n-0.5\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "5.0e3"], "$6\r\n4999.5\r\n"),
+        (vec!["set", "incrbyfloat_key", "1"], "+OK\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "nan"], "-ERR value is not a valid float\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "inf"], "-ERR increment would produce NaN or Infinity\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "abc"], "-ERR value is not a valid float\r\n"),
+        (vec!["get", "incrbyfloat_key"], "$1\r\n1\r\n"),
+        // the TTL is kept
+        (vec!["set", "incrbyfloat_key", "1.5", "ex", "100"], "+OK\r\n"),
+        (vec!["incrbyfloat", "incrbyfloat_key", "1"], "$3\r\n2.5\r\n"),
+        (vec!["ttl", "incrbyfloat_key"], ":100\r\n"),
         (vec!["set", "incrbyfloat_string", "hello"], "+OK\r\n"),
         (vec!["incrbyfloat", "incrbyfloat_string", "9.9"], "-ERR value is not a valid float\r\n"),
         (vec!["incrbyfloat", "incrbyfloat_string"], "-ERR wrong number of arguments for 'incrbyfloat' command\r\n"),
@@ -1329,6 +1463,40 @@ mod test {
         (vec!["set", "set_key1", "value4", "PX", "3000"], "+OK\r\n"),
         (vec!["ttl", "set_key1"], ":3\r\n"),
     ], "set"; "set")]
+    #[test_case(vec![
+        (vec!["set", "opt_key", "v", "EX", "100", "KEEPTTL"], "-ERR syntax error\r\n"),
+        (vec!["set", "opt_key", "v", "KEEPTTL", "EX", "100"], "-ERR syntax error\r\n"),
+        (vec!["set", "opt_key", "v", "EX", "100", "PX", "100"], "-ERR syntax error\r\n"),
+        (vec!["set", "opt_key", "v", "EX"], "-ERR syntax error\r\n"),
+        (vec!["set", "opt_key", "v", "NX", "XX"], "-ERR syntax error\r\n"),
+        (vec!["set", "opt_key", "v1", "GET"], "$-1\r\n"),
+        (vec!["get", "opt_key"], "$2\r\nv1\r\n"),
+        (vec!["set", "opt_key", "v2", "EX", "100"], "+OK\r\n"),
+        (vec!["set", "opt_key", "v3", "KEEPTTL"], "+OK\r\n"),
+        (vec!["ttl", "opt_key"], ":100\r\n"),
+        (vec!["get", "opt_key"], "$2\r\nv3\r\n"),
+        (vec!["set", "opt_key", "v4"], "+OK\r\n"),
+        (vec!["ttl", "opt_key"], ":-1\r\n"),
+        (vec!["set", "opt_key", "v5", "NX", "GET"], "$2\r\nv4\r\n"),
+        (vec!["get", "opt_key"], "$2\r\nv4\r\n"),
+        (vec!["set", "opt_key", "v6", "XX", "GET"], "$2\r\nv4\r\n"),
+        (vec!["get", "opt_key"], "$2\r\nv6\r\n"),
+        (vec!["set", "opt_key2", "v", "NX", "GET"], "$-1\r\n"),
+        (vec!["get", "opt_key2"], "$1\r\nv\r\n"),
+        (vec!["set", "opt_key3", "v", "XX", "GET"], "$-1\r\n"),
+        (vec!["get", "opt_key3"], "$-1\r\n"),
+        (vec!["set", "opt_key4", "v", "KEEPTTL", "GET"], "$-1\r\n"),
+        (vec!["ttl", "opt_key4"], ":-1\r\n"),
+        (vec!["set", "opt_key", "v", "EXAT", "4102444800"], "+OK\r\n"),
+        (vec!["set", "opt_key", "v", "PXAT", "4102444800000", "GET"], "$1\r\nv\r\n"),
+        (vec!["set", "opt_key", "v", "PXAT", "1000"], "+OK\r\n"),
+        (vec!["get", "opt_key"], "$-1\r\n"),
+        (vec!["set", "opt_key", "v", "IDLE", "10"], "+OK\r\n"),
+        (vec!["set", "opt_key", "v", "IDLE", "abc"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["lpush", "opt_list", "a"], ":1\r\n"),
+        (vec!["set", "opt_list", "v", "GET"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["set", "opt_list", "v", "NX", "GET"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+    ], "set_options"; "set_options")]
     #[test_case(vec![
         (vec!["setex", "setex_key1", "value"], "-ERR wrong number of arguments for 'setex' command\r\n"),
         (vec!["setex", "setex_key1", "42", "value"], "+OK\r\n"),
@@ -1349,6 +1517,28 @@ mod test {
         // Check that padding string works
         (vec!["setrange", "key2", "5", " world"], ":11\r\n"),
         (vec!["get", "key2"], "$11\r\n\0\0\0\0\0 world\r\n"),
+        (vec!["setrange", "key3", "5", "abc"], ":8\r\n"),
+        (vec!["strlen", "key3"], ":8\r\n"),
+        (vec!["get", "key3"], "$8\r\n\0\0\0\0\0abc\r\n"),
+        // overwrite the middle of the string
+        (vec!["setrange", "key1", "0", "J"], ":11\r\n"),
+        (vec!["get", "key1"], "$11\r\nJello world\r\n"),
+        (vec!["setrange", "key1", "6", "W"], ":11\r\n"),
+        (vec!["get", "key1"], "$11\r\nJello World\r\n"),
+        // an empty value does not create the key
+        (vec!["setrange", "key4", "5", ""], ":0\r\n"),
+        (vec!["exists", "key4"], ":0\r\n"),
+        (vec!["setrange", "key1", "100", ""], ":11\r\n"),
+        (vec!["setrange", "key1", "-1", "a"], "-ERR offset is out of range\r\n"),
+        (vec!["setrange", "key1", "abc", "a"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["setrange", "key1", "536870911", "ab"], "-ERR string exceeds maximum allowed size (proto-max-bulk-len)\r\n"),
+        (vec!["get", "key1"], "$11\r\nJello World\r\n"),
+        // the TTL is kept
+        (vec!["set", "key5", "hello", "ex", "100"], "+OK\r\n"),
+        (vec!["setrange", "key5", "0", "J"], ":5\r\n"),
+        (vec!["ttl", "key5"], ":100\r\n"),
+        (vec!["lpush", "list", "a"], ":1\r\n"),
+        (vec!["setrange", "list", "0", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
     ], "setrange"; "setrange")]
     #[test_case(vec![
         (vec!["strlen", "key1"], ":0\r\n"), // key does not exist
@@ -1518,4 +1708,49 @@ mod test {
             assert_eq!(sink3.read_all().await.as_str(), "$-1\r\n");
         });
     }
+
+    #[test]
+    fn test_expired_key_on_replica() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store.clone(), None);
+
+            let cmd = Rc::new(RedisCommand::for_test(vec![
+                "psetex",
+                "test_expired_key_on_replica",
+                "100",
+                "value",
+            ]));
+            let mut sink =
+                crate::tests::ResponseSink::with_name("test_expired_key_on_replica").await;
+            let _ = Client::handle_command(client.inner(), cmd, &mut sink.fp).await;
+            assert_eq!(sink.read_all().await.as_str(), "+OK\r\n");
+
+            // from now on, the record is only removed by the primary
+            client.inner().server_inner_state().set_replica();
+            store.set_replica_mode(true);
+
+            // sleep until the key expires, the primary's DEL did not arrive yet
+            std::thread::sleep(std::time::Duration::from_millis(150));
+
+            for (args, expected_value) in [
+                (vec!["get", "test_expired_key_on_replica"], "$-1\r\n"),
+                (vec!["ttl", "test_expired_key_on_replica"], ":-2\r\n"),
+            ] {
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_expired_key_on_replica").await;
+                let _ = Client::handle_command(client.inner(), cmd, &mut sink.fp).await;
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+
+            // the record itself is still there
+            let internal_key = crate::PrimaryKeyMetadata::new_primary_key(
+                &BytesMut::from("test_expired_key_on_replica"),
+                client.inner().database_id(),
+            );
+            assert!(store.get(&internal_key).unwrap().is_some());
+        });
+    }
 }
@@ -0,0 +1,265 @@
+#[allow(unused_imports)]
+use crate::{
+    client::ClientState,
+    commands::{ClientNextAction, ErrorStrings, HandleCommandResult},
+    Client, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct TransactionCommands {}
+
+impl TransactionCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Multi => {
+                Self::multi(client_state, &mut response_buffer);
+            }
+            RedisCommandName::Exec => {
+                Self::exec(client_state, &mut response_buffer).await?;
+            }
+            RedisCommandName::Discard => {
+                Self::discard(client_state, &mut response_buffer);
+            }
+            RedisCommandName::Watch => {
+                Self::watch(client_state, command, &mut response_buffer);
+            }
+            RedisCommandName::Unwatch => {
+                client_state.unwatch_all();
+                RespBuilderV2::default().ok(&mut response_buffer);
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non transaction command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Marks the start of a transaction block. Subsequent commands will be queued for atomic
+    /// execution using `EXEC`
+    fn multi(client_state: Rc<ClientState>, response_buffer: &mut BytesMut) {
+        let builder = RespBuilderV2::default();
+        if client_state.in_transaction() {
+            builder.error_string(response_buffer, ErrorStrings::MULTI_NESTED);
+            return;
+        }
+        client_state.begin_transaction();
+        builder.ok(response_buffer);
+    }
+
+    /// Flushes all previously queued commands in a transaction
+    fn discard(client_state: Rc<ClientState>, response_buffer: &mut BytesMut) {
+        let builder = RespBuilderV2::default();
+        client_state.unwatch_all();
+        match client_state.take_transaction() {
+            Some(_) => builder.ok(response_buffer),
+            None => builder.error_string(response_buffer, ErrorStrings::DISCARD_WITHOUT_MULTI),
+        }
+    }
+
+    /// Marks the given keys to be watched for conditional execution of a transaction. If any of
+    /// them is modified (or created) before `EXEC`, the transaction is aborted
+    fn watch(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) {
+        let builder = RespBuilderV2::default();
+        if client_state.in_transaction() {
+            builder.error_string(response_buffer, ErrorStrings::WATCH_INSIDE_MULTI);
+            return;
+        }
+        for user_key in command.args_vec().iter().skip(1) {
+            client_state.watch_key(user_key);
+        }
+        builder.ok(response_buffer);
+    }
+
+    /// Executes all previously queued commands in a transaction. A command that fails at
+    /// runtime does not stop the transaction: its error is placed in the reply array and the
+    /// remaining commands are still executed (there is no rollback). If any of the watched keys
+    /// was modified, nothing is executed and a null array is returned
+    async fn exec(
+        client_state: Rc<ClientState>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        let builder = RespBuilderV2::default();
+        let Some(txn) = client_state.take_transaction() else {
+            builder.error_string(response_buffer, ErrorStrings::EXEC_WITHOUT_MULTI);
+            return Ok(());
+        };
+
+        let watched_keys_modified = client_state.watched_keys_modified();
+        client_state.unwatch_all();
+        if txn.aborted {
+            builder.error_string(response_buffer, ErrorStrings::EXEC_ABORT);
+            return Ok(());
+        }
+
+        if watched_keys_modified {
+            builder.null_array(response_buffer);
+            return Ok(());
+        }
+
+        builder.add_array_len(response_buffer, txn.commands.len());
+        for command in txn.commands {
+            let reply = Client::handle_command_captured(client_state.clone(), command).await;
+            response_buffer.extend_from_slice(&reply);
+        }
+        Ok(())
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ServerState;
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["multi"], "+OK\r\n"),
+        (vec!["set", "k1", "v1"], "+QUEUED\r\n"),
+        (vec!["incr", "str_key"], "+QUEUED\r\n"),
+        (vec!["set", "k2", "v2"], "+QUEUED\r\n"),
+        // the failing INCR does not stop the other commands
+        (vec!["exec"], "*3\r\n+OK\r\n-ERR value is not an integer or out of range\r\n+OK\r\n"),
+        (vec!["get", "k1"], "$2\r\nv1\r\n"),
+        (vec!["get", "k2"], "$2\r\nv2\r\n"),
+    ], "test_exec_runtime_error"; "test_exec_runtime_error")]
+    #[test_case(vec![
+        (vec!["multi"], "+OK\r\n"),
+        (vec!["set", "k1", "v1"], "+QUEUED\r\n"),
+        (vec!["get"], "-ERR wrong number of arguments for 'get' command\r\n"),
+        (vec!["set", "k2", "v2"], "+QUEUED\r\n"),
+        (vec!["exec"], "-EXECABORT Transaction discarded because of previous errors.\r\n"),
+        // nothing was executed
+        (vec!["get", "k1"], "$-1\r\n"),
+        (vec!["get", "k2"], "$-1\r\n"),
+    ], "test_exec_queuing_error"; "test_exec_queuing_error")]
+    #[test_case(vec![
+        (vec!["exec"], "-ERR EXEC without MULTI\r\n"),
+        (vec!["discard"], "-ERR DISCARD without MULTI\r\n"),
+        (vec!["multi"], "+OK\r\n"),
+        (vec!["multi"], "-ERR MULTI calls can not be nested\r\n"),
+        (vec!["set", "k1", "v1"], "+QUEUED\r\n"),
+        (vec!["discard"], "+OK\r\n"),
+        (vec!["get", "k1"], "$-1\r\n"),
+        (vec!["multi"], "+OK\r\n"),
+        (vec!["exec"], "*0\r\n"),
+        (vec!["multi"], "+OK\r\n"),
+        (vec!["set", "k1", "v1", "EX", "100"], "+QUEUED\r\n"),
+        (vec!["get", "k1"], "+QUEUED\r\n"),
+        (vec!["exec"], "*2\r\n+OK\r\n$2\r\nv1\r\n"),
+    ], "test_multi_discard"; "test_multi_discard")]
+    fn test_transaction_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[test_case(vec![
+        (0, vec!["set", "k1", "v1"], "+OK\r\n"),
+        (0, vec!["watch", "k1"], "+OK\r\n"),
+        (1, vec!["set", "k1", "v2"], "+OK\r\n"),
+        (0, vec!["multi"], "+OK\r\n"),
+        (0, vec!["set", "k1", "v3"], "+QUEUED\r\n"),
+        (0, vec!["exec"], "*-1\r\n"),
+        (0, vec!["get", "k1"], "$2\r\nv2\r\n"),
+        // EXEC cleared the watch set
+        (1, vec!["set", "k1", "v4"], "+OK\r\n"),
+        (0, vec!["multi"], "+OK\r\n"),
+        (0, vec!["set", "k1", "v3"], "+QUEUED\r\n"),
+        (0, vec!["exec"], "*1\r\n+OK\r\n"),
+    ], "test_watch_modified_key"; "test_watch_modified_key")]
+    #[test_case(vec![
+        (0, vec!["watch", "list"], "+OK\r\n"),
+        (1, vec!["lpush", "list", "a"], ":1\r\n"),
+        (0, vec!["multi"], "+OK\r\n"),
+        (0, vec!["lpush", "list", "b"], "+QUEUED\r\n"),
+        (0, vec!["exec"], "*-1\r\n"),
+        (0, vec!["llen", "list"], ":1\r\n"),
+    ], "test_watch_created_key"; "test_watch_created_key")]
+    #[test_case(vec![
+        (0, vec!["watch", "k1", "k2"], "+OK\r\n"),
+        (0, vec!["unwatch"], "+OK\r\n"),
+        (1, vec!["set", "k2", "v2"], "+OK\r\n"),
+        (0, vec!["multi"], "+OK\r\n"),
+        (0, vec!["watch", "k1"], "-ERR WATCH inside MULTI is not allowed\r\n"),
+        (0, vec!["set", "k1", "v1"], "+QUEUED\r\n"),
+        (0, vec!["exec"], "*1\r\n+OK\r\n"),
+        // a watch on another database is not affected
+        (0, vec!["watch", "k1"], "+OK\r\n"),
+        (1, vec!["select", "1"], "+OK\r\n"),
+        (1, vec!["set", "k1", "v2"], "+OK\r\n"),
+        (0, vec!["multi"], "+OK\r\n"),
+        (0, vec!["get", "k1"], "+QUEUED\r\n"),
+        (0, vec!["exec"], "*1\r\n$2\r\nv1\r\n"),
+    ], "test_unwatch"; "test_unwatch")]
+    fn test_watch_commands(
+        args: Vec<(usize, Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.watched_keys());
+            let clients = [
+                Client::new(server_state.clone(), store.clone(), None),
+                Client::new(server_state.clone(), store.clone(), None),
+            ];
+
+            for (client_index, args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(clients[client_index].inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+}
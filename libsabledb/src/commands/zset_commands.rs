@@ -0,0 +1,324 @@
+#[allow(unused_imports)]
+use crate::{
+    check_args_count, check_value_type,
+    client::ClientState,
+    command_arg_at,
+    commands::{ErrorStrings, HandleCommandResult},
+    storage::{
+        ZAddFlags, ZSetAddResult, ZSetDb, ZSetDeleteResult, ZSetRangeResult, ZSetScoreResult,
+    },
+    BytesMutUtils, LockManager, RedisCommand, RedisCommandName, RespBuilderV2, SableError,
+};
+
+use bytes::BytesMut;
+use std::rc::Rc;
+use tokio::io::AsyncWriteExt;
+
+pub struct ZSetCommands {}
+
+impl ZSetCommands {
+    pub async fn handle_command(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        _tx: &mut (impl AsyncWriteExt + std::marker::Unpin),
+    ) -> Result<HandleCommandResult, SableError> {
+        let mut response_buffer = BytesMut::with_capacity(256);
+        match command.metadata().name() {
+            RedisCommandName::Zadd => {
+                Self::zadd(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Zscore => {
+                Self::zscore(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Zrange => {
+                Self::zrange(client_state, command, &mut response_buffer).await?;
+            }
+            RedisCommandName::Zrem => {
+                Self::zrem(client_state, command, &mut response_buffer).await?;
+            }
+            _ => {
+                return Err(SableError::InvalidArgument(format!(
+                    "Non sorted set command {}",
+                    command.main_command()
+                )));
+            }
+        }
+        Ok(HandleCommandResult::ResponseBufferUpdated(response_buffer))
+    }
+
+    /// Adds all the specified members with the specified scores to the sorted set stored at key.
+    /// `ZADD key [NX | XX] [GT | LT] [CH] score member [score member ...]`
+    async fn zadd(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        let mut iter = command.args_vec().iter().skip(2).peekable();
+
+        // parse the options
+        let mut flags = ZAddFlags::None;
+        while let Some(arg) = iter.peek() {
+            let option = BytesMutUtils::to_string(arg).to_lowercase();
+            match option.as_str() {
+                "nx" => flags |= ZAddFlags::Nx,
+                "xx" => flags |= ZAddFlags::Xx,
+                "gt" => flags |= ZAddFlags::Gt,
+                "lt" => flags |= ZAddFlags::Lt,
+                "ch" => flags |= ZAddFlags::Ch,
+                _ => break,
+            }
+            iter.next();
+        }
+
+        if flags.contains(ZAddFlags::Nx | ZAddFlags::Xx) {
+            builder.error_string(response_buffer, ErrorStrings::ZADD_XX_AND_NX);
+            return Ok(());
+        }
+
+        if flags.contains(ZAddFlags::Gt | ZAddFlags::Lt)
+            || (flags.contains(ZAddFlags::Nx) && flags.intersects(ZAddFlags::Gt | ZAddFlags::Lt))
+        {
+            builder.error_string(response_buffer, ErrorStrings::ZADD_GT_LT_AND_NX);
+            return Ok(());
+        }
+
+        // parse the score/member pairs
+        let mut members = Vec::<(f64, &BytesMut)>::with_capacity(command.arg_count() / 2);
+        loop {
+            match (iter.next(), iter.next()) {
+                (Some(score), Some(member)) => {
+                    let Some(score) = BytesMutUtils::parse::<f64>(score).filter(|s| !s.is_nan())
+                    else {
+                        builder.error_string(response_buffer, ErrorStrings::VALUE_NOT_VALID_FLOAT);
+                        return Ok(());
+                    };
+                    members.push((score, member));
+                }
+                (None, None) => break,
+                (_, _) => {
+                    builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                    return Ok(());
+                }
+            }
+        }
+
+        if members.is_empty() {
+            builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+            return Ok(());
+        }
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let zset_db = ZSetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match zset_db.add(key, &members, flags)? {
+            ZSetAddResult::Some(count) => builder.number_usize(response_buffer, count),
+            ZSetAddResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the score of member in the sorted set at key
+    async fn zscore(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+        let member = command_arg_at!(command, 2);
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let zset_db = ZSetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match zset_db.score(key, member)? {
+            ZSetScoreResult::Some(score) => {
+                builder.bulk_string(response_buffer, &BytesMutUtils::from::<f64>(&score))
+            }
+            ZSetScoreResult::NotFound => builder.null_string(response_buffer),
+            ZSetScoreResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the specified range of elements in the sorted set stored at key.
+    /// `ZRANGE key start stop [WITHSCORES]`
+    async fn zrange(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 4, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+        let start = command_arg_at!(command, 2);
+        let stop = command_arg_at!(command, 3);
+
+        let (Some(start), Some(stop)) = (
+            BytesMutUtils::parse::<i64>(start),
+            BytesMutUtils::parse::<i64>(stop),
+        ) else {
+            builder.error_string(
+                response_buffer,
+                ErrorStrings::VALUE_NOT_AN_INT_OR_OUT_OF_RANGE,
+            );
+            return Ok(());
+        };
+
+        let with_scores = match command.arg_count() {
+            4 => false,
+            5 if BytesMutUtils::to_string(command_arg_at!(command, 4)).to_lowercase()
+                == "withscores" =>
+            {
+                true
+            }
+            _ => {
+                builder.error_string(response_buffer, ErrorStrings::SYNTAX_ERROR);
+                return Ok(());
+            }
+        };
+
+        let _unused = LockManager::lock_user_key_shared(key, client_state.database_id());
+        let zset_db = ZSetDb::with_storage(client_state.database(), client_state.database_id());
+
+        let items = match zset_db.range_by_rank(key, start, stop)? {
+            ZSetRangeResult::Some(items) => items,
+            ZSetRangeResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+        };
+
+        if with_scores {
+            builder.add_array_len(response_buffer, items.len() * 2);
+        } else {
+            builder.add_array_len(response_buffer, items.len());
+        }
+        for (member, score) in &items {
+            builder.add_bulk_string(response_buffer, member);
+            if with_scores {
+                builder.add_bulk_string(response_buffer, &BytesMutUtils::from::<f64>(score));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the specified members from the sorted set stored at key
+    async fn zrem(
+        client_state: Rc<ClientState>,
+        command: Rc<RedisCommand>,
+        response_buffer: &mut BytesMut,
+    ) -> Result<(), SableError> {
+        check_args_count!(command, 3, response_buffer);
+        let builder = RespBuilderV2::default();
+        let key = command_arg_at!(command, 1);
+
+        // zrem key <member> [<member>..]
+        let members: Vec<&BytesMut> = command.args_vec().iter().skip(2).collect();
+
+        // Multiple db calls: exclusive lock
+        let _unused = LockManager::lock_user_key_exclusive(key, client_state.database_id());
+        let zset_db = ZSetDb::with_storage(client_state.database(), client_state.database_id());
+
+        match zset_db.delete(key, &members)? {
+            ZSetDeleteResult::Some(count) => builder.number_usize(response_buffer, count),
+            ZSetDeleteResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE)
+            }
+        }
+        Ok(())
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, ServerState};
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use test_case::test_case;
+
+    #[test_case(vec![
+        (vec!["zadd", "myzset", "1"], "-ERR wrong number of arguments for 'zadd' command\r\n"),
+        (vec!["zadd", "myzset", "1", "a", "2"], "-ERR syntax error\r\n"),
+        (vec!["zadd", "myzset", "nx", "xx", "1", "a"], "-ERR XX and NX options at the same time are not compatible\r\n"),
+        (vec!["zadd", "myzset", "gt", "lt", "1", "a"], "-ERR GT, LT, and/or NX options at the same time are not compatible\r\n"),
+        (vec!["zadd", "myzset", "nx", "gt", "1", "a"], "-ERR GT, LT, and/or NX options at the same time are not compatible\r\n"),
+        (vec!["zadd", "myzset", "abc", "a"], "-ERR value is not a valid float\r\n"),
+        (vec!["zadd", "myzset", "nan", "a"], "-ERR value is not a valid float\r\n"),
+        (vec!["zadd", "myzset", "1", "a", "2", "b", "1", "a"], ":2\r\n"),
+        (vec!["zadd", "myzset", "xx", "1", "c"], ":0\r\n"),
+        (vec!["zadd", "myzset", "nx", "ch", "5", "a", "3", "c"], ":1\r\n"),
+        (vec!["zadd", "myzset", "gt", "ch", "0", "a", "4", "b"], ":1\r\n"),
+        (vec!["zadd", "myzset", "lt", "ch", "0", "a"], ":1\r\n"),
+        (vec!["zscore", "myzset", "a"], "$1\r\n0\r\n"),
+        (vec!["zscore", "myzset", "b"], "$1\r\n4\r\n"),
+        (vec!["zscore", "myzset", "nosuchmember"], "$-1\r\n"),
+        (vec!["zscore", "nosuchzset", "a"], "$-1\r\n"),
+        (vec!["set", "str_key", "value"], "+OK\r\n"),
+        (vec!["zadd", "str_key", "1", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["zscore", "str_key", "a"], "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"),
+        (vec!["type", "myzset"], "+zset\r\n"),
+    ], "test_zadd"; "test_zadd")]
+    #[test_case(vec![
+        (vec!["zadd", "myzset", "1", "b", "-inf", "e", "1", "a", "+inf", "d", "-1.5", "c"], ":5\r\n"),
+        (vec!["zrange", "myzset", "0", "-1"], "*5\r\n$1\r\ne\r\n$1\r\nc\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nd\r\n"),
+        (vec!["zrange", "myzset", "1", "2", "withscores"], "*4\r\n$1\r\nc\r\n$4\r\n-1.5\r\n$1\r\na\r\n$1\r\n1\r\n"),
+        (vec!["zrange", "myzset", "-2", "100", "WITHSCORES"], "*4\r\n$1\r\nb\r\n$1\r\n1\r\n$1\r\nd\r\n$3\r\ninf\r\n"),
+        (vec!["zrange", "myzset", "3", "1"], "*0\r\n"),
+        (vec!["zrange", "myzset", "10", "20"], "*0\r\n"),
+        (vec!["zrange", "nosuchzset", "0", "-1"], "*0\r\n"),
+        (vec!["zrange", "myzset", "a", "1"], "-ERR value is not an integer or out of range\r\n"),
+        (vec!["zrange", "myzset", "0", "1", "withsomething"], "-ERR syntax error\r\n"),
+    ], "test_zrange"; "test_zrange")]
+    #[test_case(vec![
+        (vec!["zadd", "myzset", "1", "a", "2", "b", "3", "c"], ":3\r\n"),
+        (vec!["zrem", "myzset"], "-ERR wrong number of arguments for 'zrem' command\r\n"),
+        (vec!["zrem", "myzset", "a", "a", "nosuchmember"], ":1\r\n"),
+        (vec!["zrange", "myzset", "0", "-1"], "*2\r\n$1\r\nb\r\n$1\r\nc\r\n"),
+        (vec!["zrem", "nosuchzset", "a"], ":0\r\n"),
+        (vec!["zrem", "myzset", "b", "c"], ":2\r\n"),
+        // removing the last member removes the key
+        (vec!["exists", "myzset"], ":0\r\n"),
+    ], "test_zrem"; "test_zrem")]
+    fn test_zset_commands(
+        args: Vec<(Vec<&'static str>, &'static str)>,
+        test_name: &str,
+    ) -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let client = Client::new(Arc::<ServerState>::default(), store, None);
+
+            for (args, expected_value) in args {
+                let mut sink = crate::tests::ResponseSink::with_name(test_name).await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                if let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                {
+                    assert_eq!(sink.read_all().await.as_str(), expected_value);
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use crate::storage::{StorageIterator, WriteEvent, WriteEventType, WriteObserver};
+use crate::{PrimaryKeyMetadata, SableError, StorageAdapter, U8ArrayReader};
+use bytes::BytesMut;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/// The number of keys of a database and how many of them have a TTL
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyCounts {
+    pub keys: u64,
+    pub expires: u64,
+}
+
+/// The counter of a single database
+enum DbCounts {
+    /// The number of keys is known and maintained by the writes
+    Known(KeyCounts),
+    /// The keys are being counted. The user keys written meanwhile are recorded with their
+    /// state after the write (whether the key exists, whether it has a TTL)
+    Counting(HashMap<BytesMut, (bool, bool)>),
+}
+
+/// The number of keys per database, used by `DBSIZE` and by the `keyspace` section of `INFO`.
+///
+/// RocksDB can not count the records of a prefix cheaply, so the counter is registered as a
+/// `WriteObserver` on the storage: a put of a primary record that did not exist adds a key and
+/// a delete of a primary record that existed removes one. Item records (hash fields, list
+/// items etc) are not keys and are ignored, so a hash is counted once no matter how many fields
+/// it has. The keys with a TTL are counted the same way, from the TTL of the primary record
+/// before and after the write (`EXPIRE`, `PERSIST`, `SET ... EX` etc).
+///
+/// A database whose count is not known (after a restart, or after the storage content was
+/// replaced) is "dirty": its keys are counted by iterating the database the next time its size
+/// is requested, and the counter is maintained from there
+#[derive(Default)]
+pub struct KeyCounter {
+    /// The counter per database ID. A database with no entry is dirty
+    counts: Mutex<HashMap<u16, DbCounts>>,
+}
+
+impl KeyCounter {
+    /// Return the number of keys in database `db_id`, counting them if the database is dirty
+    pub fn dbsize(&self, store: &StorageAdapter, db_id: u16) -> Result<u64, SableError> {
+        Ok(self.key_counts(store, db_id)?.keys)
+    }
+
+    /// Return the number of keys and the number of keys with a TTL in database `db_id`,
+    /// counting them if the database is dirty.
+    ///
+    /// The keys are counted without holding the lock of the counter, so the writes (of all the
+    /// databases) are not blocked meanwhile. The count iterates a snapshot of the database: the
+    /// keys written during the count are recorded and, once the count is done, their state in
+    /// the snapshot is replaced by their state after their last write
+    pub fn key_counts(&self, store: &StorageAdapter, db_id: u16) -> Result<KeyCounts, SableError> {
+        {
+            let mut counts = self.counts.lock().expect("poisoned mutex");
+            match counts.get(&db_id) {
+                Some(DbCounts::Known(db_counts)) => return Ok(*db_counts),
+                // another count is running, it shares the recorded writes with this one
+                Some(DbCounts::Counting(_)) => {}
+                None => {
+                    counts.insert(db_id, DbCounts::Counting(HashMap::new()));
+                }
+            }
+        }
+
+        // the snapshot is taken after the writes are recorded, so no write is missed
+        let prefix = PrimaryKeyMetadata::primary_key_prefix(db_id);
+        let StorageIterator::RocksDb(mut rocksdb_iter) =
+            store.create_iterator(Rc::new(prefix.clone()))?;
+        let mut db_counts = KeyCounts::default();
+        while rocksdb_iter.valid() {
+            let (Some(key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value()) else {
+                break;
+            };
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            db_counts.keys = db_counts.keys.saturating_add(1);
+            if WriteEvent::value_has_ttl(value) {
+                db_counts.expires = db_counts.expires.saturating_add(1);
+            }
+            rocksdb_iter.next();
+        }
+
+        let mut counts = self.counts.lock().expect("poisoned mutex");
+        let written_keys = match counts.get(&db_id) {
+            // another count completed first, or the database was flushed meanwhile
+            Some(DbCounts::Known(known)) => return Ok(*known),
+            Some(DbCounts::Counting(written_keys)) => written_keys,
+            // the database was marked dirty meanwhile: the count is not kept
+            None => return Ok(db_counts),
+        };
+
+        // reconcile the keys written during the count (looked up in the snapshot)
+        for (user_key, (exists, has_ttl)) in written_keys {
+            let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, db_id);
+            rocksdb_iter.seek(&internal_key);
+            let in_snapshot = match (rocksdb_iter.key(), rocksdb_iter.value()) {
+                (Some(key), Some(value)) if key == internal_key.as_ref() => {
+                    Some(WriteEvent::value_has_ttl(value))
+                }
+                _ => None,
+            };
+            if let Some(had_ttl) = in_snapshot {
+                db_counts.keys = db_counts.keys.saturating_sub(1);
+                if had_ttl {
+                    db_counts.expires = db_counts.expires.saturating_sub(1);
+                }
+            }
+            if *exists {
+                db_counts.keys = db_counts.keys.saturating_add(1);
+                if *has_ttl {
+                    db_counts.expires = db_counts.expires.saturating_add(1);
+                }
+            }
+        }
+        counts.insert(db_id, DbCounts::Known(db_counts));
+        Ok(db_counts)
+    }
+
+    /// Forget the number of keys of `db_id`, it is counted again the next time it is requested
+    pub fn mark_dirty(&self, db_id: u16) {
+        self.counts.lock().expect("poisoned mutex").remove(&db_id);
+    }
+
+    /// Mark all the databases as dirty
+    pub fn mark_all_dirty(&self) {
+        self.counts.lock().expect("poisoned mutex").clear();
+    }
+}
+
+impl WriteObserver for KeyCounter {
+    fn on_write(&self, event: &WriteEvent) {
+        if event.keys.is_empty() {
+            return;
+        }
+
+        let mut counts = self.counts.lock().expect("poisoned mutex");
+        for key in &event.keys {
+            // dirty databases are counted from scratch when needed
+            let db_counts = match counts.get_mut(&key.db_id) {
+                Some(DbCounts::Known(db_counts)) => db_counts,
+                Some(DbCounts::Counting(written_keys)) => {
+                    let exists = key.event_type == WriteEventType::Put;
+                    written_keys.insert(key.user_key.clone(), (exists, key.has_ttl));
+                    continue;
+                }
+                None => continue,
+            };
+            match (key.event_type, key.existed) {
+                (WriteEventType::Put, false) => db_counts.keys = db_counts.keys.saturating_add(1),
+                (WriteEventType::Delete, true) => db_counts.keys = db_counts.keys.saturating_sub(1),
+                _ => {}
+            }
+            match (key.existed && key.had_ttl, key.has_ttl) {
+                (false, true) => db_counts.expires = db_counts.expires.saturating_add(1),
+                (true, false) => db_counts.expires = db_counts.expires.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    fn on_reset(&self) {
+        self.mark_all_dirty();
+    }
+
+    fn on_range_deleted(&self, prefix: &[u8]) {
+        // only primary records are keys
+        if prefix.first() != Some(&PrimaryKeyMetadata::KEY_PRIMARY) {
+            return;
+        }
+
+        let prefix_len = PrimaryKeyMetadata::primary_key_prefix(0).len();
+        let mut reader = U8ArrayReader::with_buffer(&prefix[1..]);
+        match reader.read_u16() {
+            // the whole database was deleted
+            Some(db_id) if prefix.len() == prefix_len => {
+                self.counts
+                    .lock()
+                    .expect("poisoned mutex")
+                    .insert(db_id, DbCounts::Known(KeyCounts::default()));
+            }
+            // some of the keys of the database were deleted
+            Some(db_id) => self.mark_dirty(db_id),
+            None => self.mark_all_dirty(),
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BatchUpdate, GenericDb, HashDb, PutFlags, StringsDb};
+    use crate::{CommonValueMetadata, PrimaryKeyMetadata, StringValueMetadata, U8ArrayBuilder};
+    use bytes::BytesMut;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_key_counter() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let mut value = BytesMut::new();
+        CommonValueMetadata::default().to_bytes(&mut U8ArrayBuilder::with_buffer(&mut value));
+        value.extend_from_slice(b"value");
+        let key1 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key1"), 0);
+        let key2 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key2"), 0);
+
+        // keys written before the counter was registered are found by the recount
+        store.put(&key1, &value, PutFlags::Override)?;
+        let counter = Arc::new(KeyCounter::default());
+        store.add_write_observer(counter.clone());
+        assert_eq!(counter.dbsize(&store, 0)?, 1);
+        assert_eq!(counter.dbsize(&store, 1)?, 0);
+
+        // overriding a key does not add a key, deleting a missing key does not remove one
+        store.put(&key1, &value, PutFlags::Override)?;
+        store.put(&key2, &value, PutFlags::Override)?;
+        store.delete(&PrimaryKeyMetadata::new_primary_key(
+            &BytesMut::from("no_such_key"),
+            0,
+        ))?;
+        assert_eq!(counter.dbsize(&store, 0)?, 2);
+
+        // a key deleted and written again by the same batch is still a single key
+        let mut batch = BatchUpdate::default();
+        batch.delete(key1.clone());
+        batch.put(key1.clone(), value.clone());
+        batch.put(key1.clone(), value.clone());
+        store.apply_batch(&batch)?;
+        assert_eq!(counter.dbsize(&store, 0)?, 2);
+
+        store.delete(&key2)?;
+        assert_eq!(counter.dbsize(&store, 0)?, 1);
+
+        // a dirty database is counted again
+        counter.mark_all_dirty();
+        assert_eq!(counter.dbsize(&store, 0)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_counter_expires() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let mut value = BytesMut::new();
+        CommonValueMetadata::default().to_bytes(&mut U8ArrayBuilder::with_buffer(&mut value));
+        let mut volatile_md = CommonValueMetadata::default();
+        volatile_md.expiration_mut().set_ttl_seconds(100)?;
+        let mut volatile_value = BytesMut::new();
+        volatile_md.to_bytes(&mut U8ArrayBuilder::with_buffer(&mut volatile_value));
+        let key1 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key1"), 0);
+        let key2 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key2"), 0);
+
+        // volatile keys written before the counter was registered are found by the recount
+        store.put(&key1, &volatile_value, PutFlags::Override)?;
+        let counter = Arc::new(KeyCounter::default());
+        store.add_write_observer(counter.clone());
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 1,
+                expires: 1
+            }
+        );
+
+        // a new volatile key, then the TTL is updated (still a single volatile key)
+        store.put(&key2, &volatile_value, PutFlags::Override)?;
+        store.put(&key2, &volatile_value, PutFlags::Override)?;
+        assert_eq!(counter.key_counts(&store, 0)?.expires, 2);
+
+        // the TTL is removed (`PERSIST`), then the volatile key is deleted
+        store.put(&key2, &value, PutFlags::Override)?;
+        assert_eq!(counter.key_counts(&store, 0)?.expires, 1);
+        store.delete(&key1)?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 1,
+                expires: 0
+            }
+        );
+
+        // a key deleted and written again with a TTL by the same batch
+        let mut batch = BatchUpdate::default();
+        batch.delete(key2.clone());
+        batch.put(key2.clone(), volatile_value.clone());
+        store.apply_batch(&batch)?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 1,
+                expires: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_counter_writes_during_count() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let mut value = BytesMut::new();
+        CommonValueMetadata::default().to_bytes(&mut U8ArrayBuilder::with_buffer(&mut value));
+        let mut volatile_md = CommonValueMetadata::default();
+        volatile_md.expiration_mut().set_ttl_seconds(100)?;
+        let mut volatile_value = BytesMut::new();
+        volatile_md.to_bytes(&mut U8ArrayBuilder::with_buffer(&mut volatile_value));
+        let key1 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key1"), 0);
+        let key2 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key2"), 0);
+        let key3 = PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key3"), 0);
+
+        store.put(&key1, &value, PutFlags::Override)?;
+        store.put(&key2, &value, PutFlags::Override)?;
+        let counter = Arc::new(KeyCounter::default());
+        store.add_write_observer(counter.clone());
+
+        // a count is running: the writes are recorded and reconciled with the count
+        counter
+            .counts
+            .lock()
+            .unwrap()
+            .insert(0, DbCounts::Counting(HashMap::new()));
+        store.put(&key3, &value, PutFlags::Override)?;
+        store.delete(&key1)?;
+        store.put(&key2, &volatile_value, PutFlags::Override)?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 2,
+                expires: 1
+            }
+        );
+
+        // the counter is maintained from there
+        store.delete(&key3)?;
+        assert_eq!(counter.dbsize(&store, 0)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_counter_reported_records() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let counter = Arc::new(KeyCounter::default());
+        store.add_write_observer(counter.clone());
+        assert_eq!(counter.dbsize(&store, 0)?, 0);
+
+        // a string that expired: the read deletes it, the write creates it again
+        let key = BytesMut::from("string");
+        let mut md = StringValueMetadata::new();
+        md.expiration_mut().set_ttl_millis(0)?;
+        let strings_db = StringsDb::with_storage(&store, 0);
+        strings_db.put(&key, &BytesMut::from("value"), &md, PutFlags::Override)?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 1,
+                expires: 1
+            }
+        );
+        let strings_db = StringsDb::with_storage(&store, 0);
+        assert!(strings_db.get(&key)?.is_none());
+        strings_db.put(
+            &key,
+            &BytesMut::from("value"),
+            &StringValueMetadata::new(),
+            PutFlags::Override,
+        )?;
+        strings_db.put(
+            &key,
+            &BytesMut::from("value"),
+            &StringValueMetadata::new(),
+            PutFlags::Override,
+        )?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 1,
+                expires: 0
+            }
+        );
+
+        // a hash is read through the write cache before it is written
+        let hash_db = HashDb::with_storage(&store, 0);
+        let field = BytesMut::from("field");
+        hash_db.put_multi(&BytesMut::from("hash"), &[(&field, &field)])?;
+        hash_db.put_multi(&BytesMut::from("hash"), &[(&field, &field)])?;
+        assert_eq!(counter.dbsize(&store, 0)?, 2);
+
+        // the hash expired: it is deleted by the write that creates it again
+        let generic_db = GenericDb::with_storage(&store, 0);
+        let mut expiration = generic_db.get_expiration(&BytesMut::from("hash"))?.unwrap();
+        expiration.set_ttl_millis(0)?;
+        generic_db.put_expiration(&BytesMut::from("hash"), &expiration)?;
+        let hash_db = HashDb::with_storage(&store, 0);
+        hash_db.put_multi(&BytesMut::from("hash"), &[(&field, &field)])?;
+        assert_eq!(
+            counter.key_counts(&store, 0)?,
+            KeyCounts {
+                keys: 2,
+                expires: 0
+            }
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,22 @@
+pub mod acl;
+pub mod active_expire;
 pub mod client;
 pub mod commands;
 pub mod error_codes;
 pub mod io;
+pub mod key_counter;
+pub mod memory_tracker;
 pub mod metadata;
+pub mod metrics_exporter;
+pub mod reclaimer;
 pub mod replication;
 pub mod request_parser;
 pub mod resp_builder_v2;
+pub mod scripting;
 pub mod server;
 pub mod server_options;
 pub mod shard_locker;
+pub mod slowlog;
 pub mod stopwatch;
 pub mod storage;
 pub mod telemetry;
@@ -16,26 +24,42 @@ pub mod tls;
 pub mod transport;
 pub mod types;
 pub mod utils;
+pub mod wal_syncer;
+pub mod watched_keys;
 pub mod worker;
 pub mod worker_manager;
 
+pub use acl::{Acl, AclPermission, AclUser};
+pub use active_expire::ActiveExpire;
 pub use client::Client;
 pub use commands::{
-    ClientCommands, GenericCommands, HashCommands, ListCommands, RedisCommand, RedisCommandName,
-    ServerCommands, StringCommands,
+    BitmapCommands, ClientCommands, GenericCommands, HashCommands, ListCommands, MemoryCommands,
+    ObjectCommands, PubSubCommands, RedisCommand, RedisCommandName, ScanCommands, ScriptCommands,
+    ServerCommands, SetCommands, SortCommands, StreamCommands, StringCommands, TransactionCommands,
+    ZSetCommands,
 };
 pub use error_codes::{ParserError, SableError};
+pub use key_counter::{KeyCounter, KeyCounts};
+pub use memory_tracker::MemoryTracker;
 pub use metadata::{CommonValueMetadata, Expiration, PrimaryKeyMetadata, StringValueMetadata};
+pub use metrics_exporter::MetricsExporter;
+pub use reclaimer::Reclaimer;
 pub use request_parser::RequestParser;
-pub use resp_builder_v2::RespBuilderV2;
+pub use resp_builder_v2::{RespBuilderV2, RespProtocolVersion};
 pub use server::{Server, ServerState};
-pub use server_options::ServerOptions;
+pub use server_options::{EvictionPolicy, ServerOptions, SetConfigResult};
 pub use shard_locker::LockManager;
+pub use slowlog::{SlowLog, SlowLogEntry};
 pub use stopwatch::IoDurationStopWatch;
-pub use storage::{BatchUpdate, DbWriteCache, StorageAdapter, StorageOpenParams, StorageRocksDb};
+pub use storage::{
+    AppendFsync, BatchUpdate, CompressionType, DbWriteCache, StorageAdapter, StorageOpenParams,
+    StorageRocksDb,
+};
 pub use telemetry::Telemetry;
 pub use transport::Transport;
 pub use utils::{BytesMutUtils, StringUtils, TimeUtils, U8ArrayBuilder, U8ArrayReader};
+pub use wal_syncer::WalSyncer;
+pub use watched_keys::WatchedKeys;
 pub use worker::{Worker, WorkerContext, WorkerMessage};
 pub use worker_manager::WorkerManager;
 
@@ -197,8 +221,27 @@ mod tests {
         }
     }
 
+    /// A write observer that keeps every event it observes
+    #[derive(Default)]
+    pub struct WriteEventsCollector {
+        pub events: std::sync::Mutex<Vec<crate::storage::WriteEvent>>,
+    }
+
+    impl crate::storage::WriteObserver for WriteEventsCollector {
+        fn on_write(&self, event: &crate::storage::WriteEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
     // Provide a convenient API for opening a unique database
     pub fn open_store() -> (DirDeleter, StorageAdapter) {
+        open_store_with(|open_params| open_params)
+    }
+
+    /// Same as `open_store`, with the open parameters adjusted by `configure`
+    pub fn open_store_with(
+        configure: impl FnOnce(StorageOpenParams) -> StorageOpenParams,
+    ) -> (DirDeleter, StorageAdapter) {
         let database_base_dir = format!(
             "{}/sabledb_tests",
             std::env::temp_dir().to_path_buf().display()
@@ -219,13 +262,13 @@ mod tests {
         let _ = std::fs::create_dir_all(database_base_dir.as_str());
         let db_path = PathBuf::from(database_fullpath.as_str());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
 
         let mut store = StorageAdapter::default();
-        store.open(open_params).unwrap();
+        store.open(configure(open_params)).unwrap();
         (DirDeleter::with_path(database_fullpath), store)
     }
 
@@ -0,0 +1,809 @@
+use crate::{
+    server_options::EvictionPolicy,
+    storage::{GenericDb, WriteEvent, WriteEventType, WriteObserver},
+    utils::calculate_slot,
+    CommonValueMetadata, LockManager, PrimaryKeyMetadata, SableError, ServerState, StorageAdapter,
+    Telemetry, TimeUtils, U8ArrayReader,
+};
+use bytes::BytesMut;
+use rand::Rng;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{
+    atomic::{AtomicU64, AtomicU8, Ordering},
+    Mutex,
+};
+
+/// The higher the factor, the more accesses it takes to increment the LFU counter
+const LFU_LOG_FACTOR: f64 = 10.0;
+/// The LFU counter of a key is decremented for every `LFU_DECAY_MS` milliseconds it is idle
+const LFU_DECAY_MS: u64 = 60_000;
+/// With a volatile policy, up to `maxmemory_samples * VOLATILE_SCAN_FACTOR` keys are visited to
+/// find `maxmemory_samples` keys with a TTL
+const VOLATILE_SCAN_FACTOR: usize = 10;
+/// The number of item records deleted per batch when an evicted composite key is removed
+const EVICTED_ITEMS_DELETE_BUDGET: usize = 1000;
+/// The number of shards of the tracked keys
+const KEY_SHARDS: usize = 64;
+
+/// The access statistics of a key
+#[derive(Clone, Copy, Debug)]
+struct KeyAccess {
+    /// Last access time, ms since UNIX_EPOCH
+    accessed_at: u64,
+    /// Logarithmic access counter (LFU), see `access`
+    lfu_counter: u8,
+    /// When `lfu_counter` was last decayed, ms since UNIX_EPOCH
+    decayed_at: u64,
+}
+
+impl KeyAccess {
+    fn new(accessed_at: u64) -> Self {
+        KeyAccess {
+            accessed_at,
+            lfu_counter: CommonValueMetadata::LFU_INIT_VAL,
+            decayed_at: accessed_at,
+        }
+    }
+
+    /// Start from the access statistics kept in the value metadata
+    fn with_metadata(common_md: &CommonValueMetadata) -> Self {
+        KeyAccess {
+            accessed_at: common_md.accessed_at(),
+            lfu_counter: common_md.frequency(),
+            decayed_at: common_md.frequency_decayed_at(),
+        }
+    }
+
+    /// The LFU counter, decayed by the time passed since it was last decayed
+    fn frequency(&self, now: u64) -> u8 {
+        Self::decay(self.lfu_counter, self.decayed_at, now)
+    }
+
+    /// Decrement `counter` once for every `LFU_DECAY_MS` milliseconds passed since `decayed_at`
+    fn decay(counter: u8, decayed_at: u64, now: u64) -> u8 {
+        let idle_periods = now.saturating_sub(decayed_at) / LFU_DECAY_MS;
+        counter.saturating_sub(idle_periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// Record an access to the key. Like Redis, the LFU counter is incremented with a
+    /// probability that drops as the counter grows, so it can count millions of accesses
+    fn access(&mut self, now: u64) {
+        let mut counter = self.frequency(now);
+        let base = counter.saturating_sub(CommonValueMetadata::LFU_INIT_VAL) as f64;
+        if counter < u8::MAX && rand::random::<f64>() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+            counter += 1;
+        }
+        self.lfu_counter = counter;
+        self.accessed_at = now;
+        self.decayed_at = now;
+    }
+}
+
+/// The keys of a shard of the dataset
+#[derive(Default)]
+struct KeyShard {
+    /// The keys, in no particular order, so random keys can be sampled
+    keys: Vec<(u16, BytesMut)>,
+    /// `(db_id, user_key)` -> the position of the key in `keys` and its access statistics
+    index: HashMap<(u16, BytesMut), (usize, KeyAccess)>,
+}
+
+impl KeyShard {
+    /// Add a key, or replace its access statistics
+    fn insert(&mut self, db_id: u16, user_key: BytesMut, key_access: KeyAccess) {
+        let id = (db_id, user_key);
+        if let Some((_, known)) = self.index.get_mut(&id) {
+            *known = key_access;
+            return;
+        }
+        self.index.insert(id.clone(), (self.keys.len(), key_access));
+        self.keys.push(id);
+    }
+
+    /// Record an access to a key, adding it if it is new
+    fn access(&mut self, db_id: u16, user_key: &BytesMut, now: u64) {
+        if let Some((_, key_access)) = self.index.get_mut(&(db_id, user_key.clone())) {
+            key_access.access(now);
+            return;
+        }
+        self.insert(db_id, user_key.clone(), KeyAccess::new(now));
+    }
+
+    fn remove(&mut self, db_id: u16, user_key: &BytesMut) {
+        let Some((pos, _)) = self.index.remove(&(db_id, user_key.clone())) else {
+            return;
+        };
+        self.keys.swap_remove(pos);
+        if let Some(moved) = self.keys.get(pos) {
+            if let Some((moved_pos, _)) = self.index.get_mut(moved) {
+                *moved_pos = pos;
+            }
+        }
+    }
+
+    /// Pick up to `count` random keys
+    fn sample(&self, count: usize) -> Vec<(u16, BytesMut, KeyAccess)> {
+        let positions: Vec<usize> = if self.keys.len() <= count {
+            (0..self.keys.len()).collect()
+        } else {
+            let mut rng = rand::thread_rng();
+            rand::seq::index::sample(&mut rng, self.keys.len(), count).into_vec()
+        };
+        positions
+            .into_iter()
+            .filter_map(|pos| {
+                let (db_id, user_key) = &self.keys[pos];
+                self.index
+                    .get(&(*db_id, user_key.clone()))
+                    .map(|(_, key_access)| (*db_id, user_key.clone(), *key_access))
+            })
+            .collect()
+    }
+}
+
+/// The result of measuring the dataset
+#[derive(Default)]
+struct Measured {
+    used_bytes: u64,
+    keys: Vec<(u16, BytesMut, KeyAccess)>,
+}
+
+/// The writes reported while the dataset is measured, applied once the measure is done
+#[derive(Default)]
+struct PendingWrites {
+    /// The size of the records put minus the size of the records replaced or deleted
+    bytes_delta: i64,
+    /// The keys put or deleted, in order
+    keys: Vec<(u16, BytesMut, WriteEventType)>,
+}
+
+/// Tracking is disabled
+const TRACKING_DISABLED: u8 = 0;
+/// The dataset is being measured, the writes are recorded meanwhile (`PendingWrites`)
+const TRACKING_MEASURING: u8 = 1;
+/// The dataset is measured and maintained by the writes
+const TRACKING_ENABLED: u8 = 2;
+
+/// The approximate size of the dataset and the access time and frequency of every key, used to
+/// enforce `maxmemory`.
+///
+/// Like `KeyCounter`, the tracker is registered as a `WriteObserver` on the storage: the size of
+/// every record put is added and the size of every record replaced or deleted is subtracted,
+/// keys are added and removed with their primary records. Reads are reported by the clients
+/// (`touch`). Tracking costs a lookup per written record and memory per key, so it is enabled
+/// only while `maxmemory` is set or the LFU policy is selected: the dataset is then measured by
+/// iterating the whole storage, and the keys start with the access statistics kept in their
+/// metadata.
+///
+/// The keys are sharded by their hash, so the clients of the different workers rarely wait for
+/// each other when they report their accesses. The dataset is measured without holding any of
+/// the shards: the writes reported meanwhile are recorded and applied on top of the measure
+pub struct MemoryTracker {
+    /// The keys and their access statistics
+    shards: Vec<Mutex<KeyShard>>,
+    /// The size of all the records (keys and values)
+    used_bytes: AtomicU64,
+    /// One of `TRACKING_DISABLED`, `TRACKING_MEASURING` or `TRACKING_ENABLED`. Lets the clients
+    /// skip the locks while tracking is disabled
+    tracking: AtomicU8,
+    /// The writes reported during the measure. `None` unless the dataset is being measured
+    pending: Mutex<Option<PendingWrites>>,
+    /// Held by the caller measuring the dataset, the other callers of `used_memory` wait for it
+    measure_lock: Mutex<()>,
+}
+
+impl Default for MemoryTracker {
+    fn default() -> Self {
+        MemoryTracker {
+            shards: (0..KEY_SHARDS)
+                .map(|_| Mutex::new(KeyShard::default()))
+                .collect(),
+            used_bytes: AtomicU64::new(0),
+            tracking: AtomicU8::new(TRACKING_DISABLED),
+            pending: Mutex::new(None),
+            measure_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl MemoryTracker {
+    /// Return the approximate size of the dataset, in bytes. The first call measures the dataset
+    /// and enables tracking
+    pub fn used_memory(&self, store: &StorageAdapter) -> Result<u64, SableError> {
+        if self.is_tracking() {
+            return Ok(self.used_bytes.load(Ordering::Relaxed));
+        }
+
+        let _measuring = self.measure_lock.lock().expect("poisoned mutex");
+        if self.is_tracking() {
+            // measured while we were waiting
+            return Ok(self.used_bytes.load(Ordering::Relaxed));
+        }
+
+        // record the writes before the storage is iterated, so no write is missed
+        *self.pending.lock().expect("poisoned mutex") = Some(PendingWrites::default());
+        self.tracking.store(TRACKING_MEASURING, Ordering::SeqCst);
+        let measured = match Self::measure(store) {
+            Ok(measured) => measured,
+            Err(e) => {
+                self.disable();
+                return Err(e);
+            }
+        };
+
+        let mut pending = self.pending.lock().expect("poisoned mutex");
+        let Some(writes) = pending.take() else {
+            // disabled meanwhile, the measure is not kept
+            return Ok(measured.used_bytes);
+        };
+
+        for shard in &self.shards {
+            *shard.lock().expect("poisoned mutex") = KeyShard::default();
+        }
+        for (db_id, user_key, key_access) in measured.keys {
+            self.shard(db_id, &user_key)
+                .lock()
+                .expect("poisoned mutex")
+                .insert(db_id, user_key, key_access);
+        }
+
+        let now = TimeUtils::epoch_ms().unwrap_or_default();
+        for (db_id, user_key, event_type) in writes.keys {
+            let mut shard = self.shard(db_id, &user_key).lock().expect("poisoned mutex");
+            match event_type {
+                WriteEventType::Put => shard.access(db_id, &user_key, now),
+                WriteEventType::Delete => shard.remove(db_id, &user_key),
+            }
+        }
+
+        let used_bytes = if writes.bytes_delta < 0 {
+            measured
+                .used_bytes
+                .saturating_sub(writes.bytes_delta.unsigned_abs())
+        } else {
+            measured
+                .used_bytes
+                .saturating_add(writes.bytes_delta as u64)
+        };
+        self.used_bytes.store(used_bytes, Ordering::Relaxed);
+        // still holding `pending`: a write recorded as pending is applied above, the others see
+        // the tracking enabled
+        self.tracking.store(TRACKING_ENABLED, Ordering::SeqCst);
+        Ok(used_bytes)
+    }
+
+    /// Stop tracking (`maxmemory` and the LFU policy were removed)
+    pub fn disable(&self) {
+        if self.tracking.swap(TRACKING_DISABLED, Ordering::SeqCst) == TRACKING_DISABLED {
+            return;
+        }
+        *self.pending.lock().expect("poisoned mutex") = None;
+        for shard in &self.shards {
+            *shard.lock().expect("poisoned mutex") = KeyShard::default();
+        }
+        self.used_bytes.store(0, Ordering::Relaxed);
+    }
+
+    /// Is tracking enabled (`maxmemory` or the LFU policy is set and was enforced at least once)?
+    pub fn is_tracking(&self) -> bool {
+        self.tracking.load(Ordering::SeqCst) == TRACKING_ENABLED
+    }
+
+    /// The shard of `user_key`
+    fn shard(&self, db_id: u16, user_key: &BytesMut) -> &Mutex<KeyShard> {
+        let hash = (calculate_slot(user_key) as usize).wrapping_add(db_id as usize);
+        &self.shards[hash % self.shards.len()]
+    }
+
+    /// Record a read of `user_keys` in database `db_id`. Keys that do not exist are ignored
+    pub fn touch(&self, db_id: u16, user_keys: &[&BytesMut]) {
+        if user_keys.is_empty() || !self.is_tracking() {
+            return;
+        }
+
+        let now = TimeUtils::epoch_ms().unwrap_or_default();
+        for user_key in user_keys {
+            let mut shard = self.shard(db_id, user_key).lock().expect("poisoned mutex");
+            if let Some((_, key_access)) = shard.index.get_mut(&(db_id, (*user_key).clone())) {
+                key_access.access(now);
+            }
+        }
+    }
+
+    /// Return the LFU access counter of `user_key` in database `db_id`, or `None` if the key is
+    /// not tracked
+    pub fn frequency(&self, db_id: u16, user_key: &BytesMut) -> Option<u8> {
+        if !self.is_tracking() {
+            return None;
+        }
+        let now = TimeUtils::epoch_ms().unwrap_or_default();
+        let shard = self.shard(db_id, user_key).lock().expect("poisoned mutex");
+        shard
+            .index
+            .get(&(db_id, user_key.clone()))
+            .map(|(_, key_access)| key_access.frequency(now))
+    }
+
+    /// Pick up to `count` random keys. The samples are spread over the shards, starting from a
+    /// random one
+    fn sample(&self, count: usize) -> Vec<(u16, BytesMut, KeyAccess)> {
+        let per_shard = count.div_ceil(self.shards.len()).max(1);
+        let first = rand::thread_rng().gen_range(0..self.shards.len());
+        let mut samples = Vec::with_capacity(count);
+        for i in 0..self.shards.len() {
+            let remaining = count.saturating_sub(samples.len());
+            if remaining == 0 {
+                break;
+            }
+            let shard = self.shards[(first + i) % self.shards.len()]
+                .lock()
+                .expect("poisoned mutex");
+            samples.extend(shard.sample(per_shard.min(remaining)));
+        }
+        samples
+    }
+    /// Return the LFU access counter kept in the metadata of a key that is not tracked, decayed
+    /// by the time passed since
+    pub fn stored_frequency(common_md: &CommonValueMetadata) -> u8 {
+        KeyAccess::with_metadata(common_md).frequency(TimeUtils::epoch_ms().unwrap_or_default())
+    }
+
+    /// Make room for a write of about `incoming` bytes. Keys are evicted according to
+    /// `maxmemory-policy` until the dataset and the write fit in `maxmemory`. Return `false` if
+    /// that is not possible: the write must be rejected
+    pub async fn make_room(
+        server_state: &ServerState,
+        store: &StorageAdapter,
+        incoming: usize,
+    ) -> Result<bool, SableError> {
+        let (maxmemory, policy, samples) = {
+            let options = server_state.options();
+            let settings = &options.memory_settings;
+            (
+                settings.maxmemory as u64,
+                settings.maxmemory_policy,
+                settings.maxmemory_samples,
+            )
+        };
+
+        let tracker = server_state.memory_tracker();
+        if maxmemory == 0 {
+            if policy == EvictionPolicy::AllKeysLfu {
+                // keep counting accesses for `OBJECT FREQ`
+                tracker.used_memory(store)?;
+            } else {
+                tracker.disable();
+            }
+            return Ok(true);
+        }
+
+        loop {
+            let used_bytes = tracker.used_memory(store)?;
+            if used_bytes.saturating_add(incoming as u64) <= maxmemory {
+                return Ok(true);
+            }
+
+            if policy == EvictionPolicy::NoEviction {
+                return Ok(false);
+            }
+
+            let Some((db_id, user_key)) = tracker.pick_victim(store, policy, samples)? else {
+                return Ok(false);
+            };
+
+            if Self::evict(store, db_id, &user_key).await? {
+                Telemetry::inc_evicted_keys(1);
+                server_state.notify_keyspace_event(db_id, "evicted", &user_key);
+            } else {
+                // the key is gone, make sure it is not picked again
+                tracker.forget(db_id, &user_key);
+            }
+        }
+    }
+
+    /// Pick the best key to evict out of `samples` random keys
+    fn pick_victim(
+        &self,
+        store: &StorageAdapter,
+        policy: EvictionPolicy,
+        samples: usize,
+    ) -> Result<Option<(u16, BytesMut)>, SableError> {
+        let now = TimeUtils::epoch_ms()?;
+        if !self.is_tracking() {
+            return Ok(None);
+        }
+        let candidates = if policy.is_volatile() {
+            self.sample(samples.saturating_mul(VOLATILE_SCAN_FACTOR))
+        } else {
+            self.sample(samples)
+        };
+
+        let mut best: Option<((u64, u64), u16, BytesMut)> = None;
+        let mut volatile_found = 0usize;
+        for (db_id, user_key, key_access) in candidates {
+            // the lower the score, the better the candidate
+            let score = match policy {
+                EvictionPolicy::AllKeysLru => (key_access.accessed_at, 0),
+                EvictionPolicy::AllKeysLfu => {
+                    (key_access.frequency(now) as u64, key_access.accessed_at)
+                }
+                EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl => {
+                    if volatile_found == samples {
+                        break;
+                    }
+                    let generic_db = GenericDb::with_storage(store, db_id);
+                    let Some(expiration) = generic_db.get_expiration(&user_key)? else {
+                        continue;
+                    };
+                    if !expiration.has_ttl() {
+                        continue;
+                    }
+                    volatile_found = volatile_found.saturating_add(1);
+                    if policy == EvictionPolicy::VolatileTtl {
+                        (expiration.ttl_in_millis()?, 0)
+                    } else {
+                        (key_access.accessed_at, 0)
+                    }
+                }
+                EvictionPolicy::NoEviction => return Ok(None),
+            };
+
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, _, _)| score < *best_score)
+            {
+                best = Some((score, db_id, user_key));
+            }
+        }
+        Ok(best.map(|(_, db_id, user_key)| (db_id, user_key)))
+    }
+
+    /// Delete `user_key`. Return `false` if it does not exist
+    async fn evict(
+        store: &StorageAdapter,
+        db_id: u16,
+        user_key: &BytesMut,
+    ) -> Result<bool, SableError> {
+        let generic_db = GenericDb::with_storage(store, db_id);
+        let items_prefix = {
+            let _unused = LockManager::lock_user_key_exclusive(user_key, db_id);
+            if !generic_db.contains(user_key)? {
+                return Ok(false);
+            }
+            let items_prefix = generic_db.items_prefix(user_key)?;
+            generic_db.delete(user_key)?;
+            items_prefix
+        };
+
+        // the items are deleted right away (and not by the reclaimer), so the memory they use
+        // is released before the write runs
+        if let Some(items_prefix) = items_prefix {
+            while generic_db.delete_items(&items_prefix, EVICTED_ITEMS_DELETE_BUDGET)?
+                == EVICTED_ITEMS_DELETE_BUDGET
+            {
+                tokio::task::yield_now().await;
+            }
+        }
+        Ok(true)
+    }
+
+    fn forget(&self, db_id: u16, user_key: &BytesMut) {
+        self.shard(db_id, user_key)
+            .lock()
+            .expect("poisoned mutex")
+            .remove(db_id, user_key);
+    }
+
+    /// Measure the size of all the records and collect the keys
+    fn measure(store: &StorageAdapter) -> Result<Measured, SableError> {
+        let mut measured = Measured::default();
+        let mut error = None;
+        store.iterate(
+            Rc::new(BytesMut::new()),
+            Box::new(|_, key, value| {
+                measured.used_bytes = measured
+                    .used_bytes
+                    .saturating_add(key.len().saturating_add(value.len()) as u64);
+                if !WriteEvent::is_primary_key(key) {
+                    return true;
+                }
+
+                let parsed = PrimaryKeyMetadata::from_raw(&BytesMut::from(key)).and_then(
+                    |(key_md, user_key)| {
+                        let mut reader = U8ArrayReader::with_buffer(value);
+                        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+                        Ok((
+                            key_md.db_id(),
+                            user_key,
+                            KeyAccess::with_metadata(&common_md),
+                        ))
+                    },
+                );
+                match parsed {
+                    Ok(key) => {
+                        measured.keys.push(key);
+                        true
+                    }
+                    Err(e) => {
+                        error = Some(e);
+                        false
+                    }
+                }
+            }),
+        )?;
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(measured),
+        }
+    }
+
+    /// Apply a write to the tracked dataset
+    fn apply_write(&self, event: &WriteEvent) {
+        self.used_bytes
+            .fetch_add(event.bytes_written as u64, Ordering::Relaxed);
+        let _ = self
+            .used_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used_bytes| {
+                Some(used_bytes.saturating_sub(event.bytes_released as u64))
+            });
+
+        // a write is an access
+        let now = TimeUtils::epoch_ms().unwrap_or_default();
+        for key in &event.keys {
+            let mut shard = self
+                .shard(key.db_id, &key.user_key)
+                .lock()
+                .expect("poisoned mutex");
+            match key.event_type {
+                WriteEventType::Put => shard.access(key.db_id, &key.user_key, now),
+                WriteEventType::Delete => shard.remove(key.db_id, &key.user_key),
+            }
+        }
+    }
+}
+
+impl WriteObserver for MemoryTracker {
+    fn on_write(&self, event: &WriteEvent) {
+        match self.tracking.load(Ordering::SeqCst) {
+            TRACKING_ENABLED => self.apply_write(event),
+            TRACKING_MEASURING => {
+                let mut pending = self.pending.lock().expect("poisoned mutex");
+                let Some(writes) = pending.as_mut() else {
+                    // the measure completed (or was dropped) meanwhile
+                    drop(pending);
+                    if self.is_tracking() {
+                        self.apply_write(event);
+                    }
+                    return;
+                };
+                writes.bytes_delta = writes
+                    .bytes_delta
+                    .saturating_add(event.bytes_written as i64)
+                    .saturating_sub(event.bytes_released as i64);
+                for key in &event.keys {
+                    writes
+                        .keys
+                        .push((key.db_id, key.user_key.clone(), key.event_type));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn tracks_record_sizes(&self) -> bool {
+        self.tracking.load(Ordering::SeqCst) != TRACKING_DISABLED
+    }
+
+    fn on_reset(&self) {
+        // measured again when needed
+        self.disable();
+    }
+
+    fn on_range_deleted(&self, _prefix: &[u8]) {
+        self.disable();
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::PutFlags, Client, RedisCommand};
+    use std::sync::Arc;
+
+    /// A value large enough for a single key to matter
+    const VALUE: &str = "0123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789";
+
+    #[test]
+    fn test_memory_tracker() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let tracker = std::sync::Arc::new(MemoryTracker::default());
+        store.add_write_observer(tracker.clone());
+
+        let key = BytesMut::from("key");
+        let value = BytesMut::from("value");
+        store.put(&key, &value, PutFlags::Override)?;
+
+        // records written before tracking was enabled are found by the measurement
+        assert_eq!(tracker.used_memory(&store)?, 8);
+        store.put(&BytesMut::from("key2"), &value, PutFlags::Override)?;
+        assert_eq!(tracker.used_memory(&store)?, 17);
+        // replacing a record releases the size of the old one
+        store.put(&key, &BytesMut::from("v"), PutFlags::Override)?;
+        assert_eq!(tracker.used_memory(&store)?, 13);
+        store.delete(&BytesMut::from("key2"))?;
+        assert_eq!(tracker.used_memory(&store)?, 4);
+
+        tracker.disable();
+        assert!(!tracker.tracks_record_sizes());
+        store.put(&BytesMut::from("key3"), &value, PutFlags::Override)?;
+        assert_eq!(tracker.used_memory(&store)?, 13);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_tracker_samples_all_shards() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let tracker = std::sync::Arc::new(MemoryTracker::default());
+        store.add_write_observer(tracker.clone());
+        tracker.used_memory(&store)?;
+
+        // a handful of keys, spread over the shards: the samples find them all
+        let strings_db = crate::storage::StringsDb::with_storage(&store, 0);
+        for key in ["k1", "k2", "k3"] {
+            strings_db.put(
+                &BytesMut::from(key),
+                &BytesMut::from(VALUE),
+                &crate::StringValueMetadata::default(),
+                PutFlags::Override,
+            )?;
+        }
+        let mut sampled: Vec<BytesMut> = tracker
+            .sample(5)
+            .into_iter()
+            .map(|(_, user_key, _)| user_key)
+            .collect();
+        sampled.sort();
+        assert_eq!(sampled, vec!["k1", "k2", "k3"]);
+        assert_eq!(tracker.sample(1).len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_allkeys_lru_eviction() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.memory_tracker());
+            server_state.update_options(|options| {
+                options.memory_settings.maxmemory_policy = EvictionPolicy::AllKeysLru
+            });
+            let client = Client::new(server_state.clone(), store.clone(), None);
+            let subscriber = Client::new(server_state.clone(), store.clone(), None);
+            let mut messages = subscriber.inner().take_pubsub_receiver().unwrap();
+
+            async fn call(client: &Client, args: Vec<&'static str>) -> BytesMut {
+                let command = Rc::new(RedisCommand::for_test(args));
+                let reply = Client::handle_command_captured(client.inner(), command).await;
+                // let the access times of the keys differ
+                tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+                reply
+            }
+
+            for key in ["k1", "k2", "k3"] {
+                assert_eq!(call(&client, vec!["set", key, VALUE]).await, "+OK\r\n");
+            }
+            call(&subscriber, vec!["subscribe", "__keyspace@0__:k2"]).await;
+
+            // the dataset is full: there is no room for another key
+            let used_memory = server_state.memory_tracker().used_memory(&store).unwrap();
+            server_state.update_options(|options| {
+                options.memory_settings.maxmemory = used_memory as usize + 10
+            });
+
+            // k2 becomes the least recently used key
+            assert_eq!(
+                call(&client, vec!["get", "k1"]).await,
+                format!("${}\r\n{}\r\n", VALUE.len(), VALUE)
+            );
+            assert_eq!(call(&client, vec!["set", "k4", VALUE]).await, "+OK\r\n");
+            assert_eq!(call(&client, vec!["exists", "k2"]).await, ":0\r\n");
+            assert_eq!(
+                call(&client, vec!["exists", "k1", "k3", "k4"]).await,
+                ":3\r\n"
+            );
+            assert_eq!(
+                messages.try_recv().unwrap(),
+                "*3\r\n$7\r\nmessage\r\n$17\r\n__keyspace@0__:k2\r\n$7\r\nevicted\r\n"
+            );
+
+            // without eviction, writes are rejected but keys can still be deleted
+            server_state.update_options(|options| {
+                options.memory_settings.maxmemory_policy = EvictionPolicy::NoEviction
+            });
+            assert_eq!(
+                call(&client, vec!["set", "k5", VALUE]).await,
+                format!("-{}\r\n", crate::commands::ErrorStrings::OOM)
+            );
+            assert_eq!(call(&client, vec!["del", "k1"]).await, ":1\r\n");
+            assert_eq!(call(&client, vec!["set", "k5", VALUE]).await, "+OK\r\n");
+        });
+    }
+
+    #[test]
+    fn test_lfu_frequency() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            store.add_write_observer(server_state.memory_tracker());
+            server_state.update_options(|options| {
+                options.memory_settings.maxmemory_policy = EvictionPolicy::AllKeysLfu
+            });
+            let client = Client::new(server_state.clone(), store.clone(), None);
+
+            async fn freq(client: &Client) -> u64 {
+                let command = Rc::new(RedisCommand::for_test(vec!["object", "freq", "key"]));
+                let reply = Client::handle_command_captured(client.inner(), command).await;
+                let reply = String::from_utf8_lossy(&reply).to_string();
+                reply
+                    .trim_start_matches(':')
+                    .trim_end()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("unexpected reply: {}", reply))
+            }
+
+            // the LFU policy tracks the accesses even without `maxmemory`
+            let command = Rc::new(RedisCommand::for_test(vec!["set", "key", VALUE]));
+            let reply = Client::handle_command_captured(client.inner(), command).await;
+            assert_eq!(reply, "+OK\r\n");
+            let initial = freq(&client).await;
+            assert!(initial >= CommonValueMetadata::LFU_INIT_VAL as u64);
+            // `OBJECT FREQ` does not count as an access
+            assert_eq!(freq(&client).await, initial);
+
+            // the first access after the initial value always increments the counter
+            for _ in 0..10 {
+                let command = Rc::new(RedisCommand::for_test(vec!["get", "key"]));
+                Client::handle_command_captured(client.inner(), command).await;
+            }
+            let accessed = freq(&client).await;
+            assert!(accessed > initial);
+
+            // the counter decays by one for every idle minute
+            {
+                let tracker = server_state.memory_tracker();
+                let key = BytesMut::from("key");
+                let mut shard = tracker.shard(0, &key).lock().unwrap();
+                let (_, key_access) = shard.index.get_mut(&(0, key.clone())).unwrap();
+                key_access.decayed_at = key_access.decayed_at.saturating_sub(3 * LFU_DECAY_MS);
+            }
+            assert_eq!(freq(&client).await, accessed - 3);
+        });
+    }
+
+    #[test]
+    fn test_stored_frequency_decay() -> Result<(), SableError> {
+        let now = TimeUtils::epoch_ms()?;
+        let mut common_md = CommonValueMetadata::default();
+        assert_eq!(
+            MemoryTracker::stored_frequency(&common_md),
+            CommonValueMetadata::LFU_INIT_VAL
+        );
+
+        common_md.set_frequency(10, now - 3 * LFU_DECAY_MS - 1000);
+        assert_eq!(MemoryTracker::stored_frequency(&common_md), 7);
+        common_md.set_frequency(10, now - 100 * LFU_DECAY_MS);
+        assert_eq!(MemoryTracker::stored_frequency(&common_md), 0);
+        Ok(())
+    }
+}
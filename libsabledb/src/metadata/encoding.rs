@@ -1,6 +1,6 @@
 pub struct Encoding {}
-
-/// Encoding represents the first byte (u8) used for every data type (and some values)
+
+/// Encoding represents the first byte (u8) used for every data type (and some values)
 /// that we store in the database
 impl Encoding {
     // All primary data types are encoded using `0u8` as their
@@ -8,14 +8,33 @@ impl Encoding {
     pub const KEY_STRING: u8 = 0u8;
     pub const KEY_LIST: u8 = 0u8;
     pub const KEY_HASH: u8 = 0u8;
+    pub const KEY_SET: u8 = 0u8;
+    pub const KEY_ZSET: u8 = 0u8;
+    pub const KEY_STREAM: u8 = 0u8;
 
-    // Encoding for values, each data type is encoded with its own unique value
+    // Encoding for values, each data type is encoded with its own unique value
     // again, the first byte
     pub const VALUE_STRING: u8 = 0u8;
     pub const VALUE_LIST: u8 = 1u8;
     pub const VALUE_HASH: u8 = 2u8;
+    pub const VALUE_SET: u8 = 3u8;
+    pub const VALUE_ZSET: u8 = 4u8;
+    pub const VALUE_STREAM: u8 = 5u8;
 
     // Secondary data type keys encoding
     pub const KEY_LIST_ITEM: u8 = 1u8;
     pub const KEY_HASH_ITEM: u8 = 2u8;
+    pub const KEY_SET_ITEM: u8 = 3u8;
+    pub const KEY_ZSET_ITEM: u8 = 4u8;
+
+    // Records that are not bound to a database
+    pub const KEY_FUNCTION_LIBRARY: u8 = 5u8;
+
+    // Stream entries. Declared after the function libraries to keep the existing encoding
+    pub const KEY_STREAM_ITEM: u8 = 6u8;
+
+    // Never stored: written and deleted by the batch of a range deletion, so the deletion shows
+    // up in the WAL (the WAL iterator does not report range deletions). The rest of the key is
+    // the deleted prefix
+    pub const KEY_DELETE_RANGE_MARKER: u8 = 7u8;
 }
@@ -11,17 +11,29 @@ pub struct HashValueMetadata {
     common: CommonValueMetadata,
     hash_id: u64,
     hash_size: u64,
+    /// Number of fields with a TTL (`HEXPIRE`). When `0`, no field can be expired
+    volatile_size: u64,
+    /// The layout of the field records (`FIELDS_RAW` or `FIELDS_WITH_EXPIRATION`)
+    fields_format: u8,
 }
 
 #[allow(dead_code)]
 impl HashValueMetadata {
-    pub const SIZE: usize = 2 * std::mem::size_of::<u64>() + CommonValueMetadata::SIZE;
+    pub const SIZE: usize =
+        3 * std::mem::size_of::<u64>() + std::mem::size_of::<u8>() + CommonValueMetadata::SIZE;
+    /// The field records hold the value only. Hashes created before fields could have a TTL
+    /// keep this layout until a TTL is set on one of their fields
+    const FIELDS_RAW: u8 = 0;
+    /// The field records are `HashFieldValue`s: `[expire at | value]`
+    const FIELDS_WITH_EXPIRATION: u8 = 1;
 
     pub fn with_id(hash_id: u64) -> Self {
         HashValueMetadata {
             common: CommonValueMetadata::default().set_hash(),
             hash_id,
             hash_size: 0,
+            volatile_size: 0,
+            fields_format: Self::FIELDS_WITH_EXPIRATION,
         }
     }
 
@@ -57,16 +69,60 @@ impl HashValueMetadata {
         self.hash_size = self.hash_size.saturating_sub(diff);
     }
 
+    /// Return the number of fields with a TTL
+    pub fn volatile_len(&self) -> u64 {
+        self.volatile_size
+    }
+
+    pub fn incr_volatile_len_by(&mut self, diff: u64) {
+        self.volatile_size = self.volatile_size.saturating_add(diff);
+    }
+
+    pub fn decr_volatile_len_by(&mut self, diff: u64) {
+        self.volatile_size = self.volatile_size.saturating_sub(diff);
+    }
+
     /// Set the hash ID
     pub fn set_id(&mut self, hash_id: u64) {
         self.hash_id = hash_id
     }
 
+    /// Can the field records of this hash hold an expiration? If not, they must be rewritten
+    /// with `encode_field` after calling `set_fields_with_expiration` before a TTL is set
+    pub fn fields_have_expiration(&self) -> bool {
+        self.fields_format == Self::FIELDS_WITH_EXPIRATION
+    }
+
+    pub fn set_fields_with_expiration(&mut self) {
+        self.fields_format = Self::FIELDS_WITH_EXPIRATION;
+    }
+
+    /// Decode the value of one of this hash's field records
+    pub fn decode_field<'b>(&self, value: &'b [u8]) -> Result<HashFieldValue<'b>, SableError> {
+        if self.fields_have_expiration() {
+            HashFieldValue::from_bytes(value)
+        } else {
+            Ok(HashFieldValue::with_value(value))
+        }
+    }
+
+    /// Encode the value of a field record in this hash's layout. The expiration of
+    /// `field_value` is dropped if the field records can not hold it
+    pub fn encode_field(&self, field_value: &HashFieldValue, builder: &mut U8ArrayBuilder) {
+        if self.fields_have_expiration() {
+            field_value.to_bytes(builder);
+        } else {
+            builder.write_bytes(field_value.value());
+        }
+    }
+
     /// Serialise the hash value metadata into bytes
     pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
         self.common.to_bytes(builder);
         builder.write_u64(self.hash_id);
         builder.write_u64(self.hash_size);
+        builder.write_u64(self.volatile_size);
+        builder.write_u8(self.fields_format);
     }
 
     pub fn from_bytes(reader: &mut U8ArrayReader) -> Result<Self, SableError> {
@@ -75,10 +131,21 @@ impl HashValueMetadata {
         let hash_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
         let hash_size = reader.read_u64().ok_or(SableError::SerialisationError)?;
 
+        // the records of hashes written before fields could have a TTL end here
+        let (volatile_size, fields_format) = match reader.read_u64() {
+            Some(volatile_size) => (
+                volatile_size,
+                reader.read_u8().ok_or(SableError::SerialisationError)?,
+            ),
+            None => (0, Self::FIELDS_RAW),
+        };
+
         Ok(HashValueMetadata {
             common,
             hash_id,
             hash_size,
+            volatile_size,
+            fields_format,
         })
     }
 
@@ -146,6 +213,65 @@ impl<'a> HashFieldKey<'a> {
     }
 }
 
+/// The value of a hash field record: `[expire at | value]`. `expire at` is the field expiration
+/// in milliseconds since UNIX_EPOCH (`HEXPIRE`), `0` if the field has no TTL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashFieldValue<'a> {
+    expire_at_ms: u64,
+    value: &'a [u8],
+}
+
+impl<'a> HashFieldValue<'a> {
+    // SIZE contain only the serialisable items
+    pub const SIZE: usize = std::mem::size_of::<u64>();
+    const NO_EXPIRATION: u64 = 0;
+
+    /// A field value without a TTL
+    pub fn with_value(value: &'a [u8]) -> Self {
+        HashFieldValue {
+            expire_at_ms: Self::NO_EXPIRATION,
+            value,
+        }
+    }
+
+    /// Expire the field at `expire_at_ms` (milliseconds since UNIX_EPOCH)
+    pub fn with_expiration(mut self, expire_at_ms: u64) -> Self {
+        self.expire_at_ms = expire_at_ms.max(1);
+        self
+    }
+
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        builder.write_u64(self.expire_at_ms);
+        builder.write_bytes(self.value);
+    }
+
+    pub fn from_bytes(buff: &'a [u8]) -> Result<Self, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(buff);
+        let expire_at_ms = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let (_, value) = buff.split_at(reader.consumed());
+        Ok(HashFieldValue {
+            expire_at_ms,
+            value,
+        })
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// The field expiration in milliseconds since UNIX_EPOCH, `None` if the field has no TTL
+    pub fn expire_at_ms(&self) -> Option<u64> {
+        (self.expire_at_ms != Self::NO_EXPIRATION).then_some(self.expire_at_ms)
+    }
+
+    /// Return true if the field expired at `now_ms`
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expire_at_ms()
+            .is_some_and(|expire_at_ms| expire_at_ms <= now_ms)
+    }
+}
+
 //  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
 // | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
 // | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
@@ -174,4 +300,75 @@ mod tests {
         assert_eq!(deserialised, hash_item_key);
         Ok(())
     }
+
+    #[test]
+    pub fn test_hash_field_value_serialization() -> Result<(), SableError> {
+        let value = BytesMut::from("value");
+        for field_value in [
+            HashFieldValue::with_value(&value),
+            HashFieldValue::with_value(&value).with_expiration(1000),
+        ] {
+            let mut buffer = BytesMut::with_capacity(HashFieldValue::SIZE + value.len());
+            let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+            field_value.to_bytes(&mut builder);
+            assert_eq!(buffer.len(), HashFieldValue::SIZE + value.len());
+
+            let deserialised = HashFieldValue::from_bytes(&buffer)?;
+            assert_eq!(deserialised, field_value);
+            assert_eq!(deserialised.value(), b"value");
+        }
+
+        let field_value = HashFieldValue::with_value(&value);
+        assert_eq!(field_value.expire_at_ms(), None);
+        assert!(!field_value.is_expired(u64::MAX));
+
+        let field_value = field_value.with_expiration(1000);
+        assert_eq!(field_value.expire_at_ms(), Some(1000));
+        assert!(!field_value.is_expired(999));
+        assert!(field_value.is_expired(1000));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_read_hash_without_field_expiration() -> Result<(), SableError> {
+        // a hash written before fields could have a TTL: no volatile size and no fields format
+        let mut buffer = BytesMut::new();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Encoding::VALUE_HASH);
+        Expiration::default().to_bytes(&mut builder);
+        builder.write_u64(7);
+        builder.write_u64(2);
+
+        let mut reader = U8ArrayReader::with_buffer(&buffer);
+        let mut hash = HashValueMetadata::from_bytes(&mut reader)?;
+        assert_eq!(hash.id(), 7);
+        assert_eq!(hash.len(), 2);
+        assert_eq!(hash.volatile_len(), 0);
+        assert!(!hash.fields_have_expiration());
+
+        // its field records are the bare values
+        let field_value = hash.decode_field(b"value")?;
+        assert_eq!(field_value.value(), b"value");
+        assert_eq!(field_value.expire_at_ms(), None);
+
+        let mut encoded = BytesMut::new();
+        hash.encode_field(&field_value, &mut U8ArrayBuilder::with_buffer(&mut encoded));
+        assert_eq!(&encoded[..], b"value");
+
+        // once upgraded, the field records hold the expiration
+        hash.set_fields_with_expiration();
+        let mut encoded = BytesMut::new();
+        hash.encode_field(
+            &field_value.with_expiration(1000),
+            &mut U8ArrayBuilder::with_buffer(&mut encoded),
+        );
+        assert_eq!(hash.decode_field(&encoded)?.expire_at_ms(), Some(1000));
+
+        let mut buffer = BytesMut::new();
+        hash.to_bytes(&mut U8ArrayBuilder::with_buffer(&mut buffer));
+        assert_eq!(buffer.len(), HashValueMetadata::SIZE);
+        let mut reader = U8ArrayReader::with_buffer(&buffer);
+        assert_eq!(HashValueMetadata::from_bytes(&mut reader)?, hash);
+        Ok(())
+    }
 }
@@ -1,7 +1,8 @@
 use crate::{
-    metadata::{CommonValueMetadata, Expiration, ValueTypeIs},
+    metadata::{CommonValueMetadata, Encoding, Expiration, ValueTypeIs},
     SableError, U8ArrayBuilder, U8ArrayReader,
 };
+use bytes::BytesMut;
 
 /// Contains information regarding the String type metadata
 #[derive(Clone, Debug)]
@@ -85,6 +86,16 @@ impl ListValueMetadata {
         self.list_id = list_id;
     }
 
+    /// Create a prefix for iterating all items belonged to this list
+    pub fn prefix(&self) -> BytesMut {
+        let mut buffer =
+            BytesMut::with_capacity(std::mem::size_of::<u8>() + std::mem::size_of::<u64>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Encoding::KEY_LIST_ITEM);
+        builder.write_u64(self.id());
+        buffer
+    }
+
     pub fn set_head(&mut self, item_id: u64) {
         self.head_id = item_id;
     }
@@ -7,6 +7,9 @@ pub enum ValueType {
     Str,
     List,
     Hash,
+    Set,
+    ZSet,
+    Stream,
 }
 
 mod encoding;
@@ -14,15 +17,21 @@ mod expiration;
 mod hash_value_metadata;
 mod list_value_metadata;
 mod primary_key_metadata;
+mod set_value_metadata;
+mod stream_value_metadata;
 mod string_value_metadata;
 mod value_metadata;
+mod zset_value_metadata;
 
 pub use encoding::Encoding;
 pub use expiration::Expiration;
 
-pub use hash_value_metadata::{HashFieldKey, HashValueMetadata};
+pub use hash_value_metadata::{HashFieldKey, HashFieldValue, HashValueMetadata};
 #[allow(unused_imports)]
 pub use list_value_metadata::ListValueMetadata;
 pub use primary_key_metadata::PrimaryKeyMetadata;
+pub use set_value_metadata::{SetMemberKey, SetValueMetadata};
+pub use stream_value_metadata::{StreamEntryKey, StreamId, StreamValueMetadata};
 pub use string_value_metadata::StringValueMetadata;
 pub use value_metadata::{CommonValueMetadata, ValueTypeIs};
+pub use zset_value_metadata::{ZSetMemberKey, ZSetScoreKey, ZSetValueMetadata};
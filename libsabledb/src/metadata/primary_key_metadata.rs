@@ -78,6 +78,26 @@ impl KeyMetadata {
         encoded_key
     }
 
+    /// Return the prefix shared by all the primary keys of database `db_id`
+    pub fn primary_key_prefix(db_id: u16) -> BytesMut {
+        let mut prefix =
+            BytesMut::with_capacity(std::mem::size_of::<u8>() + std::mem::size_of::<u16>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut prefix);
+        builder.write_u8(KeyMetadata::KEY_PRIMARY);
+        builder.write_u16(db_id);
+        prefix
+    }
+
+    /// Return the prefix shared by all the primary keys of database `db_id` that belong to `slot`
+    pub fn primary_key_slot_prefix(db_id: u16, slot: u16) -> BytesMut {
+        let mut prefix = BytesMut::with_capacity(KeyMetadata::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut prefix);
+        builder.write_u8(KeyMetadata::KEY_PRIMARY);
+        builder.write_u16(db_id);
+        builder.write_u16(slot);
+        prefix
+    }
+
     /// Given an encoded key, return its metadata and the user content
     pub fn from_raw(encoded_key: &BytesMut) -> Result<(KeyMetadata, BytesMut), SableError> {
         let (pk_bytes, user_bytes) = encoded_key.split_at(KeyMetadata::SIZE);
@@ -85,6 +105,11 @@ impl KeyMetadata {
         Ok((pk, BytesMut::from(user_bytes)))
     }
 
+    /// Return the database ID
+    pub fn db_id(&self) -> u16 {
+        self.db_id
+    }
+
     pub fn is_primary_key(&self) -> bool {
         self.key_type == KeyMetadata::KEY_PRIMARY
     }
@@ -0,0 +1,165 @@
+use crate::{
+    metadata::CommonValueMetadata, metadata::Encoding, Expiration, SableError, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+
+/// Contains information about the set item
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetValueMetadata {
+    common: CommonValueMetadata,
+    set_id: u64,
+    set_size: u64,
+}
+
+#[allow(dead_code)]
+impl SetValueMetadata {
+    pub const SIZE: usize = 2 * std::mem::size_of::<u64>() + CommonValueMetadata::SIZE;
+
+    pub fn with_id(set_id: u64) -> Self {
+        SetValueMetadata {
+            common: CommonValueMetadata::default().set_set(),
+            set_id,
+            set_size: 0,
+        }
+    }
+
+    pub fn expiration(&self) -> &Expiration {
+        self.common.expiration()
+    }
+
+    pub fn expiration_mut(&mut self) -> &mut Expiration {
+        self.common.expiration_mut()
+    }
+
+    /// Return the number of members owned by this set
+    pub fn len(&self) -> u64 {
+        self.set_size
+    }
+
+    /// Equivalent to `len() == 0`
+    pub fn is_empty(&self) -> bool {
+        self.set_size.eq(&0u64)
+    }
+
+    /// Return the set unique ID
+    pub fn id(&self) -> u64 {
+        self.set_id
+    }
+
+    pub fn incr_len_by(&mut self, diff: u64) {
+        self.set_size = self.set_size.saturating_add(diff);
+    }
+
+    pub fn decr_len_by(&mut self, diff: u64) {
+        self.set_size = self.set_size.saturating_sub(diff);
+    }
+
+    /// Serialise the set value metadata into bytes
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        self.common.to_bytes(builder);
+        builder.write_u64(self.set_id);
+        builder.write_u64(self.set_size);
+    }
+
+    pub fn from_bytes(reader: &mut U8ArrayReader) -> Result<Self, SableError> {
+        let common = CommonValueMetadata::from_bytes(reader)?;
+
+        let set_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let set_size = reader.read_u64().ok_or(SableError::SerialisationError)?;
+
+        Ok(SetValueMetadata {
+            common,
+            set_id,
+            set_size,
+        })
+    }
+
+    /// Create a prefix for iterating all members belonged to this set
+    pub fn prefix(&self) -> BytesMut {
+        let mut buffer =
+            BytesMut::with_capacity(std::mem::size_of::<u8>() + std::mem::size_of::<u64>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Encoding::KEY_SET_ITEM);
+        builder.write_u64(self.id());
+        buffer
+    }
+}
+
+/// The key of a set member: `[KEY_SET_ITEM | set ID | member]`. The record has no value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetMemberKey<'a> {
+    kind: u8,
+    set_id: u64,
+    member: &'a [u8],
+}
+
+impl<'a> SetMemberKey<'a> {
+    pub fn with_member(set_id: u64, member: &'a [u8]) -> Self {
+        SetMemberKey {
+            kind: Encoding::KEY_SET_ITEM,
+            set_id,
+            member,
+        }
+    }
+
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        builder.write_u8(self.kind);
+        builder.write_u64(self.set_id);
+        builder.write_bytes(self.member);
+    }
+
+    pub fn from_bytes(buff: &'a [u8]) -> Result<Self, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(buff);
+        let kind = reader.read_u8().ok_or(SableError::SerialisationError)?;
+        let set_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let (_, member) = buff.split_at(reader.consumed());
+        Ok(SetMemberKey {
+            kind,
+            set_id,
+            member,
+        })
+    }
+
+    pub fn set_id(&self) -> u64 {
+        self.set_id
+    }
+
+    pub fn member(&self) -> &[u8] {
+        self.member
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_set_member_key_serialization() -> Result<(), SableError> {
+        let member = BytesMut::from("member");
+        let member_key = SetMemberKey::with_member(42, &member);
+        assert_eq!(member_key.member(), b"member");
+        assert_eq!(member_key.set_id(), 42);
+        assert_eq!(member_key.kind, Encoding::KEY_SET_ITEM);
+
+        let mut buffer = BytesMut::with_capacity(256);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        member_key.to_bytes(&mut builder);
+
+        // the member key is prefixed by the set prefix
+        assert!(buffer.starts_with(&SetValueMetadata::with_id(42).prefix()));
+
+        let deserialised = SetMemberKey::from_bytes(&buffer).unwrap();
+        assert_eq!(deserialised, member_key);
+        Ok(())
+    }
+}
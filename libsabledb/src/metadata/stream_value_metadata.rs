@@ -0,0 +1,275 @@
+use crate::{
+    metadata::CommonValueMetadata, metadata::Encoding, Expiration, SableError, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+
+/// A stream entry ID: `<ms>-<seq>`. IDs are ordered by `ms` and then by `seq`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StreamId {
+    ms: u64,
+    seq: u64,
+}
+
+impl StreamId {
+    /// The smallest possible ID (`-`)
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    /// The largest possible ID (`+`)
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    pub fn ms(&self) -> u64 {
+        self.ms
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Parse an ID in the form of `<ms>-<seq>` or `<ms>`. When the sequence part is omitted,
+    /// `default_seq` is used
+    pub fn parse(id: &[u8], default_seq: u64) -> Option<Self> {
+        let id = std::str::from_utf8(id).ok()?;
+        match id.split_once('-') {
+            Some((ms, seq)) => Some(StreamId {
+                ms: ms.parse::<u64>().ok()?,
+                seq: seq.parse::<u64>().ok()?,
+            }),
+            None => Some(StreamId {
+                ms: id.parse::<u64>().ok()?,
+                seq: default_seq,
+            }),
+        }
+    }
+
+    /// Return the smallest ID that is greater than this one, `None` if this is `StreamId::MAX`
+    pub fn next(&self) -> Option<Self> {
+        if self.seq < u64::MAX {
+            Some(StreamId::new(self.ms, self.seq + 1))
+        } else if self.ms < u64::MAX {
+            Some(StreamId::new(self.ms + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    /// Return the ID as `<ms>-<seq>`
+    pub fn to_bytes(&self) -> BytesMut {
+        BytesMut::from(self.to_string().as_str())
+    }
+}
+
+impl std::fmt::Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// Contains information about the stream item
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamValueMetadata {
+    common: CommonValueMetadata,
+    stream_id: u64,
+    stream_size: u64,
+    /// The ID of the last entry ever added to the stream. New entries must have a greater ID
+    last_id: StreamId,
+}
+
+#[allow(dead_code)]
+impl StreamValueMetadata {
+    pub const SIZE: usize = 4 * std::mem::size_of::<u64>() + CommonValueMetadata::SIZE;
+
+    pub fn with_id(stream_id: u64) -> Self {
+        StreamValueMetadata {
+            common: CommonValueMetadata::default().set_stream(),
+            stream_id,
+            stream_size: 0,
+            last_id: StreamId::MIN,
+        }
+    }
+
+    pub fn expiration(&self) -> &Expiration {
+        self.common.expiration()
+    }
+
+    pub fn expiration_mut(&mut self) -> &mut Expiration {
+        self.common.expiration_mut()
+    }
+
+    /// Return the number of entries owned by this stream
+    pub fn len(&self) -> u64 {
+        self.stream_size
+    }
+
+    /// Equivalent to `len() == 0`
+    pub fn is_empty(&self) -> bool {
+        self.stream_size.eq(&0u64)
+    }
+
+    /// Return the stream unique ID
+    pub fn id(&self) -> u64 {
+        self.stream_id
+    }
+
+    /// Return the ID of the last entry added to the stream
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    pub fn set_last_id(&mut self, last_id: StreamId) {
+        self.last_id = last_id;
+    }
+
+    pub fn incr_len_by(&mut self, diff: u64) {
+        self.stream_size = self.stream_size.saturating_add(diff);
+    }
+
+    pub fn decr_len_by(&mut self, diff: u64) {
+        self.stream_size = self.stream_size.saturating_sub(diff);
+    }
+
+    /// Serialise the stream value metadata into bytes
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        self.common.to_bytes(builder);
+        builder.write_u64(self.stream_id);
+        builder.write_u64(self.stream_size);
+        builder.write_u64(self.last_id.ms);
+        builder.write_u64(self.last_id.seq);
+    }
+
+    pub fn from_bytes(reader: &mut U8ArrayReader) -> Result<Self, SableError> {
+        let common = CommonValueMetadata::from_bytes(reader)?;
+
+        let stream_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let stream_size = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let last_id_ms = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let last_id_seq = reader.read_u64().ok_or(SableError::SerialisationError)?;
+
+        Ok(StreamValueMetadata {
+            common,
+            stream_id,
+            stream_size,
+            last_id: StreamId::new(last_id_ms, last_id_seq),
+        })
+    }
+
+    /// Create a prefix for iterating all entries belonged to this stream
+    pub fn prefix(&self) -> BytesMut {
+        let mut buffer =
+            BytesMut::with_capacity(std::mem::size_of::<u8>() + std::mem::size_of::<u64>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Encoding::KEY_STREAM_ITEM);
+        builder.write_u64(self.id());
+        buffer
+    }
+}
+
+/// The key of a stream entry: `[KEY_STREAM_ITEM | stream ID | ms | seq]`. The integers are
+/// written big-endian, so the entries of a stream are sorted by their ID
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamEntryKey {
+    kind: u8,
+    stream_id: u64,
+    entry_id: StreamId,
+}
+
+impl StreamEntryKey {
+    pub const SIZE: usize = std::mem::size_of::<u8>() + 3 * std::mem::size_of::<u64>();
+
+    pub fn with_entry_id(stream_id: u64, entry_id: StreamId) -> Self {
+        StreamEntryKey {
+            kind: Encoding::KEY_STREAM_ITEM,
+            stream_id,
+            entry_id,
+        }
+    }
+
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        builder.write_u8(self.kind);
+        builder.write_u64(self.stream_id);
+        builder.write_u64(self.entry_id.ms);
+        builder.write_u64(self.entry_id.seq);
+    }
+
+    pub fn from_bytes(buff: &[u8]) -> Result<Self, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(buff);
+        let kind = reader.read_u8().ok_or(SableError::SerialisationError)?;
+        let stream_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let ms = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let seq = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        Ok(StreamEntryKey {
+            kind,
+            stream_id,
+            entry_id: StreamId::new(ms, seq),
+        })
+    }
+
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
+    pub fn entry_id(&self) -> StreamId {
+        self.entry_id
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_stream_entry_key_serialization() -> Result<(), SableError> {
+        let entry_key = StreamEntryKey::with_entry_id(42, StreamId::new(1000, 7));
+        assert_eq!(entry_key.stream_id(), 42);
+        assert_eq!(entry_key.entry_id(), StreamId::new(1000, 7));
+        assert_eq!(entry_key.kind, Encoding::KEY_STREAM_ITEM);
+
+        let mut buffer = BytesMut::with_capacity(StreamEntryKey::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        entry_key.to_bytes(&mut builder);
+        assert_eq!(buffer.len(), StreamEntryKey::SIZE);
+
+        // the entry key is prefixed by the stream prefix
+        assert!(buffer.starts_with(&StreamValueMetadata::with_id(42).prefix()));
+
+        let deserialised = StreamEntryKey::from_bytes(&buffer).unwrap();
+        assert_eq!(deserialised, entry_key);
+
+        // keys sort by their entry ID
+        let mut smaller = BytesMut::with_capacity(StreamEntryKey::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut smaller);
+        StreamEntryKey::with_entry_id(42, StreamId::new(999, 8)).to_bytes(&mut builder);
+        assert!(smaller < buffer);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_stream_id_parse() {
+        assert_eq!(StreamId::parse(b"5-3", 0), Some(StreamId::new(5, 3)));
+        assert_eq!(StreamId::parse(b"5", 0), Some(StreamId::new(5, 0)));
+        assert_eq!(
+            StreamId::parse(b"5", u64::MAX),
+            Some(StreamId::new(5, u64::MAX))
+        );
+        assert_eq!(StreamId::parse(b"5-", 0), None);
+        assert_eq!(StreamId::parse(b"a-1", 0), None);
+        assert_eq!(StreamId::new(5, 3).to_bytes(), BytesMut::from("5-3"));
+        assert_eq!(StreamId::new(5, u64::MAX).next(), Some(StreamId::new(6, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{metadata::Encoding, Expiration, SableError, U8ArrayBuilder, U8ArrayReader};
+use crate::{metadata::Encoding, Expiration, SableError, TimeUtils, U8ArrayBuilder, U8ArrayReader};
 
 /// Contains information regarding the String type metadata
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -6,36 +6,99 @@ pub struct CommonValueMetadata {
     value_encoding: u8,
     /// Value ttl information
     expiration: Expiration,
+    /// Last access time, ms since UNIX_EPOCH. Set when the value is created and updated by `TOUCH`
+    accessed_at: u64,
+    /// The logarithmic access counter used by the LFU eviction policy (`OBJECT FREQ`). The live
+    /// counter is kept by `MemoryTracker`, which starts from this value
+    frequency: u8,
+    /// When `frequency` was last decayed, ms since UNIX_EPOCH
+    frequency_decayed_at: u64,
 }
 
 impl Default for CommonValueMetadata {
     fn default() -> Self {
+        let now = TimeUtils::epoch_ms().unwrap_or_default();
         CommonValueMetadata {
             value_encoding: Encoding::VALUE_STRING,
             expiration: Expiration::default(),
+            accessed_at: now,
+            frequency: Self::LFU_INIT_VAL,
+            frequency_decayed_at: now,
         }
     }
 }
 
 #[allow(dead_code)]
 impl CommonValueMetadata {
-    pub const SIZE: usize = std::mem::size_of::<u8>() + Expiration::SIZE;
+    pub const SIZE: usize = std::mem::size_of::<u8>()
+        + Expiration::SIZE
+        + std::mem::size_of::<u64>()
+        + std::mem::size_of::<u8>()
+        + std::mem::size_of::<u64>();
+    /// The size of the first layout: the value encoding and the expiration, without the
+    /// access time and the LFU counter
+    pub const SIZE_V0: usize = std::mem::size_of::<u8>() + Expiration::SIZE;
+    /// The access counter of a new value (same as Redis), so a new key is not the first one
+    /// evicted by the LFU policy
+    pub const LFU_INIT_VAL: u8 = 5;
+    /// The layout version is kept in the high nibble of the first byte, the value encoding in
+    /// the low nibble. Records written before the layout was versioned are version 0
+    const VERSION_SHIFT: u8 = 4;
+    const ENCODING_MASK: u8 = 0x0F;
+    /// Version 1 added the access time and the LFU counter
+    const VERSION: u8 = 1;
 
-    /// Serialise this object into `BytesMut`
+    /// Serialise this object into `BytesMut`. The current layout is always written, so records
+    /// of an older layout are upgraded the next time they are written
     pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
-        builder.write_u8(self.value_encoding);
+        builder.write_u8((Self::VERSION << Self::VERSION_SHIFT) | self.value_encoding);
         self.expiration.to_bytes(builder);
+        builder.write_u64(self.accessed_at);
+        builder.write_u8(self.frequency);
+        builder.write_u64(self.frequency_decayed_at);
     }
 
+    /// Deserialise the metadata, in any of the layout versions. The number of bytes used by
+    /// the metadata is `reader.consumed()` afterwards
     pub fn from_bytes(reader: &mut U8ArrayReader) -> Result<Self, SableError> {
-        let Some(value_type) = reader.read_u8() else {
+        let Some(first_byte) = reader.read_u8() else {
             return Err(SableError::SerialisationError);
         };
+        let value_type = first_byte & Self::ENCODING_MASK;
 
         let expiration = Expiration::from_bytes(reader)?;
+        match first_byte >> Self::VERSION_SHIFT {
+            0 => {
+                // no access statistics yet: the last update is the best guess for the last
+                // access
+                let now = TimeUtils::epoch_ms().unwrap_or_default();
+                return Ok(CommonValueMetadata {
+                    value_encoding: value_type,
+                    accessed_at: expiration.last_updated,
+                    expiration,
+                    frequency: Self::LFU_INIT_VAL,
+                    frequency_decayed_at: now,
+                });
+            }
+            Self::VERSION => {}
+            _ => return Err(SableError::SerialisationError),
+        }
+
+        let Some(accessed_at) = reader.read_u64() else {
+            return Err(SableError::SerialisationError);
+        };
+        let Some(frequency) = reader.read_u8() else {
+            return Err(SableError::SerialisationError);
+        };
+        let Some(frequency_decayed_at) = reader.read_u64() else {
+            return Err(SableError::SerialisationError);
+        };
         Ok(CommonValueMetadata {
             value_encoding: value_type,
             expiration,
+            accessed_at,
+            frequency,
+            frequency_decayed_at,
         })
     }
 
@@ -47,6 +110,30 @@ impl CommonValueMetadata {
         &mut self.expiration
     }
 
+    /// Last access time, ms since UNIX_EPOCH
+    pub fn accessed_at(&self) -> u64 {
+        self.accessed_at
+    }
+
+    pub fn set_accessed_at(&mut self, accessed_at: u64) {
+        self.accessed_at = accessed_at;
+    }
+
+    /// The LFU access counter, as it was when `frequency_decayed_at` was set
+    pub fn frequency(&self) -> u8 {
+        self.frequency
+    }
+
+    /// When the LFU access counter was last decayed, ms since UNIX_EPOCH
+    pub fn frequency_decayed_at(&self) -> u64 {
+        self.frequency_decayed_at
+    }
+
+    pub fn set_frequency(&mut self, frequency: u8, decayed_at: u64) {
+        self.frequency = frequency;
+        self.frequency_decayed_at = decayed_at;
+    }
+
     pub fn is_string(&self) -> bool {
         self.value_encoding == Encoding::VALUE_STRING
     }
@@ -59,6 +146,18 @@ impl CommonValueMetadata {
         self.value_encoding == Encoding::VALUE_HASH
     }
 
+    pub fn is_set(&self) -> bool {
+        self.value_encoding == Encoding::VALUE_SET
+    }
+
+    pub fn is_zset(&self) -> bool {
+        self.value_encoding == Encoding::VALUE_ZSET
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.value_encoding == Encoding::VALUE_STREAM
+    }
+
     pub fn value_type(&self) -> u8 {
         self.value_encoding
     }
@@ -77,6 +176,21 @@ impl CommonValueMetadata {
         self.value_encoding = Encoding::VALUE_HASH;
         self
     }
+
+    pub fn set_set(mut self) -> Self {
+        self.value_encoding = Encoding::VALUE_SET;
+        self
+    }
+
+    pub fn set_zset(mut self) -> Self {
+        self.value_encoding = Encoding::VALUE_ZSET;
+        self
+    }
+
+    pub fn set_stream(mut self) -> Self {
+        self.value_encoding = Encoding::VALUE_STREAM;
+        self
+    }
 }
 
 pub trait ValueTypeIs {
@@ -102,6 +216,8 @@ mod test {
         let mut arr = bytes::BytesMut::with_capacity(CommonValueMetadata::SIZE);
         let mut builder = U8ArrayBuilder::with_buffer(&mut arr);
         md.expiration_mut().set_ttl_millis(30)?;
+        md.set_accessed_at(1234);
+        md.set_frequency(42, 5678);
 
         md.to_bytes(&mut builder);
         assert_eq!(arr.len(), CommonValueMetadata::SIZE);
@@ -128,10 +244,42 @@ mod test {
             deserialized_md,
         );
         assert!(deserialized_md.expiration().is_expired()? == false);
+        assert_eq!(deserialized_md.accessed_at(), 1234);
+        assert_eq!(deserialized_md.frequency(), 42);
+        assert_eq!(deserialized_md.frequency_decayed_at(), 5678);
         assert_eq!(&arr[..], &[5, 5]);
         Ok(())
     }
 
+    #[test]
+    fn test_read_version_0() -> Result<(), SableError> {
+        // a hash record written before the layout was versioned: encoding, expiration, then
+        // the hash fields
+        let mut arr = bytes::BytesMut::new();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut arr);
+        builder.write_u8(Encoding::VALUE_HASH);
+        builder.write_u64(1234);
+        builder.write_u64(u64::MAX);
+        builder.write_u64(42);
+
+        let mut reader = U8ArrayReader::with_buffer(&arr);
+        let md = CommonValueMetadata::from_bytes(&mut reader)?;
+        assert_eq!(reader.consumed(), CommonValueMetadata::SIZE_V0);
+        assert!(md.is_hash());
+        assert!(!md.expiration().has_ttl());
+        assert_eq!(md.accessed_at(), 1234);
+        assert_eq!(md.frequency(), CommonValueMetadata::LFU_INIT_VAL);
+        assert_eq!(reader.read_u64(), Some(42));
+
+        // it is written back with the current layout
+        let mut upgraded = bytes::BytesMut::new();
+        md.to_bytes(&mut U8ArrayBuilder::with_buffer(&mut upgraded));
+        assert_eq!(upgraded.len(), CommonValueMetadata::SIZE);
+        let mut reader = U8ArrayReader::with_buffer(&upgraded);
+        assert_eq!(CommonValueMetadata::from_bytes(&mut reader)?, md);
+        Ok(())
+    }
+
     #[test]
     fn test_expire_api() -> Result<(), SableError> {
         let mut md = CommonValueMetadata::default();
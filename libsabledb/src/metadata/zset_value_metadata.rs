@@ -0,0 +1,299 @@
+use crate::{
+    metadata::CommonValueMetadata, metadata::Encoding, Expiration, SableError, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+
+/// Contains information about the sorted set item.
+///
+/// Every member of a sorted set is stored twice, both records share the sorted set prefix
+/// (see `ZSetValueMetadata::prefix`):
+/// - `[KEY_ZSET_ITEM | zset ID | MEMBER_INDEX | member]` => `score`
+/// - `[KEY_ZSET_ITEM | zset ID | SCORE_INDEX | encoded score | member]` => `()`
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZSetValueMetadata {
+    common: CommonValueMetadata,
+    zset_id: u64,
+    zset_size: u64,
+}
+
+#[allow(dead_code)]
+impl ZSetValueMetadata {
+    pub const SIZE: usize = 2 * std::mem::size_of::<u64>() + CommonValueMetadata::SIZE;
+    /// Index family: member -> score
+    pub const MEMBER_INDEX: u8 = 0u8;
+    /// Index family: score + member (ordered by score, then by member)
+    pub const SCORE_INDEX: u8 = 1u8;
+
+    pub fn with_id(zset_id: u64) -> Self {
+        ZSetValueMetadata {
+            common: CommonValueMetadata::default().set_zset(),
+            zset_id,
+            zset_size: 0,
+        }
+    }
+
+    pub fn expiration(&self) -> &Expiration {
+        self.common.expiration()
+    }
+
+    pub fn expiration_mut(&mut self) -> &mut Expiration {
+        self.common.expiration_mut()
+    }
+
+    /// Return the number of members owned by this sorted set
+    pub fn len(&self) -> u64 {
+        self.zset_size
+    }
+
+    /// Equivalent to `len() == 0`
+    pub fn is_empty(&self) -> bool {
+        self.zset_size.eq(&0u64)
+    }
+
+    /// Return the sorted set unique ID
+    pub fn id(&self) -> u64 {
+        self.zset_id
+    }
+
+    pub fn incr_len_by(&mut self, diff: u64) {
+        self.zset_size = self.zset_size.saturating_add(diff);
+    }
+
+    pub fn decr_len_by(&mut self, diff: u64) {
+        self.zset_size = self.zset_size.saturating_sub(diff);
+    }
+
+    /// Serialise the sorted set value metadata into bytes
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        self.common.to_bytes(builder);
+        builder.write_u64(self.zset_id);
+        builder.write_u64(self.zset_size);
+    }
+
+    pub fn from_bytes(reader: &mut U8ArrayReader) -> Result<Self, SableError> {
+        let common = CommonValueMetadata::from_bytes(reader)?;
+
+        let zset_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let zset_size = reader.read_u64().ok_or(SableError::SerialisationError)?;
+
+        Ok(ZSetValueMetadata {
+            common,
+            zset_id,
+            zset_size,
+        })
+    }
+
+    /// Create a prefix for iterating all the records (both index families) owned by this
+    /// sorted set
+    pub fn prefix(&self) -> BytesMut {
+        let mut buffer =
+            BytesMut::with_capacity(std::mem::size_of::<u8>() + std::mem::size_of::<u64>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Encoding::KEY_ZSET_ITEM);
+        builder.write_u64(self.id());
+        buffer
+    }
+
+    /// Create a prefix for iterating the members of this sorted set, ordered by score
+    pub fn scores_prefix(&self) -> BytesMut {
+        let mut buffer = self.prefix();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u8(Self::SCORE_INDEX);
+        buffer
+    }
+}
+
+/// The key of the member -> score index. The record value is the member's score
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZSetMemberKey<'a> {
+    zset_id: u64,
+    member: &'a [u8],
+}
+
+impl<'a> ZSetMemberKey<'a> {
+    pub fn with_member(zset_id: u64, member: &'a [u8]) -> Self {
+        ZSetMemberKey { zset_id, member }
+    }
+
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        builder.write_u8(Encoding::KEY_ZSET_ITEM);
+        builder.write_u64(self.zset_id);
+        builder.write_u8(ZSetValueMetadata::MEMBER_INDEX);
+        builder.write_bytes(self.member);
+    }
+
+    /// Serialise a score into the member record value
+    pub fn score_to_bytes(score: f64, builder: &mut U8ArrayBuilder) {
+        builder.write_u64(score.to_bits());
+    }
+
+    /// Read a score from the member record value
+    pub fn score_from_bytes(buff: &[u8]) -> Result<f64, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(buff);
+        let bits = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        Ok(f64::from_bits(bits))
+    }
+
+    pub fn zset_id(&self) -> u64 {
+        self.zset_id
+    }
+
+    pub fn member(&self) -> &[u8] {
+        self.member
+    }
+}
+
+/// The key of the score index: `[KEY_ZSET_ITEM | zset ID | SCORE_INDEX | score | member]`.
+/// The score is encoded so the keys sort by score (numerically) and then by member bytes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZSetScoreKey<'a> {
+    zset_id: u64,
+    score: f64,
+    member: &'a [u8],
+}
+
+impl<'a> ZSetScoreKey<'a> {
+    pub fn with_score(zset_id: u64, score: f64, member: &'a [u8]) -> Self {
+        ZSetScoreKey {
+            zset_id,
+            score,
+            member,
+        }
+    }
+
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self, builder: &mut U8ArrayBuilder) {
+        builder.write_u8(Encoding::KEY_ZSET_ITEM);
+        builder.write_u64(self.zset_id);
+        builder.write_u8(ZSetValueMetadata::SCORE_INDEX);
+        builder.write_u64(Self::encode_score(self.score));
+        builder.write_bytes(self.member);
+    }
+
+    pub fn from_bytes(buff: &'a [u8]) -> Result<Self, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(buff);
+        let _kind = reader.read_u8().ok_or(SableError::SerialisationError)?;
+        let zset_id = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let _index = reader.read_u8().ok_or(SableError::SerialisationError)?;
+        let score = reader.read_u64().ok_or(SableError::SerialisationError)?;
+        let (_, member) = buff.split_at(reader.consumed());
+        Ok(ZSetScoreKey {
+            zset_id,
+            score: Self::decode_score(score),
+            member,
+        })
+    }
+
+    /// Map a score into a `u64` whose big-endian bytes sort in the same order as the score.
+    /// Positive numbers get their sign bit flipped, negative numbers get all their bits
+    /// flipped (so "more negative" sorts first)
+    pub fn encode_score(score: f64) -> u64 {
+        // -0.0 and 0.0 are the same score
+        let score = if score == 0.0 { 0.0 } else { score };
+        let bits = score.to_bits();
+        if bits & (1u64 << 63) != 0 {
+            !bits
+        } else {
+            bits ^ (1u64 << 63)
+        }
+    }
+
+    /// The reverse of `encode_score`
+    pub fn decode_score(encoded: u64) -> f64 {
+        let bits = if encoded & (1u64 << 63) != 0 {
+            encoded ^ (1u64 << 63)
+        } else {
+            !encoded
+        };
+        f64::from_bits(bits)
+    }
+
+    pub fn zset_id(&self) -> u64 {
+        self.zset_id
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn member(&self) -> &[u8] {
+        self.member
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_key(score: f64, member: &str) -> BytesMut {
+        let mut buffer = BytesMut::with_capacity(64);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        ZSetScoreKey::with_score(1, score, member.as_bytes()).to_bytes(&mut builder);
+        buffer
+    }
+
+    #[test]
+    pub fn test_score_encoding_is_ordered() {
+        let scores = [
+            f64::NEG_INFINITY,
+            f64::MIN,
+            -1000.5,
+            -1.0,
+            -f64::MIN_POSITIVE,
+            0.0,
+            f64::MIN_POSITIVE,
+            0.5,
+            1.0,
+            1000.5,
+            f64::MAX,
+            f64::INFINITY,
+        ];
+        for pair in scores.windows(2) {
+            assert!(score_key(pair[0], "a") < score_key(pair[1], "a"));
+        }
+
+        for score in scores {
+            let encoded = ZSetScoreKey::encode_score(score);
+            assert_eq!(ZSetScoreKey::decode_score(encoded), score);
+        }
+
+        // -0.0 and 0.0 are the same score
+        assert_eq!(score_key(-0.0, "a"), score_key(0.0, "a"));
+    }
+
+    #[test]
+    pub fn test_equal_scores_are_ordered_by_member() {
+        assert!(score_key(1.0, "a") < score_key(1.0, "b"));
+        assert!(score_key(1.0, "a") < score_key(1.0, "aa"));
+        assert!(score_key(1.0, "zzz") < score_key(2.0, "a"));
+    }
+
+    #[test]
+    pub fn test_zset_keys_serialization() -> Result<(), SableError> {
+        let zset = ZSetValueMetadata::with_id(42);
+        let buffer = score_key(-3.5, "member");
+        assert!(buffer.starts_with(&ZSetValueMetadata::with_id(1).scores_prefix()));
+
+        let deserialised = ZSetScoreKey::from_bytes(&buffer)?;
+        assert_eq!(deserialised.zset_id(), 1);
+        assert_eq!(deserialised.score(), -3.5);
+        assert_eq!(deserialised.member(), b"member");
+
+        let mut buffer = BytesMut::with_capacity(64);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        ZSetMemberKey::with_member(42, b"member").to_bytes(&mut builder);
+        assert!(buffer.starts_with(&zset.prefix()));
+        assert!(!buffer.starts_with(&zset.scores_prefix()));
+        Ok(())
+    }
+}
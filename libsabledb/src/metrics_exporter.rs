@@ -0,0 +1,176 @@
+use crate::{SableError, ServerState, Telemetry};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The largest HTTP request header accepted by the exporter
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// How long a scraper has to send its request
+const REQUEST_TIMEOUT_MS: u64 = 5000;
+
+/// Serves the telemetry over HTTP (`GET /metrics`) in Prometheus text format.
+///
+/// The exporter only reads the shared telemetry: it takes a copy while holding the lock and
+/// formats and writes the response after releasing it
+pub struct MetricsExporter {}
+
+impl MetricsExporter {
+    /// Start the exporter on its own thread (and tokio runtime), listening on `address`
+    pub fn run(address: String, server_state: Arc<ServerState>) -> Result<(), SableError> {
+        let _ = std::thread::Builder::new()
+            .name("Metrics".to_string())
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .thread_name("Metrics")
+                    .build()
+                    .unwrap_or_else(|e| {
+                        panic!("failed to create tokio runtime. {:?}", e);
+                    });
+
+                rt.block_on(async move {
+                    let listener = match TcpListener::bind(&address).await {
+                        Ok(listener) => listener,
+                        Err(e) => {
+                            tracing::error!("Failed to bind metrics address {}. {:?}", address, e);
+                            return;
+                        }
+                    };
+                    tracing::info!("Metrics exporter started on address: {}", address);
+                    Self::serve(listener, server_state.shared_telemetry()).await;
+                });
+            })?;
+        Ok(())
+    }
+
+    /// Accept scrapers connections forever
+    pub async fn serve(listener: TcpListener, telemetry: Arc<Mutex<Telemetry>>) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept metrics connection. {:?}", e);
+                    continue;
+                }
+            };
+
+            let telemetry = telemetry.clone();
+            tokio::spawn(async move {
+                if let Err(e) = Self::handle_connection(stream, telemetry).await {
+                    tracing::debug!("Metrics connection error. {:?}", e);
+                }
+            });
+        }
+    }
+
+    /// Serve a single request and close the connection
+    async fn handle_connection(
+        mut stream: TcpStream,
+        telemetry: Arc<Mutex<Telemetry>>,
+    ) -> Result<(), SableError> {
+        let mut request = Vec::<u8>::with_capacity(1024);
+        let mut buffer = [0u8; 1024];
+        let read_header = async {
+            while !request.windows(4).any(|window| window == b"\r\n\r\n")
+                && request.len() < MAX_REQUEST_SIZE
+            {
+                let count = stream.read(&mut buffer).await?;
+                if count == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..count]);
+            }
+            Ok::<(), std::io::Error>(())
+        };
+        if tokio::time::timeout(
+            tokio::time::Duration::from_millis(REQUEST_TIMEOUT_MS),
+            read_header,
+        )
+        .await
+        .is_err()
+        {
+            return Ok(());
+        }
+
+        let request_line = String::from_utf8_lossy(&request);
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => {
+                // copy the telemetry, the lock is released before anything is written
+                let snapshot = telemetry.lock().expect("poisoned mutex").clone();
+                ("200 OK", snapshot.prometheus_metrics())
+            }
+            (Some("GET"), _) => ("404 Not Found", "Not Found\n".to_string()),
+            _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn http_get(address: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn test_metrics_endpoint() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let telemetry = Arc::new(Mutex::new(Telemetry {
+                connections_opened: 5,
+                connections_closed: 2,
+                total_io_read_calls: 42,
+                total_io_write_calls: 7,
+                ..Default::default()
+            }));
+
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(MetricsExporter::serve(listener, telemetry.clone()));
+
+            let response = http_get(address, "/metrics").await;
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.contains(
+                "# HELP sabledb_io_read_calls_total Number of storage read calls\n# TYPE sabledb_io_read_calls_total counter\nsabledb_io_read_calls_total 42\n"
+            ));
+            assert!(response.contains("\nsabledb_io_write_calls_total 7\n"));
+            assert!(response.contains("\nsabledb_connections_opened_total 5\n"));
+            assert!(response.contains(
+                "# TYPE sabledb_connected_clients gauge\nsabledb_connected_clients 3\n"
+            ));
+
+            // the exporter reads the current values on every scrape
+            telemetry.lock().unwrap().total_io_read_calls = 100;
+            let response = http_get(address, "/metrics").await;
+            assert!(response.contains("\nsabledb_io_read_calls_total 100\n"));
+
+            let response = http_get(address, "/").await;
+            assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        });
+    }
+}
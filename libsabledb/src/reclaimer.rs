@@ -0,0 +1,151 @@
+use crate::{storage::GenericDb, SableError, ServerState, StorageAdapter};
+use std::sync::Arc;
+
+/// The number of item records deleted in a single batch
+const RECLAIM_BATCH_SIZE: usize = 1000;
+
+/// How often (in milliseconds) the reclaim queue is checked for new work
+const RECLAIM_INTERVAL_MS: u64 = 10;
+
+/// Deletes, in the background, the items (hash fields, list items etc) of values that were
+/// removed with `UNLINK` or found expired by a reader.
+///
+/// Both delete the primary record of the key (so the key is gone right away) and queue the
+/// prefix of the items it owned on the `ServerState` (which shares its queue with the storage). The items are unreachable once the primary
+/// record is gone, so they can be deleted here without taking any lock
+pub struct Reclaimer {
+    store: StorageAdapter,
+    server_state: Arc<ServerState>,
+}
+
+impl Reclaimer {
+    pub fn new(store: StorageAdapter, server_state: Arc<ServerState>) -> Self {
+        Reclaimer {
+            store,
+            server_state,
+        }
+    }
+
+    /// Start the reclaim task on its own thread (and tokio runtime), so it never competes
+    /// with the workers
+    pub fn run(store: StorageAdapter, server_state: Arc<ServerState>) -> Result<(), SableError> {
+        let _ = std::thread::Builder::new()
+            .name("Reclaimer".to_string())
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .thread_name("Reclaimer")
+                    .build()
+                    .unwrap_or_else(|e| {
+                        panic!("failed to create tokio runtime. {:?}", e);
+                    });
+
+                rt.block_on(async move {
+                    let reclaimer = Reclaimer::new(store, server_state);
+                    reclaimer.main_loop().await;
+                });
+            })?;
+        Ok(())
+    }
+
+    async fn main_loop(&self) {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_millis(RECLAIM_INTERVAL_MS)).await;
+            match self.reclaim().await {
+                Ok(0) => {}
+                Ok(count) => {
+                    tracing::debug!("reclaimed {} record(s)", count);
+                }
+                Err(e) => {
+                    tracing::warn!("failed to reclaim deleted items. {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// Drain the reclaim queue. Return the number of records deleted
+    pub async fn reclaim(&self) -> Result<usize, SableError> {
+        // items prefixes are not bound to a database, any database ID will do here
+        let generic_db = GenericDb::with_storage(&self.store, 0);
+        let mut reclaimed = 0usize;
+        while let Some(items_prefix) = self.server_state.pop_reclaim() {
+            loop {
+                let deleted = generic_db.delete_items(&items_prefix, RECLAIM_BATCH_SIZE)?;
+                reclaimed = reclaimed.saturating_add(deleted);
+                if deleted < RECLAIM_BATCH_SIZE {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+        Ok(reclaimed)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::ClientNextAction, Client, RedisCommand};
+    use bytes::BytesMut;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_unlink_reclaims_in_background() -> Result<(), SableError> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let (_guard, store) = crate::tests::open_store();
+            let server_state = Arc::<ServerState>::default();
+            let client = Client::new(server_state.clone(), store.clone(), None);
+
+            let generic_db = GenericDb::with_storage(&store, 0);
+            let mut hash_items_prefix = None;
+            for (args, expected_value) in [
+                (
+                    vec!["hset", "myhash", "f1", "v1", "f2", "v2", "f3", "v3"],
+                    ":3\r\n",
+                ),
+                (vec!["set", "mystr", "value"], "+OK\r\n"),
+                (vec!["unlink", "myhash", "mystr", "no_such_key"], ":2\r\n"),
+                (vec!["exists", "myhash", "mystr"], ":0\r\n"),
+                (vec!["hlen", "myhash"], ":0\r\n"),
+            ] {
+                if args[0] == "unlink" {
+                    let user_key = BytesMut::from("myhash");
+                    hash_items_prefix = generic_db.items_prefix(&user_key).unwrap();
+                }
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_unlink_reclaims_in_background")
+                        .await;
+                let cmd = Rc::new(RedisCommand::for_test(args));
+                let ClientNextAction::NoAction =
+                    Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                        .await
+                        .unwrap()
+                else {
+                    panic!("unexpected client action");
+                };
+                assert_eq!(sink.read_all().await.as_str(), expected_value);
+            }
+
+            // the key is gone, but its fields are still waiting for the reclaimer
+            assert_eq!(server_state.pending_reclaims(), 1);
+            let reclaimer = Reclaimer::new(store.clone(), server_state.clone());
+            assert_eq!(reclaimer.reclaim().await.unwrap(), 3);
+            assert_eq!(server_state.pending_reclaims(), 0);
+            assert_eq!(
+                generic_db
+                    .delete_items(&hash_items_prefix.unwrap(), 10)
+                    .unwrap(),
+                0
+            );
+        });
+        Ok(())
+    }
+}
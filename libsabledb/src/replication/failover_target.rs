@@ -0,0 +1,55 @@
+use crate::replication::{PeerConnection, PeerReply};
+use crate::{BytesMutUtils, ParserError, SableError};
+use bytes::BytesMut;
+
+/// A client connection to the replica promoted by `FAILOVER`. The target is driven with regular
+/// commands: `DEBUG REPL-OFFSET` to follow its progress and `REPLICAOF NO ONE` to promote it
+pub struct FailoverTarget {
+    connection: PeerConnection,
+}
+
+impl FailoverTarget {
+    /// Connect to the replica accepting clients on `host:port`
+    pub async fn connect(host: &str, port: u16) -> Result<Self, SableError> {
+        Ok(FailoverTarget {
+            connection: PeerConnection::connect(host, port).await?,
+        })
+    }
+
+    /// Return the sequence number of the last change the target applied from its primary, or
+    /// `None` if the target is not a replica
+    pub async fn applied_offset(&mut self) -> Result<Option<u64>, SableError> {
+        match self.connection.call(&[b"DEBUG", b"REPL-OFFSET"]).await? {
+            PeerReply::Array(items) => {
+                let mut items = items.into_iter();
+                while let Some(item) = items.next() {
+                    if item == PeerReply::Bulk(Some(BytesMut::from("applied_offset"))) {
+                        return match items.next() {
+                            Some(PeerReply::Integer(offset)) => Ok(Some(offset.max(0) as u64)),
+                            other => Err(Self::unexpected_reply(other)),
+                        };
+                    }
+                }
+                Ok(None)
+            }
+            PeerReply::Error(msg) => Err(SableError::OtherError(BytesMutUtils::to_string(&msg))),
+            other => Err(Self::unexpected_reply(Some(other))),
+        }
+    }
+
+    /// Tell the target to become a primary
+    pub async fn promote(&mut self) -> Result<(), SableError> {
+        match self.connection.call(&[b"REPLICAOF", b"NO", b"ONE"]).await? {
+            PeerReply::Status(_) => Ok(()),
+            PeerReply::Error(msg) => Err(SableError::OtherError(BytesMutUtils::to_string(&msg))),
+            other => Err(Self::unexpected_reply(Some(other))),
+        }
+    }
+
+    fn unexpected_reply(reply: Option<PeerReply>) -> SableError {
+        SableError::Parser(ParserError::ProtocolError(format!(
+            "unexpected reply from failover target {:?}",
+            reply
+        )))
+    }
+}
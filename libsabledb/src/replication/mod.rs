@@ -1,19 +1,30 @@
+mod failover_target;
+mod peer_connection;
+mod replication_backlog;
 mod replication_client;
 mod replication_config;
 mod replication_messages;
 mod replication_server;
+mod replication_stream;
 mod replication_traits;
 mod replicator;
 mod storage_updates;
 
+pub use failover_target::FailoverTarget;
+pub use peer_connection::{PeerConnection, PeerReply};
+pub use replication_backlog::ReplicationBacklog;
 pub use replication_client::{ReplClientCommand, ReplicationClient};
 pub use replication_config::{ReplicationConfig, ServerRole};
-pub use replication_messages::ReplRequest;
-pub use replication_server::{replication_thread_stop_all, ReplicationServer};
-pub use storage_updates::{DeleteRecord, PutRecord, StorageUpdates, StorageUpdatesIterItem};
+pub use replication_messages::{ReplPartialSyncResponse, ReplRequest};
+pub use replication_server::{replication_thread_stop_all, ReplicaAcks, ReplicationServer};
+pub use replication_stream::ReplicationStream;
+pub use storage_updates::{
+    DeleteRangeRecord, DeleteRecord, PutRecord, StorageUpdates, StorageUpdatesIterItem,
+};
 
 pub use replication_traits::{
-    BytesReader, BytesWriter, TcpStreamBytesReader, TcpStreamBytesWriter,
+    BytesReader, BytesWriter, CompressedBytesReader, CompressedBytesWriter, TcpStreamBytesReader,
+    TcpStreamBytesWriter,
 };
 pub use replicator::{ReplicationWorkerMessage, Replicator, ReplicatorContext};
 
@@ -0,0 +1,170 @@
+use crate::{ParserError, RespBuilderV2, SableError};
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The number of array items pre-allocated while parsing a reply. The array length comes from
+/// the peer, the items grow as they are parsed
+const MAX_PREALLOCATED_ITEMS: usize = 1024;
+
+/// A reply read from a peer server (RESP2)
+#[derive(Debug, PartialEq, Eq)]
+pub enum PeerReply {
+    Status(BytesMut),
+    Error(BytesMut),
+    Integer(i64),
+    Bulk(Option<BytesMut>),
+    Array(Vec<PeerReply>),
+}
+
+impl PeerReply {
+    /// Parse a single reply from the start of `buffer`. Return the reply and the number of bytes
+    /// it used, or `None` if `buffer` does not hold a complete reply yet
+    pub fn parse(buffer: &[u8]) -> Result<Option<(Self, usize)>, SableError> {
+        let Some(line_end) = buffer.windows(2).position(|w| w == b"\r\n") else {
+            return Ok(None);
+        };
+        let line = &buffer[1..line_end];
+        let mut consumed = line_end + 2;
+        let number = || {
+            std::str::from_utf8(line)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or_else(|| {
+                    SableError::Parser(ParserError::ProtocolError(format!(
+                        "invalid number {:?}",
+                        line
+                    )))
+                })
+        };
+
+        let reply = match buffer[0] {
+            b'+' => PeerReply::Status(BytesMut::from(line)),
+            b'-' => PeerReply::Error(BytesMut::from(line)),
+            b':' => PeerReply::Integer(number()?),
+            b'$' => {
+                let Ok(len) = usize::try_from(number()?) else {
+                    return Ok(Some((PeerReply::Bulk(None), consumed)));
+                };
+                let Some(end) = consumed.checked_add(len).and_then(|end| end.checked_add(2)) else {
+                    return Err(SableError::Parser(ParserError::ProtocolError(format!(
+                        "invalid bulk string length {}",
+                        len
+                    ))));
+                };
+                if buffer.len() < end {
+                    return Ok(None);
+                }
+                let content = BytesMut::from(&buffer[consumed..consumed + len]);
+                consumed = end;
+                PeerReply::Bulk(Some(content))
+            }
+            b'*' => {
+                let count = usize::try_from(number()?).unwrap_or_default();
+                let mut items = Vec::with_capacity(count.min(MAX_PREALLOCATED_ITEMS));
+                for _ in 0..count {
+                    let Some((item, item_len)) = Self::parse(&buffer[consumed..])? else {
+                        return Ok(None);
+                    };
+                    items.push(item);
+                    consumed += item_len;
+                }
+                PeerReply::Array(items)
+            }
+            other => {
+                return Err(SableError::Parser(ParserError::ProtocolError(format!(
+                    "unexpected reply type {:?}",
+                    other as char
+                ))))
+            }
+        };
+        Ok(Some((reply, consumed)))
+    }
+}
+
+/// A plain client connection to another server, driven with regular commands. Used when this
+/// server needs to talk to a peer as a client would: `FAILOVER` and `MIGRATE`
+pub struct PeerConnection {
+    stream: TcpStream,
+    buffer: BytesMut,
+}
+
+impl PeerConnection {
+    /// Connect to the server accepting clients on `host:port`
+    pub async fn connect(host: &str, port: u16) -> Result<Self, SableError> {
+        let stream = TcpStream::connect((host, port)).await?;
+        Ok(PeerConnection {
+            stream,
+            buffer: BytesMut::with_capacity(256),
+        })
+    }
+
+    /// Send a command and wait for its reply
+    pub async fn call(&mut self, args: &[&[u8]]) -> Result<PeerReply, SableError> {
+        let builder = RespBuilderV2::default();
+        let mut request = BytesMut::with_capacity(64);
+        builder.add_array_len(&mut request, args.len());
+        for arg in args {
+            builder.add_bulk_string_u8_arr(&mut request, arg);
+        }
+        self.stream.write_all(&request).await?;
+
+        loop {
+            if let Some((reply, consumed)) = PeerReply::parse(&self.buffer)? {
+                self.buffer.advance(consumed);
+                return Ok(reply);
+            }
+            if self.stream.read_buf(&mut self.buffer).await? == 0 {
+                return Err(SableError::ConnectionClosed);
+            }
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() -> Result<(), SableError> {
+        let reply = b"*4\r\n$12\r\nwrite_offset\r\n:42\r\n$14\r\napplied_offset\r\n:40\r\n+OK\r\n";
+        let (parsed, consumed) = PeerReply::parse(reply)?.unwrap();
+        assert_eq!(
+            parsed,
+            PeerReply::Array(vec![
+                PeerReply::Bulk(Some(BytesMut::from("write_offset"))),
+                PeerReply::Integer(42),
+                PeerReply::Bulk(Some(BytesMut::from("applied_offset"))),
+                PeerReply::Integer(40),
+            ])
+        );
+        assert_eq!(
+            PeerReply::parse(&reply[consumed..])?,
+            Some((PeerReply::Status(BytesMut::from("OK")), 5))
+        );
+
+        // incomplete replies
+        for len in 0..consumed {
+            assert!(PeerReply::parse(&reply[..len])?.is_none());
+        }
+
+        assert_eq!(
+            PeerReply::parse(b"-ERR no\r\n$-1\r\n")?,
+            Some((PeerReply::Error(BytesMut::from("ERR no")), 9))
+        );
+        assert!(PeerReply::parse(b"?\r\n").is_err());
+
+        // lengths sent by a misbehaving peer
+        assert!(PeerReply::parse(b"$18446744073709551615\r\nabc\r\n").is_err());
+        assert!(PeerReply::parse(b"$9223372036854775807\r\nabc\r\n")?.is_none());
+        assert!(PeerReply::parse(b"*9223372036854775807\r\n:1\r\n")?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::replication::StorageUpdates;
+use crate::{SableError, StorageAdapter};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// The most recent changes applied to the primary, kept in memory so a replica that lost its
+/// connection can resume from its last applied change ("partial sync") instead of downloading
+/// a full checkpoint.
+///
+/// The backlog holds all the write batches with a sequence number in `(start_seq, end_seq]`,
+/// one `StorageUpdates` per batch. It is filled from the WAL by `catch_up`, which the
+/// replication threads call before serving a request, and its oldest batches are dropped once
+/// their total size exceeds `max_size`
+pub struct ReplicationBacklog {
+    max_size: usize,
+    inner: Mutex<BacklogInner>,
+}
+
+#[derive(Default)]
+struct BacklogInner {
+    /// `false` until the first `catch_up` (or after the WAL could not be read)
+    started: bool,
+    start_seq: u64,
+    end_seq: u64,
+    batches: VecDeque<StorageUpdates>,
+    /// The total size of the serialised batches, in bytes
+    size: usize,
+}
+
+impl BacklogInner {
+    fn push(&mut self, batch: StorageUpdates, max_size: usize) {
+        self.end_seq = batch.end_seq_number;
+        self.size = self.size.saturating_add(batch.serialised_data.len());
+        self.batches.push_back(batch);
+        while self.size > max_size {
+            let Some(oldest) = self.batches.pop_front() else {
+                break;
+            };
+            self.start_seq = oldest.end_seq_number;
+            self.size = self.size.saturating_sub(oldest.serialised_data.len());
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = BacklogInner::default();
+    }
+}
+
+impl ReplicationBacklog {
+    /// The number of write batches read from the WAL at once by `catch_up`
+    const CATCH_UP_BATCHES: usize = 10_000;
+
+    /// Create a backlog holding up to `max_size` bytes of changes. `0` disables the backlog
+    pub fn new(max_size: usize) -> Self {
+        ReplicationBacklog {
+            max_size,
+            inner: Mutex::new(BacklogInner::default()),
+        }
+    }
+
+    /// Add the changes applied to `store` since the last call. The first call starts the
+    /// backlog at the current sequence number: earlier changes are not buffered
+    pub fn catch_up(&self, store: &StorageAdapter) -> Result<(), SableError> {
+        if self.max_size == 0 {
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock().expect("poisoned mutex");
+        if !inner.started {
+            let seq = store.latest_sequence_number()?;
+            inner.start_seq = seq;
+            inner.end_seq = seq;
+            inner.started = true;
+        }
+
+        loop {
+            let batches = match store.storage_batches_since(inner.end_seq, Self::CATCH_UP_BATCHES) {
+                Ok(batches) => batches,
+                Err(e) => {
+                    // the WAL no longer has our changes, start over
+                    inner.reset();
+                    return Err(e);
+                }
+            };
+            let done = batches.len() < Self::CATCH_UP_BATCHES;
+            for batch in batches {
+                inner.push(batch, self.max_size);
+            }
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Can a replica that applied all the changes up to `seq_num` resume from the backlog?
+    pub fn contains(&self, seq_num: u64) -> bool {
+        let inner = self.inner.lock().expect("poisoned mutex");
+        inner.started && inner.start_seq <= seq_num && seq_num <= inner.end_seq
+    }
+
+    /// Return the buffered changes since `seq_num`, limited by `memory_limit` (in bytes) and
+    /// `changes_count_limit`. Return `None` if `seq_num` is outside of the backlog
+    pub fn updates_since(
+        &self,
+        seq_num: u64,
+        memory_limit: u64,
+        changes_count_limit: u64,
+    ) -> Option<StorageUpdates> {
+        let inner = self.inner.lock().expect("poisoned mutex");
+        if !inner.started || seq_num < inner.start_seq || seq_num > inner.end_seq {
+            return None;
+        }
+
+        let first = inner
+            .batches
+            .partition_point(|batch| batch.end_seq_number <= seq_num);
+        let mut storage_updates = StorageUpdates::from_seq_number(seq_num);
+        for batch in inner.batches.range(first..) {
+            storage_updates.append(batch);
+            if storage_updates.len() >= memory_limit
+                || storage_updates.changes_count >= changes_count_limit
+            {
+                break;
+            }
+        }
+        Some(storage_updates)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PutFlags;
+    use bytes::BytesMut;
+
+    fn put_keys(store: &StorageAdapter, count: usize) -> Result<(), SableError> {
+        for i in 0..count {
+            store.put(
+                &BytesMut::from(format!("key_{}", i).as_str()),
+                &BytesMut::from("x".repeat(100).as_str()),
+                PutFlags::Override,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_reconnects_within_and_outside_the_backlog() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let backlog = ReplicationBacklog::new(1024);
+
+        // changes made before the backlog started are not buffered
+        put_keys(&store, 3)?;
+        backlog.catch_up(&store)?;
+        let replica_seq = store.latest_sequence_number()?;
+        assert!(!backlog.contains(replica_seq - 1));
+        assert!(backlog
+            .updates_since(replica_seq - 1, u64::MAX, u64::MAX)
+            .is_none());
+
+        // a replica that disconnected after `replica_seq` resumes from the backlog and gets
+        // the same changes it would have read from the WAL
+        put_keys(&store, 5)?;
+        backlog.catch_up(&store)?;
+        assert!(backlog.contains(replica_seq));
+        let from_backlog = backlog
+            .updates_since(replica_seq, u64::MAX, u64::MAX)
+            .unwrap();
+        let from_storage = store.storage_updates_since(replica_seq, None, None)?;
+        assert_eq!(from_backlog, from_storage);
+        assert_eq!(from_backlog.changes_count, 5);
+        assert_eq!(from_backlog.end_seq_number, store.latest_sequence_number()?);
+
+        // the limits are honoured, and the replica continues from where the message ended
+        let first = backlog.updates_since(replica_seq, u64::MAX, 2).unwrap();
+        assert_eq!(first.changes_count, 2);
+        let rest = backlog
+            .updates_since(first.end_seq_number, u64::MAX, u64::MAX)
+            .unwrap();
+        assert_eq!(rest.changes_count, 3);
+        let mut joined = first.clone();
+        joined.append(&rest);
+        assert_eq!(joined, from_storage);
+
+        // an up-to-date replica is in the backlog, there is nothing to send yet
+        let latest = store.latest_sequence_number()?;
+        assert!(backlog.contains(latest));
+        assert!(backlog
+            .updates_since(latest, u64::MAX, u64::MAX)
+            .unwrap()
+            .is_empty());
+
+        // the backlog is full, the oldest changes were dropped: the replica must full sync
+        put_keys(&store, 20)?;
+        backlog.catch_up(&store)?;
+        assert!(!backlog.contains(replica_seq));
+        assert!(backlog
+            .updates_since(replica_seq, u64::MAX, u64::MAX)
+            .is_none());
+        let latest = store.latest_sequence_number()?;
+        assert!(backlog.contains(latest - 1));
+        assert_eq!(
+            backlog
+                .updates_since(latest - 1, u64::MAX, u64::MAX)
+                .unwrap(),
+            store.storage_updates_since(latest - 1, None, None)?
+        );
+
+        // a disabled backlog never accepts a partial sync
+        let disabled = ReplicationBacklog::new(0);
+        disabled.catch_up(&store)?;
+        assert!(!disabled.contains(latest));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::replication::{
-    prepare_std_socket, BytesReader, BytesWriter, ReplRequest, TcpStreamBytesReader,
-    TcpStreamBytesWriter,
+    prepare_std_socket, BytesReader, BytesWriter, CompressedBytesReader, ReplPartialSyncResponse,
+    ReplRequest, ReplicationStream, TcpStreamBytesReader, TcpStreamBytesWriter,
 };
 use crate::server_options::ServerOptions;
 use crate::{
@@ -13,6 +13,7 @@ use num_format::{Locale, ToFormattedString};
 use std::io::Read;
 use std::net::{SocketAddr, TcpStream};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::sync::mpsc::{channel as tokio_channel, error::TryRecvError, Sender as TokioSender};
 
 #[allow(dead_code)]
@@ -35,6 +36,13 @@ enum RequestChangesResult {
     Success,
 }
 
+lazy_static::lazy_static! {
+    /// Is the replica connected to its primary (and done with the full sync)?
+    static ref PRIMARY_LINK_UP: AtomicBool = AtomicBool::new(false);
+    /// The last time (epoch milliseconds) a message was received from the primary. `0`: never
+    static ref PRIMARY_LAST_IO_MS: AtomicU64 = AtomicU64::new(0);
+}
+
 #[derive(Default)]
 pub struct ReplicationClient {}
 
@@ -50,8 +58,11 @@ impl ReplicationClient {
 
         // Spawn a thread to handle the replication
         let _ = std::thread::spawn(move || {
+            // Set once this thread synced with the primary: from there on, a reconnect attempts
+            // to resume the replication before falling back to a full sync
+            let mut synced = false;
             loop {
-                let mut stream = match Self::connect_to_primary(&options) {
+                let stream = match Self::connect_to_primary(&options) {
                     Err(e) => {
                         crate::error_with_throttling!(300, "Failed to connect to primary. {:?}", e);
 
@@ -78,40 +89,73 @@ impl ReplicationClient {
                     Ok(stream) => stream,
                 };
 
-                // Now that we are connected, we start by requesting a full sync from the primary
-                if let Err(e) = Self::fullsync(&store, &options, &mut stream) {
-                    tracing::error!("Fullsync error. {:?}", e);
-                    let _ = stream.shutdown(std::net::Shutdown::Both);
-                    break;
+                // Now that we are connected, we resume from our last applied change if the
+                // primary still has it in its backlog, or start with a full sync
+                let resumed = if synced {
+                    match Self::partial_sync(&options, &stream) {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!("Partial sync error. {:?}", e);
+                            // the connection is unusable, reconnect and full sync
+                            stream.shutdown();
+                            synced = false;
+                            continue;
+                        }
+                    }
+                } else {
+                    false
+                };
+
+                if !resumed {
+                    if let Err(e) = Self::fullsync(&store, &options, &stream) {
+                        tracing::error!("Fullsync error. {:?}", e);
+                        stream.shutdown();
+                        break;
+                    }
                 }
+                synced = true;
 
                 // hereon: use socket with timeout
-                if let Err(e) = prepare_std_socket(&stream) {
+                if let Err(e) = prepare_std_socket(stream.socket()) {
                     tracing::error!("Failed to prepare socket. {:?}", e);
-                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    stream.shutdown();
                     break;
                 }
-                let mut reader = TcpStreamBytesReader::new(&stream);
+                Self::set_primary_link_up(true);
+                let reader = TcpStreamBytesReader::new(&stream);
                 let mut writer = TcpStreamBytesWriter::new(&stream);
+                // the updates are compressed when requested (see `request_changes`)
+                let mut reader: Box<dyn BytesReader> = if options.replication_settings.compression {
+                    Box::new(CompressedBytesReader::new(reader))
+                } else {
+                    Box::new(reader)
+                };
 
                 // This is the replication main loop:
                 // We continuously calling `request_changes` from the primary
                 // and store them in our database
                 loop {
-                    match Self::request_changes(&store, &options, &mut reader, &mut writer, &mut rx)
-                    {
+                    match Self::request_changes(
+                        &store,
+                        &options,
+                        reader.as_mut(),
+                        &mut writer,
+                        &mut rx,
+                    ) {
                         RequestChangesResult::Success => {
                             // Note: if there are no changes, the primary server will stall
                             // the response
                         }
                         RequestChangesResult::Reconnect => {
                             tracing::info!("Closing connection with primary: {:?}", stream);
-                            let _ = stream.shutdown(std::net::Shutdown::Both);
+                            stream.shutdown();
+                            Self::set_primary_link_up(false);
                             break;
                         }
                         RequestChangesResult::ExitThread => {
                             tracing::info!("Closing connection with primary: {:?}", stream);
-                            let _ = stream.shutdown(std::net::Shutdown::Both);
+                            stream.shutdown();
+                            Self::set_primary_link_up(false);
                             return; // leave the thread
                         }
                     }
@@ -124,13 +168,50 @@ impl ReplicationClient {
         Ok(tx)
     }
 
-    fn connect_to_primary(options: &ServerOptions) -> Result<TcpStream, SableError> {
+    /// Is this replica connected to its primary?
+    pub fn primary_link_up() -> bool {
+        PRIMARY_LINK_UP.load(Ordering::Relaxed)
+    }
+
+    /// The number of seconds since the last message was received from the primary. `None` if
+    /// no message was ever received. Note that the primary delays its response until it has
+    /// changes to send, so an idle primary increases this value
+    pub fn primary_last_io_seconds_ago() -> Option<u64> {
+        let last_io_ms = PRIMARY_LAST_IO_MS.load(Ordering::Relaxed);
+        if last_io_ms == 0 {
+            return None;
+        }
+        let now = crate::TimeUtils::epoch_ms().ok()?;
+        Some(now.saturating_sub(last_io_ms) / 1000)
+    }
+
+    fn set_primary_link_up(link_up: bool) {
+        PRIMARY_LINK_UP.store(link_up, Ordering::Relaxed);
+    }
+
+    fn connect_to_primary(options: &ServerOptions) -> Result<ReplicationStream, SableError> {
         let repl_config = options.load_replication_config();
         let address = format!("{}:{}", repl_config.ip, repl_config.port);
         tracing::info!("Connecting to primary at: {}", address);
 
         let addr = address.parse::<SocketAddr>()?;
-        let stream = TcpStream::connect(addr)?;
+        let socket = TcpStream::connect(addr)?;
+        let settings = &options.replication_settings;
+        let stream = if settings.tls {
+            let Some(ca) = &settings.tls_ca else {
+                return Err(SableError::InvalidArgument(
+                    "Replication TLS requires `tls_ca`".to_string(),
+                ));
+            };
+            let server_name = settings.tls_server_name.as_ref().unwrap_or(&repl_config.ip);
+            ReplicationStream::tls_client(
+                socket,
+                crate::tls::create_client_config(ca)?,
+                server_name,
+            )?
+        } else {
+            ReplicationStream::plain(socket)
+        };
         tracing::info!("Successfully connected to primary at: {}", address);
         Ok(stream)
     }
@@ -152,14 +233,56 @@ impl ReplicationClient {
         }
     }
 
+    /// Ask the primary to resume the replication from the last change applied to our database.
+    /// Return `true` if the primary accepted, `false` if a full sync is required
+    fn partial_sync(
+        options: &ServerOptions,
+        stream: &ReplicationStream,
+    ) -> Result<bool, SableError> {
+        let _ = stream.socket().set_nonblocking(false);
+        let _ = stream.socket().set_read_timeout(None);
+
+        let Some(sequence_number) = Self::applied_sequence_number(&options.open_params.db_path)
+        else {
+            return Ok(false);
+        };
+        tracing::info!(
+            "Sending PARTIAL SYNC message to primary. Last applied sequence: {}",
+            sequence_number.to_formatted_string(&Locale::en)
+        );
+        let mut buffer = ReplRequest::new_partial_sync(sequence_number).to_bytes();
+        TcpStreamBytesWriter::new(stream).write_message(&mut buffer)?;
+
+        let mut reader = TcpStreamBytesReader::new(stream);
+        let buffer = loop {
+            if let Some(buffer) = reader.read_message()? {
+                break buffer;
+            }
+        };
+        let Some(response) = ReplPartialSyncResponse::from_bytes(&buffer) else {
+            return Err(SableError::OtherError(
+                "Failed to deserialise partial sync response".to_string(),
+            ));
+        };
+        tracing::info!(
+            "Partial sync {}",
+            if response.accepted {
+                "accepted"
+            } else {
+                "rejected, performing a full sync"
+            }
+        );
+        Ok(response.accepted)
+    }
+
     /// Perform a fullsync with the primary
     fn fullsync(
         store: &StorageAdapter,
         options: &ServerOptions,
-        stream: &mut std::net::TcpStream,
+        stream: &ReplicationStream,
     ) -> Result<(), SableError> {
-        let _ = stream.set_nonblocking(false);
-        let _ = stream.set_read_timeout(None);
+        let _ = stream.socket().set_nonblocking(false);
+        let _ = stream.socket().set_read_timeout(None);
 
         // Send a "FULL_SYNC" message
         tracing::info!("Sending FULL SYNC message to primary");
@@ -169,8 +292,9 @@ impl ReplicationClient {
         writer.write_message(&mut buffer)?;
 
         // Read the response
+        let mut reader = stream;
         let mut file_len = vec![0u8; std::mem::size_of::<usize>()];
-        stream.read_exact(&mut file_len)?;
+        reader.read_exact(&mut file_len)?;
 
         // split the buffer into chunks and read
         const CHUNK_SIZE: usize = 10 << 20; // 10MB
@@ -183,7 +307,7 @@ impl ReplicationClient {
         let output_file_name = format!("{}.checkpoint.tar", options.open_params.db_path.display());
         let target_folder_path = format!("{}.checkpoint", options.open_params.db_path.display());
         let mut file = std::fs::File::create(&output_file_name)?;
-        crate::io::read_exact(stream, &mut file, count)?;
+        crate::io::read_exact(&mut reader, &mut file, count)?;
         tracing::info!(
             "File {} successfully received from primary",
             output_file_name
@@ -218,8 +342,8 @@ impl ReplicationClient {
     fn request_changes(
         store: &StorageAdapter,
         options: &ServerOptions,
-        reader: &mut impl BytesReader,
-        writer: &mut impl BytesWriter,
+        reader: &mut dyn BytesReader,
+        writer: &mut dyn BytesWriter,
         rx: &mut tokio::sync::mpsc::Receiver<ReplClientCommand>,
     ) -> RequestChangesResult {
         // Before we start, check for termination request
@@ -242,7 +366,10 @@ impl ReplicationClient {
             "Next sequence: {}",
             sequence_number.to_formatted_string(&Locale::en)
         );
-        let request = ReplRequest::new_get_updates_since(sequence_number);
+        let request = ReplRequest::new_get_updates_since(sequence_number).with_flag(
+            ReplRequest::FLAG_COMPRESS_UPDATES,
+            options.replication_settings.compression,
+        );
         let mut buffer = request.to_bytes();
         if let Err(e) = writer.write_message(&mut buffer) {
             tracing::error!("Failed to send replication request. {:?}", e);
@@ -265,7 +392,12 @@ impl ReplicationClient {
                         }
                     }
                 }
-                Ok(Some(buffer)) => break buffer,
+                Ok(Some(buffer)) => {
+                    if let Ok(now) = crate::TimeUtils::epoch_ms() {
+                        PRIMARY_LAST_IO_MS.store(now, Ordering::Relaxed);
+                    }
+                    break buffer;
+                }
                 Err(e) => {
                     tracing::error!("Error reading replication response. {:?}", e);
                     return RequestChangesResult::Reconnect;
@@ -298,6 +430,24 @@ impl ReplicationClient {
                 StorageUpdatesIterItem::Del(delete_record) => {
                     batch_update.delete(delete_record.key);
                 }
+                StorageUpdatesIterItem::DelRange(delete_range_record) => {
+                    // keep the order: apply the changes that preceded the range deletion first
+                    if !batch_update.is_empty() {
+                        if let Err(e) = store.apply_batch(&batch_update) {
+                            tracing::error!(
+                                "Failed to apply replication batch into store. {:?}",
+                                e
+                            );
+                            return RequestChangesResult::Reconnect;
+                        }
+                        batch_update.clear();
+                    }
+                    if let Err(e) = store.delete_range(&delete_range_record.prefix) {
+                        tracing::error!("Failed to apply replicated range deletion. {:?}", e);
+                        return RequestChangesResult::Reconnect;
+                    }
+                    continue;
+                }
             }
             if batch_update.len() % MAX_BATCH_SIZE == 0 {
                 if let Err(e) = store.apply_batch(&batch_update) {
@@ -325,6 +475,12 @@ impl ReplicationClient {
         Self::write_next_sequence(sequence_file, sequence_number)
     }
 
+    /// Return the last primary sequence number that was applied to the database
+    /// located at `db_path`. A database that never replicated anything returns `Some(0)`
+    pub fn applied_sequence_number(db_path: &std::path::Path) -> Option<u64> {
+        Self::read_next_sequence(db_path.join("changes.seq"))
+    }
+
     /// Read the next sequence to get from the primary from the file system.
     /// If the file does not exist, return `Some(0)`. Else return the parsed value
     /// or `None` in case of any other error
@@ -370,7 +526,7 @@ impl ReplicationClient {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{storage::PutFlags, StorageOpenParams};
+    use crate::{storage::PutFlags, CompressionType, StorageOpenParams};
     use bytes::BytesMut;
     const DB_SIZE: usize = 100_000;
 
@@ -379,7 +535,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/test_{}.db", db_name));
         let _ = std::fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(true)
+            .set_compression_type(CompressionType::Snappy)
             .set_cache_size(64)
             .set_path(&db_path);
         let rocks = crate::storage_rocksdb!(open_params.clone());
@@ -425,6 +581,10 @@ mod tests {
 
         let (_tx, mut rx) = tokio_channel::<ReplClientCommand>(100);
 
+        // writes applied by the replication are reported like any other write
+        let collector = std::sync::Arc::new(crate::tests::WriteEventsCollector::default());
+        replica_db.add_write_observer(collector.clone());
+
         let mut server_options = ServerOptions::default();
         server_options.open_params = replica_db.open_params().clone();
         ReplicationClient::request_changes(
@@ -437,6 +597,77 @@ mod tests {
 
         // Ensure that all record exist in the replication db
         verify_all_records_exist(&replica_db)?;
+
+        // one event per batch applied to the replica
+        let events = collector.events.lock().unwrap();
+        assert_eq!(events.len(), DB_SIZE / 10_000);
+        assert_eq!(events.iter().map(|e| e.records).sum::<usize>(), DB_SIZE);
+
+        // The replica applied offset should converge to the primary's write offset
+        assert_eq!(
+            ReplicationClient::applied_sequence_number(&replica_db.open_params().db_path),
+            Some(primary_db.latest_sequence_number()?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicated_flushdb() -> Result<(), SableError> {
+        let primary_db = create_database("replicated_flushdb_primary", false)?;
+        let replica_db = create_database("replicated_flushdb_replica", false)?;
+
+        // populate databases 0 and 1 of the primary, then flush database 0
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let client = crate::Client::new(
+                std::sync::Arc::<crate::ServerState>::default(),
+                primary_db.clone(),
+                None,
+            );
+            for (args, expected_value) in [
+                (vec!["set", "k1", "v"], "+OK\r\n"),
+                (vec!["hset", "h1", "f1", "v1", "f2", "v2"], ":2\r\n"),
+                (vec!["select", "1"], "+OK\r\n"),
+                (vec!["set", "k2", "v"], "+OK\r\n"),
+                (vec!["select", "0"], "+OK\r\n"),
+                (vec!["flushdb"], "+OK\r\n"),
+                (vec!["set", "k3", "v"], "+OK\r\n"),
+            ] {
+                let mut sink =
+                    crate::tests::ResponseSink::with_name("test_replicated_flushdb").await;
+                let cmd = std::rc::Rc::new(crate::RedisCommand::for_test(args));
+                crate::Client::handle_command(client.inner(), cmd, &mut sink.fp)
+                    .await
+                    .unwrap();
+                assert_eq!(sink.read_all().await, expected_value);
+            }
+        });
+
+        let mut writer = SimpleBytesWriter::default();
+        let mut reader =
+            StorageUpdatesBytesReader::new(primary_db.storage_updates_since(0, None, None)?);
+        let (_tx, mut rx) = tokio_channel::<ReplClientCommand>(100);
+        let mut server_options = ServerOptions::default();
+        server_options.open_params = replica_db.open_params().clone();
+        ReplicationClient::request_changes(
+            &replica_db,
+            &server_options,
+            &mut reader,
+            &mut writer,
+            &mut rx,
+        );
+
+        // the replica flushed database 0 (including the hash fields) at the same point
+        let replica_db0 = crate::storage::GenericDb::with_storage(&replica_db, 0);
+        assert!(!replica_db0.contains(&BytesMut::from("k1"))?);
+        assert!(!replica_db0.contains(&BytesMut::from("h1"))?);
+        assert!(replica_db0.contains(&BytesMut::from("k3"))?);
+        let hash_items = BytesMut::from(&[crate::metadata::Encoding::KEY_HASH_ITEM][..]);
+        assert_eq!(replica_db0.sample_items(&hash_items, usize::MAX)?.0, 0);
+
+        // database 1 is left untouched
+        let replica_db1 = crate::storage::GenericDb::with_storage(&replica_db, 1);
+        assert!(replica_db1.contains(&BytesMut::from("k2"))?);
         Ok(())
     }
 
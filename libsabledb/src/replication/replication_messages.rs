@@ -8,19 +8,28 @@ pub struct ReplRequest {
     pub req_type: u8,
     /// Depending on the message type, this field as a different meaning
     /// - If `req_type == ReplRequest::GET_UPDATES_SINCE`, `payload` is the
-    ///     starting sequence number for changes to be sent over to the replica
+    ///   starting sequence number for changes to be sent over to the replica
+    /// - If `req_type == ReplRequest::PARTIAL_SYNC`, `payload` is the sequence number of
+    ///   the last change the replica applied
     pub payload: u64,
+    /// Options requested by the replica (see `ReplRequest::FLAG_*`)
+    pub flags: u8,
 }
 
 impl ReplRequest {
-    pub const SIZE: usize = std::mem::size_of::<u64>() + std::mem::size_of::<u8>();
+    pub const SIZE: usize = std::mem::size_of::<u64>() + 2 * std::mem::size_of::<u8>();
     pub const GET_UPDATES_SINCE: u8 = 0;
     pub const FULL_SYNC: u8 = 1;
+    pub const PARTIAL_SYNC: u8 = 2;
+
+    /// The replica asks for the updates to be compressed (see `CompressedBytesWriter`)
+    pub const FLAG_COMPRESS_UPDATES: u8 = 1 << 0;
 
     pub fn new_get_updates_since(seq_num: u64) -> Self {
         ReplRequest {
             req_type: ReplRequest::GET_UPDATES_SINCE,
             payload: seq_num,
+            flags: 0,
         }
     }
 
@@ -28,15 +37,41 @@ impl ReplRequest {
         ReplRequest {
             req_type: ReplRequest::FULL_SYNC,
             payload: 0,
+            flags: 0,
+        }
+    }
+
+    /// Ask the primary to resume the replication after `seq_num` without a full sync. The
+    /// primary responds with `ReplPartialSyncResponse`
+    pub fn new_partial_sync(seq_num: u64) -> Self {
+        ReplRequest {
+            req_type: ReplRequest::PARTIAL_SYNC,
+            payload: seq_num,
+            flags: 0,
         }
     }
 
+    /// Set or clear `flag`
+    pub fn with_flag(mut self, flag: u8, enabled: bool) -> Self {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+        self
+    }
+
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
     /// Serialise this object into `BytesMut`
     pub fn to_bytes(&self) -> BytesMut {
         let mut as_bytes = BytesMut::with_capacity(ReplRequest::SIZE);
         let mut builder = U8ArrayBuilder::with_buffer(&mut as_bytes);
         builder.write_u8(self.req_type);
         builder.write_u64(self.payload);
+        builder.write_u8(self.flags);
         as_bytes
     }
 
@@ -46,7 +81,38 @@ impl ReplRequest {
 
         let req_type = reader.read_u8()?;
         let payload = reader.read_u64()?;
+        // requests sent by older replicas have no flags
+        let flags = reader.read_u8().unwrap_or_default();
+
+        Some(ReplRequest {
+            req_type,
+            payload,
+            flags,
+        })
+    }
+}
+
+/// The primary's response to a `PARTIAL_SYNC` request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplPartialSyncResponse {
+    /// `true` if the primary still has the changes requested by the replica. Otherwise, the
+    /// replica must perform a full sync
+    pub accepted: bool,
+}
+
+impl ReplPartialSyncResponse {
+    /// Serialise this object into `BytesMut`
+    pub fn to_bytes(&self) -> BytesMut {
+        let mut as_bytes = BytesMut::with_capacity(std::mem::size_of::<u8>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut as_bytes);
+        builder.write_u8(u8::from(self.accepted));
+        as_bytes
+    }
 
-        Some(ReplRequest { req_type, payload })
+    /// Construct `ReplPartialSyncResponse` from raw bytes
+    pub fn from_bytes(buf: &BytesMut) -> Option<Self> {
+        let mut reader = U8ArrayReader::with_buffer(buf);
+        let accepted = reader.read_u8()? != 0;
+        Some(ReplPartialSyncResponse { accepted })
     }
 }
@@ -7,23 +7,82 @@ use crate::utils;
 use crate::{
     io::Archive,
     replication::{
-        BytesReader, BytesWriter, ReplRequest, TcpStreamBytesReader, TcpStreamBytesWriter,
+        BytesReader, BytesWriter, CompressedBytesWriter, ReplPartialSyncResponse, ReplRequest,
+        ReplicationBacklog, ReplicationStream, StorageUpdates, TcpStreamBytesReader,
+        TcpStreamBytesWriter,
     },
     SableError, StorageAdapter,
 };
 
+use dashmap::DashMap;
 use num_format::{Locale, ToFormattedString};
+use std::io::Write;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
 #[derive(Default)]
-pub struct ReplicationServer {}
+pub struct ReplicationServer {
+    /// The acknowledgements of the replicas served by this server
+    replica_acks: Arc<ReplicaAcks>,
+}
 
 lazy_static::lazy_static! {
     static ref REPLICATION_THREADS: AtomicUsize = AtomicUsize::new(0);
     static ref STOP_FLAG: AtomicBool = AtomicBool::new(false);
 }
 
+/// The replicas connected to a primary and the sequence number of the last change each of them
+/// applied (`None` while the replica is still performing its full sync). Updated by the
+/// replication server, read by `WAIT` and `INFO replication` (see `ServerState::replica_acks`)
+#[derive(Default)]
+pub struct ReplicaAcks {
+    acks: DashMap<String, Option<u64>>,
+}
+
+impl ReplicaAcks {
+    /// A replica connected, it did not acknowledge anything yet
+    pub fn add_replica(&self, replica_addr: &str) {
+        self.acks.insert(replica_addr.to_string(), None);
+    }
+
+    /// Record that `replica_addr` applied all the changes up to `seq_num`. A replica asking for
+    /// the changes since `seq_num` implicitly acknowledges everything before it
+    pub fn record_ack(&self, replica_addr: &str, seq_num: u64) {
+        self.acks.insert(replica_addr.to_string(), Some(seq_num));
+    }
+
+    /// Forget about a disconnected replica
+    pub fn remove_replica(&self, replica_addr: &str) {
+        self.acks.remove(replica_addr);
+    }
+
+    /// Return the number of connected replicas that applied all the changes up to `seq_num`
+    pub fn acked_replicas(&self, seq_num: u64) -> usize {
+        self.acks
+            .iter()
+            .filter(|entry| entry.value().is_some_and(|acked| acked >= seq_num))
+            .count()
+    }
+
+    /// Return the connected replicas (sorted by their address) and the sequence number of the
+    /// last change each of them applied
+    pub fn replicas(&self) -> Vec<(String, Option<u64>)> {
+        let mut replicas: Vec<(String, Option<u64>)> = self
+            .acks
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        replicas.sort();
+        replicas
+    }
+
+    /// Return the number of connected replicas
+    pub fn replicas_count(&self) -> usize {
+        self.acks.len()
+    }
+}
+
 /// Notify the replication threads to stop and wait for them to terminate
 pub async fn replication_thread_stop_all() {
     tracing::info!("Notifying all replication threads to shutdown");
@@ -59,13 +118,18 @@ fn replication_thread_is_going_down() -> bool {
 /// goes out of scope
 struct ReplicationThreadMarker {
     address: String,
+    replica_acks: Arc<ReplicaAcks>,
 }
 
 impl ReplicationThreadMarker {
-    pub fn new(address: String) -> Self {
+    pub fn new(address: String, replica_acks: Arc<ReplicaAcks>) -> Self {
         ReplicationTelemetry::update_replica_info(address.clone(), ReplicaTelemetry::default());
+        replica_acks.add_replica(&address);
         replication_thread_incr();
-        ReplicationThreadMarker { address }
+        ReplicationThreadMarker {
+            address,
+            replica_acks,
+        }
     }
 }
 
@@ -73,10 +137,36 @@ impl Drop for ReplicationThreadMarker {
     fn drop(&mut self) {
         replication_thread_decr();
         ReplicationTelemetry::remove_replica(&self.address);
+        self.replica_acks.remove_replica(&self.address);
+    }
+}
+
+/// The state the primary keeps for a connected replica. Every replica is served by its own
+/// thread and pulls the changes from its own position ("cursor") in the update stream, so a slow
+/// replica does not hold back the others
+struct ReplicaSession {
+    address: String,
+    /// Set once the replica is served from the backlog. A replica that falls behind the backlog
+    /// afterwards can not keep up with the primary: it is disconnected, and performs a full sync
+    /// when it reconnects
+    following_backlog: bool,
+}
+
+impl ReplicaSession {
+    fn new(address: String) -> Self {
+        ReplicaSession {
+            address,
+            following_backlog: false,
+        }
     }
 }
 
 impl ReplicationServer {
+    /// Create a server that records the acknowledgements of its replicas in `replica_acks`
+    pub fn new(replica_acks: Arc<ReplicaAcks>) -> Self {
+        ReplicationServer { replica_acks }
+    }
+
     fn read_request(reader: &mut dyn BytesReader) -> Result<Option<ReplRequest>, SableError> {
         // Read the request (this is a fixed size request)
         let result = reader.read_message()?;
@@ -94,12 +184,12 @@ impl ReplicationServer {
         store: &StorageAdapter,
         _options: &ServerOptions,
         replica_addr: &String,
-        stream: &mut std::net::TcpStream,
+        stream: &ReplicationStream,
     ) -> Result<(), SableError> {
         // async sockets are non-blocking. Make it blocking for sending the file
-        stream.set_nonblocking(false)?;
-        stream.set_write_timeout(None)?;
-        stream.set_read_timeout(None)?;
+        stream.socket().set_nonblocking(false)?;
+        stream.socket().set_write_timeout(None)?;
+        stream.socket().set_read_timeout(None)?;
 
         let ts = utils::current_time(utils::CurrentTimeResolution::Microseconds).to_string();
         tracing::info!("Preparing checkpoint for replica {}", replica_addr);
@@ -126,25 +216,59 @@ impl ReplicationServer {
             file_len.to_formatted_string(&Locale::en)
         );
 
+        let mut writer = stream;
         let mut file_len = crate::BytesMutUtils::from_usize(&file_len);
-        crate::io::write_bytes(stream, &mut file_len)?;
+        crate::io::write_bytes(&mut writer, &mut file_len)?;
 
         // Now send the content
-        std::io::copy(&mut file, stream)?;
+        std::io::copy(&mut file, &mut writer)?;
+        writer.flush()?;
         tracing::info!("Sending tar file {} completed", tar_file.display());
-        prepare_std_socket(stream)?;
+        prepare_std_socket(stream.socket())?;
         Ok(())
     }
 
+    /// Return the changes since `seq_num`, from the backlog when possible. Replicas that did
+    /// not reach the backlog yet (e.g. right after a full sync) are served directly from the WAL
+    fn updates_since(
+        store: &StorageAdapter,
+        options: &ServerOptions,
+        backlog: &ReplicationBacklog,
+        session: &mut ReplicaSession,
+        seq_num: u64,
+    ) -> Result<StorageUpdates, SableError> {
+        let memory_limit = options.replication_limits.single_update_buffer_size as u64;
+        let changes_count_limit = options.replication_limits.num_updates_per_message as u64;
+        if let Err(e) = backlog.catch_up(store) {
+            tracing::warn!("Failed to update the replication backlog. {:?}", e);
+        }
+        if let Some(storage_updates) =
+            backlog.updates_since(seq_num, memory_limit, changes_count_limit)
+        {
+            session.following_backlog = true;
+            return Ok(storage_updates);
+        }
+        if session.following_backlog {
+            return Err(SableError::OtherError(format!(
+                "Replica {} fell behind the replication backlog",
+                session.address
+            )));
+        }
+        store.storage_updates_since(seq_num, Some(memory_limit), Some(changes_count_limit))
+    }
+
     /// The main replication request -> reply flow is happening
     /// This function reads a single replication request
     /// and responds with the proper response.
     fn handle_single_request(
         store: &StorageAdapter,
         options: &ServerOptions,
-        stream: &mut std::net::TcpStream,
-        replica_addr: &String,
+        backlog: &ReplicationBacklog,
+        replica_acks: &ReplicaAcks,
+        stream: &ReplicationStream,
+        session: &mut ReplicaSession,
     ) -> bool {
+        let replica_addr = &session.address;
         let mut reader = TcpStreamBytesReader::new(stream);
         let mut writer = TcpStreamBytesWriter::new(stream);
 
@@ -180,6 +304,28 @@ impl ReplicationServer {
                     );
                     return false;
                 }
+                session.following_backlog = false;
+            }
+            ReplRequest::PARTIAL_SYNC => {
+                if let Err(e) = backlog.catch_up(store) {
+                    tracing::warn!("Failed to update the replication backlog. {:?}", e);
+                }
+                let accepted = backlog.contains(req.payload);
+                tracing::info!(
+                    "Replica {} is requesting a partial sync from: {}. Accepted: {}",
+                    replica_addr,
+                    req.payload,
+                    accepted
+                );
+                if accepted {
+                    replica_acks.record_ack(replica_addr, req.payload);
+                    session.following_backlog = true;
+                }
+                let mut buffer = ReplPartialSyncResponse { accepted }.to_bytes();
+                if let Err(e) = writer.write_message(&mut buffer) {
+                    tracing::error!("Failed to send partial sync response to replica. {:?}", e);
+                    return false;
+                }
             }
             ReplRequest::GET_UPDATES_SINCE => {
                 tracing::debug!(
@@ -187,20 +333,25 @@ impl ReplicationServer {
                     replica_addr,
                     req.payload
                 );
+                replica_acks.record_ack(replica_addr, req.payload);
                 let storage_updates = loop {
-                    let storage_updates = match store.storage_updates_since(
-                        req.payload,
-                        Some(options.replication_limits.single_update_buffer_size as u64),
-                        Some(options.replication_limits.num_updates_per_message as u64),
-                    ) {
-                        Err(e) => {
-                            tracing::error!("Failed to construct 'changes since' message. {:?}", e);
-                            return false;
-                        }
-                        Ok(changes_since) => changes_since,
-                    };
+                    let storage_updates =
+                        match Self::updates_since(store, options, backlog, session, req.payload) {
+                            Err(e) => {
+                                tracing::error!(
+                                    "Failed to construct 'changes since' message. {:?}",
+                                    e
+                                );
+                                return false;
+                            }
+                            Ok(changes_since) => changes_since,
+                        };
 
                     if storage_updates.is_empty() {
+                        if replication_thread_is_going_down() {
+                            tracing::info!("Received request to shutdown - bye");
+                            return false;
+                        }
                         // Nothing to send, suspend ourselves for a bit
                         // until we have something to send
                         std::thread::sleep(std::time::Duration::from_millis(
@@ -215,7 +366,12 @@ impl ReplicationServer {
                 // Serialise the data
                 tracing::info!("Sending replication update: {}", storage_updates);
                 let mut buffer = storage_updates.to_bytes();
-                match writer.write_message(&mut buffer) {
+                let result = if req.has_flag(ReplRequest::FLAG_COMPRESS_UPDATES) {
+                    CompressedBytesWriter::new(&mut writer).write_message(&mut buffer)
+                } else {
+                    writer.write_message(&mut buffer)
+                };
+                match result {
                     Err(SableError::BrokenPipe) => {
                         tracing::warn!("Failed to send changes to replica (broken pipe)");
                         return false;
@@ -246,6 +402,26 @@ impl ReplicationServer {
         let repl_config = options.load_replication_config();
         let address = format!("{}:{}", repl_config.ip, repl_config.port);
 
+        // the replication traffic is encrypted with the certificate used for the clients
+        let tls_config = if options.replication_settings.tls {
+            let (Some(cert), Some(key)) = (
+                &options.general_settings.cert,
+                &options.general_settings.key,
+            ) else {
+                return Err(SableError::InvalidArgument(
+                    "Replication TLS requires both `cert` and `key`".to_string(),
+                ));
+            };
+            Some(crate::tls::create_server_config(cert, key)?)
+        } else {
+            None
+        };
+
+        // shared by all the replication threads
+        let backlog = Arc::new(ReplicationBacklog::new(
+            options.replication_limits.backlog_size,
+        ));
+
         let listener = TcpListener::bind(address.clone())
             .await
             .unwrap_or_else(|_| panic!("failed to bind address {}", address));
@@ -253,7 +429,7 @@ impl ReplicationServer {
         loop {
             let (socket, addr) = listener.accept().await?;
             tracing::info!("Accepted new connection from replica: {:?}", addr);
-            let mut stream = match socket.into_std() {
+            let socket = match socket.into_std() {
                 Ok(socket) => socket,
                 Err(e) => {
                     tracing::error!("Failed to convert async socket -> std socket!. {:?}", e);
@@ -261,24 +437,39 @@ impl ReplicationServer {
                 }
             };
 
+            let stream = match &tls_config {
+                Some(tls_config) => {
+                    match ReplicationStream::tls_server(socket, tls_config.clone()) {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            tracing::error!("Failed to create TLS stream. {:?}", e);
+                            continue;
+                        }
+                    }
+                }
+                None => ReplicationStream::plain(socket),
+            };
+
             let store_clone = store.clone();
             let server_options_clone = options.clone();
+            let backlog_clone = backlog.clone();
+            let replica_acks = self.replica_acks.clone();
             // spawn a thread to so we could move to sync api
             // this will allow us to write directly from the storage -> network
             // without building buffers in the memory
             let _handle = std::thread::spawn(move || {
                 tracing::info!("Replication thread started for connection {:?}", addr);
-                let _guard = ReplicationThreadMarker::new(addr.to_string());
-                let replica_name = addr.to_string();
+                let _guard = ReplicationThreadMarker::new(addr.to_string(), replica_acks.clone());
+                let mut session = ReplicaSession::new(addr.to_string());
 
                 // we now work in a simple request/reply mode:
                 // the replica sends a request requesting changes since
                 // the Nth update and this primary sends back the changes
 
                 // First, prepare the socket
-                if let Err(e) = prepare_std_socket(&stream) {
+                if let Err(e) = prepare_std_socket(stream.socket()) {
                     tracing::error!("Failed to prepare socket. {:?}", e);
-                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    stream.shutdown();
                     return;
                 }
 
@@ -286,11 +477,13 @@ impl ReplicationServer {
                     if !Self::handle_single_request(
                         &store_clone,
                         &server_options_clone,
-                        &mut stream,
-                        &replica_name,
+                        &backlog_clone,
+                        &replica_acks,
+                        &stream,
+                        &mut session,
                     ) {
                         tracing::info!("Closing connection with replica: {:?}", stream);
-                        let _ = stream.shutdown(std::net::Shutdown::Both);
+                        stream.shutdown();
                         break;
                     }
                 }
@@ -302,3 +495,123 @@ impl ReplicationServer {
         Ok::<(), SableError>(())
     }
 }
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replication::{ReplClientCommand, ReplicationClient, ReplicationConfig, ServerRole};
+    use crate::{storage::PutFlags, StorageOpenParams};
+    use bytes::BytesMut;
+    use std::path::PathBuf;
+
+    /// Open an empty database with its own configuration directory
+    fn create_server(
+        name: &str,
+        repl_config: &ReplicationConfig,
+    ) -> Result<(StorageAdapter, ServerOptions), SableError> {
+        let config_dir = PathBuf::from(format!("tests/test_{}", name));
+        let _ = std::fs::remove_dir_all(&config_dir);
+        std::fs::create_dir_all(&config_dir)?;
+        ReplicationConfig::write_file(repl_config, Some(&config_dir))?;
+
+        let open_params = StorageOpenParams::default().set_path(&config_dir.join("db"));
+        let store = crate::storage_rocksdb!(open_params.clone());
+        let mut options = ServerOptions {
+            open_params,
+            ..Default::default()
+        };
+        options.general_settings.config_dir = Some(config_dir);
+        Ok((store, options))
+    }
+
+    fn put_keys(store: &StorageAdapter, keys: std::ops::Range<usize>) -> Result<(), SableError> {
+        for i in keys {
+            store.put(
+                &BytesMut::from(format!("key_{}", i).as_str()),
+                &BytesMut::from(format!("value_{}", i).as_str()),
+                PutFlags::Override,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn has_keys(store: &StorageAdapter, keys: std::ops::Range<usize>) -> Result<bool, SableError> {
+        for i in keys {
+            let key = BytesMut::from(format!("key_{}", i).as_str());
+            if store.get(&key)? != Some(BytesMut::from(format!("value_{}", i).as_str())) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    #[test]
+    fn test_replication_fan_out() -> Result<(), SableError> {
+        const KEYS: usize = 1000;
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        let (primary, primary_options) = create_server(
+            "fan_out_primary",
+            &ReplicationConfig::primary_config("127.0.0.1".to_string(), port),
+        )?;
+        let replica_config = ReplicationConfig {
+            role: ServerRole::Replica,
+            ip: "127.0.0.1".to_string(),
+            port,
+        };
+        let replicas = [
+            create_server("fan_out_replica_1", &replica_config)?,
+            create_server("fan_out_replica_2", &replica_config)?,
+        ];
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            // the replicas start with a full sync, and then tail the primary's changes
+            put_keys(&primary, 0..KEYS / 2)?;
+            let primary_clone = primary.clone();
+            let replica_acks = Arc::new(ReplicaAcks::default());
+            let server = ReplicationServer::new(replica_acks.clone());
+            let server = tokio::spawn(async move {
+                let _ = server.run(primary_options, primary_clone).await;
+            });
+            let mut clients = Vec::new();
+            for (store, options) in &replicas {
+                clients.push(
+                    ReplicationClient::default()
+                        .run(options.clone(), store.clone())
+                        .await?,
+                );
+            }
+            put_keys(&primary, KEYS / 2..KEYS)?;
+
+            // both replicas converge to the primary's keyspace
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+            for (store, _) in &replicas {
+                while !has_keys(store, 0..KEYS)? {
+                    assert!(
+                        std::time::Instant::now() < deadline,
+                        "replica did not converge"
+                    );
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+            }
+            assert_eq!(replica_acks.replicas_count(), 2);
+
+            for client in clients {
+                let _ = client.send(ReplClientCommand::Shutdown).await;
+            }
+            server.abort();
+            replication_thread_stop_all().await;
+            Ok(())
+        })
+    }
+}
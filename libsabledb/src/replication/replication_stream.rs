@@ -0,0 +1,123 @@
+use crate::SableError;
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use tokio_rustls::rustls::{
+    pki_types::ServerName, ClientConfig, ClientConnection, ServerConfig, ServerConnection,
+    StreamOwned,
+};
+
+enum TlsStream {
+    /// The primary side of the connection
+    Server(Box<StreamOwned<ServerConnection, TcpStream>>),
+    /// The replica side of the connection
+    Client(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+/// A connection between a primary and one of its replicas: a plain TCP stream, or a TLS session
+/// on top of it.
+///
+/// Like `&TcpStream`, `&ReplicationStream` implements `Read` and `Write` so a reader and a writer
+/// can share the same connection. The TLS handshake is performed by the first read or write
+pub struct ReplicationStream {
+    /// The underlying socket, used to change its options (timeouts, blocking mode) and to shut
+    /// the connection down
+    socket: TcpStream,
+    tls: Option<RefCell<TlsStream>>,
+}
+
+impl ReplicationStream {
+    /// A plain TCP connection
+    pub fn plain(socket: TcpStream) -> Self {
+        ReplicationStream { socket, tls: None }
+    }
+
+    /// The primary side of a TLS connection
+    pub fn tls_server(socket: TcpStream, config: Arc<ServerConfig>) -> Result<Self, SableError> {
+        let conn = ServerConnection::new(config)
+            .map_err(|e| SableError::OtherError(format!("TLS error. {:?}", e)))?;
+        let tls = TlsStream::Server(Box::new(StreamOwned::new(conn, socket.try_clone()?)));
+        Ok(ReplicationStream {
+            socket,
+            tls: Some(RefCell::new(tls)),
+        })
+    }
+
+    /// The replica side of a TLS connection. The primary's certificate is verified against
+    /// `server_name`
+    pub fn tls_client(
+        socket: TcpStream,
+        config: Arc<ClientConfig>,
+        server_name: &str,
+    ) -> Result<Self, SableError> {
+        let server_name = ServerName::try_from(server_name.to_string()).map_err(|e| {
+            SableError::InvalidArgument(format!("Invalid TLS server name. {:?}", e))
+        })?;
+        let conn = ClientConnection::new(config, server_name)
+            .map_err(|e| SableError::OtherError(format!("TLS error. {:?}", e)))?;
+        let tls = TlsStream::Client(Box::new(StreamOwned::new(conn, socket.try_clone()?)));
+        Ok(ReplicationStream {
+            socket,
+            tls: Some(RefCell::new(tls)),
+        })
+    }
+
+    /// The underlying socket
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.socket.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+impl std::fmt::Debug for ReplicationStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicationStream")
+            .field("socket", &self.socket)
+            .field("tls", &self.is_tls())
+            .finish()
+    }
+}
+
+impl Read for &ReplicationStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &self.tls {
+            None => (&self.socket).read(buf),
+            Some(tls) => match &mut *tls.borrow_mut() {
+                TlsStream::Server(stream) => stream.read(buf),
+                TlsStream::Client(stream) => stream.read(buf),
+            },
+        }
+    }
+}
+
+impl Write for &ReplicationStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &self.tls {
+            None => (&self.socket).write(buf),
+            Some(tls) => match &mut *tls.borrow_mut() {
+                TlsStream::Server(stream) => stream.write(buf),
+                TlsStream::Client(stream) => stream.write(buf),
+            },
+        }
+    }
+
+    /// With TLS, the encrypted records might still be buffered after `write` returns. `flush`
+    /// pushes them to the socket
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &self.tls {
+            None => (&self.socket).flush(),
+            Some(tls) => match &mut *tls.borrow_mut() {
+                TlsStream::Server(stream) => stream.flush(),
+                TlsStream::Client(stream) => stream.flush(),
+            },
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::io;
+use crate::replication::ReplicationStream;
 use crate::{BytesMutUtils, SableError, U8ArrayReader};
 use bytes::BytesMut;
-use std::net::TcpStream;
+use std::io::Write;
 
 pub trait BytesWriter {
     /// Write a message. A "message" format is always: the length of the message
@@ -15,13 +16,25 @@ pub trait BytesReader {
     fn read_message(&mut self) -> Result<Option<BytesMut>, SableError>;
 }
 
+impl<T: BytesWriter + ?Sized> BytesWriter for &mut T {
+    fn write_message(&mut self, message: &mut BytesMut) -> Result<(), SableError> {
+        (**self).write_message(message)
+    }
+}
+
+impl<T: BytesReader + ?Sized> BytesReader for &mut T {
+    fn read_message(&mut self) -> Result<Option<BytesMut>, SableError> {
+        (**self).read_message()
+    }
+}
+
 /// TCP based writer
 pub struct TcpStreamBytesWriter<'a> {
-    tcp_stream: &'a TcpStream,
+    tcp_stream: &'a ReplicationStream,
 }
 
 impl<'a> TcpStreamBytesWriter<'a> {
-    pub fn new(tcp_stream: &'a TcpStream) -> Self {
+    pub fn new(tcp_stream: &'a ReplicationStream) -> Self {
         TcpStreamBytesWriter { tcp_stream }
     }
 }
@@ -36,18 +49,20 @@ impl<'a> TcpStreamBytesWriter<'a> {
 impl<'a> BytesWriter for TcpStreamBytesWriter<'a> {
     fn write_message(&mut self, message: &mut BytesMut) -> Result<(), SableError> {
         self.write_usize(message.len())?;
-        io::write_bytes(&mut self.tcp_stream, message)
+        io::write_bytes(&mut self.tcp_stream, message)?;
+        self.tcp_stream.flush()?;
+        Ok(())
     }
 }
 
 /// TCP based reader
 pub struct TcpStreamBytesReader<'a> {
-    tcp_stream: &'a TcpStream,
+    tcp_stream: &'a ReplicationStream,
     bytes_read: BytesMut,
 }
 
 impl<'a> TcpStreamBytesReader<'a> {
-    pub fn new(tcp_stream: &'a TcpStream) -> TcpStreamBytesReader {
+    pub fn new(tcp_stream: &'a ReplicationStream) -> TcpStreamBytesReader {
         TcpStreamBytesReader {
             tcp_stream,
             bytes_read: BytesMut::new(),
@@ -132,3 +147,133 @@ impl<'a> BytesReader for TcpStreamBytesReader<'a> {
         Ok(None)
     }
 }
+
+/// Compress every message written to the inner writer (LZ4). The compressed message starts with
+/// the size of the original message (32 bit, little endian)
+pub struct CompressedBytesWriter<W: BytesWriter> {
+    writer: W,
+}
+
+impl<W: BytesWriter> CompressedBytesWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CompressedBytesWriter { writer }
+    }
+}
+
+impl<W: BytesWriter> BytesWriter for CompressedBytesWriter<W> {
+    fn write_message(&mut self, message: &mut BytesMut) -> Result<(), SableError> {
+        let mut compressed = BytesMut::from(lz4_flex::compress_prepend_size(message).as_slice());
+        self.writer.write_message(&mut compressed)
+    }
+}
+
+/// Decompress the messages read from the inner reader, written by `CompressedBytesWriter`
+pub struct CompressedBytesReader<R: BytesReader> {
+    reader: R,
+}
+
+impl<R: BytesReader> CompressedBytesReader<R> {
+    pub fn new(reader: R) -> Self {
+        CompressedBytesReader { reader }
+    }
+}
+
+impl<R: BytesReader> BytesReader for CompressedBytesReader<R> {
+    fn read_message(&mut self) -> Result<Option<BytesMut>, SableError> {
+        let Some(compressed) = self.reader.read_message()? else {
+            return Ok(None);
+        };
+        let message = lz4_flex::decompress_size_prepended(&compressed).map_err(|e| {
+            SableError::OtherError(format!("Failed to decompress message. {:?}", e))
+        })?;
+        Ok(Some(BytesMut::from(message.as_slice())))
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replication::{StorageUpdates, StorageUpdatesIterItem};
+    use std::collections::VecDeque;
+
+    /// Messages written by the writer are queued for the reader
+    #[derive(Default)]
+    struct MessageQueue {
+        messages: VecDeque<BytesMut>,
+    }
+
+    impl BytesWriter for MessageQueue {
+        fn write_message(&mut self, message: &mut BytesMut) -> Result<(), SableError> {
+            self.messages.push_back(message.clone());
+            Ok(())
+        }
+    }
+
+    impl BytesReader for MessageQueue {
+        fn read_message(&mut self) -> Result<Option<BytesMut>, SableError> {
+            Ok(self.messages.pop_front())
+        }
+    }
+
+    fn records(updates: &StorageUpdates) -> Vec<(u8, BytesMut, Option<BytesMut>)> {
+        let mut records = Vec::new();
+        let mut reader = U8ArrayReader::with_buffer(&updates.serialised_data);
+        while let Some(item) = updates.next(&mut reader) {
+            match item {
+                StorageUpdatesIterItem::Put(put) => records.push((0, put.key, Some(put.value))),
+                StorageUpdatesIterItem::Del(del) => records.push((1, del.key, None)),
+                StorageUpdatesIterItem::DelRange(del_range) => {
+                    records.push((2, del_range.prefix, None))
+                }
+            }
+        }
+        records
+    }
+
+    #[test]
+    fn test_compressed_storage_updates_round_trip() -> Result<(), SableError> {
+        let mut updates = StorageUpdates::from_seq_number(10);
+        for i in 0..1000 {
+            let key = format!("key_{}", i);
+            if i % 10 == 0 {
+                updates.add_delete(key.as_bytes());
+            } else {
+                updates.add_put(key.as_bytes(), format!("value_{}", i).repeat(8).as_bytes());
+            }
+            updates.changes_count += 1;
+        }
+        updates.end_seq_number = 1010;
+
+        let mut queue = MessageQueue::default();
+        let mut plain = updates.to_bytes();
+        CompressedBytesWriter::new(&mut queue).write_message(&mut plain)?;
+
+        // the updates are repetitive, the message on the wire is smaller
+        assert!(queue.messages[0].len() < plain.len() / 2);
+
+        let mut reader = CompressedBytesReader::new(&mut queue);
+        let message = reader.read_message()?.unwrap();
+        assert_eq!(message, plain);
+        let decoded = StorageUpdates::from_bytes(&message).unwrap();
+        assert_eq!(decoded, updates);
+        assert_eq!(records(&decoded), records(&updates));
+        assert_eq!(records(&decoded).len(), 1000);
+        assert!(reader.read_message()?.is_none());
+
+        // corrupted (here: truncated) messages are reported
+        CompressedBytesWriter::new(&mut queue).write_message(&mut plain)?;
+        let compressed_len = queue.messages[0].len();
+        queue.messages[0].truncate(compressed_len / 2);
+        assert!(CompressedBytesReader::new(&mut queue)
+            .read_message()
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
-use crate::replication::ReplicationServer;
+use crate::replication::{ReplicaAcks, ReplicationServer};
 use crate::telemetry::ReplicationTelemetry;
+use std::sync::Arc;
 
 #[allow(unused_imports)]
 use crate::{
@@ -44,6 +45,8 @@ pub struct Replicator {
     rx_channel: ReplicatorReceiver,
     /// The store
     store: StorageAdapter,
+    /// The acknowledgements of the replicas, while this server is a primary
+    replica_acks: Arc<ReplicaAcks>,
 }
 
 #[derive(Clone, Debug)]
@@ -75,19 +78,23 @@ impl Replicator {
         rx: ReplicatorReceiver,
         server_options: ServerOptions,
         store: StorageAdapter,
+        replica_acks: Arc<ReplicaAcks>,
     ) -> Self {
         Replicator {
             rx_channel: rx,
             server_options,
             store,
+            replica_acks,
         }
     }
 
     /// Spawn the replication thread returning a a context for the caller
-    /// The context can be used to communicate with the replicator
+    /// The context can be used to communicate with the replicator. While this server is a
+    /// primary, the acknowledgements of its replicas are recorded in `replica_acks`
     pub fn run(
         server_options: ServerOptions,
         store: StorageAdapter,
+        replica_acks: Arc<ReplicaAcks>,
     ) -> Result<ReplicatorContext, SableError> {
         let (tx, rx) = tokio::sync::mpsc::channel::<ReplicationWorkerMessage>(100);
         let (handle_sender, handle_receiver) = std::sync::mpsc::channel();
@@ -114,7 +121,8 @@ impl Replicator {
                 let local = tokio::task::LocalSet::new();
                 local.block_on(&rt, async move {
                     let mut replicator =
-                        Replicator::new(rx, server_options.clone(), store.clone()).await;
+                        Replicator::new(rx, server_options.clone(), store.clone(), replica_acks)
+                            .await;
                     if let Err(e) = replicator.main_loop().await {
                         tracing::error!("replicator error. {:?}", e);
                     }
@@ -148,6 +156,7 @@ impl Replicator {
             .run(self.server_options.clone(), self.store.clone())
             .await?;
         ReplicationTelemetry::set_role(ServerRole::Replica);
+        self.store.set_replica_mode(true);
         loop {
             match self.rx_channel.recv().await {
                 Some(ReplicationWorkerMessage::ConnectToPrimary) => {
@@ -181,8 +190,9 @@ impl Replicator {
             "Running primary loop using config: {:?}",
             replication_config
         );
-        let server = ReplicationServer::default();
+        let server = ReplicationServer::new(self.replica_acks.clone());
         ReplicationTelemetry::set_role(ServerRole::Primary);
+        self.store.set_replica_mode(false);
         loop {
             tokio::select! {
                 cmd = self.rx_channel.recv() => {
@@ -4,6 +4,7 @@ use num_format::{Locale, ToFormattedString};
 
 const OPCODE_PUT: u8 = 0;
 const OPCODE_DEL: u8 = 1;
+const OPCODE_DEL_RANGE: u8 = 2;
 const USIZE_SIZE: usize = std::mem::size_of::<usize>();
 const U64_SIZE: usize = std::mem::size_of::<u64>();
 
@@ -66,10 +67,37 @@ impl DeleteRecord {
     }
 }
 
+/// The deletion of all the records starting with `prefix` (e.g. `FLUSHDB`)
+#[derive(Debug, Clone)]
+pub struct DeleteRangeRecord {
+    pub prefix: BytesMut,
+}
+
+impl DeleteRangeRecord {
+    pub fn to_bytes(builder: &mut U8ArrayBuilder, prefix: &[u8]) {
+        builder.write_usize(prefix.len());
+        builder.write_bytes(prefix);
+    }
+
+    /// Deserialise `DeleteRangeRecord` from bytes.
+    /// On failure return `None`. On success, return the deserialised object +
+    /// remove the bytes used to construct the object from the buffer
+    pub fn from_bytes(reader: &mut U8ArrayReader) -> Option<DeleteRangeRecord> {
+        let prefix_len = reader.read_usize()?;
+        let prefix = reader.read_bytes(prefix_len)?;
+        Some(DeleteRangeRecord::new(prefix))
+    }
+
+    pub fn new(prefix: BytesMut) -> Self {
+        DeleteRangeRecord { prefix }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum StorageUpdatesIterItem {
     Put(PutRecord),
     Del(DeleteRecord),
+    DelRange(DeleteRangeRecord),
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -131,6 +159,21 @@ impl StorageUpdates {
         DeleteRecord::to_bytes(&mut writer, key);
     }
 
+    /// Serialise a range deletion
+    pub fn add_delete_range(&mut self, prefix: &[u8]) {
+        let mut writer = U8ArrayBuilder::with_buffer(&mut self.serialised_data);
+        writer.write_u8(OPCODE_DEL_RANGE);
+        DeleteRangeRecord::to_bytes(&mut writer, prefix);
+    }
+
+    /// Append the changes of `other`, which must start where this object ends
+    pub fn append(&mut self, other: &StorageUpdates) {
+        self.end_seq_number = other.end_seq_number;
+        self.changes_count = self.changes_count.saturating_add(other.changes_count);
+        self.serialised_data
+            .extend_from_slice(&other.serialised_data);
+    }
+
     /// Return the size of the changes, in bytes
     pub fn len(&self) -> u64 {
         self.serialised_data.len() as u64
@@ -182,6 +225,10 @@ impl StorageUpdates {
                 let record = DeleteRecord::from_bytes(reader)?;
                 Some(StorageUpdatesIterItem::Del(record))
             }
+            OPCODE_DEL_RANGE => {
+                let record = DeleteRangeRecord::from_bytes(reader)?;
+                Some(StorageUpdatesIterItem::DelRange(record))
+            }
             _ => None,
         }
     }
@@ -246,6 +293,7 @@ mod tests {
         message.add_put(b"put_key2", b"put_value2");
         message.add_delete(b"delete_key1");
         message.add_put(b"put_key3", b"put_value3");
+        message.add_delete_range(b"prefix");
 
         let mut reader = U8ArrayReader::with_buffer(&message.serialised_data);
         {
@@ -282,5 +330,15 @@ mod tests {
             assert_eq!(put_rec.key, "put_key3");
             assert_eq!(put_rec.value, "put_value3");
         }
+
+        {
+            let Some(StorageUpdatesIterItem::DelRange(del_range_rec)) = message.next(&mut reader)
+            else {
+                assert!(false);
+                return;
+            };
+            assert_eq!(del_range_rec.prefix, "prefix");
+        }
+        assert!(message.next(&mut reader).is_none());
     }
 }
@@ -1,13 +1,19 @@
-use crate::{ParserError, RedisCommand, SableError, StringUtils};
+use crate::{server_options::ClientLimits, ParserError, RedisCommand, SableError, StringUtils};
 use bytes::BytesMut;
 use std::rc::Rc;
 
-#[derive(Default)]
+/// The longest inline request we accept (same as Redis). A longer line without a line terminator
+/// is a protocol error, so a client can not make us buffer an endless line
+const INLINE_MAX_SIZE: usize = 64 << 10;
+
+/// The longest length field of a `*` or `$` header: the digits of the largest `usize`
+const LENGTH_FIELD_MAX_SIZE: usize = 20;
+
 pub struct RequestParser {
     /// Parsed arguments in the input buffer in the form
     /// of: (`start_pos`, `token_len`)
     args: Vec<(usize, usize)>,
-    /// The current string start pos
+    /// The current string start pos (the start of the inline request)
     cur_string_start_pos: usize,
     /// The current string end position
     curpos: usize,
@@ -15,6 +21,17 @@ pub struct RequestParser {
     state: ParserState,
     /// Number of items we expect
     expected_items: usize,
+    /// The longest bulk string we accept (`proto-max-bulk-len`)
+    max_bulk_len: usize,
+    /// The largest number of arguments in a request (`proto-max-multibulk-len`)
+    max_multibulk_len: usize,
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        let limits = ClientLimits::default();
+        RequestParser::with_limits(limits.proto_max_bulk_len, limits.proto_max_multibulk_len)
+    }
 }
 
 #[derive(Default, Debug)]
@@ -34,31 +51,55 @@ enum ParserState {
 }
 
 impl RequestParser {
-    /// Read length. Return the `length` + number of bytes consumed from buffer
-    /// in order to parse the length
-    fn read_len(&self, buffer: &[u8]) -> Result<(usize, usize), SableError> {
+    /// Create a parser that rejects bulk strings longer than `max_bulk_len` bytes and requests
+    /// with more than `max_multibulk_len` arguments
+    pub fn with_limits(max_bulk_len: usize, max_multibulk_len: usize) -> Self {
+        RequestParser {
+            args: Vec::default(),
+            cur_string_start_pos: 0,
+            curpos: 0,
+            state: ParserState::default(),
+            expected_items: 0,
+            max_bulk_len,
+            max_multibulk_len,
+        }
+    }
+
+    /// Read the length field of a `*` or `$` header (`buffer` starts right after the `*` or
+    /// `$`). Return the `length` + number of bytes consumed from buffer in order to parse the
+    /// length. Negative, non numeric and lengths greater than `max_len` are protocol errors:
+    /// `header_name` is used in the error message
+    fn read_len(
+        &self,
+        buffer: &[u8],
+        max_len: usize,
+        header_name: &str,
+    ) -> Result<(usize, usize), SableError> {
+        let invalid_length = || {
+            SableError::Parser(ParserError::ProtocolError(format!(
+                "invalid {} length",
+                header_name
+            )))
+        };
+
         let Some(pos) = StringUtils::find_subsequence(buffer, b"\r\n") else {
+            if buffer.len() > LENGTH_FIELD_MAX_SIZE + 1 {
+                // no need to wait for the rest of this header: it is already too long
+                return Err(invalid_length());
+            }
             return Err(SableError::Parser(ParserError::NeedMoreData));
         };
 
-        if pos > 20 {
-            // we allow max of 20 chars of string length
-            return Err(SableError::Parser(ParserError::BufferTooBig));
+        if pos == 0 || pos > LENGTH_FIELD_MAX_SIZE || !buffer[..pos].iter().all(u8::is_ascii_digit)
+        {
+            return Err(invalid_length());
         }
 
         let len_as_str = String::from_utf8_lossy(&buffer[0..pos]);
-        let Ok(length) = len_as_str.parse::<usize>() else {
-            return Err(SableError::Parser(ParserError::ProtocolError(
-                "failed to parse string to usize".to_string(),
-            )));
-        };
-
-        if length > 512 << 20 {
-            // string input is larger than 512MB
-            return Err(SableError::Parser(ParserError::BufferTooBig));
+        match len_as_str.parse::<usize>() {
+            Ok(length) if length <= max_len => Ok((length, pos.saturating_add(2))),
+            _ => Err(invalid_length()),
         }
-
-        Ok((length, len_as_str.len().saturating_add(2)))
     }
 
     /// Reset the parser state
@@ -85,24 +126,37 @@ impl RequestParser {
         }
 
         match self.state {
-            ParserState::Initial => match buffer[0] {
-                b'*' => {
-                    let (array_len, bytes_to_skip) = self.read_len(&buffer[1..])?;
-                    let Some(curpos) = self.curpos.checked_add(1 + bytes_to_skip) else {
-                        return Err(SableError::Parser(ParserError::Overflow));
-                    };
-                    self.curpos = curpos;
-                    self.expected_items = array_len;
-                    self.state = ParserState::BulkString;
-                    self.parse_array_of_bulk_strings(buffer)
+            ParserState::Initial => {
+                // empty lines (e.g. a user hitting "enter" in a telnet session) are ignored
+                let start = Self::empty_lines_len(buffer);
+                if start == buffer.len() {
+                    return Err(SableError::Parser(ParserError::NeedMoreData));
                 }
-                _ => {
-                    // inline string
-                    self.state = ParserState::InlineString;
-                    self.curpos = 0;
-                    self.parse_inline_string(buffer)
+
+                match buffer[start] {
+                    b'*' => {
+                        let (array_len, bytes_to_skip) = self.read_len(
+                            &buffer[start + 1..],
+                            self.max_multibulk_len,
+                            "multibulk",
+                        )?;
+                        let Some(curpos) = start.checked_add(1 + bytes_to_skip) else {
+                            return Err(SableError::Parser(ParserError::Overflow));
+                        };
+                        self.curpos = curpos;
+                        self.expected_items = array_len;
+                        self.state = ParserState::BulkString;
+                        self.parse_array_of_bulk_strings(buffer)
+                    }
+                    _ => {
+                        // inline string
+                        self.state = ParserState::InlineString;
+                        self.cur_string_start_pos = start;
+                        self.curpos = start;
+                        self.parse_inline_string(buffer)
+                    }
                 }
-            },
+            }
             ParserState::InlineString => {
                 // skip what we already parsed
                 self.parse_inline_string(buffer)
@@ -111,7 +165,21 @@ impl RequestParser {
         }
     }
 
-    /// Read until the first `\r\n` that we find
+    /// Return the length of the empty lines (lines made of whitespace only) that start `buffer`
+    fn empty_lines_len(buffer: &[u8]) -> usize {
+        let mut len = 0usize;
+        while let Some(pos) = buffer[len..].iter().position(|c| *c == b'\n') {
+            let line = &buffer[len..len + pos];
+            if !line.iter().all(|c| matches!(c, b' ' | b'\t' | b'\r')) {
+                break;
+            }
+            len = len.saturating_add(pos).saturating_add(1);
+        }
+        len
+    }
+
+    /// Read an inline request: a single line of space separated arguments, terminated by `\r\n`
+    /// (or `\n`). Arguments may be quoted, see `StringUtils::split`
     fn parse_inline_string(&mut self, buffer: &[u8]) -> Result<ParseResult, SableError> {
         if self.curpos >= buffer.len() {
             return Err(SableError::Parser(ParserError::ProtocolError(
@@ -120,31 +188,49 @@ impl RequestParser {
             )));
         }
 
+        let start = self.cur_string_start_pos;
         let curbuf = &buffer[self.curpos..];
-        let Some(pos) = StringUtils::find_subsequence(curbuf, b"\r\n") else {
-            if buffer.ends_with(b"\r") {
-                // we managed to find the `\r` but not the `\n`
-                self.curpos = buffer.len() - 1;
-            } else {
-                self.curpos = buffer.len();
+        let Some(pos) = curbuf.iter().position(|c| *c == b'\n') else {
+            if buffer.len().saturating_sub(start) > INLINE_MAX_SIZE {
+                return Err(Self::too_big_inline_request());
             }
+            // continue from here once more data arrives
+            self.curpos = buffer.len();
             return Err(SableError::Parser(ParserError::NeedMoreData));
         };
 
-        let mut command_string = BytesMut::with_capacity(self.curpos + pos);
-        command_string.extend_from_slice(&buffer[..self.curpos + pos]);
-        let bytes_consumed = self.curpos.saturating_add(pos).saturating_add(2);
+        let line_end = self.curpos.saturating_add(pos);
+        if line_end.saturating_sub(start) > INLINE_MAX_SIZE {
+            return Err(Self::too_big_inline_request());
+        }
+
+        let line = &buffer[start..line_end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut command_string = BytesMut::from(line);
+        // its OK to add `+1` here, since we managed to find `\n` above
+        let bytes_consumed = line_end.saturating_add(1);
 
         // reset the parser
         self.reset();
 
+        let Ok(args) = StringUtils::split(&mut command_string) else {
+            return Err(SableError::Parser(ParserError::ProtocolError(
+                "unbalanced quotes in request".to_string(),
+            )));
+        };
+
         Ok(ParseResult {
-            command: Rc::new(RedisCommand::new(StringUtils::split(&mut command_string)?)?),
-            // its OK to add `+2` here, since we managed to find `\r\n` above
+            command: Rc::new(RedisCommand::new(args)?),
             bytes_consumed,
         })
     }
 
+    fn too_big_inline_request() -> SableError {
+        SableError::Parser(ParserError::ProtocolError(
+            "too big inline request".to_string(),
+        ))
+    }
+
     fn parse_array_of_bulk_strings(&mut self, buffer: &[u8]) -> Result<ParseResult, SableError> {
         let mut curpos = self.curpos;
         while self.expected_items > 0 {
@@ -155,7 +241,7 @@ impl RequestParser {
             // locate the `$`
             if buffer[curpos] != b'$' {
                 return Err(SableError::Parser(ParserError::ProtocolError(format!(
-                    "bulk string must start with '$'. Found {}",
+                    "expected '$', got '{}'",
                     buffer[curpos] as char
                 ))));
             }
@@ -163,7 +249,8 @@ impl RequestParser {
             curpos = curpos.saturating_add(1);
 
             // read the length
-            let (str_len, bytes_to_skip) = self.read_len(&buffer[curpos..])?;
+            let (str_len, bytes_to_skip) =
+                self.read_len(&buffer[curpos..], self.max_bulk_len, "bulk")?;
             curpos = curpos.saturating_add(bytes_to_skip);
 
             // keep the current string position
@@ -174,7 +261,13 @@ impl RequestParser {
                 return Err(SableError::Parser(ParserError::NeedMoreData));
             }
 
-            curpos = curpos.saturating_add(str_len).saturating_add(2);
+            curpos = curpos.saturating_add(str_len);
+            if &buffer[curpos..curpos + 2] != b"\r\n" {
+                return Err(SableError::Parser(ParserError::ProtocolError(
+                    "expected CRLF after bulk string".to_string(),
+                )));
+            }
+            curpos = curpos.saturating_add(2);
 
             self.args.push((string_start_pos, str_len));
             self.curpos = curpos;
@@ -397,4 +490,135 @@ mod tests {
         // remove the buffer read
         let _ = buffer.split_to(result.bytes_consumed);
     }
+
+    #[test]
+    fn test_inline_message_terminated_by_newline() {
+        let mut request_parser = RequestParser::default();
+        let buffer = BytesMut::from("\r\n  \nSET KEY VALUE\nPING\r\n");
+        let result = request_parser.parse(&buffer).unwrap();
+        // the empty lines that precede the request are consumed with it
+        assert_eq!(result.bytes_consumed, 19);
+        assert_eq!(result.command.arg_count(), 3);
+        assert_eq!(result.command.arg(0).unwrap(), "SET");
+        assert_eq!(result.command.arg(2).unwrap(), "VALUE");
+
+        let result = request_parser
+            .parse(&buffer[result.bytes_consumed..])
+            .unwrap();
+        assert_eq!(result.bytes_consumed, 6);
+        assert_eq!(result.command.arg(0).unwrap(), "PING");
+
+        // an array request that follows an empty line
+        let buffer = BytesMut::from("\r\n*1\r\n$4\r\nPING\r\n");
+        let result = request_parser.parse(&buffer).unwrap();
+        assert_eq!(result.bytes_consumed, buffer.len());
+        assert_eq!(result.command.arg(0).unwrap(), "PING");
+
+        let result = request_parser.parse(b"\r\n\r\n");
+        assert!(result
+            .unwrap_err()
+            .eq_parser_error(&ParserError::NeedMoreData));
+    }
+
+    #[test]
+    fn test_inline_message_with_escapes() {
+        let mut request_parser = RequestParser::default();
+        let buffer = BytesMut::from("SET KEY \"a\\tb\\x41\" \"\"\r\n");
+        let result = request_parser.parse(&buffer).unwrap();
+        assert_eq!(result.command.arg_count(), 4);
+        assert_eq!(result.command.arg(2).unwrap(), "a\tbA");
+        assert_eq!(result.command.arg(3).unwrap(), "");
+    }
+
+    #[test]
+    fn test_inline_message_protocol_errors() {
+        let unbalanced = ParserError::ProtocolError("unbalanced quotes in request".to_string());
+        for input in [
+            "SET KEY \"VALUE\r\n",
+            "SET KEY 'VALUE\r\n",
+            "SET KEY \"A\"B\r\n",
+        ] {
+            let mut request_parser = RequestParser::default();
+            let result = request_parser.parse(input.as_bytes());
+            assert!(
+                result.unwrap_err().eq_parser_error(&unbalanced),
+                "{}",
+                input
+            );
+        }
+
+        // a line can not grow beyond the limit, with or without its terminator
+        let too_big = ParserError::ProtocolError("too big inline request".to_string());
+        let mut buffer = BytesMut::from("SET KEY ");
+        buffer.extend_from_slice(&vec![b'x'; INLINE_MAX_SIZE]);
+        let mut request_parser = RequestParser::default();
+        let result = request_parser.parse(&buffer);
+        assert!(result.unwrap_err().eq_parser_error(&too_big));
+
+        buffer.extend_from_slice(b"\r\n");
+        let mut request_parser = RequestParser::default();
+        let result = request_parser.parse(&buffer);
+        assert!(result.unwrap_err().eq_parser_error(&too_big));
+    }
+
+    #[test]
+    fn test_truncated_headers_need_more_data() {
+        for input in ["*", "*3", "*3\r", "*1\r\n$", "*1\r\n$10", "*1\r\n$3\r\nGE"] {
+            let mut request_parser = RequestParser::default();
+            let result = request_parser.parse(input.as_bytes());
+            assert!(
+                result
+                    .unwrap_err()
+                    .eq_parser_error(&ParserError::NeedMoreData),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let invalid_multibulk = ParserError::ProtocolError("invalid multibulk length".to_string());
+        let invalid_bulk = ParserError::ProtocolError("invalid bulk length".to_string());
+        let max_usize_plus_one = format!("*{}0\r\n", usize::MAX);
+        let endless_header = format!("*{}", "1".repeat(1000));
+        for (input, expected_error) in [
+            ("*-1\r\n", &invalid_multibulk),
+            ("*abc\r\n", &invalid_multibulk),
+            ("*\r\n", &invalid_multibulk),
+            ("*1 \r\n", &invalid_multibulk),
+            ("*+1\r\n", &invalid_multibulk),
+            (max_usize_plus_one.as_str(), &invalid_multibulk),
+            // there is no need to wait for the rest of a header that is already too long
+            (endless_header.as_str(), &invalid_multibulk),
+            // more arguments than `proto-max-multibulk-len`
+            ("*1025\r\n", &invalid_multibulk),
+            ("*1\r\n$-1\r\n", &invalid_bulk),
+            ("*1\r\n$abc\r\n", &invalid_bulk),
+            // longer than `proto-max-bulk-len`, rejected before the data arrives
+            ("*1\r\n$1025\r\n", &invalid_bulk),
+            ("*1\r\n$99999999999999999999999\r\n", &invalid_bulk),
+            (
+                "*2\r\n$3\r\nGET\r\n:1\r\n",
+                &ParserError::ProtocolError("expected '$', got ':'".to_string()),
+            ),
+            (
+                "*1\r\n$3\r\nGETXX",
+                &ParserError::ProtocolError("expected CRLF after bulk string".to_string()),
+            ),
+        ] {
+            let mut request_parser = RequestParser::with_limits(1024, 1024);
+            let result = request_parser.parse(input.as_bytes());
+            assert!(
+                result.unwrap_err().eq_parser_error(expected_error),
+                "{}",
+                input
+            );
+        }
+
+        // right at the limits
+        let mut request_parser = RequestParser::with_limits(3, 1);
+        let result = request_parser.parse(b"*1\r\n$3\r\nGET\r\n").unwrap();
+        assert_eq!(result.command.arg(0).unwrap(), "GET");
+    }
 }
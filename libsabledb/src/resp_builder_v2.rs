@@ -5,19 +5,58 @@ const DOLLAR: &str = "$";
 const CRLF_LEN: usize = 2;
 const DOLLAR_LEN: usize = 1;
 
-//const PLUS: &str = "+";
+const PLUS: &str = "+";
 const ERR: &str = "-";
 const OK: &str = "+OK\r\n";
 const NULL_STRING: &str = "$-1\r\n";
 const EMPTY_ARRAY: &str = "*0\r\n";
+const NULL_ARRAY: &str = "*-1\r\n";
 const EMPTY_STRING: &str = "$0\r\n\r\n";
 const PONG: &str = "+PONG\r\n";
+const RESP3_NULL: &str = "_\r\n";
+const RESP3_EMPTY_MAP: &str = "%0\r\n";
 
+/// The RESP protocol version negotiated with the client (`HELLO`)
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RespProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl RespProtocolVersion {
+    /// The protocol version, as used by `HELLO`
+    pub fn as_number(&self) -> u64 {
+        match self {
+            RespProtocolVersion::Resp2 => 2,
+            RespProtocolVersion::Resp3 => 3,
+        }
+    }
+}
+
+/// Builds RESP responses. The default builder speaks RESP2. A builder created with
+/// `RespProtocolVersion::Resp3` emits the RESP3 types (maps, doubles, booleans, big numbers and
+/// nulls), the RESP2 builder falls back to their RESP2 representation
 #[derive(Default, Clone)]
-pub struct RespBuilderV2 {}
+pub struct RespBuilderV2 {
+    protocol: RespProtocolVersion,
+}
 
 #[allow(dead_code)]
 impl RespBuilderV2 {
+    pub fn with_protocol(protocol: RespProtocolVersion) -> Self {
+        RespBuilderV2 { protocol }
+    }
+
+    /// The protocol version used by this builder
+    pub fn protocol(&self) -> RespProtocolVersion {
+        self.protocol
+    }
+
+    fn is_resp3(&self) -> bool {
+        self.protocol == RespProtocolVersion::Resp3
+    }
+
     fn append_str(&self, buffer: &mut BytesMut, s: &str) {
         buffer.extend_from_slice(s.as_bytes());
     }
@@ -38,7 +77,11 @@ impl RespBuilderV2 {
     }
 
     fn add_null_string_internal(&self, buffer: &mut BytesMut) {
-        self.append_str(buffer, NULL_STRING);
+        if self.is_resp3() {
+            self.append_str(buffer, RESP3_NULL);
+        } else {
+            self.append_str(buffer, NULL_STRING);
+        }
     }
 
     /// Clears the buffer and create a bulk string RESP response
@@ -65,6 +108,14 @@ impl RespBuilderV2 {
         self.append_str(buffer, EMPTY_STRING);
     }
 
+    /// Clears the buffer and create a simple string RESP response
+    pub fn simple_string(&self, buffer: &mut BytesMut, msg: &str) {
+        buffer.clear();
+        self.append_str(buffer, PLUS);
+        self.append_str(buffer, msg);
+        self.append_str(buffer, CRLF);
+    }
+
     /// Clears the buffer and create an error string RESP response
     pub fn error_string(&self, buffer: &mut BytesMut, msg: &str) {
         buffer.clear();
@@ -79,6 +130,26 @@ impl RespBuilderV2 {
         self.append_str(buffer, EMPTY_ARRAY);
     }
 
+    /// Clears the buffer and create a null array RESP response
+    pub fn null_array(&self, buffer: &mut BytesMut) {
+        buffer.clear();
+        if self.is_resp3() {
+            self.append_str(buffer, RESP3_NULL);
+        } else {
+            self.append_str(buffer, NULL_ARRAY);
+        }
+    }
+
+    /// Clears the buffer and create an empty map RESP response (an empty array in RESP2)
+    pub fn empty_map(&self, buffer: &mut BytesMut) {
+        buffer.clear();
+        if self.is_resp3() {
+            self.append_str(buffer, RESP3_EMPTY_MAP);
+        } else {
+            self.append_str(buffer, EMPTY_ARRAY);
+        }
+    }
+
     /// Clears the buffer and create a RESP `PONG` response
     pub fn pong(&self, buffer: &mut BytesMut) {
         buffer.clear();
@@ -190,4 +261,88 @@ impl RespBuilderV2 {
     pub fn add_empty_array(&self, buffer: &mut BytesMut) {
         buffer.extend_from_slice(EMPTY_ARRAY.as_bytes());
     }
+
+    /// Append map len (the number of key-value pairs) to the buffer. In RESP2, maps are sent
+    /// as flat arrays of `2 * num` elements
+    /// NOTE: this function does not clear the buffer
+    pub fn add_map_len(&self, buffer: &mut BytesMut, num: usize) {
+        if self.is_resp3() {
+            let s = format!("%{}\r\n", num);
+            buffer.extend_from_slice(s.as_bytes());
+        } else {
+            self.add_array_len(buffer, num.saturating_mul(2));
+        }
+    }
+
+    /// Append a double. RESP2 has no double type, the number is sent as a bulk string
+    /// NOTE: this function does not clear the buffer
+    pub fn add_double(&self, buffer: &mut BytesMut, num: f64) {
+        if self.is_resp3() {
+            let s = format!(",{}\r\n", num);
+            buffer.extend_from_slice(s.as_bytes());
+        } else {
+            self.add_bulk_string_u8_arr(buffer, format!("{}", num).as_bytes());
+        }
+    }
+
+    /// Append a boolean. RESP2 has no boolean type, the value is sent as `:1` or `:0`
+    /// NOTE: this function does not clear the buffer
+    pub fn add_bool(&self, buffer: &mut BytesMut, value: bool) {
+        let s = match (self.is_resp3(), value) {
+            (true, true) => "#t\r\n",
+            (true, false) => "#f\r\n",
+            (false, true) => ":1\r\n",
+            (false, false) => ":0\r\n",
+        };
+        self.append_str(buffer, s);
+    }
+
+    /// Append a big number (`num` is its decimal representation). RESP2 has no big number
+    /// type, the number is sent as a bulk string
+    /// NOTE: this function does not clear the buffer
+    pub fn add_big_number(&self, buffer: &mut BytesMut, num: &str) {
+        if self.is_resp3() {
+            self.append_str(buffer, "(");
+            self.append_str(buffer, num);
+            self.append_str(buffer, CRLF);
+        } else {
+            self.add_bulk_string_u8_arr(buffer, num.as_bytes());
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resp3_types() {
+        for (protocol, expected_value) in [
+            (
+                RespProtocolVersion::Resp2,
+                "*2\r\n$3\r\n1.5\r\n:1\r\n:0\r\n$5\r\n12345\r\n$-1\r\n",
+            ),
+            (
+                RespProtocolVersion::Resp3,
+                "%1\r\n,1.5\r\n#t\r\n#f\r\n(12345\r\n_\r\n",
+            ),
+        ] {
+            let builder = RespBuilderV2::with_protocol(protocol);
+            let mut buffer = BytesMut::new();
+            builder.add_map_len(&mut buffer, 1);
+            builder.add_double(&mut buffer, 1.5);
+            builder.add_bool(&mut buffer, true);
+            builder.add_bool(&mut buffer, false);
+            builder.add_big_number(&mut buffer, "12345");
+            builder.add_null_string(&mut buffer);
+            assert_eq!(buffer, expected_value);
+        }
+    }
 }
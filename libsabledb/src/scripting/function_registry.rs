@@ -0,0 +1,171 @@
+use crate::{scripting::ScriptRunner, storage::FunctionDb, SableError, StorageAdapter};
+use bytes::BytesMut;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+/// A function registered by a library with `redis.register_function`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+/// A library loaded with `FUNCTION LOAD`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionLibrary {
+    pub name: String,
+    /// The library source code, including its `#!lua name=<name>` metadata line
+    pub code: BytesMut,
+    pub functions: Vec<FunctionInfo>,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    /// Library name -> library
+    libraries: BTreeMap<String, Arc<FunctionLibrary>>,
+    /// Function name -> the name of the library that registered it
+    functions: HashMap<String, String>,
+}
+
+/// The function libraries known to the server. The registry is shared by all the workers and is
+/// rebuilt from the storage when the server starts, the commands keep both in sync
+#[derive(Default)]
+pub struct FunctionRegistry {
+    inner: RwLock<RegistryInner>,
+}
+
+impl FunctionRegistry {
+    /// Load the libraries persisted in `store`. A library that no longer loads is skipped
+    pub fn load(&self, store: &StorageAdapter) -> Result<(), SableError> {
+        for (name, code) in FunctionDb::with_storage(store).list()? {
+            match ScriptRunner::load_library(&code).and_then(|library| self.add(library, true)) {
+                Ok(()) => tracing::info!("Loaded function library '{}'", name),
+                Err(e) => tracing::warn!("Failed to load function library '{}'. {}", name, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `library`. Unless `replace` is set, an existing library with the same name is an
+    /// error. A function name can only be registered by a single library
+    pub fn add(&self, library: FunctionLibrary, replace: bool) -> Result<(), String> {
+        let mut inner = self.inner.write().expect("poisoned lock");
+        if !replace && inner.libraries.contains_key(&library.name) {
+            return Err(format!("ERR Library '{}' already exists", library.name));
+        }
+
+        for function in &library.functions {
+            match inner.functions.get(&function.name) {
+                Some(owner) if *owner != library.name => {
+                    return Err(format!("ERR Function {} already exists", function.name));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(old) = inner.libraries.remove(&library.name) {
+            for function in &old.functions {
+                inner.functions.remove(&function.name);
+            }
+        }
+        for function in &library.functions {
+            inner
+                .functions
+                .insert(function.name.clone(), library.name.clone());
+        }
+        inner
+            .libraries
+            .insert(library.name.clone(), Arc::new(library));
+        Ok(())
+    }
+
+    /// Remove the library `name`. Return `false` if there is no such library
+    pub fn remove(&self, name: &str) -> bool {
+        let mut inner = self.inner.write().expect("poisoned lock");
+        let Some(library) = inner.libraries.remove(name) else {
+            return false;
+        };
+        for function in &library.functions {
+            inner.functions.remove(&function.name);
+        }
+        true
+    }
+
+    /// Remove all the libraries
+    pub fn flush(&self) {
+        let mut inner = self.inner.write().expect("poisoned lock");
+        inner.libraries.clear();
+        inner.functions.clear();
+    }
+
+    /// Return the library that registered the function `name`
+    pub fn find_function(&self, name: &str) -> Option<Arc<FunctionLibrary>> {
+        let inner = self.inner.read().expect("poisoned lock");
+        let library = inner.functions.get(name)?;
+        inner.libraries.get(library).cloned()
+    }
+
+    /// Return all the libraries, ordered by name
+    pub fn libraries(&self) -> Vec<Arc<FunctionLibrary>> {
+        let inner = self.inner.read().expect("poisoned lock");
+        inner.libraries.values().cloned().collect()
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str, functions: &[&str]) -> FunctionLibrary {
+        FunctionLibrary {
+            name: name.to_string(),
+            code: BytesMut::new(),
+            functions: functions
+                .iter()
+                .map(|name| FunctionInfo {
+                    name: name.to_string(),
+                    description: None,
+                    flags: Vec::default(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_function_registry() {
+        let registry = FunctionRegistry::default();
+        registry.add(library("lib1", &["f1", "f2"]), false).unwrap();
+        assert_eq!(
+            registry.add(library("lib1", &["f3"]), false),
+            Err("ERR Library 'lib1' already exists".to_string())
+        );
+        assert_eq!(
+            registry.add(library("lib2", &["f2"]), false),
+            Err("ERR Function f2 already exists".to_string())
+        );
+        assert_eq!(registry.find_function("f1").unwrap().name, "lib1");
+
+        // replacing a library drops the functions it no longer registers
+        registry.add(library("lib1", &["f3"]), true).unwrap();
+        assert!(registry.find_function("f1").is_none());
+        assert_eq!(registry.find_function("f3").unwrap().name, "lib1");
+        registry.add(library("lib2", &["f2"]), false).unwrap();
+        assert_eq!(registry.libraries().len(), 2);
+
+        assert!(registry.remove("lib2"));
+        assert!(!registry.remove("lib2"));
+        assert!(registry.find_function("f2").is_none());
+
+        registry.flush();
+        assert!(registry.libraries().is_empty());
+        assert!(registry.find_function("f3").is_none());
+    }
+}
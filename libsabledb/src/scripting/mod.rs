@@ -0,0 +1,7 @@
+mod function_registry;
+mod script_cache;
+mod script_runner;
+
+pub use function_registry::{FunctionInfo, FunctionLibrary, FunctionRegistry};
+pub use script_cache::ScriptCache;
+pub use script_runner::ScriptRunner;
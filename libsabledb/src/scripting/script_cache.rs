@@ -0,0 +1,80 @@
+use bytes::BytesMut;
+use dashmap::DashMap;
+
+/// The scripts known to the server (`EVAL` and `SCRIPT LOAD`), keyed by the SHA1 digest of their
+/// body. The cache is shared by all the workers, so `EVALSHA` works on any connection
+#[derive(Default)]
+pub struct ScriptCache {
+    scripts: DashMap<String, BytesMut>,
+}
+
+impl ScriptCache {
+    /// Return the SHA1 digest of `body` as a lowercase hex string
+    pub fn sha1_hex(body: &[u8]) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, body);
+        digest
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Add `body` to the cache and return its SHA1 digest
+    pub fn add(&self, body: &BytesMut) -> String {
+        let sha1 = Self::sha1_hex(body);
+        self.scripts
+            .entry(sha1.clone())
+            .or_insert_with(|| body.clone());
+        sha1
+    }
+
+    /// Return the script with the given SHA1 digest (case insensitive)
+    pub fn get(&self, sha1: &str) -> Option<BytesMut> {
+        self.scripts
+            .get(&sha1.to_lowercase())
+            .map(|body| body.value().clone())
+    }
+
+    /// Is the script with the given SHA1 digest (case insensitive) in the cache?
+    pub fn contains(&self, sha1: &str) -> bool {
+        self.scripts.contains_key(&sha1.to_lowercase())
+    }
+
+    /// Remove all the scripts (`SCRIPT FLUSH`)
+    pub fn flush(&self) {
+        self.scripts.clear();
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_cache() {
+        assert_eq!(
+            ScriptCache::sha1_hex(b""),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+
+        let cache = ScriptCache::default();
+        let sha1 = cache.add(&BytesMut::from("return 1"));
+        assert_eq!(sha1, "e0e1f9fabfc9d4800c877a703b823ac0578ff8db");
+        assert!(cache.contains(&sha1.to_uppercase()));
+        assert_eq!(
+            cache.get(&sha1.to_uppercase()),
+            Some(BytesMut::from("return 1"))
+        );
+
+        cache.flush();
+        assert!(!cache.contains(&sha1));
+        assert!(cache.get(&sha1).is_none());
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{
+    client::ClientState,
+    commands::{ErrorStrings, RedisCommandFlags},
+    replication::PeerReply,
+    scripting::{FunctionInfo, FunctionLibrary},
+    BytesMutUtils, Client, RedisCommand, RedisCommandName, RespBuilderV2, RespProtocolVersion,
+    StringUtils,
+};
+use bytes::BytesMut;
+use mlua::{ChunkMode, Function, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The name given to the scripts, as it appears in the Lua error messages
+const SCRIPT_NAME: &str = "user_script";
+
+/// The flags a function may be registered with
+const FUNCTION_FLAGS: &[&str] = &[
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+/// Runs the Lua scripts of `EVAL` and `EVALSHA` and the functions of `FCALL`. Every run gets its
+/// own interpreter, so a script can not leave state behind for the next one. Scripts call back into the server with
+/// `redis.call` and `redis.pcall`, the commands run on behalf of the calling client
+pub struct ScriptRunner {}
+
+impl ScriptRunner {
+    /// Check that `body` compiles. Return the error reply otherwise
+    pub fn compile(body: &[u8]) -> Result<(), String> {
+        let lua = Self::new_lua().map_err(|e| Self::error_reply(&e))?;
+        lua.load(body)
+            .set_name(SCRIPT_NAME)
+            .set_mode(ChunkMode::Text)
+            .into_function()
+            .map(|_| ())
+            .map_err(|e| Self::error_reply(&e))
+    }
+
+    /// Run `body` with the `KEYS` and `ARGV` tables built from `keys` and `args`. Return the
+    /// script's result converted to RESP, using the client's protocol
+    pub async fn run(
+        client_state: Rc<ClientState>,
+        body: &[u8],
+        keys: &[BytesMut],
+        args: &[BytesMut],
+    ) -> BytesMut {
+        let builder = client_state.resp_builder();
+        let mut reply = BytesMut::with_capacity(64);
+
+        // the commands called by the script reply in RESP2, whatever the client speaks. A
+        // `SELECT` called by the script does not outlive it
+        let resp_version = client_state.resp_version();
+        let db_id = client_state.database_id();
+        client_state.set_resp_version(RespProtocolVersion::Resp2);
+        let result = Self::eval(client_state.clone(), body, keys, args, &builder, &mut reply).await;
+        client_state.set_resp_version(resp_version);
+        client_state.set_database_id(db_id);
+
+        if let Err(e) = result {
+            builder.error_string(&mut reply, &Self::error_reply(&e));
+        }
+        reply
+    }
+
+    async fn eval(
+        client_state: Rc<ClientState>,
+        body: &[u8],
+        keys: &[BytesMut],
+        args: &[BytesMut],
+        builder: &RespBuilderV2,
+        reply: &mut BytesMut,
+    ) -> mlua::Result<()> {
+        let lua = Self::new_lua()?;
+        let globals = lua.globals();
+        globals.set("KEYS", Self::to_lua_strings(&lua, keys)?)?;
+        globals.set("ARGV", Self::to_lua_strings(&lua, args)?)?;
+
+        let redis = Self::new_redis_table(&lua)?;
+        Self::add_call_functions(&lua, &redis, client_state)?;
+        globals.set("redis", redis)?;
+
+        let value = lua
+            .load(body)
+            .set_name(SCRIPT_NAME)
+            .set_mode(ChunkMode::Text)
+            .eval_async::<Value>()
+            .await?;
+        Self::add_value(builder, reply, &value)
+    }
+
+    /// Parse and run the code of a function library (`FUNCTION LOAD`), collecting the functions
+    /// it registers. The library code can not call the server while it is being loaded
+    pub fn load_library(code: &[u8]) -> Result<FunctionLibrary, String> {
+        let (name, body) = Self::parse_library_metadata(code)?;
+        let lua = Self::new_lua().map_err(|e| Self::error_reply(&e))?;
+        let functions = Self::register_library(&lua, body).map_err(|e| Self::error_reply(&e))?;
+        if functions.is_empty() {
+            return Err("ERR No functions registered".to_string());
+        }
+
+        Ok(FunctionLibrary {
+            name,
+            code: BytesMut::from(code),
+            functions: functions.into_iter().map(|(info, _)| info).collect(),
+        })
+    }
+
+    /// Run the function `function` of the library `code` (`FCALL`). The function is called with
+    /// the `keys` and `args` tables as its arguments. Return its result converted to RESP, using
+    /// the client's protocol
+    pub async fn fcall(
+        client_state: Rc<ClientState>,
+        code: &[u8],
+        function: &str,
+        keys: &[BytesMut],
+        args: &[BytesMut],
+    ) -> BytesMut {
+        let builder = client_state.resp_builder();
+        let mut reply = BytesMut::with_capacity(64);
+
+        let resp_version = client_state.resp_version();
+        let db_id = client_state.database_id();
+        client_state.set_resp_version(RespProtocolVersion::Resp2);
+        let result = Self::call_function(
+            client_state.clone(),
+            code,
+            function,
+            keys,
+            args,
+            &builder,
+            &mut reply,
+        )
+        .await;
+        client_state.set_resp_version(resp_version);
+        client_state.set_database_id(db_id);
+
+        if let Err(msg) = result {
+            builder.error_string(&mut reply, &msg);
+        }
+        reply
+    }
+
+    async fn call_function(
+        client_state: Rc<ClientState>,
+        code: &[u8],
+        function: &str,
+        keys: &[BytesMut],
+        args: &[BytesMut],
+        builder: &RespBuilderV2,
+        reply: &mut BytesMut,
+    ) -> Result<(), String> {
+        let (_, body) = Self::parse_library_metadata(code)?;
+        let lua = Self::new_lua().map_err(|e| Self::error_reply(&e))?;
+        let functions = Self::register_library(&lua, body).map_err(|e| Self::error_reply(&e))?;
+        let Some((_, callback)) = functions
+            .into_iter()
+            .find(|(info, _)| info.name == function)
+        else {
+            return Err(ErrorStrings::FUNCTION_NOT_FOUND.to_string());
+        };
+
+        // the server can only be called once the library is loaded
+        let run = async {
+            let redis = lua.globals().get::<Table>("redis")?;
+            Self::add_call_functions(&lua, &redis, client_state)?;
+            let value = callback
+                .call_async::<Value>((
+                    Self::to_lua_strings(&lua, keys)?,
+                    Self::to_lua_strings(&lua, args)?,
+                ))
+                .await?;
+            Self::add_value(builder, reply, &value)
+        };
+        run.await.map_err(|e| Self::error_reply(&e))
+    }
+
+    /// Split a library code into its name and its body. The code must start with a metadata
+    /// line: `#!lua name=<library name>`. The body keeps the (now empty) metadata line, so the
+    /// line numbers of the Lua errors match the code
+    fn parse_library_metadata(code: &[u8]) -> Result<(String, &[u8]), String> {
+        let line_end = code.iter().position(|c| *c == b'\n').unwrap_or(code.len());
+        let Some(metadata) = code[..line_end].strip_prefix(b"#!") else {
+            return Err("ERR Missing library metadata".to_string());
+        };
+
+        let metadata = String::from_utf8_lossy(metadata);
+        let mut tokens = metadata.split_whitespace();
+        let engine = tokens.next().unwrap_or_default();
+        if !engine.eq_ignore_ascii_case("lua") {
+            return Err(format!("ERR Engine '{}' not found", engine));
+        }
+
+        let mut name = None;
+        for token in tokens {
+            match token.strip_prefix("name=") {
+                Some(value) => name = Some(value.to_string()),
+                None => return Err(format!("ERR Invalid metadata value given: {}", token)),
+            }
+        }
+        let Some(name) = name else {
+            return Err("ERR Library name was not given".to_string());
+        };
+        if !Self::is_valid_name(&name) {
+            return Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_string());
+        }
+        Ok((name, &code[line_end..]))
+    }
+
+    /// Run a library body in `lua`. Return the functions registered by the library, along with
+    /// their callbacks
+    fn register_library(lua: &Lua, body: &[u8]) -> mlua::Result<Vec<(FunctionInfo, Function)>> {
+        let registered = Rc::new(RefCell::new(Vec::<(FunctionInfo, Function)>::new()));
+        let redis = Self::new_redis_table(lua)?;
+        let functions = registered.clone();
+        redis.set(
+            "register_function",
+            lua.create_function(move |_, args: MultiValue| {
+                let function = Self::to_registered_function(&args)?;
+                let mut functions = functions.borrow_mut();
+                if functions
+                    .iter()
+                    .any(|(info, _)| info.name == function.0.name)
+                {
+                    return Err(mlua::Error::external(
+                        "ERR Function already exists in the library",
+                    ));
+                }
+                functions.push(function);
+                Ok(())
+            })?,
+        )?;
+        lua.globals().set("redis", redis)?;
+
+        lua.load(body)
+            .set_name(SCRIPT_NAME)
+            .set_mode(ChunkMode::Text)
+            .exec()?;
+        let functions = registered.take();
+        Ok(functions)
+    }
+
+    /// Parse the arguments of `redis.register_function`, either `(name, callback)` or a single
+    /// table with the `function_name`, `callback`, `flags` and `description` fields
+    fn to_registered_function(args: &MultiValue) -> mlua::Result<(FunctionInfo, Function)> {
+        let wrong_arguments =
+            || mlua::Error::external("ERR wrong arguments given to redis.register_function");
+        let (name, callback, description, flags) = match (args.front(), args.get(1)) {
+            (Some(Value::String(name)), Some(Value::Function(callback))) if args.len() == 2 => (
+                name.to_string_lossy(),
+                callback.clone(),
+                None,
+                Vec::default(),
+            ),
+            (Some(Value::Table(table)), None) => {
+                let Value::String(name) = table.raw_get::<Value>("function_name")? else {
+                    return Err(wrong_arguments());
+                };
+                let Value::Function(callback) = table.raw_get::<Value>("callback")? else {
+                    return Err(wrong_arguments());
+                };
+                let description = match table.raw_get::<Value>("description")? {
+                    Value::Nil => None,
+                    Value::String(description) => Some(description.to_string_lossy()),
+                    _ => return Err(wrong_arguments()),
+                };
+                let flags = match table.raw_get::<Value>("flags")? {
+                    Value::Nil => Vec::default(),
+                    Value::Table(flags) => flags
+                        .sequence_values::<mlua::String>()
+                        .map(|flag| flag.map(|flag| flag.to_string_lossy()))
+                        .collect::<mlua::Result<Vec<_>>>()?,
+                    _ => return Err(wrong_arguments()),
+                };
+                (name.to_string_lossy(), callback, description, flags)
+            }
+            _ => return Err(wrong_arguments()),
+        };
+
+        if !Self::is_valid_name(&name) {
+            return Err(mlua::Error::external("ERR Function names can only contain letters, numbers, or underscores(_) and must be at least one character long"));
+        }
+        if let Some(flag) = flags
+            .iter()
+            .find(|flag| !FUNCTION_FLAGS.contains(&flag.as_str()))
+        {
+            return Err(mlua::Error::external(format!(
+                "ERR Unknown flag given: {}",
+                flag
+            )));
+        }
+
+        Ok((
+            FunctionInfo {
+                name,
+                description,
+                flags,
+            },
+            callback,
+        ))
+    }
+
+    /// Library and function names are made of letters, numbers and underscores
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// The `redis` table, without the functions that call the server
+    fn new_redis_table(lua: &Lua) -> mlua::Result<Table> {
+        let redis = lua.create_table()?;
+        redis.set(
+            "error_reply",
+            lua.create_function(|lua, msg: mlua::String| lua.create_table_from([("err", msg)]))?,
+        )?;
+        redis.set(
+            "status_reply",
+            lua.create_function(|lua, msg: mlua::String| lua.create_table_from([("ok", msg)]))?,
+        )?;
+        Ok(redis)
+    }
+
+    /// Add `redis.call` and `redis.pcall` to `redis`. The commands run on behalf of
+    /// `client_state`
+    fn add_call_functions(
+        lua: &Lua,
+        redis: &Table,
+        client_state: Rc<ClientState>,
+    ) -> mlua::Result<()> {
+        let state = client_state.clone();
+        redis.set(
+            "call",
+            lua.create_async_function(move |lua, args: MultiValue| {
+                Self::call(lua, state.clone(), args, true)
+            })?,
+        )?;
+        let state = client_state;
+        redis.set(
+            "pcall",
+            lua.create_async_function(move |lua, args: MultiValue| {
+                Self::call(lua, state.clone(), args, false)
+            })?,
+        )?;
+        Ok(())
+    }
+
+    /// An interpreter with the libraries a script may use: no access to the file system or to
+    /// the process
+    fn new_lua() -> mlua::Result<Lua> {
+        Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8,
+            LuaOptions::default(),
+        )
+    }
+
+    /// `redis.call` (`raise` is `true`) and `redis.pcall`. The error replies of `redis.call` are
+    /// raised as Lua errors, `redis.pcall` returns them as a `{err = ...}` table
+    async fn call(
+        lua: Lua,
+        client_state: Rc<ClientState>,
+        args: MultiValue,
+        raise: bool,
+    ) -> mlua::Result<Value> {
+        let reply = match Self::to_command(&args) {
+            Ok(command) => {
+                let reply = Client::handle_command_captured(client_state, command).await;
+                match PeerReply::parse(&reply) {
+                    Ok(Some((reply, _))) => reply,
+                    _ => PeerReply::Error(BytesMut::from("ERR invalid reply from command")),
+                }
+            }
+            Err(msg) => PeerReply::Error(BytesMut::from(msg)),
+        };
+
+        match reply {
+            PeerReply::Error(msg) if raise => {
+                Err(mlua::Error::external(BytesMutUtils::to_string(&msg)))
+            }
+            reply => Self::to_lua(&lua, reply),
+        }
+    }
+
+    /// Build the command called by the script
+    fn to_command(args: &MultiValue) -> Result<Rc<RedisCommand>, &'static str> {
+        let mut command_args = Vec::<BytesMut>::with_capacity(args.len());
+        for arg in args.iter() {
+            let arg = match arg {
+                Value::String(s) => BytesMut::from(s.as_bytes().as_ref()),
+                Value::Integer(n) => BytesMut::from(n.to_string().as_str()),
+                Value::Number(n) => BytesMut::from(StringUtils::format_redis_float(*n).as_str()),
+                _ => return Err("ERR Lua redis lib command arguments must be strings or integers"),
+            };
+            command_args.push(arg);
+        }
+
+        let Ok(command) = RedisCommand::new(command_args) else {
+            return Err("ERR Please specify at least one argument for this redis lib call");
+        };
+
+        let metadata = command.metadata();
+        // `PUBLISH` never waits, it is the only pub-sub command allowed
+        let allowed = metadata.allowed_from_script()
+            && (!metadata.in_category(&RedisCommandFlags::PubSub)
+                || matches!(metadata.name(), RedisCommandName::Publish));
+        if !allowed {
+            return Err(ErrorStrings::COMMAND_NOT_ALLOWED_FROM_SCRIPT);
+        }
+        Ok(Rc::new(command))
+    }
+
+    fn to_lua_strings(lua: &Lua, values: &[BytesMut]) -> mlua::Result<mlua::Table> {
+        let strings = values
+            .iter()
+            .map(|value| lua.create_string(value))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        lua.create_sequence_from(strings)
+    }
+
+    /// Convert a command reply into a Lua value: integers become numbers, bulk strings become
+    /// strings, arrays become tables, nulls become `false`. Status and error replies become
+    /// `{ok = ...}` and `{err = ...}` tables
+    fn to_lua(lua: &Lua, reply: PeerReply) -> mlua::Result<Value> {
+        let value = match reply {
+            PeerReply::Status(msg) => {
+                Value::Table(lua.create_table_from([("ok", lua.create_string(&msg)?)])?)
+            }
+            PeerReply::Error(msg) => {
+                Value::Table(lua.create_table_from([("err", lua.create_string(&msg)?)])?)
+            }
+            PeerReply::Integer(n) => Value::Integer(n),
+            PeerReply::Bulk(Some(content)) => Value::String(lua.create_string(&content)?),
+            PeerReply::Bulk(None) => Value::Boolean(false),
+            PeerReply::Array(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| Self::to_lua(lua, item))
+                    .collect::<mlua::Result<Vec<_>>>()?;
+                Value::Table(lua.create_sequence_from(items)?)
+            }
+        };
+        Ok(value)
+    }
+
+    /// Append the RESP representation of a Lua value to `buffer`. Numbers are truncated to
+    /// integers, `true` is `1`, `false` and `nil` are null. A table is an array made of its
+    /// elements up to the first `nil`, unless it has an `err` or `ok` field
+    fn add_value(
+        builder: &RespBuilderV2,
+        buffer: &mut BytesMut,
+        value: &Value,
+    ) -> mlua::Result<()> {
+        match value {
+            Value::Boolean(true) => builder.add_number::<i64>(buffer, 1, false),
+            Value::Integer(n) => builder.add_number::<i64>(buffer, *n, false),
+            Value::Number(n) => builder.add_number::<i64>(buffer, *n as i64, false),
+            Value::String(s) => builder.add_bulk_string_u8_arr(buffer, &s.as_bytes()),
+            Value::Table(table) => {
+                if let Value::String(msg) = table.raw_get::<Value>("err")? {
+                    builder.add_resp_string(buffer, b"-");
+                    builder.add_resp_string(buffer, &msg.as_bytes());
+                    builder.add_resp_string(buffer, b"\r\n");
+                } else if let Value::String(msg) = table.raw_get::<Value>("ok")? {
+                    builder.add_resp_string(buffer, b"+");
+                    builder.add_resp_string(buffer, &msg.as_bytes());
+                    builder.add_resp_string(buffer, b"\r\n");
+                } else {
+                    let items = table
+                        .sequence_values::<Value>()
+                        .collect::<mlua::Result<Vec<_>>>()?;
+                    builder.add_array_len(buffer, items.len());
+                    for item in &items {
+                        Self::add_value(builder, buffer, item)?;
+                    }
+                }
+            }
+            _ => builder.add_null_string(buffer),
+        }
+        Ok(())
+    }
+
+    /// The error reply for a failed script. Errors raised by `redis.call` are replied as is. The
+    /// stack traceback is dropped, an error reply must fit on a single line
+    fn error_reply(error: &mlua::Error) -> String {
+        let reply = match error {
+            mlua::Error::CallbackError { cause, .. } => return Self::error_reply(cause),
+            mlua::Error::ExternalError(e) => e.to_string(),
+            mlua::Error::SyntaxError { message, .. } => {
+                format!("ERR Error compiling script: {}", message)
+            }
+            mlua::Error::RuntimeError(message) => {
+                format!("ERR Error running script: {}", message)
+            }
+            other => format!("ERR Error running script: {}", other),
+        };
+        match reply.split_once('\n') {
+            Some((first_line, _)) => first_line.to_string(),
+            None => reply,
+        }
+    }
+}
@@ -1,35 +1,105 @@
+use crate::client::ClientInfo;
 use crate::worker::{BroadcastMessageType, WorkerMessage, WorkerSender};
 use crate::{
     replication::{
-        ReplicationConfig, ReplicationWorkerMessage, Replicator, ReplicatorContext, ServerRole,
+        ReplicaAcks, ReplicationConfig, ReplicationWorkerMessage, Replicator, ReplicatorContext,
+        ServerRole,
     },
-    Client, SableError, ServerOptions, StorageAdapter, Telemetry, WorkerContext, WorkerManager,
+    scripting::{FunctionRegistry, ScriptCache},
+    Acl, ActiveExpire, Client, KeyCounter, MemoryTracker, MetricsExporter, Reclaimer,
+    RespBuilderV2, SableError, ServerOptions, SlowLog, StorageAdapter, StringUtils, Telemetry,
+    TimeUtils, WalSyncer, WatchedKeys, WorkerContext, WorkerManager,
 };
 use bytes::BytesMut;
 use crossbeam::queue::SegQueue;
 #[allow(unused_imports)]
 use dashmap::{DashMap, DashSet};
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Mutex,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex, RwLock, RwLockReadGuard,
 };
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::Receiver as TokioReceiver;
 use tokio::sync::mpsc::Sender as TokioSender;
+use tokio::sync::Notify;
 
-// contains a table that maps a clientId -> Sender channel
-type ChannelQueue = Arc<SegQueue<TokioSender<u8>>>;
+// The channels of the clients blocked by a key, in the order they were blocked
+type ChannelQueue = VecDeque<TokioSender<u8>>;
 
 // Contains a table that maps between a `Key` and a list of channels (FIFO)
 type BlockedClientTable = DashMap<BytesMut, ChannelQueue>;
 
+// Contains a table that maps between a pub-sub channel and its subscribers
+type PubSubChannelTable = DashMap<BytesMut, Vec<PubSubSubscriber>>;
+
+/// A client subscribed to a pub-sub channel or pattern
+#[derive(Clone)]
+struct PubSubSubscriber {
+    client_id: u128,
+    /// The channel used to push messages to the client
+    tx: TokioSender<BytesMut>,
+    /// Notified when the client does not keep up with the published messages and must be
+    /// disconnected
+    overflow: Arc<Notify>,
+}
+
 pub struct ServerState {
     blocked_clients: BlockedClientTable,
+    pubsub_channels: PubSubChannelTable,
+    /// Same as `pubsub_channels`, but keyed by glob-style patterns
+    pubsub_patterns: PubSubChannelTable,
     telemetry: Arc<Mutex<Telemetry>>,
-    opts: ServerOptions,
+    /// The server options. Some of them can be changed at runtime (`CONFIG SET`)
+    opts: RwLock<ServerOptions>,
+    /// The number of times the options were changed at runtime. Lets the hot paths keep a copy
+    /// of the options they use and read them again only after a change
+    opts_generation: AtomicU64,
     role_primary: AtomicBool,
+    /// Set while `FAILOVER` runs: write commands are held until it completes
+    failover_in_progress: AtomicBool,
+    /// Set by `CLIENT PAUSE`: the time (ms since the Unix epoch) at which the pause ends. `0`
+    /// when clients are not paused
+    pause_until_ms: AtomicU64,
+    /// `CLIENT PAUSE ALL`: all the commands are paused, not only the write commands
+    pause_all: AtomicBool,
+    /// Set while a `SAVE` or `BGSAVE` backup runs
+    save_in_progress: AtomicBool,
+    /// The time of the last successful backup, seconds since the Unix epoch (`LASTSAVE`). Set to
+    /// the startup time until the first backup
+    last_save: AtomicU64,
+    /// Set while a `DEBUG COMPACT` compaction runs
+    compaction_in_progress: AtomicBool,
+    /// The time the last `DEBUG COMPACT` completed, seconds since the Unix epoch. `0` if none
+    /// completed since startup
+    last_compaction: AtomicU64,
     replicator_context: Option<Arc<ReplicatorContext>>,
+    /// The acknowledgements of the replicas, while this server is a primary (`WAIT`)
+    replica_acks: Arc<ReplicaAcks>,
     worker_tx_channels: DashMap<std::thread::ThreadId, WorkerSender>,
+    watched_keys: Arc<WatchedKeys>,
+    /// The number of keys per database (`DBSIZE`)
+    key_counter: Arc<KeyCounter>,
+    /// The dataset size and the key access statistics (`maxmemory`)
+    memory_tracker: Arc<MemoryTracker>,
+    /// Items prefixes of deleted values, waiting for the `Reclaimer`
+    reclaim_queue: Arc<SegQueue<BytesMut>>,
+    /// Commands that exceeded `slowlog_log_slower_than` (`SLOWLOG`)
+    slowlog: SlowLog,
+    /// The users and their permissions (`AUTH`, `ACL`)
+    acl: Acl,
+    /// The scripts loaded by `EVAL` and `SCRIPT LOAD`
+    script_cache: ScriptCache,
+    /// The function libraries loaded by `FUNCTION LOAD`
+    function_registry: FunctionRegistry,
+    /// The connected clients, from all the workers (`CLIENT LIST`)
+    clients: DashMap<u128, ClientInfo>,
+    /// The number of times each database was flushed (`FLUSHDB`). `SCAN` cursors carry the
+    /// generation of their database, so a cursor created before a flush is rejected
+    db_generations: DashMap<u16, u64>,
+    /// The number of times all the databases were flushed (`FLUSHALL`)
+    flushall_generation: AtomicU64,
 }
 
 #[allow(dead_code)]
@@ -49,10 +119,32 @@ impl ServerState {
         ServerState {
             telemetry: Arc::new(Mutex::new(Telemetry::default())),
             blocked_clients: BlockedClientTable::new(),
-            opts: ServerOptions::default(),
+            pubsub_channels: PubSubChannelTable::new(),
+            pubsub_patterns: PubSubChannelTable::new(),
+            opts: RwLock::new(ServerOptions::default()),
+            opts_generation: AtomicU64::new(0),
             role_primary: AtomicBool::new(true),
+            failover_in_progress: AtomicBool::new(false),
+            pause_until_ms: AtomicU64::new(0),
+            pause_all: AtomicBool::new(false),
+            save_in_progress: AtomicBool::new(false),
+            last_save: AtomicU64::new(TimeUtils::epoch_ms().unwrap_or_default() / 1000),
+            compaction_in_progress: AtomicBool::new(false),
+            last_compaction: AtomicU64::new(0),
             replicator_context: None,
+            replica_acks: Arc::new(ReplicaAcks::default()),
             worker_tx_channels: DashMap::<std::thread::ThreadId, WorkerSender>::new(),
+            watched_keys: Arc::new(WatchedKeys::default()),
+            key_counter: Arc::new(KeyCounter::default()),
+            memory_tracker: Arc::new(MemoryTracker::default()),
+            reclaim_queue: Arc::new(SegQueue::<BytesMut>::new()),
+            slowlog: SlowLog::default(),
+            acl: Acl::default(),
+            script_cache: ScriptCache::default(),
+            function_registry: FunctionRegistry::default(),
+            clients: DashMap::<u128, ClientInfo>::new(),
+            db_generations: DashMap::<u16, u64>::new(),
+            flushall_generation: AtomicU64::new(0),
         }
     }
 
@@ -71,8 +163,13 @@ impl ServerState {
     }
 
     pub fn set_server_options(mut self, opts: ServerOptions) -> Self {
-        self.opts = opts;
-        match self.opts.load_replication_config().role {
+        let role = opts.load_replication_config().role;
+        self.acl = Acl::new(
+            opts.general_settings.requirepass.clone(),
+            opts.general_settings.aclfile.clone(),
+        );
+        self.opts = RwLock::new(opts);
+        match role {
             ServerRole::Primary => self.set_primary(),
             ServerRole::Replica => self.set_replica(),
         }
@@ -84,6 +181,24 @@ impl ServerState {
         self
     }
 
+    /// Use `replica_acks`, the table updated by the replication server
+    pub fn set_replica_acks(mut self, replica_acks: Arc<ReplicaAcks>) -> Self {
+        self.replica_acks = replica_acks;
+        self
+    }
+
+    /// Share the reclaim queue of the storage, so the items of the expired values removed by
+    /// the readers are reclaimed along with the unlinked ones
+    pub fn set_reclaim_queue(mut self, reclaim_queue: Arc<SegQueue<BytesMut>>) -> Self {
+        self.reclaim_queue = reclaim_queue;
+        self
+    }
+
+    /// The replicas connected to this primary and the changes they acknowledged
+    pub fn replica_acks(&self) -> &ReplicaAcks {
+        &self.replica_acks
+    }
+
     /// Mark client as "terminated"
     pub async fn terminate_client(&self, client_id: u128) -> Result<(), SableError> {
         // first, try to local thread, if this fails, broadcast the message to other threads
@@ -94,12 +209,176 @@ impl ServerState {
         Ok(())
     }
 
+    /// The keys watched by clients (`WATCH`). The table must be registered as a write
+    /// observer on the storage used by the clients
+    pub fn watched_keys(&self) -> Arc<WatchedKeys> {
+        self.watched_keys.clone()
+    }
+
+    /// The number of keys per database (`DBSIZE`). Like `watched_keys`, the counter must be
+    /// registered as a write observer on the storage used by the clients
+    pub fn key_counter(&self) -> Arc<KeyCounter> {
+        self.key_counter.clone()
+    }
+
+    /// The dataset size and the key access statistics used to enforce `maxmemory`. Like
+    /// `watched_keys`, the tracker must be registered as a write observer on the storage used
+    /// by the clients
+    pub fn memory_tracker(&self) -> Arc<MemoryTracker> {
+        self.memory_tracker.clone()
+    }
+
+    /// Queue the items (hash fields, list items etc) found under `items_prefix` for deletion by
+    /// the `Reclaimer`. The value owning them must already be deleted
+    pub fn enqueue_reclaim(&self, items_prefix: BytesMut) {
+        self.reclaim_queue.push(items_prefix);
+    }
+
+    /// Pop the next items prefix to reclaim
+    pub fn pop_reclaim(&self) -> Option<BytesMut> {
+        self.reclaim_queue.pop()
+    }
+
+    /// Number of items prefixes waiting to be reclaimed
+    pub fn pending_reclaims(&self) -> usize {
+        self.reclaim_queue.len()
+    }
+
+    /// The generation of database `db_id`. It changes every time the database is flushed
+    pub fn db_generation(&self, db_id: u16) -> u64 {
+        let flushes = self
+            .db_generations
+            .get(&db_id)
+            .map(|flushes| *flushes)
+            .unwrap_or_default();
+        self.flushall_generation
+            .load(Ordering::Relaxed)
+            .wrapping_add(flushes)
+    }
+
+    /// Database `db_id` was flushed, or all of them when `db_id` is `None`
+    pub fn bump_db_generation(&self, db_id: Option<u16>) {
+        match db_id {
+            Some(db_id) => {
+                let mut flushes = self.db_generations.entry(db_id).or_default();
+                *flushes = flushes.wrapping_add(1);
+            }
+            None => {
+                self.flushall_generation.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Mark a `SAVE` or `BGSAVE` backup as started. Return `false` if a backup is already
+    /// running
+    pub fn start_save(&self) -> bool {
+        self.save_in_progress
+            .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    pub fn end_save(&self) {
+        self.save_in_progress.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_save_in_progress(&self) -> bool {
+        self.save_in_progress.load(Ordering::Relaxed)
+    }
+
+    /// The time of the last successful backup, seconds since the Unix epoch
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn set_last_save(&self, timestamp: u64) {
+        self.last_save.store(timestamp, Ordering::Relaxed);
+    }
+
+    /// Mark a `DEBUG COMPACT` compaction as started. Return `false` if a compaction is already
+    /// running
+    pub fn start_compaction(&self) -> bool {
+        self.compaction_in_progress
+            .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Mark the running compaction as completed at `timestamp` (seconds since the Unix epoch)
+    pub fn end_compaction(&self, timestamp: u64) {
+        self.last_compaction.store(timestamp, Ordering::Relaxed);
+        self.compaction_in_progress.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_compaction_in_progress(&self) -> bool {
+        self.compaction_in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_compaction(&self) -> u64 {
+        self.last_compaction.load(Ordering::Relaxed)
+    }
+
+    pub fn slowlog(&self) -> &SlowLog {
+        &self.slowlog
+    }
+
+    pub fn acl(&self) -> &Acl {
+        &self.acl
+    }
+
+    pub fn script_cache(&self) -> &ScriptCache {
+        &self.script_cache
+    }
+
+    pub fn function_registry(&self) -> &FunctionRegistry {
+        &self.function_registry
+    }
+
+    /// Add a newly connected client to the clients registry
+    pub fn register_client(&self, client_id: u128, info: ClientInfo) {
+        self.clients.insert(client_id, info);
+    }
+
+    /// Remove a disconnected client from the clients registry
+    pub fn unregister_client(&self, client_id: u128) {
+        self.clients.remove(&client_id);
+    }
+
+    /// Update the registry entry of `client_id`, if it exists
+    pub fn update_client(&self, client_id: u128, f: impl FnOnce(&mut ClientInfo)) {
+        if let Some(mut info) = self.clients.get_mut(&client_id) {
+            f(info.value_mut());
+        }
+    }
+
+    /// The connected clients, sorted by their ID
+    pub fn clients(&self) -> Vec<(u128, ClientInfo)> {
+        let mut clients: Vec<(u128, ClientInfo)> = self
+            .clients
+            .iter()
+            .map(|item| (*item.key(), item.value().clone()))
+            .collect();
+        clients.sort_by_key(|(client_id, _)| *client_id);
+        clients
+    }
+
     pub fn shared_telemetry(&self) -> Arc<Mutex<Telemetry>> {
         self.telemetry.clone()
     }
 
-    pub fn options(&self) -> &ServerOptions {
-        &self.opts
+    pub fn options(&self) -> RwLockReadGuard<'_, ServerOptions> {
+        self.opts.read().expect("poisoned mutex")
+    }
+
+    /// Update the server options in place (`CONFIG SET`). `f` runs while holding the options
+    /// lock
+    pub fn update_options<R>(&self, f: impl FnOnce(&mut ServerOptions) -> R) -> R {
+        let mut opts = self.opts.write().expect("poisoned mutex");
+        self.opts_generation.fetch_add(1, Ordering::Relaxed);
+        f(&mut opts)
+    }
+
+    /// The number of times the options were changed by `update_options`
+    pub fn options_generation(&self) -> u64 {
+        self.opts_generation.load(Ordering::Relaxed)
     }
 
     /// Is the server role is primary?
@@ -122,7 +401,57 @@ impl ServerState {
         self.role_primary.store(true, Ordering::Relaxed);
     }
 
-    /// If we have blocked clients waiting for `key` -> wake them up now
+    /// Mark a failover as started. Return `false` if a failover is already in progress
+    pub fn start_failover(&self) -> bool {
+        self.failover_in_progress
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    pub fn end_failover(&self) {
+        self.failover_in_progress.store(false, Ordering::Release);
+    }
+
+    /// Is a failover in progress? (writes are held)
+    pub fn failover_in_progress(&self) -> bool {
+        self.failover_in_progress.load(Ordering::Acquire)
+    }
+
+    /// Pause the clients until `until_ms` (`CLIENT PAUSE`). If the clients are already paused,
+    /// the pause ends at the latest of the two times, and `ALL` wins over `WRITE`
+    pub fn pause_clients(&self, until_ms: u64, all: bool) {
+        let now_ms = TimeUtils::epoch_ms().unwrap_or_default();
+        let previous = self.pause_until_ms.fetch_max(until_ms, Ordering::AcqRel);
+        if previous <= now_ms {
+            // the previous pause (if any) already expired
+            self.pause_all.store(all, Ordering::Release);
+        } else if all {
+            self.pause_all.store(true, Ordering::Release);
+        }
+    }
+
+    /// End the pause early (`CLIENT UNPAUSE`)
+    pub fn unpause_clients(&self) {
+        self.pause_until_ms.store(0, Ordering::Release);
+        self.pause_all.store(false, Ordering::Release);
+    }
+
+    /// Return how long, in milliseconds, a command must wait before it runs because of
+    /// `CLIENT PAUSE`. `None` if it can run now
+    pub fn pause_remaining_ms(&self, is_write: bool) -> Option<u64> {
+        let until_ms = self.pause_until_ms.load(Ordering::Acquire);
+        if until_ms == 0 || !(is_write || self.pause_all.load(Ordering::Acquire)) {
+            return None;
+        }
+        let now_ms = TimeUtils::epoch_ms().ok()?;
+        until_ms
+            .checked_sub(now_ms)
+            .filter(|remaining| *remaining > 0)
+    }
+
+    /// If we have blocked clients waiting for `key` -> wake them up now. Exactly one client is
+    /// woken per available element: clients that already timed out, or that already have a
+    /// pending wakeup (e.g. a client blocked on multiple keys), are skipped
     pub async fn wakeup_clients(&self, key: &BytesMut, mut num_clients: usize) {
         tracing::debug!("waking up {} client(s) for key: {:?}", num_clients, key);
         if self.blocked_clients.is_empty() {
@@ -130,54 +459,187 @@ impl ServerState {
             return;
         }
 
-        // based on num_clients, wakeup all the clients that are blocked by
-        // this key
-        let remove_key = if let Some(channel_queue) = self.blocked_clients.get(key) {
+        // based on num_clients, wakeup the clients that are blocked by this key (FIFO)
+        if let Some(mut channel_queue) = self.blocked_clients.get_mut(key) {
             while num_clients > 0 {
-                if let Some(client_channel) = channel_queue.pop() {
-                    if let Err(e) = client_channel.send(0u8).await {
-                        tracing::debug!(
-                            "error while sending wakeup bit. client already timed out. {:?}",
-                            e
-                        );
-                    } else {
-                        num_clients = num_clients.saturating_sub(1);
-                    }
-                } else {
+                let Some(client_channel) = channel_queue.pop_front() else {
                     // No more clients in the queue
                     break;
+                };
+                match client_channel.try_send(0u8) {
+                    Ok(()) => num_clients = num_clients.saturating_sub(1),
+                    Err(TrySendError::Closed(_)) => {
+                        tracing::debug!("client already timed out or was served by another key");
+                    }
+                    Err(TrySendError::Full(_)) => {
+                        tracing::debug!("client already has a pending wakeup");
+                    }
                 }
             }
-            channel_queue.is_empty()
-        } else {
-            false
-        };
-
-        if remove_key {
-            // we no longer have clients blocked by this key
-            let _ = self.blocked_clients.remove(key);
         }
+
+        // we no longer have clients blocked by this key. Checked under the shard lock, so a
+        // client that was blocked in the meantime is not lost
+        self.blocked_clients
+            .remove_if(key, |_, channel_queue| channel_queue.is_empty());
     }
 
-    /// Block the current client for the provided keys
+    /// Block the current client for the provided keys. The client is woken at most once, by
+    /// the first key that gets an element
     pub async fn block_client(&self, keys: &[BytesMut]) -> TokioReceiver<u8> {
         tracing::debug!("blocking client for keys {:?}", keys);
-        let (tx, rx) = tokio::sync::mpsc::channel(keys.len());
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
 
         for key in keys.iter() {
-            if let Some(channel_queue) = self.blocked_clients.get(key) {
-                channel_queue.push(tx.clone());
-            } else {
-                // first time
-                let channel_queue = Arc::new(SegQueue::<TokioSender<u8>>::new());
-                channel_queue.push(tx.clone());
-                self.blocked_clients
-                    .insert(key.clone(), channel_queue.clone());
-            };
+            let mut channel_queue = self.blocked_clients.entry(key.clone()).or_default();
+            // drop the entries of clients that are no longer blocked (timed out, or woken by
+            // another key)
+            channel_queue.retain(|client_channel| !client_channel.is_closed());
+            channel_queue.push_back(tx.clone());
         }
         rx
     }
 
+    /// Subscribe client `client_id` to `channel`. Messages published to `channel` are sent
+    /// over `tx`. `overflow` is notified if `tx` is full when a message is published
+    pub fn subscribe(
+        &self,
+        channel: &BytesMut,
+        client_id: u128,
+        tx: TokioSender<BytesMut>,
+        overflow: Arc<Notify>,
+    ) {
+        self.pubsub_channels
+            .entry(channel.clone())
+            .or_default()
+            .push(PubSubSubscriber {
+                client_id,
+                tx,
+                overflow,
+            });
+    }
+
+    /// Remove client `client_id` from the subscribers of `channel`
+    pub fn unsubscribe(&self, channel: &BytesMut, client_id: u128) {
+        self.pubsub_channels
+            .remove_if_mut(channel, |_, subscribers| {
+                subscribers.retain(|subscriber| subscriber.client_id != client_id);
+                subscribers.is_empty()
+            });
+    }
+
+    /// Subscribe client `client_id` to all the channels matching `pattern`. Messages are sent
+    /// over `tx`. `overflow` is notified if `tx` is full when a message is published
+    pub fn psubscribe(
+        &self,
+        pattern: &BytesMut,
+        client_id: u128,
+        tx: TokioSender<BytesMut>,
+        overflow: Arc<Notify>,
+    ) {
+        self.pubsub_patterns
+            .entry(pattern.clone())
+            .or_default()
+            .push(PubSubSubscriber {
+                client_id,
+                tx,
+                overflow,
+            });
+    }
+
+    /// Remove client `client_id` from the subscribers of `pattern`
+    pub fn punsubscribe(&self, pattern: &BytesMut, client_id: u128) {
+        self.pubsub_patterns
+            .remove_if_mut(pattern, |_, subscribers| {
+                subscribers.retain(|subscriber| subscriber.client_id != client_id);
+                subscribers.is_empty()
+            });
+    }
+
+    /// Send `message` to all the subscribers of `channel` and to all the subscribers of the
+    /// patterns matching `channel`. Subscribers may live on any worker thread. Return the
+    /// number of clients that received the message.
+    ///
+    /// Never waits: a subscriber whose queue of pending messages is full is unsubscribed from
+    /// everything and disconnected, like Redis does when a client exceeds its pub-sub output
+    /// buffer limit
+    pub fn publish(&self, channel: &BytesMut, message: &BytesMut) -> usize {
+        let builder = RespBuilderV2::default();
+
+        // build the frames and clone the subscribers so we do not hold the table locks while
+        // sending
+        let mut deliveries = Vec::<(PubSubSubscriber, BytesMut)>::new();
+        if let Some(subscribers) = self.pubsub_channels.get(channel) {
+            let mut frame = BytesMut::with_capacity(32 + channel.len() + message.len());
+            builder.add_array_len(&mut frame, 3);
+            builder.add_bulk_string_u8_arr(&mut frame, b"message");
+            builder.add_bulk_string(&mut frame, channel);
+            builder.add_bulk_string(&mut frame, message);
+            for subscriber in subscribers.iter() {
+                deliveries.push((subscriber.clone(), frame.clone()));
+            }
+        }
+
+        for entry in self.pubsub_patterns.iter() {
+            let pattern = entry.key();
+            if !StringUtils::glob_match(pattern, channel, false) {
+                continue;
+            }
+            let mut frame =
+                BytesMut::with_capacity(48 + pattern.len() + channel.len() + message.len());
+            builder.add_array_len(&mut frame, 4);
+            builder.add_bulk_string_u8_arr(&mut frame, b"pmessage");
+            builder.add_bulk_string(&mut frame, pattern);
+            builder.add_bulk_string(&mut frame, channel);
+            builder.add_bulk_string(&mut frame, message);
+            for subscriber in entry.value().iter() {
+                deliveries.push((subscriber.clone(), frame.clone()));
+            }
+        }
+
+        let mut received = 0usize;
+        for (subscriber, frame) in deliveries {
+            match subscriber.tx.try_send(frame) {
+                Ok(()) => {
+                    received = received.saturating_add(1);
+                }
+                Err(TrySendError::Closed(_)) => {
+                    tracing::debug!("failed to deliver message. subscriber is gone");
+                }
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!(
+                        "client {} does not keep up with the published messages. disconnecting it",
+                        subscriber.client_id
+                    );
+                    self.remove_subscriber(subscriber.client_id);
+                    subscriber.overflow.notify_one();
+                }
+            }
+        }
+        received
+    }
+
+    /// Remove client `client_id` from the subscribers of all the channels and patterns
+    fn remove_subscriber(&self, client_id: u128) {
+        for table in [&self.pubsub_channels, &self.pubsub_patterns] {
+            table.retain(|_, subscribers| {
+                subscribers.retain(|subscriber| subscriber.client_id != client_id);
+                !subscribers.is_empty()
+            });
+        }
+    }
+
+    /// Publish a keyspace event for `user_key`: `event` is sent to `__keyspace@<db>__:<key>`
+    /// and the key is sent to `__keyevent@<db>__:<event>`
+    pub fn notify_keyspace_event(&self, db_id: u16, event: &str, user_key: &BytesMut) {
+        let mut keyspace_channel = BytesMut::from(format!("__keyspace@{}__:", db_id).as_str());
+        keyspace_channel.extend_from_slice(user_key);
+        self.publish(&keyspace_channel, &BytesMut::from(event));
+
+        let keyevent_channel = BytesMut::from(format!("__keyevent@{}__:{}", db_id, event).as_str());
+        self.publish(&keyevent_channel, user_key);
+    }
+
     // Connect to primary instance
     pub async fn connect_to_primary(&self, address: String, port: u16) -> Result<(), SableError> {
         if let Some(repliction_context) = &self.replicator_context {
@@ -209,7 +671,9 @@ impl ServerState {
                 .general_settings
                 .replication_listen_ip
                 .to_string(),
-            port: self.options().general_settings.port as u16 + 1000,
+            port: ServerOptions::default_replication_port(
+                self.options().general_settings.port as u16,
+            ),
         };
 
         ReplicationConfig::write_file(
@@ -232,12 +696,33 @@ impl Server {
         store: StorageAdapter,
         workers_count: usize,
     ) -> Result<Self, SableError> {
-        let replicator_context = Replicator::run(opts.clone(), store.clone())?;
+        crate::commands::init_commands_manager(&opts.rename_commands)?;
+        let replica_acks = Arc::new(ReplicaAcks::default());
+        let replicator_context =
+            Replicator::run(opts.clone(), store.clone(), replica_acks.clone())?;
         let state = Arc::new(
             ServerState::new()
                 .set_server_options(opts)
+                .set_replica_acks(replica_acks)
+                .set_reclaim_queue(store.reclaim_queue())
                 .set_replication_context(replicator_context),
         );
+        state.acl().load()?;
+        state.function_registry().load(&store)?;
+        store.add_write_observer(state.watched_keys());
+        store.add_write_observer(state.key_counter());
+        store.add_write_observer(state.memory_tracker());
+        ActiveExpire::run(store.clone(), state.clone())?;
+        Reclaimer::run(store.clone(), state.clone())?;
+        WalSyncer::run(store.clone())?;
+        if let Some(metrics_port) = state.options().general_settings.metrics_port {
+            let address = format!(
+                "{}:{}",
+                state.options().general_settings.listen_ip,
+                metrics_port
+            );
+            MetricsExporter::run(address, state.clone())?;
+        }
 
         let worker_manager = WorkerManager::new(workers_count, store.clone(), state.clone())?;
         Ok(Server {
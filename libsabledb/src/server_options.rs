@@ -1,6 +1,6 @@
 use crate::{
-    ini_bool, ini_usize, parse_number, replication::ReplicationConfig, SableError,
-    StorageOpenParams,
+    ini_bool, ini_usize, parse_number, replication::ReplicationConfig, AppendFsync,
+    CompressionType, SableError, StorageOpenParams,
 };
 use ini::Ini;
 use std::path::PathBuf;
@@ -17,6 +17,9 @@ pub struct GeneralSettings {
     pub replication_listen_ip: String,
     /// Server workers count. set to 0 to let sabledb decide
     pub workers: usize,
+    /// The number of logical databases. `SELECT` accepts indices in `[0, databases)` (up to
+    /// 65536 databases)
+    pub databases: usize,
     /// Database log level
     pub log_level: tracing::Level,
     /// Path to the service certification path
@@ -27,6 +30,13 @@ pub struct GeneralSettings {
     pub key: Option<PathBuf>,
     /// Configuration files directory. Default: current process working directory
     pub config_dir: Option<PathBuf>,
+    /// When set, serve the telemetry in Prometheus text format on `http://<listen_ip>:<port>/metrics`
+    pub metrics_port: Option<usize>,
+    /// The SHA-256 digest of the password clients must provide with `AUTH` (`requirepass`).
+    /// `None` lets every client in. The password itself is not kept in memory
+    pub requirepass: Option<Vec<u8>>,
+    /// The users created with `ACL SETUSER` are saved to this file and loaded from it at startup
+    pub aclfile: Option<PathBuf>,
 }
 
 impl Default for GeneralSettings {
@@ -35,10 +45,14 @@ impl Default for GeneralSettings {
             port: 6379,
             listen_ip: "127.0.0.1".to_string(),
             workers: 0,
+            databases: 16,
             log_level: tracing::Level::INFO,
             cert: None,
             key: None,
             config_dir: None,
+            metrics_port: None,
+            requirepass: None,
+            aclfile: None,
             replication_listen_ip: "127.0.0.1".to_string(),
         }
     }
@@ -55,6 +69,10 @@ pub struct ReplicationLimits {
     /// However, when there are no changes to send to the replica, the replication task
     /// suspend itself for `check_for_updates_interval_ms` milliseconds.
     pub check_for_updates_interval_ms: usize,
+    /// The primary keeps the most recent changes, up to `backlog_size` bytes, in memory. A
+    /// replica that reconnects and whose last applied change is still in the backlog resumes
+    /// from there instead of performing a full sync. `0` disables the backlog
+    pub backlog_size: usize,
 }
 
 impl Default for ReplicationLimits {
@@ -63,6 +81,7 @@ impl Default for ReplicationLimits {
             single_update_buffer_size: 50 << 20, // 50mb
             num_updates_per_message: 10_000,
             check_for_updates_interval_ms: 5,
+            backlog_size: 64 << 20, // 64mb
         }
     }
 }
@@ -72,29 +91,250 @@ pub struct ClientLimits {
     /// Build up to `response_buffer_size` bytes in memory before flushing
     /// to the network
     pub client_response_buffer_size: usize,
+    /// The longest bulk string accepted in a request. A longer one is a protocol error
+    pub proto_max_bulk_len: usize,
+    /// The largest number of arguments accepted in a request. More is a protocol error
+    pub proto_max_multibulk_len: usize,
 }
 
 impl Default for ClientLimits {
     fn default() -> Self {
         ClientLimits {
             client_response_buffer_size: 1 << 20, // 1mb
+            proto_max_bulk_len: 512 << 20,        // 512mb
+            proto_max_multibulk_len: 1 << 20,
         }
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct ExpirationSettings {
+    /// Run the active expiration scan every `active_expire_interval_ms` milliseconds.
+    /// Set to `0` to disable active expiration (expired keys are then only removed when accessed)
+    pub active_expire_interval_ms: usize,
+    /// The number of keys sampled by a single active expiration scan
+    pub active_expire_sample_size: usize,
+}
+
+impl Default for ExpirationSettings {
+    fn default() -> Self {
+        ExpirationSettings {
+            active_expire_interval_ms: 100,
+            active_expire_sample_size: 200,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SlowLogSettings {
+    /// Commands that run longer than `slowlog_log_slower_than` microseconds are logged.
+    /// `0` logs every command, a negative value disables the slow log
+    pub slowlog_log_slower_than: i64,
+    /// The maximum number of entries kept in the slow log. The oldest entries are dropped
+    pub slowlog_max_len: usize,
+}
+
+impl Default for SlowLogSettings {
+    fn default() -> Self {
+        SlowLogSettings {
+            slowlog_log_slower_than: 10_000,
+            slowlog_max_len: 128,
+        }
+    }
+}
+
+/// Which keys are evicted once the dataset reaches `maxmemory` (`maxmemory-policy`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Nothing is evicted, writes fail with `-OOM` instead
+    #[default]
+    NoEviction,
+    /// Evict the least recently used keys
+    AllKeysLru,
+    /// Evict the least frequently used keys
+    AllKeysLfu,
+    /// Evict the least recently used keys among the keys with a TTL
+    VolatileLru,
+    /// Evict the keys with the nearest expiration time
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EvictionPolicy::NoEviction => "noeviction",
+            EvictionPolicy::AllKeysLru => "allkeys-lru",
+            EvictionPolicy::AllKeysLfu => "allkeys-lfu",
+            EvictionPolicy::VolatileLru => "volatile-lru",
+            EvictionPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    /// Parse a policy name (case insensitive)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "noeviction" => Some(EvictionPolicy::NoEviction),
+            "allkeys-lru" => Some(EvictionPolicy::AllKeysLru),
+            "allkeys-lfu" => Some(EvictionPolicy::AllKeysLfu),
+            "volatile-lru" => Some(EvictionPolicy::VolatileLru),
+            "volatile-ttl" => Some(EvictionPolicy::VolatileTtl),
+            _ => None,
+        }
+    }
+
+    /// Are only the keys with a TTL evicted?
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemorySettings {
+    /// The approximate size, in bytes, the dataset may reach. Once reached, keys are evicted
+    /// according to `maxmemory_policy` before a write is executed. `0` means no limit
+    pub maxmemory: usize,
+    pub maxmemory_policy: EvictionPolicy,
+    /// The number of keys sampled to pick the next key to evict
+    pub maxmemory_samples: usize,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        MemorySettings {
+            maxmemory: 0,
+            maxmemory_policy: EvictionPolicy::default(),
+            maxmemory_samples: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EncodingSettings {
+    /// Sorted sets with up to `zset_max_listpack_entries` members are reported with the
+    /// `listpack` encoding (`OBJECT ENCODING`), larger ones with `skiplist`
+    pub zset_max_listpack_entries: usize,
+    /// A sorted set with a member longer than `zset_max_listpack_value` bytes is reported with
+    /// the `skiplist` encoding, whatever its size
+    pub zset_max_listpack_value: usize,
+}
+
+impl Default for EncodingSettings {
+    fn default() -> Self {
+        EncodingSettings {
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BackupSettings {
+    /// Where `BGSAVE` stores the backups. Defaults to `<db_path>.backups`
+    pub backup_dir: Option<PathBuf>,
+    /// The number of backups kept. Once a new backup is created, the oldest ones are deleted
+    pub backup_keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            backup_dir: None,
+            backup_keep: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReplicationSettings {
+    /// Encrypt the replication traffic. The primary uses the server `cert` and `key`, a replica
+    /// verifies the certificate of its primary using `tls_ca`
+    pub tls: bool,
+    /// Path to the certificate(s) of the authority that signed the primary's certificate
+    pub tls_ca: Option<PathBuf>,
+    /// The name the primary's certificate is verified against. Defaults to the primary IP
+    pub tls_server_name: Option<String>,
+    /// When running as a replica, ask the primary to compress the replication updates (LZ4).
+    /// This trades CPU for bandwidth, e.g. when the primary is in another availability zone
+    pub compression: bool,
+}
+
+/// The outcome of `ServerOptions::set_config_parameter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetConfigResult {
+    Ok,
+    /// The parameter is known, but can not be changed while the server is running
+    Immutable,
+    /// The value can not be parsed or is out of range. Describes the values the parameter
+    /// accepts
+    InvalidValue(&'static str),
+    /// No such parameter
+    NotFound,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct ServerOptions {
     pub general_settings: GeneralSettings,
     pub open_params: StorageOpenParams,
     pub replication_limits: ReplicationLimits,
+    pub replication_settings: ReplicationSettings,
     pub client_limits: ClientLimits,
+    pub expiration_settings: ExpirationSettings,
+    pub slowlog_settings: SlowLogSettings,
+    pub memory_settings: MemorySettings,
+    pub encoding_settings: EncodingSettings,
+    pub backup_settings: BackupSettings,
+    /// Commands to rename, as `(name, new_name)` pairs in the order of the configuration
+    /// file (so a command can be renamed more than once). An empty new name disables
+    /// the command
+    pub rename_commands: Vec<(String, String)>,
+    /// The configuration file these options were loaded from (`None` when running with the
+    /// default options)
+    pub config_file: Option<PathBuf>,
 }
 
 impl ServerOptions {
+    /// The directory `BGSAVE` stores the backups in
+    pub fn backup_dir(&self) -> PathBuf {
+        match &self.backup_settings.backup_dir {
+            Some(backup_dir) => backup_dir.clone(),
+            None => {
+                let mut backup_dir = self.open_params.db_path.clone().into_os_string();
+                backup_dir.push(".backups");
+                PathBuf::from(backup_dir)
+            }
+        }
+    }
+
     pub fn use_tls(&self) -> bool {
         self.general_settings.key.is_some() && self.general_settings.cert.is_some()
     }
 
+    /// The port on which a server accepting clients on `port` accepts its replicas (unless
+    /// configured otherwise in `replication.json`)
+    pub fn default_replication_port(port: u16) -> u16 {
+        port.saturating_add(1000)
+    }
+
+    /// Require clients to authenticate with `password`. An empty password disables
+    /// authentication
+    pub fn set_requirepass(&mut self, password: &str) {
+        self.general_settings.requirepass = if password.is_empty() {
+            None
+        } else {
+            Some(Self::password_digest(password.as_bytes()))
+        };
+    }
+
+    /// The SHA-256 digest of `password`, the form in which passwords are kept
+    pub fn password_digest(password: &[u8]) -> Vec<u8> {
+        ring::digest::digest(&ring::digest::SHA256, password)
+            .as_ref()
+            .to_vec()
+    }
+
     /// Load the replication configuration from disk
     pub fn load_replication_config(&self) -> ReplicationConfig {
         if self.general_settings.config_dir.is_some() {
@@ -102,23 +342,384 @@ impl ServerOptions {
             ReplicationConfig::from_dir(
                 self.general_settings.config_dir.as_deref(),
                 self.general_settings.replication_listen_ip.clone(),
-                self.general_settings.port as u16 + 1000,
+                Self::default_replication_port(self.general_settings.port as u16),
             )
         } else {
             // just adjust the port
             ReplicationConfig::from_dir(
                 None,
                 self.general_settings.replication_listen_ip.clone(),
-                self.general_settings.port as u16 + 1000,
+                Self::default_replication_port(self.general_settings.port as u16),
             )
         }
     }
 
+    /// Return the configuration parameters exposed by `CONFIG GET`, using their Redis names.
+    /// Parameters that have no meaning in `SableDb` report the value that describes
+    /// `SableDb`'s behavior (e.g. there are no RDB snapshots and no append only file)
+    pub fn config_parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("bind", self.general_settings.listen_ip.clone()),
+            ("port", self.general_settings.port.to_string()),
+            ("io-threads", self.general_settings.workers.to_string()),
+            ("databases", self.general_settings.databases.to_string()),
+            (
+                "loglevel",
+                self.general_settings.log_level.to_string().to_lowercase(),
+            ),
+            ("maxmemory", self.memory_settings.maxmemory.to_string()),
+            (
+                "maxmemory-policy",
+                self.memory_settings.maxmemory_policy.as_str().to_string(),
+            ),
+            (
+                "maxmemory-samples",
+                self.memory_settings.maxmemory_samples.to_string(),
+            ),
+            (
+                "zset-max-listpack-entries",
+                self.encoding_settings.zset_max_listpack_entries.to_string(),
+            ),
+            (
+                "zset-max-listpack-value",
+                self.encoding_settings.zset_max_listpack_value.to_string(),
+            ),
+            ("save", String::default()),
+            ("appendonly", "no".to_string()),
+            (
+                "appendfsync",
+                self.open_params.rocksdb.appendfsync.as_str().to_string(),
+            ),
+            (
+                "client-response-buffer-size",
+                self.client_limits.client_response_buffer_size.to_string(),
+            ),
+            (
+                "proto-max-bulk-len",
+                self.client_limits.proto_max_bulk_len.to_string(),
+            ),
+            (
+                "proto-max-multibulk-len",
+                self.client_limits.proto_max_multibulk_len.to_string(),
+            ),
+            (
+                "active-expire-interval-ms",
+                self.expiration_settings
+                    .active_expire_interval_ms
+                    .to_string(),
+            ),
+            (
+                "active-expire-sample-size",
+                self.expiration_settings
+                    .active_expire_sample_size
+                    .to_string(),
+            ),
+            (
+                "slowlog-log-slower-than",
+                self.slowlog_settings.slowlog_log_slower_than.to_string(),
+            ),
+            (
+                "slowlog-max-len",
+                self.slowlog_settings.slowlog_max_len.to_string(),
+            ),
+            (
+                "rocksdb-write-buffer-size",
+                self.open_params.rocksdb.write_buffer_size.to_string(),
+            ),
+            (
+                "rocksdb-max-open-files",
+                self.open_params.rocksdb.max_open_files.to_string(),
+            ),
+            (
+                "rocksdb-compression-enabled",
+                if self.open_params.rocksdb.compression != CompressionType::None {
+                    "yes".to_string()
+                } else {
+                    "no".to_string()
+                },
+            ),
+            (
+                "rocksdb-compression",
+                self.open_params.rocksdb.compression.as_str().to_string(),
+            ),
+            (
+                "rocksdb-block-cache-size",
+                self.open_params.rocksdb.block_cache_size.to_string(),
+            ),
+            (
+                "rocksdb-bloom-filter-bits-per-key",
+                self.open_params
+                    .rocksdb
+                    .bloom_filter_bits_per_key
+                    .to_string(),
+            ),
+            (
+                "rocksdb-cache-index-and-filter-blocks",
+                if self.open_params.rocksdb.cache_index_and_filter_blocks {
+                    "yes".to_string()
+                } else {
+                    "no".to_string()
+                },
+            ),
+            (
+                "rocksdb-group-commit-interval-micros",
+                self.open_params
+                    .rocksdb
+                    .group_commit_interval_micros
+                    .to_string(),
+            ),
+        ]
+    }
+
+    /// Change the value of a configuration parameter (`CONFIG SET`). Only the parameters that
+    /// are read while the server is running can be changed, the others (e.g. the RocksDB
+    /// options, which are applied when the database is opened) are immutable
+    pub fn set_config_parameter(&mut self, name: &str, value: &str) -> SetConfigResult {
+        const POSITIVE_INTEGER: &str = "argument must be a positive integer";
+        const NON_NEGATIVE_INTEGER: &str = "argument must be a non-negative integer";
+        let parse_positive = |value: &str| value.parse::<usize>().ok().filter(|num| *num > 0);
+        match name {
+            "maxmemory" => {
+                let Ok(num) = Self::parse_memory(value) else {
+                    return SetConfigResult::InvalidValue("argument must be a memory value");
+                };
+                self.memory_settings.maxmemory = num;
+            }
+            "maxmemory-policy" => {
+                let Some(policy) = EvictionPolicy::parse(value) else {
+                    return SetConfigResult::InvalidValue(
+                        "argument(s) must be one of the following: noeviction, allkeys-lru, allkeys-lfu, volatile-lru, volatile-ttl",
+                    );
+                };
+                self.memory_settings.maxmemory_policy = policy;
+            }
+            "maxmemory-samples" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.memory_settings.maxmemory_samples = num;
+            }
+            "zset-max-listpack-entries" => {
+                let Ok(num) = value.parse::<usize>() else {
+                    return SetConfigResult::InvalidValue(NON_NEGATIVE_INTEGER);
+                };
+                self.encoding_settings.zset_max_listpack_entries = num;
+            }
+            "zset-max-listpack-value" => {
+                let Ok(num) = value.parse::<usize>() else {
+                    return SetConfigResult::InvalidValue(NON_NEGATIVE_INTEGER);
+                };
+                self.encoding_settings.zset_max_listpack_value = num;
+            }
+            "client-response-buffer-size" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.client_limits.client_response_buffer_size = num;
+            }
+            "proto-max-bulk-len" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.client_limits.proto_max_bulk_len = num;
+            }
+            "proto-max-multibulk-len" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.client_limits.proto_max_multibulk_len = num;
+            }
+            "active-expire-sample-size" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.expiration_settings.active_expire_sample_size = num;
+            }
+            "slowlog-log-slower-than" => {
+                let Ok(num) = value.parse::<i64>() else {
+                    return SetConfigResult::InvalidValue("argument must be an integer");
+                };
+                self.slowlog_settings.slowlog_log_slower_than = num;
+            }
+            "slowlog-max-len" => {
+                let Some(num) = parse_positive(value) else {
+                    return SetConfigResult::InvalidValue(POSITIVE_INTEGER);
+                };
+                self.slowlog_settings.slowlog_max_len = num;
+            }
+            _ => {
+                let parameters = self.config_parameters();
+                let Some((_, current_value)) =
+                    parameters.iter().find(|(parameter, _)| *parameter == name)
+                else {
+                    return SetConfigResult::NotFound;
+                };
+                // setting an immutable parameter to its current value is allowed (e.g. clients
+                // that disable snapshots with `CONFIG SET save ""`)
+                if !current_value.eq_ignore_ascii_case(value) {
+                    return SetConfigResult::Immutable;
+                }
+            }
+        }
+        SetConfigResult::Ok
+    }
+
+    /// Parse a memory value: a number of bytes, optionally followed by a unit (e.g. `100mb`
+    /// or `1gb`, see `parse_number!`)
+    fn parse_memory(value: &str) -> Result<usize, SableError> {
+        Ok(parse_number!(value, usize))
+    }
+
+    /// The parameters that can be changed at runtime, as they appear in the configuration
+    /// file: `(section, key, value)`
+    fn runtime_parameters(&self) -> Vec<(&'static str, &'static str, String)> {
+        vec![
+            (
+                "client_limits",
+                "client_response_buffer_size",
+                self.client_limits.client_response_buffer_size.to_string(),
+            ),
+            (
+                "client_limits",
+                "proto_max_bulk_len",
+                self.client_limits.proto_max_bulk_len.to_string(),
+            ),
+            (
+                "client_limits",
+                "proto_max_multibulk_len",
+                self.client_limits.proto_max_multibulk_len.to_string(),
+            ),
+            (
+                "expiration",
+                "active_expire_sample_size",
+                self.expiration_settings
+                    .active_expire_sample_size
+                    .to_string(),
+            ),
+            (
+                "slowlog",
+                "slowlog_log_slower_than",
+                self.slowlog_settings.slowlog_log_slower_than.to_string(),
+            ),
+            (
+                "slowlog",
+                "slowlog_max_len",
+                self.slowlog_settings.slowlog_max_len.to_string(),
+            ),
+            (
+                "memory",
+                "maxmemory",
+                self.memory_settings.maxmemory.to_string(),
+            ),
+            (
+                "memory",
+                "maxmemory_policy",
+                self.memory_settings.maxmemory_policy.as_str().to_string(),
+            ),
+            (
+                "memory",
+                "maxmemory_samples",
+                self.memory_settings.maxmemory_samples.to_string(),
+            ),
+            (
+                "encoding",
+                "zset_max_listpack_entries",
+                self.encoding_settings.zset_max_listpack_entries.to_string(),
+            ),
+            (
+                "encoding",
+                "zset_max_listpack_value",
+                self.encoding_settings.zset_max_listpack_value.to_string(),
+            ),
+        ]
+    }
+
+    /// Write the parameters that can be changed at runtime back to the configuration file
+    /// (`CONFIG REWRITE`). The file is updated line by line, so comments, unknown keys and the
+    /// layout of the file are kept. Missing keys are added at the end of their section
+    pub fn rewrite_config(&self) -> Result<(), SableError> {
+        let Some(config_file) = &self.config_file else {
+            return Err(SableError::OtherError(
+                "The server is running without a config file".to_string(),
+            ));
+        };
+
+        let content = std::fs::read_to_string(config_file)?;
+        let mut pending = self.runtime_parameters();
+        let mut lines = Vec::<String>::new();
+        let mut section = String::default();
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                Self::append_pending_keys(&section, &mut pending, &mut lines);
+                section = trimmed[1..trimmed.len() - 1].trim().to_string();
+                lines.push(line.to_string());
+                continue;
+            }
+
+            let is_comment = trimmed.starts_with('#') || trimmed.starts_with(';');
+            if let (false, Some((key, _))) = (is_comment, trimmed.split_once('=')) {
+                let key = key.trim();
+                if let Some(pos) = pending
+                    .iter()
+                    .position(|(sec, name, _)| *sec == section && *name == key)
+                {
+                    let (_, name, value) = pending.remove(pos);
+                    lines.push(format!("{} = {}", name, value));
+                    continue;
+                }
+            }
+            lines.push(line.to_string());
+        }
+        Self::append_pending_keys(&section, &mut pending, &mut lines);
+
+        // sections that do not exist in the file
+        while let Some((sec, _, _)) = pending.first() {
+            let sec = sec.to_string();
+            lines.push(String::default());
+            lines.push(format!("[{}]", sec));
+            Self::append_pending_keys(&sec, &mut pending, &mut lines);
+        }
+
+        // write to a temporary file first, so a failure never leaves a truncated config file
+        let mut tmp_file = config_file.clone().into_os_string();
+        tmp_file.push(".tmp");
+        let mut content = lines.join("\n");
+        content.push('\n');
+        std::fs::write(&tmp_file, content)?;
+        std::fs::rename(&tmp_file, config_file)?;
+        Ok(())
+    }
+
+    /// Add the pending keys of `section` after the last non empty line of the section
+    fn append_pending_keys(
+        section: &str,
+        pending: &mut Vec<(&'static str, &'static str, String)>,
+        lines: &mut Vec<String>,
+    ) {
+        let mut insert_at = lines
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map(|pos| pos + 1)
+            .unwrap_or_default();
+        pending.retain(|(sec, name, value)| {
+            if *sec != section {
+                return true;
+            }
+            lines.insert(insert_at, format!("{} = {}", name, value));
+            insert_at += 1;
+            false
+        });
+    }
+
     //pub fn load_replication_configuration(&mut self,
     /// Read values from INI configuration file and return `ServerOptions` structure
     pub fn from_config(config_file: String) -> Result<Self, SableError> {
-        let ini_file = Ini::load_from_file(config_file)?;
-        let mut options = ServerOptions::default();
+        let ini_file = Ini::load_from_file(&config_file)?;
+        let mut options = ServerOptions {
+            config_file: Some(PathBuf::from(config_file)),
+            ..Default::default()
+        };
         // parse rocksdb section
         if let Some(properties) = ini_file.section(Some("rocksdb")) {
             for (key, value) in properties.iter() {
@@ -136,16 +737,53 @@ impl ServerOptions {
                     "wal_ttl_seconds" => {
                         options.open_params.rocksdb.wal_ttl_seconds = parse_number!(value, usize)
                     }
+                    "compression" => {
+                        let Some(compression) = CompressionType::parse(value) else {
+                            return Err(SableError::InvalidArgument(format!(
+                                "invalid compression `{}`",
+                                value
+                            )));
+                        };
+                        options.open_params.rocksdb.compression = compression;
+                    }
+                    // deprecated, replaced by `compression`
                     "compression_enabled" => {
-                        options.open_params.rocksdb.compression_enabled = ini_bool!(value)
+                        options.open_params.rocksdb.compression = if ini_bool!(value) {
+                            CompressionType::Snappy
+                        } else {
+                            CompressionType::None
+                        };
                     }
                     "disable_wal" => options.open_params.rocksdb.disable_wal = ini_bool!(value),
+                    "appendfsync" => {
+                        let Some(appendfsync) = AppendFsync::parse(value) else {
+                            return Err(SableError::InvalidArgument(format!(
+                                "invalid appendfsync `{}`",
+                                value
+                            )));
+                        };
+                        options.open_params.rocksdb.appendfsync = appendfsync;
+                    }
                     "manual_wal_flush" => {
                         options.open_params.rocksdb.manual_wal_flush = ini_bool!(value)
                     }
                     "max_open_files" => {
                         options.open_params.rocksdb.max_open_files = parse_number!(value, isize)
                     }
+                    "block_cache_size" => {
+                        options.open_params.rocksdb.block_cache_size = parse_number!(value, usize)
+                    }
+                    "bloom_filter_bits_per_key" => {
+                        options.open_params.rocksdb.bloom_filter_bits_per_key =
+                            parse_number!(value, usize)
+                    }
+                    "cache_index_and_filter_blocks" => {
+                        options.open_params.rocksdb.cache_index_and_filter_blocks = ini_bool!(value)
+                    }
+                    "group_commit_interval_micros" => {
+                        options.open_params.rocksdb.group_commit_interval_micros =
+                            parse_number!(value, usize)
+                    }
                     _ => {}
                 }
             }
@@ -161,6 +799,10 @@ impl ServerOptions {
                     "port" => options.general_settings.port = ini_usize!(value),
                     "listen_ip" => options.general_settings.listen_ip = value.to_string(),
                     "workers" => options.general_settings.workers = ini_usize!(value),
+                    "databases" => {
+                        options.general_settings.databases =
+                            ini_usize!(value).clamp(1, u16::MAX as usize + 1)
+                    }
                     "log_level" => {
                         options.general_settings.log_level = match value.to_lowercase().as_str() {
                             "info" => tracing::Level::INFO,
@@ -179,6 +821,11 @@ impl ServerOptions {
                     }
                     "cert" => options.general_settings.cert = Some(PathBuf::from(value)),
                     "key" => options.general_settings.key = Some(PathBuf::from(value)),
+                    "metrics_port" => {
+                        options.general_settings.metrics_port = Some(ini_usize!(value))
+                    }
+                    "requirepass" => options.set_requirepass(value),
+                    "aclfile" => options.general_settings.aclfile = Some(PathBuf::from(value)),
                     _ => {}
                 }
             }
@@ -199,6 +846,23 @@ impl ServerOptions {
                         options.replication_limits.check_for_updates_interval_ms =
                             parse_number!(value, usize);
                     }
+                    "backlog_size" => {
+                        options.replication_limits.backlog_size = parse_number!(value, usize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("replication")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "tls" => options.replication_settings.tls = ini_bool!(value),
+                    "tls_ca" => options.replication_settings.tls_ca = Some(PathBuf::from(value)),
+                    "tls_server_name" => {
+                        options.replication_settings.tls_server_name = Some(value.to_string())
+                    }
+                    "compression" => options.replication_settings.compression = ini_bool!(value),
                     _ => {}
                 }
             }
@@ -206,11 +870,114 @@ impl ServerOptions {
 
         if let Some(properties) = ini_file.section(Some("client_limits")) {
             for (key, value) in properties.iter() {
-                if key == "client_response_buffer_size" {
-                    options.client_limits.client_response_buffer_size = parse_number!(value, usize);
+                match key {
+                    "client_response_buffer_size" => {
+                        options.client_limits.client_response_buffer_size =
+                            parse_number!(value, usize);
+                    }
+                    "proto_max_bulk_len" => {
+                        options.client_limits.proto_max_bulk_len = parse_number!(value, usize);
+                    }
+                    "proto_max_multibulk_len" => {
+                        options.client_limits.proto_max_multibulk_len = parse_number!(value, usize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("expiration")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "active_expire_interval_ms" => {
+                        options.expiration_settings.active_expire_interval_ms =
+                            parse_number!(value, usize);
+                    }
+                    "active_expire_sample_size" => {
+                        options.expiration_settings.active_expire_sample_size =
+                            parse_number!(value, usize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("slowlog")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "slowlog_log_slower_than" => {
+                        options.slowlog_settings.slowlog_log_slower_than =
+                            parse_number!(value, i64);
+                    }
+                    "slowlog_max_len" => {
+                        options.slowlog_settings.slowlog_max_len = parse_number!(value, usize);
+                    }
+                    _ => {}
                 }
             }
         }
+
+        if let Some(properties) = ini_file.section(Some("memory")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "maxmemory" => {
+                        options.memory_settings.maxmemory = parse_number!(value, usize);
+                    }
+                    "maxmemory_policy" => {
+                        let Some(policy) = EvictionPolicy::parse(value) else {
+                            return Err(SableError::InvalidArgument(format!(
+                                "invalid maxmemory_policy `{}`",
+                                value
+                            )));
+                        };
+                        options.memory_settings.maxmemory_policy = policy;
+                    }
+                    "maxmemory_samples" => {
+                        options.memory_settings.maxmemory_samples =
+                            parse_number!(value, usize).max(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("encoding")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "zset_max_listpack_entries" => {
+                        options.encoding_settings.zset_max_listpack_entries =
+                            parse_number!(value, usize);
+                    }
+                    "zset_max_listpack_value" => {
+                        options.encoding_settings.zset_max_listpack_value =
+                            parse_number!(value, usize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("backup")) {
+            for (key, value) in properties.iter() {
+                match key {
+                    "backup_dir" => {
+                        options.backup_settings.backup_dir = Some(PathBuf::from(value));
+                    }
+                    "backup_keep" => {
+                        options.backup_settings.backup_keep = parse_number!(value, usize).max(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(properties) = ini_file.section(Some("rename_commands")) {
+            for (key, value) in properties.iter() {
+                options
+                    .rename_commands
+                    .push((key.to_lowercase(), value.trim_matches('"').to_lowercase()));
+            }
+        }
         Ok(options)
     }
 }
@@ -1,6 +1,7 @@
 use crate::utils::calculate_slot;
 use crate::PrimaryKeyMetadata;
 use bytes::BytesMut;
+use std::future::Future;
 use std::rc::Rc;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
@@ -8,6 +9,12 @@ lazy_static::lazy_static! {
     static ref MULTI_LOCK: ShardLocker = ShardLocker::default();
 }
 
+tokio::task_local! {
+    /// The slots locked by `LockManager::with_user_keys_locked` for the task that runs inside it
+    /// (sorted). Locking them again from within that task is a no-op
+    static SCOPED_SLOTS: Vec<u16>;
+}
+
 #[allow(dead_code)]
 pub struct ShardLockGuard<'a> {
     read_locks: Option<Vec<RwLockReadGuard<'a, u16>>>,
@@ -48,6 +55,9 @@ impl LockManager {
         slots.dedup();
 
         for idx in slots.into_iter() {
+            if Self::is_scoped_slot(idx) {
+                continue;
+            }
             let Some(lock) = MULTI_LOCK.locks.get(idx as usize) else {
                 unreachable!("No lock in index {}", idx);
             };
@@ -101,6 +111,50 @@ impl LockManager {
         Self::lock_multi_internal_keys_exclusive(&primary_keys_refs)
     }
 
+    // obtain an exclusive lock on the same user key in multiple databases
+    pub fn lock_user_key_in_dbs_exclusive<'a>(
+        user_key: &BytesMut,
+        db_ids: &[u16],
+    ) -> ShardLockGuard<'a> {
+        let primary_keys: Vec<Rc<BytesMut>> = db_ids
+            .iter()
+            .map(|db_id| Rc::new(PrimaryKeyMetadata::new_primary_key(user_key, *db_id)))
+            .collect();
+        Self::lock_multi_internal_keys_exclusive(&primary_keys)
+    }
+
+    /// Run `fut` while holding an exclusive lock on `user_keys`. The locks taken by `fut` itself
+    /// on the same slots are no-ops, so it can run regular commands against these keys (e.g. a
+    /// script calling `redis.call`)
+    pub async fn with_user_keys_locked<F: Future>(
+        user_keys: &[&BytesMut],
+        db_id: u16,
+        fut: F,
+    ) -> F::Output {
+        let mut slots: Vec<u16> = user_keys
+            .iter()
+            .map(|user_key| calculate_slot(&PrimaryKeyMetadata::new_primary_key(user_key, db_id)))
+            .collect();
+        let _unused = Self::lock_user_keys_exclusive(user_keys, db_id);
+        if let Ok(outer_slots) = SCOPED_SLOTS.try_with(|outer_slots| outer_slots.clone()) {
+            slots.extend(outer_slots);
+        }
+        slots.sort();
+        slots.dedup();
+        SCOPED_SLOTS.scope(slots, fut).await
+    }
+
+    /// Is the current task running inside `with_user_keys_locked`?
+    pub fn in_locked_scope() -> bool {
+        SCOPED_SLOTS.try_with(|_| ()).is_ok()
+    }
+
+    fn is_scoped_slot(slot: u16) -> bool {
+        SCOPED_SLOTS
+            .try_with(|slots| slots.binary_search(&slot).is_ok())
+            .unwrap_or(false)
+    }
+
     /// Lock the entire storage
     pub fn lock_all_keys_exclusive<'a>() -> ShardLockGuard<'a> {
         let mut write_locks =
@@ -116,6 +170,9 @@ impl LockManager {
         slots.dedup();
 
         for idx in slots.into_iter() {
+            if Self::is_scoped_slot(idx) {
+                continue;
+            }
             let Some(lock) = MULTI_LOCK.locks.get(idx as usize) else {
                 unreachable!("No lock in index {}", idx);
             };
@@ -148,6 +205,9 @@ impl LockManager {
         slots.dedup();
 
         for idx in slots.into_iter() {
+            if Self::is_scoped_slot(idx) {
+                continue;
+            }
             let Some(lock) = MULTI_LOCK.locks.get(idx as usize) else {
                 unreachable!("No lock in index {}", idx);
             };
@@ -179,6 +239,9 @@ impl LockManager {
         slots.dedup();
 
         for idx in slots.into_iter() {
+            if Self::is_scoped_slot(idx) {
+                continue;
+            }
             let Some(lock) = MULTI_LOCK.locks.get(idx as usize) else {
                 unreachable!("No lock in index {}", idx);
             };
@@ -200,6 +263,9 @@ impl LockManager {
         let mut read_locks = Vec::<RwLockReadGuard<'a, u16>>::with_capacity(1);
         // Calculate the slots and sort them
         let slot = calculate_slot(key);
+        if Self::is_scoped_slot(slot) {
+            return Self::noop_lock();
+        }
 
         read_locks.push(
             MULTI_LOCK
@@ -216,7 +282,6 @@ impl LockManager {
         }
     }
 
-    #[allow(dead_code)]
     fn noop_lock<'a>() -> ShardLockGuard<'a> {
         ShardLockGuard {
             write_locks: None,
@@ -228,6 +293,9 @@ impl LockManager {
         let mut write_locks = Vec::<RwLockWriteGuard<'a, u16>>::with_capacity(1);
         // Calculate the slots and sort them
         let slot = calculate_slot(key);
+        if Self::is_scoped_slot(slot) {
+            return Self::noop_lock();
+        }
 
         write_locks.push(
             MULTI_LOCK
@@ -331,4 +399,24 @@ mod tests {
         let _ = h1.join();
         let _ = h2.join();
     }
+
+    #[test]
+    fn test_locked_scope() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            let k1 = BytesMut::from("key1");
+            let k2 = BytesMut::from("key2");
+            assert!(!LockManager::in_locked_scope());
+            LockManager::with_user_keys_locked(&[&k1], 0, async {
+                assert!(LockManager::in_locked_scope());
+                // the slot of `key1` is already held by this task
+                let locker = LockManager::lock_user_key_exclusive(&k1, 0);
+                assert!(locker.write_locks.is_none());
+                let locker = LockManager::lock_user_keys_shared(&[&k1, &k2], 0);
+                assert_eq!(locker.read_locks.unwrap().len(), 1);
+            })
+            .await;
+            assert!(!LockManager::in_locked_scope());
+        });
+    }
 }
@@ -0,0 +1,166 @@
+use bytes::BytesMut;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Up to this number of arguments are kept per entry. The last kept argument is replaced with
+/// a "... (N more arguments)" note
+const SLOWLOG_ENTRY_MAX_ARGC: usize = 32;
+
+/// Longer arguments are truncated to this number of bytes, followed by a
+/// "... (N more bytes)" note
+const SLOWLOG_ENTRY_MAX_STRING: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowLogEntry {
+    /// Unique, increasing, entry ID
+    pub id: u64,
+    /// When the command was processed (seconds since the Unix epoch)
+    pub timestamp: u64,
+    /// The command execution time, in microseconds
+    pub duration_us: u64,
+    /// The command and its arguments, capped to `SLOWLOG_ENTRY_MAX_ARGC` arguments of up to
+    /// `SLOWLOG_ENTRY_MAX_STRING` bytes each
+    pub arguments: Vec<BytesMut>,
+    pub client_id: u128,
+    /// The client name (`CLIENT SETNAME`), empty when not set
+    pub client_name: String,
+}
+
+#[derive(Default, Debug)]
+struct SlowLogInner {
+    next_id: u64,
+    /// The newest entry is at the front
+    entries: VecDeque<SlowLogEntry>,
+}
+
+/// Bounded log of the commands that took longer than `slowlog_log_slower_than` microseconds
+#[derive(Default, Debug)]
+pub struct SlowLog {
+    inner: Mutex<SlowLogInner>,
+}
+
+impl SlowLog {
+    /// Add an entry for `arguments`, dropping the oldest entries so the log holds at most
+    /// `max_len` entries
+    pub fn add(
+        &self,
+        arguments: &[BytesMut],
+        duration_us: u64,
+        timestamp: u64,
+        client_id: u128,
+        client_name: String,
+        max_len: usize,
+    ) {
+        let arguments = Self::capped_arguments(arguments);
+        let mut inner = self.inner.lock().expect("poisoned mutex");
+        let id = inner.next_id;
+        inner.next_id = inner.next_id.saturating_add(1);
+        inner.entries.push_front(SlowLogEntry {
+            id,
+            timestamp,
+            duration_us,
+            arguments,
+            client_id,
+            client_name,
+        });
+        inner.entries.truncate(max_len);
+    }
+
+    /// Return up to `count` entries, newest first
+    pub fn entries(&self, count: usize) -> Vec<SlowLogEntry> {
+        let inner = self.inner.lock().expect("poisoned mutex");
+        inner.entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("poisoned mutex").entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all the entries. Entries IDs keep increasing
+    pub fn reset(&self) {
+        self.inner.lock().expect("poisoned mutex").entries.clear();
+    }
+
+    /// Copy the arguments, without keeping huge payloads around
+    fn capped_arguments(arguments: &[BytesMut]) -> Vec<BytesMut> {
+        let mut capped = Vec::with_capacity(arguments.len().min(SLOWLOG_ENTRY_MAX_ARGC));
+        for (index, arg) in arguments.iter().enumerate() {
+            if index == SLOWLOG_ENTRY_MAX_ARGC - 1 && arguments.len() > SLOWLOG_ENTRY_MAX_ARGC {
+                capped.push(BytesMut::from(
+                    format!("... ({} more arguments)", arguments.len() - index).as_str(),
+                ));
+                break;
+            }
+
+            if arg.len() > SLOWLOG_ENTRY_MAX_STRING {
+                let mut truncated = BytesMut::from(&arg[..SLOWLOG_ENTRY_MAX_STRING]);
+                truncated.extend_from_slice(
+                    format!("... ({} more bytes)", arg.len() - SLOWLOG_ENTRY_MAX_STRING).as_bytes(),
+                );
+                capped.push(truncated);
+            } else {
+                capped.push(arg.clone());
+            }
+        }
+        capped
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowlog_bounded_and_capped() {
+        let slowlog = SlowLog::default();
+        for i in 0..5 {
+            let args = vec![
+                BytesMut::from("get"),
+                BytesMut::from(format!("key_{}", i).as_str()),
+            ];
+            slowlog.add(&args, 100 + i, 1000, 1, String::default(), 3);
+        }
+        assert_eq!(slowlog.len(), 3);
+        let entries = slowlog.entries(10);
+        assert_eq!(
+            entries.iter().map(|e| e.id).collect::<Vec<u64>>(),
+            vec![4, 3, 2]
+        );
+        assert_eq!(entries[0].arguments[1], BytesMut::from("key_4"));
+        assert_eq!(slowlog.entries(1).len(), 1);
+
+        // long arguments and long arguments lists are truncated
+        let mut args = vec![BytesMut::from("mset")];
+        args.push(BytesMut::from("x".repeat(200).as_str()));
+        for i in 0..40 {
+            args.push(BytesMut::from(format!("{}", i).as_str()));
+        }
+        slowlog.add(&args, 10, 1000, 1, String::default(), 3);
+        let entry = &slowlog.entries(1)[0];
+        assert_eq!(entry.arguments.len(), SLOWLOG_ENTRY_MAX_ARGC);
+        assert_eq!(
+            entry.arguments[1],
+            BytesMut::from(format!("{}... (72 more bytes)", "x".repeat(128)).as_str())
+        );
+        assert_eq!(
+            entry.arguments[SLOWLOG_ENTRY_MAX_ARGC - 1],
+            BytesMut::from("... (11 more arguments)")
+        );
+
+        slowlog.reset();
+        assert!(slowlog.is_empty());
+        slowlog.add(&args, 10, 1000, 1, String::default(), 3);
+        assert_eq!(slowlog.entries(1)[0].id, 6);
+    }
+}
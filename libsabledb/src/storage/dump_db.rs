@@ -0,0 +1,326 @@
+#[allow(unused_imports)]
+use crate::{
+    metadata::Encoding,
+    storage::{
+        GenericDb, HashDb, HashGetAllResult, PutFlags, SetDb, SetMembersResult, StringsDb,
+        ZAddFlags, ZSetDb, ZSetRangeResult,
+    },
+    types::{List, ListValuesResult},
+    CommonValueMetadata, Expiration, SableError, StorageAdapter, StringValueMetadata,
+    U8ArrayBuilder, U8ArrayReader,
+};
+use bytes::BytesMut;
+
+/// The version of the `DUMP` payload format. Bump it whenever the format changes, `RESTORE`
+/// rejects the versions it does not know
+const DUMP_VERSION: u16 = 1;
+
+/// The payload ends with the format version (`u16`) followed by a CRC16 (`u16`) of everything
+/// that precedes it
+const TRAILER_SIZE: usize = 2 * std::mem::size_of::<u16>();
+
+/// `DumpDb::restore` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum RestoreResult {
+    /// The key was created
+    Restored,
+    /// The payload is corrupted, or was produced by an unknown format version
+    InvalidPayload,
+}
+
+/// A value, decoupled from the way it is stored. The payload produced by `DUMP` is:
+///
+/// `[type: u8][value][version: u16][crc16: u16]`
+///
+/// Where `type` is the `Encoding::VALUE_*` of the value and `value` is built from length
+/// prefixed (`u64`) byte strings:
+///
+/// - string: the string
+/// - list and set: the number of elements, followed by the elements
+/// - hash: the number of fields, followed by the field / value pairs
+/// - sorted set: the number of members, followed by the member / score (`f64` bits) pairs
+#[derive(Debug, PartialEq)]
+enum DumpedValue {
+    Str(BytesMut),
+    List(Vec<BytesMut>),
+    Hash(Vec<(BytesMut, BytesMut)>),
+    Set(Vec<BytesMut>),
+    ZSet(Vec<(BytesMut, f64)>),
+}
+
+impl DumpedValue {
+    fn to_bytes(&self) -> BytesMut {
+        let mut buffer = BytesMut::new();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        match self {
+            DumpedValue::Str(value) => {
+                builder.write_u8(Encoding::VALUE_STRING);
+                Self::write_string(&mut builder, value);
+            }
+            DumpedValue::List(values) | DumpedValue::Set(values) => {
+                builder.write_u8(if matches!(self, DumpedValue::List(_)) {
+                    Encoding::VALUE_LIST
+                } else {
+                    Encoding::VALUE_SET
+                });
+                builder.write_u64(values.len() as u64);
+                for value in values {
+                    Self::write_string(&mut builder, value);
+                }
+            }
+            DumpedValue::Hash(fields) => {
+                builder.write_u8(Encoding::VALUE_HASH);
+                builder.write_u64(fields.len() as u64);
+                for (field, value) in fields {
+                    Self::write_string(&mut builder, field);
+                    Self::write_string(&mut builder, value);
+                }
+            }
+            DumpedValue::ZSet(members) => {
+                builder.write_u8(Encoding::VALUE_ZSET);
+                builder.write_u64(members.len() as u64);
+                for (member, score) in members {
+                    Self::write_string(&mut builder, member);
+                    builder.write_u64(score.to_bits());
+                }
+            }
+        }
+        builder.write_u16(DUMP_VERSION);
+        let crc = crc16::State::<crc16::XMODEM>::calculate(&buffer);
+        U8ArrayBuilder::with_buffer(&mut buffer).write_u16(crc);
+        buffer
+    }
+
+    /// Decode `payload`. Return `None` if the checksum or the version do not match, or if the
+    /// payload is malformed
+    fn from_bytes(payload: &[u8]) -> Option<Self> {
+        if payload.len() < TRAILER_SIZE {
+            return None;
+        }
+
+        let (content, crc) = payload.split_at(payload.len() - std::mem::size_of::<u16>());
+        if U8ArrayReader::with_buffer(crc).read_u16()?
+            != crc16::State::<crc16::XMODEM>::calculate(content)
+        {
+            return None;
+        }
+
+        let (content, version) = content.split_at(content.len() - std::mem::size_of::<u16>());
+        if U8ArrayReader::with_buffer(version).read_u16()? != DUMP_VERSION {
+            return None;
+        }
+
+        let mut reader = U8ArrayReader::with_buffer(content);
+        let value = match reader.read_u8()? {
+            Encoding::VALUE_STRING => DumpedValue::Str(Self::read_string(&mut reader)?),
+            Encoding::VALUE_LIST => DumpedValue::List(Self::read_strings(&mut reader)?),
+            Encoding::VALUE_SET => DumpedValue::Set(Self::read_strings(&mut reader)?),
+            Encoding::VALUE_HASH => {
+                let count = reader.read_u64()?;
+                let mut fields = Vec::<(BytesMut, BytesMut)>::new();
+                for _ in 0..count {
+                    let field = Self::read_string(&mut reader)?;
+                    let value = Self::read_string(&mut reader)?;
+                    fields.push((field, value));
+                }
+                DumpedValue::Hash(fields)
+            }
+            Encoding::VALUE_ZSET => {
+                let count = reader.read_u64()?;
+                let mut members = Vec::<(BytesMut, f64)>::new();
+                for _ in 0..count {
+                    let member = Self::read_string(&mut reader)?;
+                    let score = f64::from_bits(reader.read_u64()?);
+                    members.push((member, score));
+                }
+                DumpedValue::ZSet(members)
+            }
+            _ => return None,
+        };
+
+        // trailing garbage means a malformed payload
+        if reader.consumed() != content.len() {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn write_string(builder: &mut U8ArrayBuilder, value: &[u8]) {
+        builder.write_u64(value.len() as u64);
+        builder.write_bytes(value);
+    }
+
+    fn read_string(reader: &mut U8ArrayReader) -> Option<BytesMut> {
+        let len = usize::try_from(reader.read_u64()?).ok()?;
+        reader.read_bytes(len)
+    }
+
+    fn read_strings(reader: &mut U8ArrayReader) -> Option<Vec<BytesMut>> {
+        let count = reader.read_u64()?;
+        let mut values = Vec::<BytesMut>::new();
+        for _ in 0..count {
+            values.push(Self::read_string(reader)?);
+        }
+        Some(values)
+    }
+}
+
+/// Serialise values into opaque payloads and back (`DUMP` and `RESTORE`)
+///
+/// Locking strategy: this class does not lock anything and relies on the caller
+/// to obtain the locks if needed
+pub struct DumpDb<'a> {
+    store: &'a StorageAdapter,
+    db_id: u16,
+}
+
+impl<'a> DumpDb<'a> {
+    pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
+        DumpDb { store, db_id }
+    }
+
+    /// Serialise the value stored at `user_key`. Return `None` if the key does not exist
+    pub fn dump(&self, user_key: &BytesMut) -> Result<Option<BytesMut>, SableError> {
+        let generic_db = GenericDb::with_storage(self.store, self.db_id);
+        let Some(value) = generic_db.get_live(user_key)? else {
+            return Ok(None);
+        };
+
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        let dumped = match common_md.value_type() {
+            Encoding::VALUE_STRING => {
+                let strings_db = StringsDb::with_storage(self.store, self.db_id);
+                let Some((value, _)) = strings_db.get(user_key)? else {
+                    return Ok(None);
+                };
+                DumpedValue::Str(value)
+            }
+            Encoding::VALUE_LIST => {
+                let list = List::with_storage(self.store, self.db_id);
+                match list.values(user_key)? {
+                    ListValuesResult::Some(values) => DumpedValue::List(values),
+                    ListValuesResult::None => return Ok(None),
+                    ListValuesResult::WrongType => return Err(SableError::SerialisationError),
+                }
+            }
+            Encoding::VALUE_HASH => {
+                let hash_db = HashDb::with_storage(self.store, self.db_id);
+                match hash_db.get_all(user_key)? {
+                    HashGetAllResult::Some(fields) => DumpedValue::Hash(fields),
+                    HashGetAllResult::WrongType => return Err(SableError::SerialisationError),
+                }
+            }
+            Encoding::VALUE_SET => {
+                let set_db = SetDb::with_storage(self.store, self.db_id);
+                match set_db.members(user_key)? {
+                    SetMembersResult::Some(members) => DumpedValue::Set(members),
+                    SetMembersResult::WrongType => return Err(SableError::SerialisationError),
+                }
+            }
+            Encoding::VALUE_ZSET => {
+                let zset_db = ZSetDb::with_storage(self.store, self.db_id);
+                match zset_db.range_by_rank(user_key, 0, -1)? {
+                    ZSetRangeResult::Some(members) => DumpedValue::ZSet(members),
+                    ZSetRangeResult::WrongType => return Err(SableError::SerialisationError),
+                }
+            }
+            unknown_type => {
+                return Err(SableError::OtherError(format!(
+                    "can not dump value of unknown type {}",
+                    unknown_type
+                )));
+            }
+        };
+        Ok(Some(dumped.to_bytes()))
+    }
+
+    /// Create `user_key` from `payload`, a value serialised by `dump`, with `expiration`. An
+    /// existing value is replaced, the caller is responsible for deleting its items (nothing is
+    /// written if the payload is invalid)
+    pub fn restore(
+        &self,
+        user_key: &BytesMut,
+        payload: &[u8],
+        expiration: &Expiration,
+    ) -> Result<RestoreResult, SableError> {
+        let Some(value) = DumpedValue::from_bytes(payload) else {
+            return Ok(RestoreResult::InvalidPayload);
+        };
+
+        GenericDb::with_storage(self.store, self.db_id).delete(user_key)?;
+        match value {
+            DumpedValue::Str(value) => {
+                let mut md = StringValueMetadata::new();
+                *md.expiration_mut() = expiration.clone();
+                let strings_db = StringsDb::with_storage(self.store, self.db_id);
+                strings_db.put(user_key, &value, &md, PutFlags::Override)?;
+                return Ok(RestoreResult::Restored);
+            }
+            DumpedValue::List(values) => {
+                List::with_storage(self.store, self.db_id).store(user_key, &values)?;
+            }
+            DumpedValue::Hash(fields) => {
+                let fields: Vec<(&BytesMut, &BytesMut)> =
+                    fields.iter().map(|(field, value)| (field, value)).collect();
+                HashDb::with_storage(self.store, self.db_id).put_multi(user_key, &fields)?;
+            }
+            DumpedValue::Set(members) => {
+                SetDb::with_storage(self.store, self.db_id).store(user_key, &members)?;
+            }
+            DumpedValue::ZSet(members) => {
+                let members: Vec<(f64, &BytesMut)> = members
+                    .iter()
+                    .map(|(member, score)| (*score, member))
+                    .collect();
+                ZSetDb::with_storage(self.store, self.db_id).add(
+                    user_key,
+                    &members,
+                    ZAddFlags::None,
+                )?;
+            }
+        }
+
+        if expiration.has_ttl() {
+            GenericDb::with_storage(self.store, self.db_id).put_expiration(user_key, expiration)?;
+        }
+        Ok(RestoreResult::Restored)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dumped_value_serialization() {
+        let values = vec![
+            DumpedValue::Str(BytesMut::from("value")),
+            DumpedValue::List(vec![BytesMut::from("a"), BytesMut::from("")]),
+            DumpedValue::Hash(vec![(BytesMut::from("field"), BytesMut::from("value"))]),
+            DumpedValue::Set(Vec::new()),
+            DumpedValue::ZSet(vec![(BytesMut::from("member"), -1.5)]),
+        ];
+
+        for value in values {
+            let payload = value.to_bytes();
+            assert_eq!(DumpedValue::from_bytes(&payload), Some(value));
+
+            // a corrupted payload is detected by the checksum
+            let mut corrupted = payload.clone();
+            corrupted[1] ^= 0xff;
+            assert_eq!(DumpedValue::from_bytes(&corrupted), None);
+
+            // a truncated payload is rejected
+            assert_eq!(DumpedValue::from_bytes(&payload[..payload.len() - 1]), None);
+        }
+        assert_eq!(DumpedValue::from_bytes(&[]), None);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    metadata::Encoding,
+    storage::{BatchUpdate, PutFlags},
+    SableError, StorageAdapter,
+};
+use bytes::BytesMut;
+use std::rc::Rc;
+
+/// Stores the source code of the function libraries (`FUNCTION LOAD`), so they survive a restart.
+/// The libraries are not bound to a database:
+///
+/// `[Encoding::KEY_FUNCTION_LIBRARY | library name]` -> `[library code]`
+///
+/// Locking strategy: this class does not lock anything and relies on the caller to serialise the
+/// updates
+pub struct FunctionDb<'a> {
+    store: &'a StorageAdapter,
+}
+
+impl<'a> FunctionDb<'a> {
+    pub fn with_storage(store: &'a StorageAdapter) -> Self {
+        FunctionDb { store }
+    }
+
+    /// Add or replace the library `name`
+    pub fn put(&self, name: &str, code: &BytesMut) -> Result<(), SableError> {
+        self.store
+            .put(&Self::encode_key(name), code, PutFlags::Override)
+    }
+
+    /// Delete the library `name`
+    pub fn delete(&self, name: &str) -> Result<(), SableError> {
+        self.store.delete(&Self::encode_key(name))
+    }
+
+    /// Return all the stored libraries as `(name, code)` pairs, ordered by name
+    pub fn list(&self) -> Result<Vec<(String, BytesMut)>, SableError> {
+        let prefix = BytesMut::from(&[Encoding::KEY_FUNCTION_LIBRARY][..]);
+        let mut libraries = Vec::<(String, BytesMut)>::new();
+        self.store.iterate(
+            Rc::new(prefix),
+            Box::new(|prefix, key, value| {
+                let name = String::from_utf8_lossy(&key[prefix.len()..]).to_string();
+                libraries.push((name, BytesMut::from(value)));
+                true
+            }),
+        )?;
+        Ok(libraries)
+    }
+
+    /// Delete all the stored libraries. The records are deleted one by one (and not with a range
+    /// deletion) so the replicas see the change
+    pub fn flush(&self) -> Result<(), SableError> {
+        let libraries = self.list()?;
+        if libraries.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = BatchUpdate::with_capacity(libraries.len());
+        for (name, _) in libraries {
+            batch.delete(Self::encode_key(&name));
+        }
+        self.store.apply_batch(&batch)
+    }
+
+    fn encode_key(name: &str) -> BytesMut {
+        let mut key = BytesMut::with_capacity(1 + name.len());
+        key.extend_from_slice(&[Encoding::KEY_FUNCTION_LIBRARY]);
+        key.extend_from_slice(name.as_bytes());
+        key
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_db() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let function_db = FunctionDb::with_storage(&store);
+        assert!(function_db.list()?.is_empty());
+
+        function_db.put("lib2", &BytesMut::from("code2"))?;
+        function_db.put("lib1", &BytesMut::from("code1"))?;
+        function_db.put("lib1", &BytesMut::from("code1.1"))?;
+        assert_eq!(
+            function_db.list()?,
+            vec![
+                ("lib1".to_string(), BytesMut::from("code1.1")),
+                ("lib2".to_string(), BytesMut::from("code2")),
+            ]
+        );
+
+        function_db.delete("lib2")?;
+        assert_eq!(
+            function_db.list()?,
+            vec![("lib1".to_string(), BytesMut::from("code1.1"))]
+        );
+
+        function_db.flush()?;
+        assert!(function_db.list()?.is_empty());
+        Ok(())
+    }
+}
@@ -1,9 +1,17 @@
 /// A database accessor that does not really care about the value
 use crate::{
-    storage::PutFlags, CommonValueMetadata, Expiration, PrimaryKeyMetadata, SableError,
-    StorageAdapter, U8ArrayBuilder, U8ArrayReader,
+    metadata::{
+        Encoding, HashValueMetadata, ListValueMetadata, SetValueMetadata, StreamValueMetadata,
+        ZSetValueMetadata,
+    },
+    storage::{BatchUpdate, PutFlags, RecordsRead, StorageIterator},
+    utils::SLOT_SIZE,
+    CommonValueMetadata, Expiration, PrimaryKeyMetadata, SableError, StorageAdapter, TimeUtils,
+    U8ArrayBuilder, U8ArrayReader,
 };
 use bytes::BytesMut;
+use rand::Rng;
+use std::rc::Rc;
 
 #[allow(dead_code)]
 /// General purpose database wrapper. This class uses the fact that
@@ -13,16 +21,24 @@ use bytes::BytesMut;
 ///
 /// Locking strategy: this class does not lock anything and relies on the caller
 /// to obtain the locks if needed
+///
+/// The primary records read by this accessor are remembered and reported along with their
+/// writes (see `RecordsRead`)
 pub struct GenericDb<'a> {
     /// This class handles String command database access
     store: &'a StorageAdapter,
     db_id: u16,
+    records_read: RecordsRead,
 }
 
 #[allow(dead_code)]
 impl<'a> GenericDb<'a> {
     pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
-        GenericDb { store, db_id }
+        GenericDb {
+            store,
+            db_id,
+            records_read: RecordsRead::default(),
+        }
     }
 
     /// Get a key value + its common metadata.
@@ -37,7 +53,179 @@ impl<'a> GenericDb<'a> {
     /// Delete key. The key is assumed to be a user key (i.e. not encoded)
     pub fn delete(&self, user_key: &BytesMut) -> Result<(), SableError> {
         let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
-        self.store.delete(&internal_key)
+        let before = self.records_read.take(&internal_key);
+        self.store.delete_with_before(&internal_key, before)?;
+        self.records_read.insert(&internal_key, None);
+        Ok(())
+    }
+
+    /// Put the raw value (metadata included) of `user_key`
+    pub fn put_value(
+        &self,
+        user_key: &BytesMut,
+        value: &BytesMut,
+        put_flags: PutFlags,
+    ) -> Result<(), SableError> {
+        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+        // with the other flags, the put may not happen
+        let overrides = matches!(put_flags, PutFlags::Override);
+        let before = self.records_read.take(&internal_key);
+        self.store
+            .put_with_before(&internal_key, value, put_flags, before)?;
+        if overrides {
+            self.records_read.insert(&internal_key, Some(value));
+        }
+        Ok(())
+    }
+
+    /// Return the prefix shared by all the item records owned by `user_key` (e.g. the fields
+    /// of a hash or the items of a list). Returns `None` if the key does not exist or if its type
+    /// keeps everything in the primary record (e.g. a string)
+    pub fn items_prefix(&self, user_key: &BytesMut) -> Result<Option<BytesMut>, SableError> {
+        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+        let Some(value) = self.store.get(&internal_key)? else {
+            return Ok(None);
+        };
+        Self::items_prefix_from_value(&value)
+    }
+
+    /// Return the raw value (including its metadata) of `user_key`, or `None` if the key does not
+    /// exist or expired. This is the place that decides whether a key is logically present: an
+    /// expired key is deleted as a side effect (see `delete_if_expired`).
+    ///
+    /// The caller must hold a lock on `user_key`. A shared lock is enough: no writer can update
+    /// the key while it is held, so removing an expired key can not race with a write
+    pub fn get_live(&self, user_key: &BytesMut) -> Result<Option<BytesMut>, SableError> {
+        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+        let value = self.store.get(&internal_key)?;
+        self.records_read.insert(&internal_key, value.as_deref());
+        let Some(value) = value else {
+            return Ok(None);
+        };
+
+        if self.delete_if_expired(user_key, &value)? {
+            Ok(None)
+        } else {
+            Ok(Some(value))
+        }
+    }
+
+    /// Given `value`, the raw value of `user_key`, delete the key if it expired. Return `true` if
+    /// the key expired. The items it owns are unreachable once the key is deleted: they are left
+    /// to the `Reclaimer`, so a read never sweeps a huge collection. A replica does not delete
+    /// anything, it leaves the deletion to its primary
+    pub fn delete_if_expired(&self, user_key: &BytesMut, value: &[u8]) -> Result<bool, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        if !common_md.expiration().is_expired()? {
+            return Ok(false);
+        }
+
+        if !self.store.is_replica_mode() {
+            let items_prefix = Self::items_prefix_from_value(value)?;
+            self.delete(user_key)?;
+            if let Some(items_prefix) = items_prefix {
+                self.store.enqueue_reclaim(items_prefix);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Delete up to `budget` records starting with `prefix`. Return the number of records deleted.
+    /// A return value lower than `budget` means that no records are left under `prefix`
+    pub fn delete_items(&self, prefix: &BytesMut, budget: usize) -> Result<usize, SableError> {
+        let prefix = Rc::new(prefix.clone());
+        let mut updates = BatchUpdate::with_capacity(budget);
+        match self.store.create_iterator(prefix.clone())? {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                while rocksdb_iter.valid() && updates.len() < budget {
+                    let Some(key) = rocksdb_iter.key() else {
+                        break;
+                    };
+
+                    if !key.starts_with(prefix.as_ref()) {
+                        break;
+                    }
+
+                    updates.delete(BytesMut::from(key));
+                    rocksdb_iter.next();
+                }
+            }
+        }
+
+        let deleted = updates.len();
+        if !updates.is_empty() {
+            self.store.apply_batch(&updates)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Visit up to `limit` item records that start with `prefix`. Return the number of records
+    /// visited and the total size of their keys and values
+    pub fn sample_items(
+        &self,
+        prefix: &BytesMut,
+        limit: usize,
+    ) -> Result<(usize, usize), SableError> {
+        let prefix = Rc::new(prefix.clone());
+        let mut count = 0usize;
+        let mut total_size = 0usize;
+        match self.store.create_iterator(prefix.clone())? {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                while rocksdb_iter.valid() && count < limit {
+                    let (Some(key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value())
+                    else {
+                        break;
+                    };
+
+                    if !key.starts_with(prefix.as_ref()) {
+                        break;
+                    }
+
+                    count = count.saturating_add(1);
+                    total_size = total_size.saturating_add(key.len() + value.len());
+                    rocksdb_iter.next();
+                }
+            }
+        }
+        Ok((count, total_size))
+    }
+
+    /// Delete all the keys of this database with a single range deletion of their primary
+    /// records, and return the prefixes of the items they owned (items are not grouped per
+    /// database, so the collections are listed first). The items are unreachable once the keys
+    /// are gone, the caller deletes them (`delete_range` or the `Reclaimer`): the values written
+    /// afterwards own new prefixes.
+    ///
+    /// The caller must hold the lock of all the keys
+    pub fn flush(&self) -> Result<Vec<BytesMut>, SableError> {
+        let primary_prefix = PrimaryKeyMetadata::primary_key_prefix(self.db_id);
+        let mut items_prefixes = Vec::<BytesMut>::new();
+        match self
+            .store
+            .create_iterator(Rc::new(primary_prefix.clone()))?
+        {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                while rocksdb_iter.valid() {
+                    let (Some(key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value())
+                    else {
+                        break;
+                    };
+
+                    if !key.starts_with(&primary_prefix) {
+                        break;
+                    }
+
+                    if let Some(items_prefix) = Self::items_prefix_from_value(value)? {
+                        items_prefixes.push(items_prefix);
+                    }
+                    rocksdb_iter.next();
+                }
+            }
+        }
+
+        self.store.delete_range(&primary_prefix)?;
+        Ok(items_prefixes)
     }
 
     /// Put or Replace key
@@ -52,6 +240,103 @@ impl<'a> GenericDb<'a> {
         self.put_internal(user_key, value, metadata, put_flags)
     }
 
+    /// Move the value of `src` (metadata and expiration included) to `dst`, replacing the value
+    /// of `dst`. The records owned by a value (hash fields, list items etc) are keyed by the value
+    /// ID and not by the user key, so moving the primary record moves the entire value. Both
+    /// records are updated in a single batch.
+    ///
+    /// The caller must hold an exclusive lock on both keys and is responsible for deleting the
+    /// items of the replaced value (see `items_prefix`). Return `false` if `src` does not exist
+    pub fn rename(&self, src: &BytesMut, dst: &BytesMut) -> Result<bool, SableError> {
+        let Some(value) = self.get_live(src)? else {
+            return Ok(false);
+        };
+
+        let mut updates = BatchUpdate::with_capacity(2);
+        self.delete_in_batch(&mut updates, src);
+        self.put_in_batch(&mut updates, dst, value);
+        self.store.apply_batch(&updates)?;
+        Ok(true)
+    }
+
+    /// Move `user_key` to database `target_db_id`, keeping its value as is (including its
+    /// expiration). The items owned by a collection are not bound to a database (their prefix is
+    /// built from the value ID) so only the primary record is moved.
+    ///
+    /// The caller must hold an exclusive lock on the key in both databases. Return `false` if the
+    /// key does not exist in this database or already exists in the target database
+    pub fn move_key(&self, user_key: &BytesMut, target_db_id: u16) -> Result<bool, SableError> {
+        let Some(value) = self.get_live(user_key)? else {
+            return Ok(false);
+        };
+
+        let target_db = GenericDb::with_storage(self.store, target_db_id);
+        if target_db.get_live(user_key)?.is_some() {
+            return Ok(false);
+        }
+
+        let mut updates = BatchUpdate::with_capacity(2);
+        self.delete_in_batch(&mut updates, user_key);
+        target_db.put_in_batch(&mut updates, user_key, value);
+        self.store.apply_batch(&updates)?;
+        Ok(true)
+    }
+
+    /// Set the last access time of the existing keys in `user_keys` to now. Only the common
+    /// metadata (the first bytes of the value) is rewritten, and all the keys are updated in a
+    /// single batch. The caller must hold a lock on all the keys (a shared lock is enough, as the
+    /// value itself is not modified). Return the number of keys that exist
+    pub fn touch(&self, user_keys: &[&BytesMut]) -> Result<usize, SableError> {
+        let now = TimeUtils::epoch_ms()?;
+        let mut updates = BatchUpdate::with_capacity(user_keys.len());
+        let mut existing_keys = 0usize;
+        for user_key in user_keys {
+            let Some(value) = self.get_live(user_key)? else {
+                continue;
+            };
+            existing_keys = existing_keys.saturating_add(1);
+
+            let mut reader = U8ArrayReader::with_buffer(&value);
+            let mut common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+            common_md.set_accessed_at(now);
+
+            let md_len = reader.consumed();
+
+            // the metadata may grow if it was written with an older layout
+            let mut updated_value =
+                BytesMut::with_capacity(value.len() + CommonValueMetadata::SIZE);
+            let mut builder = U8ArrayBuilder::with_buffer(&mut updated_value);
+            common_md.to_bytes(&mut builder);
+            builder.write_bytes(&value[md_len..]);
+            self.put_in_batch(&mut updates, user_key, updated_value);
+        }
+
+        // a replica receives the updated metadata from its primary
+        if !updates.is_empty() && !self.store.is_replica_mode() {
+            self.store.apply_batch(&updates)?;
+        }
+        Ok(existing_keys)
+    }
+
+    /// Return a random (non expired) key of this database, or `None` if the database is empty.
+    ///
+    /// RocksDB has no random access, so we seek to a random slot and return the first live key
+    /// from there, wrapping around to the first slot once. Keys are spread over the slots by
+    /// their hash, but the distribution is not uniform: a key that follows a range of empty slots
+    /// is picked more often than a key that shares its slot with other keys
+    pub fn random_key(&self) -> Result<Option<BytesMut>, SableError> {
+        let slot = rand::thread_rng().gen_range(0..SLOT_SIZE);
+        let from_slot = self.store.user_keys_iterator_from_slot(self.db_id, slot)?;
+        let from_start = self.store.user_keys_iterator(self.db_id, None)?;
+        for item in from_slot.chain(from_start) {
+            let (user_key, common_md) = item?;
+            if !common_md.expiration().is_expired()? {
+                return Ok(Some(user_key));
+            }
+        }
+        Ok(None)
+    }
+
     /// Return true if user key exists in the db
     /// Note that same keys might exists for different db IDs
     pub fn contains(&self, user_key: &BytesMut) -> Result<bool, SableError> {
@@ -84,6 +369,23 @@ impl<'a> GenericDb<'a> {
     // =========-------------------------------------------
     // Internal helpers
     // =========-------------------------------------------
+    fn items_prefix_from_value(value: &[u8]) -> Result<Option<BytesMut>, SableError> {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+
+        let mut reader = U8ArrayReader::with_buffer(value);
+        match common_md.value_type() {
+            Encoding::VALUE_LIST => Ok(Some(ListValueMetadata::from_bytes(&mut reader)?.prefix())),
+            Encoding::VALUE_HASH => Ok(Some(HashValueMetadata::from_bytes(&mut reader)?.prefix())),
+            Encoding::VALUE_SET => Ok(Some(SetValueMetadata::from_bytes(&mut reader)?.prefix())),
+            Encoding::VALUE_ZSET => Ok(Some(ZSetValueMetadata::from_bytes(&mut reader)?.prefix())),
+            Encoding::VALUE_STREAM => {
+                Ok(Some(StreamValueMetadata::from_bytes(&mut reader)?.prefix()))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn put_internal(
         &self,
         user_key: &BytesMut,
@@ -92,35 +394,43 @@ impl<'a> GenericDb<'a> {
         put_flags: PutFlags,
     ) -> Result<(), SableError> {
         let mut joined_value = BytesMut::with_capacity(value.len() + CommonValueMetadata::SIZE);
-        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
         let mut builder = U8ArrayBuilder::with_buffer(&mut joined_value);
         metadata.to_bytes(&mut builder);
         builder.write_bytes(value);
-        self.store.put(&internal_key, &joined_value, put_flags)
+        self.put_value(user_key, &joined_value, put_flags)
+    }
+
+    /// Add the deletion of `user_key` to `updates`, with the state of the record if it was read
+    fn delete_in_batch(&self, updates: &mut BatchUpdate, user_key: &BytesMut) {
+        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+        if let Some(before) = self.records_read.take(&internal_key) {
+            updates.set_record_before(&internal_key, before);
+        }
+        updates.delete(internal_key);
     }
 
-    /// Get a string key from the underlying storage
+    /// Add the put of the raw `value` of `user_key` to `updates`, with the state of the record
+    /// if it was read
+    fn put_in_batch(&self, updates: &mut BatchUpdate, user_key: &BytesMut, value: BytesMut) {
+        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+        if let Some(before) = self.records_read.take(&internal_key) {
+            updates.set_record_before(&internal_key, before);
+        }
+        updates.put(internal_key, value);
+    }
+
+    /// Get a key from the underlying storage
     fn get_internal(
         &self,
         user_key: &BytesMut,
     ) -> Result<Option<(BytesMut, CommonValueMetadata)>, SableError> {
-        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
-
-        let raw_value = self.store.get(&internal_key)?;
-        if let Some(mut value) = raw_value {
-            let mut reader = U8ArrayReader::with_buffer(&value);
-            let md = CommonValueMetadata::from_bytes(&mut reader)?;
-
-            if md.expiration().is_expired()? {
-                self.store.delete(&internal_key)?;
-                Ok(None)
-            } else {
-                let _ = value.split_to(CommonValueMetadata::SIZE);
-                Ok(Some((value, md)))
-            }
-        } else {
-            Ok(None)
-        }
+        let Some(mut value) = self.get_live(user_key)? else {
+            return Ok(None);
+        };
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let md = CommonValueMetadata::from_bytes(&mut reader)?;
+        let _ = value.split_to(reader.consumed());
+        Ok(Some((value, md)))
     }
 }
 
@@ -134,7 +444,10 @@ impl<'a> GenericDb<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{metadata::Encoding, storage::StringsDb, StorageOpenParams, StringValueMetadata};
+    use crate::{
+        metadata::Encoding, storage::StringsDb, CompressionType, StorageOpenParams,
+        StringValueMetadata,
+    };
     use std::fs;
     use std::path::PathBuf;
 
@@ -143,7 +456,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/{}.db", name));
         let _ = fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
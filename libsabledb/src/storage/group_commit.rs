@@ -0,0 +1,111 @@
+use crate::SableError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Completes once the writes of a commit request are durable, or with the error of the sync
+pub type CommitReceiver = oneshot::Receiver<Result<(), String>>;
+
+/// Sync the write-ahead-log once for a group of writes, instead of once per write.
+///
+/// The writes are applied to the store without syncing the WAL, so they are visible at once
+/// and ordered by the key locks as usual. A client that acknowledges a write requests a commit
+/// and awaits its reply before replying: the worker keeps serving its other clients meanwhile.
+///
+/// The requests are served by a dedicated thread. The first request of a group waits for
+/// `interval`, so the requests that arrive meanwhile join the group, then the WAL is synced
+/// once and every request of the group is answered. The sync covers all the writes applied
+/// before it, so the requests are answered in the order of their writes
+pub struct GroupCommit {
+    /// The WAL is known to be synced up to this sequence number
+    synced_seq: Arc<AtomicU64>,
+    /// The number of times the WAL was synced
+    syncs: Arc<AtomicU64>,
+    requests: Option<Sender<oneshot::Sender<Result<(), String>>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GroupCommit {
+    /// Start the commit thread of `store`
+    pub fn start(store: Arc<rocksdb::DB>, interval: Duration) -> Result<Self, SableError> {
+        let synced_seq = Arc::new(AtomicU64::new(store.latest_sequence_number()));
+        let (requests, requests_rx) = std::sync::mpsc::channel();
+        let syncs = Arc::new(AtomicU64::new(0));
+        let thread_synced_seq = synced_seq.clone();
+        let thread_syncs = syncs.clone();
+        let thread = std::thread::Builder::new()
+            .name("group-commit".to_string())
+            .spawn(move || {
+                Self::run(
+                    store,
+                    interval,
+                    requests_rx,
+                    thread_synced_seq,
+                    thread_syncs,
+                )
+            })?;
+        Ok(GroupCommit {
+            synced_seq,
+            syncs,
+            requests: Some(requests),
+            thread: Some(thread),
+        })
+    }
+
+    /// Request the commit of the writes applied up to sequence number `seq`. Return `None` if
+    /// they are already durable
+    pub fn request(&self, seq: u64) -> Option<CommitReceiver> {
+        if seq <= self.synced_seq.load(Ordering::Acquire) {
+            return None;
+        }
+        let (reply, reply_rx) = oneshot::channel();
+        if let Some(requests) = &self.requests {
+            // if the thread is gone, the reply is dropped and the receiver reports it
+            let _ = requests.send(reply);
+        }
+        Some(reply_rx)
+    }
+
+    /// The number of times the WAL was synced, i.e. the number of groups committed
+    pub fn syncs(&self) -> u64 {
+        self.syncs.load(Ordering::Relaxed)
+    }
+
+    /// The commit thread: sync the WAL once per group of requests
+    fn run(
+        store: Arc<rocksdb::DB>,
+        interval: Duration,
+        requests: Receiver<oneshot::Sender<Result<(), String>>>,
+        synced_seq: Arc<AtomicU64>,
+        syncs: Arc<AtomicU64>,
+    ) {
+        while let Ok(first) = requests.recv() {
+            // let the requests of the other clients join the group
+            std::thread::sleep(interval);
+            let group: Vec<_> = std::iter::once(first).chain(requests.try_iter()).collect();
+
+            let seq = store.latest_sequence_number();
+            let result = store.flush_wal(true).map_err(|e| e.to_string());
+            syncs.fetch_add(1, Ordering::Relaxed);
+            if result.is_ok() {
+                synced_seq.fetch_max(seq, Ordering::AcqRel);
+            }
+            for reply in group {
+                let _ = reply.send(result.clone());
+            }
+        }
+    }
+}
+
+impl Drop for GroupCommit {
+    fn drop(&mut self) {
+        // the thread answers the pending requests, then exits and releases the store
+        drop(self.requests.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,12 +1,14 @@
 /// A database accessor that does not really care about the value
 #[allow(unused_imports)]
 use crate::{
-    metadata::{HashFieldKey, HashValueMetadata},
+    metadata::{HashFieldKey, HashFieldValue, HashValueMetadata},
     storage::{DbWriteCache, PutFlags},
-    CommonValueMetadata, PrimaryKeyMetadata, SableError, StorageAdapter, U8ArrayBuilder,
+    CommonValueMetadata, PrimaryKeyMetadata, SableError, StorageAdapter, TimeUtils, U8ArrayBuilder,
     U8ArrayReader,
 };
 use bytes::BytesMut;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 // Internal enum
 #[derive(Debug, PartialEq, Eq)]
@@ -52,6 +54,15 @@ pub enum HashGetResult {
     FieldNotFound,
 }
 
+/// `HashDb::get_all` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum HashGetAllResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The fields and their values (empty if the hash does not exist)
+    Some(Vec<(BytesMut, BytesMut)>),
+}
+
 /// `HashDb::delete` result
 #[derive(PartialEq, Eq, Debug)]
 pub enum HashDeleteResult {
@@ -81,6 +92,55 @@ pub enum HashExistsResult {
     NotExists,
 }
 
+/// `HashDb::expire_fields` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum HashExpireResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Per field status: `-2` no such field, `0` the condition was not met, `1` the expiration
+    /// was set and `2` the field was deleted (the expiration time is in the past)
+    Some(Vec<i64>),
+}
+
+/// `HashDb::fields_ttl` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum HashTtlResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Per field TTL in milliseconds: `-2` no such field, `-1` the field has no TTL
+    Some(Vec<i64>),
+}
+
+/// The condition for setting a field expiration (`HEXPIRE` `NX`, `XX`, `GT` and `LT` options)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HashExpireCondition {
+    /// Always set the expiration
+    Always,
+    /// Set the expiration only when the field has no expiration
+    Nx,
+    /// Set the expiration only when the field has an existing expiration
+    Xx,
+    /// Set the expiration only when the new expiration is greater than the current one. A field
+    /// without an expiration is treated as an infinite TTL
+    Gt,
+    /// Set the expiration only when the new expiration is less than the current one. A field
+    /// without an expiration is treated as an infinite TTL
+    Lt,
+}
+
+impl HashExpireCondition {
+    /// Return true if the condition is met for a field that currently expires at `current`
+    fn is_met(&self, current: Option<u64>, expire_at_ms: u64) -> bool {
+        match self {
+            HashExpireCondition::Always => true,
+            HashExpireCondition::Nx => current.is_none(),
+            HashExpireCondition::Xx => current.is_some(),
+            HashExpireCondition::Gt => current.is_some_and(|current| expire_at_ms > current),
+            HashExpireCondition::Lt => current.map_or(true, |current| expire_at_ms < current),
+        }
+    }
+}
+
 enum PutFieldResult {
     /// Ok...
     Inserted,
@@ -134,7 +194,7 @@ impl<'a> HashDb<'a> {
         let mut items_added = 0usize;
         for (key, value) in field_vals {
             // we overide the field's value
-            match self.put_hash_field_value(hash.id(), key, value)? {
+            match self.put_hash_field_value(&mut hash, key, value)? {
                 PutFieldResult::Updated => {}
                 PutFieldResult::Inserted => items_added = items_added.saturating_add(1),
             }
@@ -160,7 +220,7 @@ impl<'a> HashDb<'a> {
         }
 
         // locate the hash
-        let hash = match self.hash_metadata(user_key)? {
+        let mut hash = match self.hash_metadata(user_key)? {
             GetHashMetadataResult::WrongType => {
                 return Ok(HashGetMultiResult::WrongType);
             }
@@ -170,18 +230,23 @@ impl<'a> HashDb<'a> {
             GetHashMetadataResult::Some(hash) => hash,
         };
 
+        let hash_len = hash.len();
         let mut values = Vec::<Option<BytesMut>>::with_capacity(fields.len());
         for field in fields {
-            values.push(self.get_hash_field_value(hash.id(), field)?);
+            values.push(
+                self.get_hash_field_value(&mut hash, field)?
+                    .map(|(value, _)| value),
+            );
         }
 
+        self.commit_expired_fields(user_key, &hash, hash_len)?;
         Ok(HashGetMultiResult::Some(values))
     }
 
     /// Return the value of a hash field
     pub fn get(&self, user_key: &BytesMut, field: &BytesMut) -> Result<HashGetResult, SableError> {
         // locate the hash
-        let hash = match self.hash_metadata(user_key)? {
+        let mut hash = match self.hash_metadata(user_key)? {
             GetHashMetadataResult::WrongType => {
                 return Ok(HashGetResult::WrongType);
             }
@@ -191,7 +256,11 @@ impl<'a> HashDb<'a> {
             GetHashMetadataResult::Some(hash) => hash,
         };
 
-        let Some(value) = self.get_hash_field_value(hash.id(), field)? else {
+        let hash_len = hash.len();
+        let value = self.get_hash_field_value(&mut hash, field)?;
+        self.commit_expired_fields(user_key, &hash, hash_len)?;
+
+        let Some((value, _)) = value else {
             return Ok(HashGetResult::FieldNotFound);
         };
 
@@ -217,8 +286,11 @@ impl<'a> HashDb<'a> {
 
         let mut items_deleted = 0usize;
         for field in fields {
-            if self.contains_hash_field(hash.id(), field)? {
+            if let Some((_, expire_at_ms)) = self.get_hash_field_value(&mut hash, field)? {
                 self.delete_hash_field_key(hash.id(), field)?;
+                if expire_at_ms.is_some() {
+                    hash.decr_volatile_len_by(1);
+                }
                 items_deleted = items_deleted.saturating_add(1);
             }
         }
@@ -258,7 +330,7 @@ impl<'a> HashDb<'a> {
         user_field: &BytesMut,
     ) -> Result<HashExistsResult, SableError> {
         // locate the hash
-        let hash = match self.hash_metadata(user_key)? {
+        let mut hash = match self.hash_metadata(user_key)? {
             GetHashMetadataResult::WrongType => {
                 return Ok(HashExistsResult::WrongType);
             }
@@ -268,17 +340,171 @@ impl<'a> HashDb<'a> {
             GetHashMetadataResult::Some(hash) => hash,
         };
 
-        if self.contains_hash_field(hash.id(), user_field)? {
+        let hash_len = hash.len();
+        let exists = self.get_hash_field_value(&mut hash, user_field)?.is_some();
+        self.commit_expired_fields(user_key, &hash, hash_len)?;
+
+        if exists {
             Ok(HashExistsResult::Exists)
         } else {
             Ok(HashExistsResult::NotExists)
         }
     }
 
+    /// Set an expiration (milliseconds since UNIX_EPOCH) on the `fields` of the hash stored at
+    /// `user_key`. An expiration in the past deletes the field
+    pub fn expire_fields(
+        &self,
+        user_key: &BytesMut,
+        fields: &[&BytesMut],
+        expire_at_ms: u64,
+        condition: HashExpireCondition,
+    ) -> Result<HashExpireResult, SableError> {
+        let mut hash = match self.hash_metadata(user_key)? {
+            GetHashMetadataResult::WrongType => return Ok(HashExpireResult::WrongType),
+            GetHashMetadataResult::NotFound => {
+                return Ok(HashExpireResult::Some(vec![-2; fields.len()]))
+            }
+            GetHashMetadataResult::Some(hash) => hash,
+        };
+
+        let now_ms = TimeUtils::epoch_ms()?;
+        let mut statuses = Vec::<i64>::with_capacity(fields.len());
+        for field in fields {
+            let Some((value, current)) = self.get_hash_field_value(&mut hash, field)? else {
+                statuses.push(-2);
+                continue;
+            };
+
+            if !condition.is_met(current, expire_at_ms) {
+                statuses.push(0);
+                continue;
+            }
+
+            if current.is_some() {
+                hash.decr_volatile_len_by(1);
+            }
+
+            if expire_at_ms <= now_ms {
+                self.delete_hash_field_key(hash.id(), field)?;
+                hash.decr_len_by(1);
+                statuses.push(2);
+            } else {
+                self.upgrade_fields(&mut hash)?;
+                let field_value = HashFieldValue::with_value(&value).with_expiration(expire_at_ms);
+                self.put_hash_field_record(&hash, field, &field_value)?;
+                hash.incr_volatile_len_by(1);
+                statuses.push(1);
+            }
+        }
+
+        if hash.is_empty() {
+            self.delete_hash_metadata(user_key)?;
+        } else {
+            self.put_hash_metadata(user_key, &hash)?;
+        }
+
+        // flush the changes
+        self.flush_cache()?;
+        Ok(HashExpireResult::Some(statuses))
+    }
+
+    /// Return the remaining time to live (in milliseconds) of the `fields` of the hash stored at
+    /// `user_key`
+    pub fn fields_ttl(
+        &self,
+        user_key: &BytesMut,
+        fields: &[&BytesMut],
+    ) -> Result<HashTtlResult, SableError> {
+        let mut hash = match self.hash_metadata(user_key)? {
+            GetHashMetadataResult::WrongType => return Ok(HashTtlResult::WrongType),
+            GetHashMetadataResult::NotFound => {
+                return Ok(HashTtlResult::Some(vec![-2; fields.len()]))
+            }
+            GetHashMetadataResult::Some(hash) => hash,
+        };
+
+        let hash_len = hash.len();
+        let now_ms = TimeUtils::epoch_ms()?;
+        let mut ttls = Vec::<i64>::with_capacity(fields.len());
+        for field in fields {
+            let ttl = match self.get_hash_field_value(&mut hash, field)? {
+                None => -2,
+                Some((_, None)) => -1,
+                Some((_, Some(expire_at_ms))) => expire_at_ms
+                    .saturating_sub(now_ms)
+                    .try_into()
+                    .unwrap_or(i64::MAX),
+            };
+            ttls.push(ttl);
+        }
+
+        self.commit_expired_fields(user_key, &hash, hash_len)?;
+        Ok(HashTtlResult::Some(ttls))
+    }
+
+    /// Delete the expired fields of the hash stored at `user_key` and return its up to date
+    /// metadata. Use this before iterating the hash fields directly from the store
+    pub fn purge_expired_fields(
+        &self,
+        user_key: &BytesMut,
+    ) -> Result<GetHashMetadataResult, SableError> {
+        let mut hash = match self.hash_metadata(user_key)? {
+            GetHashMetadataResult::Some(hash) => hash,
+            other => return Ok(other),
+        };
+
+        if hash.volatile_len() == 0 {
+            return Ok(GetHashMetadataResult::Some(hash));
+        }
+
+        let hash_len = hash.len();
+        for field in self.expired_fields(&hash)? {
+            self.delete_hash_field_key(hash.id(), &field)?;
+            hash.decr_len_by(1);
+            hash.decr_volatile_len_by(1);
+        }
+
+        self.commit_expired_fields(user_key, &hash, hash_len)?;
+        if hash.is_empty() {
+            Ok(GetHashMetadataResult::NotFound)
+        } else {
+            Ok(GetHashMetadataResult::Some(hash))
+        }
+    }
+
+    /// Return all the fields of the hash stored at `user_key`, with their values
+    pub fn get_all(&self, user_key: &BytesMut) -> Result<HashGetAllResult, SableError> {
+        let hash = match self.purge_expired_fields(user_key)? {
+            GetHashMetadataResult::WrongType => return Ok(HashGetAllResult::WrongType),
+            GetHashMetadataResult::NotFound => return Ok(HashGetAllResult::Some(Vec::new())),
+            GetHashMetadataResult::Some(hash) => hash,
+        };
+
+        let fields = Rc::new(RefCell::new(Vec::<(BytesMut, BytesMut)>::new()));
+        let fields_clone = fields.clone();
+        let prefix = Rc::new(hash.prefix());
+        self.store.iterate(
+            prefix,
+            Box::new(move |_prefix, key, value| {
+                if let (Ok(field_key), Ok(field_value)) =
+                    (HashFieldKey::from_bytes(key), hash.decode_field(value))
+                {
+                    fields_clone.borrow_mut().push((
+                        BytesMut::from(field_key.key()),
+                        BytesMut::from(field_value.value()),
+                    ));
+                }
+                true
+            }),
+        )?;
+        let fields = fields.take();
+        Ok(HashGetAllResult::Some(fields))
+    }
+
     /// Load hash value metadata from the store
     pub fn hash_metadata(&self, user_key: &BytesMut) -> Result<GetHashMetadataResult, SableError> {
-        let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
-        let Some(value) = self.cache.get(&encoded_key)? else {
+        let Some(value) = self.cache.get_live(user_key, self.db_id)? else {
             return Ok(GetHashMetadataResult::NotFound);
         };
 
@@ -362,37 +588,136 @@ impl<'a> HashDb<'a> {
         Ok(())
     }
 
-    /// Return the value of hash field
+    /// Return the value of hash field and its expiration. An expired field is deleted (the
+    /// changes to `hash` are committed by the caller) and `None` is returned
     fn get_hash_field_value(
         &self,
-        hash_id: u64,
+        hash: &mut HashValueMetadata,
         user_field: &BytesMut,
-    ) -> Result<Option<BytesMut>, SableError> {
-        let key = self.encode_hash_field_key(hash_id, user_field)?;
-        self.cache.get(&key)
-    }
+    ) -> Result<Option<(BytesMut, Option<u64>)>, SableError> {
+        let key = self.encode_hash_field_key(hash.id(), user_field)?;
+        let Some(raw_value) = self.cache.get(&key)? else {
+            return Ok(None);
+        };
 
-    /// Return the value of hash field
-    fn contains_hash_field(&self, hash_id: u64, user_field: &BytesMut) -> Result<bool, SableError> {
-        let key = self.encode_hash_field_key(hash_id, user_field)?;
-        self.cache.contains(&key)
+        let field_value = hash.decode_field(&raw_value)?;
+        if field_value.is_expired(TimeUtils::epoch_ms()?) {
+            self.cache.delete(&key)?;
+            hash.decr_len_by(1);
+            hash.decr_volatile_len_by(1);
+            return Ok(None);
+        }
+        Ok(Some((
+            BytesMut::from(field_value.value()),
+            field_value.expire_at_ms(),
+        )))
     }
 
-    /// Put the value for a hash field
+    /// Put the value for a hash field. Overriding a field clears its expiration
     fn put_hash_field_value(
         &self,
-        hash_id: u64,
+        hash: &mut HashValueMetadata,
         user_field: &BytesMut,
         user_value: &BytesMut,
     ) -> Result<PutFieldResult, SableError> {
-        let key = self.encode_hash_field_key(hash_id, user_field)?;
-        let updating = self.cache.contains(&key)?;
-        self.cache.put(&key, user_value.clone())?;
-        Ok(if updating {
-            PutFieldResult::Updated
+        let result = match self.get_hash_field_value(hash, user_field)? {
+            Some((_, expire_at_ms)) => {
+                if expire_at_ms.is_some() {
+                    hash.decr_volatile_len_by(1);
+                }
+                PutFieldResult::Updated
+            }
+            None => PutFieldResult::Inserted,
+        };
+        self.put_hash_field_record(hash, user_field, &HashFieldValue::with_value(user_value))?;
+        Ok(result)
+    }
+
+    /// Write the record of a hash field
+    fn put_hash_field_record(
+        &self,
+        hash: &HashValueMetadata,
+        user_field: &BytesMut,
+        field_value: &HashFieldValue,
+    ) -> Result<(), SableError> {
+        let key = self.encode_hash_field_key(hash.id(), user_field)?;
+        let mut buffer = BytesMut::with_capacity(HashFieldValue::SIZE + field_value.value().len());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        hash.encode_field(field_value, &mut builder);
+        self.cache.put(&key, buffer)
+    }
+
+    /// Rewrite the field records of a hash created before fields could have a TTL, so they
+    /// can hold an expiration
+    fn upgrade_fields(&self, hash: &mut HashValueMetadata) -> Result<(), SableError> {
+        if hash.fields_have_expiration() {
+            return Ok(());
+        }
+
+        let fields = Rc::new(RefCell::new(Vec::<(BytesMut, BytesMut)>::new()));
+        let fields_clone = fields.clone();
+        self.store.iterate(
+            Rc::new(hash.prefix()),
+            Box::new(move |_prefix, key, value| {
+                fields_clone
+                    .borrow_mut()
+                    .push((BytesMut::from(key), BytesMut::from(value)));
+                true
+            }),
+        )?;
+
+        hash.set_fields_with_expiration();
+        for (key, value) in fields.take() {
+            let mut buffer = BytesMut::with_capacity(HashFieldValue::SIZE + value.len());
+            let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+            hash.encode_field(&HashFieldValue::with_value(&value), &mut builder);
+            self.cache.put(&key, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Return the names of the expired fields of `hash`
+    fn expired_fields(&self, hash: &HashValueMetadata) -> Result<Vec<BytesMut>, SableError> {
+        let now_ms = TimeUtils::epoch_ms()?;
+        let expired = Rc::new(RefCell::new(Vec::<BytesMut>::new()));
+        let expired_clone = expired.clone();
+        let hash = hash.clone();
+        self.store.iterate(
+            Rc::new(hash.prefix()),
+            Box::new(move |_prefix, key, value| {
+                if let (Ok(field_key), Ok(field_value)) =
+                    (HashFieldKey::from_bytes(key), hash.decode_field(value))
+                {
+                    if field_value.is_expired(now_ms) {
+                        expired_clone
+                            .borrow_mut()
+                            .push(BytesMut::from(field_key.key()));
+                    }
+                }
+                true
+            }),
+        )?;
+        Ok(expired.take())
+    }
+
+    /// Persist the deletion of expired fields found by a read operation. `hash_len` is the hash
+    /// length before the read
+    fn commit_expired_fields(
+        &self,
+        user_key: &BytesMut,
+        hash: &HashValueMetadata,
+        hash_len: u64,
+    ) -> Result<(), SableError> {
+        if hash.len() == hash_len {
+            return Ok(());
+        }
+
+        if hash.is_empty() {
+            self.delete_hash_metadata(user_key)?;
         } else {
-            PutFieldResult::Inserted
-        })
+            self.put_hash_metadata(user_key, hash)?;
+        }
+        self.flush_cache()
     }
 
     /// Given raw bytes (read from the db) return whether it represents a `HashValueMetadata`
@@ -422,7 +747,7 @@ impl<'a> HashDb<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::StorageOpenParams;
+    use crate::{CompressionType, StorageOpenParams};
     use std::path::PathBuf;
 
     fn create_database(db_name: &str) -> StorageAdapter {
@@ -430,7 +755,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/{}.db", db_name));
         let _ = std::fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(true)
+            .set_compression_type(CompressionType::Snappy)
             .set_cache_size(64)
             .set_path(&db_path);
         crate::storage_rocksdb!(open_params.clone())
@@ -546,4 +871,160 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_hash_written_before_field_expiration() -> Result<(), SableError> {
+        let db = create_database("test_hash_written_before_field_expiration");
+        let hash_db = HashDb::with_storage(&db, 0);
+
+        // a hash with 2 fields, written before fields could have a TTL: the metadata ends
+        // after the hash size and the field records are the bare values
+        let hash_name = BytesMut::from("myhash");
+        let field1 = BytesMut::from("field1");
+        let field2 = BytesMut::from("field2");
+        let mut metadata = BytesMut::new();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut metadata);
+        builder.write_u8(crate::metadata::Encoding::VALUE_HASH);
+        crate::Expiration::default().to_bytes(&mut builder);
+        builder.write_u64(42);
+        builder.write_u64(2);
+        db.put(
+            &PrimaryKeyMetadata::new_primary_key(&hash_name, 0),
+            &metadata,
+            PutFlags::Override,
+        )?;
+        for field in [&field1, &field2] {
+            let mut key = BytesMut::new();
+            HashFieldKey::with_user_key(42, field)
+                .to_bytes(&mut U8ArrayBuilder::with_buffer(&mut key));
+            db.put(&key, field, PutFlags::Override)?;
+        }
+
+        assert_eq!(hash_db.len(&hash_name)?, HashLenResult::Some(2));
+        assert_eq!(
+            hash_db.get(&hash_name, &field1)?,
+            HashGetResult::Some(field1.clone())
+        );
+
+        // setting a TTL rewrites the field records so they can hold it
+        let now_ms = TimeUtils::epoch_ms()?;
+        assert_eq!(
+            hash_db.expire_fields(
+                &hash_name,
+                &[&field1],
+                now_ms + 60_000,
+                HashExpireCondition::Always
+            )?,
+            HashExpireResult::Some(vec![1])
+        );
+        let HashTtlResult::Some(ttls) = hash_db.fields_ttl(&hash_name, &[&field1, &field2])? else {
+            panic!("expected a hash");
+        };
+        assert!(ttls[0] > 0 && ttls[0] <= 60_000);
+        assert_eq!(ttls[1], -1);
+        assert_eq!(
+            hash_db.get_all(&hash_name)?,
+            HashGetAllResult::Some(vec![(field1.clone(), field1), (field2.clone(), field2)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_field_expiration() -> Result<(), SableError> {
+        let db = create_database("test_hash_field_expiration");
+        let hash_db = HashDb::with_storage(&db, 0);
+
+        let hash_name = BytesMut::from("myhash");
+        let field1 = BytesMut::from("field1");
+        let field2 = BytesMut::from("field2");
+        let field3 = BytesMut::from("field3");
+        let no_such_field = BytesMut::from("nosuchfield");
+
+        assert_eq!(
+            hash_db.put_multi(
+                &hash_name,
+                &[(&field1, &field1), (&field2, &field2), (&field3, &field3)]
+            )?,
+            HashPutResult::Some(3)
+        );
+
+        let now_ms = TimeUtils::epoch_ms()?;
+        assert_eq!(
+            hash_db.expire_fields(
+                &hash_name,
+                &[&field1, &field2, &no_such_field],
+                now_ms + 60_000,
+                HashExpireCondition::Always
+            )?,
+            HashExpireResult::Some(vec![1, 1, -2])
+        );
+
+        // NX: field1 already has an expiration, field3 does not
+        assert_eq!(
+            hash_db.expire_fields(
+                &hash_name,
+                &[&field1, &field3],
+                now_ms + 60_000,
+                HashExpireCondition::Nx
+            )?,
+            HashExpireResult::Some(vec![0, 1])
+        );
+
+        // GT: a shorter expiration is rejected
+        assert_eq!(
+            hash_db.expire_fields(
+                &hash_name,
+                &[&field1],
+                now_ms + 1000,
+                HashExpireCondition::Gt
+            )?,
+            HashExpireResult::Some(vec![0])
+        );
+
+        let HashTtlResult::Some(ttls) =
+            hash_db.fields_ttl(&hash_name, &[&field1, &no_such_field])?
+        else {
+            panic!("fields_ttl failed");
+        };
+        assert!(ttls[0] > 0 && ttls[0] <= 60_000);
+        assert_eq!(ttls[1], -2);
+
+        // overriding a field clears its expiration
+        hash_db.put_multi(&hash_name, &[(&field3, &field3)])?;
+        assert_eq!(
+            hash_db.fields_ttl(&hash_name, &[&field3])?,
+            HashTtlResult::Some(vec![-1])
+        );
+
+        // an expiration in the past deletes the field
+        assert_eq!(
+            hash_db.expire_fields(
+                &hash_name,
+                &[&field2],
+                now_ms - 1,
+                HashExpireCondition::Always
+            )?,
+            HashExpireResult::Some(vec![2])
+        );
+        assert_eq!(hash_db.len(&hash_name)?, HashLenResult::Some(2));
+
+        // let field1 expire: it is no longer visible
+        hash_db.expire_fields(
+            &hash_name,
+            &[&field1],
+            TimeUtils::epoch_ms()? + 10,
+            HashExpireCondition::Always,
+        )?;
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(
+            hash_db.get_all(&hash_name)?,
+            HashGetAllResult::Some(vec![(field3.clone(), field3.clone())])
+        );
+        assert_eq!(hash_db.len(&hash_name)?, HashLenResult::Some(1));
+        assert_eq!(
+            hash_db.get(&hash_name, &field1)?,
+            HashGetResult::FieldNotFound
+        );
+        Ok(())
+    }
 }
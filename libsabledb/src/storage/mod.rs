@@ -1,24 +1,52 @@
+mod dump_db;
+mod function_db;
 mod generic_db;
+mod group_commit;
 mod hash_db;
+mod set_db;
 mod storage_adapter;
 mod storage_rocksdb;
 mod storage_trait;
+mod stream_db;
 mod string_db;
 mod write_cache;
+mod write_observer;
+mod zset_db;
 
 pub use crate::replication::{StorageUpdates, StorageUpdatesIterItem};
 pub use crate::storage::storage_adapter::{
-    BatchUpdate, PutFlags, StorageAdapter, StorageOpenParams,
+    AppendFsync, BatchUpdate, ChangesIterator, CompressionType, PutFlags, StorageAdapter,
+    StorageChange, StorageOpenParams, UserKeysIterator,
 };
+pub use dump_db::{DumpDb, RestoreResult};
+pub use function_db::FunctionDb;
 pub use generic_db::GenericDb;
+pub use group_commit::CommitReceiver;
 pub use hash_db::{
-    GetHashMetadataResult, HashDb, HashDeleteResult, HashExistsResult, HashGetMultiResult,
-    HashGetResult, HashLenResult, HashPutResult,
+    GetHashMetadataResult, HashDb, HashDeleteResult, HashExistsResult, HashExpireCondition,
+    HashExpireResult, HashGetAllResult, HashGetMultiResult, HashGetResult, HashLenResult,
+    HashPutResult, HashTtlResult,
+};
+pub use set_db::{
+    GetSetMetadataResult, SetDb, SetDeleteResult, SetExistsResult, SetLenResult, SetMembersResult,
+    SetMoveResult, SetPutResult,
 };
 pub use storage_rocksdb::StorageRocksDb;
-pub use storage_trait::{IterateCallback, StorageIterator, StorageTrait};
+pub use storage_trait::{BackupInfo, IterateCallback, StorageIterator, StorageTrait};
+pub use stream_db::{
+    GetStreamMetadataResult, StreamAddResult, StreamDb, StreamDeleteResult, StreamEntry,
+    StreamInfo, StreamInfoResult, StreamLenResult, StreamRangeResult, StreamTrimOptions,
+    StreamTrimResult, StreamTrimThreshold,
+};
 pub use string_db::StringsDb;
 pub use write_cache::DbWriteCache;
+pub use write_observer::{
+    RecordBefore, RecordsRead, WriteEvent, WriteEventType, WriteObserver, WrittenKey,
+};
+pub use zset_db::{
+    GetZSetMetadataResult, ZAddFlags, ZSetAddResult, ZSetDb, ZSetDeleteResult, ZSetLenResult,
+    ZSetRangeResult, ZSetScoreResult,
+};
 
 #[macro_export]
 macro_rules! storage_rocksdb {
@@ -0,0 +1,698 @@
+#[allow(unused_imports)]
+use crate::{
+    metadata::{SetMemberKey, SetValueMetadata},
+    storage::{DbWriteCache, PutFlags},
+    CommonValueMetadata, PrimaryKeyMetadata, SableError, StorageAdapter, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+use rand::Rng;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GetSetMetadataResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// A match was found
+    Some(SetValueMetadata),
+    /// No entry exist
+    NotFound,
+}
+
+/// `SetDb::put_multi` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetPutResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Number of members added (members that already exist are not counted)
+    Some(usize),
+}
+
+/// `SetDb::delete` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetDeleteResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Number of members deleted
+    Some(usize),
+}
+
+/// `SetDb::len` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetLenResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The set size
+    Some(usize),
+}
+
+/// `SetDb::member_exists` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetExistsResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Member exists in the set
+    Exists,
+    /// Member does not exist in the set
+    NotExists,
+}
+
+/// `SetDb::members`, `SetDb::pop` and `SetDb::random_members` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetMembersResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The members
+    Some(Vec<BytesMut>),
+}
+
+/// `SetDb::move_member` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum SetMoveResult {
+    /// An entry exists in the db for the source or the destination key, but for a different type
+    WrongType,
+    /// The member was moved
+    Moved,
+    /// The member does not exist in the source set, nothing was done
+    NotMoved,
+}
+
+/// Set DB wrapper. This class is specialized in reading/writing sets
+/// (commands from the `SADD`, `SCARD` etc family)
+///
+/// Locking strategy: this class does not lock anything and relies on the caller
+/// to obtain the locks if needed
+pub struct SetDb<'a> {
+    store: &'a StorageAdapter,
+    db_id: u16,
+    cache: Box<DbWriteCache<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> SetDb<'a> {
+    pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
+        let cache = Box::new(DbWriteCache::with_storage(store));
+        SetDb {
+            store,
+            db_id,
+            cache,
+        }
+    }
+
+    /// Add the specified members to the set stored at `user_key`
+    pub fn put_multi(
+        &self,
+        user_key: &BytesMut,
+        members: &[&BytesMut],
+    ) -> Result<SetPutResult, SableError> {
+        // locate the set
+        let mut set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetPutResult::WrongType),
+            GetSetMetadataResult::NotFound => SetValueMetadata::with_id(self.store.generate_id()),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        let mut items_added = 0usize;
+        for member in members {
+            let key = self.encode_member_key(set.id(), member);
+            if !self.cache.contains(&key)? {
+                self.cache.put(&key, BytesMut::new())?;
+                items_added = items_added.saturating_add(1);
+            }
+        }
+
+        if items_added > 0 {
+            set.incr_len_by(items_added as u64);
+            self.put_set_metadata(user_key, &set)?;
+        }
+
+        // flush the changes
+        self.flush_cache()?;
+        Ok(SetPutResult::Some(items_added))
+    }
+
+    /// Remove the specified members from the set stored at `user_key`
+    pub fn delete(
+        &self,
+        user_key: &BytesMut,
+        members: &[&BytesMut],
+    ) -> Result<SetDeleteResult, SableError> {
+        // locate the set
+        let mut set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetDeleteResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetDeleteResult::Some(0)),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        let mut items_deleted = 0usize;
+        for member in members {
+            let key = self.encode_member_key(set.id(), member);
+            if self.cache.contains(&key)? {
+                self.cache.delete(&key)?;
+                items_deleted = items_deleted.saturating_add(1);
+            }
+        }
+
+        self.update_set_len_after_delete(user_key, &mut set, items_deleted)?;
+        self.flush_cache()?;
+        Ok(SetDeleteResult::Some(items_deleted))
+    }
+
+    /// Return the size of the set
+    pub fn len(&self, user_key: &BytesMut) -> Result<SetLenResult, SableError> {
+        match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => Ok(SetLenResult::WrongType),
+            GetSetMetadataResult::NotFound => Ok(SetLenResult::Some(0)),
+            GetSetMetadataResult::Some(set) => Ok(SetLenResult::Some(set.len() as usize)),
+        }
+    }
+
+    /// Check whether `member` belongs to the set `user_key`
+    pub fn member_exists(
+        &self,
+        user_key: &BytesMut,
+        member: &BytesMut,
+    ) -> Result<SetExistsResult, SableError> {
+        let set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetExistsResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetExistsResult::NotExists),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        if self.contains_member(&set, member)? {
+            Ok(SetExistsResult::Exists)
+        } else {
+            Ok(SetExistsResult::NotExists)
+        }
+    }
+
+    /// Move `member` from the set `src` to the set `dst`. `dst` is created if it does not exist
+    pub fn move_member(
+        &self,
+        src: &BytesMut,
+        dst: &BytesMut,
+        member: &BytesMut,
+    ) -> Result<SetMoveResult, SableError> {
+        let mut src_set = match self.set_metadata(src)? {
+            GetSetMetadataResult::WrongType => return Ok(SetMoveResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetMoveResult::NotMoved),
+            GetSetMetadataResult::Some(set) => set,
+        };
+        let mut dst_set = match self.set_metadata(dst)? {
+            GetSetMetadataResult::WrongType => return Ok(SetMoveResult::WrongType),
+            GetSetMetadataResult::NotFound => SetValueMetadata::with_id(self.store.generate_id()),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        if !self.contains_member(&src_set, member)? {
+            return Ok(SetMoveResult::NotMoved);
+        }
+
+        if src == dst {
+            return Ok(SetMoveResult::Moved);
+        }
+
+        self.cache
+            .delete(&self.encode_member_key(src_set.id(), member))?;
+        self.update_set_len_after_delete(src, &mut src_set, 1)?;
+
+        if !self.contains_member(&dst_set, member)? {
+            self.cache.put(
+                &self.encode_member_key(dst_set.id(), member),
+                BytesMut::new(),
+            )?;
+            dst_set.incr_len_by(1);
+            self.put_set_metadata(dst, &dst_set)?;
+        }
+
+        self.flush_cache()?;
+        Ok(SetMoveResult::Moved)
+    }
+
+    /// Return the members found in all the sets stored at `user_keys`. A missing key is an empty
+    /// set
+    pub fn intersection(&self, user_keys: &[&BytesMut]) -> Result<SetMembersResult, SableError> {
+        let Some(sets) = self.sets_metadata(user_keys)? else {
+            return Ok(SetMembersResult::WrongType);
+        };
+
+        let Some(mut sets) = sets.into_iter().collect::<Option<Vec<_>>>() else {
+            // intersecting with an empty set
+            return Ok(SetMembersResult::Some(Vec::new()));
+        };
+
+        // iterate over the smallest set and probe the others
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, others)) = sets.split_first() else {
+            return Ok(SetMembersResult::Some(Vec::new()));
+        };
+
+        let mut members = Vec::<BytesMut>::new();
+        'members: for member in self.collect_members(smallest, None)? {
+            for set in others {
+                if !self.contains_member(set, &member)? {
+                    continue 'members;
+                }
+            }
+            members.push(member);
+        }
+        Ok(SetMembersResult::Some(members))
+    }
+
+    /// Return the members found in any of the sets stored at `user_keys`
+    pub fn union(&self, user_keys: &[&BytesMut]) -> Result<SetMembersResult, SableError> {
+        let Some(sets) = self.sets_metadata(user_keys)? else {
+            return Ok(SetMembersResult::WrongType);
+        };
+
+        let mut members = BTreeSet::<BytesMut>::new();
+        for set in sets.iter().flatten() {
+            members.extend(self.collect_members(set, None)?);
+        }
+        Ok(SetMembersResult::Some(members.into_iter().collect()))
+    }
+
+    /// Return the members of the first set stored at `user_keys` that are not found in any of
+    /// the other sets
+    pub fn difference(&self, user_keys: &[&BytesMut]) -> Result<SetMembersResult, SableError> {
+        let Some(sets) = self.sets_metadata(user_keys)? else {
+            return Ok(SetMembersResult::WrongType);
+        };
+
+        let Some((Some(first), others)) = sets.split_first() else {
+            return Ok(SetMembersResult::Some(Vec::new()));
+        };
+
+        let mut members = Vec::<BytesMut>::new();
+        'members: for member in self.collect_members(first, None)? {
+            for set in others.iter().flatten() {
+                if self.contains_member(set, &member)? {
+                    continue 'members;
+                }
+            }
+            members.push(member);
+        }
+        Ok(SetMembersResult::Some(members))
+    }
+
+    /// Replace the value stored at `user_key` (whatever its type) with a new set made of the
+    /// distinct `members`. An empty `members` deletes `user_key`. The items owned by the replaced
+    /// value are left for the caller to delete (see `GenericDb::items_prefix`).
+    /// Return the size of the new set
+    pub fn store(&self, user_key: &BytesMut, members: &[BytesMut]) -> Result<usize, SableError> {
+        if members.is_empty() {
+            let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+            self.cache.delete(&encoded_key)?;
+        } else {
+            let mut set = SetValueMetadata::with_id(self.store.generate_id());
+            for member in members {
+                self.cache
+                    .put(&self.encode_member_key(set.id(), member), BytesMut::new())?;
+            }
+            set.incr_len_by(members.len() as u64);
+            self.put_set_metadata(user_key, &set)?;
+        }
+        self.flush_cache()?;
+        Ok(members.len())
+    }
+
+    /// Return all the members of the set stored at `user_key`
+    pub fn members(&self, user_key: &BytesMut) -> Result<SetMembersResult, SableError> {
+        let set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetMembersResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetMembersResult::Some(Vec::new())),
+            GetSetMetadataResult::Some(set) => set,
+        };
+        Ok(SetMembersResult::Some(self.collect_members(&set, None)?))
+    }
+
+    /// Remove and return `count` random members from the set stored at `user_key`
+    pub fn pop(&self, user_key: &BytesMut, count: usize) -> Result<SetMembersResult, SableError> {
+        let mut set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetMembersResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetMembersResult::Some(Vec::new())),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        let set_len = set.len() as usize;
+        let count = std::cmp::min(count, set_len);
+        let members = if count == set_len {
+            // popping everything
+            self.collect_members(&set, None)?
+        } else {
+            let mut rng = rand::thread_rng();
+            let mut indices = rand::seq::index::sample(&mut rng, set_len, count).into_vec();
+            indices.sort();
+            self.collect_members(&set, Some(indices))?
+        };
+
+        for member in &members {
+            let key = self.encode_member_key(set.id(), member);
+            self.cache.delete(&key)?;
+        }
+
+        self.update_set_len_after_delete(user_key, &mut set, members.len())?;
+        self.flush_cache()?;
+        Ok(SetMembersResult::Some(members))
+    }
+
+    /// Return `count` random members of the set stored at `user_key`, without removing them.
+    /// Unless `allow_dups` is set, the members are distinct and at most the set size are
+    /// returned
+    pub fn random_members(
+        &self,
+        user_key: &BytesMut,
+        count: usize,
+        allow_dups: bool,
+    ) -> Result<SetMembersResult, SableError> {
+        let set = match self.set_metadata(user_key)? {
+            GetSetMetadataResult::WrongType => return Ok(SetMembersResult::WrongType),
+            GetSetMetadataResult::NotFound => return Ok(SetMembersResult::Some(Vec::new())),
+            GetSetMetadataResult::Some(set) => set,
+        };
+
+        let set_len = set.len() as usize;
+        let mut rng = rand::thread_rng();
+        if !allow_dups {
+            let count = std::cmp::min(count, set_len);
+            let mut indices = rand::seq::index::sample(&mut rng, set_len, count).into_vec();
+            indices.sort();
+            return Ok(SetMembersResult::Some(
+                self.collect_members(&set, Some(indices))?,
+            ));
+        }
+
+        // pick the positions (possibly the same one multiple times), then load every distinct
+        // position once
+        let picks: Vec<usize> = (0..count).map(|_| rng.gen_range(0..set_len)).collect();
+        let mut indices = picks.clone();
+        indices.sort();
+        indices.dedup();
+        let members = self.collect_members(&set, Some(indices.clone()))?;
+
+        let mut result = Vec::<BytesMut>::with_capacity(picks.len());
+        for pick in picks {
+            let Ok(pos) = indices.binary_search(&pick) else {
+                continue;
+            };
+            if let Some(member) = members.get(pos) {
+                result.push(member.clone());
+            }
+        }
+        Ok(SetMembersResult::Some(result))
+    }
+
+    /// Load set value metadata from the store. An expired set is reported as `NotFound`
+    pub fn set_metadata(&self, user_key: &BytesMut) -> Result<GetSetMetadataResult, SableError> {
+        let Some(value) = self.cache.get_live(user_key, self.db_id)? else {
+            return Ok(GetSetMetadataResult::NotFound);
+        };
+
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        if !common_md.is_set() {
+            return Ok(GetSetMetadataResult::WrongType);
+        }
+
+        reader.rewind();
+        let set_md = SetValueMetadata::from_bytes(&mut reader)?;
+        Ok(GetSetMetadataResult::Some(set_md))
+    }
+
+    // =======================================================
+    // Internal API for this class
+    // =======================================================
+
+    /// Apply the changes to the store and clear the cache
+    fn flush_cache(&self) -> Result<(), SableError> {
+        let batch = self.cache.to_write_batch();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.cache.clear();
+        self.store.apply_batch(&batch)
+    }
+
+    /// Check whether `member` belongs to `set`
+    fn contains_member(&self, set: &SetValueMetadata, member: &[u8]) -> Result<bool, SableError> {
+        let key = self.encode_member_key(set.id(), member);
+        self.cache.contains(&key)
+    }
+
+    /// Load the metadata of the sets stored at `user_keys`, `None` is used for a missing key.
+    /// Return `None` if any of the keys holds a value of a different type
+    fn sets_metadata(
+        &self,
+        user_keys: &[&BytesMut],
+    ) -> Result<Option<Vec<Option<SetValueMetadata>>>, SableError> {
+        let mut sets = Vec::with_capacity(user_keys.len());
+        for user_key in user_keys {
+            match self.set_metadata(user_key)? {
+                GetSetMetadataResult::WrongType => return Ok(None),
+                GetSetMetadataResult::NotFound => sets.push(None),
+                GetSetMetadataResult::Some(set) => sets.push(Some(set)),
+            }
+        }
+        Ok(Some(sets))
+    }
+
+    /// Iterate over the members of `set`. If `indices` (sorted) is provided, return only the
+    /// members found at these positions
+    fn collect_members(
+        &self,
+        set: &SetValueMetadata,
+        indices: Option<Vec<usize>>,
+    ) -> Result<Vec<BytesMut>, SableError> {
+        let members = Rc::new(RefCell::new(Vec::<BytesMut>::new()));
+        let members_clone = members.clone();
+        let mut wanted = indices.map(|indices| indices.into_iter().peekable());
+        let mut curidx = 0usize;
+        self.store.iterate(
+            Rc::new(set.prefix()),
+            Box::new(move |_prefix, key, _value| {
+                let pick = match wanted.as_mut() {
+                    None => true,
+                    Some(wanted) => {
+                        if wanted.peek().is_none() {
+                            // nothing left to pick
+                            return false;
+                        }
+                        wanted.next_if_eq(&curidx).is_some()
+                    }
+                };
+                curidx = curidx.saturating_add(1);
+                if pick {
+                    if let Ok(member_key) = SetMemberKey::from_bytes(key) {
+                        members_clone
+                            .borrow_mut()
+                            .push(BytesMut::from(member_key.member()));
+                    }
+                }
+                true
+            }),
+        )?;
+        let members = members.take();
+        Ok(members)
+    }
+
+    /// Update the set metadata after `items_deleted` members were removed from it. An empty set
+    /// is removed from the database
+    fn update_set_len_after_delete(
+        &self,
+        user_key: &BytesMut,
+        set: &mut SetValueMetadata,
+        items_deleted: usize,
+    ) -> Result<(), SableError> {
+        if items_deleted == 0 {
+            return Ok(());
+        }
+
+        set.decr_len_by(items_deleted as u64);
+        if set.is_empty() {
+            let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+            self.cache.delete(&encoded_key)?;
+        } else {
+            self.put_set_metadata(user_key, set)?;
+        }
+        Ok(())
+    }
+
+    /// Put a set entry in the database
+    fn put_set_metadata(
+        &self,
+        user_key: &BytesMut,
+        set_md: &SetValueMetadata,
+    ) -> Result<(), SableError> {
+        let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+
+        // serialise the set value into bytes
+        let mut buffer = BytesMut::with_capacity(SetValueMetadata::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        set_md.to_bytes(&mut builder);
+
+        self.cache.put(&encoded_key, buffer)?;
+        Ok(())
+    }
+
+    /// Encode a set member key
+    fn encode_member_key(&self, set_id: u64, member: &[u8]) -> BytesMut {
+        let mut buffer = BytesMut::with_capacity(
+            std::mem::size_of::<u8>() + std::mem::size_of::<u64>() + member.len(),
+        );
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        SetMemberKey::with_member(set_id, member).to_bytes(&mut builder);
+        buffer
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressionType, StorageOpenParams};
+    use std::path::PathBuf;
+
+    fn create_database(db_name: &str) -> StorageAdapter {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from(format!("tests/{}.db", db_name));
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let open_params = StorageOpenParams::default()
+            .set_compression_type(CompressionType::Snappy)
+            .set_cache_size(64)
+            .set_path(&db_path);
+        crate::storage_rocksdb!(open_params.clone())
+    }
+
+    #[test]
+    fn test_set_wrong_type() -> Result<(), SableError> {
+        let db = create_database("test_set_wrong_type");
+        let set_db = SetDb::with_storage(&db, 0);
+        let strings_db = crate::storage::StringsDb::with_storage(&db, 0);
+
+        let key = BytesMut::from("key");
+        let value = BytesMut::from("value");
+        strings_db.put(
+            &key,
+            &value,
+            &crate::StringValueMetadata::default(),
+            PutFlags::Override,
+        )?;
+
+        // run a set operation on a string key
+        assert_eq!(set_db.len(&key)?, SetLenResult::WrongType);
+        assert_eq!(set_db.put_multi(&key, &[&key])?, SetPutResult::WrongType);
+        assert_eq!(set_db.delete(&key, &[&key])?, SetDeleteResult::WrongType);
+        assert_eq!(set_db.members(&key)?, SetMembersResult::WrongType);
+        assert_eq!(set_db.pop(&key, 1)?, SetMembersResult::WrongType);
+        assert_eq!(set_db.intersection(&[&key])?, SetMembersResult::WrongType);
+        assert_eq!(
+            set_db.move_member(&key, &value, &value)?,
+            SetMoveResult::WrongType
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_db() -> Result<(), SableError> {
+        let db = create_database("test_set_db");
+        let set_db = SetDb::with_storage(&db, 0);
+
+        let set_name = BytesMut::from("myset");
+        let a = BytesMut::from("a");
+        let b = BytesMut::from("b");
+        let c = BytesMut::from("c");
+
+        assert_eq!(
+            set_db.put_multi(&set_name, &[&a, &b, &a])?,
+            SetPutResult::Some(2)
+        );
+        assert_eq!(
+            set_db.put_multi(&set_name, &[&b, &c])?,
+            SetPutResult::Some(1)
+        );
+        assert_eq!(set_db.len(&set_name)?, SetLenResult::Some(3));
+        assert_eq!(
+            set_db.members(&set_name)?,
+            SetMembersResult::Some(vec![a.clone(), b.clone(), c.clone()])
+        );
+        assert_eq!(
+            set_db.member_exists(&set_name, &b)?,
+            SetExistsResult::Exists
+        );
+
+        assert_eq!(
+            set_db.delete(&set_name, &[&b, &b])?,
+            SetDeleteResult::Some(1)
+        );
+        assert_eq!(
+            set_db.member_exists(&set_name, &b)?,
+            SetExistsResult::NotExists
+        );
+
+        let SetMembersResult::Some(popped) = set_db.pop(&set_name, 1)? else {
+            panic!("expected a member");
+        };
+        assert_eq!(popped.len(), 1);
+        assert!(popped[0] == a || popped[0] == c);
+        assert_eq!(set_db.len(&set_name)?, SetLenResult::Some(1));
+
+        // popping the last member removes the set
+        let SetMembersResult::Some(popped) = set_db.pop(&set_name, 10)? else {
+            panic!("expected a member");
+        };
+        assert_eq!(popped.len(), 1);
+        assert_eq!(
+            set_db.set_metadata(&set_name)?,
+            GetSetMetadataResult::NotFound
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_random_members() -> Result<(), SableError> {
+        let db = create_database("test_set_random_members");
+        let set_db = SetDb::with_storage(&db, 0);
+
+        let set_name = BytesMut::from("myset");
+        let members: Vec<BytesMut> = (0..10)
+            .map(|i| BytesMut::from(format!("member_{}", i).as_str()))
+            .collect();
+        let members_refs: Vec<&BytesMut> = members.iter().collect();
+        set_db.put_multi(&set_name, &members_refs)?;
+
+        // distinct members, at most the set size
+        let SetMembersResult::Some(picked) = set_db.random_members(&set_name, 20, false)? else {
+            panic!("expected members");
+        };
+        assert_eq!(picked, members);
+
+        let SetMembersResult::Some(picked) = set_db.random_members(&set_name, 5, false)? else {
+            panic!("expected members");
+        };
+        let distinct: BTreeSet<&BytesMut> = picked.iter().collect();
+        assert_eq!(distinct.len(), 5);
+
+        // duplicates are allowed, the count is always honored
+        let SetMembersResult::Some(picked) = set_db.random_members(&set_name, 50, true)? else {
+            panic!("expected members");
+        };
+        assert_eq!(picked.len(), 50);
+        assert!(picked.iter().all(|member| members.contains(member)));
+
+        // the set is not modified
+        assert_eq!(set_db.len(&set_name)?, SetLenResult::Some(10));
+        assert_eq!(
+            set_db.random_members(&BytesMut::from("nosuchset"), 5, true)?,
+            SetMembersResult::Some(Vec::new())
+        );
+        Ok(())
+    }
+}
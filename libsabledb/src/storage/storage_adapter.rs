@@ -1,19 +1,24 @@
 use crate::{
-    replication::StorageUpdates,
-    storage::{storage_trait::StorageIterator, IterateCallback, StorageTrait},
-    utils, StorageRocksDb,
+    replication::{StorageUpdates, StorageUpdatesIterItem},
+    storage::{
+        storage_trait::StorageIterator, BackupInfo, IterateCallback, RecordBefore, StorageTrait,
+        WriteEvent, WriteEventType, WriteObserver,
+    },
+    utils, CommonValueMetadata, PrimaryKeyMetadata, StorageRocksDb, U8ArrayReader,
 };
 
 #[allow(unused_imports)]
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 
 use crate::SableError;
 use bytes::BytesMut;
+use crossbeam::queue::SegQueue;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, RwLock,
 };
 
 #[derive(Debug, Clone)]
@@ -23,6 +28,71 @@ pub enum PutFlags {
     Override,
 }
 
+/// The codec used to compress the data blocks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressionType {
+    None,
+    #[default]
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl CompressionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompressionType::None => "none",
+            CompressionType::Snappy => "snappy",
+            CompressionType::Lz4 => "lz4",
+            CompressionType::Zstd => "zstd",
+        }
+    }
+
+    /// Parse a codec name (case insensitive)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Some(CompressionType::None),
+            "snappy" => Some(CompressionType::Snappy),
+            "lz4" => Some(CompressionType::Lz4),
+            "zstd" => Some(CompressionType::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// When the write-ahead-log is synced to the disk (`appendfsync`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// Sync the WAL as part of every write. The slowest, but no acknowledged write is lost
+    Always,
+    /// Sync the WAL once per second from a background thread. Up to one second of writes can
+    /// be lost on a machine crash
+    EverySec,
+    /// Never sync the WAL explicitly, leave it to the OS
+    #[default]
+    No,
+}
+
+impl AppendFsync {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+    }
+
+    /// Parse a policy name (case insensitive)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "always" => Some(AppendFsync::Always),
+            "everysec" => Some(AppendFsync::EverySec),
+            "no" => Some(AppendFsync::No),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StorageOpenParams {
     pub rocksdb: RocksDbParams,
@@ -52,9 +122,9 @@ pub struct RocksDbParams {
     ///
     /// Default: 3600 seconds
     pub wal_ttl_seconds: usize,
-    /// Enable data compression
-    /// Default: true
-    pub compression_enabled: bool,
+    /// The data compression codec
+    /// Default: Snappy
+    pub compression: CompressionType,
     /// If true, writes will not first go to the write ahead log,
     /// and the write may get lost after a crash. The backup engine
     /// relies on write-ahead logs to back up the memtable.
@@ -64,12 +134,34 @@ pub struct RocksDbParams {
     pub manual_wal_flush: bool,
     /// If `manual_wal_flush` is enabled, flush it every `manual_wal_flush_interval_ms` mislliseconds
     pub manual_wal_flush_interval_ms: usize,
+    /// When the WAL is synced to the disk. Ignored when `disable_wal` is set
+    /// Default: No
+    pub appendfsync: AppendFsync,
     /// Sets the number of open files that can be used by the DB. You may need to
     /// increase this if your database has a large working set. Value `-1` means
     /// files opened are always kept open. You can estimate number of files based
     /// on target_file_size_base and target_file_size_multiplier for level-based
     /// compaction. For universal-style compaction, you can usually set it to `-1`.
     pub max_open_files: isize,
+    /// The size of the LRU cache that keeps the uncompressed data blocks in memory, shared
+    /// by all the reads. `0` keeps the RocksDB default cache
+    /// Default: 128MB
+    pub block_cache_size: usize,
+    /// Build a bloom filter with `bloom_filter_bits_per_key` bits per key for every table file.
+    /// Point lookups of keys that do not exist skip the files whose filter rules the key out
+    /// (with 10 bits per key, about 1% of the lookups are not filtered). `0` disables the filter
+    /// Default: 10
+    pub bloom_filter_bits_per_key: usize,
+    /// Keep the index and the filter blocks in the block cache, so their memory is bounded by
+    /// `block_cache_size`, instead of keeping them outside of the cache for every open file
+    /// Default: false
+    pub cache_index_and_filter_blocks: bool,
+    /// With `appendfsync always`, when not `0`, the WAL is synced once for all the writes
+    /// arriving within `group_commit_interval_micros` microseconds, instead of once per write.
+    /// The replies to the writes are held until their group is synced. This trades some write
+    /// latency for a higher write throughput when many clients write concurrently
+    /// Default: 0
+    pub group_commit_interval_micros: usize,
 }
 
 impl Default for StorageOpenParams {
@@ -80,11 +172,16 @@ impl Default for StorageOpenParams {
                 max_write_buffer_number: 4,
                 write_buffer_size: 256usize.saturating_mul(1024).saturating_mul(1024),
                 wal_ttl_seconds: 3600,
-                compression_enabled: true,
+                compression: CompressionType::Snappy,
                 disable_wal: false,
                 manual_wal_flush: false,
                 manual_wal_flush_interval_ms: 500,
+                appendfsync: AppendFsync::No,
                 max_open_files: -1,
+                block_cache_size: 128usize.saturating_mul(1024).saturating_mul(1024),
+                bloom_filter_bits_per_key: 10,
+                cache_index_and_filter_blocks: false,
+                group_commit_interval_micros: 0,
             },
             db_path: PathBuf::from("sabledb.db"),
         }
@@ -99,9 +196,19 @@ lazy_static::lazy_static! {
 }
 
 impl StorageOpenParams {
-    /// Enable compression
-    pub fn set_compression(mut self, enable_compression: bool) -> Self {
-        self.rocksdb.compression_enabled = enable_compression;
+    /// Enable (Snappy) or disable compression
+    #[deprecated(note = "use `set_compression_type`")]
+    pub fn set_compression(self, enable_compression: bool) -> Self {
+        self.set_compression_type(if enable_compression {
+            CompressionType::Snappy
+        } else {
+            CompressionType::None
+        })
+    }
+
+    /// Set the data compression codec
+    pub fn set_compression_type(mut self, compression: CompressionType) -> Self {
+        self.rocksdb.compression = compression;
         self
     }
 
@@ -118,11 +225,31 @@ impl StorageOpenParams {
         self
     }
 
+    /// Set when the WAL is synced to the disk
+    pub fn set_appendfsync(mut self, appendfsync: AppendFsync) -> Self {
+        self.rocksdb.appendfsync = appendfsync;
+        self
+    }
+
     /// Set the cache size
     pub fn set_cache_size(mut self, cache_size: usize) -> Self {
         self.rocksdb.write_buffer_size = cache_size;
         self
     }
+
+    /// With `appendfsync always`, sync the WAL once for all the writes arriving within
+    /// `interval_micros` microseconds. `0` disables the group commit
+    pub fn set_group_commit_interval_micros(mut self, interval_micros: usize) -> Self {
+        self.rocksdb.group_commit_interval_micros = interval_micros;
+        self
+    }
+
+    /// Are the WAL syncs grouped? The group commit only applies when every write is synced
+    pub fn group_commit_enabled(&self) -> bool {
+        self.rocksdb.group_commit_interval_micros > 0
+            && self.rocksdb.appendfsync == AppendFsync::Always
+            && !self.rocksdb.disable_wal
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -131,6 +258,9 @@ pub struct BatchUpdate {
     delete_keys: Option<Vec<BytesMut>>,
     /// List of keys to put
     put_keys: Option<Vec<(BytesMut, BytesMut)>>,
+    /// The state of the primary records before this batch, as reported by the writer that read
+    /// them. The records not listed here are looked up for the write observers
+    records_before: Option<HashMap<BytesMut, RecordBefore>>,
 }
 
 impl BatchUpdate {
@@ -140,9 +270,24 @@ impl BatchUpdate {
         BatchUpdate {
             delete_keys,
             put_keys,
+            records_before: None,
         }
     }
 
+    /// Report the state of the primary record `key` before this batch, as read by the caller
+    pub fn set_record_before(&mut self, key: &[u8], before: RecordBefore) {
+        self.records_before
+            .get_or_insert_with(HashMap::new)
+            .insert(BytesMut::from(key), before);
+    }
+
+    /// The state of `key` before this batch, if reported by the caller
+    pub fn record_before(&self, key: &[u8]) -> Option<RecordBefore> {
+        self.records_before
+            .as_ref()
+            .and_then(|records| records.get(key).copied())
+    }
+
     pub fn put(&mut self, key: BytesMut, value: BytesMut) {
         if self.put_keys.is_none() {
             self.put_keys = Some(Vec::<(BytesMut, BytesMut)>::new());
@@ -203,6 +348,109 @@ impl BatchUpdate {
     pub fn clear(&mut self) {
         self.put_keys = None;
         self.delete_keys = None;
+        self.records_before = None;
+    }
+}
+
+/// Iterates over the records of a single database, yielding the decoded user key
+/// and the common value metadata of every record
+pub struct UserKeysIterator<'a> {
+    iterator: StorageIterator<'a>,
+    prefix: BytesMut,
+}
+
+impl<'a> UserKeysIterator<'a> {
+    fn decode(key: &[u8], value: &[u8]) -> Result<(BytesMut, CommonValueMetadata), SableError> {
+        let (_, user_key) = PrimaryKeyMetadata::from_raw(&BytesMut::from(key))?;
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let md = CommonValueMetadata::from_bytes(&mut reader)?;
+        Ok((user_key, md))
+    }
+}
+
+impl<'a> Iterator for UserKeysIterator<'a> {
+    type Item = Result<(BytesMut, CommonValueMetadata), SableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.iterator {
+            StorageIterator::RocksDb(rocksdb_iter) => {
+                if !rocksdb_iter.valid() {
+                    return None;
+                }
+
+                let key = rocksdb_iter.key()?;
+                if !key.starts_with(&self.prefix) {
+                    return None;
+                }
+
+                let item = Self::decode(key, rocksdb_iter.value()?);
+                rocksdb_iter.next();
+                Some(item)
+            }
+        }
+    }
+}
+
+/// The number of write batches `ChangesIterator` reads from the WAL at a time
+const CHANGES_BATCHES_PER_FETCH: usize = 100;
+
+/// A single change read back from the write-ahead-log
+#[derive(Debug, Clone)]
+pub struct StorageChange {
+    /// The sequence number of the write batch that made the change. All the changes of a batch
+    /// share the same sequence number
+    pub seq: u64,
+    pub change: StorageUpdatesIterItem,
+}
+
+/// Iterates over the changes made after a given sequence number, in the order they were
+/// applied. The WAL is read lazily, `CHANGES_BATCHES_PER_FETCH` write batches at a time. The
+/// iteration ends once the latest change is reached
+pub struct ChangesIterator<'a> {
+    store: &'a StorageAdapter,
+    /// Batches with a sequence number up to `last_seq` were already returned
+    last_seq: u64,
+    pending: VecDeque<StorageChange>,
+}
+
+impl<'a> ChangesIterator<'a> {
+    /// Read the next write batches from the WAL into `pending`
+    fn fetch(&mut self) -> Result<(), SableError> {
+        let batches = self
+            .store
+            .storage_batches_since(self.last_seq, CHANGES_BATCHES_PER_FETCH)?;
+        for batch in batches {
+            let seq = batch.end_seq_number;
+            if seq <= self.last_seq {
+                // the WAL starts from the batch that contains `last_seq`
+                continue;
+            }
+            let mut reader = U8ArrayReader::with_buffer(&batch.serialised_data);
+            while let Some(change) = batch.next(&mut reader) {
+                self.pending.push_back(StorageChange { seq, change });
+            }
+            self.last_seq = seq;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for ChangesIterator<'a> {
+    type Item = Result<StorageChange, SableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // a write batch may carry no changes, keep reading until a change is found
+        while self.pending.is_empty() {
+            let last_seq = self.last_seq;
+            if let Err(e) = self.fetch() {
+                return Some(Err(e));
+            }
+            if self.last_seq == last_seq {
+                // caught up
+                return None;
+            }
+        }
+        self.pending.pop_front().map(Ok)
     }
 }
 
@@ -210,6 +458,13 @@ impl BatchUpdate {
 pub struct StorageAdapter {
     store: Option<Arc<dyn StorageTrait>>,
     open_params: StorageOpenParams,
+    /// When set, the storage is fed by a primary. Shared between all the clones of this adapter
+    replica_mode: Arc<AtomicBool>,
+    /// Notified after every committed write. Shared between all the clones of this adapter
+    write_observers: Arc<RwLock<Vec<Arc<dyn WriteObserver>>>>,
+    /// Items prefixes of deleted values, waiting for the `Reclaimer`. Shared between all the
+    /// clones of this adapter
+    reclaim_queue: Arc<SegQueue<BytesMut>>,
 }
 
 /// We use an adapter to hide all `RocksDb` details and (maybe)
@@ -235,6 +490,112 @@ impl StorageAdapter {
         &self.open_params
     }
 
+    /// Mark the storage as being fed by a primary (or not). A replica never removes expired
+    /// records on its own: it hides them from readers until the primary's deletion arrives
+    pub fn set_replica_mode(&self, replica_mode: bool) {
+        self.replica_mode.store(replica_mode, Ordering::Relaxed);
+    }
+
+    /// Is the storage being fed by a primary?
+    pub fn is_replica_mode(&self) -> bool {
+        self.replica_mode.load(Ordering::Relaxed)
+    }
+
+    /// Queue the items (hash fields, list items etc) found under `items_prefix` for deletion by
+    /// the `Reclaimer`. The value owning them must already be deleted
+    pub fn enqueue_reclaim(&self, items_prefix: BytesMut) {
+        self.reclaim_queue.push(items_prefix);
+    }
+
+    /// The queue drained by the `Reclaimer`. The server shares it with its state (see
+    /// `ServerState::set_reclaim_queue`)
+    pub fn reclaim_queue(&self) -> Arc<SegQueue<BytesMut>> {
+        self.reclaim_queue.clone()
+    }
+
+    /// Register `observer` to be notified after every write committed through this adapter
+    /// (including the writes applied by the replication)
+    pub fn add_write_observer(&self, observer: Arc<dyn WriteObserver>) {
+        if let Ok(mut observers) = self.write_observers.write() {
+            observers.push(observer);
+        }
+    }
+
+    fn has_write_observers(&self) -> bool {
+        self.write_observers
+            .read()
+            .map(|observers| !observers.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Before writing `key`, load the record it replaces so the observers can tell a new key
+    /// from an update (and a key that gains or loses a TTL). Only primary records are looked up,
+    /// only when the writes are observed and only when the writer did not report the record it
+    /// read (see `RecordsRead`)
+    fn record_before_write(&self, key: &[u8]) -> Result<RecordBefore, SableError> {
+        if !WriteEvent::is_primary_key(key) || !self.has_write_observers() {
+            return Ok(RecordBefore::default());
+        }
+        let value = self.get(&BytesMut::from(key))?;
+        Ok(RecordBefore::with_value(value.as_deref()))
+    }
+
+    /// Is any of the observers tracking the record sizes?
+    fn tracks_record_sizes(&self) -> bool {
+        self.write_observers
+            .read()
+            .map(|observers| {
+                observers
+                    .iter()
+                    .any(|observer| observer.tracks_record_sizes())
+            })
+            .unwrap_or(false)
+    }
+
+    /// Before writing `key`, look up the record it replaces: the state of the primary record
+    /// and, while an observer tracks the record sizes, the size of the record (`0` if there is
+    /// none). The state reported by the caller (`known`) is used as is, the record is only
+    /// looked up if its size is needed
+    fn lookup_before_write(
+        &self,
+        key: &[u8],
+        known: Option<RecordBefore>,
+    ) -> Result<(RecordBefore, usize), SableError> {
+        if !self.tracks_record_sizes() {
+            let before = match known {
+                Some(before) => before,
+                None => self.record_before_write(key)?,
+            };
+            return Ok((before, 0));
+        }
+        let value = self.get(&BytesMut::from(key))?;
+        let size = value
+            .as_ref()
+            .map(|value| key.len().saturating_add(value.len()));
+        let before = if WriteEvent::is_primary_key(key) {
+            RecordBefore::with_value(value.as_deref())
+        } else {
+            RecordBefore::default()
+        };
+        Ok((before, size.unwrap_or_default()))
+    }
+
+    /// Dispatch the event built by `build_event` to all the registered observers.
+    /// The event is not built when there are no observers
+    fn notify_write(&self, build_event: impl FnOnce() -> WriteEvent) {
+        let Ok(observers) = self.write_observers.read() else {
+            return;
+        };
+        if observers.is_empty() {
+            return;
+        }
+
+        let event = build_event();
+        for observer in observers.iter() {
+            observer.on_write(&event);
+        }
+    }
+
     /// build the database path
     pub fn database_path(dbpath: &Path) -> PathBuf {
         let name = if dbpath.to_string_lossy().is_empty() {
@@ -259,11 +620,32 @@ impl StorageAdapter {
         key: &BytesMut,
         value: &BytesMut,
         put_flags: PutFlags,
+    ) -> Result<(), SableError> {
+        self.put_with_before(key, value, put_flags, None)
+    }
+
+    /// Like `put`, for a caller that read the record it replaces: `before` is the state it read
+    /// (see `RecordsRead`), `None` if it did not read it
+    pub fn put_with_before(
+        &self,
+        key: &BytesMut,
+        value: &BytesMut,
+        put_flags: PutFlags,
+        before: Option<RecordBefore>,
     ) -> Result<(), SableError> {
         let Some(db) = &self.store else {
             return Err(SableError::OtherError("Database is not opened".to_string()));
         };
-        db.put(key, value, put_flags)?;
+        let (before, released) = self.lookup_before_write(key, before)?;
+        if db.put(key, value, put_flags)? {
+            self.notify_write(|| {
+                let mut event = WriteEvent::default();
+                event.add_record(key, WriteEventType::Put, before, Some(&value[..]));
+                event.bytes_written = key.len().saturating_add(value.len());
+                event.bytes_released = released;
+                event
+            });
+        }
         Ok(())
     }
 
@@ -277,10 +659,44 @@ impl StorageAdapter {
 
     /// Similar to delete, but with no app locking
     pub fn delete(&self, key: &BytesMut) -> Result<(), SableError> {
+        self.delete_with_before(key, None)
+    }
+
+    /// Like `delete`, for a caller that read the record it deletes: `before` is the state it
+    /// read (see `RecordsRead`), `None` if it did not read it
+    pub fn delete_with_before(
+        &self,
+        key: &BytesMut,
+        before: Option<RecordBefore>,
+    ) -> Result<(), SableError> {
         let Some(db) = &self.store else {
             return Err(SableError::OtherError("Database is not opened".to_string()));
         };
+        let (before, released) = self.lookup_before_write(key, before)?;
         db.delete(key)?;
+        self.notify_write(|| {
+            let mut event = WriteEvent::default();
+            event.add_record(key, WriteEventType::Delete, before, None);
+            event.bytes_released = released;
+            event
+        });
+        Ok(())
+    }
+
+    /// Delete all the records starting with `prefix` with a single range deletion. The deleted
+    /// records are not reported one by one to the write observers, they are told that the whole
+    /// range was deleted instead. The deletion is replicated as a single change, see
+    /// `StorageUpdatesIterItem::DelRange`
+    pub fn delete_range(&self, prefix: &BytesMut) -> Result<(), SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.delete_range(prefix)?;
+        if let Ok(observers) = self.write_observers.read() {
+            for observer in observers.iter() {
+                observer.on_range_deleted(prefix);
+            }
+        }
         Ok(())
     }
 
@@ -295,7 +711,81 @@ impl StorageAdapter {
         let Some(db) = &self.store else {
             return Err(SableError::OtherError("Database is not opened".to_string()));
         };
+        // the deletes are applied before the puts. Track the records state through the batch,
+        // so a key that is deleted and put again by the same batch is reported as updated. The
+        // state of a record is its size (`0` when the sizes are not tracked) and whether it has
+        // a TTL, `None` if it does not exist. The state reported by the caller is used when the
+        // sizes are not needed
+        let mut before = Vec::<RecordBefore>::new();
+        let mut released = 0usize;
+        if self.has_write_observers() {
+            let track_sizes = self.tracks_record_sizes();
+            let mut state = HashMap::<&[u8], Option<(usize, bool)>>::new();
+            let deletes = update.keys_to_delete().into_iter().flatten();
+            let puts = update.items_to_put().into_iter().flatten();
+            for (key, value) in deletes
+                .map(|key| (key, None))
+                .chain(puts.map(|(key, value)| (key, Some(value))))
+            {
+                let is_primary_key = WriteEvent::is_primary_key(key);
+                let state_before = match state.get(key.as_ref()) {
+                    Some(record) => *record,
+                    None if track_sizes => self.get(key)?.map(|value| {
+                        (
+                            key.len().saturating_add(value.len()),
+                            is_primary_key && WriteEvent::value_has_ttl(&value),
+                        )
+                    }),
+                    None => {
+                        let record = match update.record_before(key) {
+                            Some(record) => record,
+                            None => self.record_before_write(key)?,
+                        };
+                        record.existed.then_some((0, record.had_ttl))
+                    }
+                };
+                before.push(match state_before {
+                    Some((_, had_ttl)) if is_primary_key => RecordBefore {
+                        existed: true,
+                        had_ttl,
+                    },
+                    _ => RecordBefore::default(),
+                });
+                released =
+                    released.saturating_add(state_before.map(|(size, _)| size).unwrap_or_default());
+                let state_after = value.map(|value| {
+                    let size = key.len().saturating_add(value.len());
+                    (
+                        if track_sizes { size } else { 0 },
+                        is_primary_key && WriteEvent::value_has_ttl(value),
+                    )
+                });
+                state.insert(key.as_ref(), state_after);
+            }
+        }
+
         db.apply_batch(update)?;
+        self.notify_write(|| {
+            let mut event = WriteEvent::default();
+            let mut before = before.into_iter();
+            if let Some(keys) = update.keys_to_delete() {
+                for key in keys.iter() {
+                    let key_before = before.next().unwrap_or_default();
+                    event.add_record(key, WriteEventType::Delete, key_before, None);
+                }
+            }
+            if let Some(put_keys) = update.items_to_put() {
+                for (key, value) in put_keys.iter() {
+                    let key_before = before.next().unwrap_or_default();
+                    event.add_record(key, WriteEventType::Put, key_before, Some(&value[..]));
+                    event.bytes_written = event
+                        .bytes_written
+                        .saturating_add(key.len().saturating_add(value.len()));
+                }
+            }
+            event.bytes_released = released;
+            event
+        });
         Ok(())
     }
 
@@ -306,6 +796,27 @@ impl StorageAdapter {
         db.create_checkpoint(location)
     }
 
+    /// Create a new backup under `location` (a directory) and delete the oldest backups, so
+    /// only the `keep_n` most recent backups remain (`BGSAVE`)
+    pub fn create_backup_retaining(
+        &self,
+        location: &Path,
+        keep_n: usize,
+    ) -> Result<BackupInfo, SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.create_backup_retaining(location, keep_n)
+    }
+
+    /// Return the backups found under `location`, oldest first
+    pub fn list_backups(&self, location: &Path) -> Result<Vec<BackupInfo>, SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.list_backups(location)
+    }
+
     /// Restore database from a backup database located at `backup_location` (a directory)
     /// If `delete_all_before_store` is true, we will purge all current records from the
     /// db before starting the restore
@@ -317,7 +828,13 @@ impl StorageAdapter {
         let Some(db) = &self.store else {
             return Err(SableError::OtherError("Database is not opened".to_string()));
         };
-        db.restore_from_checkpoint(backup_location, delete_all_before_store)
+        db.restore_from_checkpoint(backup_location, delete_all_before_store)?;
+        if let Ok(observers) = self.write_observers.read() {
+            for observer in observers.iter() {
+                observer.on_reset();
+            }
+        }
+        Ok(())
     }
 
     /// Manually flush any journal to the disk
@@ -351,6 +868,36 @@ impl StorageAdapter {
         Ok(())
     }
 
+    /// Wait until the writes applied so far are durable. Only the group commit defers the sync of
+    /// a write, otherwise there is nothing to wait for. The caller is suspended, not blocked: the
+    /// WAL is synced by the group commit thread
+    pub async fn commit(&self) -> Result<(), SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        let Some(commit) = db.request_commit() else {
+            return Ok(());
+        };
+        match commit.await {
+            Ok(result) => result.map_err(SableError::OtherError),
+            Err(_) => Err(SableError::OtherError(
+                "Group commit thread has exited".to_string(),
+            )),
+        }
+    }
+
+    /// Sync the WAL to the disk (used by the `appendfsync everysec` policy)
+    pub fn sync_wal(&self) -> Result<(), SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+
+        if self.open_params.rocksdb.disable_wal {
+            return Ok(());
+        }
+        db.sync_wal()
+    }
+
     /// Return all changes since the requested `sequence_number`
     /// If not `None`, `memory_limit` sets the limit for the
     /// memory (in bytes) that a single change since message can
@@ -367,6 +914,66 @@ impl StorageAdapter {
         db.storage_updates_since(sequence_number, memory_limit, changes_count_limit)
     }
 
+    /// Return the write batches applied since `sequence_number`, one `StorageUpdates` per batch
+    pub fn storage_batches_since(
+        &self,
+        sequence_number: u64,
+        batches_limit: usize,
+    ) -> Result<Vec<StorageUpdates>, SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.storage_batches_since(sequence_number, batches_limit)
+    }
+
+    /// Return the changes made after the write batch with sequence number `seq`, decoded and in
+    /// the order they were applied. This is the change-data-capture API: unlike the replication,
+    /// which ships the raw updates, a consumer gets the individual puts and deletes.
+    ///
+    /// The delivery is at-least-once: a consumer should persist the `seq` of the last change it
+    /// processed and resume from it. A consumer that fails before doing so reads the changes
+    /// again. The changes are available as long as their WAL files are kept (`wal_ttl_seconds`)
+    pub fn changes_since(&self, seq: u64) -> Result<ChangesIterator<'_>, SableError> {
+        if self.store.is_none() {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        }
+        Ok(ChangesIterator {
+            store: self,
+            last_seq: seq,
+            pending: VecDeque::default(),
+        })
+    }
+
+    /// Return the sequence number of the last write applied to the database
+    pub fn latest_sequence_number(&self) -> Result<u64, SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.latest_sequence_number()
+    }
+
+    /// Compact the records starting with `prefix`, or all the records when `prefix` is `None`
+    pub fn compact(&self, prefix: Option<&BytesMut>) -> Result<(), SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        match prefix {
+            None => db.compact_range(None, None),
+            Some(prefix) => {
+                let end = StorageRocksDb::prefix_successor(prefix);
+                db.compact_range(Some(prefix), end.as_deref())
+            }
+        }
+    }
+
+    /// Return the storage engine statistics as `(name, value)`
+    pub fn stats(&self) -> Result<Vec<(String, u64)>, SableError> {
+        let Some(db) = &self.store else {
+            return Err(SableError::OtherError("Database is not opened".to_string()));
+        };
+        db.stats()
+    }
+
     /// Iterate on all items starting with `prefix` and apply `callback` on them
     pub fn iterate(
         &self,
@@ -385,6 +992,38 @@ impl StorageAdapter {
         };
         db.create_iterator(prefix)
     }
+
+    /// Iterate over the user keys of database `db_id`. If `start_from` is provided, the iteration
+    /// starts from that user key (or from the key that follows it, if it does not exist)
+    pub fn user_keys_iterator(
+        &self,
+        db_id: u16,
+        start_from: Option<&BytesMut>,
+    ) -> Result<UserKeysIterator<'_>, SableError> {
+        let prefix = PrimaryKeyMetadata::primary_key_prefix(db_id);
+        let seek_key = match start_from {
+            Some(user_key) => PrimaryKeyMetadata::new_primary_key(user_key, db_id),
+            None => prefix.clone(),
+        };
+        Ok(UserKeysIterator {
+            iterator: self.create_iterator(Rc::new(seek_key))?,
+            prefix,
+        })
+    }
+
+    /// Iterate over the user keys of database `db_id`, starting from the first key that belongs
+    /// to `slot` (or to the slot that follows it, if `slot` has no keys)
+    pub fn user_keys_iterator_from_slot(
+        &self,
+        db_id: u16,
+        slot: u16,
+    ) -> Result<UserKeysIterator<'_>, SableError> {
+        let seek_key = PrimaryKeyMetadata::primary_key_slot_prefix(db_id, slot);
+        Ok(UserKeysIterator {
+            iterator: self.create_iterator(Rc::new(seek_key))?,
+            prefix: PrimaryKeyMetadata::primary_key_prefix(db_id),
+        })
+    }
 }
 
 #[allow(unsafe_code)]
@@ -401,6 +1040,7 @@ unsafe impl Send for StorageAdapter {}
 mod tests {
     use super::*;
     use crate::storage::storage_trait::StorageIterator;
+    use crate::storage::WrittenKey;
     #[allow(unused_imports)]
     use crate::BytesMutUtils;
     use std::cell::RefCell;
@@ -414,7 +1054,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/{}.db", name));
         let _ = fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
@@ -430,7 +1070,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/test_persistency_{}.db", engine));
         let _ = fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
@@ -524,13 +1164,61 @@ mod tests {
         assert_eq!(matches, 2);
     }
 
+    #[test]
+    fn test_user_keys_iterator() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let mut expected = std::collections::HashSet::<BytesMut>::new();
+        for i in 0..100 {
+            let key = BytesMut::from(format!("key_{}", i).as_str());
+            let value = BytesMut::from("value");
+            crate::storage::StringsDb::with_storage(&store, 0).put(
+                &key,
+                &value,
+                &crate::StringValueMetadata::new(),
+                PutFlags::Override,
+            )?;
+            expected.insert(key);
+
+            // keys of another database are not visited
+            let key = BytesMut::from(format!("other_db_key_{}", i).as_str());
+            crate::storage::StringsDb::with_storage(&store, 1).put(
+                &key,
+                &value,
+                &crate::StringValueMetadata::new(),
+                PutFlags::Override,
+            )?;
+        }
+
+        let mut visited = Vec::<BytesMut>::new();
+        for item in store.user_keys_iterator(0, None)? {
+            let (user_key, md) = item?;
+            assert!(md.is_string());
+            visited.push(user_key);
+        }
+        assert_eq!(visited.len(), expected.len());
+        assert_eq!(
+            visited
+                .iter()
+                .cloned()
+                .collect::<std::collections::HashSet<BytesMut>>(),
+            expected
+        );
+
+        // start the iteration from the middle
+        let mut iter = store.user_keys_iterator(0, Some(&visited[50]))?;
+        let (user_key, _) = iter.next().unwrap()?;
+        assert_eq!(user_key, visited[50]);
+        assert_eq!(iter.count(), 49);
+        Ok(())
+    }
+
     #[test]
     fn test_prefix_iteration() -> Result<(), SableError> {
         let _ = std::fs::create_dir_all("tests");
         let db_path = PathBuf::from("tests/test_prefix.db");
         let _ = fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
@@ -580,4 +1268,150 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_write_observer() -> Result<(), SableError> {
+        let (_guard, store) = crate::tests::open_store();
+        let collector = Arc::new(crate::tests::WriteEventsCollector::default());
+        // observers are shared between clones of the adapter
+        store.clone().add_write_observer(collector.clone());
+
+        let key = BytesMut::from("key");
+        let internal_key = PrimaryKeyMetadata::new_primary_key(&key, 1);
+        let value = BytesMut::from("value");
+        store.put(&internal_key, &value, PutFlags::Override)?;
+        // nothing is written here
+        store.put(&internal_key, &value, PutFlags::PutIfNotExists)?;
+        store.delete(&internal_key)?;
+
+        let mut batch = BatchUpdate::default();
+        batch.put(internal_key.clone(), value.clone());
+        batch.put(
+            PrimaryKeyMetadata::new_primary_key(&BytesMut::from("key2"), 0),
+            value.clone(),
+        );
+        // not a primary key (e.g. a hash field)
+        batch.put(BytesMut::from("\x02hash_item"), value.clone());
+        store.apply_batch(&batch)?;
+
+        let events = collector.events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+
+        let put_event = WrittenKey {
+            db_id: 1,
+            user_key: key.clone(),
+            event_type: WriteEventType::Put,
+            existed: false,
+            had_ttl: false,
+            has_ttl: false,
+        };
+        assert_eq!(events[0].records, 1);
+        assert_eq!(events[0].keys, vec![put_event.clone()]);
+
+        assert_eq!(events[1].records, 1);
+        assert_eq!(
+            events[1].keys,
+            vec![WrittenKey {
+                db_id: 1,
+                user_key: key.clone(),
+                event_type: WriteEventType::Delete,
+                existed: true,
+                had_ttl: false,
+                has_ttl: false,
+            }]
+        );
+
+        assert_eq!(events[2].records, 3);
+        assert_eq!(
+            events[2].keys,
+            vec![
+                put_event,
+                WrittenKey {
+                    db_id: 0,
+                    user_key: BytesMut::from("key2"),
+                    event_type: WriteEventType::Put,
+                    existed: false,
+                    had_ttl: false,
+                    has_ttl: false,
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_since() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from("tests/test_changes_since.db");
+        let _ = fs::remove_dir_all(db_path.clone());
+        // the changes are read from the WAL
+        let open_params = StorageOpenParams::default()
+            .set_cache_size(64)
+            .set_path(&db_path);
+        let mut store = StorageAdapter::default();
+        store.open(open_params)?;
+
+        // every batch replaces the key written by the previous batch
+        let mut expected = Vec::<(String, String)>::new();
+        for i in 0..250 {
+            let mut batch = BatchUpdate::default();
+            if i > 0 {
+                let prev_key = format!("key_{}", i - 1);
+                batch.delete(BytesMut::from(prev_key.as_str()));
+                expected.push(("del".to_string(), prev_key));
+            }
+            let key = format!("key_{}", i);
+            batch.put(BytesMut::from(key.as_str()), BytesMut::from("value"));
+            expected.push(("put".to_string(), key));
+            store.apply_batch(&batch)?;
+        }
+
+        // a range deletion is reported once, with the deleted prefix
+        store.delete_range(&BytesMut::from("key_"))?;
+        expected.push(("delrange".to_string(), "key_".to_string()));
+
+        let read_changes = |seq: u64| -> Result<Vec<(u64, String, String)>, SableError> {
+            let mut changes = Vec::new();
+            for change in store.changes_since(seq)? {
+                let change = change?;
+                let (kind, key) = match change.change {
+                    StorageUpdatesIterItem::Put(put) => {
+                        assert_eq!(put.value, BytesMut::from("value"));
+                        ("put", put.key)
+                    }
+                    StorageUpdatesIterItem::Del(del) => ("del", del.key),
+                    StorageUpdatesIterItem::DelRange(del_range) => ("delrange", del_range.prefix),
+                };
+                changes.push((
+                    change.seq,
+                    kind.to_string(),
+                    String::from_utf8_lossy(&key).to_string(),
+                ));
+            }
+            Ok(changes)
+        };
+
+        // all the changes, in order (spanning multiple fetches)
+        let changes = read_changes(0)?;
+        let kinds_and_keys: Vec<(String, String)> = changes
+            .iter()
+            .map(|(_, kind, key)| (kind.clone(), key.clone()))
+            .collect();
+        assert_eq!(kinds_and_keys, expected);
+        assert!(changes.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+
+        // resume after the 100th batch (`changes[198]` is its put)
+        let resume_seq = changes[198].0;
+        assert_ne!(changes[199].0, resume_seq);
+        let resumed = read_changes(resume_seq)?;
+        assert_eq!(resumed.as_slice(), &changes[199..]);
+
+        // caught up
+        let last_seq = changes.last().map(|(seq, _, _)| *seq).unwrap_or_default();
+        assert!(read_changes(last_seq)?.is_empty());
+
+        drop(store);
+        let _ = fs::remove_dir_all(db_path);
+        Ok(())
+    }
 }
@@ -1,8 +1,13 @@
 #[allow(unused_imports)]
 use crate::{
+    metadata::Encoding,
     replication::{StorageUpdates, StorageUpdatesIterItem},
-    storage::{storage_trait::StorageIterator, IterateCallback, PutFlags, StorageTrait},
+    storage::{
+        group_commit::GroupCommit, storage_trait::StorageIterator, AppendFsync, BackupInfo,
+        CommitReceiver, CompressionType, IterateCallback, PutFlags, StorageTrait,
+    },
     BatchUpdate, BytesMutUtils, IoDurationStopWatch, SableError, StorageOpenParams, Telemetry,
+    TimeUtils,
 };
 
 use bytes::BytesMut;
@@ -10,13 +15,24 @@ use num_format::{Locale, ToFormattedString};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 type Database = rocksdb::DB;
 
+/// Written last into a backup directory: a directory without it is an incomplete backup
+const BACKUP_TIMESTAMP_FILE: &str = "backup.timestamp";
+/// RocksDB uses 7 levels by default
+const NUM_LEVELS: usize = 7;
+
 pub struct StorageRocksDb {
     store: Arc<Database>,
     path: PathBuf,
     write_opts: rocksdb::WriteOptions,
+    /// Does every write sync the WAL? (`write_opts` can not be queried)
+    sync_writes: bool,
+    /// When enabled, the WAL is synced once per group of writes, by a dedicated thread. Declared
+    /// after `store`: the thread is joined before the database is closed
+    group_commit: Option<GroupCommit>,
 }
 
 struct UpdateBatchIterator {
@@ -38,9 +54,18 @@ impl UpdateBatchIterator {
 
 impl rocksdb::WriteBatchIterator for UpdateBatchIterator {
     fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
-        self.storage_updates.add_put(&key, &value);
+        match key.split_first() {
+            Some((&Encoding::KEY_DELETE_RANGE_MARKER, prefix)) => {
+                self.storage_updates.add_delete_range(prefix);
+            }
+            _ => self.storage_updates.add_put(&key, &value),
+        }
     }
     fn delete(&mut self, key: Box<[u8]>) {
+        // the marker of a range deletion is reported once, by its put
+        if key.first() == Some(&Encoding::KEY_DELETE_RANGE_MARKER) {
+            return;
+        }
         self.storage_updates.add_delete(&key);
     }
 }
@@ -54,68 +79,124 @@ impl StorageRocksDb {
         options.set_max_write_buffer_number(open_params.rocksdb.max_write_buffer_number as i32);
         options.set_max_background_jobs(open_params.rocksdb.max_background_jobs as i32);
         options.set_manual_wal_flush(open_params.rocksdb.manual_wal_flush);
-        options.set_compression_type(if open_params.rocksdb.compression_enabled {
-            rocksdb::DBCompressionType::Snappy
-        } else {
-            rocksdb::DBCompressionType::None
+        options.set_compression_type(match open_params.rocksdb.compression {
+            CompressionType::None => rocksdb::DBCompressionType::None,
+            CompressionType::Snappy => rocksdb::DBCompressionType::Snappy,
+            CompressionType::Lz4 => rocksdb::DBCompressionType::Lz4,
+            CompressionType::Zstd => rocksdb::DBCompressionType::Zstd,
         });
         options.set_write_buffer_size(open_params.rocksdb.write_buffer_size);
         options.set_log_level(rocksdb::LogLevel::Info);
         options.set_max_open_files(open_params.rocksdb.max_open_files as i32);
         options.set_wal_ttl_seconds(open_params.rocksdb.wal_ttl_seconds as u64);
-        let store = rocksdb::DB::open(&options, open_params.db_path.clone())?;
 
+        let mut table_options: rocksdb::BlockBasedOptions = Default::default();
+        if open_params.rocksdb.block_cache_size > 0 {
+            let cache = rocksdb::Cache::new_lru_cache(open_params.rocksdb.block_cache_size);
+            table_options.set_block_cache(&cache);
+        }
+        if open_params.rocksdb.bloom_filter_bits_per_key > 0 {
+            table_options
+                .set_bloom_filter(open_params.rocksdb.bloom_filter_bits_per_key as f64, false);
+        }
+        table_options
+            .set_cache_index_and_filter_blocks(open_params.rocksdb.cache_index_and_filter_blocks);
+        options.set_block_based_table_factory(&table_options);
+
+        let store = Arc::new(rocksdb::DB::open(&options, open_params.db_path.clone())?);
+
+        // Every write syncs the WAL with `appendfsync always`, unless the syncs are grouped. RocksDB
+        // rejects sync writes when the WAL is disabled
+        let sync_writes = open_params.rocksdb.appendfsync == AppendFsync::Always
+            && !open_params.rocksdb.disable_wal;
+        let group_commit = if open_params.group_commit_enabled() {
+            Some(GroupCommit::start(
+                store.clone(),
+                Duration::from_micros(open_params.rocksdb.group_commit_interval_micros as u64),
+            )?)
+        } else {
+            None
+        };
+
+        let sync_writes = sync_writes && group_commit.is_none();
         let mut write_opts = rocksdb::WriteOptions::default();
-        write_opts.set_sync(false);
+        write_opts.set_sync(sync_writes);
         write_opts.disable_wal(open_params.rocksdb.disable_wal);
 
         Ok(StorageRocksDb {
-            store: Arc::new(store),
+            store,
             write_opts,
+            sync_writes,
             path: open_params.db_path.clone(),
+            group_commit,
         })
     }
 
+    /// Write the operations added by `add_to_batch` as a single batch
+    fn write_batch(
+        &self,
+        add_to_batch: impl FnOnce(&mut rocksdb::WriteBatch),
+    ) -> Result<(), SableError> {
+        let mut updates = rocksdb::WriteBatch::default();
+        add_to_batch(&mut updates);
+        Telemetry::inc_total_io_write_calls();
+        let _io_stop_watch = IoDurationStopWatch::default();
+        self.store.write_opt(updates, &self.write_opts)?;
+        Ok(())
+    }
+
+    /// Put `key`:`value`, the errors are ignored (like the puts of `put_internal` always did)
+    fn put_value(&self, key: &BytesMut, value: &BytesMut) {
+        Telemetry::inc_total_io_write_calls();
+        let _ = self
+            .store
+            .put_opt(key.clone(), value.clone(), &self.write_opts);
+    }
+
     fn put_internal(
         &self,
         key: &BytesMut,
         value: &BytesMut,
         put_flags: PutFlags,
-    ) -> Result<(), SableError> {
+    ) -> Result<bool, SableError> {
         let _io_stop_watch = IoDurationStopWatch::default();
         match put_flags {
             PutFlags::Override => {
-                Telemetry::inc_total_io_write_calls();
-                let _ = self
-                    .store
-                    .put_opt(key.clone(), value.clone(), &self.write_opts);
+                self.put_value(key, value);
             }
             PutFlags::PutIfNotExists => {
                 Telemetry::inc_total_io_read_calls();
                 let old_value = self.store.get(key)?;
                 if old_value.is_some() {
                     // key already exists
-                    return Ok(());
+                    return Ok(false);
                 }
-                Telemetry::inc_total_io_write_calls();
-                let _ = self
-                    .store
-                    .put_opt(key.clone(), value.clone(), &self.write_opts);
+                self.put_value(key, value);
             }
             PutFlags::PutIfExists => {
                 Telemetry::inc_total_io_read_calls();
                 let old_value = self.store.get(key)?;
                 if old_value.is_none() {
                     // key not found
-                    return Ok(());
+                    return Ok(false);
                 }
-                Telemetry::inc_total_io_write_calls();
-                let _ = self
-                    .store
-                    .put_opt(key.clone(), value.clone(), &self.write_opts);
+                self.put_value(key, value);
             }
         }
-        Ok(())
+        Ok(true)
+    }
+
+    /// Return the smallest key that is greater than all the keys starting with `prefix`, or
+    /// `None` if there is no such key (`prefix` is empty or made of `0xFF` bytes only)
+    pub(crate) fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+        let mut end = prefix.to_vec();
+        while let Some(last) = end.pop() {
+            if last < u8::MAX {
+                end.push(last + 1);
+                return Some(end);
+            }
+        }
+        None
     }
 
     /// Write the last sequence number change
@@ -124,6 +205,45 @@ impl StorageRocksDb {
         std::fs::write(sequence_file, content)?;
         Ok(())
     }
+
+    /// Return the IDs of the backup directories under `location` (complete or not), sorted
+    fn backup_ids(location: &Path) -> Result<Vec<u64>, SableError> {
+        if !location.exists() {
+            return Ok(Vec::default());
+        }
+
+        let mut ids = Vec::<u64>::new();
+        for entry in std::fs::read_dir(location)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(id) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u64>().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// The total size of the files under `path`
+    fn directory_size(path: &Path) -> Result<u64, SableError> {
+        let mut size = 0u64;
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            size = size.saturating_add(if metadata.is_dir() {
+                Self::directory_size(&entry.path())?
+            } else {
+                metadata.len()
+            });
+        }
+        Ok(size)
+    }
 }
 
 impl StorageTrait for StorageRocksDb {
@@ -133,24 +253,26 @@ impl StorageTrait for StorageRocksDb {
         Ok(())
     }
 
+    fn sync_wal(&self) -> Result<(), SableError> {
+        let _io_stop_watch = IoDurationStopWatch::default();
+        self.store.flush_wal(true)?;
+        Ok(())
+    }
+
     fn apply_batch(&self, update: &BatchUpdate) -> Result<(), SableError> {
-        let mut updates = rocksdb::WriteBatch::default();
-        if let Some(keys) = update.keys_to_delete() {
-            for k in keys.iter() {
-                updates.delete(k);
+        self.write_batch(|updates| {
+            if let Some(keys) = update.keys_to_delete() {
+                for k in keys.iter() {
+                    updates.delete(k);
+                }
             }
-        }
 
-        if let Some(put_keys) = update.items_to_put() {
-            for (k, v) in put_keys.iter() {
-                updates.put(k, v);
+            if let Some(put_keys) = update.items_to_put() {
+                for (k, v) in put_keys.iter() {
+                    updates.put(k, v);
+                }
             }
-        }
-
-        Telemetry::inc_total_io_write_calls();
-        let _io_stop_watch = IoDurationStopWatch::default();
-        self.store.write_opt(updates, &self.write_opts)?;
-        Ok(())
+        })
     }
 
     fn flush(&self) -> Result<(), SableError> {
@@ -179,7 +301,12 @@ impl StorageTrait for StorageRocksDb {
         Ok((self.store.get_pinned(key)?).is_some())
     }
 
-    fn put(&self, key: &BytesMut, value: &BytesMut, put_flags: PutFlags) -> Result<(), SableError> {
+    fn put(
+        &self,
+        key: &BytesMut,
+        value: &BytesMut,
+        put_flags: PutFlags,
+    ) -> Result<bool, SableError> {
         self.put_internal(key, value, put_flags)
     }
 
@@ -191,6 +318,31 @@ impl StorageTrait for StorageRocksDb {
         Ok(())
     }
 
+    /// Delete the range `[prefix, successor(prefix))`. RocksDB writes a single range tombstone
+    /// instead of a tombstone per record, the records are dropped by the next compactions.
+    ///
+    /// Range deletions are not reported by the WAL iterator (`UpdateBatchIterator`), so the
+    /// same batch puts and deletes a `KEY_DELETE_RANGE_MARKER` record carrying the prefix. The
+    /// iterator reports the marker as a range deletion, which ships it to the replicas and to
+    /// the CDC consumers
+    fn delete_range(&self, prefix: &BytesMut) -> Result<(), SableError> {
+        let Some(end) = Self::prefix_successor(prefix) else {
+            return Err(SableError::InvalidArgument(format!(
+                "Can not delete range of prefix {:?}",
+                prefix
+            )));
+        };
+
+        let mut marker = BytesMut::with_capacity(1 + prefix.len());
+        marker.extend_from_slice(&[Encoding::KEY_DELETE_RANGE_MARKER]);
+        marker.extend_from_slice(prefix);
+        self.write_batch(|updates| {
+            updates.delete_range(&prefix[..], &end[..]);
+            updates.put(&marker, b"");
+            updates.delete(&marker);
+        })
+    }
+
     /// Create a consistent checkpoint at `location`
     /// Note that `location` must not exist, it will be created
     fn create_checkpoint(&self, location: &Path) -> Result<(), SableError> {
@@ -202,6 +354,67 @@ impl StorageTrait for StorageRocksDb {
         Ok(())
     }
 
+    /// Every backup is a checkpoint stored in its own directory, `location/<backup ID>`. RocksDB
+    /// hard links the table files of a checkpoint when it is on the same file system as the
+    /// database, so consecutive backups share their unchanged files instead of copying them
+    fn create_backup_retaining(
+        &self,
+        location: &Path,
+        keep_n: usize,
+    ) -> Result<BackupInfo, SableError> {
+        if keep_n == 0 {
+            return Err(SableError::InvalidArgument(
+                "At least one backup must be kept".to_string(),
+            ));
+        }
+
+        std::fs::create_dir_all(location)?;
+        let backup_id = Self::backup_ids(location)?
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .saturating_add(1);
+        let backup_path = location.join(backup_id.to_string());
+        self.create_checkpoint(&backup_path)?;
+
+        let timestamp = TimeUtils::epoch_ms()? / 1000;
+        std::fs::write(
+            backup_path.join(BACKUP_TIMESTAMP_FILE),
+            timestamp.to_string(),
+        )?;
+
+        // purge the oldest backups, including incomplete ones left by a failure
+        for id in Self::backup_ids(location)? {
+            if id.saturating_add(keep_n as u64) <= backup_id {
+                std::fs::remove_dir_all(location.join(id.to_string()))?;
+            }
+        }
+
+        Ok(BackupInfo {
+            id: backup_id,
+            timestamp,
+            size: Self::directory_size(&backup_path)?,
+        })
+    }
+
+    fn list_backups(&self, location: &Path) -> Result<Vec<BackupInfo>, SableError> {
+        let mut backups = Vec::<BackupInfo>::new();
+        for id in Self::backup_ids(location)? {
+            let backup_path = location.join(id.to_string());
+            let Ok(timestamp) = std::fs::read_to_string(backup_path.join(BACKUP_TIMESTAMP_FILE))
+            else {
+                // incomplete backup
+                continue;
+            };
+            backups.push(BackupInfo {
+                id,
+                timestamp: timestamp.trim().parse::<u64>().unwrap_or_default(),
+                size: Self::directory_size(&backup_path)?,
+            });
+        }
+        Ok(backups)
+    }
+
     /// Restore the database from checkpoint database.
     /// This operation locks the entire database before it starts
     /// All write operations are stalled during this operation
@@ -298,6 +511,74 @@ impl StorageTrait for StorageRocksDb {
         Ok(myiter.storage_updates)
     }
 
+    fn storage_batches_since(
+        &self,
+        sequence_number: u64,
+        batches_limit: usize,
+    ) -> Result<Vec<StorageUpdates>, SableError> {
+        let changes_iter = self.store.get_updates_since(sequence_number)?;
+
+        let mut batches = Vec::new();
+        let mut prev_seq = sequence_number;
+        for change in changes_iter.take(batches_limit) {
+            let (seq, write_batch) = change.map_err(SableError::RocksDbError)?;
+            let mut myiter = UpdateBatchIterator::new(prev_seq);
+            write_batch.iterate(&mut myiter);
+            myiter.update(seq);
+            prev_seq = seq;
+            batches.push(myiter.storage_updates);
+        }
+        Ok(batches)
+    }
+
+    fn latest_sequence_number(&self) -> Result<u64, SableError> {
+        Ok(self.store.latest_sequence_number())
+    }
+
+    fn request_commit(&self) -> Option<CommitReceiver> {
+        let group_commit = self.group_commit.as_ref()?;
+        group_commit.request(self.store.latest_sequence_number())
+    }
+
+    fn compact_range(&self, start: Option<&[u8]>, end: Option<&[u8]>) -> Result<(), SableError> {
+        self.store.compact_range(start, end);
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<Vec<(String, u64)>, SableError> {
+        let mut properties: Vec<String> = [
+            "estimate-num-keys",
+            "total-sst-files-size",
+            "live-sst-files-size",
+            "estimate-pending-compaction-bytes",
+            "num-running-compactions",
+            "num-deletes-active-mem-table",
+            "block-cache-capacity",
+            "block-cache-usage",
+            "block-cache-pinned-usage",
+            "estimate-table-readers-mem",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        for level in 0..NUM_LEVELS {
+            properties.push(format!("num-files-at-level{}", level));
+        }
+
+        let mut stats = Vec::<(String, u64)>::with_capacity(properties.len());
+        for name in properties {
+            let value = self
+                .store
+                .property_int_value(format!("rocksdb.{}", name).as_str())?
+                .unwrap_or_default();
+            stats.push((name.replace('-', "_"), value));
+        }
+        if let Some(group_commit) = &self.group_commit {
+            stats.push(("group_commit_syncs".to_string(), group_commit.syncs()));
+        }
+        Ok(stats)
+    }
+
     fn iterate(
         &self,
         prefix: Rc<BytesMut>,
@@ -412,7 +693,7 @@ mod tests {
         let db_path = PathBuf::from("tests/test_get_updates_since.db");
         let _ = std::fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(true)
+            .set_compression_type(CompressionType::Snappy)
             .set_cache_size(64)
             .set_path(&db_path);
         let rocks = crate::StorageRocksDb::open(open_params.clone()).expect("rockdb open");
@@ -479,7 +760,7 @@ mod tests {
         // checkpoint path must not exist
         let _ = std::fs::remove_dir_all(backup_db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(true)
+            .set_compression_type(CompressionType::Snappy)
             .set_cache_size(64)
             .set_path(&db_path);
         let db = StorageRocksDb::open(open_params.clone())?;
@@ -533,4 +814,268 @@ mod tests {
         println!("All records restored successfully");
         Ok(())
     }
+
+    #[test]
+    fn test_backups_retention() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from("tests/test_backups_retention.db");
+        let backups_path = PathBuf::from("tests/test_backups_retention.db.backups");
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let _ = std::fs::remove_dir_all(backups_path.clone());
+        let open_params = StorageOpenParams::default()
+            .set_compression_type(CompressionType::None)
+            .set_cache_size(64)
+            .set_path(&db_path);
+        let db = StorageRocksDb::open(open_params.clone())?;
+
+        let start = TimeUtils::epoch_ms()? / 1000;
+        for i in 1..=3 {
+            let key = format!("key_{}", i);
+            db.put(
+                &BytesMut::from(key.as_str()),
+                &BytesMut::from("value"),
+                PutFlags::Override,
+            )?;
+            let backup = db.create_backup_retaining(&backups_path, 2)?;
+            assert_eq!(backup.id, i);
+        }
+
+        // the oldest backup was purged
+        let backups = db.list_backups(&backups_path)?;
+        assert_eq!(
+            backups.iter().map(|backup| backup.id).collect::<Vec<u64>>(),
+            vec![2, 3]
+        );
+        assert!(!backups_path.join("1").exists());
+        for backup in &backups {
+            assert!(backup.timestamp >= start);
+            assert!(backup.size > 0);
+        }
+        assert!(backups[0].timestamp <= backups[1].timestamp);
+        assert!(db.create_backup_retaining(&backups_path, 0).is_err());
+
+        // the latest backup holds all the keys
+        drop(db);
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let db = StorageRocksDb::open(open_params)?;
+        db.restore_from_checkpoint(&backups_path.join("3"), false)?;
+        for i in 1..=3 {
+            let key = format!("key_{}", i);
+            assert!(db.get(&BytesMut::from(key.as_str()))?.is_some());
+        }
+
+        let _ = std::fs::remove_dir_all(db_path);
+        let _ = std::fs::remove_dir_all(backups_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_range() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from("tests/test_compact_range.db");
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let open_params = StorageOpenParams::default()
+            .set_compression_type(CompressionType::None)
+            .set_cache_size(64)
+            .set_path(&db_path);
+        let db = StorageRocksDb::open(open_params)?;
+
+        for i in 0..10_000 {
+            let key = format!("key_{:05}", i);
+            db.put(
+                &BytesMut::from(key.as_str()),
+                &BytesMut::from("value"),
+                PutFlags::Override,
+            )?;
+        }
+        for i in 1_000..10_000 {
+            let key = format!("key_{:05}", i);
+            db.delete(&BytesMut::from(key.as_str()))?;
+        }
+        db.flush()?;
+        db.compact_range(None, None)?;
+
+        // the deleted records are gone from the disk
+        let mut count = 0usize;
+        db.iterate(
+            Rc::new(BytesMut::from("key_")),
+            Box::new(|_, _, _| {
+                count = count.saturating_add(1);
+                true
+            }),
+        )?;
+        assert_eq!(count, 1_000);
+
+        let stats: std::collections::HashMap<String, u64> = db.stats()?.into_iter().collect();
+        assert_eq!(stats.get("estimate_num_keys"), Some(&1_000));
+        assert!(stats.contains_key("num_files_at_level6"));
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(db_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression_types() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        for compression in [
+            CompressionType::None,
+            CompressionType::Snappy,
+            CompressionType::Lz4,
+            CompressionType::Zstd,
+        ] {
+            assert_eq!(
+                CompressionType::parse(compression.as_str()),
+                Some(compression)
+            );
+            let db_path = PathBuf::from(format!(
+                "tests/test_compression_{}.db",
+                compression.as_str()
+            ));
+            let _ = std::fs::remove_dir_all(db_path.clone());
+            let open_params = StorageOpenParams::default()
+                .set_compression_type(compression)
+                .set_cache_size(64)
+                .set_path(&db_path);
+            let db = StorageRocksDb::open(open_params)?;
+
+            let key = BytesMut::from("key");
+            let value = BytesMut::from("value".repeat(100).as_str());
+            db.put(&key, &value, PutFlags::Override)?;
+            db.flush()?;
+            assert_eq!(db.get(&key)?, Some(value));
+
+            drop(db);
+            let _ = std::fs::remove_dir_all(db_path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_compression_shim() {
+        let open_params = StorageOpenParams::default().set_compression(true);
+        assert_eq!(open_params.rocksdb.compression, CompressionType::Snappy);
+        let open_params = open_params.set_compression(false);
+        assert_eq!(open_params.rocksdb.compression, CompressionType::None);
+    }
+
+    #[test]
+    fn test_block_cache_and_bloom_filter() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from("tests/test_block_cache_and_bloom_filter.db");
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let mut open_params = StorageOpenParams::default()
+            .set_cache_size(64)
+            .set_path(&db_path);
+        open_params.rocksdb.block_cache_size = 8 << 20;
+        open_params.rocksdb.bloom_filter_bits_per_key = 10;
+        open_params.rocksdb.cache_index_and_filter_blocks = true;
+        let db = StorageRocksDb::open(open_params)?;
+
+        for i in 0..1_000 {
+            let key = format!("key_{}", i);
+            db.put(
+                &BytesMut::from(key.as_str()),
+                &BytesMut::from("value"),
+                PutFlags::Override,
+            )?;
+        }
+        db.flush()?;
+
+        for i in 0..1_000 {
+            let key = format!("key_{}", i);
+            assert!(db.get(&BytesMut::from(key.as_str()))?.is_some());
+            let missing_key = format!("missing_key_{}", i);
+            assert!(db.get(&BytesMut::from(missing_key.as_str()))?.is_none());
+        }
+
+        let stats: std::collections::HashMap<String, u64> = db.stats()?.into_iter().collect();
+        for name in [
+            "block_cache_capacity",
+            "block_cache_usage",
+            "block_cache_pinned_usage",
+        ] {
+            assert!(stats.contains_key(name), "missing stat {}", name);
+        }
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(db_path);
+        Ok(())
+    }
+
+    /// Copy the files of the database at `src` into `dst`, as they are on the disk right now
+    fn copy_db_files(src: &Path, dst: &Path) -> Result<(), SableError> {
+        let _ = std::fs::remove_dir_all(dst);
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            std::fs::copy(entry.path(), dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_appendfsync_always_preserves_last_write() -> Result<(), SableError> {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from("tests/test_appendfsync_always.db");
+        let crash_path = PathBuf::from("tests/test_appendfsync_always_crash.db");
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        for appendfsync in [AppendFsync::Always, AppendFsync::EverySec, AppendFsync::No] {
+            assert_eq!(AppendFsync::parse(appendfsync.as_str()), Some(appendfsync));
+        }
+
+        // only `appendfsync always` syncs every write, unless the syncs are grouped
+        let open_params = StorageOpenParams::default()
+            .set_cache_size(64)
+            .set_path(&db_path);
+        for (open_params, sync_writes) in [
+            (
+                open_params.clone().set_appendfsync(AppendFsync::Always),
+                true,
+            ),
+            (
+                open_params.clone().set_appendfsync(AppendFsync::EverySec),
+                false,
+            ),
+            (open_params.clone().set_appendfsync(AppendFsync::No), false),
+            (
+                open_params
+                    .clone()
+                    .set_appendfsync(AppendFsync::Always)
+                    .set_wal_disabled(true),
+                false,
+            ),
+            (
+                open_params
+                    .clone()
+                    .set_appendfsync(AppendFsync::Always)
+                    .set_group_commit_interval_micros(100),
+                false,
+            ),
+        ] {
+            assert_eq!(StorageRocksDb::open(open_params)?.sync_writes, sync_writes);
+        }
+
+        // the WAL is buffered by RocksDB until it is flushed, unless the write is synced
+        let mut open_params = open_params.set_appendfsync(AppendFsync::Always);
+        open_params.rocksdb.manual_wal_flush = true;
+        let key = BytesMut::from("key");
+        {
+            let db = StorageRocksDb::open(open_params.clone())?;
+            for i in 0..10 {
+                let value = BytesMut::from(format!("value_{}", i).as_str());
+                db.put(&key, &value, PutFlags::Override)?;
+            }
+            // "crash": take the files as they are on the disk, while the database is open
+            copy_db_files(&db_path, &crash_path)?;
+        }
+
+        let db = StorageRocksDb::open(open_params.set_path(&crash_path))?;
+        assert_eq!(db.get(&key)?, Some(BytesMut::from("value_9")));
+        drop(db);
+        let _ = std::fs::remove_dir_all(db_path);
+        let _ = std::fs::remove_dir_all(crash_path);
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    storage::{BatchUpdate, PutFlags, StorageUpdates},
+    storage::{BatchUpdate, CommitReceiver, PutFlags, StorageUpdates},
     SableError,
 };
 use bytes::BytesMut;
@@ -12,13 +12,30 @@ pub enum StorageIterator<'a> {
     RocksDb(rocksdb::DBRawIteratorWithThreadMode<'a, rocksdb::DB>),
 }
 
+/// A backup created by `StorageTrait::create_backup_retaining`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Backups are numbered from `1`, a newer backup has a larger ID
+    pub id: u64,
+    /// Creation time, seconds since UNIX_EPOCH
+    pub timestamp: u64,
+    /// The size of the backup files, in bytes
+    pub size: u64,
+}
+
 /// Define the database interface
 pub trait StorageTrait {
     /// Get a record from the store
     fn get(&self, key: &BytesMut) -> Result<Option<BytesMut>, SableError>;
 
-    /// Put key:value in the store
-    fn put(&self, key: &BytesMut, value: &BytesMut, put_flags: PutFlags) -> Result<(), SableError>;
+    /// Put key:value in the store. Return `true` if the record was written (`put_flags` might
+    /// prevent it)
+    fn put(
+        &self,
+        key: &BytesMut,
+        value: &BytesMut,
+        put_flags: PutFlags,
+    ) -> Result<bool, SableError>;
 
     /// Check whether `key` exists in the store
     fn contains(&self, key: &BytesMut) -> Result<bool, SableError>;
@@ -26,6 +43,9 @@ pub trait StorageTrait {
     /// Delete a record from the store
     fn delete(&self, key: &BytesMut) -> Result<(), SableError>;
 
+    /// Delete all the records starting with `prefix` using a single range deletion
+    fn delete_range(&self, prefix: &BytesMut) -> Result<(), SableError>;
+
     /// Apply batch update to the database
     fn apply_batch(&self, update: &BatchUpdate) -> Result<(), SableError>;
 
@@ -35,10 +55,24 @@ pub trait StorageTrait {
     /// Manually flush any journal to the disk
     fn flush_wal(&self) -> Result<(), SableError>;
 
+    /// Flush the journal and sync it to the disk
+    fn sync_wal(&self) -> Result<(), SableError>;
+
     /// Create a database checkpoint for backup purposes and store it at `location`
     /// `location` is a directory
     fn create_checkpoint(&self, location: &Path) -> Result<(), SableError>;
 
+    /// Create a new backup under `location` (a directory) and delete the oldest backups, so
+    /// only the `keep_n` most recent backups remain
+    fn create_backup_retaining(
+        &self,
+        location: &Path,
+        keep_n: usize,
+    ) -> Result<BackupInfo, SableError>;
+
+    /// Return the backups found under `location`, oldest first
+    fn list_backups(&self, location: &Path) -> Result<Vec<BackupInfo>, SableError>;
+
     /// Restore database from a backup database located at `backup_location` (a directory)
     /// If `delete_all_before_store` is true, we will purge all current records from the
     /// db before starting the restore
@@ -59,6 +93,29 @@ pub trait StorageTrait {
         changes_count_limit: Option<u64>,
     ) -> Result<StorageUpdates, SableError>;
 
+    /// Same as `storage_updates_since`, but every write batch is returned as a separate
+    /// `StorageUpdates` (with a `changes_count` of 1). Stops after `batches_limit` batches
+    fn storage_batches_since(
+        &self,
+        sequence_number: u64,
+        batches_limit: usize,
+    ) -> Result<Vec<StorageUpdates>, SableError>;
+
+    /// Return the sequence number of the last write applied to the store
+    fn latest_sequence_number(&self) -> Result<u64, SableError>;
+
+    /// Request the commit of the writes applied so far. With the group commit, the WAL is synced
+    /// once per group of writes and the receiver completes when these writes are durable. `None`
+    /// if there is nothing to wait for
+    fn request_commit(&self) -> Option<CommitReceiver>;
+
+    /// Compact the records in the range `[start, end)`. A `None` bound extends the range to the
+    /// first (last) record. Deleted records are dropped from the disk by the compaction
+    fn compact_range(&self, start: Option<&[u8]>, end: Option<&[u8]>) -> Result<(), SableError>;
+
+    /// Return the storage engine statistics (files, levels and compactions) as `(name, value)`
+    fn stats(&self) -> Result<Vec<(String, u64)>, SableError>;
+
     /// Iterate on all keys starting with `prefix` and apply `callback` on them
     fn iterate(
         &self,
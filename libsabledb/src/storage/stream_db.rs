@@ -0,0 +1,794 @@
+#[allow(unused_imports)]
+use crate::{
+    metadata::{StreamEntryKey, StreamId, StreamValueMetadata},
+    storage::{DbWriteCache, PutFlags, StorageIterator},
+    CommonValueMetadata, PrimaryKeyMetadata, SableError, StorageAdapter, TimeUtils, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A stream entry: its ID and its field-value pairs
+pub type StreamEntry = (StreamId, Vec<(BytesMut, BytesMut)>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GetStreamMetadataResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// A match was found
+    Some(StreamValueMetadata),
+    /// No entry exist
+    NotFound,
+}
+
+/// `StreamDb::add` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamAddResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The explicit ID is `0-0`
+    IdIsZero,
+    /// The explicit ID is equal or smaller than the ID of the last entry of the stream
+    IdTooSmall,
+    /// The ID of the new entry
+    Some(StreamId),
+}
+
+/// `StreamDb::len` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamLenResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The number of entries in the stream
+    Some(usize),
+}
+
+/// `StreamDb::range` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamRangeResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The entries, sorted by their ID
+    Some(Vec<StreamEntry>),
+}
+
+/// `StreamDb::trim` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamTrimResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The number of entries removed from the stream
+    Some(usize),
+}
+
+/// `StreamDb::delete` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamDeleteResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The number of entries deleted
+    Some(usize),
+}
+
+/// The stream details reported by `XINFO STREAM`
+#[derive(PartialEq, Eq, Debug)]
+pub struct StreamInfo {
+    /// The number of entries in the stream
+    pub len: u64,
+    /// The ID of the last entry ever added to the stream, even if it was deleted since
+    pub last_generated_id: StreamId,
+    /// The oldest entry, `None` for an empty stream
+    pub first_entry: Option<StreamEntry>,
+    /// The newest entry, `None` for an empty stream
+    pub last_entry: Option<StreamEntry>,
+}
+
+/// `StreamDb::info` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum StreamInfoResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// No stream exists for the given key
+    NotFound,
+    /// The stream details
+    Some(StreamInfo),
+}
+
+/// The threshold of `XADD` and `XTRIM` trimming
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StreamTrimThreshold {
+    /// Keep at most this number of entries (`MAXLEN`)
+    MaxLen(u64),
+    /// Remove the entries with an ID smaller than this one (`MINID`)
+    MinId(StreamId),
+}
+
+/// How to trim a stream: `<MAXLEN | MINID> [= | ~] threshold [LIMIT count]`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StreamTrimOptions {
+    pub threshold: StreamTrimThreshold,
+    /// `~`: only remove whole chunks of `StreamDb::TRIM_CHUNK_SIZE` entries, so the stream
+    /// may keep a few more entries than the threshold
+    pub approximate: bool,
+    /// The maximum number of entries removed by an approximate trim, `None` for no limit
+    pub limit: Option<usize>,
+}
+
+/// Stream DB wrapper. This class is specialized in reading/writing streams
+/// (commands from the `XADD`, `XRANGE` etc family)
+///
+/// Locking strategy: this class does not lock anything and relies on the caller
+/// to obtain the locks if needed
+pub struct StreamDb<'a> {
+    store: &'a StorageAdapter,
+    db_id: u16,
+    cache: Box<DbWriteCache<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> StreamDb<'a> {
+    pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
+        let cache = Box::new(DbWriteCache::with_storage(store));
+        StreamDb {
+            store,
+            db_id,
+            cache,
+        }
+    }
+
+    /// Approximate trimming removes entries in chunks of this size (the default
+    /// `stream-node-max-entries` of Redis)
+    pub const TRIM_CHUNK_SIZE: usize = 100;
+    /// The default `LIMIT` of approximate trimming
+    pub const DEFAULT_TRIM_LIMIT: usize = 100 * Self::TRIM_CHUNK_SIZE;
+
+    /// Append an entry made of `fields` to the stream stored at `user_key`. The stream is
+    /// created if it does not exist. When `id` is `None` (`*`), the ID is generated from the
+    /// current time, and is always greater than the ID of the last entry. If `trim` is provided,
+    /// the stream is trimmed in the same batch
+    pub fn add(
+        &self,
+        user_key: &BytesMut,
+        id: Option<StreamId>,
+        fields: &[(&BytesMut, &BytesMut)],
+        trim: Option<&StreamTrimOptions>,
+    ) -> Result<StreamAddResult, SableError> {
+        // locate the stream
+        let mut stream = match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => return Ok(StreamAddResult::WrongType),
+            GetStreamMetadataResult::NotFound => {
+                StreamValueMetadata::with_id(self.store.generate_id())
+            }
+            GetStreamMetadataResult::Some(stream) => stream,
+        };
+
+        let last_id = stream.last_id();
+        let entry_id = match id {
+            Some(id) if id == StreamId::MIN => return Ok(StreamAddResult::IdIsZero),
+            Some(id) if id <= last_id => return Ok(StreamAddResult::IdTooSmall),
+            Some(id) => id,
+            None => {
+                let now = TimeUtils::epoch_ms()?;
+                if now > last_id.ms() {
+                    StreamId::new(now, 0)
+                } else {
+                    // the clock did not move (or moved backward): keep the IDs monotonic
+                    let Some(next_id) = last_id.next() else {
+                        return Ok(StreamAddResult::IdTooSmall);
+                    };
+                    next_id
+                }
+            }
+        };
+
+        // Trim before adding: the new entry is the newest one, so the trim makes room for it.
+        // An exact threshold that leaves no room at all drops the new entry right away
+        let mut keep_entry = true;
+        if let Some(trim) = trim {
+            let mut trim = *trim;
+            match trim.threshold {
+                StreamTrimThreshold::MaxLen(max_len) => {
+                    trim.threshold = StreamTrimThreshold::MaxLen(max_len.saturating_sub(1));
+                    keep_entry = max_len > 0 || trim.approximate;
+                }
+                StreamTrimThreshold::MinId(min_id) => {
+                    keep_entry = entry_id >= min_id || trim.approximate;
+                }
+            }
+            self.trim_entries(&mut stream, &trim)?;
+        }
+
+        if keep_entry {
+            self.cache.put(
+                &self.encode_entry_key(stream.id(), entry_id),
+                Self::encode_entry_value(fields),
+            )?;
+            stream.incr_len_by(1);
+        }
+        stream.set_last_id(entry_id);
+        self.put_stream_metadata(user_key, &stream)?;
+
+        // flush the changes
+        self.flush_cache()?;
+        Ok(StreamAddResult::Some(entry_id))
+    }
+
+    /// Remove the oldest entries of the stream, according to `options`
+    pub fn trim(
+        &self,
+        user_key: &BytesMut,
+        options: &StreamTrimOptions,
+    ) -> Result<StreamTrimResult, SableError> {
+        let mut stream = match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => return Ok(StreamTrimResult::WrongType),
+            GetStreamMetadataResult::NotFound => return Ok(StreamTrimResult::Some(0)),
+            GetStreamMetadataResult::Some(stream) => stream,
+        };
+
+        let removed = self.trim_entries(&mut stream, options)?;
+        if removed > 0 {
+            self.put_stream_metadata(user_key, &stream)?;
+            self.flush_cache()?;
+        }
+        Ok(StreamTrimResult::Some(removed))
+    }
+
+    /// Delete the entries with the given IDs. IDs that do not exist in the stream are skipped.
+    /// The ID of the last entry ever added is kept, even if that entry is deleted
+    pub fn delete(
+        &self,
+        user_key: &BytesMut,
+        ids: &[StreamId],
+    ) -> Result<StreamDeleteResult, SableError> {
+        let mut stream = match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => return Ok(StreamDeleteResult::WrongType),
+            GetStreamMetadataResult::NotFound => return Ok(StreamDeleteResult::Some(0)),
+            GetStreamMetadataResult::Some(stream) => stream,
+        };
+
+        let mut deleted = 0usize;
+        for id in ids {
+            // the cache reports the entries deleted by a previous ID as missing
+            let entry_key = self.encode_entry_key(stream.id(), *id);
+            if self.cache.contains(&entry_key)? {
+                self.cache.delete(&entry_key)?;
+                deleted = deleted.saturating_add(1);
+            }
+        }
+
+        if deleted > 0 {
+            stream.decr_len_by(deleted as u64);
+            self.put_stream_metadata(user_key, &stream)?;
+            self.flush_cache()?;
+        }
+        Ok(StreamDeleteResult::Some(deleted))
+    }
+
+    /// Return the details of the stream: its length, the last generated ID and its first and
+    /// last entries
+    pub fn info(&self, user_key: &BytesMut) -> Result<StreamInfoResult, SableError> {
+        let stream = match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => return Ok(StreamInfoResult::WrongType),
+            GetStreamMetadataResult::NotFound => return Ok(StreamInfoResult::NotFound),
+            GetStreamMetadataResult::Some(stream) => stream,
+        };
+
+        Ok(StreamInfoResult::Some(StreamInfo {
+            len: stream.len(),
+            last_generated_id: stream.last_id(),
+            first_entry: self.edge_entry(&stream, false)?,
+            last_entry: self.edge_entry(&stream, true)?,
+        }))
+    }
+
+    /// Return the number of entries in the stream
+    pub fn len(&self, user_key: &BytesMut) -> Result<StreamLenResult, SableError> {
+        match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => Ok(StreamLenResult::WrongType),
+            GetStreamMetadataResult::NotFound => Ok(StreamLenResult::Some(0)),
+            GetStreamMetadataResult::Some(stream) => {
+                Ok(StreamLenResult::Some(stream.len() as usize))
+            }
+        }
+    }
+
+    /// Return the entries with an ID between `start` and `end` (both inclusive), in ID order.
+    /// At most `count` entries are returned, if provided
+    pub fn range(
+        &self,
+        user_key: &BytesMut,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+    ) -> Result<StreamRangeResult, SableError> {
+        let stream = match self.stream_metadata(user_key)? {
+            GetStreamMetadataResult::WrongType => return Ok(StreamRangeResult::WrongType),
+            GetStreamMetadataResult::NotFound => return Ok(StreamRangeResult::Some(Vec::new())),
+            GetStreamMetadataResult::Some(stream) => stream,
+        };
+
+        if start > end || count == Some(0) {
+            return Ok(StreamRangeResult::Some(Vec::new()));
+        }
+
+        let entries = Rc::new(RefCell::new(Vec::<StreamEntry>::new()));
+        let entries_clone = entries.clone();
+        let limit = count.unwrap_or(usize::MAX);
+        self.store.iterate(
+            Rc::new(stream.prefix()),
+            Box::new(move |_prefix, key, value| {
+                let Ok(entry_key) = StreamEntryKey::from_bytes(key) else {
+                    return true;
+                };
+                let entry_id = entry_key.entry_id();
+                if entry_id < start {
+                    return true;
+                }
+                if entry_id > end {
+                    return false;
+                }
+                let Some(fields) = Self::decode_entry_value(value) else {
+                    return true;
+                };
+                let mut entries = entries_clone.borrow_mut();
+                entries.push((entry_id, fields));
+                entries.len() < limit
+            }),
+        )?;
+        let entries = entries.take();
+        Ok(StreamRangeResult::Some(entries))
+    }
+
+    /// Load stream value metadata from the store. An expired stream is reported as `NotFound`
+    pub fn stream_metadata(
+        &self,
+        user_key: &BytesMut,
+    ) -> Result<GetStreamMetadataResult, SableError> {
+        let Some(value) = self.cache.get_live(user_key, self.db_id)? else {
+            return Ok(GetStreamMetadataResult::NotFound);
+        };
+
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        if !common_md.is_stream() {
+            return Ok(GetStreamMetadataResult::WrongType);
+        }
+
+        reader.rewind();
+        let stream_md = StreamValueMetadata::from_bytes(&mut reader)?;
+        Ok(GetStreamMetadataResult::Some(stream_md))
+    }
+
+    // =======================================================
+    // Internal API for this class
+    // =======================================================
+
+    /// Apply the changes to the store and clear the cache
+    fn flush_cache(&self) -> Result<(), SableError> {
+        let batch = self.cache.to_write_batch();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.cache.clear();
+        self.store.apply_batch(&batch)
+    }
+
+    /// Return the first entry of `stream`, or its last entry when `last` is `true`. Either way,
+    /// a single seek is needed
+    fn edge_entry(
+        &self,
+        stream: &StreamValueMetadata,
+        last: bool,
+    ) -> Result<Option<StreamEntry>, SableError> {
+        let prefix = stream.prefix();
+        let seek_key = if last {
+            self.encode_entry_key(stream.id(), StreamId::MAX)
+        } else {
+            prefix.clone()
+        };
+
+        match self.store.create_iterator(Rc::new(seek_key.clone()))? {
+            StorageIterator::RocksDb(mut rocksdb_iter) => {
+                if last {
+                    rocksdb_iter.seek_for_prev(&seek_key);
+                }
+                if !rocksdb_iter.valid() {
+                    return Ok(None);
+                }
+                let (Some(key), Some(value)) = (rocksdb_iter.key(), rocksdb_iter.value()) else {
+                    return Ok(None);
+                };
+                if !key.starts_with(&prefix) {
+                    return Ok(None);
+                }
+                let entry_id = StreamEntryKey::from_bytes(key)?.entry_id();
+                Ok(Self::decode_entry_value(value).map(|fields| (entry_id, fields)))
+            }
+        }
+    }
+
+    /// Delete the entries removed by `options`, oldest first, and update the length of `stream`.
+    /// Only the entries that are removed are visited (plus one for `MINID`), so the work is
+    /// bounded by the trim and never by the size of the stream. Return the number of entries
+    /// removed
+    fn trim_entries(
+        &self,
+        stream: &mut StreamValueMetadata,
+        options: &StreamTrimOptions,
+    ) -> Result<usize, SableError> {
+        let (mut max_entries, min_id) = match options.threshold {
+            StreamTrimThreshold::MaxLen(max_len) => (
+                usize::try_from(stream.len().saturating_sub(max_len)).unwrap_or(usize::MAX),
+                StreamId::MAX,
+            ),
+            StreamTrimThreshold::MinId(min_id) => (usize::MAX, min_id),
+        };
+        if options.approximate {
+            max_entries = max_entries.min(options.limit.unwrap_or(usize::MAX));
+            max_entries -= max_entries % Self::TRIM_CHUNK_SIZE;
+        }
+        if max_entries == 0 {
+            return Ok(0);
+        }
+
+        let entry_keys = Rc::new(RefCell::new(Vec::<BytesMut>::new()));
+        let entry_keys_clone = entry_keys.clone();
+        self.store.iterate(
+            Rc::new(stream.prefix()),
+            Box::new(move |_prefix, key, _value| {
+                let Ok(entry_key) = StreamEntryKey::from_bytes(key) else {
+                    return true;
+                };
+                if entry_key.entry_id() >= min_id {
+                    return false;
+                }
+                let mut entry_keys = entry_keys_clone.borrow_mut();
+                entry_keys.push(BytesMut::from(key));
+                entry_keys.len() < max_entries
+            }),
+        )?;
+
+        let mut entry_keys = entry_keys.take();
+        if options.approximate {
+            // `MINID` may stop in the middle of a chunk: keep that chunk
+            entry_keys.truncate(entry_keys.len() - entry_keys.len() % Self::TRIM_CHUNK_SIZE);
+        }
+        for entry_key in &entry_keys {
+            self.cache.delete(entry_key)?;
+        }
+        stream.decr_len_by(entry_keys.len() as u64);
+        Ok(entry_keys.len())
+    }
+
+    /// Put a stream entry in the database
+    fn put_stream_metadata(
+        &self,
+        user_key: &BytesMut,
+        stream_md: &StreamValueMetadata,
+    ) -> Result<(), SableError> {
+        let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+
+        // serialise the stream value into bytes
+        let mut buffer = BytesMut::with_capacity(StreamValueMetadata::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        stream_md.to_bytes(&mut builder);
+
+        self.cache.put(&encoded_key, buffer)?;
+        Ok(())
+    }
+
+    /// Encode a stream entry key
+    fn encode_entry_key(&self, stream_id: u64, entry_id: StreamId) -> BytesMut {
+        let mut buffer = BytesMut::with_capacity(StreamEntryKey::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        StreamEntryKey::with_entry_id(stream_id, entry_id).to_bytes(&mut builder);
+        buffer
+    }
+
+    /// Encode the entry fields: `[count | (len | field | len | value)...]`
+    fn encode_entry_value(fields: &[(&BytesMut, &BytesMut)]) -> BytesMut {
+        let mut buffer = BytesMut::new();
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        builder.write_u64(fields.len() as u64);
+        for (field, value) in fields {
+            builder.write_u64(field.len() as u64);
+            builder.write_bytes(field);
+            builder.write_u64(value.len() as u64);
+            builder.write_bytes(value);
+        }
+        buffer
+    }
+
+    /// Decode the entry fields written by `encode_entry_value`
+    fn decode_entry_value(value: &[u8]) -> Option<Vec<(BytesMut, BytesMut)>> {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        let count = reader.read_u64()?;
+        let mut fields = Vec::<(BytesMut, BytesMut)>::new();
+        for _ in 0..count {
+            let len = usize::try_from(reader.read_u64()?).ok()?;
+            let field = reader.read_bytes(len)?;
+            let len = usize::try_from(reader.read_u64()?).ok()?;
+            let value = reader.read_bytes(len)?;
+            fields.push((field, value));
+        }
+        Some(fields)
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressionType, StorageOpenParams};
+    use std::path::PathBuf;
+
+    fn create_database(db_name: &str) -> StorageAdapter {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from(format!("tests/{}.db", db_name));
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let open_params = StorageOpenParams::default()
+            .set_compression_type(CompressionType::Snappy)
+            .set_cache_size(64)
+            .set_path(&db_path);
+        crate::storage_rocksdb!(open_params.clone())
+    }
+
+    #[test]
+    fn test_stream_wrong_type() -> Result<(), SableError> {
+        let db = create_database("test_stream_wrong_type");
+        let stream_db = StreamDb::with_storage(&db, 0);
+        let strings_db = crate::storage::StringsDb::with_storage(&db, 0);
+
+        let key = BytesMut::from("key");
+        let value = BytesMut::from("value");
+        strings_db.put(
+            &key,
+            &value,
+            &crate::StringValueMetadata::default(),
+            PutFlags::Override,
+        )?;
+
+        // run a stream operation on a string key
+        assert_eq!(stream_db.len(&key)?, StreamLenResult::WrongType);
+        assert_eq!(
+            stream_db.add(&key, None, &[(&key, &value)], None)?,
+            StreamAddResult::WrongType
+        );
+        assert_eq!(
+            stream_db.range(&key, StreamId::MIN, StreamId::MAX, None)?,
+            StreamRangeResult::WrongType
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_auto_id_is_monotonic() -> Result<(), SableError> {
+        let db = create_database("test_stream_auto_id_is_monotonic");
+        let stream_db = StreamDb::with_storage(&db, 0);
+
+        let stream_name = BytesMut::from("mystream");
+        let field = BytesMut::from("field");
+        let value = BytesMut::from("value");
+
+        // many entries are added within the same millisecond, the sequence breaks the tie
+        let mut last_id = StreamId::MIN;
+        for _ in 0..500 {
+            let StreamAddResult::Some(id) =
+                stream_db.add(&stream_name, None, &[(&field, &value)], None)?
+            else {
+                panic!("expected an ID");
+            };
+            assert!(id > last_id, "{} should be greater than {}", id, last_id);
+            last_id = id;
+        }
+        assert_eq!(stream_db.len(&stream_name)?, StreamLenResult::Some(500));
+
+        // an ID in the future, the auto ID keeps growing from it
+        let future_id = StreamId::new(last_id.ms() + 60_000, 5);
+        assert_eq!(
+            stream_db.add(&stream_name, Some(future_id), &[(&field, &value)], None)?,
+            StreamAddResult::Some(future_id)
+        );
+        assert_eq!(
+            stream_db.add(&stream_name, None, &[(&field, &value)], None)?,
+            StreamAddResult::Some(StreamId::new(future_id.ms(), 6))
+        );
+
+        // explicit IDs must be greater than the top item
+        assert_eq!(
+            stream_db.add(&stream_name, Some(future_id), &[(&field, &value)], None)?,
+            StreamAddResult::IdTooSmall
+        );
+        assert_eq!(
+            stream_db.add(&stream_name, Some(StreamId::MIN), &[(&field, &value)], None)?,
+            StreamAddResult::IdIsZero
+        );
+        assert_eq!(stream_db.len(&stream_name)?, StreamLenResult::Some(502));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_range() -> Result<(), SableError> {
+        let db = create_database("test_stream_range");
+        let stream_db = StreamDb::with_storage(&db, 0);
+
+        let stream_name = BytesMut::from("mystream");
+        let field = BytesMut::from("field");
+        let values: Vec<BytesMut> = (0..5)
+            .map(|i| BytesMut::from(format!("{}", i).as_str()))
+            .collect();
+        for (i, value) in values.iter().enumerate() {
+            stream_db.add(
+                &stream_name,
+                Some(StreamId::new(10, i as u64 + 1)),
+                &[(&field, value)],
+                None,
+            )?;
+        }
+
+        let ids = |result: StreamRangeResult| -> Vec<StreamId> {
+            let StreamRangeResult::Some(entries) = result else {
+                panic!("expected entries");
+            };
+            entries.into_iter().map(|(id, _)| id).collect()
+        };
+
+        // both bounds are inclusive
+        assert_eq!(
+            ids(stream_db.range(
+                &stream_name,
+                StreamId::new(10, 2),
+                StreamId::new(10, 4),
+                None
+            )?),
+            vec![
+                StreamId::new(10, 2),
+                StreamId::new(10, 3),
+                StreamId::new(10, 4)
+            ]
+        );
+        assert_eq!(
+            ids(stream_db.range(&stream_name, StreamId::MIN, StreamId::MAX, Some(2))?),
+            vec![StreamId::new(10, 1), StreamId::new(10, 2)]
+        );
+        assert!(ids(stream_db.range(
+            &stream_name,
+            StreamId::new(10, 4),
+            StreamId::new(10, 2),
+            None
+        )?)
+        .is_empty());
+
+        let StreamRangeResult::Some(entries) =
+            stream_db.range(&stream_name, StreamId::new(10, 5), StreamId::MAX, None)?
+        else {
+            panic!("expected entries");
+        };
+        assert_eq!(
+            entries,
+            vec![(
+                StreamId::new(10, 5),
+                vec![(field.clone(), values[4].clone())]
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_trim() -> Result<(), SableError> {
+        let db = create_database("test_stream_trim");
+        let stream_db = StreamDb::with_storage(&db, 0);
+
+        let stream_name = BytesMut::from("mystream");
+        let field = BytesMut::from("field");
+        let value = BytesMut::from("value");
+        for i in 1..=5 {
+            stream_db.add(
+                &stream_name,
+                Some(StreamId::new(i, 0)),
+                &[(&field, &value)],
+                None,
+            )?;
+        }
+
+        let exact = |threshold| StreamTrimOptions {
+            threshold,
+            approximate: false,
+            limit: None,
+        };
+        assert_eq!(
+            stream_db.trim(
+                &stream_name,
+                &exact(StreamTrimThreshold::MinId(StreamId::new(3, 0)))
+            )?,
+            StreamTrimResult::Some(2)
+        );
+        assert_eq!(
+            stream_db.trim(&stream_name, &exact(StreamTrimThreshold::MaxLen(2)))?,
+            StreamTrimResult::Some(1)
+        );
+        assert_eq!(stream_db.len(&stream_name)?, StreamLenResult::Some(2));
+
+        // adding with a trim makes room for the new entry
+        let StreamAddResult::Some(new_id) = stream_db.add(
+            &stream_name,
+            None,
+            &[(&field, &value)],
+            Some(&exact(StreamTrimThreshold::MaxLen(2))),
+        )?
+        else {
+            panic!("expected an ID");
+        };
+        let StreamRangeResult::Some(entries) =
+            stream_db.range(&stream_name, StreamId::MIN, StreamId::MAX, None)?
+        else {
+            panic!("expected entries");
+        };
+        let ids: Vec<StreamId> = entries.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![StreamId::new(5, 0), new_id]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_delete_and_info() -> Result<(), SableError> {
+        let db = create_database("test_stream_delete_and_info");
+        let stream_db = StreamDb::with_storage(&db, 0);
+
+        let stream_name = BytesMut::from("mystream");
+        let field = BytesMut::from("field");
+        let value = BytesMut::from("value");
+        for i in 1..=3 {
+            stream_db.add(
+                &stream_name,
+                Some(StreamId::new(i, 0)),
+                &[(&field, &value)],
+                None,
+            )?;
+        }
+
+        // missing and repeated IDs are not counted
+        assert_eq!(
+            stream_db.delete(
+                &stream_name,
+                &[
+                    StreamId::new(3, 0),
+                    StreamId::new(3, 0),
+                    StreamId::new(9, 0)
+                ]
+            )?,
+            StreamDeleteResult::Some(1)
+        );
+        assert_eq!(
+            stream_db.info(&stream_name)?,
+            StreamInfoResult::Some(StreamInfo {
+                len: 2,
+                last_generated_id: StreamId::new(3, 0),
+                first_entry: Some((StreamId::new(1, 0), vec![(field.clone(), value.clone())])),
+                last_entry: Some((StreamId::new(2, 0), vec![(field.clone(), value.clone())])),
+            })
+        );
+
+        stream_db.delete(&stream_name, &[StreamId::new(1, 0), StreamId::new(2, 0)])?;
+        assert_eq!(
+            stream_db.info(&stream_name)?,
+            StreamInfoResult::Some(StreamInfo {
+                len: 0,
+                last_generated_id: StreamId::new(3, 0),
+                first_entry: None,
+                last_entry: None,
+            })
+        );
+        assert_eq!(
+            stream_db.info(&BytesMut::from("nosuchstream"))?,
+            StreamInfoResult::NotFound
+        );
+        Ok(())
+    }
+}
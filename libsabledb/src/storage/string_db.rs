@@ -1,5 +1,5 @@
 use crate::{
-    storage::{BatchUpdate, PutFlags, StorageAdapter},
+    storage::{BatchUpdate, GenericDb, PutFlags, RecordBefore, StorageAdapter},
     PrimaryKeyMetadata, SableError, StringValueMetadata, U8ArrayBuilder, U8ArrayReader,
 };
 use bytes::BytesMut;
@@ -10,11 +10,18 @@ use bytes::BytesMut;
 pub struct StringsDb<'a> {
     store: &'a StorageAdapter,
     db_id: u16,
+    /// Reads and writes the primary records, so a key read before it is written is reported
+    /// with its write (see `RecordsRead`)
+    generic_db: GenericDb<'a>,
 }
 
 impl<'a> StringsDb<'a> {
     pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
-        StringsDb { store, db_id }
+        StringsDb {
+            store,
+            db_id,
+            generic_db: GenericDb::with_storage(store, db_id),
+        }
     }
 
     /// Put or Replace key
@@ -39,8 +46,7 @@ impl<'a> StringsDb<'a> {
 
     /// Delete key. The key is assumed to be a user key (i.e. not encoded)
     pub fn delete(&self, user_key: &BytesMut) -> Result<(), SableError> {
-        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
-        self.store.delete(&internal_key)
+        self.generic_db.delete(user_key)
     }
 
     /// Put multiple items in the store. On success, return `true`
@@ -57,8 +63,14 @@ impl<'a> StringsDb<'a> {
             let internal_key = PrimaryKeyMetadata::new_primary_key(key, self.db_id);
             let can_continue = match put_flags {
                 PutFlags::Override => true,
-                PutFlags::PutIfNotExists => self.store.get(&internal_key)?.is_none(),
-                PutFlags::PutIfExists => self.store.get(&internal_key)?.is_some(),
+                PutFlags::PutIfNotExists | PutFlags::PutIfExists => {
+                    let old_value = self.store.get(&internal_key)?;
+                    updates.set_record_before(
+                        &internal_key,
+                        RecordBefore::with_value(old_value.as_deref()),
+                    );
+                    old_value.is_some() == matches!(put_flags, PutFlags::PutIfExists)
+                }
             };
 
             // Can not continue
@@ -88,11 +100,11 @@ impl<'a> StringsDb<'a> {
         put_flags: PutFlags,
     ) -> Result<(), SableError> {
         let mut joined_value = BytesMut::with_capacity(value.len() + StringValueMetadata::SIZE);
-        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
         let mut builder = U8ArrayBuilder::with_buffer(&mut joined_value);
         metadata.to_bytes(&mut builder);
         builder.write_bytes(value);
-        self.store.put(&internal_key, &joined_value, put_flags)
+        self.generic_db
+            .put_value(user_key, &joined_value, put_flags)
     }
 
     /// Get a string key from the underlying storage
@@ -100,22 +112,12 @@ impl<'a> StringsDb<'a> {
         &self,
         user_key: &BytesMut,
     ) -> Result<Option<(BytesMut, StringValueMetadata)>, SableError> {
-        let internal_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
-
-        let raw_value = self.store.get(&internal_key)?;
-        if let Some(mut value) = raw_value {
-            let mut reader = U8ArrayReader::with_buffer(&value);
-            let md = StringValueMetadata::from_bytes(&mut reader)?;
-
-            if md.expiration().is_expired()? {
-                self.store.delete(&internal_key)?;
-                Ok(None)
-            } else {
-                let _ = value.split_to(StringValueMetadata::SIZE);
-                Ok(Some((value, md)))
-            }
-        } else {
-            Ok(None)
-        }
+        let Some(mut value) = self.generic_db.get_live(user_key)? else {
+            return Ok(None);
+        };
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let md = StringValueMetadata::from_bytes(&mut reader)?;
+        let _ = value.split_to(reader.consumed());
+        Ok(Some((value, md)))
     }
 }
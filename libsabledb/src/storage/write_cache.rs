@@ -1,4 +1,7 @@
-use crate::{storage::BatchUpdate, storage::PutFlags, SableError, StorageAdapter};
+use crate::{
+    storage::{BatchUpdate, GenericDb, PutFlags, RecordsRead},
+    PrimaryKeyMetadata, SableError, StorageAdapter,
+};
 use bytes::BytesMut;
 use dashmap::DashMap;
 use std::rc::Rc;
@@ -21,13 +24,19 @@ impl CacheEntry {
 /// allows the higher level databases (`HashDb`, `GenericDb`, `StringsDb` etc)
 /// to perform `gut` / `delete` in-memory without interacting with the disk
 ///
-/// `get` operations are directed to the disk
+/// `get` and `contains` consult the pending changes first, so a caller always reads its own
+/// writes (a key deleted in the cache is reported as missing, even while the store still has
+/// it). Keys without pending changes are read from the disk
 ///
 /// The changes accumlated in the cacne can be flushed to disk by
 /// calling to `DbWriteCache::to_write_batch()` followed by `StroageAdapter::apply_batch` call
+///
+/// The primary records read from the disk are remembered, and their state is reported with the
+/// write batch (see `RecordsRead`)
 pub struct DbWriteCache<'a> {
     store: &'a StorageAdapter,
     changes: DashMap<BytesMut, Option<Rc<CacheEntry>>>,
+    records_read: RecordsRead,
 }
 
 impl<'a> DbWriteCache<'a> {
@@ -35,6 +44,7 @@ impl<'a> DbWriteCache<'a> {
         DbWriteCache {
             store,
             changes: DashMap::<BytesMut, Option<Rc<CacheEntry>>>::default(),
+            records_read: RecordsRead::default(),
         }
     }
 
@@ -72,12 +82,14 @@ impl<'a> DbWriteCache<'a> {
         }
     }
 
-    /// Get a key from cache. If the key does not exist in the cache, fetch it from the store
-    /// and keep a copy in the cache. If the key exists in the cache, but with a `None` value
-    /// this means that it was deleted, so return a `None` as well
+    /// Get a key from cache. If the key does not exist in the cache, fetch it from the store.
+    /// If the key exists in the cache, but with a `None` value this means that it was deleted,
+    /// so return a `None` as well
     pub fn get(&self, key: &BytesMut) -> Result<Option<BytesMut>, SableError> {
         let Some(value) = self.changes.get(key) else {
-            return self.store.get(key);
+            let value = self.store.get(key)?;
+            self.records_read.insert(key, value.as_deref());
+            return Ok(value);
         };
 
         // found an match in cache
@@ -90,9 +102,34 @@ impl<'a> DbWriteCache<'a> {
         }
     }
 
+    /// Get the primary record of `user_key` from the cache, like `GenericDb::get_live`: an
+    /// expired key is deleted and reported as missing
+    pub fn get_live(
+        &self,
+        user_key: &BytesMut,
+        db_id: u16,
+    ) -> Result<Option<BytesMut>, SableError> {
+        let key = PrimaryKeyMetadata::new_primary_key(user_key, db_id);
+        let Some(value) = self.get(&key)? else {
+            return Ok(None);
+        };
+
+        if GenericDb::with_storage(self.store, db_id).delete_if_expired(user_key, &value)? {
+            // the record is gone (a replica leaves it to its primary)
+            if !self.store.is_replica_mode() {
+                self.records_read.insert(&key, None);
+            }
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
     pub fn to_write_batch(&self) -> BatchUpdate {
         let mut batch_update = BatchUpdate::default();
         for entry in &self.changes {
+            if let Some(before) = self.records_read.take(entry.key()) {
+                batch_update.set_record_before(entry.key(), before);
+            }
             match entry.value() {
                 None => batch_update.delete(entry.key().clone()),
                 Some(value) => batch_update.put(entry.key().clone(), value.data.clone()),
@@ -102,14 +139,15 @@ impl<'a> DbWriteCache<'a> {
     }
 
     pub fn clear(&self) {
-        self.changes.clear()
+        self.changes.clear();
+        self.records_read.clear();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{storage::PutFlags, StorageOpenParams};
+    use crate::{storage::PutFlags, CompressionType, StorageOpenParams};
     use std::fs;
     use std::path::PathBuf;
 
@@ -118,7 +156,7 @@ mod tests {
         let db_path = PathBuf::from(format!("tests/{}.db", name));
         let _ = fs::remove_dir_all(db_path.clone());
         let open_params = StorageOpenParams::default()
-            .set_compression(false)
+            .set_compression_type(CompressionType::None)
             .set_cache_size(64)
             .set_path(&db_path)
             .set_wal_disabled(true);
@@ -197,6 +235,55 @@ mod tests {
         assert!(!db_cache.contains(&no_such_key).unwrap());
     }
 
+    #[test]
+    fn test_get_reads_cached_put() {
+        let store = open_store("test_get_reads_cached_put").unwrap();
+        let key = BytesMut::from("key");
+        store
+            .put(&key, &BytesMut::from("old_value"), PutFlags::Override)
+            .unwrap();
+
+        let db_cache = DbWriteCache::with_storage(&store);
+        db_cache.put(&key, BytesMut::from("new_value")).unwrap();
+        assert_eq!(
+            db_cache.get(&key).unwrap(),
+            Some(BytesMut::from("new_value"))
+        );
+
+        // a key that exists only in the cache
+        let new_key = BytesMut::from("new_key");
+        db_cache.put(&new_key, BytesMut::from("value")).unwrap();
+        assert_eq!(
+            db_cache.get(&new_key).unwrap(),
+            Some(BytesMut::from("value"))
+        );
+        assert!(db_cache.contains(&new_key).unwrap());
+
+        // nothing was written to the store yet
+        assert_eq!(store.get(&key).unwrap(), Some(BytesMut::from("old_value")));
+        assert!(store.get(&new_key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_reads_cached_delete() {
+        let store = open_store("test_get_reads_cached_delete").unwrap();
+        let key = BytesMut::from("key");
+        let value = BytesMut::from("value");
+        store.put(&key, &value, PutFlags::Override).unwrap();
+
+        let db_cache = DbWriteCache::with_storage(&store);
+        db_cache.delete(&key).unwrap();
+        assert!(db_cache.get(&key).unwrap().is_none());
+        assert!(!db_cache.contains(&key).unwrap());
+
+        // the store still has the old value
+        assert_eq!(store.get(&key).unwrap(), Some(value));
+
+        // re-adding the key after the delete
+        db_cache.put(&key, BytesMut::from("other")).unwrap();
+        assert_eq!(db_cache.get(&key).unwrap(), Some(BytesMut::from("other")));
+    }
+
     #[test]
     fn test_delete_cache() {
         let store = open_store("test_delete_cache").unwrap();
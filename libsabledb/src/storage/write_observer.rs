@@ -0,0 +1,149 @@
+use crate::{CommonValueMetadata, PrimaryKeyMetadata, U8ArrayReader};
+use bytes::BytesMut;
+use dashmap::DashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteEventType {
+    Put,
+    Delete,
+}
+
+/// A user key whose primary record was changed by a committed write
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrittenKey {
+    pub db_id: u16,
+    pub user_key: BytesMut,
+    pub event_type: WriteEventType,
+    /// Whether the primary record existed right before this write: a `Put` of a record that did
+    /// not exist creates the key, a `Delete` of a record that existed removes it
+    pub existed: bool,
+    /// Whether the primary record had a TTL right before this write
+    pub had_ttl: bool,
+    /// Whether the primary record has a TTL after this write (always `false` for a `Delete`)
+    pub has_ttl: bool,
+}
+
+/// The primary record a write is about to replace or delete
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordBefore {
+    pub existed: bool,
+    pub had_ttl: bool,
+}
+
+impl RecordBefore {
+    /// Build the state of a primary record from its value, `None` if it does not exist
+    pub fn with_value(value: Option<&[u8]>) -> Self {
+        RecordBefore {
+            existed: value.is_some(),
+            had_ttl: value.is_some_and(WriteEvent::value_has_ttl),
+        }
+    }
+}
+
+/// The primary records read by a database accessor (`GenericDb`, `DbWriteCache` etc) before it
+/// writes them. The accessor reports the state of a record it read along with its write, so the
+/// storage does not look the record up again for the write observers. The caller holds the lock
+/// of the key between the read and the write, so the state read is still accurate
+#[derive(Default)]
+pub struct RecordsRead {
+    records: DashMap<BytesMut, RecordBefore>,
+}
+
+impl RecordsRead {
+    /// Remember the primary record `key` as read from the storage, `value` is `None` if it does
+    /// not exist. Other records are ignored
+    pub fn insert(&self, key: &[u8], value: Option<&[u8]>) {
+        if WriteEvent::is_primary_key(key) {
+            self.records
+                .insert(BytesMut::from(key), RecordBefore::with_value(value));
+        }
+    }
+
+    /// Return the state of `key`, if it was read. The state is forgotten: once the record is
+    /// written, the caller remembers its new state (or reads it again)
+    pub fn take(&self, key: &[u8]) -> Option<RecordBefore> {
+        self.records.remove(key).map(|(_, before)| before)
+    }
+
+    pub fn clear(&self) {
+        self.records.clear()
+    }
+}
+
+/// A single write (`put`, `delete` or a batch) that was committed to the storage
+#[derive(Clone, Debug, Default)]
+pub struct WriteEvent {
+    /// The user keys touched by this write
+    pub keys: Vec<WrittenKey>,
+    /// The number of records committed by this write. This includes records that do not map to
+    /// a user key (e.g. hash fields or list items)
+    pub records: usize,
+    /// The size (key and value) of the records put by this write
+    pub bytes_written: usize,
+    /// The size of the records this write replaced or deleted. Only known when one of the
+    /// observers tracks the record sizes, see `WriteObserver::tracks_record_sizes`
+    pub bytes_released: usize,
+}
+
+impl WriteEvent {
+    /// Is `key` the primary record of a user key?
+    pub fn is_primary_key(key: &[u8]) -> bool {
+        key.len() >= PrimaryKeyMetadata::SIZE && key[0] == PrimaryKeyMetadata::KEY_PRIMARY
+    }
+
+    /// Does the primary record `value` have a TTL?
+    pub fn value_has_ttl(value: &[u8]) -> bool {
+        let mut reader = U8ArrayReader::with_buffer(value);
+        CommonValueMetadata::from_bytes(&mut reader)
+            .map(|md| md.expiration().has_ttl())
+            .unwrap_or(false)
+    }
+
+    /// Add a committed record to this event. `value` is the record written by a `Put` (`None`
+    /// for a `Delete`). Records that are not primary keys are only counted
+    pub fn add_record(
+        &mut self,
+        key: &[u8],
+        event_type: WriteEventType,
+        before: RecordBefore,
+        value: Option<&[u8]>,
+    ) {
+        self.records = self.records.saturating_add(1);
+        if !Self::is_primary_key(key) {
+            return;
+        }
+
+        let Ok((key_md, user_key)) = PrimaryKeyMetadata::from_raw(&BytesMut::from(key)) else {
+            return;
+        };
+        self.keys.push(WrittenKey {
+            db_id: key_md.db_id(),
+            user_key,
+            event_type,
+            existed: before.existed,
+            had_ttl: before.had_ttl,
+            has_ttl: value.is_some_and(Self::value_has_ttl),
+        });
+    }
+}
+
+/// Implemented by anything that needs to observe every logical write (keyspace notifications,
+/// client tracking, WATCH etc)
+pub trait WriteObserver: Send + Sync {
+    /// Called once per committed write, after it was applied to the storage
+    fn on_write(&self, event: &WriteEvent);
+
+    /// Does this observer need `WriteEvent::bytes_released`? Computing it costs a lookup of
+    /// every record before it is written, so it is only done while an observer asks for it
+    fn tracks_record_sizes(&self) -> bool {
+        false
+    }
+
+    /// Called after the whole content of the storage was replaced (e.g. a replica that loaded a
+    /// checkpoint of its primary). The records that were replaced are not reported
+    fn on_reset(&self) {}
+
+    /// Called after all the records starting with `prefix` were deleted by a single range
+    /// deletion (e.g. `FLUSHDB`). The deleted records are not reported one by one
+    fn on_range_deleted(&self, _prefix: &[u8]) {}
+}
@@ -0,0 +1,564 @@
+#[allow(unused_imports)]
+use crate::{
+    metadata::{ZSetMemberKey, ZSetScoreKey, ZSetValueMetadata},
+    storage::{DbWriteCache, PutFlags},
+    CommonValueMetadata, PrimaryKeyMetadata, SableError, StorageAdapter, U8ArrayBuilder,
+    U8ArrayReader,
+};
+use bytes::BytesMut;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+bitflags::bitflags! {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZAddFlags: u32  {
+    const None = 0;
+    /// Only add new members, do not update existing ones
+    const Nx = 1 << 0;
+    /// Only update existing members, never add new ones
+    const Xx = 1 << 1;
+    /// Only update existing members if the new score is greater than the current one
+    const Gt = 1 << 2;
+    /// Only update existing members if the new score is less than the current one
+    const Lt = 1 << 3;
+    /// Count changed members (new members + members whose score was updated)
+    const Ch = 1 << 4;
+}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GetZSetMetadataResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// A match was found
+    Some(ZSetValueMetadata),
+    /// No entry exist
+    NotFound,
+}
+
+/// `ZSetDb::add` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum ZSetAddResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Number of members added (or changed, when `ZAddFlags::Ch` is set)
+    Some(usize),
+}
+
+/// `ZSetDb::delete` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum ZSetDeleteResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// Number of members deleted
+    Some(usize),
+}
+
+/// `ZSetDb::len` result
+#[derive(PartialEq, Eq, Debug)]
+pub enum ZSetLenResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The sorted set size
+    Some(usize),
+}
+
+/// `ZSetDb::score` result
+#[derive(PartialEq, Debug)]
+pub enum ZSetScoreResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The sorted set or the member does not exist
+    NotFound,
+    /// The member's score
+    Some(f64),
+}
+
+/// `ZSetDb::range_by_rank` result
+#[derive(PartialEq, Debug)]
+pub enum ZSetRangeResult {
+    /// An entry exists in the db for the given key, but for a different type
+    WrongType,
+    /// The members in the range (ordered by score) and their scores
+    Some(Vec<(BytesMut, f64)>),
+}
+
+/// Sorted set DB wrapper. This class is specialized in reading/writing sorted sets
+/// (commands from the `ZADD`, `ZRANGE` etc family)
+///
+/// Locking strategy: this class does not lock anything and relies on the caller
+/// to obtain the locks if needed
+pub struct ZSetDb<'a> {
+    store: &'a StorageAdapter,
+    db_id: u16,
+    cache: Box<DbWriteCache<'a>>,
+}
+
+#[allow(dead_code)]
+impl<'a> ZSetDb<'a> {
+    pub fn with_storage(store: &'a StorageAdapter, db_id: u16) -> Self {
+        let cache = Box::new(DbWriteCache::with_storage(store));
+        ZSetDb {
+            store,
+            db_id,
+            cache,
+        }
+    }
+
+    /// Add the members with their scores to the sorted set stored at `user_key`, or update
+    /// the scores of existing members
+    pub fn add(
+        &self,
+        user_key: &BytesMut,
+        members: &[(f64, &BytesMut)],
+        flags: ZAddFlags,
+    ) -> Result<ZSetAddResult, SableError> {
+        let mut zset = match self.zset_metadata(user_key)? {
+            GetZSetMetadataResult::WrongType => return Ok(ZSetAddResult::WrongType),
+            GetZSetMetadataResult::NotFound if flags.contains(ZAddFlags::Xx) => {
+                // nothing to update
+                return Ok(ZSetAddResult::Some(0));
+            }
+            GetZSetMetadataResult::NotFound => ZSetValueMetadata::with_id(self.store.generate_id()),
+            GetZSetMetadataResult::Some(zset) => zset,
+        };
+
+        let mut items_added = 0usize;
+        let mut items_updated = 0usize;
+        for (score, member) in members {
+            match self.get_member_score(zset.id(), member)? {
+                Some(old_score) => {
+                    if flags.contains(ZAddFlags::Nx)
+                        || (flags.contains(ZAddFlags::Gt) && *score <= old_score)
+                        || (flags.contains(ZAddFlags::Lt) && *score >= old_score)
+                        || *score == old_score
+                    {
+                        continue;
+                    }
+                    self.delete_score_key(zset.id(), old_score, member)?;
+                    self.put_member(zset.id(), *score, member)?;
+                    items_updated = items_updated.saturating_add(1);
+                }
+                None => {
+                    if flags.contains(ZAddFlags::Xx) {
+                        continue;
+                    }
+                    self.put_member(zset.id(), *score, member)?;
+                    items_added = items_added.saturating_add(1);
+                }
+            }
+        }
+
+        // the metadata is rewritten on score updates as well: the primary record is what
+        // marks the key as modified (e.g. for `WATCH`)
+        if items_added > 0 || items_updated > 0 {
+            zset.incr_len_by(items_added as u64);
+            self.put_zset_metadata(user_key, &zset)?;
+        }
+
+        // flush the changes
+        self.flush_cache()?;
+        if flags.contains(ZAddFlags::Ch) {
+            Ok(ZSetAddResult::Some(
+                items_added.saturating_add(items_updated),
+            ))
+        } else {
+            Ok(ZSetAddResult::Some(items_added))
+        }
+    }
+
+    /// Remove the specified members from the sorted set stored at `user_key`
+    pub fn delete(
+        &self,
+        user_key: &BytesMut,
+        members: &[&BytesMut],
+    ) -> Result<ZSetDeleteResult, SableError> {
+        let mut zset = match self.zset_metadata(user_key)? {
+            GetZSetMetadataResult::WrongType => return Ok(ZSetDeleteResult::WrongType),
+            GetZSetMetadataResult::NotFound => return Ok(ZSetDeleteResult::Some(0)),
+            GetZSetMetadataResult::Some(zset) => zset,
+        };
+
+        let mut items_deleted = 0usize;
+        for member in members {
+            let Some(score) = self.get_member_score(zset.id(), member)? else {
+                continue;
+            };
+            self.delete_score_key(zset.id(), score, member)?;
+            self.cache
+                .delete(&self.encode_member_key(zset.id(), member))?;
+            items_deleted = items_deleted.saturating_add(1);
+        }
+
+        if items_deleted > 0 {
+            zset.decr_len_by(items_deleted as u64);
+            if zset.is_empty() {
+                let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+                self.cache.delete(&encoded_key)?;
+            } else {
+                self.put_zset_metadata(user_key, &zset)?;
+            }
+        }
+
+        self.flush_cache()?;
+        Ok(ZSetDeleteResult::Some(items_deleted))
+    }
+
+    /// Return the number of members of the sorted set
+    pub fn len(&self, user_key: &BytesMut) -> Result<ZSetLenResult, SableError> {
+        match self.zset_metadata(user_key)? {
+            GetZSetMetadataResult::WrongType => Ok(ZSetLenResult::WrongType),
+            GetZSetMetadataResult::NotFound => Ok(ZSetLenResult::Some(0)),
+            GetZSetMetadataResult::Some(zset) => Ok(ZSetLenResult::Some(zset.len() as usize)),
+        }
+    }
+
+    /// Return the score of `member`
+    pub fn score(
+        &self,
+        user_key: &BytesMut,
+        member: &BytesMut,
+    ) -> Result<ZSetScoreResult, SableError> {
+        let zset = match self.zset_metadata(user_key)? {
+            GetZSetMetadataResult::WrongType => return Ok(ZSetScoreResult::WrongType),
+            GetZSetMetadataResult::NotFound => return Ok(ZSetScoreResult::NotFound),
+            GetZSetMetadataResult::Some(zset) => zset,
+        };
+
+        match self.get_member_score(zset.id(), member)? {
+            Some(score) => Ok(ZSetScoreResult::Some(score)),
+            None => Ok(ZSetScoreResult::NotFound),
+        }
+    }
+
+    /// Return the members found between the ranks `start` and `stop` (both inclusive), ordered
+    /// by score (members with the same score are ordered by their bytes). Negative ranks are
+    /// counted from the end of the sorted set
+    pub fn range_by_rank(
+        &self,
+        user_key: &BytesMut,
+        start: i64,
+        stop: i64,
+    ) -> Result<ZSetRangeResult, SableError> {
+        let zset = match self.zset_metadata(user_key)? {
+            GetZSetMetadataResult::WrongType => return Ok(ZSetRangeResult::WrongType),
+            GetZSetMetadataResult::NotFound => return Ok(ZSetRangeResult::Some(Vec::new())),
+            GetZSetMetadataResult::Some(zset) => zset,
+        };
+
+        let len = zset.len() as i64;
+        let start = if start < 0 {
+            (start + len).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            stop + len
+        } else {
+            stop.min(len - 1)
+        };
+        if start > stop || start >= len {
+            return Ok(ZSetRangeResult::Some(Vec::new()));
+        }
+
+        let (start, stop) = (start as usize, stop as usize);
+        let items = Rc::new(RefCell::new(Vec::<(BytesMut, f64)>::new()));
+        let items_clone = items.clone();
+        let mut curidx = 0usize;
+        self.store.iterate(
+            Rc::new(zset.scores_prefix()),
+            Box::new(move |_prefix, key, _value| {
+                if curidx > stop {
+                    return false;
+                }
+                if curidx >= start {
+                    if let Ok(score_key) = ZSetScoreKey::from_bytes(key) {
+                        items_clone
+                            .borrow_mut()
+                            .push((BytesMut::from(score_key.member()), score_key.score()));
+                    }
+                }
+                curidx = curidx.saturating_add(1);
+                true
+            }),
+        )?;
+        let items = items.take();
+        Ok(ZSetRangeResult::Some(items))
+    }
+
+    /// Load sorted set value metadata from the store. An expired sorted set is reported as
+    /// `NotFound`
+    pub fn zset_metadata(&self, user_key: &BytesMut) -> Result<GetZSetMetadataResult, SableError> {
+        let Some(value) = self.cache.get_live(user_key, self.db_id)? else {
+            return Ok(GetZSetMetadataResult::NotFound);
+        };
+
+        let mut reader = U8ArrayReader::with_buffer(&value);
+        let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
+        if !common_md.is_zset() {
+            return Ok(GetZSetMetadataResult::WrongType);
+        }
+
+        reader.rewind();
+        let zset_md = ZSetValueMetadata::from_bytes(&mut reader)?;
+        Ok(GetZSetMetadataResult::Some(zset_md))
+    }
+
+    // =======================================================
+    // Internal API for this class
+    // =======================================================
+
+    /// Apply the changes to the store and clear the cache
+    fn flush_cache(&self) -> Result<(), SableError> {
+        let batch = self.cache.to_write_batch();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.cache.clear();
+        self.store.apply_batch(&batch)
+    }
+
+    /// Put a sorted set entry in the database
+    fn put_zset_metadata(
+        &self,
+        user_key: &BytesMut,
+        zset_md: &ZSetValueMetadata,
+    ) -> Result<(), SableError> {
+        let encoded_key = PrimaryKeyMetadata::new_primary_key(user_key, self.db_id);
+
+        // serialise the sorted set value into bytes
+        let mut buffer = BytesMut::with_capacity(ZSetValueMetadata::SIZE);
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        zset_md.to_bytes(&mut builder);
+
+        self.cache.put(&encoded_key, buffer)?;
+        Ok(())
+    }
+
+    /// Put both index records of a member
+    fn put_member(&self, zset_id: u64, score: f64, member: &[u8]) -> Result<(), SableError> {
+        let mut value = BytesMut::with_capacity(std::mem::size_of::<u64>());
+        let mut builder = U8ArrayBuilder::with_buffer(&mut value);
+        ZSetMemberKey::score_to_bytes(score, &mut builder);
+        self.cache
+            .put(&self.encode_member_key(zset_id, member), value)?;
+        self.cache.put(
+            &self.encode_score_key(zset_id, score, member),
+            BytesMut::new(),
+        )?;
+        Ok(())
+    }
+
+    /// Delete the score index record of a member
+    fn delete_score_key(&self, zset_id: u64, score: f64, member: &[u8]) -> Result<(), SableError> {
+        self.cache
+            .delete(&self.encode_score_key(zset_id, score, member))
+    }
+
+    /// Return the score of a member, if it exists
+    fn get_member_score(&self, zset_id: u64, member: &[u8]) -> Result<Option<f64>, SableError> {
+        let Some(value) = self.cache.get(&self.encode_member_key(zset_id, member))? else {
+            return Ok(None);
+        };
+        Ok(Some(ZSetMemberKey::score_from_bytes(&value)?))
+    }
+
+    /// Encode the member -> score index key
+    fn encode_member_key(&self, zset_id: u64, member: &[u8]) -> BytesMut {
+        let mut buffer = BytesMut::with_capacity(
+            2 * std::mem::size_of::<u8>() + std::mem::size_of::<u64>() + member.len(),
+        );
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        ZSetMemberKey::with_member(zset_id, member).to_bytes(&mut builder);
+        buffer
+    }
+
+    /// Encode the score index key
+    fn encode_score_key(&self, zset_id: u64, score: f64, member: &[u8]) -> BytesMut {
+        let mut buffer = BytesMut::with_capacity(
+            2 * std::mem::size_of::<u8>() + 2 * std::mem::size_of::<u64>() + member.len(),
+        );
+        let mut builder = U8ArrayBuilder::with_buffer(&mut buffer);
+        ZSetScoreKey::with_score(zset_id, score, member).to_bytes(&mut builder);
+        buffer
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressionType, StorageOpenParams};
+    use std::path::PathBuf;
+
+    fn create_database(db_name: &str) -> StorageAdapter {
+        let _ = std::fs::create_dir_all("tests");
+        let db_path = PathBuf::from(format!("tests/{}.db", db_name));
+        let _ = std::fs::remove_dir_all(db_path.clone());
+        let open_params = StorageOpenParams::default()
+            .set_compression_type(CompressionType::Snappy)
+            .set_cache_size(64)
+            .set_path(&db_path);
+        crate::storage_rocksdb!(open_params.clone())
+    }
+
+    fn members(range: ZSetRangeResult) -> Vec<(String, f64)> {
+        let ZSetRangeResult::Some(items) = range else {
+            panic!("expected a range");
+        };
+        items
+            .into_iter()
+            .map(|(member, score)| (crate::BytesMutUtils::to_string(&member), score))
+            .collect()
+    }
+
+    #[test]
+    fn test_zset_wrong_type() -> Result<(), SableError> {
+        let db = create_database("test_zset_wrong_type");
+        let zset_db = ZSetDb::with_storage(&db, 0);
+        let strings_db = crate::storage::StringsDb::with_storage(&db, 0);
+
+        let key = BytesMut::from("key");
+        let value = BytesMut::from("value");
+        strings_db.put(
+            &key,
+            &value,
+            &crate::StringValueMetadata::default(),
+            PutFlags::Override,
+        )?;
+
+        assert_eq!(zset_db.len(&key)?, ZSetLenResult::WrongType);
+        assert_eq!(
+            zset_db.add(&key, &[(1.0, &key)], ZAddFlags::None)?,
+            ZSetAddResult::WrongType
+        );
+        assert_eq!(zset_db.delete(&key, &[&key])?, ZSetDeleteResult::WrongType);
+        assert_eq!(zset_db.score(&key, &key)?, ZSetScoreResult::WrongType);
+        assert_eq!(
+            zset_db.range_by_rank(&key, 0, -1)?,
+            ZSetRangeResult::WrongType
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_ordering() -> Result<(), SableError> {
+        let db = create_database("test_zset_ordering");
+        let zset_db = ZSetDb::with_storage(&db, 0);
+        let key = BytesMut::from("myzset");
+
+        let (a, b, c, d, e) = (
+            BytesMut::from("a"),
+            BytesMut::from("b"),
+            BytesMut::from("c"),
+            BytesMut::from("d"),
+            BytesMut::from("e"),
+        );
+        assert_eq!(
+            zset_db.add(
+                &key,
+                &[
+                    (1.0, &c),
+                    (f64::INFINITY, &a),
+                    (-2.5, &d),
+                    (1.0, &b),
+                    (f64::NEG_INFINITY, &e),
+                ],
+                ZAddFlags::None
+            )?,
+            ZSetAddResult::Some(5)
+        );
+
+        // equal scores are ordered by the member bytes
+        assert_eq!(
+            members(zset_db.range_by_rank(&key, 0, -1)?),
+            vec![
+                ("e".to_string(), f64::NEG_INFINITY),
+                ("d".to_string(), -2.5),
+                ("b".to_string(), 1.0),
+                ("c".to_string(), 1.0),
+                ("a".to_string(), f64::INFINITY),
+            ]
+        );
+        assert_eq!(
+            members(zset_db.range_by_rank(&key, -3, 2)?),
+            vec![("b".to_string(), 1.0)]
+        );
+        assert!(members(zset_db.range_by_rank(&key, 3, 1)?).is_empty());
+
+        // moving a member re-orders it and drops its old index record
+        assert_eq!(
+            zset_db.add(&key, &[(-10.0, &a)], ZAddFlags::Ch)?,
+            ZSetAddResult::Some(1)
+        );
+        assert_eq!(
+            members(zset_db.range_by_rank(&key, 0, 2)?),
+            vec![
+                ("e".to_string(), f64::NEG_INFINITY),
+                ("a".to_string(), -10.0),
+                ("d".to_string(), -2.5),
+            ]
+        );
+        assert_eq!(zset_db.len(&key)?, ZSetLenResult::Some(5));
+
+        assert_eq!(
+            zset_db.delete(&key, &[&a, &a, &b])?,
+            ZSetDeleteResult::Some(2)
+        );
+        assert_eq!(zset_db.score(&key, &a)?, ZSetScoreResult::NotFound);
+        assert_eq!(zset_db.score(&key, &c)?, ZSetScoreResult::Some(1.0));
+        assert_eq!(members(zset_db.range_by_rank(&key, 0, -1)?).len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_add_flags() -> Result<(), SableError> {
+        let db = create_database("test_zset_add_flags");
+        let zset_db = ZSetDb::with_storage(&db, 0);
+        let key = BytesMut::from("myzset");
+        let (a, b) = (BytesMut::from("a"), BytesMut::from("b"));
+
+        // XX never creates the sorted set
+        assert_eq!(
+            zset_db.add(&key, &[(1.0, &a)], ZAddFlags::Xx)?,
+            ZSetAddResult::Some(0)
+        );
+        assert_eq!(
+            zset_db.zset_metadata(&key)?,
+            GetZSetMetadataResult::NotFound
+        );
+
+        assert_eq!(
+            zset_db.add(&key, &[(5.0, &a)], ZAddFlags::None)?,
+            ZSetAddResult::Some(1)
+        );
+        // NX does not update existing members
+        assert_eq!(
+            zset_db.add(&key, &[(1.0, &a), (1.0, &b)], ZAddFlags::Nx | ZAddFlags::Ch)?,
+            ZSetAddResult::Some(1)
+        );
+        assert_eq!(zset_db.score(&key, &a)?, ZSetScoreResult::Some(5.0));
+
+        // GT / LT
+        assert_eq!(
+            zset_db.add(&key, &[(4.0, &a)], ZAddFlags::Gt | ZAddFlags::Ch)?,
+            ZSetAddResult::Some(0)
+        );
+        assert_eq!(
+            zset_db.add(&key, &[(6.0, &a)], ZAddFlags::Gt | ZAddFlags::Ch)?,
+            ZSetAddResult::Some(1)
+        );
+        assert_eq!(
+            zset_db.add(&key, &[(7.0, &a)], ZAddFlags::Lt | ZAddFlags::Ch)?,
+            ZSetAddResult::Some(0)
+        );
+        assert_eq!(zset_db.score(&key, &a)?, ZSetScoreResult::Some(6.0));
+        Ok(())
+    }
+}
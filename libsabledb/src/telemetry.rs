@@ -119,6 +119,10 @@ pub struct Telemetry {
     pub db_miss: u128,
     /// Total number of database hits
     pub db_hit: u128,
+    /// Number of expired keys deleted by the active expiration task
+    pub active_expired_keys: u128,
+    /// Number of keys evicted to enforce `maxmemory`
+    pub evicted_keys: u128,
     /// Total number of commands processed
     pub total_commands_processed: u128,
     /// Total number of microseconds spent doing Disk IO
@@ -209,6 +213,22 @@ impl Telemetry {
         });
     }
 
+    /// Increase the number of keys deleted by the active expiration task by `count`
+    pub fn inc_active_expired_keys(count: u128) {
+        WORKER_TELEMETRY.with(|telemetry| {
+            let new_val = telemetry.borrow().active_expired_keys.saturating_add(count);
+            telemetry.borrow_mut().active_expired_keys = new_val;
+        });
+    }
+
+    /// Increase the number of keys evicted to enforce `maxmemory` by `count`
+    pub fn inc_evicted_keys(count: u128) {
+        WORKER_TELEMETRY.with(|telemetry| {
+            let new_val = telemetry.borrow().evicted_keys.saturating_add(count);
+            telemetry.borrow_mut().evicted_keys = new_val;
+        });
+    }
+
     /// Increase the number of network bytes read by `count`
     pub fn inc_net_bytes_read(count: u128) {
         WORKER_TELEMETRY.with(|telemetry| {
@@ -234,6 +254,8 @@ impl Telemetry {
             telemetry.borrow_mut().net_bytes_written = 0;
             telemetry.borrow_mut().db_miss = 0;
             telemetry.borrow_mut().db_hit = 0;
+            telemetry.borrow_mut().active_expired_keys = 0;
+            telemetry.borrow_mut().evicted_keys = 0;
             telemetry.borrow_mut().total_commands_processed = 0;
             telemetry.borrow_mut().total_io_read_calls = 0;
             telemetry.borrow_mut().total_io_write_calls = 0;
@@ -257,6 +279,12 @@ impl Telemetry {
             .saturating_add(worker_telemetry.net_bytes_read);
         self.db_miss = self.db_miss.saturating_add(worker_telemetry.db_miss);
         self.db_hit = self.db_hit.saturating_add(worker_telemetry.db_hit);
+        self.active_expired_keys = self
+            .active_expired_keys
+            .saturating_add(worker_telemetry.active_expired_keys);
+        self.evicted_keys = self
+            .evicted_keys
+            .saturating_add(worker_telemetry.evicted_keys);
         self.total_commands_processed = self
             .total_commands_processed
             .saturating_add(worker_telemetry.total_commands_processed);
@@ -270,10 +298,9 @@ impl Telemetry {
             .total_io_duration
             .saturating_add(worker_telemetry.total_io_duration);
     }
-}
 
-impl std::fmt::Display for Telemetry {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// Return the `INFO` sections built from the telemetry: `(section name, section content)`
+    pub fn info_sections(&self) -> Vec<(&'static str, String)> {
         let mut total_connections = self.connections_opened;
         total_connections = total_connections.saturating_sub(self.connections_closed);
         let mut avg_io_per_command = 0f64;
@@ -281,39 +308,144 @@ impl std::fmt::Display for Telemetry {
             avg_io_per_command =
                 self.total_io_duration as f64 / self.total_commands_processed as f64;
         }
-        let mut lines = Vec::<String>::new();
 
-        lines.push("# Commands".to_string());
-        lines.push(format!(
-            "total_commands_processed: {}",
-            self.total_commands_processed
-        ));
-
-        lines.push("\n# Network".to_string());
-        lines.push(format!("total_connections: {}", total_connections));
-        lines.push(format!("net_bytes_written: {}", self.net_bytes_written));
-        lines.push(format!("net_bytes_read: {}", self.net_bytes_read));
-        lines.push("\n# Disk I/O".to_string());
-        lines.push(format!(
-            "total_io_write_calls: {}",
-            self.total_io_write_calls
-        ));
-        lines.push(format!("total_io_read_calls: {}", self.total_io_read_calls));
-        lines.push(format!("total_io_duration: {}", self.total_io_duration));
-        lines.push(format!("avg_io_per_command_micros: {}", avg_io_per_command));
-
-        lines.push("\n# Statistics".to_string());
-        lines.push(format!("db_miss: {}", self.db_miss));
-        lines.push(format!("db_hit: {}", self.db_hit));
-        lines.push("\n".to_string());
+        let commands = [
+            "# Commands".to_string(),
+            format!(
+                "total_commands_processed: {}",
+                self.total_commands_processed
+            ),
+        ];
+
+        let network = [
+            "# Network".to_string(),
+            format!("total_connections: {}", total_connections),
+            format!("net_bytes_written: {}", self.net_bytes_written),
+            format!("net_bytes_read: {}", self.net_bytes_read),
+        ];
+
+        let disk = [
+            "# Disk I/O".to_string(),
+            format!("total_io_write_calls: {}", self.total_io_write_calls),
+            format!("total_io_read_calls: {}", self.total_io_read_calls),
+            format!("total_io_duration: {}", self.total_io_duration),
+            format!("avg_io_per_command_micros: {}", avg_io_per_command),
+        ];
+
+        let statistics = [
+            "# Statistics".to_string(),
+            format!("db_miss: {}", self.db_miss),
+            format!("db_hit: {}", self.db_hit),
+            format!("active_expired_keys: {}", self.active_expired_keys),
+            format!("evicted_keys: {}", self.evicted_keys),
+        ];
+
+        vec![
+            ("commands", commands.join("\n")),
+            ("network", network.join("\n")),
+            ("disk", disk.join("\n")),
+            ("statistics", statistics.join("\n")),
+        ]
+    }
 
-        let as_str = lines.join("\n");
+    /// Return the telemetry in Prometheus text exposition format
+    pub fn prometheus_metrics(&self) -> String {
+        let connected_clients = self
+            .connections_opened
+            .saturating_sub(self.connections_closed);
+        let metrics: [(&str, &str, &str, u128); 12] = [
+            (
+                "sabledb_connections_opened_total",
+                "counter",
+                "Number of connections opened",
+                self.connections_opened,
+            ),
+            (
+                "sabledb_connections_closed_total",
+                "counter",
+                "Number of connections closed",
+                self.connections_closed,
+            ),
+            (
+                "sabledb_connected_clients",
+                "gauge",
+                "Number of open connections",
+                connected_clients,
+            ),
+            (
+                "sabledb_commands_processed_total",
+                "counter",
+                "Number of commands processed",
+                self.total_commands_processed,
+            ),
+            (
+                "sabledb_net_bytes_read_total",
+                "counter",
+                "Number of bytes read from the network",
+                self.net_bytes_read,
+            ),
+            (
+                "sabledb_net_bytes_written_total",
+                "counter",
+                "Number of bytes written to the network",
+                self.net_bytes_written,
+            ),
+            (
+                "sabledb_db_hits_total",
+                "counter",
+                "Number of lookups that found the key",
+                self.db_hit,
+            ),
+            (
+                "sabledb_db_misses_total",
+                "counter",
+                "Number of lookups that did not find the key",
+                self.db_miss,
+            ),
+            (
+                "sabledb_active_expired_keys_total",
+                "counter",
+                "Number of expired keys deleted by the active expiration task",
+                self.active_expired_keys,
+            ),
+            (
+                "sabledb_evicted_keys_total",
+                "counter",
+                "Number of keys evicted to enforce maxmemory",
+                self.evicted_keys,
+            ),
+            (
+                "sabledb_io_read_calls_total",
+                "counter",
+                "Number of storage read calls",
+                self.total_io_read_calls,
+            ),
+            (
+                "sabledb_io_write_calls_total",
+                "counter",
+                "Number of storage write calls",
+                self.total_io_write_calls,
+            ),
+        ];
+
+        let mut output = String::with_capacity(2048);
+        for (name, metric_type, help, value) in metrics {
+            output.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+                name, help, name, metric_type, name, value
+            ));
+        }
+        output
+    }
+}
 
-        write!(
-            f,
-            "{}\n{}",
-            as_str,
-            REPLICATION_INFO.lock().expect("poisoned mutex")
-        )
+impl std::fmt::Display for Telemetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections: Vec<String> = self
+            .info_sections()
+            .into_iter()
+            .map(|(_, content)| content)
+            .collect();
+        writeln!(f, "{}", sections.join("\n\n"))
     }
 }
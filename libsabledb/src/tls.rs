@@ -6,6 +6,7 @@ use std::io;
 use std::io::BufReader;
 use std::path::Path;
 use std::sync::Arc;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
 
 fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
     certs(&mut BufReader::new(File::open(path)?)).collect()
@@ -19,17 +20,41 @@ pub fn create_tls_acceptor(
     cert: &Path,
     key: &Path,
 ) -> Result<tokio_rustls::TlsAcceptor, SableError> {
+    Ok(tokio_rustls::TlsAcceptor::from(create_server_config(
+        cert, key,
+    )?))
+}
+
+/// Create the TLS configuration used by a server: the clients, or the replicas when used for
+/// replication
+pub fn create_server_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>, SableError> {
     let cert = load_certs(cert)?;
-    let Some(pk) = load_keys(key)? else {
-        tracing::error!("failed to load TLS key");
-        return Err(SableError::NotFound);
+    let Some(pk) = load_keys(key)? else {
+        tracing::error!("failed to load TLS key");
+        return Err(SableError::NotFound);
     };
 
-    let config = tokio_rustls::rustls::ServerConfig::builder()
+    let config = ServerConfig::builder()
         .with_no_client_auth()
         .with_single_cert(cert, pk)
         .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
-    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+    Ok(Arc::new(config))
+}
+
+/// Create the TLS configuration used by a replica to connect to its primary. The primary's
+/// certificate must be signed by one of the certificates found in `ca`
+pub fn create_client_config(ca: &Path) -> Result<Arc<ClientConfig>, SableError> {
+    let mut root_store = RootCertStore::empty();
+    for cert in load_certs(ca)? {
+        root_store
+            .add(cert)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
 }
 
 #[cfg(test)]
@@ -47,4 +72,14 @@ mod tests {
         let key = PathBuf::from("../ssl/sabledb.key");
         assert!(load_keys(&key).is_ok());
     }
+
+    #[test]
+    fn test_replication_configs() {
+        let cert = PathBuf::from("../ssl/sabledb.crt");
+        let key = PathBuf::from("../ssl/sabledb.key");
+        let ca = PathBuf::from("../ssl/ca.crt");
+        assert!(create_server_config(&cert, &key).is_ok());
+        assert!(create_client_config(&ca).is_ok());
+        assert!(create_client_config(&PathBuf::from("../ssl/no_such_file.crt")).is_err());
+    }
 }
@@ -41,6 +41,14 @@ pub enum MultiPopResult {
     None,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListValuesResult {
+    /// The list values, from head to tail
+    Some(Vec<BytesMut>),
+    WrongType,
+    None,
+}
+
 #[derive(PartialEq, Eq)]
 enum IterResult {
     Some(Rc<RefCell<ListItem>>),
@@ -147,7 +155,9 @@ impl<'a> List<'a> {
         }
     }
 
-    /// Insert `element` after or before `pivot` element
+    /// Insert `element` after or before the first occurrence of the `pivot` element.
+    /// The items are linked to their neighbours by ID (and not by position), so inserting in the
+    /// middle of the list only updates the new item and its two neighbours, nothing is reindexed
     pub fn linsert(
         &self,
         list_name: &BytesMut,
@@ -215,17 +225,36 @@ impl<'a> List<'a> {
         }
     }
 
+    /// Pop `count` items from the head (`ListFlags::FromLeft`) or the tail of the list. Without
+    /// `count`, a single item is popped and returned as a bulk string. With `count`, the reply is
+    /// an array (`null` when the list does not exist)
     pub fn pop(
         &self,
         list_name: &BytesMut,
-        count: usize,
+        count: Option<usize>,
         response_buffer: &mut BytesMut,
         flags: ListFlags,
     ) -> Result<(), SableError> {
         let builder = RespBuilderV2::default();
-        let mut list = list_md_or_null_string!(&self, list_name, response_buffer, builder);
-        let mut output_arr = Vec::<ListItem>::with_capacity(count);
-        for _ in 0..count {
+        let mut list = match self.get_list_metadata_with_name(list_name)? {
+            GetListMetadataResult::WrongType => {
+                builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
+                return Ok(());
+            }
+            GetListMetadataResult::None if count.is_some() => {
+                builder.null_array(response_buffer);
+                return Ok(());
+            }
+            GetListMetadataResult::None => {
+                builder.null_string(response_buffer);
+                return Ok(());
+            }
+            GetListMetadataResult::Some(list) => list,
+        };
+
+        let items_to_pop = count.unwrap_or(1).min(list.len() as usize);
+        let mut output_arr = Vec::<ListItem>::with_capacity(items_to_pop);
+        for _ in 0..items_to_pop {
             let item_to_remove = if flags.intersects(ListFlags::FromLeft) {
                 list.head()
             } else {
@@ -245,10 +274,10 @@ impl<'a> List<'a> {
 
         self.flush_cache()?;
 
-        match output_arr.len() {
-            0 => builder.null_string(response_buffer),
-            1 => builder.bulk_string(response_buffer, &output_arr.first().unwrap().user_data),
-            _ => {
+        match (count, output_arr.first()) {
+            (None, None) => builder.null_string(response_buffer),
+            (None, Some(item)) => builder.bulk_string(response_buffer, &item.user_data),
+            (Some(_), _) => {
                 // expected array of bulk strings
                 response_buffer.clear();
                 builder.add_array_len(response_buffer, output_arr.len());
@@ -309,6 +338,11 @@ impl<'a> List<'a> {
         };
         item.borrow_mut().user_data = user_value;
         item.borrow().save(&self.cache)?;
+        // rewrite the (unchanged) list metadata: the primary record is what marks the key as
+        // modified (e.g. for `WATCH`)
+        if let GetListMetadataResult::Some(list_md) = self.get_list_metadata_with_name(list_name)? {
+            self.put_list_metadata_internal(&list_md, list_name)?;
+        }
         self.flush_cache()?;
         builder.ok(response_buffer);
         Ok(())
@@ -397,15 +431,11 @@ impl<'a> List<'a> {
                 builder.error_string(response_buffer, ErrorStrings::LIST_RANK_INVALID);
             }
             (Some(_), PosResult::Some(value)) => {
-                // count provided, we return array
+                // count provided, we return array (possibly empty)
                 response_buffer.clear();
-                if value.is_empty() {
-                    builder.null_string(response_buffer);
-                } else {
-                    builder.add_array_len(response_buffer, value.len());
-                    for val in value {
-                        builder.add_number::<usize>(response_buffer, val, false);
-                    }
+                builder.add_array_len(response_buffer, value.len());
+                for val in value {
+                    builder.add_number::<usize>(response_buffer, val, false);
                 }
             }
             (None, PosResult::Some(value)) => {
@@ -419,7 +449,10 @@ impl<'a> List<'a> {
             (_, PosResult::WrongType) => {
                 builder.error_string(response_buffer, ErrorStrings::WRONGTYPE);
             }
-            (_, PosResult::None) => {
+            (Some(_), PosResult::None) => {
+                builder.empty_array(response_buffer);
+            }
+            (None, PosResult::None) => {
                 builder.null_string(response_buffer);
             }
         }
@@ -491,6 +524,47 @@ impl<'a> List<'a> {
         Ok(())
     }
 
+    /// Return all the values of the list, from head to tail
+    pub fn values(&self, list_name: &BytesMut) -> Result<ListValuesResult, SableError> {
+        let list = match self.get_list_metadata_with_name(list_name)? {
+            GetListMetadataResult::WrongType => return Ok(ListValuesResult::WrongType),
+            GetListMetadataResult::None => return Ok(ListValuesResult::None),
+            GetListMetadataResult::Some(list) => list,
+        };
+
+        let mut values = Vec::<BytesMut>::with_capacity(list.len() as usize);
+        let mut cur_item_opt: Option<Rc<RefCell<ListItem>>> = None;
+        loop {
+            let cur_item = match self.next(&list, cur_item_opt)? {
+                IterResult::WrongType => return Ok(ListValuesResult::WrongType),
+                IterResult::None => break,
+                IterResult::Some(list_item) => list_item,
+            };
+            values.push(cur_item.borrow().user_data.clone());
+            cur_item_opt = Some(cur_item);
+        }
+        Ok(ListValuesResult::Some(values))
+    }
+
+    /// Replace the value stored at `list_name` (of any type) with a new list holding `values`.
+    /// If `values` is empty, `list_name` is deleted. Return the length of the new list
+    pub fn store(&self, list_name: &BytesMut, values: &[BytesMut]) -> Result<usize, SableError> {
+        if values.is_empty() {
+            self.delete_list_metadata_internal(list_name)?;
+        } else {
+            let mut list = self.new_list_internal();
+            for value in values {
+                self.push_internal(&mut list, value, &ListFlags::FromRight)?;
+            }
+            self.put_list_metadata_internal(&list, list_name)?;
+        }
+        self.flush_cache()?;
+        Ok(values.len())
+    }
+
+    /// Keep only the items in the range `start..=end` (negative indices count from the tail, out
+    /// of range bounds are clamped). The other items are deleted in a single batch, an empty
+    /// result deletes the list
     pub fn ltrim(
         &self,
         list_name: &BytesMut,
@@ -514,6 +588,12 @@ impl<'a> List<'a> {
         // translate indices
         let (start, end) = self.convert_trim_indices(start, end, list.len() as i32);
 
+        // the range covers the entire list: nothing to delete
+        if start == 0 && u64::from(end) >= list.len().saturating_sub(1) {
+            builder.ok(response_buffer);
+            return Ok(());
+        }
+
         #[derive(PartialEq, Eq, Debug)]
         enum State {
             Delete = 0,
@@ -714,6 +794,23 @@ impl<'a> List<'a> {
             GetListMetadataResult::Some(list) => list,
         };
 
+        // same source and destination: rotate the list. Both operations must be applied to the
+        // same metadata, otherwise the second write overrides the first one
+        if src_list_name == target_list_name {
+            let Some(v) = self.pop_internal(&mut src_list_md, 1, &src_flags)? else {
+                return Ok(MoveResult::None);
+            };
+
+            let Some(popped_item) = v.first() else {
+                return Ok(MoveResult::None);
+            };
+
+            self.push_internal(&mut src_list_md, &popped_item.user_data, &target_flags)?;
+            self.put_list_metadata_internal(&src_list_md, src_list_name)?;
+            self.flush_cache()?;
+            return Ok(MoveResult::Some(popped_item.clone()));
+        }
+
         let mut target_list_md = match self.get_list_metadata_with_name(target_list_name)? {
             GetListMetadataResult::WrongType => return Ok(MoveResult::WrongType),
             GetListMetadataResult::None => self.new_list_internal(),
@@ -1124,8 +1221,7 @@ impl<'a> List<'a> {
         &self,
         list_name: &BytesMut,
     ) -> Result<GetListMetadataResult, SableError> {
-        let internal_key = PrimaryKeyMetadata::new_primary_key(list_name, self.db_id);
-        if let Some(mut value) = self.cache.get(&internal_key)? {
+        if let Some(value) = self.cache.get_live(list_name, self.db_id)? {
             let mut reader = U8ArrayReader::with_buffer(&value);
             let common_md = CommonValueMetadata::from_bytes(&mut reader)?;
 
@@ -1135,13 +1231,7 @@ impl<'a> List<'a> {
 
             let mut reader = U8ArrayReader::with_buffer(&value);
             let md = ListValueMetadata::from_bytes(&mut reader)?;
-            if md.expiration().is_expired()? {
-                self.cache.delete(&internal_key)?;
-                Ok(GetListMetadataResult::None)
-            } else {
-                let _ = value.split_to(ListValueMetadata::SIZE);
-                Ok(GetListMetadataResult::Some(md))
-            }
+            Ok(GetListMetadataResult::Some(md))
         } else {
             Ok(GetListMetadataResult::None)
         }
@@ -1458,7 +1548,7 @@ impl ListItem {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::StorageOpenParams;
+    use crate::{CompressionType, StorageOpenParams};
     use std::fs;
     use std::path::PathBuf;
     use test_case::test_case;
@@ -1470,7 +1560,7 @@ mod tests {
             let db_path = PathBuf::from($dbpath);
             let _ = fs::remove_dir_all(db_path.clone());
             let open_params = StorageOpenParams::default()
-                .set_compression(false)
+                .set_compression_type(CompressionType::None)
                 .set_cache_size(64)
                 .set_path(&db_path)
                 .set_wal_disabled(true);
@@ -1545,24 +1635,24 @@ mod tests {
                 ":3\r\n"
             );
 
-            list.pop(&list_name, 1, &mut response_buffer, ListFlags::FromLeft)?;
+            list.pop(&list_name, None, &mut response_buffer, ListFlags::FromLeft)?;
             assert_eq!(
                 BytesMutUtils::to_string(&response_buffer).as_str(),
                 "$3\r\nfoo\r\n"
             );
-            list.pop(&list_name, 1, &mut response_buffer, ListFlags::FromLeft)?;
+            list.pop(&list_name, None, &mut response_buffer, ListFlags::FromLeft)?;
             assert_eq!(
                 BytesMutUtils::to_string(&response_buffer).as_str(),
                 "$5\r\nworld\r\n"
             );
 
-            list.pop(&list_name, 1, &mut response_buffer, ListFlags::FromLeft)?;
+            list.pop(&list_name, None, &mut response_buffer, ListFlags::FromLeft)?;
             assert_eq!(
                 BytesMutUtils::to_string(&response_buffer).as_str(),
                 "$5\r\nhello\r\n"
             );
 
-            list.pop(&list_name, 1, &mut response_buffer, ListFlags::FromLeft)?;
+            list.pop(&list_name, None, &mut response_buffer, ListFlags::FromLeft)?;
             assert_eq!(
                 BytesMutUtils::to_string(&response_buffer).as_str(),
                 "$-1\r\n"
@@ -17,6 +17,7 @@ enum InlineState {
 const UNCLOSED_DOUBLE_QUOTES: &str = "unclosed double quotes";
 const UNCLOSED_SINGLE_QUOTES: &str = "unclosed single quotes";
 const TRAILING_ESCAPE_CHAR: &str = "trailing escape character";
+const QUOTE_NOT_FOLLOWED_BY_SPACE: &str = "closing quote must be followed by a space";
 
 impl StringUtils {
     /// Find `what` in `buffer`
@@ -24,16 +25,52 @@ impl StringUtils {
         buffer.windows(what.len()).position(|window| window == what)
     }
 
-    /// Split `buffer` by whitespace
+    /// Split `buffer` by whitespace. Quoted strings (`"..."` or `'...'`) are kept as a single
+    /// word, even when empty. Inside double quotes, `\xHH` is the byte with the hex value `HH`
     pub fn split(buffer: &mut BytesMut) -> Result<Vec<BytesMut>, ParserError> {
         let mut word = BytesMut::with_capacity(1024);
         let mut words = Vec::<BytesMut>::new();
         let mut state = InlineState::Normal;
         let mut prev_state = InlineState::Normal;
+        let mut quote_closed = false;
+
+        let mut pos = 0usize;
+        while let Some(ch) = buffer.get(pos) {
+            pos = pos.saturating_add(1);
+            if quote_closed {
+                if *ch != b' ' && *ch != b'\t' {
+                    return Err(ParserError::InvalidInput(
+                        QUOTE_NOT_FOLLOWED_BY_SPACE.to_string(),
+                    ));
+                }
+                quote_closed = false;
+            }
 
-        for ch in buffer.iter() {
             match state {
                 InlineState::Escape => match ch {
+                    b'x' if prev_state == InlineState::DoubleQuotes => {
+                        let hex = buffer
+                            .get(pos..pos.saturating_add(2))
+                            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                            .and_then(|hex| std::str::from_utf8(hex).ok())
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                        match hex {
+                            Some(byte) => {
+                                word.extend([byte]);
+                                pos = pos.saturating_add(2);
+                            }
+                            None => word.extend([b'x']),
+                        }
+                        state = prev_state;
+                    }
+                    b'a' => {
+                        word.extend([0x07]);
+                        state = prev_state;
+                    }
+                    b'b' => {
+                        word.extend([0x08]);
+                        state = prev_state;
+                    }
                     b'n' => {
                         word.extend([b'\n']);
                         state = prev_state;
@@ -82,11 +119,10 @@ impl StringUtils {
                 },
                 InlineState::DoubleQuotes => match ch {
                     b'"' => {
-                        if !word.is_empty() {
-                            words.push(word.clone());
-                            word.clear();
-                        }
+                        words.push(word.clone());
+                        word.clear();
                         state = InlineState::Normal;
+                        quote_closed = true;
                     }
                     b'\\' => {
                         prev_state = InlineState::DoubleQuotes;
@@ -98,11 +134,10 @@ impl StringUtils {
                 },
                 InlineState::SingleQuotes => match ch {
                     b'\'' => {
-                        if !word.is_empty() {
-                            words.push(word.clone());
-                            word.clear();
-                        }
+                        words.push(word.clone());
+                        word.clear();
                         state = InlineState::Normal;
+                        quote_closed = true;
                     }
                     b'\\' => {
                         prev_state = InlineState::SingleQuotes;
@@ -137,6 +172,115 @@ impl StringUtils {
         }
     }
 
+    /// Glob-style pattern matching, following the rules of Redis `stringmatchlen`:
+    /// `*` matches any sequence, `?` matches a single character, `[...]` matches a set
+    /// of characters (`^` negates the set, `a-z` describes a range) and `\\` escapes
+    /// the next character
+    pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+        let eq = |a: u8, b: u8| {
+            if nocase {
+                a.eq_ignore_ascii_case(&b)
+            } else {
+                a == b
+            }
+        };
+
+        let mut p = 0usize;
+        let mut s = 0usize;
+        while p < pattern.len() && s < string.len() {
+            match pattern[p] {
+                b'*' => {
+                    // collapse consecutive stars
+                    while p + 1 < pattern.len() && pattern[p + 1] == b'*' {
+                        p += 1;
+                    }
+                    if p + 1 == pattern.len() {
+                        return true;
+                    }
+                    for start in s..string.len() {
+                        if Self::glob_match(&pattern[p + 1..], &string[start..], nocase) {
+                            return true;
+                        }
+                    }
+                    return false;
+                }
+                b'?' => {
+                    s += 1;
+                }
+                b'[' => {
+                    p += 1;
+                    let negate = p < pattern.len() && pattern[p] == b'^';
+                    if negate {
+                        p += 1;
+                    }
+                    let mut matched = false;
+                    while p < pattern.len() && pattern[p] != b']' {
+                        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                            p += 1;
+                            matched |= eq(pattern[p], string[s]);
+                        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' {
+                            let (mut start, mut end, mut c) =
+                                (pattern[p], pattern[p + 2], string[s]);
+                            if nocase {
+                                start = start.to_ascii_lowercase();
+                                end = end.to_ascii_lowercase();
+                                c = c.to_ascii_lowercase();
+                            }
+                            if start > end {
+                                std::mem::swap(&mut start, &mut end);
+                            }
+                            matched |= (start..=end).contains(&c);
+                            p += 2;
+                        } else {
+                            matched |= eq(pattern[p], string[s]);
+                        }
+                        p += 1;
+                    }
+                    if pattern.get(p).is_none() {
+                        // unterminated set: the pattern is treated as if it was closed here
+                        p = pattern.len().saturating_sub(1);
+                    }
+                    if matched == negate {
+                        return false;
+                    }
+                    s += 1;
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    p += 1;
+                    if !eq(pattern[p], string[s]) {
+                        return false;
+                    }
+                    s += 1;
+                }
+                ch => {
+                    if !eq(ch, string[s]) {
+                        return false;
+                    }
+                    s += 1;
+                }
+            }
+            p += 1;
+        }
+
+        // the string is consumed, only trailing stars may remain in the pattern
+        s == string.len() && pattern[p.min(pattern.len())..].iter().all(|c| *c == b'*')
+    }
+
+    /// Format `value` the way Redis formats the result of `INCRBYFLOAT` and `HINCRBYFLOAT`:
+    /// a plain decimal number, without an exponent and without trailing zeros.
+    /// The value is rounded to 15 significant digits (the precision a double can hold) so the
+    /// binary rounding noise is not printed, e.g. `10.5 + 0.1` is `10.6`
+    pub fn format_redis_float(value: f64) -> String {
+        let rounded = format!("{:.14e}", value).parse::<f64>().unwrap_or(value);
+        // `Display` prints the shortest representation and never uses an exponent
+        if rounded == 0.0 {
+            // no "-0"
+            "0".to_string()
+        } else {
+            format!("{}", rounded)
+        }
+    }
+
     /// Convert `s` into `usize`
     pub fn parse_str_to_number<F: FromStr>(s: &str) -> Result<F, SableError> {
         let Ok(num) = FromStr::from_str(s) else {
@@ -184,6 +328,31 @@ impl BytesMutUtils {
         Some(num)
     }
 
+    /// Parse `value` as a 64 bit signed integer, accepting only the canonical form Redis uses
+    /// for strings that hold a number: an optional `-`, no leading zeros and no whitespace.
+    /// Strings that pass this check are the ones reported with the `int` encoding
+    pub fn parse_integer(value: &[u8]) -> Option<i64> {
+        let digits = value.strip_prefix(b"-").unwrap_or(value);
+        let canonical = match digits {
+            [] => false,
+            [b'0'] => digits.len() == value.len(), // "-0" is not canonical
+            [first, ..] => *first != b'0' && digits.iter().all(u8::is_ascii_digit),
+        };
+        if !canonical {
+            return None;
+        }
+        std::str::from_utf8(value).ok()?.parse::<i64>().ok()
+    }
+
+    /// Parse `value` as a float. Like Redis, `NaN` and values with spaces are rejected
+    pub fn parse_float(value: &[u8]) -> Option<f64> {
+        let value = std::str::from_utf8(value).ok()?;
+        if value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace) {
+            return None;
+        }
+        value.parse::<f64>().ok().filter(|value| !value.is_nan())
+    }
+
     pub fn to_string(value: &BytesMut) -> String {
         String::from_utf8_lossy(value).to_string()
     }
@@ -248,6 +417,26 @@ impl BytesMutUtils {
         u16::from_be_bytes(arr)
     }
 
+    /// Encode `bytes` as a lowercase hex string
+    pub fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Decode a hex string produced by `to_hex`. Return `None` if `hex` is not a valid
+    /// hex string
+    pub fn from_hex(hex: &[u8]) -> Option<BytesMut> {
+        let pairs = hex.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return None;
+        }
+        let mut bytes = BytesMut::with_capacity(hex.len() / 2);
+        for pair in pairs {
+            let pair = std::str::from_utf8(pair).ok()?;
+            bytes.extend_from_slice(&[u8::from_str_radix(pair, 16).ok()?]);
+        }
+        Some(bytes)
+    }
+
     /// Given two sequences, return the longest subsequence present in both of them
     /// and the indices in each sequence
     pub fn lcs(seq1: &BytesMut, seq2: &BytesMut) -> (BytesMut, Vec<(usize, usize)>) {
@@ -440,6 +629,10 @@ mod test {
     #[test_case(r#"set key "value \"with space""#, 3,"value \"with space" ; "escape double quote")]
     #[test_case(r#"set key 'value \'with space'"#, 3,"value 'with space" ; "escape single quote")]
     #[test_case(r#"set key "value with space" extra"#, 4, "extra" ; "param after")]
+    #[test_case(r#"set key """#, 3, "" ; "empty double quotes")]
+    #[test_case(r#"set key ''"#, 3, "" ; "empty single quotes")]
+    #[test_case(r#"set key "\x41\x6a\x6B""#, 3, "Ajk" ; "hex escape")]
+    #[test_case(r#"set key "\xZZ""#, 3, "xZZ" ; "invalid hex escape")]
     fn test_split_with_spaces(input_string: &str, count: usize, third_string: &str) {
         let mut input = BytesMut::from(input_string);
         let res = StringUtils::split(&mut input);
@@ -454,6 +647,7 @@ mod test {
     #[test_case(r#"set key value '"#, UNCLOSED_SINGLE_QUOTES ; "broken single quote")]
     #[test_case(r#"set key value ""#, UNCLOSED_DOUBLE_QUOTES ; "broken double quote")]
     #[test_case(r#"set key "value \"#, TRAILING_ESCAPE_CHAR; "broken escape")]
+    #[test_case(r#"set key "value"extra"#, QUOTE_NOT_FOLLOWED_BY_SPACE; "text after closing quote")]
     fn test_split_with_broke_string(input_string: &str, message: &str) {
         let mut input = BytesMut::from(input_string);
         let res = StringUtils::split(&mut input);
@@ -488,6 +682,77 @@ mod test {
         Ok(())
     }
 
+    #[test_case("*", "anything", false, true ; "star matches all")]
+    #[test_case("user:*", "user:1", false, true ; "prefix star")]
+    #[test_case("user:*", "item:1", false, false ; "prefix star no match")]
+    #[test_case("h?llo", "hello", false, true ; "question mark")]
+    #[test_case("h?llo", "hllo", false, false ; "question mark requires a char")]
+    #[test_case("h[ae]llo", "hallo", false, true ; "set")]
+    #[test_case("h[^e]llo", "hello", false, false ; "negated set")]
+    #[test_case("h[a-b]llo", "hbllo", false, true ; "range")]
+    #[test_case("h\\*llo", "h*llo", false, true ; "escaped star")]
+    #[test_case("h\\*llo", "hello", false, false ; "escaped star no match")]
+    #[test_case("HELLO", "hello", true, true ; "nocase")]
+    #[test_case("a*b*c", "aXXbYYc", false, true ; "multiple stars")]
+    #[test_case("a*b*c", "aXXbYY", false, false ; "multiple stars no match")]
+    #[test_case("abc**", "abc", false, true ; "trailing stars")]
+    fn test_glob_match(pattern: &str, string: &str, nocase: bool, expected: bool) {
+        assert_eq!(
+            StringUtils::glob_match(pattern.as_bytes(), string.as_bytes(), nocase),
+            expected
+        );
+    }
+
+    #[test_case("0", Some(0) ; "zero")]
+    #[test_case("1234", Some(1234) ; "positive")]
+    #[test_case("-42", Some(-42) ; "negative")]
+    #[test_case("-9223372036854775808", Some(i64::MIN) ; "min")]
+    #[test_case("9223372036854775808", None ; "overflow")]
+    #[test_case("007", None ; "leading zeros")]
+    #[test_case("-0", None ; "negative zero")]
+    #[test_case("+5", None ; "plus sign")]
+    #[test_case(" 5", None ; "whitespace")]
+    #[test_case("", None ; "empty")]
+    #[test_case("-", None ; "sign only")]
+    #[test_case("12a", None ; "not a number")]
+    fn test_parse_integer(value: &str, expected: Option<i64>) {
+        assert_eq!(BytesMutUtils::parse_integer(value.as_bytes()), expected);
+    }
+
+    #[test_case(10.6, "10.6" ; "simple")]
+    #[test_case(10.5 + 0.1, "10.6" ; "rounding noise")]
+    #[test_case(0.1 + 0.2, "0.3" ; "more rounding noise")]
+    #[test_case(3200.0, "3200" ; "no trailing zeros")]
+    #[test_case(-1.5, "-1.5" ; "negative")]
+    #[test_case(-0.0, "0" ; "negative zero")]
+    #[test_case(1e20, "100000000000000000000" ; "large number")]
+    #[test_case(0.0000001, "0.0000001" ; "small number")]
+    #[test_case(5.0e3 + 2.0e-4, "5000.0002" ; "fraction")]
+    fn test_format_redis_float(value: f64, expected: &str) {
+        assert_eq!(StringUtils::format_redis_float(value), expected);
+    }
+
+    #[test_case("3.0e3", Some(3000.0) ; "exponent")]
+    #[test_case("-.5", Some(-0.5) ; "no leading digit")]
+    #[test_case("inf", Some(f64::INFINITY) ; "infinity")]
+    #[test_case("nan", None ; "nan")]
+    #[test_case(" 1", None ; "leading space")]
+    #[test_case("1 ", None ; "trailing space")]
+    #[test_case("abc", None ; "not a number")]
+    fn test_parse_float(value: &str, expected: Option<f64>) {
+        assert_eq!(BytesMutUtils::parse_float(value.as_bytes()), expected);
+    }
+
+    #[test]
+    fn test_hex() {
+        let bytes = BytesMut::from(&[0u8, 1, 0xab, 0xff][..]);
+        let hex = BytesMutUtils::to_hex(&bytes);
+        assert_eq!(hex, "0001abff");
+        assert_eq!(BytesMutUtils::from_hex(hex.as_bytes()), Some(bytes));
+        assert_eq!(BytesMutUtils::from_hex(b"abc"), None);
+        assert_eq!(BytesMutUtils::from_hex(b"zz"), None);
+    }
+
     #[test]
     fn test_conversion() -> Result<(), SableError> {
         {
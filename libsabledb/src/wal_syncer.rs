@@ -0,0 +1,44 @@
+use crate::{AppendFsync, SableError, StorageAdapter};
+
+/// How often (in milliseconds) the WAL is synced under the `everysec` policy
+const SYNC_INTERVAL_MS: u64 = 1000;
+
+/// Syncs the write-ahead-log to the disk once per second (`appendfsync everysec`).
+///
+/// With `always` every write is synced by RocksDB itself and with `no` syncing is left to the
+/// OS, so the task only runs for the `everysec` policy
+pub struct WalSyncer {
+    store: StorageAdapter,
+}
+
+impl WalSyncer {
+    pub fn new(store: StorageAdapter) -> Self {
+        WalSyncer { store }
+    }
+
+    /// Start the sync task on its own thread, so a slow disk never blocks the workers
+    pub fn run(store: StorageAdapter) -> Result<(), SableError> {
+        let rocksdb_params = &store.open_params().rocksdb;
+        if rocksdb_params.appendfsync != AppendFsync::EverySec || rocksdb_params.disable_wal {
+            return Ok(());
+        }
+
+        let _ = std::thread::Builder::new()
+            .name("WalSyncer".to_string())
+            .spawn(move || {
+                let wal_syncer = WalSyncer::new(store);
+                wal_syncer.main_loop();
+            })?;
+        Ok(())
+    }
+
+    fn main_loop(&self) {
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(SYNC_INTERVAL_MS));
+            if let Err(e) = self.store.sync_wal() {
+                // log this error every 5 minutes
+                crate::error_with_throttling!(300, "Failed to sync WAL. {:?}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::storage::{WriteEvent, WriteObserver};
+use crate::PrimaryKeyMetadata;
+use bytes::BytesMut;
+use dashmap::DashMap;
+
+/// A watched key: `(database ID, user key)`
+type WatchedKey = (u16, BytesMut);
+
+#[derive(Default)]
+struct WatchedKeyEntry {
+    /// Bumped every time the key's primary record is written
+    version: u64,
+    /// Number of clients watching this key
+    watchers: usize,
+}
+
+/// The change-detection table used by `WATCH`.
+///
+/// Instead of a global sequence number snapshot (which would abort a transaction on a write to
+/// *any* key) we keep an in-memory version counter per watched key. The table is registered as a
+/// `WriteObserver` on the storage and bumps the version of a watched key whenever its primary
+/// record is put or deleted. Since the entry is created by `WATCH` and does not depend on the key
+/// existing in the storage, creating a key that did not exist at `WATCH` time invalidates the
+/// watch as well.
+///
+/// Only keys that are currently watched are tracked, an entry is removed once its last watcher
+/// is gone
+#[derive(Default)]
+pub struct WatchedKeys {
+    keys: DashMap<WatchedKey, WatchedKeyEntry>,
+}
+
+impl WatchedKeys {
+    /// Start watching `user_key` and return its current version
+    pub fn watch(&self, db_id: u16, user_key: &BytesMut) -> u64 {
+        let mut entry = self.keys.entry((db_id, user_key.clone())).or_default();
+        entry.watchers = entry.watchers.saturating_add(1);
+        entry.version
+    }
+
+    /// Stop watching `user_key`
+    pub fn unwatch(&self, db_id: u16, user_key: &BytesMut) {
+        self.keys
+            .remove_if_mut(&(db_id, user_key.clone()), |_, entry| {
+                entry.watchers = entry.watchers.saturating_sub(1);
+                entry.watchers == 0
+            });
+    }
+
+    /// Return the current version of a watched key
+    pub fn version(&self, db_id: u16, user_key: &BytesMut) -> Option<u64> {
+        self.keys
+            .get(&(db_id, user_key.clone()))
+            .map(|entry| entry.version)
+    }
+}
+
+impl WriteObserver for WatchedKeys {
+    fn on_write(&self, event: &WriteEvent) {
+        if self.keys.is_empty() {
+            return;
+        }
+        for key in &event.keys {
+            if let Some(mut entry) = self.keys.get_mut(&(key.db_id, key.user_key.clone())) {
+                entry.version = entry.version.wrapping_add(1);
+            }
+        }
+    }
+
+    fn on_range_deleted(&self, prefix: &[u8]) {
+        for mut entry in self.keys.iter_mut() {
+            let (db_id, user_key) = entry.key();
+            if PrimaryKeyMetadata::new_primary_key(user_key, *db_id).starts_with(prefix) {
+                entry.version = entry.version.wrapping_add(1);
+            }
+        }
+    }
+}
+
+//  _    _ _   _ _____ _______      _______ ______  _____ _______ _____ _   _  _____
+// | |  | | \ | |_   _|__   __|    |__   __|  ____|/ ____|__   __|_   _| \ | |/ ____|
+// | |  | |  \| | | |    | |    _     | |  | |__  | (___    | |    | | |  \| | |  __|
+// | |  | | . ` | | |    | |   / \    | |  |  __|  \___ \   | |    | | | . ` | | |_ |
+// | |__| | |\  |_| |_   | |   \_/    | |  | |____ ____) |  | |   _| |_| |\  | |__| |
+//  \____/|_| \_|_____|  |_|          |_|  |______|_____/   |_|  |_____|_| \_|\_____|
+//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{WriteEventType, WrittenKey};
+
+    fn write_event(db_id: u16, user_key: &str) -> WriteEvent {
+        WriteEvent {
+            keys: vec![WrittenKey {
+                db_id,
+                user_key: BytesMut::from(user_key),
+                event_type: WriteEventType::Put,
+                existed: true,
+                had_ttl: false,
+                has_ttl: false,
+            }],
+            records: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_watched_keys() {
+        let table = WatchedKeys::default();
+        let key = BytesMut::from("key");
+
+        // writes to keys that are not watched are ignored
+        table.on_write(&write_event(0, "key"));
+        assert_eq!(table.version(0, &key), None);
+
+        let v1 = table.watch(0, &key);
+        let v2 = table.watch(0, &key);
+        assert_eq!(v1, v2);
+
+        // a different database or key does not affect the version
+        table.on_write(&write_event(1, "key"));
+        table.on_write(&write_event(0, "other_key"));
+        assert_eq!(table.version(0, &key), Some(v1));
+
+        table.on_write(&write_event(0, "key"));
+        assert_ne!(table.version(0, &key), Some(v1));
+
+        // deleting the database of the key, but not other databases, changes the version
+        let v2 = table.version(0, &key);
+        table.on_range_deleted(&PrimaryKeyMetadata::primary_key_prefix(1));
+        assert_eq!(table.version(0, &key), v2);
+        table.on_range_deleted(&PrimaryKeyMetadata::primary_key_prefix(0));
+        assert_ne!(table.version(0, &key), v2);
+
+        // the entry is removed with its last watcher
+        table.unwatch(0, &key);
+        assert!(table.version(0, &key).is_some());
+        table.unwatch(0, &key);
+        assert_eq!(table.version(0, &key), None);
+    }
+}